@@ -0,0 +1,268 @@
+/// Example: Rendering Strategy Benchmark
+///
+/// This example renders a synthetic 5,000-point activity at 1080p with
+/// each rendering strategy and prints wall time, frames per second and
+/// peak memory. Every strategy is built from public config fields and the
+/// public frame APIs, so the numbers reflect what library users get:
+///
+/// - baseline: default config, owned frames from the `FrameComposer`
+///   iterator (one copy per frame)
+/// - no_clone: default config, borrowed frames from `next_mat`
+/// - pipelined: owned frames composed on a worker thread while the main
+///   thread encodes
+/// - draft: `next_mat` with `output_size` at 960x540, `memory_profile: Low`
+///   and dot `render_style`
+///
+/// Each strategy runs in its own process so peak RSS (VmHWM from
+/// /proc/self/status) is per strategy; it is reported as "-" where /proc
+/// is unavailable. Pick strategies by name, or run them all:
+///
+/// cargo run --release --example bench_render -- no_clone draft
+///
+/// No input files are needed; outputs go to a temp directory.
+use std::{
+  env, fs, process::Command, sync::mpsc::sync_channel, thread, time::Instant,
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use opencv::core::{self, Mat, CV_8UC3};
+use runarium::{
+  configs::video_config::{MemoryProfile, RouteRenderStyle, RouteVideoConfig},
+  generators::frame_composer::FrameComposer,
+  utils::{
+    creator::image_creator, frame_sink::FrameSink, testing::synthetic_activity,
+    workspace::Workspace,
+  },
+};
+
+const POINTS: usize = 5000;
+const STRATEGIES: [&str; 4] = ["baseline", "no_clone", "pipelined", "draft"];
+/// Frames composed ahead of the encoder in the pipelined strategy
+const PIPELINE_DEPTH: usize = 4;
+
+fn main() -> Result<()> {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if let [flag, strategy] = args.as_slice() {
+    if flag == "--child" {
+      return child(strategy);
+    }
+  }
+
+  let selected: Vec<&str> = if args.is_empty() {
+    STRATEGIES.to_vec()
+  } else {
+    args.iter().map(String::as_str).collect()
+  };
+  for strategy in &selected {
+    ensure!(
+      STRATEGIES.contains(strategy),
+      "Unknown strategy {}; expected one of {:?}",
+      strategy,
+      STRATEGIES
+    );
+  }
+
+  println!(
+    "{:<10} {:>10} {:>8} {:>10} {:>12}",
+    "strategy", "size", "frames", "wall (s)", "peak RSS"
+  );
+  let exe = env::current_exe()?;
+  for strategy in selected {
+    let output = Command::new(&exe).args(["--child", strategy]).output()?;
+    ensure!(
+      output.status.success(),
+      "{} failed: {}",
+      strategy,
+      String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(result) = stdout.lines().find_map(|l| l.strip_prefix("RESULT "))
+    else {
+      bail!("{} printed no result", strategy);
+    };
+    // The peak RSS is last, as it holds a space
+    let fields: Vec<&str> = result.splitn(4, ' ').collect();
+    let [size, frames, seconds, rss] = fields[..] else {
+      bail!(
+        "Malformed result from {}: {}",
+        strategy,
+        result
+      );
+    };
+    let frames: usize = frames.parse()?;
+    let seconds: f64 = seconds.parse()?;
+    println!(
+      "{:<10} {:>10} {:>8} {:>10.2} {:>12}   {:.1} fps",
+      strategy,
+      size,
+      frames,
+      seconds,
+      rss,
+      frames as f64 / seconds.max(1e-9)
+    );
+  }
+  Ok(())
+}
+
+/// Renders one strategy and prints `RESULT <size> <frames> <secs> <rss>`
+fn child(strategy: &str) -> Result<()> {
+  let workspace = Workspace::new("runarium_bench")?;
+  let background = workspace.path_for("background.png");
+  let background = background.to_string_lossy().into_owned();
+  image_creator(
+    &background,
+    &Mat::new_rows_cols_with_default(
+      1080,
+      1920,
+      CV_8UC3,
+      core::Scalar::all(40.0),
+    )?,
+  )?;
+
+  let mut config = RouteVideoConfig::default();
+  config.file_config.background_image = background;
+  config.file_config.output_file = workspace
+    .path_for("bench.mp4")
+    .to_string_lossy()
+    .into_owned();
+  config.output_size = Some((1920, 1080));
+  if strategy == "draft" {
+    config.output_size = Some((960, 540));
+    config.memory_profile = MemoryProfile::Low;
+    config.render_style = RouteRenderStyle::Dots {
+      radius: 3,
+      spacing_m: 25.0,
+    };
+  }
+
+  let start = Instant::now();
+  let (width, height, frames) = match strategy {
+    "baseline" => owned_frames(config)?,
+    "pipelined" => pipelined(config)?,
+    _ => borrowed_frames(config)?,
+  };
+  let seconds = start.elapsed().as_secs_f64();
+
+  println!(
+    "RESULT {}x{} {} {:.3} {}",
+    width,
+    height,
+    frames,
+    seconds,
+    peak_rss().unwrap_or_else(|| "-".to_string())
+  );
+  Ok(())
+}
+
+/// Encodes a copy of every frame from the composer's iterator
+fn owned_frames(config: RouteVideoConfig) -> Result<(i32, i32, usize)> {
+  let composer = FrameComposer::new(synthetic_activity(POINTS), config)?;
+  let (width, height) = (composer.width(), composer.height());
+  let mut sink = FrameSink::open(
+    width,
+    height,
+    composer.fps(),
+    composer.config(),
+  )?;
+
+  let mut frames = 0;
+  for frame in composer {
+    sink.write(&frame?.data)?;
+    frames += 1;
+  }
+  sink.finish()?;
+  Ok((width, height, frames))
+}
+
+/// Encodes the composer's own buffer without copying it
+fn borrowed_frames(config: RouteVideoConfig) -> Result<(i32, i32, usize)> {
+  let mut composer = FrameComposer::new(synthetic_activity(POINTS), config)?;
+  let (width, height) = (composer.width(), composer.height());
+  let mut sink = FrameSink::open(
+    width,
+    height,
+    composer.fps(),
+    composer.config(),
+  )?;
+
+  let mut frames = 0;
+  while let Some(frame) = composer.next_mat() {
+    sink.write(frame?)?;
+    frames += 1;
+  }
+  sink.finish()?;
+  Ok((width, height, frames))
+}
+
+/// What the composer thread hands to the encoder
+enum Message {
+  /// Width, height and fps, sent before the first frame
+  Size(i32, i32, f64),
+  Frame(Mat),
+}
+
+/// Composes on a worker thread, a few frames ahead of the encoder
+fn pipelined(config: RouteVideoConfig) -> Result<(i32, i32, usize)> {
+  let (sender, receiver) = sync_channel(PIPELINE_DEPTH);
+  let sink_config = config.clone();
+  let worker = thread::spawn(move || -> Result<()> {
+    let composer = FrameComposer::new(synthetic_activity(POINTS), config)?;
+    let size = Message::Size(
+      composer.width(),
+      composer.height(),
+      composer.fps(),
+    );
+    // A failed send means the encoder stopped on an error of its own
+    if sender.send(size).is_err() {
+      return Ok(());
+    }
+    for frame in composer {
+      if sender.send(Message::Frame(frame?.data)).is_err() {
+        break;
+      }
+    }
+    Ok(())
+  });
+
+  let encoded = encode_messages(receiver.iter(), &sink_config);
+  // Dropping the receiver unblocks the worker if encoding failed
+  drop(receiver);
+  worker
+    .join()
+    .map_err(|_| anyhow!("Composer thread panicked"))??;
+  encoded
+}
+
+/// Opens the sink on the size message and encodes the frames after it
+fn encode_messages(
+  mut messages: impl Iterator<Item = Message>,
+  config: &RouteVideoConfig,
+) -> Result<(i32, i32, usize)> {
+  let Some(Message::Size(width, height, fps)) = messages.next() else {
+    bail!("Composer thread sent no frame size");
+  };
+  let mut sink = FrameSink::open(width, height, fps, config)?;
+  let mut frames = 0;
+  for message in messages {
+    if let Message::Frame(frame) = message {
+      sink.write(&frame)?;
+      frames += 1;
+    }
+  }
+  sink.finish()?;
+  Ok((width, height, frames))
+}
+
+/// Peak resident set size of this process, from /proc on Linux
+fn peak_rss() -> Option<String> {
+  let status = fs::read_to_string("/proc/self/status").ok()?;
+  let kb: f64 = status
+    .lines()
+    .find_map(|line| line.strip_prefix("VmHWM:"))?
+    .trim()
+    .trim_end_matches("kB")
+    .trim()
+    .parse()
+    .ok()?;
+  Some(format!("{:.1} MB", kb / 1024.0))
+}
@@ -0,0 +1,75 @@
+/// Example: Raw Frames for an External Encoder
+///
+/// This example composes the route video frame by frame without OpenCV's
+/// video writer and saves each frame as raw BGR bytes, e.g. to pipe into
+/// ffmpeg:
+///
+/// ffmpeg -f rawvideo -pix_fmt bgr24 -s WIDTHxHEIGHT -r FPS \
+///   -i <(cat outputs/frames/*.bgr) outputs/video.mp4
+///
+/// Required files:
+/// - source/example.fit (your GPS data)
+/// - source/example.jpg (background map image)
+///
+/// Output: outputs/frames/frame_00000.bgr, ...
+use std::fs;
+
+use anyhow::{ensure, Result};
+use opencv::prelude::*;
+use runarium::{
+  configs::video_config::RouteVideoConfig,
+  generators::frame_composer::FrameComposer, utils::read_file::activity_reader,
+};
+
+fn main() -> Result<()> {
+  let config = RouteVideoConfig::default();
+  let read = || {
+    activity_reader(
+      &config.file_config.fit_file,
+      &config.captured_fields(),
+    )
+  };
+
+  let composer = FrameComposer::new(read()?, config.clone())?;
+  println!(
+    "Composing {}x{} frames at {} fps",
+    composer.width(),
+    composer.height(),
+    composer.fps()
+  );
+
+  // The composer borrows its buffer in `next_mat`, which is what the
+  // video generator encodes; owned frames must match it pixel for pixel
+  let mut reference = FrameComposer::new(read()?, config.clone())?;
+
+  fs::create_dir_all("outputs/frames")?;
+  let mut count = 0;
+  for frame in composer {
+    let frame = frame?;
+    let bytes = frame.to_bytes()?;
+
+    let expected = reference
+      .next_mat()
+      .expect("reference composer ended early")?;
+    ensure!(
+      bytes == expected.data_bytes()?,
+      "frame {} differs from the video path",
+      frame.index
+    );
+
+    fs::write(
+      format!(
+        "outputs/frames/frame_{:05}.bgr",
+        frame.index
+      ),
+      bytes,
+    )?;
+    count += 1;
+  }
+
+  println!(
+    "✅ Wrote {} raw frames to outputs/frames",
+    count
+  );
+  Ok(())
+}
@@ -0,0 +1,90 @@
+/* runarium C API. Build the library with `--features ffi`. */
+
+#ifndef RUNARIUM_H
+#define RUNARIUM_H
+
+/* Generated with cbindgen, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success
+ */
+#define RUNARIUM_OK 0
+
+/**
+ * A pointer argument was null or not valid UTF-8
+ */
+#define RUNARIUM_ERR_INVALID_ARGUMENT 1
+
+/**
+ * The config JSON does not match the config schema
+ */
+#define RUNARIUM_ERR_CONFIG 2
+
+/**
+ * Reading the FIT file or rendering the output failed
+ */
+#define RUNARIUM_ERR_GENERATION 3
+
+/**
+ * The library panicked; the message describes the panic
+ */
+#define RUNARIUM_ERR_PANIC 4
+
+/**
+ * The input or requested output is larger than the configured `Limits`
+ */
+#define RUNARIUM_ERR_LIMIT_EXCEEDED 5
+
+/**
+ * Renders a route video as configured by `config_json`.
+ *
+ * Returns `RUNARIUM_OK`, or an error code with a message written to
+ * `out_err` (if not null).
+ *
+ * # Safety
+ * `config_json` must be a valid NUL-terminated string and `out_err` null or
+ * a valid pointer.
+ */
+int runarium_generate_video(const char *config_json, char **out_err);
+
+/**
+ * Renders a static route image as configured by `config_json`.
+ *
+ * Returns `RUNARIUM_OK`, or an error code with a message written to
+ * `out_err` (if not null).
+ *
+ * # Safety
+ * `config_json` must be a valid NUL-terminated string and `out_err` null or
+ * a valid pointer.
+ */
+int runarium_generate_image(const char *config_json, char **out_err);
+
+/**
+ * Summarizes a FIT file as a JSON object (sport, session totals, moving
+ * and elapsed time, record/lap counts and parse warnings).
+ *
+ * Returns the JSON, to be released with `runarium_string_free`, or null
+ * with a message written to `out_err` (if not null).
+ *
+ * # Safety
+ * `fit_path` must be a valid NUL-terminated string and `out_err` null or a
+ * valid pointer.
+ */
+char *runarium_inspect(const char *fit_path, char **out_err);
+
+/**
+ * Releases a string returned by the library. Null is ignored.
+ *
+ * # Safety
+ * `value` must be null or a string returned by the library that has not
+ * been freed yet.
+ */
+void runarium_string_free(char *value);
+
+#endif  /* RUNARIUM_H */
@@ -0,0 +1,65 @@
+/*
+ * Smoke test for the runarium C API. Run from the repository root:
+ *
+ *   make ffi-test
+ */
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "runarium.h"
+
+static void test_inspect(void) {
+  char *err = NULL;
+  char *json = runarium_inspect("source/example.fit", &err);
+  if (json == NULL) {
+    fprintf(stderr, "inspect failed: %s\n", err);
+    runarium_string_free(err);
+    assert(0);
+  }
+  assert(strstr(json, "\"record_count\"") != NULL);
+  printf("inspect: %s\n", json);
+  runarium_string_free(json);
+}
+
+static void test_generate_image(void) {
+  const char *config =
+    "{\"file_config\": {\"output_file\": \"outputs/ffi.png\"},"
+    " \"line_thickness\": 3}";
+  char *err = NULL;
+  int code = runarium_generate_image(config, &err);
+  if (code != RUNARIUM_OK) {
+    fprintf(stderr, "generate_image failed (%d): %s\n", code, err);
+    runarium_string_free(err);
+    assert(0);
+  }
+  assert(err == NULL);
+  printf("generate_image: outputs/ffi.png\n");
+}
+
+static void test_invalid_config(void) {
+  char *err = NULL;
+  int code = runarium_generate_video("{\"scale\": 0.3}", &err);
+  assert(code == RUNARIUM_ERR_CONFIG);
+  assert(err != NULL && strstr(err, "scale") != NULL);
+  printf("invalid config: %s\n", err);
+  runarium_string_free(err);
+}
+
+static void test_missing_fit_file(void) {
+  char *err = NULL;
+  char *json = runarium_inspect("source/missing.fit", &err);
+  assert(json == NULL);
+  assert(err != NULL);
+  printf("missing file: %s\n", err);
+  runarium_string_free(err);
+}
+
+int main(void) {
+  test_inspect();
+  test_generate_image();
+  test_invalid_config();
+  test_missing_fit_file();
+  printf("All FFI tests passed\n");
+  return 0;
+}
@@ -0,0 +1,264 @@
+//! Python bindings for the runarium generators.
+//!
+//! Configs are passed as a JSON string or a dict and applied as a
+//! `RouteVideoConfigPatch` / `RouteImageConfigPatch` on top of the defaults,
+//! so they use the same keys as the CLI `--set` flags and the HTTP server.
+
+use std::io;
+
+use pyo3::{
+  create_exception,
+  exceptions::PyException,
+  prelude::*,
+  types::{PyDict, PyString},
+};
+use runarium::{
+  configs::{
+    FileConfig, RouteImageConfig, RouteImageConfigPatch, RouteVideoConfig,
+    RouteVideoConfigPatch,
+  },
+  error,
+  generators::{
+    route_image::image_route_with_config,
+    route_video::progressive_route_with_progress,
+  },
+  types::output::RenderOutput,
+  utils::read_file::activity_reader,
+};
+use serde::de::DeserializeOwned;
+
+create_exception!(
+  runarium,
+  RunariumError,
+  PyException,
+  "Base class for runarium errors"
+);
+create_exception!(
+  runarium,
+  ConfigError,
+  RunariumError,
+  "The config does not match the config schema"
+);
+create_exception!(
+  runarium,
+  InputError,
+  RunariumError,
+  "An input file could not be opened"
+);
+create_exception!(
+  runarium,
+  FitError,
+  RunariumError,
+  "The FIT file could not be parsed"
+);
+create_exception!(
+  runarium,
+  RenderError,
+  RunariumError,
+  "Drawing or encoding the output failed"
+);
+create_exception!(
+  runarium,
+  LimitExceededError,
+  RunariumError,
+  "The input or requested output is larger than the limits allow"
+);
+
+/// Maps an error to its Python exception; limit and I/O failures have
+/// their own types, everything else is reported as `fallback`
+fn to_py_err(err: anyhow::Error, fallback: fn(String) -> PyErr) -> PyErr {
+  let message = format!("{:#}", err);
+  // The Rust error type shares its name with the Python base exception
+  if let Some(error::RunariumError::LimitExceeded { .. }) = err.downcast_ref() {
+    LimitExceededError::new_err(message)
+  } else if err.chain().any(|cause| cause.is::<io::Error>()) {
+    InputError::new_err(message)
+  } else {
+    fallback(message)
+  }
+}
+
+/// Parses a JSON string or dict into a config patch
+fn parse_patch<T: DeserializeOwned + Default>(
+  py: Python<'_>,
+  config: Option<&Bound<'_, PyAny>>,
+) -> PyResult<T> {
+  let Some(config) = config.filter(|config| !config.is_none()) else {
+    return Ok(T::default());
+  };
+
+  let json = match config.downcast::<PyString>() {
+    Ok(text) => text.to_string(),
+    Err(_) => py
+      .import("json")?
+      .call_method1("dumps", (config,))?
+      .extract()?,
+  };
+  serde_json::from_str(&json)
+    .map_err(|e| ConfigError::new_err(format!("Invalid config: {}", e)))
+}
+
+/// Converts the render result to
+/// `{"output_file": ..., "projection": {...}, "warnings": [...]}`, plus
+/// `"time_index": [...]` when the config asks for one
+fn output_dict(py: Python<'_>, output: RenderOutput) -> PyResult<Py<PyDict>> {
+  let json = serde_json::to_string(&output)
+    .map_err(|e| RunariumError::new_err(e.to_string()))?;
+
+  let dict = py.import("json")?.call_method1("loads", (json,))?;
+  Ok(dict.downcast_into::<PyDict>()?.unbind())
+}
+
+/// Renders the route video of `fit_path` over `bg_path` to `out_path`.
+///
+/// `progress(done, total)` is called after each planned frame is encoded.
+/// An exception raised by the callback is re-raised once rendering ends.
+#[pyfunction]
+#[pyo3(signature = (fit_path, bg_path, out_path, config=None, progress=None))]
+fn generate_video(
+  py: Python<'_>,
+  fit_path: String,
+  bg_path: String,
+  out_path: String,
+  config: Option<&Bound<'_, PyAny>>,
+  progress: Option<PyObject>,
+) -> PyResult<Py<PyDict>> {
+  let patch: RouteVideoConfigPatch = parse_patch(py, config)?;
+  let mut config = RouteVideoConfig::default();
+  patch.apply(&mut config);
+  config.file_config = FileConfig::new(fit_path, bg_path, out_path);
+
+  let mut callback_err = None;
+  let result = py.allow_threads(|| {
+    progressive_route_with_progress(config, |done, total| {
+      let Some(progress) = &progress else {
+        return;
+      };
+      if callback_err.is_some() {
+        return;
+      }
+      Python::with_gil(|py| {
+        if let Err(err) = progress.call1(py, (done, total)) {
+          callback_err = Some(err);
+        }
+      });
+    })
+  });
+
+  if let Some(err) = callback_err {
+    return Err(err);
+  }
+  let output = result.map_err(|e| to_py_err(e, RenderError::new_err))?;
+  output_dict(py, output)
+}
+
+/// Renders the static route image of `fit_path` over `bg_path` to
+/// `out_path`
+#[pyfunction]
+#[pyo3(signature = (fit_path, bg_path, out_path, config=None))]
+fn generate_image(
+  py: Python<'_>,
+  fit_path: String,
+  bg_path: String,
+  out_path: String,
+  config: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Py<PyDict>> {
+  let patch: RouteImageConfigPatch = parse_patch(py, config)?;
+  let mut config = RouteImageConfig::default(
+    fit_path.clone(),
+    bg_path.clone(),
+    out_path.clone(),
+  );
+  patch.apply(&mut config);
+  config.file_config = FileConfig::new(fit_path, bg_path, out_path);
+
+  let output = py
+    .allow_threads(|| image_route_with_config(config))
+    .map_err(|e| to_py_err(e, RenderError::new_err))?;
+  output_dict(py, output)
+}
+
+/// Summarizes a FIT file: sport, session totals, moving and elapsed time,
+/// record/lap counts and parse warnings
+#[pyfunction]
+fn inspect(py: Python<'_>, fit_path: String) -> PyResult<Py<PyDict>> {
+  let activity = py
+    .allow_threads(|| activity_reader(&fit_path, &[]))
+    .map_err(|e| to_py_err(e, FitError::new_err))?;
+  let session = activity.session;
+  let times = session.times;
+
+  let dict = PyDict::new(py);
+  dict.set_item("sport", session.sport)?;
+  dict.set_item("total_distance", session.total_distance)?;
+  dict.set_item(
+    "total_timer_time",
+    session.total_timer_time,
+  )?;
+  dict.set_item("total_ascent", session.total_ascent)?;
+  dict.set_item("start_time", session.start_time)?;
+  dict.set_item(
+    "moving_time",
+    times.map(|times| times.moving_time),
+  )?;
+  dict.set_item(
+    "elapsed_time",
+    times.map(|times| times.elapsed_time),
+  )?;
+  dict.set_item(
+    "average_moving_pace",
+    times.and_then(|times| times.average_moving_pace),
+  )?;
+  dict.set_item(
+    "record_count",
+    activity.route.gps_points.len(),
+  )?;
+  dict.set_item(
+    "lap_count",
+    activity.lap.total_distance.len(),
+  )?;
+  let warnings = serde_json::to_string(&activity.warnings)
+    .map_err(|e| RunariumError::new_err(e.to_string()))?;
+  dict.set_item(
+    "warnings",
+    py.import("json")?.call_method1("loads", (warnings,))?,
+  )?;
+  Ok(dict.unbind())
+}
+
+#[pymodule]
+#[pyo3(name = "runarium")]
+fn runarium_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+  let py = module.py();
+  module.add(
+    "RunariumError",
+    py.get_type::<RunariumError>(),
+  )?;
+  module.add(
+    "ConfigError",
+    py.get_type::<ConfigError>(),
+  )?;
+  module.add(
+    "InputError",
+    py.get_type::<InputError>(),
+  )?;
+  module.add("FitError", py.get_type::<FitError>())?;
+  module.add(
+    "RenderError",
+    py.get_type::<RenderError>(),
+  )?;
+  module.add(
+    "LimitExceededError",
+    py.get_type::<LimitExceededError>(),
+  )?;
+  module.add_function(wrap_pyfunction!(
+    generate_video,
+    module
+  )?)?;
+  module.add_function(wrap_pyfunction!(
+    generate_image,
+    module
+  )?)?;
+  module.add_function(wrap_pyfunction!(inspect, module)?)?;
+  Ok(())
+}
@@ -0,0 +1,59 @@
+"""Smoke tests for the runarium Python module.
+
+Run from the repository root after `maturin develop -m python/Cargo.toml`:
+
+    pytest python/tests
+"""
+
+import os
+
+import pytest
+
+import runarium
+
+FIT = "source/example.fit"
+BACKGROUND = "source/example.jpg"
+
+
+def test_exceptions_share_a_base():
+    for error in (
+        runarium.ConfigError,
+        runarium.InputError,
+        runarium.FitError,
+        runarium.RenderError,
+    ):
+        assert issubclass(error, runarium.RunariumError)
+
+
+def test_unknown_config_key_raises_config_error():
+    with pytest.raises(runarium.ConfigError):
+        runarium.generate_video(FIT, BACKGROUND, "outputs/x.mp4", {"scale": 0.3})
+
+
+def test_missing_fit_raises_input_error():
+    with pytest.raises(runarium.InputError):
+        runarium.inspect("source/missing.fit")
+
+
+@pytest.mark.skipif(not os.path.exists(FIT), reason="example FIT file not found")
+def test_inspect():
+    info = runarium.inspect(FIT)
+    assert info["record_count"] > 0
+    assert "sport" in info
+
+
+@pytest.mark.skipif(not os.path.exists(FIT), reason="example FIT file not found")
+def test_generate_video_reports_progress(tmp_path):
+    calls = []
+    output = runarium.generate_video(
+        FIT,
+        BACKGROUND,
+        str(tmp_path / "video.mp4"),
+        '{"duration_secs": 1.0}',
+        progress=lambda done, total: calls.append((done, total)),
+    )
+
+    assert output["output_file"].endswith("video.mp4")
+    assert set(output["projection"]) >= {"geo_bounds", "pixel_rect"}
+    assert all("kind" in warning for warning in output["warnings"])
+    assert calls and calls[-1][0] == calls[-1][1]
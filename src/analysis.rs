@@ -0,0 +1,1557 @@
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{
+  configs::{DistanceRepair, LapSource, SessionSelection, SpikeFilter},
+  types::{
+    fit_data::{Activity, LapData, RouteData},
+    warning::{Warning, Warnings},
+  },
+  utils::{
+    converter::{simplify_indices, speed_to_pace},
+    progression::{activity_times, lap_end_distances},
+  },
+};
+
+/// Split length used when the FIT file has no laps
+pub const AUTO_SPLIT_M: f64 = 1000.0;
+/// Mean Earth radius (meters) for distances between GPS points
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Share of a loop's second half lying on its first half above which
+/// the loop is an out-and-back
+const RETRACE_LIMIT: f64 = 0.5;
+/// Stretch (meters) of altitudes each grade is fitted over
+const GRADE_SMOOTHING_M: f64 = 50.0;
+
+/// Where one lap starts and ends, in records and in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LapBoundary {
+  pub lap_index: usize,
+  /// First record of the lap
+  pub start_record: usize,
+  /// Record after the last one of the lap (`start_record` for a lap
+  /// without records)
+  pub end_record: usize,
+  /// Cumulative distance the lap starts at
+  pub start_distance_m: f64,
+  /// Cumulative distance the lap ends at
+  pub end_distance_m: f64,
+}
+
+impl LapBoundary {
+  /// Records of the lap
+  pub fn records(&self) -> Range<usize> {
+    self.start_record..self.end_record
+  }
+
+  /// Records of the lap, plus the last record of the lap before so a
+  /// drawn lap joins the previous one
+  pub fn joined_records(&self) -> Range<usize> {
+    self.start_record.saturating_sub(1)..self.end_record
+  }
+}
+
+/// Splits the records of an activity into laps.
+///
+/// Lap ends come from the parsed lap distances, or every `AUTO_SPLIT_M`
+/// meters plus a final partial lap when the file has no laps (or only
+/// zero-distance ones). A record belongs to the first lap whose end
+/// distance is at or past its own, so a record exactly on a boundary
+/// closes its lap. Records past the last lap end, and records without a
+/// distance, belong to the last lap.
+///
+/// Every record is in exactly one lap and the laps are in record order.
+/// Without any distance data the whole route is one lap; without GPS
+/// points there are no laps.
+///
+/// # Example
+/// ```no_run
+/// use runarium::analysis::lap_boundaries;
+/// use runarium::utils::read_file::fit_reader;
+///
+/// let (route, lap) = fit_reader("source/example.fit").unwrap();
+/// for boundary in lap_boundaries(&route, &lap) {
+///   println!(
+///     "lap {} covers records {:?}",
+///     boundary.lap_index + 1,
+///     boundary.records()
+///   );
+/// }
+/// ```
+pub fn lap_boundaries(route: &RouteData, lap: &LapData) -> Vec<LapBoundary> {
+  let count = route.gps_points.len();
+  if count == 0 {
+    return Vec::new();
+  }
+
+  let distances = &route.distances[..route.distances.len().min(count)];
+  let Some(&last_distance) = distances.last() else {
+    return vec![LapBoundary {
+      lap_index: 0,
+      start_record: 0,
+      end_record: count,
+      start_distance_m: 0.0,
+      end_distance_m: 0.0,
+    }];
+  };
+
+  let lap_ends = if lap.total_distance.iter().sum::<f64>() > 0.0 {
+    lap_end_distances(&lap.total_distance)
+  } else {
+    split_ends(last_distance, AUTO_SPLIT_M)
+  };
+
+  let mut boundaries = Vec::with_capacity(lap_ends.len());
+  let (mut start_record, mut start_distance_m) = (0, 0.0);
+  for (lap_index, &end_distance_m) in lap_ends.iter().enumerate() {
+    let end_record = if lap_index + 1 == lap_ends.len() {
+      count
+    } else {
+      distances
+        .partition_point(|&d| d <= end_distance_m)
+        .max(start_record)
+    };
+    boundaries.push(LapBoundary {
+      lap_index,
+      start_record,
+      end_record,
+      start_distance_m,
+      end_distance_m,
+    });
+    (start_record, start_distance_m) = (end_record, end_distance_m);
+  }
+  boundaries
+}
+
+/// Makes a cumulative distance series non-decreasing: each drop (a
+/// device reboot resetting the distance, or a GPS correction) is offset
+/// away from every later value, so the distance after it carries on from
+/// the value before. Repeated values are kept, as a standstill; values
+/// that are not finite are skipped. Returns the number of drops and of
+/// values shifted.
+pub fn repair_distances(distances: &mut [f64]) -> (usize, usize) {
+  let (mut resets, mut shifted) = (0, 0);
+  let mut offset = 0.0;
+  let mut previous: Option<f64> = None;
+  for distance in distances.iter_mut().filter(|d| d.is_finite()) {
+    let value = *distance + offset;
+    if let Some(previous) = previous.filter(|&previous| value < previous) {
+      offset += previous - value;
+      resets += 1;
+    }
+    if offset > 0.0 {
+      *distance += offset;
+      shifted += 1;
+    }
+    previous = Some(*distance);
+  }
+  (resets, shifted)
+}
+
+/// Repairs the record distances of `route` as `mode` asks, before laps
+/// and splits are worked out from them. The warning counts what was
+/// repaired.
+pub fn repair_route_distances(
+  mode: DistanceRepair,
+  route: &mut RouteData,
+) -> Warnings {
+  let mut warnings = Warnings::default();
+  if mode == DistanceRepair::Monotonicize {
+    let (resets, points) = repair_distances(&mut route.distances);
+    warnings.push_if(
+      resets > 0,
+      Warning::DistanceRepaired { resets, points },
+    );
+  }
+  warnings
+}
+
+/// Replaces GPS spikes in `points`: a position reached from the last
+/// kept one faster than `max_speed_mps` takes the place between the kept
+/// positions around it, by time. The first timed position is trusted, and
+/// positions without a timestamp are kept. Returns the number replaced.
+pub fn reject_spikes(
+  points: &mut [(f64, f64)],
+  timestamps: &[Option<i64>],
+  max_speed_mps: f64,
+) -> usize {
+  let time = |i: usize| timestamps.get(i).copied().flatten();
+  let mut spikes = vec![false; points.len()];
+  let mut kept: Option<usize> = None;
+  for i in 0..points.len() {
+    let Some(now) = time(i) else {
+      continue;
+    };
+    if let Some(last) = kept {
+      let seconds = (now - time(last).unwrap_or(now)).max(0) as f64;
+      let meters = ground_m(points[last], points[i]);
+      if meters > max_speed_mps * seconds {
+        spikes[i] = true;
+        continue;
+      }
+    }
+    kept = Some(i);
+  }
+
+  // Each run of spikes is spread between the kept positions around it;
+  // a run at the end stays at the last kept position
+  let mut i = 0;
+  while i < points.len() {
+    if !spikes[i] {
+      i += 1;
+      continue;
+    }
+    let before = i - 1;
+    let mut after = i;
+    while after < points.len() && spikes[after] {
+      after += 1;
+    }
+    let start = points[before];
+    let end = points.get(after).copied().unwrap_or(start);
+    let span = match (time(before), time(after)) {
+      (Some(from), Some(to)) if to > from => Some((from, to)),
+      _ => None,
+    };
+    let run = points.iter_mut().enumerate().take(after).skip(i);
+    for (j, point) in run {
+      let fraction = match (span, time(j)) {
+        (Some((from, to)), Some(at)) => {
+          ((at - from) as f64 / (to - from) as f64).clamp(0.0, 1.0)
+        }
+        _ => (j - before) as f64 / (after - before) as f64,
+      };
+      *point = (
+        start.0 + (end.0 - start.0) * fraction,
+        start.1 + (end.1 - start.1) * fraction,
+      );
+    }
+    i = after;
+  }
+  spikes.iter().filter(|&&spike| spike).count()
+}
+
+/// Replaces the GPS spikes of `route` when `filter` is set, before the
+/// route is projected. The warning counts what was replaced.
+pub fn reject_route_spikes(
+  filter: Option<SpikeFilter>,
+  route: &mut RouteData,
+) -> Warnings {
+  let mut warnings = Warnings::default();
+  if let Some(filter) = filter {
+    let count = reject_spikes(
+      &mut route.gps_points,
+      &route.timestamps,
+      filter.max_speed_mps,
+    );
+    warnings.push_if(
+      count > 0,
+      Warning::SpikesReplaced {
+        count,
+        max_speed_mps: filter.max_speed_mps,
+      },
+    );
+  }
+  warnings
+}
+
+/// Keeps at most `max_points` records of `route` (when set), picked by
+/// `simplify_indices` so the track keeps its shape. Every record series
+/// keeps the values of the picked records, so the last record and the
+/// final distance stay; line breaks move to the picked records around
+/// them.
+pub fn downsample_route(max_points: Option<usize>, route: &mut RouteData) {
+  let Some(max_points) = max_points else {
+    return;
+  };
+  let indices = simplify_indices(
+    &route.gps_points,
+    &route.segment_breaks,
+    max_points,
+  );
+  if indices.len() == route.gps_points.len() {
+    return;
+  }
+  fn pick<T: Clone>(series: &mut Vec<T>, indices: &[usize]) {
+    *series = indices
+      .iter()
+      .filter_map(|&i| series.get(i).cloned())
+      .collect();
+  }
+  pick(&mut route.paces, &indices);
+  pick(&mut route.gps_points, &indices);
+  pick(&mut route.distances, &indices);
+  pick(&mut route.heart_rates, &indices);
+  pick(&mut route.speeds, &indices);
+  pick(&mut route.cadences, &indices);
+  pick(&mut route.powers, &indices);
+  pick(&mut route.altitudes, &indices);
+  pick(&mut route.timestamps, &indices);
+  for values in route.extra.values_mut() {
+    pick(values, &indices);
+  }
+  // Both records around a break are picked
+  route.segment_breaks = route
+    .segment_breaks
+    .iter()
+    .filter_map(|record| indices.binary_search(record).ok())
+    .collect();
+}
+
+/// Laps `source` asks for: `lap` as the device recorded it, or
+/// `auto_splits` of the records in its place
+pub fn resolve_laps(
+  source: LapSource,
+  route: &RouteData,
+  lap: LapData,
+) -> LapData {
+  match source {
+    LapSource::Device => lap,
+    LapSource::AutoSplits { every_km } => auto_splits(route, every_km * 1000.0),
+    LapSource::Auto if lap.avg_speed.is_empty() => {
+      auto_splits(route, AUTO_SPLIT_M)
+    }
+    LapSource::Auto => lap,
+  }
+}
+
+/// Narrows `activity` to the session `selection` picks. Joined and
+/// separate sessions keep the whole activity; a single session keeps its
+/// records and laps, with distances from 0 and the totals of the session
+/// summary cleared unless it is the first. An activity without sessions
+/// is session 0.
+pub fn select_session(
+  activity: &mut Activity,
+  selection: SessionSelection,
+) -> Result<()> {
+  let SessionSelection::Index(index) = selection else {
+    return Ok(());
+  };
+  if activity.sessions.is_empty() && index == 0 {
+    return Ok(());
+  }
+  let Some(range) = activity.sessions.get(index).cloned() else {
+    bail!(
+      "Session {} does not exist; the activity has {}",
+      index,
+      activity.sessions.len().max(1)
+    );
+  };
+
+  let route = &mut activity.route;
+  let records = range.records.clone();
+  route.paces = route.paces[records.clone()].to_vec();
+  route.gps_points = route.gps_points[records.clone()].to_vec();
+  route.heart_rates = route.heart_rates[records.clone()].to_vec();
+  route.speeds = route.speeds[records.clone()].to_vec();
+  route.cadences = route.cadences[records.clone()].to_vec();
+  route.powers = route.powers[records.clone()].to_vec();
+  route.altitudes = route.altitudes[records.clone()].to_vec();
+  route.timestamps = route.timestamps[records.clone()].to_vec();
+  route.segment_breaks = route
+    .segment_breaks
+    .iter()
+    .filter(|&record| records.contains(record))
+    .map(|record| record - records.start)
+    .collect();
+  for values in route.extra.values_mut() {
+    *values = values[records.clone()].to_vec();
+  }
+  route.distances = route.distances[range.distances].to_vec();
+  if let Some(&first) = route.distances.first() {
+    route.distances.iter_mut().for_each(|d| *d -= first);
+  }
+
+  let lap = &mut activity.lap;
+  let laps = range.laps;
+  lap.avg_heart_rate = lap.avg_heart_rate[laps.clone()].to_vec();
+  lap.avg_power = lap.avg_power[laps.clone()].to_vec();
+  lap.enhanced_avg_speed = lap.enhanced_avg_speed[laps.clone()].to_vec();
+  lap.avg_step_length = lap.avg_step_length[laps.clone()].to_vec();
+  lap.total_distance = lap.total_distance[laps.clone()].to_vec();
+  lap.avg_speed = lap.avg_speed[laps.clone()].to_vec();
+  lap.avg_cadence = lap.avg_cadence[laps.clone()].to_vec();
+  lap.total_elapsed_time = lap.total_elapsed_time[laps].to_vec();
+
+  // The summary describes the first session
+  let session = &mut activity.session;
+  if index > 0 {
+    session.total_distance = None;
+    session.total_timer_time = None;
+    session.total_ascent = None;
+    session.total_calories = None;
+    session.avg_heart_rate = None;
+    if let Some(&start) = route.timestamps.iter().flatten().next() {
+      session.start_time = Some(start);
+    }
+  }
+  if range.sport.is_some() {
+    session.sport = range.sport;
+  }
+  let distance = session
+    .total_distance
+    .or_else(|| route.distances.last().copied())
+    .unwrap_or(0.0);
+  session.times = activity_times(
+    &route.timestamps,
+    &route.distances,
+    &lap.total_elapsed_time,
+    distance,
+  );
+  activity.sessions.clear();
+  Ok(())
+}
+
+/// Laps of `every_m` meters built from the records, the last one partial,
+/// for activities recorded without laps.
+///
+/// Speed, heart rate, power and cadence are the averages of each split's
+/// records (so the cadence is in steps per minute, or rpm when cycling),
+/// the stride follows from speed and cadence, and the duration
+/// from the timestamps (or distance over speed without them). A split
+/// length that is not positive falls back to `AUTO_SPLIT_M`; a route
+/// without distances has no splits.
+pub fn auto_splits(route: &RouteData, every_m: f64) -> LapData {
+  let count = route.gps_points.len();
+  let distances = &route.distances[..route.distances.len().min(count)];
+  let total = distances.last().copied().unwrap_or(0.0);
+  if total <= 0.0 {
+    return LapData::default();
+  }
+  let every_m = if every_m > 0.0 { every_m } else { AUTO_SPLIT_M };
+  let mut start = 0.0;
+  let splits = LapData {
+    total_distance: split_ends(total, every_m)
+      .into_iter()
+      .map(|end| {
+        let length = end - start;
+        start = end;
+        length
+      })
+      .collect(),
+    ..LapData::default()
+  };
+
+  let mut lap = LapData::default();
+  for boundary in lap_boundaries(route, &splits) {
+    let records = boundary.records();
+    let distance = boundary.end_distance_m - boundary.start_distance_m;
+    let speed = mean(
+      route
+        .speeds
+        .get(records.clone())
+        .unwrap_or_default()
+        .iter()
+        .copied()
+        .filter(|speed| speed.is_finite()),
+    )
+    .unwrap_or(0.0);
+    let series_mean = |series: &[Option<u8>]| {
+      let values = series.get(records.clone()).unwrap_or_default();
+      mean(values.iter().flatten().map(|&value| value as f64))
+    };
+    let heart_rate = series_mean(&route.heart_rates);
+    let cadence = mean(
+      route
+        .cadences
+        .get(records.clone())
+        .unwrap_or_default()
+        .iter()
+        .flatten()
+        .map(|&cadence| cadence as f64),
+    );
+    let power = mean(
+      route
+        .powers
+        .get(records.clone())
+        .unwrap_or_default()
+        .iter()
+        .flatten()
+        .map(|&power| power as f64),
+    );
+
+    // Cadence is in steps per minute; strides are stored in decimeters
+    let stride_dm = cadence
+      .filter(|&cadence| cadence > 0.0)
+      .map(|cadence| speed * 60.0 / cadence * 10.0);
+    let timestamp = |record: usize| route.timestamps.get(record).copied()?;
+    let start_record = records.start.saturating_sub(1);
+    let elapsed = match (
+      timestamp(start_record),
+      timestamp(records.end.saturating_sub(1)),
+    ) {
+      (Some(start), Some(end)) if end > start => (end - start) as f64,
+      _ if speed > 0.0 => distance / speed,
+      _ => 0.0,
+    };
+
+    lap
+      .avg_heart_rate
+      .push(heart_rate.map(|hr| hr.round() as u8));
+    lap.avg_power.push(power.map(|power| power.round() as u16));
+    lap.enhanced_avg_speed.push(speed_to_pace(speed as f32));
+    lap.avg_step_length.push(stride_dm);
+    lap.total_distance.push(distance);
+    lap.avg_speed.push(speed);
+    lap
+      .avg_cadence
+      .push(cadence.map_or(0, |cadence| cadence.round() as u8));
+    lap.total_elapsed_time.push(elapsed);
+  }
+  lap
+}
+
+/// One pass of a loop the route runs more than once, found from the GPS
+/// track alone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Loop {
+  /// Record the loop starts on (the last record of the loop before)
+  pub start_idx: usize,
+  /// Record the loop closes on, the nearest to its start point
+  pub end_idx: usize,
+  /// Seconds from the start to the end record (None without timestamps)
+  pub duration_s: Option<f64>,
+  /// Distance covered from the start to the end record
+  pub distance_m: f64,
+}
+
+/// Finds the loops of a route that keeps coming back to where it started,
+/// e.g. intervals on a 400 m track, independent of the watch's laps.
+///
+/// A loop closes at the record nearest its start point once the route
+/// has covered at least `min_loop_m` and come back within `proximity_m`
+/// of that point; the next loop starts where it closed. A return along
+/// the way out (an out-and-back) is not a loop: when more than half of
+/// the way back runs the other way within `proximity_m` of the way out,
+/// the stretch is skipped and the search starts again from its end.
+///
+/// Distances come from the records when every point has one, and from
+/// the GPS track otherwise.
+///
+/// # Example
+/// ```no_run
+/// use runarium::analysis::detect_loops;
+/// use runarium::utils::read_file::fit_reader;
+///
+/// let (route, _) = fit_reader("source/example.fit").unwrap();
+/// for (i, found) in detect_loops(&route, 300.0, 25.0).iter().enumerate() {
+///   println!("loop {}: {:.0} m", i + 1, found.distance_m);
+/// }
+/// ```
+pub fn detect_loops(
+  route: &RouteData,
+  min_loop_m: f64,
+  proximity_m: f64,
+) -> Vec<Loop> {
+  let points = &route.gps_points;
+  let along = along_track_m(route);
+  let mut loops = Vec::new();
+  let mut start = 0;
+  let mut i = 1;
+  while i < points.len() {
+    let from_start = |j: usize| ground_m(points[start], points[j]);
+    if along[i] - along[start] < min_loop_m || from_start(i) > proximity_m {
+      i += 1;
+      continue;
+    }
+
+    // Close at the nearest approach while the route stays within reach
+    let mut end = i;
+    while i + 1 < points.len() && from_start(i + 1) <= proximity_m {
+      i += 1;
+      if from_start(i) < from_start(end) {
+        end = i;
+      }
+    }
+    if !retraces(&points[start..=end], proximity_m) {
+      let duration_s = match (
+        route.timestamps.get(start),
+        route.timestamps.get(end),
+      ) {
+        (Some(&Some(from)), Some(&Some(to))) => Some((to - from) as f64),
+        _ => None,
+      };
+      loops.push(Loop {
+        start_idx: start,
+        end_idx: end,
+        duration_s,
+        distance_m: along[end] - along[start],
+      });
+    }
+    start = end;
+    i = end + 1;
+  }
+  loops
+}
+
+/// Steepest stretch of a route, found by `steepest_climb`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Climb {
+  /// Record the climb starts on
+  pub start_idx: usize,
+  /// Record the climb ends on
+  pub end_idx: usize,
+  /// Distance covered from the start to the end record
+  pub distance_m: f64,
+  /// Average grade over the climb (%)
+  pub grade: f32,
+}
+
+/// Grade (%) at every record: the slope of a least-squares line through
+/// the altitudes within `GRADE_SMOOTHING_M / 2` meters on either side,
+/// which smooths out the noise of barometric and GPS altitudes.
+///
+/// The fit never reaches across a record without an altitude or a
+/// distance; such records have no grade, and neither has a record with
+/// no neighbour to fit against.
+///
+/// # Example
+/// ```no_run
+/// use runarium::analysis::grade_series;
+/// use runarium::utils::read_file::fit_reader;
+///
+/// let (route, _) = fit_reader("source/example.fit").unwrap();
+/// let grades = grade_series(&route);
+/// let steepest = grades.into_iter().flatten().fold(0.0, f32::max);
+/// println!("steepest grade: {:.1}%", steepest);
+/// ```
+pub fn grade_series(route: &RouteData) -> Vec<Option<f32>> {
+  let count = route.gps_points.len();
+  let sample = |i: usize| match (
+    route.distances.get(i),
+    route.altitudes.get(i),
+  ) {
+    (Some(&distance), Some(&Some(altitude))) => Some((distance, altitude)),
+    _ => None,
+  };
+  let half = GRADE_SMOOTHING_M / 2.0;
+
+  let mut grades = vec![None; count];
+  let mut start = 0;
+  while start < count {
+    // Fit within each run of records with a distance and an altitude
+    let run: Vec<(f64, f64)> = (start..count).map_while(sample).collect();
+    let (mut low, mut high) = (0, 0);
+    for (i, &(distance, _)) in run.iter().enumerate() {
+      while run[low].0 < distance - half {
+        low += 1;
+      }
+      while high < run.len() && run[high].0 <= distance + half {
+        high += 1;
+      }
+      grades[start + i] = slope(&run[low..high]).map(|s| (s * 100.0) as f32);
+    }
+    start += run.len() + 1;
+  }
+  grades
+}
+
+/// Steepest stretch of the route at least `window_m` meters long, by its
+/// average grade: the rise of the smoothed altitude over the distance.
+///
+/// Stretches with a record without a grade (see `grade_series`) are left
+/// out, so a gap in the altitudes is never taken for a climb. None when no
+/// stretch long enough has grades throughout, or none of them climbs.
+///
+/// # Example
+/// ```no_run
+/// use runarium::analysis::steepest_climb;
+/// use runarium::utils::read_file::fit_reader;
+///
+/// let (route, _) = fit_reader("source/example.fit").unwrap();
+/// if let Some(climb) = steepest_climb(&route, 200.0) {
+///   println!("{:.0} m at {:.1}%", climb.distance_m, climb.grade);
+/// }
+/// ```
+pub fn steepest_climb(route: &RouteData, window_m: f64) -> Option<Climb> {
+  let grades = grade_series(route);
+  let distances = &route.distances;
+  let count = grades.len().min(distances.len());
+
+  // Rise up to each record from the mean grade of every segment before,
+  // and the segments without a grade before it
+  let mut rise = vec![0.0; count];
+  let mut gaps = vec![0; count];
+  for i in 1..count {
+    let step = distances[i] - distances[i - 1];
+    let segment = match (grades[i - 1], grades[i]) {
+      (Some(a), Some(b)) => Some((a + b) as f64 / 200.0 * step),
+      _ => None,
+    };
+    rise[i] = rise[i - 1] + segment.unwrap_or(0.0);
+    gaps[i] = gaps[i - 1] + usize::from(segment.is_none());
+  }
+
+  let mut steepest: Option<Climb> = None;
+  let mut end = 0;
+  for start in 0..count {
+    // Shortest stretch from `start` that covers the window
+    end = end.max(start);
+    while end < count && distances[end] - distances[start] < window_m {
+      end += 1;
+    }
+    if end == count {
+      break;
+    }
+    let distance_m = distances[end] - distances[start];
+    if gaps[end] > gaps[start] || distance_m <= 0.0 {
+      continue;
+    }
+    let grade = ((rise[end] - rise[start]) / distance_m * 100.0) as f32;
+    if grade > 0.0 && steepest.is_none_or(|climb| grade > climb.grade) {
+      steepest = Some(Climb {
+        start_idx: start,
+        end_idx: end,
+        distance_m,
+        grade,
+      });
+    }
+  }
+  steepest
+}
+
+/// Speed (m/s) the GPS track implies over each record's segment to the
+/// next: the ground distance over the time between their timestamps.
+///
+/// None where either record has no timestamp; a jump with no time
+/// passing is infinitely fast. One value per segment, so one fewer than
+/// the GPS points.
+pub fn segment_speeds(route: &RouteData) -> Vec<Option<f64>> {
+  let time = |i: usize| route.timestamps.get(i).copied().flatten();
+  route
+    .gps_points
+    .windows(2)
+    .enumerate()
+    .map(|(i, pair)| {
+      let seconds = time(i + 1)? - time(i)?;
+      let meters = ground_m(pair[0], pair[1]);
+      Some(match seconds {
+        s if s > 0 => meters / s as f64,
+        _ if meters > 0.0 => f64::INFINITY,
+        _ => 0.0,
+      })
+    })
+    .collect()
+}
+
+/// Whether each record's segment implies a speed above `max_speed_mps`,
+/// as a GPS glitch jumping across the map would. Segments without
+/// timestamps are not flagged.
+pub fn speed_glitches(route: &RouteData, max_speed_mps: f64) -> Vec<bool> {
+  segment_speeds(route)
+    .into_iter()
+    .map(|speed| speed.is_some_and(|speed| speed > max_speed_mps))
+    .collect()
+}
+
+/// Cumulative distance at every GPS point: the record distances when
+/// all are there, the summed GPS track otherwise
+fn along_track_m(route: &RouteData) -> Vec<f64> {
+  let points = &route.gps_points;
+  if route.distances.len() >= points.len() {
+    return route.distances[..points.len()].to_vec();
+  }
+  let mut total = 0.0;
+  let mut along = Vec::with_capacity(points.len());
+  for (i, &point) in points.iter().enumerate() {
+    if i > 0 {
+      total += ground_m(points[i - 1], point);
+    }
+    along.push(total);
+  }
+  along
+}
+
+/// Whether more than `RETRACE_LIMIT` of the second half of `points` runs
+/// back along the first half, within `tolerance_m` and against its
+/// direction, as on an out-and-back. A loop run twice retraces itself in
+/// the same direction and does not count.
+fn retraces(points: &[(f64, f64)], tolerance_m: f64) -> bool {
+  let origin = points[0];
+  let local: Vec<(f64, f64)> =
+    points.iter().map(|&p| local_m(origin, p)).collect();
+  let (out, back) = local.split_at(local.len() / 2);
+  if out.len() < 2 || back.len() < 2 {
+    return false;
+  }
+  let steps = back.len() - 1;
+  let on_way_out = back
+    .windows(2)
+    .filter(|step| {
+      let heading = (
+        step[1].0 - step[0].0,
+        step[1].1 - step[0].1,
+      );
+      out.windows(2).any(|pair| {
+        let out_heading = (
+          pair[1].0 - pair[0].0,
+          pair[1].1 - pair[0].1,
+        );
+        heading.0 * out_heading.0 + heading.1 * out_heading.1 < 0.0
+          && segment_distance(step[0], pair[0], pair[1]) <= tolerance_m
+      })
+    })
+    .count();
+  on_way_out as f64 > steps as f64 * RETRACE_LIMIT
+}
+
+/// Meters between two (lat, lon) points, by haversine
+pub(crate) fn ground_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+  let (lat_a, lat_b) = (a.0.to_radians(), b.0.to_radians());
+  let d_lat = lat_b - lat_a;
+  let d_lon = (b.1 - a.1).to_radians();
+  let h = (d_lat / 2.0).sin().powi(2)
+    + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+  2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// `point` in meters east and north of `origin`, flat over short spans
+fn local_m(origin: (f64, f64), point: (f64, f64)) -> (f64, f64) {
+  let east = (point.1 - origin.1).to_radians()
+    * EARTH_RADIUS_M
+    * origin.0.to_radians().cos();
+  let north = (point.0 - origin.0).to_radians() * EARTH_RADIUS_M;
+  (east, north)
+}
+
+/// Distance from `p` to the segment `a`-`b`
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+  let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+  let length2 = dx * dx + dy * dy;
+  let t = if length2 > 0.0 {
+    (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+  } else {
+    0.0
+  };
+  (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Lap ends every `every_m` meters up to `total`, the last one partial
+fn split_ends(total: f64, every_m: f64) -> Vec<f64> {
+  let mut ends = Vec::new();
+  let mut end = every_m;
+  while end < total {
+    ends.push(end);
+    end += every_m;
+  }
+  ends.push(total);
+  ends
+}
+
+/// Slope of the least-squares line through (x, y) `samples`, None with
+/// fewer than two distinct x values
+fn slope(samples: &[(f64, f64)]) -> Option<f64> {
+  let mean_x = mean(samples.iter().map(|&(x, _)| x))?;
+  let mean_y = mean(samples.iter().map(|&(_, y)| y))?;
+  let (covariance, variance) =
+    samples.iter().fold((0.0, 0.0), |(cov, var), &(x, y)| {
+      let dx = x - mean_x;
+      (cov + dx * (y - mean_y), var + dx * dx)
+    });
+  (variance > 0.0).then(|| covariance / variance)
+}
+
+/// Mean of `values`, None when there are none
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+  let (sum, count) = values.fold((0.0, 0), |(sum, count), value| {
+    (sum + value, count + 1)
+  });
+  (count > 0).then(|| sum / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, f64::consts::TAU};
+
+  use super::*;
+  use crate::{
+    types::fit_data::SessionRange, utils::testing::synthetic_activity,
+  };
+
+  fn route(distances: &[f64], points: usize) -> RouteData {
+    RouteData {
+      paces: vec![],
+      gps_points: vec![(13.7, 100.5); points],
+      distances: distances.to_vec(),
+      heart_rates: vec![],
+      speeds: vec![],
+      cadences: vec![],
+      powers: vec![],
+      altitudes: vec![],
+      timestamps: vec![],
+      segment_breaks: vec![],
+      extra: HashMap::new(),
+    }
+  }
+
+  fn laps(distances: &[f64]) -> LapData {
+    LapData {
+      avg_heart_rate: vec![],
+      avg_power: vec![],
+      enhanced_avg_speed: vec![],
+      avg_step_length: vec![],
+      total_distance: distances.to_vec(),
+      avg_speed: vec![],
+      avg_cadence: vec![],
+      total_elapsed_time: vec![],
+    }
+  }
+
+  /// Synthetic run of 400 records as two sessions of one lap each
+  fn duathlon() -> Activity {
+    let mut activity = synthetic_activity(400);
+    activity.sessions = vec![
+      SessionRange {
+        records: 0..200,
+        distances: 0..200,
+        laps: 0..1,
+        sport: Some("running".to_string()),
+      },
+      SessionRange {
+        records: 200..400,
+        distances: 200..400,
+        laps: 1..2,
+        sport: Some("cycling".to_string()),
+      },
+    ];
+    activity
+  }
+
+  fn ranges(boundaries: &[LapBoundary]) -> Vec<Range<usize>> {
+    boundaries.iter().map(LapBoundary::records).collect()
+  }
+
+  #[test]
+  fn test_parsed_laps() {
+    let distances = [0.0, 400.0, 900.0, 1100.0, 1600.0, 2100.0, 2500.0];
+    let boundaries = lap_boundaries(
+      &route(&distances, 7),
+      &laps(&[1000.0, 1000.0, 500.0]),
+    );
+
+    assert_eq!(
+      ranges(&boundaries),
+      vec![0..3, 3..5, 5..7]
+    );
+    assert_eq!(
+      boundaries[1],
+      LapBoundary {
+        lap_index: 1,
+        start_record: 3,
+        end_record: 5,
+        start_distance_m: 1000.0,
+        end_distance_m: 2000.0,
+      }
+    );
+    assert_eq!(boundaries[2].end_distance_m, 2500.0);
+  }
+
+  #[test]
+  fn test_record_on_boundary_closes_lap() {
+    // Exactly 1.000 km belongs to the first lap, not the second
+    let distances = [0.0, 500.0, 1000.0, 1000.5, 2000.0];
+    let boundaries = lap_boundaries(
+      &route(&distances, 5),
+      &laps(&[1000.0, 1000.0]),
+    );
+    assert_eq!(ranges(&boundaries), vec![0..3, 3..5]);
+  }
+
+  #[test]
+  fn test_records_past_last_lap_join_it() {
+    // The watch kept recording after the last lap ended
+    let distances = [0.0, 600.0, 1200.0, 1300.0];
+    let boundaries = lap_boundaries(&route(&distances, 4), &laps(&[1000.0]));
+    assert_eq!(boundaries.len(), 1);
+    assert_eq!(boundaries[0].records(), 0..4);
+    assert_eq!(boundaries[0].end_distance_m, 1000.0);
+  }
+
+  #[test]
+  fn test_zero_distance_laps_are_empty() {
+    let distances = [0.0, 500.0, 1000.0, 1500.0];
+    let boundaries = lap_boundaries(
+      &route(&distances, 4),
+      &laps(&[1000.0, 0.0, 500.0]),
+    );
+    assert_eq!(
+      ranges(&boundaries),
+      vec![0..3, 3..3, 3..4]
+    );
+    assert_eq!(
+      (
+        boundaries[1].start_distance_m,
+        boundaries[1].end_distance_m
+      ),
+      (1000.0, 1000.0)
+    );
+    assert_eq!(boundaries[2].joined_records(), 2..4);
+  }
+
+  #[test]
+  fn test_repair_distance_reset() {
+    // The device rebooted halfway through 2 km and started again from 0
+    let mut track = route(
+      &[0.0, 500.0, 1000.0, 0.0, 500.0, 1000.0, 1000.0],
+      7,
+    );
+    let warnings =
+      repair_route_distances(DistanceRepair::Monotonicize, &mut track);
+    assert_eq!(
+      track.distances,
+      vec![0.0, 500.0, 1000.0, 1000.0, 1500.0, 2000.0, 2000.0]
+    );
+    assert_eq!(
+      warnings.0,
+      vec![Warning::DistanceRepaired {
+        resets: 1,
+        points: 4,
+      }]
+    );
+
+    let boundaries = lap_boundaries(&track, &laps(&[1000.0, 1000.0]));
+    assert_eq!(ranges(&boundaries), vec![0..4, 4..7]);
+    let splits = auto_splits(&track, 1000.0);
+    assert_eq!(
+      splits.total_distance,
+      vec![1000.0, 1000.0]
+    );
+
+    // Off keeps the reset, and a series that never drops is left alone
+    let mut raw = route(&[0.0, 500.0, 100.0], 3);
+    assert!(repair_route_distances(DistanceRepair::Off, &mut raw).is_empty());
+    assert_eq!(raw.distances, vec![0.0, 500.0, 100.0]);
+    let mut steady = [0.0, 5.0, 5.0, f64::NAN, 9.0];
+    assert_eq!(repair_distances(&mut steady), (0, 0));
+  }
+
+  #[test]
+  fn test_select_session() {
+    let mut activity = duathlon();
+    select_session(
+      &mut activity,
+      SessionSelection::Separate,
+    )
+    .unwrap();
+    assert_eq!(activity.route.gps_points.len(), 400);
+    assert_eq!(activity.sessions.len(), 2);
+
+    let mut activity = duathlon();
+    activity.route.segment_breaks = vec![100, 300];
+    let start = activity.route.timestamps[200];
+    select_session(
+      &mut activity,
+      SessionSelection::Index(1),
+    )
+    .unwrap();
+    let route = &activity.route;
+    assert_eq!(route.gps_points.len(), 200);
+    assert_eq!(route.distances.len(), 200);
+    assert_eq!(route.distances[0], 0.0);
+    assert_eq!(route.distances[199], 995.0);
+    assert_eq!(route.segment_breaks, vec![100]);
+    assert_eq!(activity.lap.avg_speed.len(), 1);
+    assert!(activity.sessions.is_empty());
+
+    // The summary totals are the first session's
+    let session = &activity.session;
+    assert_eq!(
+      session.sport.as_deref(),
+      Some("cycling")
+    );
+    assert_eq!(session.total_distance, None);
+    assert_eq!(session.start_time, start);
+    assert!(session.times.is_some());
+
+    let err = select_session(
+      &mut duathlon(),
+      SessionSelection::Index(2),
+    );
+    assert_eq!(
+      err.unwrap_err().to_string(),
+      "Session 2 does not exist; the activity has 2"
+    );
+
+    // A single-session activity is session 0
+    let mut activity = synthetic_activity(10);
+    select_session(
+      &mut activity,
+      SessionSelection::Index(0),
+    )
+    .unwrap();
+    assert_eq!(activity.route.gps_points.len(), 10);
+    assert!(select_session(
+      &mut activity,
+      SessionSelection::Index(1)
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_auto_splits_without_laps() {
+    let distances = [0.0, 700.0, 1000.0, 1400.0, 2100.0, 2350.0];
+    let boundaries = lap_boundaries(&route(&distances, 6), &laps(&[]));
+    assert_eq!(
+      ranges(&boundaries),
+      vec![0..3, 3..4, 4..6]
+    );
+    let ends: Vec<f64> = boundaries.iter().map(|b| b.end_distance_m).collect();
+    assert_eq!(ends, vec![1000.0, 2000.0, 2350.0]);
+
+    // Only zero-distance laps count as no laps
+    assert_eq!(
+      lap_boundaries(
+        &route(&distances, 6),
+        &laps(&[0.0, 0.0])
+      ),
+      boundaries
+    );
+  }
+
+  #[test]
+  fn test_auto_splits_end_on_whole_kilometer() {
+    // No empty partial lap after an exact 2.000 km
+    let distances = [0.0, 1000.0, 1500.0, 2000.0];
+    let boundaries = lap_boundaries(&route(&distances, 4), &laps(&[]));
+    assert_eq!(ranges(&boundaries), vec![0..2, 2..4]);
+    assert_eq!(boundaries[1].end_distance_m, 2000.0);
+  }
+
+  /// 5 km at a constant 2.5 m/s, a record every 5 m (2 s), with the heart
+  /// rate alternating 140/160 and a cadence of 160
+  fn steady_route() -> RouteData {
+    let records = 1001;
+    RouteData {
+      paces: vec!["6:40".to_string(); records],
+      gps_points: vec![(13.7, 100.5); records],
+      distances: (0..records).map(|i| i as f64 * 5.0).collect(),
+      heart_rates: (0..records)
+        .map(|i| Some(if i % 2 == 0 { 140 } else { 160 }))
+        .collect(),
+      speeds: vec![2.5; records],
+      cadences: vec![Some(160); records],
+      powers: (0..records).map(|i| (i % 4 != 3).then_some(200)).collect(),
+      altitudes: vec![None; records],
+      timestamps: (0..records).map(|i| Some(i as i64 * 2)).collect(),
+      segment_breaks: Vec::new(),
+      extra: HashMap::new(),
+    }
+  }
+
+  #[test]
+  fn test_auto_splits_average_the_records() {
+    let splits = auto_splits(&steady_route(), 1000.0);
+    assert_eq!(splits.total_distance, vec![1000.0; 5]);
+    assert_eq!(splits.avg_speed, vec![2.5; 5]);
+    assert_eq!(
+      splits.enhanced_avg_speed,
+      vec!["6:40"; 5]
+    );
+    assert_eq!(
+      splits.total_elapsed_time,
+      vec![400.0; 5]
+    );
+    assert_eq!(
+      splits.avg_heart_rate,
+      vec![Some(150); 5]
+    );
+    // Records without power are left out of the average
+    assert_eq!(splits.avg_power, vec![Some(200); 5]);
+    assert_eq!(splits.avg_cadence, vec![160; 5]);
+    // 2.5 m/s at 160 steps/min is 0.9375 m per step
+    assert_eq!(
+      splits.avg_step_length,
+      vec![Some(9.375); 5]
+    );
+
+    // The last split is partial
+    let splits = auto_splits(&steady_route(), 2000.0);
+    assert_eq!(
+      splits.total_distance,
+      vec![2000.0, 2000.0, 1000.0]
+    );
+    assert_eq!(
+      splits.total_elapsed_time,
+      vec![800.0, 800.0, 400.0]
+    );
+
+    // Without timestamps the duration is distance over speed
+    let mut untimed = steady_route();
+    untimed.timestamps = vec![None; 1001];
+    assert_eq!(
+      auto_splits(&untimed, 1000.0).total_elapsed_time,
+      vec![400.0; 5]
+    );
+    assert!(auto_splits(&route(&[], 3), 1000.0).avg_speed.is_empty());
+  }
+
+  #[test]
+  fn test_resolve_laps() {
+    let route = steady_route();
+    let device = || LapData {
+      avg_speed: vec![3.0],
+      total_distance: vec![5000.0],
+      ..LapData::default()
+    };
+    let count = |source, lap| resolve_laps(source, &route, lap).avg_speed.len();
+
+    assert_eq!(
+      count(LapSource::Device, LapData::default()),
+      0
+    );
+    assert_eq!(
+      count(LapSource::Auto, LapData::default()),
+      5
+    );
+    assert_eq!(count(LapSource::Auto, device()), 1);
+    assert_eq!(
+      count(
+        LapSource::AutoSplits { every_km: 0.5 },
+        device()
+      ),
+      10
+    );
+  }
+
+  #[test]
+  fn test_short_distance_series() {
+    // Records without a distance go to the last lap
+    let distances = [0.0, 800.0, 1200.0];
+    let boundaries = lap_boundaries(
+      &route(&distances, 6),
+      &laps(&[1000.0, 1000.0]),
+    );
+    assert_eq!(ranges(&boundaries), vec![0..2, 2..6]);
+
+    // No distances at all: one lap of every record
+    let boundaries = lap_boundaries(&route(&[], 6), &laps(&[1000.0]));
+    assert_eq!(boundaries.len(), 1);
+    assert_eq!(boundaries[0].records(), 0..6);
+  }
+
+  #[test]
+  fn test_no_points_no_laps() {
+    assert!(lap_boundaries(&route(&[], 0), &laps(&[1000.0])).is_empty());
+  }
+
+  /// Route through points given in meters east and north of a start
+  /// point, one record a second
+  fn track(path: &[(f64, f64)]) -> RouteData {
+    let origin: (f64, f64) = (13.7, 100.5);
+    let mut data = route(&[], 0);
+    let mut total = 0.0;
+    for (i, &(east, north)) in path.iter().enumerate() {
+      if i > 0 {
+        let (last_east, last_north) = path[i - 1];
+        total += (east - last_east).hypot(north - last_north);
+      }
+      data.gps_points.push((
+        origin.0 + (north / EARTH_RADIUS_M).to_degrees(),
+        origin.1
+          + (east / (EARTH_RADIUS_M * origin.0.to_radians().cos()))
+            .to_degrees(),
+      ));
+      data.distances.push(total);
+      data.timestamps.push(Some(i as i64));
+    }
+    data
+  }
+
+  /// `count` times round a circle of `circumference` meters from its
+  /// southern point, a point every 5 m
+  fn circles(count: usize, circumference: f64) -> Vec<(f64, f64)> {
+    let steps = (circumference / 5.0) as usize;
+    let radius = circumference / TAU;
+    (0..=count * steps)
+      .map(|i| {
+        let angle = TAU * i as f64 / steps as f64;
+        (
+          radius * angle.sin(),
+          radius * (1.0 - angle.cos()),
+        )
+      })
+      .collect()
+  }
+
+  /// Out `length` meters east and back `offset` meters further north
+  fn out_and_back(length: f64, offset: f64) -> Vec<(f64, f64)> {
+    let steps = (length / 5.0) as i32;
+    let out = (0..=steps).map(|i| (i as f64 * 5.0, 0.0));
+    let back = (0..=steps).rev().map(|i| (i as f64 * 5.0, offset));
+    out.chain(back).collect()
+  }
+
+  #[test]
+  fn test_repeated_loops() {
+    let loops = detect_loops(&track(&circles(4, 400.0)), 200.0, 25.0);
+    assert_eq!(loops.len(), 4);
+    for (i, found) in loops.iter().enumerate() {
+      assert_eq!(
+        (found.start_idx, found.end_idx),
+        (i * 80, (i + 1) * 80)
+      );
+      assert!((found.distance_m - 400.0).abs() < 1.0);
+      assert_eq!(found.duration_s, Some(80.0));
+    }
+  }
+
+  #[test]
+  fn test_out_and_back_is_not_a_loop() {
+    // Back on the same side of the road, or on the other side
+    for offset in [0.0, 12.0] {
+      let route = track(&out_and_back(1000.0, offset));
+      assert!(detect_loops(&route, 200.0, 25.0).is_empty());
+    }
+  }
+
+  #[test]
+  fn test_loops_after_out_and_back() {
+    // A warm-up out and back, then three loops from the same start
+    let mut path = out_and_back(800.0, 0.0);
+    path.extend(circles(3, 400.0).into_iter().skip(1));
+    let loops = detect_loops(&track(&path), 200.0, 25.0);
+    assert_eq!(loops.len(), 3);
+    assert_eq!(loops[0].start_idx, 321);
+    assert!(loops
+      .iter()
+      .all(|found| (found.distance_m - 400.0).abs() < 1.0));
+  }
+
+  #[test]
+  fn test_loop_shorter_than_minimum_joins_the_next() {
+    // Two 150 m circles only close as one 300 m loop
+    let loops = detect_loops(&track(&circles(2, 150.0)), 200.0, 10.0);
+    assert_eq!(loops.len(), 1);
+    assert!((loops[0].distance_m - 300.0).abs() < 1.0);
+  }
+
+  #[test]
+  fn test_loops_from_gps_without_record_data() {
+    let mut route = track(&circles(2, 400.0));
+    route.distances.clear();
+    route.timestamps.clear();
+    let loops = detect_loops(&route, 200.0, 25.0);
+    assert_eq!(loops.len(), 2);
+    // The track is a polygon inside the circle
+    assert!((loops[1].distance_m - 400.0).abs() < 1.0);
+    assert_eq!(loops[1].duration_s, None);
+
+    // A route that never comes back has no loops
+    let route = track(&out_and_back(1000.0, 0.0)[..200]);
+    assert!(detect_loops(&route, 200.0, 25.0).is_empty());
+    assert!(detect_loops(&track(&[]), 200.0, 25.0).is_empty());
+  }
+
+  /// Records every 10 m along `sections` of (length m, grade %)
+  fn hill(sections: &[(f64, f64)]) -> RouteData {
+    let mut altitudes = vec![Some(100.0)];
+    for &(length, grade) in sections {
+      for _ in 0..(length / 10.0) as usize {
+        let last = altitudes.last().unwrap().unwrap();
+        altitudes.push(Some(last + grade / 10.0));
+      }
+    }
+    let distances: Vec<f64> =
+      (0..altitudes.len()).map(|i| i as f64 * 10.0).collect();
+    RouteData {
+      altitudes,
+      ..route(&distances, distances.len())
+    }
+  }
+
+  #[test]
+  fn test_grade_series_smooths_noisy_altitudes() {
+    // A steady 6% with the altitude jumping 1 m up and down every record
+    let mut route = hill(&[(500.0, 6.0)]);
+    for (i, altitude) in route.altitudes.iter_mut().enumerate() {
+      *altitude = altitude.map(|a| a + if i % 2 == 0 { 0.5 } else { -0.5 });
+    }
+    let grades = grade_series(&route);
+    assert_eq!(grades.len(), 51);
+    assert!(grades
+      .iter()
+      .all(|grade| (grade.unwrap() - 6.0).abs() < 2.5));
+    // With records on both sides the swings cancel out
+    assert!(grades[2..49]
+      .iter()
+      .all(|grade| (grade.unwrap() - 6.0).abs() < 1e-3));
+
+    // Record to record, the same altitudes swing between -4% and 16%
+    let raw = (route.altitudes[2].unwrap() - route.altitudes[1].unwrap())
+      / 10.0
+      * 100.0;
+    assert!((raw - 16.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_grade_series_without_altitudes() {
+    let mut route = hill(&[(300.0, 4.0)]);
+    for altitude in &mut route.altitudes[10..13] {
+      *altitude = None;
+    }
+    // Record 13 stands alone between the gap and record 14
+    route.altitudes[14] = None;
+    let grades = grade_series(&route);
+    assert!(grades[..10].iter().all(Option::is_some));
+    assert!(grades[10..15].iter().all(Option::is_none));
+    assert!((grades[20].unwrap() - 4.0).abs() < 1e-4);
+
+    route.altitudes.clear();
+    assert!(grade_series(&route).iter().all(Option::is_none));
+  }
+
+  #[test]
+  fn test_steepest_climb_on_a_hill() {
+    // Flat, a long 5% drag, a short 12% wall, then down again
+    let route =
+      hill(&[(500.0, 0.0), (500.0, 5.0), (300.0, 12.0), (400.0, -8.0)]);
+    let climb = steepest_climb(&route, 200.0).unwrap();
+    assert!((climb.grade - 12.0).abs() < 1e-3);
+    assert_eq!(climb.distance_m, 200.0);
+    // Within the wall, clear of its smoothed edges
+    assert!(climb.start_idx >= 102 && climb.end_idx <= 128);
+
+    // No 400 m of the wall alone, so the drag pulls the average down
+    let longer = steepest_climb(&route, 400.0).unwrap();
+    assert!(longer.grade > 5.0 && longer.grade < 12.0);
+    assert!(longer.start_idx >= 50 && longer.end_idx <= 130);
+
+    // A route that only descends has no climb
+    assert_eq!(
+      steepest_climb(&hill(&[(500.0, -3.0)]), 200.0),
+      None
+    );
+    // Nor does one shorter than the window
+    assert_eq!(
+      steepest_climb(&hill(&[(100.0, 9.0)]), 200.0),
+      None
+    );
+  }
+
+  #[test]
+  fn test_steepest_climb_skips_missing_altitudes() {
+    let mut route = hill(&[(500.0, 5.0), (300.0, 12.0)]);
+    // The foot of the wall lost its altitudes
+    for altitude in &mut route.altitudes[48..70] {
+      *altitude = None;
+    }
+    let climb = steepest_climb(&route, 200.0).unwrap();
+    assert!((climb.grade - 5.0).abs() < 1e-3);
+    assert!(climb.end_idx < 48);
+
+    route.altitudes = vec![None; route.altitudes.len()];
+    assert_eq!(steepest_climb(&route, 200.0), None);
+  }
+
+  #[test]
+  fn test_reject_gps_spike() {
+    let activity = synthetic_activity(400);
+    let mut track = activity.route.clone();
+    // A tunnel exit throws one record 500 m north
+    track.gps_points[200].0 += 500.0 / 111_320.0;
+    let raw = track.clone();
+
+    // Off by default: the track stays as recorded
+    assert!(reject_route_spikes(None, &mut track).is_empty());
+    assert_eq!(track.gps_points, raw.gps_points);
+
+    let warnings =
+      reject_route_spikes(Some(SpikeFilter::default()), &mut track);
+    assert_eq!(
+      warnings.0,
+      vec![Warning::SpikesReplaced {
+        count: 1,
+        max_speed_mps: 12.0,
+      }]
+    );
+    let original = activity.route.gps_points[200];
+    assert!(ground_m(track.gps_points[200], original) < 5.0);
+    for i in (0..400).filter(|&i| i != 200) {
+      assert_eq!(track.gps_points[i], raw.gps_points[i]);
+    }
+
+    // A spike on the last record stays at the last kept position
+    let mut points = vec![(13.7, 100.5), (13.7001, 100.5), (13.75, 100.5)];
+    let times = [Some(0), Some(5), Some(10)];
+    assert_eq!(
+      reject_spikes(&mut points, &times, 12.0),
+      1
+    );
+    assert_eq!(points[2], (13.7001, 100.5));
+  }
+
+  #[test]
+  fn test_downsample_route() {
+    let mut track = synthetic_activity(2000).route;
+    track.segment_breaks = vec![700];
+    let raw = track.clone();
+
+    downsample_route(None, &mut track);
+    assert_eq!(track.gps_points, raw.gps_points);
+
+    downsample_route(Some(300), &mut track);
+    assert_eq!(track.gps_points.len(), 300);
+    for series in [
+      track.paces.len(),
+      track.distances.len(),
+      track.speeds.len(),
+      track.heart_rates.len(),
+      track.timestamps.len(),
+    ] {
+      assert_eq!(series, 300);
+    }
+    // Still ends at the true final distance, with the break kept
+    assert_eq!(
+      track.distances.last(),
+      raw.distances.last()
+    );
+    assert_eq!(track.gps_points[0], raw.gps_points[0]);
+    let break_at = track.segment_breaks[0];
+    assert_eq!(
+      track.distances[break_at],
+      raw.distances[700]
+    );
+    assert_eq!(
+      track.distances[break_at + 1],
+      raw.distances[701]
+    );
+    assert!(track.distances.windows(2).all(|pair| pair[0] < pair[1]));
+  }
+
+  #[test]
+  fn test_segment_speeds() {
+    // 0.0001 degrees of latitude is 11.1 m
+    let mut track = route(&[], 0);
+    track.gps_points = vec![
+      (13.7, 100.5),
+      (13.7001, 100.5),
+      (13.7101, 100.5),
+      (13.7102, 100.5),
+      (13.7103, 100.5),
+      (13.7103, 100.5),
+    ];
+    track.timestamps = vec![Some(0), Some(1), Some(2), None, Some(4), Some(4)];
+
+    let speeds = segment_speeds(&track);
+    assert_eq!(speeds.len(), 5);
+    assert!((speeds[0].unwrap() - 11.12).abs() < 0.01);
+    // A kilometer in a second
+    assert!((speeds[1].unwrap() - 1111.95).abs() < 0.01);
+    assert_eq!(speeds[2], None);
+    assert_eq!(speeds[3], None);
+    assert_eq!(speeds[4], Some(0.0));
+
+    assert_eq!(
+      speed_glitches(&track, 12.0),
+      vec![false, true, false, false, false]
+    );
+    // Moving without time passing
+    track.gps_points[5] = (13.7104, 100.5);
+    assert_eq!(
+      segment_speeds(&track)[4],
+      Some(f64::INFINITY)
+    );
+    assert!(segment_speeds(&route(&[], 1)).is_empty());
+  }
+}
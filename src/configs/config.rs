@@ -1,5 +1,15 @@
+use std::{fmt::Write, path::Path, sync::Arc};
+
+use chrono::{
+  format::{Item, StrftimeItems},
+  DateTime, FixedOffset, Local,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{LimitKind, RunariumError};
+
 /// Configuration for route scaling and positioning on the map
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct RouteScale {
   /// Scale factor for route visualization (0.0-1.0 recommended)
   pub scale: f64,
@@ -50,7 +60,7 @@ impl Default for RouteScale {
 }
 
 /// Color configuration for different route elements (BGRA format)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct RouteColor {
   /// Color for the route line (default: red)
   pub route_line: [f64; 4],
@@ -97,6 +107,28 @@ impl RouteColor {
       lap_bars: [255.0, 0.0, 255.0, 0.0],   // Magenta
     }
   }
+
+  /// Creates an Okabe-Ito scheme that stays distinct with red-green
+  /// color blindness, for dark or photo backgrounds
+  pub fn colorblind_safe() -> Self {
+    Self {
+      route_line: [0.0, 159.0, 230.0, 0.0], // Orange
+      current_position: [233.0, 180.0, 86.0, 0.0], // Sky blue
+      text: [255.0, 255.0, 255.0, 0.0],     // White
+      lap_bars: [115.0, 158.0, 0.0, 0.0],   // Bluish green
+    }
+  }
+
+  /// Creates an Okabe-Ito scheme that stays distinct with red-green
+  /// color blindness, for light backgrounds
+  pub fn colorblind_safe_light() -> Self {
+    Self {
+      route_line: [178.0, 114.0, 0.0, 0.0], // Blue
+      current_position: [0.0, 94.0, 213.0, 0.0], // Vermillion
+      text: [0.0, 0.0, 0.0, 0.0],           // Black
+      lap_bars: [178.0, 114.0, 0.0, 0.0],   // Blue
+    }
+  }
 }
 
 impl Default for RouteColor {
@@ -111,15 +143,1043 @@ impl Default for RouteColor {
   }
 }
 
+/// How values are mapped onto a scale (gradient colors, lap bars), so a
+/// few outliers do not squeeze everything else together
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+  /// The scale spans the smallest to the largest value
+  MinMax,
+  /// The scale spans the `low` to the `high` percentile (0-100, e.g. 5 and
+  /// 95); values outside are clamped
+  Percentile { low: f64, high: f64 },
+  /// The scale spans two standard deviations around the mean, within the
+  /// values' own range; values outside are clamped
+  ZScoreClamped,
+}
+
+impl Default for Normalization {
+  /// Creates default normalization (min-max)
+  fn default() -> Self {
+    Normalization::MinMax
+  }
+}
+
+/// How each segment of the route line is colored
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteSegmentColoring {
+  /// Every segment in `RouteColor::route_line`
+  Solid,
+  /// Blends from `low` to `high` by altitude, normalized to the activity's
+  /// altitude range or to a fixed `range` (meters) for comparing activities
+  ElevationGradient {
+    low: Color,
+    high: Color,
+    #[serde(default)]
+    range: Option<(f64, f64)>,
+    /// How the activity's altitude range is found when `range` is None
+    #[serde(default)]
+    normalization: Normalization,
+  },
+}
+
+impl Default for RouteSegmentColoring {
+  /// Creates default coloring (solid)
+  fn default() -> Self {
+    RouteSegmentColoring::Solid
+  }
+}
+
+/// How the route itself is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteRenderStyle {
+  /// Connected line through every point
+  Line,
+  /// Dots of `radius` pixels at points at least `spacing_m` meters apart
+  /// along the route, which also thins dense GPS clusters
+  Dots { radius: i32, spacing_m: f64 },
+  /// Line with dots on top
+  Both { radius: i32, spacing_m: f64 },
+}
+
+impl Default for RouteRenderStyle {
+  /// Creates default style (line)
+  fn default() -> Self {
+    RouteRenderStyle::Line
+  }
+}
+
+impl RouteRenderStyle {
+  /// Whether the connected line is drawn
+  pub fn draws_line(&self) -> bool {
+    !matches!(self, RouteRenderStyle::Dots { .. })
+  }
+
+  /// Dot radius and spacing, if dots are drawn
+  pub fn dots(&self) -> Option<(i32, f64)> {
+    match *self {
+      RouteRenderStyle::Line => None,
+      RouteRenderStyle::Dots { radius, spacing_m }
+      | RouteRenderStyle::Both { radius, spacing_m } => {
+        Some((radius, spacing_m))
+      }
+    }
+  }
+}
+
+/// Filter applied to the background before anything is drawn over it;
+/// a list of them is applied in order
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundFx {
+  /// Gaussian blur (`sigma` in pixels)
+  Blur { sigma: f64 },
+  /// Scales the brightness by `factor` (below 1.0 darkens)
+  Darken { factor: f64 },
+  /// Muted map look for satellite photos: local contrast on the
+  /// lightness, slight desaturation and lifted shadows, blended with the
+  /// original by `strength` (0.0-1.0)
+  Mapify { strength: f64 },
+}
+
+/// How a background layer is combined with the layers below it
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerBlend {
+  /// The layer is laid over the ones below at its opacity
+  Normal,
+}
+
+impl Default for LayerBlend {
+  /// Creates default blend (normal)
+  fn default() -> Self {
+    LayerBlend::Normal
+  }
+}
+
+/// Direction a gradient layer runs in
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientDirection {
+  /// `from` at the top, `to` at the bottom
+  Vertical,
+  /// `from` at the left, `to` at the right
+  Horizontal,
+}
+
+impl Default for GradientDirection {
+  /// Creates default direction (top to bottom)
+  fn default() -> Self {
+    GradientDirection::Vertical
+  }
+}
+
+/// What a background layer is filled with
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerSource {
+  /// Image file, scaled to cover the canvas and center-cropped
+  Image { path: String },
+  /// One color (BGRA; the alpha is ignored, the layer's opacity applies)
+  Solid { color: [f64; 4] },
+  /// Linear blend from one color (BGRA) to another across the canvas
+  Gradient {
+    from: [f64; 4],
+    to: [f64; 4],
+    #[serde(default)]
+    direction: GradientDirection,
+  },
+}
+
+/// One layer of a layered background; layers are composited in order,
+/// the first at the bottom, before anything is drawn over them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BackgroundLayer {
+  pub source: LayerSource,
+  /// Opacity of the layer over the ones below (0.0-1.0)
+  pub opacity: f64,
+  #[serde(default)]
+  pub blend: LayerBlend,
+}
+
+impl BackgroundLayer {
+  /// Creates a fully opaque layer of `source`
+  pub fn new(source: LayerSource) -> Self {
+    Self {
+      source,
+      opacity: 1.0,
+      blend: LayerBlend::default(),
+    }
+  }
+
+  /// Creates a fully opaque image layer, like a single background image
+  pub fn image(path: &str) -> Self {
+    Self::new(LayerSource::Image {
+      path: path.to_string(),
+    })
+  }
+
+  /// Sets the opacity
+  pub fn with_opacity(mut self, opacity: f64) -> Self {
+    self.opacity = opacity;
+    self
+  }
+}
+
+/// Interpolation used to resize background images
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interp {
+  /// Nearest pixel; blocky, keeps pixel art crisp
+  Nearest,
+  /// Bilinear
+  Linear,
+  /// Bicubic; smooth when enlarging
+  Cubic,
+  /// Lanczos over 8x8 pixels; sharpest when shrinking
+  Lanczos4,
+}
+
+/// How background images are resized to the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackgroundScaling {
+  /// Resize interpolation (None = Cubic when the image is enlarged,
+  /// Lanczos4 otherwise)
+  pub interpolation: Option<Interp>,
+  /// Whether a background smaller than the largest size it is fitted
+  /// within is enlarged to it, instead of kept at its own size
+  pub allow_upscale: bool,
+}
+
+impl Default for BackgroundScaling {
+  /// Creates default scaling (shrink only, Lanczos4)
+  fn default() -> Self {
+    Self {
+      interpolation: None,
+      allow_upscale: false,
+    }
+  }
+}
+
+impl BackgroundScaling {
+  /// Interpolation of a resize that enlarges the image or not
+  pub fn interpolation(&self, enlarging: bool) -> Interp {
+    self.interpolation.unwrap_or(if enlarging {
+      Interp::Cubic
+    } else {
+      Interp::Lanczos4
+    })
+  }
+
+  /// Scale factor fitting a `width` x `height` image within `max_dim`
+  pub fn fit(&self, width: f64, height: f64, max_dim: i32) -> f64 {
+    let scale = max_dim as f64 / width.max(height);
+    if self.allow_upscale {
+      scale
+    } else {
+      scale.min(1.0)
+    }
+  }
+}
+
+/// Where a seeded style takes its seed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StyleSeed {
+  /// This number, e.g. one printed in an earlier render's metadata
+  Fixed(u64),
+  /// The activity's start time, so each run gets its own look and a
+  /// re-render of the same run the same one
+  FromActivity,
+}
+
+/// Brightness of the background a seeded palette must stand out from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundTone {
+  /// Night maps and dark photos; the palette is bright
+  Dark,
+  /// Light maps; the palette is deep
+  Light,
+}
+
+impl Default for BackgroundTone {
+  /// Creates default tone (dark)
+  fn default() -> Self {
+    BackgroundTone::Dark
+  }
+}
+
+/// "Surprise me" colors: a palette derived from a seed replaces the route,
+/// marker and lap bar colors of the config (whatever preset they came
+/// from) and tints the background
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct SeededStyle {
+  pub seed: StyleSeed,
+  #[serde(default)]
+  pub background: BackgroundTone,
+}
+
+impl SeededStyle {
+  /// Creates a style of a fixed seed over a dark background
+  pub fn fixed(seed: u64) -> Self {
+    Self {
+      seed: StyleSeed::Fixed(seed),
+      background: BackgroundTone::default(),
+    }
+  }
+
+  /// Whether the seed comes from the activity's start time
+  pub fn needs_start_time(&self) -> bool {
+    self.seed == StyleSeed::FromActivity
+  }
+}
+
+/// Soft shadow drawn under the route line
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+  /// Shadow offset from the line (pixels, x right and y down)
+  pub offset: (i32, i32),
+  /// Gaussian blur sigma (pixels; 0 = hard shadow)
+  pub blur_sigma: f64,
+  /// Shadow color
+  pub color: Color,
+  /// Shadow opacity (0.0-1.0)
+  pub opacity: f64,
+}
+
+impl Default for ShadowConfig {
+  /// Creates default shadow (soft black, slightly below the line)
+  fn default() -> Self {
+    Self {
+      offset: (3, 6),
+      blur_sigma: 4.0,
+      color: Color::Black,
+      opacity: 0.6,
+    }
+  }
+}
+
+/// Arrowheads along the route showing the direction of travel
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArrowConfig {
+  /// Distance between arrows along the route (meters)
+  pub every_m: f64,
+  /// Arrow length (pixels)
+  pub size_px: i32,
+  /// Arrow color
+  pub color: Color,
+}
+
+impl Default for ArrowConfig {
+  /// Creates default arrows (white, 14 px, every kilometer)
+  fn default() -> Self {
+    Self {
+      every_m: 1000.0,
+      size_px: 14,
+      color: Color::White,
+    }
+  }
+}
+
+/// The steepest climb drawn over the route in its own color, labeled with
+/// its average grade
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClimbHighlightConfig {
+  /// Shortest stretch (meters) the average grade is taken over
+  pub window_m: f64,
+  /// Color of the climb's stretch of route
+  pub color: Color,
+  /// Whether to label the climb with its grade, e.g. "Max grade 11%"
+  pub show_label: bool,
+}
+
+impl Default for ClimbHighlightConfig {
+  /// Creates default highlight (steepest 200 m, yellow, labeled)
+  fn default() -> Self {
+    Self {
+      window_m: 200.0,
+      color: Color::Yellow,
+      show_label: true,
+    }
+  }
+}
+
+/// What is done to a cumulative distance series that goes backwards, as
+/// after a device reboot resets it mid-activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceRepair {
+  /// Distances are used as recorded
+  Off,
+  /// Each drop is offset away, so the distance after it carries on from
+  /// the value before
+  Monotonicize,
+}
+
+impl Default for DistanceRepair {
+  /// Creates default repair (monotonicize)
+  fn default() -> Self {
+    DistanceRepair::Monotonicize
+  }
+}
+
+/// Segments of the route whose implied speed is too fast to be real,
+/// drawn apart from the rest so GPS glitches show on the image
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GlitchStyle {
+  /// Segments implying a faster speed (meters per second) are flagged
+  pub max_speed_mps: f64,
+  /// Color of flagged segments (BGRA)
+  pub color: [f64; 4],
+  /// Whether flagged segments are dashed instead of solid
+  pub dashed: bool,
+}
+
+impl Default for GlitchStyle {
+  /// Creates default style (over 12 m/s, dashed gray)
+  fn default() -> Self {
+    Self {
+      max_speed_mps: 12.0,
+      color: [128.0, 128.0, 128.0, 0.0],
+      dashed: true,
+    }
+  }
+}
+
+/// Rejection of GPS spikes: a position reached from the last kept one
+/// faster than `max_speed_mps` is replaced by a position between its
+/// neighbors, so a single bad record draws no spike across the map
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpikeFilter {
+  /// Positions implying a faster speed (meters per second) are replaced
+  pub max_speed_mps: f64,
+}
+
+impl Default for SpikeFilter {
+  /// Creates default filter (over 12 m/s, fast for running)
+  fn default() -> Self {
+    Self {
+      max_speed_mps: 12.0,
+    }
+  }
+}
+
+/// Line chart of cadence against distance. Videos draw the whole chart
+/// and move a cursor along it at the current distance.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CadenceChartConfig {
+  /// Top-left corner as percentage of width and height (0.0-1.0)
+  pub position: (f64, f64),
+  /// Chart size (width, height) in pixels
+  pub size: (i32, i32),
+  /// Line color
+  pub color: Color,
+  /// Dashed reference line at this cadence, in the unit shown for the
+  /// sport (steps or revolutions per minute); None = no line
+  pub target_line: Option<u8>,
+}
+
+impl Default for CadenceChartConfig {
+  /// Creates default chart (yellow, 320x120, bottom left, no target)
+  fn default() -> Self {
+    Self {
+      position: (0.05, 0.7),
+      size: (320, 120),
+      color: Color::Yellow,
+      target_line: None,
+    }
+  }
+}
+
+/// How the drawn route line is smoothed
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingMethod {
+  /// Corner cutting: rounds corners off without passing through the
+  /// records; each iteration doubles the points (at most 5 iterations)
+  Chaikin { iterations: u32 },
+  /// Curve through every record, with `samples_per_segment` points per
+  /// segment (at most 32)
+  CatmullRom { samples_per_segment: u32 },
+}
+
+/// Smoothing of the drawn route line. Only the drawing is smoothed; stats,
+/// laps and arrows still use the raw GPS points.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct SmoothingConfig {
+  pub method: SmoothingMethod,
+}
+
+impl Default for SmoothingConfig {
+  /// Creates default smoothing (two Chaikin iterations)
+  fn default() -> Self {
+    Self {
+      method: SmoothingMethod::Chaikin { iterations: 2 },
+    }
+  }
+}
+
+/// How GPS positions are smoothed before they are projected
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpsSmoothingMethod {
+  /// Average of the `window` records centered on each one (at most 51);
+  /// the window narrows toward the ends of a stretch
+  MovingAverage { window: usize },
+  /// Kalman filter holding each position steady: the larger
+  /// `measurement_noise_m` is against `process_noise_m`, the smoother
+  Kalman {
+    process_noise_m: f64,
+    measurement_noise_m: f64,
+  },
+}
+
+/// Smoothing of the GPS positions the route is drawn from. Each record
+/// keeps its place, so paces and distances stay in step; stats still use
+/// the raw positions.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct GpsSmoothingConfig {
+  pub method: GpsSmoothingMethod,
+}
+
+impl Default for GpsSmoothingConfig {
+  /// Creates default smoothing (moving average over 5 records)
+  fn default() -> Self {
+    Self {
+      method: GpsSmoothingMethod::MovingAverage { window: 5 },
+    }
+  }
+}
+
+/// Shape of the current-position marker
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerStyle {
+  /// Filled dot
+  Dot,
+  /// Outlined circle
+  Ring { thickness: i32 },
+  /// Image centered on the position, `scale` times its own size; PNG
+  /// transparency is kept
+  Sprite { path: String, scale: f64 },
+  /// Filled dot whose radius swings from `max_r` down to `min_r` and back
+  /// every `period_frames` frames
+  PulsingDot {
+    period_frames: u32,
+    min_r: i32,
+    max_r: i32,
+  },
+}
+
+/// Marker drawn at the current position (video) or the finish (image)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PositionMarker {
+  pub style: MarkerStyle,
+  /// Marker color (None = `colors.current_position`); sprites keep their
+  /// own colors
+  pub color: Option<Color>,
+}
+
+impl Default for PositionMarker {
+  /// Creates default marker (the theme's filled dot)
+  fn default() -> Self {
+    Self {
+      style: MarkerStyle::Dot,
+      color: None,
+    }
+  }
+}
+
+/// How pace seconds are rounded to whole seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaceRounding {
+  /// Nearest second, halves rounded up (5:59.5 shows as 6:00)
+  RoundHalfUp,
+  /// Whole seconds only (5:59.9 shows as 5:59)
+  Floor,
+}
+
+impl Default for PaceRounding {
+  /// Creates default rounding (half up)
+  fn default() -> Self {
+    PaceRounding::RoundHalfUp
+  }
+}
+
+/// Precision of the numbers shown in overlays, panels and labels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NumberFormat {
+  /// Decimals of distances (km or mi)
+  pub distance_decimals: usize,
+  /// Decimals of lap stride lengths (m)
+  pub stride_decimals: usize,
+  /// Rounding of pace seconds
+  pub pace_rounding: PaceRounding,
+  /// Decimals of latitudes and longitudes (degrees)
+  pub coordinate_decimals: usize,
+}
+
+impl Default for NumberFormat {
+  /// Creates default format (2 decimals, pace rounded half up,
+  /// coordinates to 4 decimals)
+  fn default() -> Self {
+    Self {
+      distance_decimals: 2,
+      stride_decimals: 2,
+      pace_rounding: PaceRounding::RoundHalfUp,
+      coordinate_decimals: 4,
+    }
+  }
+}
+
+/// Direction of the legend's color bar
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegendOrientation {
+  /// Low value on the left, high on the right
+  Horizontal,
+  /// High value on top, low at the bottom
+  Vertical,
+}
+
+/// Color legend for gradient route coloring, drawn once with the static
+/// overlays. Nothing is drawn while the route color is solid.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LegendConfig {
+  /// Top-left corner as percentage of width and height (0.0-1.0)
+  pub position: (f64, f64),
+  pub orientation: LegendOrientation,
+  /// Length of the color bar (pixels)
+  pub width_px: i32,
+  /// Label template; `{value}` is replaced by the rounded range end
+  pub label_format: String,
+}
+
+impl Default for LegendConfig {
+  /// Creates default legend (horizontal, top left, meters)
+  fn default() -> Self {
+    Self {
+      position: (0.05, 0.05),
+      orientation: LegendOrientation::Horizontal,
+      width_px: 240,
+      label_format: "{value} m".to_string(),
+    }
+  }
+}
+
+impl LegendConfig {
+  /// Label text for one end of the range
+  pub fn label(&self, value: f64) -> String {
+    self
+      .label_format
+      .replace("{value}", &format!("{:.0}", value))
+  }
+}
+
+/// Point given as fractions (x, y) of the frame width and height, each
+/// 0.0-1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PercentPos(f64, f64);
+
+impl PercentPos {
+  /// Creates a percent position; fails outside 0.0-1.0
+  pub fn new(x: f64, y: f64) -> anyhow::Result<Self> {
+    check_percent(x, y).map_err(anyhow::Error::msg)?;
+    Ok(Self(x, y))
+  }
+
+  /// Creates a percent position, moving each coordinate into 0.0-1.0
+  pub fn clamped(x: f64, y: f64) -> Self {
+    Self(x.clamp(0.0, 1.0), y.clamp(0.0, 1.0))
+  }
+
+  pub fn x(&self) -> f64 {
+    self.0
+  }
+
+  pub fn y(&self) -> f64 {
+    self.1
+  }
+}
+
+impl<'de> Deserialize<'de> for PercentPos {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let (x, y) = <(f64, f64)>::deserialize(deserializer)?;
+    check_percent(x, y).map_err(serde::de::Error::custom)?;
+    Ok(Self(x, y))
+  }
+}
+
+fn check_percent(x: f64, y: f64) -> Result<(), String> {
+  if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+    return Err(format!(
+      "Percent position ({}, {}) is outside 0.0-1.0",
+      x, y
+    ));
+  }
+  Ok(())
+}
+
+/// Point given in pixels (x, y) from the top left of the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PixelPos(pub i32, pub i32);
+
+/// Where an overlay sits: a fraction of the frame, or fixed pixels.
+///
+/// JSON: `{"percent": [0.5, 0.1]}` or `{"pixels": [640, 80]}`; a bare
+/// `[0.5, 0.1]` is read as a percent position.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+  Percent(PercentPos),
+  Pixels(PixelPos),
+}
+
+impl Position {
+  /// Pixel coordinates of the position in a frame of `frame_size`
+  /// (width, height)
+  pub fn resolve(&self, frame_size: (i32, i32)) -> (i32, i32) {
+    match *self {
+      Position::Percent(PercentPos(x, y)) => (
+        (x * frame_size.0 as f64) as i32,
+        (y * frame_size.1 as f64) as i32,
+      ),
+      Position::Pixels(PixelPos(x, y)) => (x, y),
+    }
+  }
+}
+
+impl From<PercentPos> for Position {
+  fn from(position: PercentPos) -> Self {
+    Position::Percent(position)
+  }
+}
+
+impl From<PixelPos> for Position {
+  fn from(position: PixelPos) -> Self {
+    Position::Pixels(position)
+  }
+}
+
+impl From<(f64, f64)> for Position {
+  /// Percent position, clamped into 0.0-1.0
+  fn from((x, y): (f64, f64)) -> Self {
+    Position::Percent(PercentPos::clamped(x, y))
+  }
+}
+
+impl From<(i32, i32)> for Position {
+  /// Pixel position
+  fn from((x, y): (i32, i32)) -> Self {
+    Position::Pixels(PixelPos(x, y))
+  }
+}
+
+/// Serde form of `Position`, checked before it is built
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PositionRepr {
+  Tagged(TaggedPosition),
+  Bare(f64, f64),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TaggedPosition {
+  Percent(f64, f64),
+  Pixels(i32, i32),
+}
+
+impl<'de> Deserialize<'de> for Position {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let (x, y) = match PositionRepr::deserialize(deserializer)? {
+      PositionRepr::Tagged(TaggedPosition::Pixels(x, y)) => {
+        return Ok(Position::Pixels(PixelPos(x, y)));
+      }
+      PositionRepr::Tagged(TaggedPosition::Percent(x, y))
+      | PositionRepr::Bare(x, y) => (x, y),
+    };
+    check_percent(x, y).map_err(serde::de::Error::custom)?;
+    Ok(Position::Percent(PercentPos(x, y)))
+  }
+}
+
+/// Frame corner an overlay is anchored to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+/// Credit line for the background map, drawn over every other overlay
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttributionConfig {
+  /// Credit to draw, e.g. "© OpenStreetMap contributors" for a map
+  /// screenshot (None = no credit)
+  pub text_override: Option<String>,
+  /// Corner of the safe area the credit is drawn in
+  pub position: Corner,
+  pub font_scale: f64,
+  /// Leaves the credit out even when text is set
+  pub suppress: bool,
+}
+
+impl Default for AttributionConfig {
+  /// Creates default credit (no text, small, bottom right)
+  fn default() -> Self {
+    Self {
+      text_override: None,
+      position: Corner::BottomRight,
+      font_scale: 0.4,
+      suppress: false,
+    }
+  }
+}
+
+impl AttributionConfig {
+  /// Credit to draw, if any, in characters the Hershey fonts can draw:
+  /// "©" becomes "(c)" and other non-ASCII characters "?"
+  pub fn drawn_text(&self) -> Option<String> {
+    if self.suppress {
+      return None;
+    }
+    let text = self.text_override.as_deref()?.trim();
+    if text.is_empty() {
+      return None;
+    }
+    Some(
+      text
+        .chars()
+        .map(|c| match c {
+          '©' => "(c)".to_string(),
+          c if c.is_ascii() => c.to_string(),
+          _ => "?".to_string(),
+        })
+        .collect(),
+    )
+  }
+}
+
+/// Time zone the activity start time is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateTimezone {
+  Utc,
+  /// Fixed offset from UTC (hours, e.g. 7.0 or -3.5)
+  FixedOffset(f64),
+  /// Time zone of the machine doing the rendering
+  Local,
+}
+
+/// Activity start date stamp
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DateStampConfig {
+  /// chrono `strftime` format; month and day names are English
+  #[serde(deserialize_with = "date_format")]
+  pub format: String,
+  pub timezone: DateTimezone,
+  /// Corner of the safe area the stamp is drawn in
+  pub corner: Corner,
+  pub font_scale: f64,
+  pub thickness: i32,
+  pub font: Font,
+  pub color: Color,
+}
+
+impl Default for DateStampConfig {
+  /// Creates default stamp ("12 May 2024, 07:32" UTC, bottom right)
+  fn default() -> Self {
+    Self {
+      format: "%-d %b %Y, %H:%M".to_string(),
+      timezone: DateTimezone::Utc,
+      corner: Corner::BottomRight,
+      font_scale: 0.8,
+      thickness: 2,
+      font: Font::Simplex,
+      color: Color::White,
+    }
+  }
+}
+
+impl DateStampConfig {
+  /// Fails if `format` has an invalid or unsupported specifier
+  pub fn check_format(&self) -> anyhow::Result<()> {
+    check_date_format(&self.format).map_err(anyhow::Error::msg)
+  }
+
+  /// Formats a Unix timestamp in the configured time zone; None if the
+  /// offset or timestamp is out of range or the format is invalid
+  pub fn format(&self, unix_seconds: i64) -> Option<String> {
+    let utc = DateTime::from_timestamp(unix_seconds, 0)?;
+    let mut text = String::new();
+    let written = match self.timezone {
+      DateTimezone::Utc => write!(text, "{}", utc.format(&self.format)),
+      DateTimezone::FixedOffset(hours) => {
+        let offset = FixedOffset::east_opt((hours * 3600.0).round() as i32)?;
+        let local = utc.with_timezone(&offset);
+        write!(text, "{}", local.format(&self.format))
+      }
+      DateTimezone::Local => {
+        let local = utc.with_timezone(&Local);
+        write!(text, "{}", local.format(&self.format))
+      }
+    };
+    written.ok().map(|_| text)
+  }
+}
+
+/// Rejects formats chrono cannot render
+fn check_date_format(format: &str) -> Result<(), String> {
+  if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+    return Err(format!(
+      "Invalid date format \"{}\"",
+      format
+    ));
+  }
+  Ok(())
+}
+
+fn date_format<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<String, D::Error> {
+  let format = String::deserialize(deserializer)?;
+  check_date_format(&format).map_err(serde::de::Error::custom)?;
+  Ok(format)
+}
+
+/// Which activity time is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBasis {
+  /// Time spent moving; detected pauses are left out
+  Moving,
+  /// Wall-clock time from start to finish
+  Elapsed,
+}
+
+impl Default for TimeBasis {
+  /// Creates default basis (elapsed)
+  fn default() -> Self {
+    TimeBasis::Elapsed
+  }
+}
+
+impl TimeBasis {
+  /// Uppercase label for stat panels
+  pub fn label(&self) -> &'static str {
+    match self {
+      TimeBasis::Moving => "MOVING TIME",
+      TimeBasis::Elapsed => "ELAPSED TIME",
+    }
+  }
+}
+
+/// Format of the activity file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFormat {
+  /// From the file extension: `.gpx` is GPX, `.tcx` TCX, `.kml` KML,
+  /// anything else FIT
+  Auto,
+  Fit,
+  /// GPX track, e.g. a Strava export
+  Gpx,
+  /// Training Center XML with laps, e.g. a Garmin Connect export
+  Tcx,
+  /// Route planned in Google Earth or My Maps; its LineStrings give the
+  /// route, without times or laps
+  Kml,
+}
+
+impl InputFormat {
+  /// Resolves `Auto` from the extension of `file_path`; other formats are
+  /// returned unchanged
+  pub fn resolve(self, file_path: &str) -> Self {
+    if self != InputFormat::Auto {
+      return self;
+    }
+    let extension = Path::new(file_path)
+      .extension()
+      .and_then(|e| e.to_str())
+      .unwrap_or_default();
+    if extension.eq_ignore_ascii_case("gpx") {
+      InputFormat::Gpx
+    } else if extension.eq_ignore_ascii_case("tcx") {
+      InputFormat::Tcx
+    } else if extension.eq_ignore_ascii_case("kml") {
+      InputFormat::Kml
+    } else {
+      InputFormat::Fit
+    }
+  }
+}
+
+impl Default for InputFormat {
+  /// Creates default format (auto)
+  fn default() -> Self {
+    InputFormat::Auto
+  }
+}
+
+/// Which LineStrings of a KML file make up the route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KmlLines {
+  /// Every LineString in document order; the line breaks between them
+  Concatenate,
+  /// The LineString with the longest ground distance
+  Longest,
+}
+
+impl Default for KmlLines {
+  /// Creates default selection (concatenate)
+  fn default() -> Self {
+    KmlLines::Concatenate
+  }
+}
+
 /// File paths configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FileConfig {
-  /// Path to FIT file
+  /// Path to the activity file (FIT, GPX, TCX or KML)
   pub fit_file: String,
   /// Path to background image
   pub background_image: String,
   /// Output video file path
   pub output_file: String,
+  /// Format of `fit_file`
+  #[serde(default)]
+  pub input_format: InputFormat,
+  /// LineStrings read from a KML `fit_file`
+  #[serde(default)]
+  pub kml_lines: KmlLines,
+  /// Activity file held in memory, read instead of `fit_file` (None =
+  /// read `fit_file`). Not serialized.
+  #[serde(skip)]
+  pub fit_bytes: Option<Arc<[u8]>>,
 }
 
 impl FileConfig {
@@ -133,6 +1193,9 @@ impl FileConfig {
       fit_file,
       background_image,
       output_file,
+      input_format: InputFormat::Auto,
+      kml_lines: KmlLines::default(),
+      fit_bytes: None,
     }
   }
 }
@@ -144,12 +1207,16 @@ impl Default for FileConfig {
       fit_file: "source/example.fit".to_string(),
       background_image: "source/example.jpg".to_string(),
       output_file: "outputs/output.mp4".to_string(),
+      input_format: InputFormat::Auto,
+      kml_lines: KmlLines::default(),
+      fit_bytes: None,
     }
   }
 }
 
 /// Color options for lap data text
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Color {
   Black,
   White,
@@ -190,7 +1257,8 @@ impl Color {
 }
 
 /// Font family options for text rendering
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Font {
   /// Normal size sans-serif font
   Simplex,
@@ -228,3 +1296,328 @@ impl Font {
     }
   }
 }
+
+/// Unit system for displayed distances, paces and elevation
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+  /// Kilometers, min/km and meters
+  Metric,
+  /// Miles, min/mi and feet
+  Imperial,
+}
+
+impl Default for Units {
+  /// Creates default units (metric)
+  fn default() -> Self {
+    Units::Metric
+  }
+}
+
+impl Units {
+  /// Converts meters to kilometers or miles
+  pub fn distance(&self, meters: f64) -> f64 {
+    match self {
+      Units::Metric => meters / 1000.0,
+      Units::Imperial => meters / 1609.344,
+    }
+  }
+
+  /// Converts meters to meters or feet
+  pub fn elevation(&self, meters: f64) -> f64 {
+    match self {
+      Units::Metric => meters,
+      Units::Imperial => meters * 3.28084,
+    }
+  }
+
+  /// Distance unit label
+  pub fn distance_label(&self) -> &'static str {
+    match self {
+      Units::Metric => "km",
+      Units::Imperial => "mi",
+    }
+  }
+
+  /// Pace unit label
+  pub fn pace_label(&self) -> &'static str {
+    match self {
+      Units::Metric => "/km",
+      Units::Imperial => "/mi",
+    }
+  }
+
+  /// Elevation unit label
+  pub fn elevation_label(&self) -> &'static str {
+    match self {
+      Units::Metric => "m",
+      Units::Imperial => "ft",
+    }
+  }
+}
+
+/// Width (px) of the lap panel's power column; the columns after it move
+/// right by this much when it is shown
+pub const POWER_COLUMN_WIDTH: i32 = 50;
+
+/// Sport of the activity, which picks the stats and labels shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SportProfile {
+  /// Pace (min/km) and stride length
+  Running,
+  /// Speed (km/h) and cadence
+  Cycling,
+  /// Like running, with a wider pace cap
+  Walking,
+  /// Picked from the sport recorded in the FIT file
+  Auto,
+}
+
+impl Default for SportProfile {
+  /// Creates default sport profile (auto)
+  fn default() -> Self {
+    SportProfile::Auto
+  }
+}
+
+impl SportProfile {
+  /// Profile for a FIT `sport` name such as `cycling`
+  pub fn from_fit_sport(name: &str) -> Option<Self> {
+    match name {
+      "running" => Some(SportProfile::Running),
+      "cycling" | "e_biking" => Some(SportProfile::Cycling),
+      "walking" | "hiking" => Some(SportProfile::Walking),
+      _ => None,
+    }
+  }
+
+  /// Resolves `Auto` from the FIT sport name, falling back to running;
+  /// other profiles are returned unchanged
+  pub fn resolve(self, fit_sport: Option<&str>) -> Self {
+    match self {
+      SportProfile::Auto => fit_sport
+        .and_then(SportProfile::from_fit_sport)
+        .unwrap_or(SportProfile::Running),
+      sport => sport,
+    }
+  }
+
+  /// Whether speed is shown instead of pace
+  pub fn shows_speed(&self) -> bool {
+    *self == SportProfile::Cycling
+  }
+
+  /// Slowest pace shown, in seconds per km; slower paces are capped
+  pub fn pace_cap_secs(&self) -> f32 {
+    match self {
+      SportProfile::Walking => 1800.0,
+      _ => 900.0,
+    }
+  }
+
+  /// Lap panel header labels and their x offsets; with `show_power` a
+  /// PWR column follows HR and the detail column moves right to make room
+  pub fn lap_labels(&self, show_power: bool) -> Vec<(&'static str, i32)> {
+    let (stat, detail) = if self.shows_speed() {
+      ("KM   KM/H", "CAD")
+    } else {
+      ("KM   PACE", "LENGTH")
+    };
+    let mut labels = vec![(stat, -20), ("BAR", 150), ("HR", 285)];
+    if show_power {
+      labels.push(("PWR", 335));
+    }
+    let shift = if show_power { POWER_COLUMN_WIDTH } else { 0 };
+    labels.push((detail, 320 + shift));
+    labels
+  }
+}
+
+/// How an extra FIT field value is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraFieldKind {
+  /// Any integer or float value, read as f64
+  Numeric,
+}
+
+/// Record field captured beyond the well-known ones, such as running power
+/// from a developer field
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExtraField {
+  /// Field name, matched case-insensitively and ignoring developer data
+  /// index prefixes (`0_power`, `dev_0_power`)
+  pub match_name: String,
+  /// How the value is read
+  pub kind: ExtraFieldKind,
+  /// Display label, also the key in `RouteData::extra`
+  pub label: String,
+  /// Unit shown after the value (e.g. "W")
+  #[serde(default)]
+  pub unit: String,
+}
+
+impl ExtraField {
+  /// Creates a numeric extra field
+  pub fn numeric(match_name: &str, label: &str, unit: &str) -> Self {
+    Self {
+      match_name: match_name.to_string(),
+      kind: ExtraFieldKind::Numeric,
+      label: label.to_string(),
+      unit: unit.to_string(),
+    }
+  }
+
+  /// Whether a FIT field name refers to this field
+  pub fn matches(&self, field_name: &str) -> bool {
+    normalize_field_name(field_name) == normalize_field_name(&self.match_name)
+  }
+
+  /// Formats a value with the label and unit, e.g. "Pwr 287 W"
+  pub fn format(&self, value: f64) -> String {
+    format!(
+      "{} {:.0} {}",
+      self.label, value, self.unit
+    )
+    .trim_end()
+    .to_string()
+  }
+}
+
+/// Hard limits on input and output size, so untrusted requests cannot
+/// exhaust the host.
+///
+/// Checked by the generators against what is actually drawn and encoded.
+/// Not part of the config patches, so server users cannot raise them.
+///
+/// # Example
+/// ```
+/// use runarium::configs::Limits;
+///
+/// let limits = Limits::default();
+/// assert!(limits.check_output_size(1080, 1920).is_ok());
+/// assert!(limits.check_output_size(16000, 16000).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Limits {
+  /// Most route records drawn
+  pub max_records: usize,
+  /// Most output pixels (width x height)
+  pub max_output_pixels: u64,
+  /// Most video frames, lap pause holds included
+  pub max_frames: usize,
+  /// Longest video in seconds
+  pub max_duration_seconds: f64,
+}
+
+impl Limits {
+  /// Checks the number of route records drawn
+  pub fn check_records(&self, records: usize) -> Result<(), RunariumError> {
+    check(
+      LimitKind::Records,
+      records as u64,
+      self.max_records as u64,
+    )
+  }
+
+  /// Checks the output size in pixels
+  pub fn check_output_size(
+    &self,
+    width: i32,
+    height: i32,
+  ) -> Result<(), RunariumError> {
+    let pixels = width.max(0) as u64 * height.max(0) as u64;
+    check(
+      LimitKind::OutputPixels,
+      pixels,
+      self.max_output_pixels,
+    )
+  }
+
+  /// Checks the size of a background image before it is decoded. Large
+  /// backgrounds are decoded at down to 1/8 scale per side, so up to
+  /// `BACKGROUND_REDUCTION²` times `max_output_pixels` are allowed.
+  pub fn check_background_size(
+    &self,
+    width: u32,
+    height: u32,
+  ) -> Result<(), RunariumError> {
+    check(
+      LimitKind::BackgroundPixels,
+      width as u64 * height as u64,
+      self
+        .max_output_pixels
+        .saturating_mul(BACKGROUND_REDUCTION * BACKGROUND_REDUCTION),
+    )
+  }
+
+  /// Checks the number of video frames
+  pub fn check_frames(&self, frames: usize) -> Result<(), RunariumError> {
+    check(
+      LimitKind::Frames,
+      frames as u64,
+      self.max_frames as u64,
+    )
+  }
+
+  /// Checks the video length in seconds (reported rounded up)
+  pub fn check_duration(&self, seconds: f64) -> Result<(), RunariumError> {
+    if seconds <= self.max_duration_seconds {
+      return Ok(());
+    }
+    Err(RunariumError::LimitExceeded {
+      which: LimitKind::DurationSeconds,
+      actual: seconds.ceil() as u64,
+      limit: self.max_duration_seconds as u64,
+    })
+  }
+}
+
+/// Largest factor per side a background is reduced by while decoding
+pub const BACKGROUND_REDUCTION: u64 = 8;
+
+impl Default for Limits {
+  /// Creates generous limits: a day-long activity at one record per second
+  /// and 4K outputs pass
+  fn default() -> Self {
+    Self {
+      max_records: 200_000,
+      max_output_pixels: 4096 * 4096,
+      max_frames: 250_000,
+      max_duration_seconds: 600.0,
+    }
+  }
+}
+
+fn check(
+  which: LimitKind,
+  actual: u64,
+  limit: u64,
+) -> Result<(), RunariumError> {
+  if actual <= limit {
+    return Ok(());
+  }
+  Err(RunariumError::LimitExceeded {
+    which,
+    actual,
+    limit,
+  })
+}
+
+/// Lowercases a field name and strips a developer data index prefix
+fn normalize_field_name(name: &str) -> String {
+  let name = name.trim().to_lowercase();
+  let name = name
+    .strip_prefix("developer_")
+    .or_else(|| name.strip_prefix("dev_"))
+    .unwrap_or(&name);
+  let digits =
+    name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+  if digits > 0 {
+    if let Some(rest) = name[digits..].strip_prefix(['_', ':', '.']) {
+      return rest.to_string();
+    }
+  }
+  name.to_string()
+}
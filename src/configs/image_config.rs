@@ -1,10 +1,23 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use serde::{Deserialize, Deserializer, Serialize};
+
 use super::{
-  config::{FileConfig, RouteColor, RouteScale},
-  video_config::LapDataConfig,
+  config::{
+    ArrowConfig, AttributionConfig, BackgroundFx, BackgroundLayer,
+    BackgroundScaling, CadenceChartConfig, ClimbHighlightConfig, Color, Corner,
+    DateStampConfig, DistanceRepair, FileConfig, Font, GlitchStyle,
+    GpsSmoothingConfig, LegendConfig, Limits, NumberFormat, PositionMarker,
+    RouteColor, RouteRenderStyle, RouteScale, RouteSegmentColoring,
+    SeededStyle, ShadowConfig, SmoothingConfig, SpikeFilter, SportProfile,
+    TimeBasis, Units,
+  },
+  video_config::{LapDataConfig, OutputSink, SafeArea},
 };
 
 /// Configuration for route image generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RouteImageConfig {
   /// Route scale and positioning
   pub route_scale: RouteScale,
@@ -18,6 +31,91 @@ pub struct RouteImageConfig {
   pub lap_data: Option<LapDataConfig>,
   /// Whether to show lap data panel
   pub show_lap_data: bool,
+  /// Write a JSON pixel map of the drawn route here (optional)
+  pub pixel_map_export: Option<PathBuf>,
+  /// Export every Nth drawn point to the pixel map
+  pub pixel_map_stride: usize,
+  /// Sport-specific lap stats and labels (Auto = from the FIT file)
+  pub sport: SportProfile,
+  /// Pin encoder settings so identical inputs give byte-identical images
+  pub deterministic: bool,
+  /// Write the config next to the output as `<output>.config.json`, and
+  /// into a PNG output's metadata
+  pub embed_config: bool,
+  /// Hard limits on records and output size
+  pub limits: Limits,
+  /// What is done to record distances that go backwards
+  pub distance_repair: DistanceRepair,
+  /// Replacement of GPS positions that jump implausibly far (None = kept
+  /// as recorded)
+  pub spike_filter: Option<SpikeFilter>,
+  /// Whether the route line runs on across timer pauses and gaps in the
+  /// records instead of breaking there
+  pub connect_pauses: bool,
+  /// How route segments are colored
+  pub segment_coloring: RouteSegmentColoring,
+  /// Shadow under the route line (None = no shadow)
+  pub route_shadow: Option<ShadowConfig>,
+  /// Smoothing of the drawn route line (None = straight segments)
+  pub line_smoothing: Option<SmoothingConfig>,
+  /// Smoothing of the GPS positions before they are projected (None =
+  /// as recorded)
+  pub gps_smoothing: Option<GpsSmoothingConfig>,
+  /// Marker at the finish (None = no marker)
+  pub finish_marker: Option<PositionMarker>,
+  /// Route drawn as a line, dots or both
+  pub render_style: RouteRenderStyle,
+  /// Color legend for gradient coloring (None = no legend)
+  pub legend: Option<LegendConfig>,
+  /// Whether to stamp the activity start date
+  pub show_date: bool,
+  /// Date stamp format, time zone and placement
+  pub date_stamp: DateStampConfig,
+  /// Whether to draw a bottom bar with the session totals ("10.02 km ·
+  /// 52:31 · 531 kcal · 156 bpm")
+  pub show_summary: bool,
+  /// Whether to list the loops found in the GPS track with their times
+  pub show_detected_loops: bool,
+  /// How loops are found and where their list is drawn
+  pub loop_detection: LoopDetectionConfig,
+  /// Whether to highlight the steepest climb on the route
+  pub highlight_steepest_climb: bool,
+  /// How the steepest climb is found and drawn
+  pub steepest_climb: ClimbHighlightConfig,
+  /// Segments implying an impossible speed drawn in their own style
+  /// (None = drawn like the rest of the route)
+  pub glitch_style: Option<GlitchStyle>,
+  /// Background map credit
+  pub attribution: AttributionConfig,
+  /// Background layers composited in order, the first at the bottom
+  /// (empty = `file_config.background_image` alone)
+  pub backgrounds: Vec<BackgroundLayer>,
+  /// Filters applied in order to the background (empty = as loaded)
+  pub background_fx: Vec<BackgroundFx>,
+  /// Interpolation and upscaling of background images
+  pub background_scaling: BackgroundScaling,
+  /// Colors derived from a seed, replacing `colors` (None = `colors`)
+  pub seeded_style: Option<SeededStyle>,
+  /// Image encoder and its compression or quality
+  pub output_options: ImageOutputOptions,
+  /// Arrows along the route showing the direction of travel (None = no
+  /// arrows)
+  pub direction_arrows: Option<ArrowConfig>,
+  /// Cadence chart (None = no chart)
+  pub cadence_chart: Option<CadenceChartConfig>,
+  /// Precision of strides and paces in the lap panel
+  pub number_format: NumberFormat,
+  /// Margins that overlays stay inside
+  pub safe_area: SafeArea,
+  /// Outline the safe area on the image, for tuning margins
+  pub debug_draw_safe_area: bool,
+  /// Draw on a transparent canvas of this width and height instead of the
+  /// background, written as a PNG with alpha (None = background image)
+  pub transparent_canvas: Option<(i32, i32)>,
+  /// Run after the image is written, e.g. to upload it (None = keep the
+  /// file only). Not serialized, so it never reaches a sidecar.
+  #[serde(skip)]
+  pub on_complete: Option<OutputSink>,
 }
 
 impl RouteImageConfig {
@@ -35,6 +133,43 @@ impl RouteImageConfig {
       line_thickness,
       lap_data: None,
       show_lap_data: false,
+      pixel_map_export: None,
+      pixel_map_stride: 10,
+      sport: SportProfile::default(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      connect_pauses: false,
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      finish_marker: None,
+      render_style: RouteRenderStyle::default(),
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      show_detected_loops: false,
+      loop_detection: LoopDetectionConfig::default(),
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      glitch_style: None,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      output_options: ImageOutputOptions::default(),
+      direction_arrows: None,
+      cadence_chart: None,
+      number_format: NumberFormat::default(),
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      transparent_canvas: None,
+      on_complete: None,
     }
   }
 
@@ -53,6 +188,43 @@ impl RouteImageConfig {
       line_thickness,
       lap_data: Some(lap_data),
       show_lap_data: true,
+      pixel_map_export: None,
+      pixel_map_stride: 10,
+      sport: SportProfile::default(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      connect_pauses: false,
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      finish_marker: None,
+      render_style: RouteRenderStyle::default(),
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      show_detected_loops: false,
+      loop_detection: LoopDetectionConfig::default(),
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      glitch_style: None,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      output_options: ImageOutputOptions::default(),
+      direction_arrows: None,
+      cadence_chart: None,
+      number_format: NumberFormat::default(),
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      transparent_canvas: None,
+      on_complete: None,
     }
   }
 
@@ -69,6 +241,530 @@ impl RouteImageConfig {
       line_thickness: 2,
       lap_data: None,
       show_lap_data: false,
+      pixel_map_export: None,
+      pixel_map_stride: 10,
+      sport: SportProfile::default(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      connect_pauses: false,
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      finish_marker: None,
+      render_style: RouteRenderStyle::default(),
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      show_detected_loops: false,
+      loop_detection: LoopDetectionConfig::default(),
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      glitch_style: None,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      output_options: ImageOutputOptions::default(),
+      direction_arrows: None,
+      cadence_chart: None,
+      number_format: NumberFormat::default(),
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      transparent_canvas: None,
+      on_complete: None,
+    }
+  }
+
+  /// Background layers to composite: `backgrounds`, or a single layer of
+  /// `file_config.background_image` when there are none
+  pub fn background_layers(&self) -> Vec<BackgroundLayer> {
+    if self.backgrounds.is_empty() {
+      vec![BackgroundLayer::image(&self.file_config.background_image)]
+    } else {
+      self.backgrounds.clone()
+    }
+  }
+
+  /// Config a PNG was rendered with, read from its embedded metadata, or
+  /// from the `.config.json` sidecar of any other output
+  pub fn from_image_metadata(path: &str) -> anyhow::Result<Self> {
+    Ok(crate::provenance::read_config(path)?.config)
+  }
+}
+
+/// Loops found in the GPS track, listed with their times
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoopDetectionConfig {
+  /// Distance (meters) covered before a return to the start closes a loop
+  pub min_loop_m: f64,
+  /// How close (meters) the route must come back to the loop's start
+  pub proximity_m: f64,
+  /// Corner of the safe area the list is drawn in
+  pub corner: Corner,
+  pub font_scale: f64,
+  pub color: Color,
+}
+
+impl Default for LoopDetectionConfig {
+  /// Creates default detection (200 m loops, 25 m reach, bottom left)
+  fn default() -> Self {
+    Self {
+      min_loop_m: 200.0,
+      proximity_m: 25.0,
+      corner: Corner::BottomLeft,
+      font_scale: 0.6,
+      color: Color::White,
+    }
+  }
+}
+
+/// Encoder of a written image and its settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+  /// Lossless; zlib `compression` level 0-9 (higher is smaller and slower)
+  Png { compression: i32 },
+  /// `quality` 0-100
+  Jpeg { quality: i32 },
+  /// `quality` 1-100; 100 is lossless
+  Webp { quality: i32 },
+}
+
+impl ImageFormat {
+  /// Display name of the format
+  pub fn name(&self) -> &'static str {
+    match self {
+      ImageFormat::Png { .. } => "PNG",
+      ImageFormat::Jpeg { .. } => "JPEG",
+      ImageFormat::Webp { .. } => "WebP",
+    }
+  }
+
+  /// File extensions OpenCV writes with this encoder
+  pub fn extensions(&self) -> &'static [&'static str] {
+    match self {
+      ImageFormat::Png { .. } => &["png"],
+      ImageFormat::Jpeg { .. } => &["jpg", "jpeg", "jpe"],
+      ImageFormat::Webp { .. } => &["webp"],
+    }
+  }
+
+  /// Fails if the compression level or quality is out of range
+  pub fn check(&self) -> Result<(), String> {
+    let (setting, value, range) = match *self {
+      ImageFormat::Png { compression } => ("compression", compression, 0..=9),
+      ImageFormat::Jpeg { quality } => ("quality", quality, 0..=100),
+      ImageFormat::Webp { quality } => ("quality", quality, 1..=100),
+    };
+    if !range.contains(&value) {
+      return Err(format!(
+        "{} {} {} is out of range ({}-{})",
+        self.name(),
+        setting,
+        value,
+        range.start(),
+        range.end()
+      ));
+    }
+    Ok(())
+  }
+}
+
+/// How a route image is encoded
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize,
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageOutputOptions {
+  /// Encoder settings (None = the encoder for the output file extension
+  /// with OpenCV's default settings)
+  #[serde(deserialize_with = "image_format")]
+  pub format: Option<ImageFormat>,
+}
+
+impl ImageOutputOptions {
+  /// Format to write `output_file` with; fails if its settings are out of
+  /// range or it does not match the file extension
+  pub fn resolve(
+    &self,
+    output_file: &str,
+  ) -> anyhow::Result<Option<ImageFormat>> {
+    let Some(format) = self.format else {
+      return Ok(None);
+    };
+    format.check().map_err(anyhow::Error::msg)?;
+
+    let extension = Path::new(output_file)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .map(|ext| ext.to_ascii_lowercase())
+      .unwrap_or_default();
+    if !format.extensions().contains(&extension.as_str()) {
+      bail!(
+        "Output file {} does not match the {} format (expected .{})",
+        output_file,
+        format.name(),
+        format.extensions()[0]
+      );
     }
+    Ok(Some(format))
+  }
+}
+
+fn image_format<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<ImageFormat>, D::Error> {
+  let format = Option::<ImageFormat>::deserialize(deserializer)?;
+  if let Some(format) = &format {
+    format.check().map_err(serde::de::Error::custom)?;
+  }
+  Ok(format)
+}
+
+/// How an activity of a multi-route image gets its color
+#[derive(Debug, Clone, Copy)]
+pub enum ColorAssignment {
+  /// This color, whatever the palette
+  Fixed(Color),
+  /// Stable color hashed from the file name and activity start time, so
+  /// re-renders keep the same colors
+  Auto,
+}
+
+/// One activity of a multi-route image
+#[derive(Debug, Clone)]
+pub struct RouteEntry {
+  pub fit_file: String,
+  /// Route color (None = next of `route_colors`)
+  pub color: Option<ColorAssignment>,
+}
+
+impl RouteEntry {
+  /// Entry colored from `route_colors`
+  pub fn new(fit_file: impl Into<String>) -> Self {
+    Self {
+      fit_file: fit_file.into(),
+      color: None,
+    }
+  }
+
+  pub fn with_color(mut self, color: ColorAssignment) -> Self {
+    self.color = Some(color);
+    self
+  }
+}
+
+/// Configuration for drawing several activities onto one image
+#[derive(Debug, Clone)]
+pub struct MultiRouteImageConfig {
+  /// Activities to draw, in layer order (first is drawn at the bottom)
+  pub routes: Vec<RouteEntry>,
+  /// Path to background image
+  pub background_image: String,
+  /// Path to output image
+  pub output_file: String,
+  /// Route scale and positioning (shared bounds of all activities)
+  pub route_scale: RouteScale,
+  /// Route colors (BGRA), cycled across activities without a color of
+  /// their own; `palette::distinct_colors` gives an evenly spread palette
+  pub route_colors: Vec<[f64; 4]>,
+  /// Line thickness for routes
+  pub line_thickness: i32,
+  /// Route opacity (0.0-1.0)
+  pub opacity: f64,
+  /// Top-left corner of the color legend as percentage of width and
+  /// height (None = no legend)
+  pub legend_position: Option<(f64, f64)>,
+}
+
+impl MultiRouteImageConfig {
+  /// Creates a new MultiRouteImageConfig with default styling
+  pub fn new(
+    fit_files: Vec<String>,
+    background_image: String,
+    output_file: String,
+  ) -> Self {
+    Self {
+      routes: fit_files.into_iter().map(RouteEntry::new).collect(),
+      background_image,
+      output_file,
+      route_scale: RouteScale::default(),
+      route_colors: vec![RouteColor::default().route_line],
+      line_thickness: 2,
+      opacity: 1.0,
+      legend_position: None,
+    }
+  }
+
+  /// Palette color for the activity at `index`
+  pub fn route_color(&self, index: usize) -> [f64; 4] {
+    if self.route_colors.is_empty() {
+      return RouteColor::default().route_line;
+    }
+    self.route_colors[index % self.route_colors.len()]
+  }
+}
+
+/// Background of a share card
+#[derive(Debug, Clone)]
+pub enum ShareCardBackground {
+  /// Solid color (BGRA)
+  Solid([f64; 4]),
+  /// Image path, center-cropped to a square, blurred and darkened
+  Blurred(String),
+}
+
+/// Configuration for a 1080x1080 activity share card
+#[derive(Debug, Clone)]
+pub struct ShareCardConfig {
+  /// Path to FIT file
+  pub fit_file: String,
+  /// Card background
+  pub background: ShareCardBackground,
+  /// Accent color (BGRA) for the route and stat labels
+  pub accent_color: [f64; 4],
+  /// Title text (None = the activity distance)
+  pub title_override: Option<String>,
+  /// Path to output image
+  pub output_file: String,
+  /// Units for distance, pace and elevation
+  pub units: Units,
+  /// Time shown on the card (the pace follows it)
+  pub time_basis: TimeBasis,
+  /// Precision of the title distance and the pace
+  pub number_format: NumberFormat,
+}
+
+impl ShareCardConfig {
+  /// Creates a new ShareCardConfig with a dark background and orange accent
+  pub fn new(fit_file: String, output_file: String) -> Self {
+    Self {
+      fit_file,
+      background: ShareCardBackground::Solid([30.0, 30.0, 30.0, 0.0]),
+      accent_color: [0.0, 140.0, 255.0, 0.0],
+      title_override: None,
+      output_file,
+      units: Units::Metric,
+      time_basis: TimeBasis::Moving,
+      number_format: NumberFormat::default(),
+    }
+  }
+}
+
+/// Configuration for a grid of per-lap route thumbnails
+#[derive(Debug, Clone)]
+pub struct LapGridConfig {
+  /// Path to FIT file
+  pub fit_file: String,
+  /// Path to output image
+  pub output_file: String,
+  /// Tiles per row
+  pub columns: usize,
+  /// Route area of each tile in pixels (width, height)
+  pub tile_size: (i32, i32),
+  /// Height of the label strip under each tile
+  pub label_height: i32,
+  /// Gap between tiles and around the grid in pixels
+  pub spacing: i32,
+  /// Share of the shorter tile side kept clear around the route (0.0-0.5)
+  pub padding: f64,
+  /// Border around each tile in pixels (0 = no border)
+  pub border_thickness: i32,
+  /// Border color (BGRA)
+  pub border_color: [f64; 4],
+  /// Grid background color (BGRA)
+  pub background_color: [f64; 4],
+  /// Tile background color (BGRA)
+  pub tile_color: [f64; 4],
+  /// Route color (BGRA)
+  pub route_color: [f64; 4],
+  /// Line thickness for the route
+  pub line_thickness: i32,
+  /// Label font
+  pub font: Font,
+  /// Label font scale
+  pub font_scale: f64,
+  /// Label color (BGRA)
+  pub text_color: [f64; 4],
+  /// Units for the pace labels
+  pub units: Units,
+  /// Rounding of the pace labels
+  pub number_format: NumberFormat,
+}
+
+impl LapGridConfig {
+  /// Creates a new LapGridConfig with three 300x300 tiles per row on a
+  /// dark background
+  pub fn new(fit_file: String, output_file: String) -> Self {
+    Self {
+      fit_file,
+      output_file,
+      columns: 3,
+      tile_size: (300, 300),
+      label_height: 48,
+      spacing: 20,
+      padding: 0.1,
+      border_thickness: 0,
+      border_color: [255.0, 255.0, 255.0, 0.0],
+      background_color: [30.0, 30.0, 30.0, 0.0],
+      tile_color: [50.0, 50.0, 50.0, 0.0],
+      route_color: [0.0, 140.0, 255.0, 0.0],
+      line_thickness: 3,
+      font: Font::Simplex,
+      font_scale: 0.7,
+      text_color: [255.0, 255.0, 255.0, 0.0],
+      units: Units::Metric,
+      number_format: NumberFormat::default(),
+    }
+  }
+
+  /// Columns actually used for `tiles` tiles: never more than the tiles
+  pub fn grid_columns(&self, tiles: usize) -> usize {
+    self.columns.clamp(1, tiles.max(1))
+  }
+
+  /// Size (width, height) of the whole grid image for `tiles` tiles
+  pub fn grid_size(&self, tiles: usize) -> (i32, i32) {
+    let columns = self.grid_columns(tiles);
+    let rows = tiles.max(1).div_ceil(columns);
+    let (width, height) = self.cell_size();
+    (
+      columns as i32 * width + self.spacing,
+      rows as i32 * height + self.spacing,
+    )
+  }
+
+  /// Top-left corner of the route area of tile `index` (row-major)
+  pub fn tile_origin(&self, index: usize, tiles: usize) -> (i32, i32) {
+    let columns = self.grid_columns(tiles);
+    let (width, height) = self.cell_size();
+    (
+      self.spacing + (index % columns) as i32 * width,
+      self.spacing + (index / columns) as i32 * height,
+    )
+  }
+
+  /// Tile with its label strip and the gap after it
+  fn cell_size(&self) -> (i32, i32) {
+    let (width, height) = self.tile_size;
+    (
+      width + self.spacing,
+      height + self.label_height + self.spacing,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_multi_route_colors_cycle() {
+    let mut config = MultiRouteImageConfig::new(
+      vec!["a.fit".to_string(), "b.fit".to_string()],
+      "bg.png".to_string(),
+      "out.png".to_string(),
+    );
+    config.route_colors = vec![[1.0; 4], [2.0; 4]];
+    assert_eq!(config.route_color(0), [1.0; 4]);
+    assert_eq!(config.route_color(3), [2.0; 4]);
+
+    config.route_colors.clear();
+    assert_eq!(
+      config.route_color(5),
+      RouteColor::default().route_line
+    );
+  }
+
+  #[test]
+  fn test_route_entries() {
+    let config = MultiRouteImageConfig::new(
+      vec!["a.fit".to_string()],
+      "bg.png".to_string(),
+      "out.png".to_string(),
+    );
+    assert_eq!(config.routes[0].fit_file, "a.fit");
+    assert!(config.routes[0].color.is_none());
+    assert!(config.legend_position.is_none());
+
+    let entry = RouteEntry::new("b.fit").with_color(ColorAssignment::Auto);
+    assert!(matches!(
+      entry.color,
+      Some(ColorAssignment::Auto)
+    ));
+  }
+
+  #[test]
+  fn test_lap_grid_layout() {
+    let mut config = LapGridConfig::new(
+      "a.fit".to_string(),
+      "out.png".to_string(),
+    );
+    config.tile_size = (100, 80);
+    config.label_height = 20;
+    config.spacing = 10;
+
+    // Five laps in three columns: two rows
+    assert_eq!(config.grid_size(5), (340, 230));
+    assert_eq!(config.tile_origin(0, 5), (10, 10));
+    assert_eq!(config.tile_origin(4, 5), (120, 120));
+
+    // A single lap is a single tile
+    assert_eq!(config.grid_size(1), (120, 120));
+    assert_eq!(config.grid_size(0), (120, 120));
+  }
+
+  #[test]
+  fn test_image_output_options() {
+    // Inferred from the extension by default
+    let options = ImageOutputOptions::default();
+    assert_eq!(
+      options.resolve("out.jpg").unwrap(),
+      None
+    );
+
+    let jpeg = ImageOutputOptions {
+      format: Some(ImageFormat::Jpeg { quality: 50 }),
+    };
+    assert_eq!(
+      jpeg.resolve("out.JPEG").unwrap(),
+      Some(ImageFormat::Jpeg { quality: 50 })
+    );
+    assert!(jpeg.resolve("out.png").is_err());
+
+    let webp = ImageOutputOptions {
+      format: Some(ImageFormat::Webp { quality: 0 }),
+    };
+    let err = webp.resolve("out.webp").unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "WebP quality 0 is out of range (1-100)"
+    );
+  }
+
+  #[test]
+  fn test_route_image_config_json_round_trip() {
+    let mut config = RouteImageConfig::default(
+      "run.fit".to_string(),
+      "bg.png".to_string(),
+      "out.png".to_string(),
+    );
+    config.background_fx = vec![BackgroundFx::Blur { sigma: 2.0 }];
+    config.transparent_canvas = Some((640, 480));
+    config.embed_config = true;
+
+    let json = serde_json::to_string(&config).unwrap();
+    let back: RouteImageConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+      serde_json::to_string(&back).unwrap(),
+      json
+    );
   }
 }
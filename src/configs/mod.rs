@@ -1,8 +1,12 @@
 pub mod config;
 pub mod image_config;
+pub mod patch;
+pub mod social;
 pub mod video_config;
 
 // Re-export commonly used types
 pub use config::*;
 pub use image_config::*;
+pub use patch::*;
+pub use social::*;
 pub use video_config::*;
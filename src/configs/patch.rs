@@ -0,0 +1,1740 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+use super::{
+  config::{
+    ArrowConfig, AttributionConfig, BackgroundFx, BackgroundScaling,
+    CadenceChartConfig, ClimbHighlightConfig, Color, DateStampConfig,
+    DistanceRepair, ExtraField, FileConfig, Font, GlitchStyle,
+    GpsSmoothingConfig, InputFormat, LegendConfig, MarkerStyle, Normalization,
+    NumberFormat, PercentPos, PixelPos, Position, PositionMarker, RouteColor,
+    RouteRenderStyle, RouteScale, RouteSegmentColoring, SeededStyle,
+    ShadowConfig, SmoothingConfig, SpikeFilter, SportProfile, TimeBasis,
+  },
+  image_config::{ImageOutputOptions, LoopDetectionConfig, RouteImageConfig},
+  video_config::{
+    BarAxis, BarStyle, BottomBarMode, CoursePreview, CustomMetricConfig,
+    EndCardConfig, FadeConfig, LapDataConfig, LapPause, LapSource, LivePreview,
+    MemoryProfile, OverlayElement, PaceDistConfig, PacingCurve,
+    ProgressionMode, RouteVideoConfig, SafeArea, SessionSelection, StatChip,
+    TimeIndexSampling, VideoCodec, VideoFallback, VideoTiming,
+  },
+};
+
+/// Partial override for `RouteScale`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteScalePatch {
+  pub scale: Option<f64>,
+  pub offset_x_percent: Option<f64>,
+  pub offset_y_percent: Option<f64>,
+}
+
+impl RouteScalePatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut RouteScale) {
+    set(&mut base.scale, self.scale);
+    set(
+      &mut base.offset_x_percent,
+      self.offset_x_percent,
+    );
+    set(
+      &mut base.offset_y_percent,
+      self.offset_y_percent,
+    );
+  }
+}
+
+/// Partial override for `RouteColor` (BGRA format)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteColorPatch {
+  pub route_line: Option<[f64; 4]>,
+  pub current_position: Option<[f64; 4]>,
+  pub text: Option<[f64; 4]>,
+  pub lap_bars: Option<[f64; 4]>,
+}
+
+impl RouteColorPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut RouteColor) {
+    set(&mut base.route_line, self.route_line);
+    set(
+      &mut base.current_position,
+      self.current_position,
+    );
+    set(&mut base.text, self.text);
+    set(&mut base.lap_bars, self.lap_bars);
+  }
+}
+
+/// Partial override for `PaceDistConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaceDistPatch {
+  pub font_scale: Option<f64>,
+  pub thickness: Option<i32>,
+  pub font: Option<Font>,
+  /// Sets a fixed bar position, e.g. `{"pixels": [40, 1000]}`
+  pub position: Option<Position>,
+  pub show_pace: Option<bool>,
+  pub show_distance: Option<bool>,
+  pub show_heart_rate: Option<bool>,
+  pub show_power: Option<bool>,
+  pub show_cadence: Option<bool>,
+  pub cadence_label: Option<String>,
+  pub smooth_counter: Option<bool>,
+  pub mode: Option<BottomBarMode>,
+}
+
+impl PaceDistPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut PaceDistConfig) {
+    set(&mut base.font_scale, self.font_scale);
+    set(&mut base.thickness, self.thickness);
+    set(&mut base.font, self.font);
+    if let Some(position) = self.position {
+      base.position = Some(position);
+    }
+    set(&mut base.show_pace, self.show_pace);
+    set(
+      &mut base.show_distance,
+      self.show_distance,
+    );
+    set(
+      &mut base.show_heart_rate,
+      self.show_heart_rate,
+    );
+    set(&mut base.show_power, self.show_power);
+    set(
+      &mut base.show_cadence,
+      self.show_cadence,
+    );
+    if let Some(label) = &self.cadence_label {
+      base.cadence_label = Some(label.clone());
+    }
+    set(
+      &mut base.smooth_counter,
+      self.smooth_counter,
+    );
+    if let Some(mode) = &self.mode {
+      base.mode = mode.clone();
+    }
+  }
+}
+
+/// Partial override for a `Position`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum PositionPatch {
+  /// Replaces the position, e.g. `{"pixels": [640, 80]}`
+  Replace(Position),
+  /// Overrides single coordinates in the unit of the current position,
+  /// e.g. `[null, 0.2]`; percents are clamped into 0.0-1.0
+  Axes(Option<f64>, Option<f64>),
+}
+
+impl PositionPatch {
+  /// Overwrites the position, or only the coordinates that are set
+  pub fn apply(&self, base: &mut Position) {
+    match (*self, *base) {
+      (PositionPatch::Replace(position), _) => *base = position,
+      (PositionPatch::Axes(x, y), Position::Percent(current)) => {
+        *base = Position::Percent(PercentPos::clamped(
+          x.unwrap_or(current.x()),
+          y.unwrap_or(current.y()),
+        ));
+      }
+      (PositionPatch::Axes(x, y), Position::Pixels(PixelPos(cx, cy))) => {
+        *base = Position::Pixels(PixelPos(
+          x.map_or(cx, |x| x.round() as i32),
+          y.map_or(cy, |y| y.round() as i32),
+        ));
+      }
+    }
+  }
+}
+
+/// Partial override for `LapDataConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LapDataPatch {
+  /// A new position, or single coordinates, e.g. `[null, 0.2]`
+  pub position: Option<PositionPatch>,
+  pub font_scale: Option<f64>,
+  pub thickness: Option<i32>,
+  pub font: Option<Font>,
+  pub text_color: Option<Color>,
+  pub show_heart_rate: Option<bool>,
+  pub show_power: Option<bool>,
+  pub show_stride_length: Option<bool>,
+  pub show_pace_bars: Option<bool>,
+  pub pace_bar_normalization: Option<Normalization>,
+  pub bar_style: Option<BarStyle>,
+  pub bar_axis: Option<BarAxis>,
+  pub show_elevation_gain: Option<bool>,
+  pub source: Option<LapSource>,
+}
+
+impl LapDataPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut LapDataConfig) {
+    if let Some(position) = &self.position {
+      position.apply(&mut base.position);
+    }
+    set(&mut base.font_scale, self.font_scale);
+    set(&mut base.thickness, self.thickness);
+    set(&mut base.font, self.font);
+    set(&mut base.text_color, self.text_color);
+    set(
+      &mut base.show_heart_rate,
+      self.show_heart_rate,
+    );
+    set(&mut base.show_power, self.show_power);
+    set(
+      &mut base.show_stride_length,
+      self.show_stride_length,
+    );
+    set(
+      &mut base.show_pace_bars,
+      self.show_pace_bars,
+    );
+    set(
+      &mut base.pace_bar_normalization,
+      self.pace_bar_normalization,
+    );
+    set(&mut base.bar_style, self.bar_style);
+    if let Some(axis) = self.bar_axis {
+      base.bar_axis = Some(axis);
+    }
+    set(
+      &mut base.show_elevation_gain,
+      self.show_elevation_gain,
+    );
+    set(&mut base.source, self.source);
+  }
+}
+
+/// Partial override for `FileConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfigPatch {
+  pub fit_file: Option<String>,
+  pub background_image: Option<String>,
+  pub output_file: Option<String>,
+  pub input_format: Option<InputFormat>,
+}
+
+impl FileConfigPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut FileConfig) {
+    set(
+      &mut base.fit_file,
+      self.fit_file.clone(),
+    );
+    set(
+      &mut base.background_image,
+      self.background_image.clone(),
+    );
+    set(
+      &mut base.output_file,
+      self.output_file.clone(),
+    );
+    set(
+      &mut base.input_format,
+      self.input_format,
+    );
+  }
+}
+
+/// Partial override for `LivePreview`. There is no `path`: patches come
+/// from server and FFI clients, so the preview goes where the Rust-side
+/// config puts it (default next to the output)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LivePreviewPatch {
+  pub every_frames: Option<usize>,
+}
+
+impl LivePreviewPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut LivePreview) {
+    set(
+      &mut base.every_frames,
+      self.every_frames,
+    );
+  }
+}
+
+/// Partial override for `RouteVideoConfig`.
+///
+/// Every field is optional, so a patch only describes the settings that
+/// should change. Used by the HTTP server (`config` form field) and the
+/// CLI (`--set key=value`).
+///
+/// # Example
+/// ```
+/// use runarium::configs::{Position, RouteVideoConfig, RouteVideoConfigPatch};
+///
+/// let patch: RouteVideoConfigPatch =
+///   serde_json::from_str(r#"{"lap_data": {"position": [null, 0.2]}}"#)
+///     .unwrap();
+///
+/// let mut config = RouteVideoConfig::default();
+/// patch.apply(&mut config);
+/// assert_eq!(config.lap_data.position, Position::from((0.5, 0.2)));
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteVideoConfigPatch {
+  pub route_scale: Option<RouteScalePatch>,
+  pub colors: Option<RouteColorPatch>,
+  pub pace_dist: Option<PaceDistPatch>,
+  pub lap_data: Option<LapDataPatch>,
+  pub file_config: Option<FileConfigPatch>,
+  pub show_bottom_bar: Option<bool>,
+  pub show_route: Option<bool>,
+  pub show_lap_data: Option<bool>,
+  pub progression: Option<ProgressionMode>,
+  pub pacing: Option<PacingCurve>,
+  pub timing: Option<VideoTiming>,
+  pub lap_pause: Option<LapPause>,
+  pub end_card: Option<EndCardConfig>,
+  pub overlays: Option<Vec<OverlayElement>>,
+  pub memory_profile: Option<MemoryProfile>,
+  pub output_size: Option<(i32, i32)>,
+  pub duration_secs: Option<f64>,
+  pub max_duration_secs: Option<f64>,
+  pub codec: Option<VideoCodec>,
+  pub fallback: Option<VideoFallback>,
+  pub time_index: Option<TimeIndexSampling>,
+  pub safe_area: Option<SafeArea>,
+  pub debug_draw_safe_area: Option<bool>,
+  pub sport: Option<SportProfile>,
+  pub extra_record_fields: Option<Vec<ExtraField>>,
+  pub display_extra_field: Option<String>,
+  pub custom_metrics: Option<Vec<CustomMetricConfig>>,
+  pub deterministic: Option<bool>,
+  pub embed_config: Option<bool>,
+  pub distance_repair: Option<DistanceRepair>,
+  pub spike_filter: Option<SpikeFilter>,
+  pub max_points: Option<usize>,
+  pub sessions: Option<SessionSelection>,
+  pub connect_pauses: Option<bool>,
+  pub chips: Option<Vec<StatChip>>,
+  pub segment_coloring: Option<RouteSegmentColoring>,
+  pub route_shadow: Option<ShadowConfig>,
+  pub line_smoothing: Option<SmoothingConfig>,
+  pub gps_smoothing: Option<GpsSmoothingConfig>,
+  /// No sprites: their path can only come from Rust
+  #[serde(default, deserialize_with = "trusted_marker")]
+  pub position_marker: Option<PositionMarker>,
+  pub render_style: Option<RouteRenderStyle>,
+  pub route_fade: Option<FadeConfig>,
+  pub course_preview: Option<CoursePreview>,
+  pub legend: Option<LegendConfig>,
+  /// Also sets `show_date`
+  pub date_stamp: Option<DateStampConfig>,
+  pub show_date: Option<bool>,
+  pub show_summary: Option<bool>,
+  pub attribution: Option<AttributionConfig>,
+  pub background_fx: Option<Vec<BackgroundFx>>,
+  pub background_scaling: Option<BackgroundScaling>,
+  pub seeded_style: Option<SeededStyle>,
+  pub time_basis: Option<TimeBasis>,
+  pub cadence_chart: Option<CadenceChartConfig>,
+  /// Also sets `highlight_steepest_climb`
+  pub steepest_climb: Option<ClimbHighlightConfig>,
+  pub highlight_steepest_climb: Option<bool>,
+  pub number_format: Option<NumberFormat>,
+  /// Turns the preview on, keeping the path of an existing one
+  pub live_preview: Option<LivePreviewPatch>,
+}
+
+impl RouteVideoConfigPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut RouteVideoConfig) {
+    if let Some(route_scale) = &self.route_scale {
+      route_scale.apply(&mut base.route_scale);
+    }
+    if let Some(colors) = &self.colors {
+      colors.apply(&mut base.colors);
+    }
+    if let Some(pace_dist) = &self.pace_dist {
+      pace_dist.apply(&mut base.pace_dist);
+    }
+    if let Some(lap_data) = &self.lap_data {
+      lap_data.apply(&mut base.lap_data);
+    }
+    if let Some(file_config) = &self.file_config {
+      file_config.apply(&mut base.file_config);
+    }
+    set(
+      &mut base.show_bottom_bar,
+      self.show_bottom_bar,
+    );
+    set(&mut base.show_route, self.show_route);
+    set(
+      &mut base.show_lap_data,
+      self.show_lap_data,
+    );
+    set(&mut base.progression, self.progression);
+    set(&mut base.pacing, self.pacing);
+    set(&mut base.timing, self.timing);
+    if let Some(lap_pause) = self.lap_pause {
+      base.lap_pause = Some(lap_pause);
+    }
+    if let Some(end_card) = self.end_card {
+      base.end_card = Some(end_card);
+    }
+    set(
+      &mut base.overlays,
+      self.overlays.clone(),
+    );
+    set(
+      &mut base.memory_profile,
+      self.memory_profile,
+    );
+    if let Some(output_size) = self.output_size {
+      base.output_size = Some(output_size);
+    }
+    set(
+      &mut base.duration_secs,
+      self.duration_secs,
+    );
+    if let Some(max_duration_secs) = self.max_duration_secs {
+      base.max_duration_secs = Some(max_duration_secs);
+    }
+    set(&mut base.codec, self.codec);
+    set(&mut base.fallback, self.fallback);
+    set(&mut base.time_index, self.time_index);
+    set(&mut base.safe_area, self.safe_area);
+    set(
+      &mut base.debug_draw_safe_area,
+      self.debug_draw_safe_area,
+    );
+    set(&mut base.sport, self.sport);
+    set(
+      &mut base.extra_record_fields,
+      self.extra_record_fields.clone(),
+    );
+    if let Some(label) = &self.display_extra_field {
+      base.display_extra_field = Some(label.clone());
+    }
+    set(
+      &mut base.custom_metrics,
+      self.custom_metrics.clone(),
+    );
+    set(
+      &mut base.deterministic,
+      self.deterministic,
+    );
+    set(
+      &mut base.embed_config,
+      self.embed_config,
+    );
+    set(
+      &mut base.distance_repair,
+      self.distance_repair,
+    );
+    if let Some(spike_filter) = self.spike_filter {
+      base.spike_filter = Some(spike_filter);
+    }
+    if let Some(max_points) = self.max_points {
+      base.max_points = Some(max_points);
+    }
+    set(&mut base.sessions, self.sessions);
+    set(
+      &mut base.connect_pauses,
+      self.connect_pauses,
+    );
+    set(&mut base.chips, self.chips.clone());
+    set(
+      &mut base.segment_coloring,
+      self.segment_coloring,
+    );
+    if let Some(route_shadow) = self.route_shadow {
+      base.route_shadow = Some(route_shadow);
+    }
+    if let Some(line_smoothing) = self.line_smoothing {
+      base.line_smoothing = Some(line_smoothing);
+    }
+    if let Some(gps_smoothing) = self.gps_smoothing {
+      base.gps_smoothing = Some(gps_smoothing);
+    }
+    set(
+      &mut base.position_marker,
+      self.position_marker.clone(),
+    );
+    set(
+      &mut base.render_style,
+      self.render_style,
+    );
+    if let Some(route_fade) = self.route_fade {
+      base.route_fade = Some(route_fade);
+    }
+    if let Some(course_preview) = self.course_preview {
+      base.course_preview = Some(course_preview);
+    }
+    if let Some(legend) = &self.legend {
+      base.legend = Some(legend.clone());
+    }
+    if let Some(date_stamp) = &self.date_stamp {
+      base.date_stamp = date_stamp.clone();
+      base.show_date = true;
+    }
+    set(&mut base.show_date, self.show_date);
+    set(
+      &mut base.show_summary,
+      self.show_summary,
+    );
+    if let Some(attribution) = &self.attribution {
+      base.attribution = attribution.clone();
+    }
+    if let Some(background_fx) = &self.background_fx {
+      base.background_fx = background_fx.clone();
+    }
+    set(
+      &mut base.background_scaling,
+      self.background_scaling,
+    );
+    if let Some(style) = self.seeded_style {
+      base.seeded_style = Some(style);
+    }
+    set(&mut base.time_basis, self.time_basis);
+    if let Some(cadence_chart) = self.cadence_chart {
+      base.cadence_chart = Some(cadence_chart);
+    }
+    if let Some(steepest_climb) = self.steepest_climb {
+      base.steepest_climb = steepest_climb;
+      base.highlight_steepest_climb = true;
+    }
+    set(
+      &mut base.highlight_steepest_climb,
+      self.highlight_steepest_climb,
+    );
+    set(
+      &mut base.number_format,
+      self.number_format,
+    );
+    if let Some(preview) = &self.live_preview {
+      preview.apply(base.live_preview.get_or_insert_with(Default::default));
+    }
+  }
+
+  /// Builds a patch from `key=value` pairs such as `lap_data.position.1=0.2`.
+  ///
+  /// Keys are dotted paths into `RouteVideoConfig`; numeric segments index
+  /// into tuples and arrays. Values are parsed as JSON, falling back to a
+  /// plain string (so `file_config.output_file=out.mp4` works unquoted).
+  pub fn from_set_args<I, S>(args: I) -> Result<Self>
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let mut root = Value::Object(Map::new());
+
+    for arg in args {
+      let arg = arg.as_ref();
+      let (key, raw) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected key=value, got '{}'", arg))?;
+      let value = serde_json::from_str(raw)
+        .unwrap_or_else(|_| Value::String(raw.to_string()));
+
+      insert_path(&mut root, key, value)?;
+    }
+
+    serde_json::from_value(root)
+      .map_err(|e| anyhow!("Invalid --set override: {}", e))
+  }
+}
+
+/// Partial override for the per-frame settings of a `RouteVideoConfig`:
+/// the only ones a `RenderCache` can change without rebuilding its base
+/// frame. Static settings (scale, colors, lap panel, ...) are unknown
+/// fields here, so they are rejected rather than silently ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DynamicConfigPatch {
+  pub pace_dist: Option<PaceDistPatch>,
+  pub show_bottom_bar: Option<bool>,
+  pub chips: Option<Vec<StatChip>>,
+  pub output_file: Option<String>,
+  pub codec: Option<VideoCodec>,
+  pub fallback: Option<VideoFallback>,
+  pub deterministic: Option<bool>,
+}
+
+impl DynamicConfigPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut RouteVideoConfig) {
+    if let Some(pace_dist) = &self.pace_dist {
+      pace_dist.apply(&mut base.pace_dist);
+    }
+    set(
+      &mut base.show_bottom_bar,
+      self.show_bottom_bar,
+    );
+    set(&mut base.chips, self.chips.clone());
+    set(
+      &mut base.file_config.output_file,
+      self.output_file.clone(),
+    );
+    set(&mut base.codec, self.codec);
+    set(&mut base.fallback, self.fallback);
+    set(
+      &mut base.deterministic,
+      self.deterministic,
+    );
+  }
+}
+
+/// Partial override for `RouteImageConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteImageConfigPatch {
+  pub route_scale: Option<RouteScalePatch>,
+  pub colors: Option<RouteColorPatch>,
+  pub file_config: Option<FileConfigPatch>,
+  pub line_thickness: Option<i32>,
+  /// Also sets `show_lap_data`
+  pub lap_data: Option<LapDataPatch>,
+  pub show_lap_data: Option<bool>,
+  pub pixel_map_stride: Option<usize>,
+  pub sport: Option<SportProfile>,
+  pub deterministic: Option<bool>,
+  pub embed_config: Option<bool>,
+  pub distance_repair: Option<DistanceRepair>,
+  pub spike_filter: Option<SpikeFilter>,
+  pub connect_pauses: Option<bool>,
+  pub segment_coloring: Option<RouteSegmentColoring>,
+  pub route_shadow: Option<ShadowConfig>,
+  pub line_smoothing: Option<SmoothingConfig>,
+  pub gps_smoothing: Option<GpsSmoothingConfig>,
+  /// No sprites: their path can only come from Rust
+  #[serde(default, deserialize_with = "trusted_marker")]
+  pub finish_marker: Option<PositionMarker>,
+  pub render_style: Option<RouteRenderStyle>,
+  pub legend: Option<LegendConfig>,
+  /// Also sets `show_date`
+  pub date_stamp: Option<DateStampConfig>,
+  pub show_date: Option<bool>,
+  pub show_summary: Option<bool>,
+  /// Also sets `show_detected_loops`
+  pub loop_detection: Option<LoopDetectionConfig>,
+  pub show_detected_loops: Option<bool>,
+  /// Also sets `highlight_steepest_climb`
+  pub steepest_climb: Option<ClimbHighlightConfig>,
+  pub highlight_steepest_climb: Option<bool>,
+  pub glitch_style: Option<GlitchStyle>,
+  pub attribution: Option<AttributionConfig>,
+  pub background_fx: Option<Vec<BackgroundFx>>,
+  pub background_scaling: Option<BackgroundScaling>,
+  pub seeded_style: Option<SeededStyle>,
+  pub output_options: Option<ImageOutputOptions>,
+  pub direction_arrows: Option<ArrowConfig>,
+  pub cadence_chart: Option<CadenceChartConfig>,
+  pub number_format: Option<NumberFormat>,
+  pub safe_area: Option<SafeArea>,
+  pub debug_draw_safe_area: Option<bool>,
+  pub transparent_canvas: Option<(i32, i32)>,
+}
+
+impl RouteImageConfigPatch {
+  /// Overwrites only the fields that are set in the patch
+  pub fn apply(&self, base: &mut RouteImageConfig) {
+    if let Some(route_scale) = &self.route_scale {
+      route_scale.apply(&mut base.route_scale);
+    }
+    if let Some(colors) = &self.colors {
+      colors.apply(&mut base.colors);
+    }
+    if let Some(file_config) = &self.file_config {
+      file_config.apply(&mut base.file_config);
+    }
+    set(
+      &mut base.line_thickness,
+      self.line_thickness,
+    );
+    if let Some(lap_data) = &self.lap_data {
+      lap_data.apply(base.lap_data.get_or_insert_with(LapDataConfig::default));
+      base.show_lap_data = true;
+    }
+    set(
+      &mut base.show_lap_data,
+      self.show_lap_data,
+    );
+    set(
+      &mut base.pixel_map_stride,
+      self.pixel_map_stride,
+    );
+    set(&mut base.sport, self.sport);
+    set(
+      &mut base.deterministic,
+      self.deterministic,
+    );
+    set(
+      &mut base.embed_config,
+      self.embed_config,
+    );
+    set(
+      &mut base.distance_repair,
+      self.distance_repair,
+    );
+    if let Some(spike_filter) = self.spike_filter {
+      base.spike_filter = Some(spike_filter);
+    }
+    set(
+      &mut base.connect_pauses,
+      self.connect_pauses,
+    );
+    set(
+      &mut base.segment_coloring,
+      self.segment_coloring,
+    );
+    if let Some(route_shadow) = self.route_shadow {
+      base.route_shadow = Some(route_shadow);
+    }
+    if let Some(line_smoothing) = self.line_smoothing {
+      base.line_smoothing = Some(line_smoothing);
+    }
+    if let Some(gps_smoothing) = self.gps_smoothing {
+      base.gps_smoothing = Some(gps_smoothing);
+    }
+    if let Some(finish_marker) = &self.finish_marker {
+      base.finish_marker = Some(finish_marker.clone());
+    }
+    set(
+      &mut base.render_style,
+      self.render_style,
+    );
+    if let Some(legend) = &self.legend {
+      base.legend = Some(legend.clone());
+    }
+    if let Some(date_stamp) = &self.date_stamp {
+      base.date_stamp = date_stamp.clone();
+      base.show_date = true;
+    }
+    set(&mut base.show_date, self.show_date);
+    set(
+      &mut base.show_summary,
+      self.show_summary,
+    );
+    if let Some(loop_detection) = self.loop_detection {
+      base.loop_detection = loop_detection;
+      base.show_detected_loops = true;
+    }
+    set(
+      &mut base.show_detected_loops,
+      self.show_detected_loops,
+    );
+    if let Some(attribution) = &self.attribution {
+      base.attribution = attribution.clone();
+    }
+    if let Some(background_fx) = &self.background_fx {
+      base.background_fx = background_fx.clone();
+    }
+    set(
+      &mut base.background_scaling,
+      self.background_scaling,
+    );
+    if let Some(style) = self.seeded_style {
+      base.seeded_style = Some(style);
+    }
+    set(
+      &mut base.output_options,
+      self.output_options,
+    );
+    if let Some(direction_arrows) = self.direction_arrows {
+      base.direction_arrows = Some(direction_arrows);
+    }
+    if let Some(cadence_chart) = self.cadence_chart {
+      base.cadence_chart = Some(cadence_chart);
+    }
+    if let Some(steepest_climb) = self.steepest_climb {
+      base.steepest_climb = steepest_climb;
+      base.highlight_steepest_climb = true;
+    }
+    set(
+      &mut base.highlight_steepest_climb,
+      self.highlight_steepest_climb,
+    );
+    if let Some(glitch_style) = self.glitch_style {
+      base.glitch_style = Some(glitch_style);
+    }
+    set(
+      &mut base.number_format,
+      self.number_format,
+    );
+    set(&mut base.safe_area, self.safe_area);
+    set(
+      &mut base.debug_draw_safe_area,
+      self.debug_draw_safe_area,
+    );
+    if let Some(size) = self.transparent_canvas {
+      base.transparent_canvas = Some(size);
+    }
+  }
+}
+
+/// Marker of a patch; a sprite is refused, since patches come from server
+/// and FFI clients and its path is opened by the renderer
+fn trusted_marker<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> std::result::Result<Option<PositionMarker>, D::Error> {
+  let marker = Option::<PositionMarker>::deserialize(deserializer)?;
+  if let Some(PositionMarker {
+    style: MarkerStyle::Sprite { .. },
+    ..
+  }) = &marker
+  {
+    return Err(serde::de::Error::custom(
+      "sprite markers can only be set from Rust",
+    ));
+  }
+  Ok(marker)
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+  if let Some(value) = value {
+    *target = value;
+  }
+}
+
+fn insert_path(root: &mut Value, key: &str, value: Value) -> Result<()> {
+  let segments: Vec<&str> = key.split('.').collect();
+  if segments.iter().any(|s| s.is_empty()) {
+    bail!("Invalid key '{}'", key);
+  }
+
+  let mut node = root;
+  for (i, segment) in segments.iter().enumerate() {
+    let is_last = i == segments.len() - 1;
+    let next = if is_last {
+      value.clone()
+    } else if segments[i + 1].parse::<usize>().is_ok() {
+      Value::Array(Vec::new())
+    } else {
+      Value::Object(Map::new())
+    };
+
+    node = match (node, segment.parse::<usize>()) {
+      (Value::Array(items), Ok(index)) => {
+        if items.len() <= index {
+          items.resize(index + 1, Value::Null);
+        }
+        if is_last || items[index].is_null() {
+          items[index] = next;
+        }
+        &mut items[index]
+      }
+      (Value::Object(map), _) => {
+        let entry = map.entry(segment.to_string()).or_insert(next.clone());
+        if is_last {
+          *entry = next;
+        }
+        entry
+      }
+      _ => bail!(
+        "Key '{}' conflicts with another override",
+        key
+      ),
+    };
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use super::*;
+  use crate::configs::{
+    BarLabel, Corner, GpsSmoothingMethod, ImageFormat, LegendOrientation,
+    MarkerStyle, Metric, PaceRounding, SmoothingMethod, TickerTransition,
+  };
+
+  #[test]
+  fn test_empty_patch_keeps_base() {
+    let mut config = RouteVideoConfig::neon();
+    RouteVideoConfigPatch::default().apply(&mut config);
+
+    let neon = RouteVideoConfig::neon();
+    assert_eq!(
+      config.route_scale.scale,
+      neon.route_scale.scale
+    );
+    assert_eq!(
+      config.colors.route_line,
+      neon.colors.route_line
+    );
+    assert_eq!(
+      config.lap_data.position,
+      neon.lap_data.position
+    );
+    assert_eq!(config.show_lap_data, neon.show_lap_data);
+  }
+
+  #[test]
+  fn test_position_patch() {
+    let patch = |json| serde_json::from_str::<PositionPatch>(json).unwrap();
+    let mut position = Position::from((0.5, 0.09));
+
+    patch(r#"{"pixels": [640, 80]}"#).apply(&mut position);
+    assert_eq!(
+      position,
+      Position::Pixels(PixelPos(640, 80))
+    );
+    // Single coordinates keep the unit of the current position
+    patch("[null, 120]").apply(&mut position);
+    assert_eq!(
+      position,
+      Position::Pixels(PixelPos(640, 120))
+    );
+
+    patch("[0.2, 0.3]").apply(&mut position);
+    assert_eq!(position, Position::from((0.2, 0.3)));
+    patch("[1.4, null]").apply(&mut position);
+    assert_eq!(position, Position::from((1.0, 0.3)));
+
+    let mut config = RouteVideoConfig::default();
+    RouteVideoConfigPatch::from_set_args(["lap_data.position.pixels=[40,60]"])
+      .unwrap()
+      .apply(&mut config);
+    assert_eq!(
+      config.lap_data.position,
+      Position::from((40, 60))
+    );
+  }
+
+  #[test]
+  fn test_deep_nested_override() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"lap_data": {"position": [null, 0.25]}}"#)
+        .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    assert_eq!(
+      config.lap_data.position,
+      Position::from((0.5, 0.25))
+    );
+    assert_eq!(config.lap_data.font_scale, 0.5);
+    assert!(config.lap_data.show_heart_rate);
+    assert_eq!(config.route_scale.scale, 0.2);
+  }
+
+  #[test]
+  fn test_show_power_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"pace_dist": {"show_power": true},
+          "lap_data": {"show_power": true}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert!(!config.pace_dist.show_power);
+    assert!(!config.lap_data.show_power);
+    patch.apply(&mut config);
+    assert!(config.pace_dist.show_power);
+    assert!(config.lap_data.show_power);
+  }
+
+  #[test]
+  fn test_unspecified_fields_keep_base() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"route_scale": {"scale": 0.5}, "show_route": false}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::detailed();
+    patch.apply(&mut config);
+
+    assert_eq!(config.route_scale.scale, 0.5);
+    assert_eq!(
+      config.route_scale.offset_x_percent,
+      0.15
+    );
+    assert_eq!(
+      config.route_scale.offset_y_percent,
+      0.15
+    );
+    assert!(!config.show_route);
+    assert!(config.show_bottom_bar);
+    assert!(config.show_lap_data);
+    assert_eq!(config.pace_dist.font_scale, 0.8);
+  }
+
+  #[test]
+  fn test_unknown_field_rejected() {
+    let result: Result<RouteVideoConfigPatch, _> =
+      serde_json::from_str(r#"{"scale": 0.3}"#);
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn test_from_set_args() {
+    let patch = RouteVideoConfigPatch::from_set_args([
+      "lap_data.position.1=0.3",
+      "colors.route_line=[255,0,0,0]",
+      "file_config.output_file=outputs/set.mp4",
+      "show_bottom_bar=false",
+    ])
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    assert_eq!(
+      config.lap_data.position,
+      Position::from((0.5, 0.3))
+    );
+    assert_eq!(
+      config.colors.route_line,
+      [255.0, 0.0, 0.0, 0.0]
+    );
+    assert_eq!(
+      config.file_config.output_file,
+      "outputs/set.mp4"
+    );
+    assert!(!config.show_bottom_bar);
+    assert!(config.show_route);
+  }
+
+  #[test]
+  fn test_memory_profile_from_set_args() {
+    let patch =
+      RouteVideoConfigPatch::from_set_args(["memory_profile=low"]).unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(
+      config.memory_profile,
+      MemoryProfile::Standard
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.memory_profile,
+      MemoryProfile::Low
+    );
+  }
+
+  #[test]
+  fn test_chips_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"chips": [
+        {"metric": "heart_rate", "position": [0.9, 0.05]},
+        {"metric": "elevation_gain", "position": [0.1, 0.9],
+         "style": {"opacity": 0.8}, "hide_when_missing": true}
+      ]}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+    assert_eq!(config.chips.len(), 2);
+    assert_eq!(
+      config.chips[0].metric,
+      Metric::HeartRate
+    );
+    assert_eq!(config.chips[0].style.padding, 10);
+    assert_eq!(config.chips[1].style.opacity, 0.8);
+    assert!(config.chips[1].hide_when_missing);
+  }
+
+  #[test]
+  fn test_from_set_args_merges_same_parent() {
+    let patch = RouteVideoConfigPatch::from_set_args([
+      "lap_data.position.0=0.1",
+      "lap_data.position.1=0.9",
+      "lap_data.show_pace_bars=false",
+    ])
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    assert_eq!(
+      config.lap_data.position,
+      Position::from((0.1, 0.9))
+    );
+    assert!(!config.lap_data.show_pace_bars);
+  }
+
+  #[test]
+  fn test_from_set_args_invalid() {
+    assert!(RouteVideoConfigPatch::from_set_args(["scale"]).is_err());
+    assert!(RouteVideoConfigPatch::from_set_args(["nope=1"]).is_err());
+    assert!(RouteVideoConfigPatch::from_set_args(["lap_data..x=1"]).is_err());
+  }
+
+  #[test]
+  fn test_image_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"line_thickness": 4, "lap_data": {"font_scale": 0.8}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+
+    assert_eq!(config.line_thickness, 4);
+    assert!(config.show_lap_data);
+    assert_eq!(config.lap_data.unwrap().font_scale, 0.8);
+    assert_eq!(config.file_config.output_file, "a.png");
+
+    // Like the video exports, the pixel map path is Rust-side only
+    assert!(
+      serde_json::from_str::<RouteImageConfigPatch>(
+        r#"{"pixel_map_export": "/tmp/x.json"}"#
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_route_shadow_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"route_shadow": {"offset": [0, 8], "opacity": 0.4}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    assert!(config.route_shadow.is_none());
+    patch.apply(&mut config);
+
+    let shadow = config.route_shadow.unwrap();
+    assert_eq!(shadow.offset, (0, 8));
+    assert_eq!(shadow.opacity, 0.4);
+    assert_eq!(shadow.blur_sigma, 4.0);
+  }
+
+  #[test]
+  fn test_line_smoothing_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"line_smoothing": {"method": {"chaikin": {"iterations": 3}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert!(config.line_smoothing.is_none());
+    patch.apply(&mut config);
+    assert_eq!(
+      config.line_smoothing.unwrap().method,
+      SmoothingMethod::Chaikin { iterations: 3 }
+    );
+  }
+
+  #[test]
+  fn test_spike_filter_patch() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"spike_filter": {"max_speed_mps": 20}}"#)
+        .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert!(config.spike_filter.is_none());
+    patch.apply(&mut config);
+    assert_eq!(
+      config.spike_filter.unwrap().max_speed_mps,
+      20.0
+    );
+
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"max_points": 3000}"#).unwrap();
+    patch.apply(&mut config);
+    assert_eq!(config.max_points, Some(3000));
+
+    // Patches come from server and FFI clients, so they cannot name a file
+    // for the render to write
+    for json in [
+      r#"{"export_geojson": "/etc/cron.d/job"}"#,
+      r#"{"export_csv": "/etc/cron.d/job"}"#,
+    ] {
+      assert!(serde_json::from_str::<RouteVideoConfigPatch>(json).is_err());
+    }
+  }
+
+  #[test]
+  fn test_custom_metrics_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"custom_metrics": [
+        {"field_name": "Ground Time", "label": "GCT", "unit": "ms"}
+      ]}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+    assert_eq!(
+      config.custom_metrics,
+      vec![CustomMetricConfig::new("Ground Time", "GCT", "ms")]
+    );
+  }
+
+  #[test]
+  fn test_timing_patch() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"timing": {"real_time": {"speedup": 60}}}"#)
+        .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(config.timing, VideoTiming::PerPoint);
+    patch.apply(&mut config);
+    assert_eq!(
+      config.timing,
+      VideoTiming::RealTime { speedup: 60.0 }
+    );
+  }
+
+  #[test]
+  fn test_gps_smoothing_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"gps_smoothing": {"method": {"moving_average": {"window": 7}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    assert!(config.gps_smoothing.is_none());
+    patch.apply(&mut config);
+    assert_eq!(
+      config.gps_smoothing.unwrap().method,
+      GpsSmoothingMethod::MovingAverage { window: 7 }
+    );
+  }
+
+  #[test]
+  fn test_position_marker_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"position_marker": {"style": {"ring": {"thickness": 3}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(
+      config.position_marker.style,
+      MarkerStyle::Dot
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.position_marker.style,
+      MarkerStyle::Ring { thickness: 3 }
+    );
+    assert!(config.position_marker.color.is_none());
+
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"finish_marker": {"style": "dot", "color": "red"}}"#,
+    )
+    .unwrap();
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.finish_marker.unwrap().style,
+      MarkerStyle::Dot
+    );
+
+    // The renderer opens a sprite's path, so clients cannot name one
+    let sprite =
+      r#"{"style": {"sprite": {"path": "/etc/shadow", "scale": 1.0}}}"#;
+    let err = serde_json::from_str::<RouteVideoConfigPatch>(&format!(
+      r#"{{"position_marker": {}}}"#,
+      sprite
+    ))
+    .unwrap_err();
+    assert!(err.to_string().contains("sprite markers"));
+    assert!(
+      serde_json::from_str::<RouteImageConfigPatch>(&format!(
+        r#"{{"finish_marker": {}}}"#,
+        sprite
+      ))
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_bottom_bar_mode_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"pace_dist": {"mode": {"ticker": {
+        "metrics": ["pace", "heart_rate"],
+        "seconds_per_metric": 3.0,
+        "transition": "slide"
+      }}}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(
+      config.pace_dist.mode,
+      BottomBarMode::Static
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.pace_dist.mode,
+      BottomBarMode::Ticker {
+        metrics: vec![Metric::Pace, Metric::HeartRate],
+        seconds_per_metric: 3.0,
+        transition: TickerTransition::Slide,
+      }
+    );
+    assert!(config.pace_dist.show_pace);
+  }
+
+  #[test]
+  fn test_end_card_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"end_card": {"end_hold_seconds": 3.0, "accent_color": "green"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert!(config.end_card.is_none());
+    patch.apply(&mut config);
+    let card = config.end_card.unwrap();
+    assert_eq!(card.end_hold_seconds, 3.0);
+    assert_eq!(card.thickness, 14);
+  }
+
+  #[test]
+  fn test_time_index_patch() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"time_index": {"every_frames": 30}}"#).unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(
+      config.time_index,
+      TimeIndexSampling::Off
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.time_index,
+      TimeIndexSampling::EveryFrames(30)
+    );
+  }
+
+  #[test]
+  fn test_attribution_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"attribution": {"text_override": "OSM", "position": "top_left"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+    let attribution = config.attribution;
+    assert_eq!(
+      attribution.text_override.as_deref(),
+      Some("OSM")
+    );
+    assert_eq!(attribution.position, Corner::TopLeft);
+    assert_eq!(attribution.font_scale, 0.4);
+    assert!(!attribution.suppress);
+  }
+
+  #[test]
+  fn test_lap_source_patch() {
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(config.lap_data.source, LapSource::Auto);
+
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"lap_data": {"source": {"auto_splits": {"every_km": 1.609}}}}"#,
+    )
+    .unwrap();
+    patch.apply(&mut config);
+    assert_eq!(
+      config.lap_data.source,
+      LapSource::AutoSplits { every_km: 1.609 }
+    );
+
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"lap_data": {"source": "device"}}"#).unwrap();
+    patch.apply(&mut config);
+    assert_eq!(
+      config.lap_data.source,
+      LapSource::Device
+    );
+  }
+
+  #[test]
+  fn test_bar_style_patch() {
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(config.lap_data.bar_style.alpha, 1.0);
+
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"lap_data": {"bar_style": {
+        "alpha": 0.6,
+        "show_value_label": true
+      }}}"#,
+    )
+    .unwrap();
+    patch.apply(&mut config);
+    let style = config.lap_data.bar_style;
+    assert_eq!(style.alpha, 0.6);
+    assert!(style.show_value_label);
+    assert_eq!(
+      style.label_format,
+      BarLabel::DeltaSeconds
+    );
+  }
+
+  #[test]
+  fn test_bar_axis_patch() {
+    let mut config = RouteVideoConfig::default();
+    assert!(config.lap_data.bar_axis.is_none());
+
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"lap_data": {"bar_axis": {"tick_interval_s": 15.0}}}"#,
+    )
+    .unwrap();
+    patch.apply(&mut config);
+    let axis = config.lap_data.bar_axis.unwrap();
+    assert_eq!(axis.tick_interval_s, 15.0);
+    assert!(axis.show_labels);
+  }
+
+  #[test]
+  fn test_normalization_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{
+        "lap_data": {"pace_bar_normalization": "z_score_clamped"},
+        "segment_coloring": {"elevation_gradient": {
+          "low": "blue",
+          "high": "red",
+          "normalization": {"percentile": {"low": 5.0, "high": 95.0}}
+        }}
+      }"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(
+      config.lap_data.pace_bar_normalization,
+      Normalization::MinMax
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.lap_data.pace_bar_normalization,
+      Normalization::ZScoreClamped
+    );
+    assert!(matches!(
+      config.segment_coloring,
+      RouteSegmentColoring::ElevationGradient {
+        range: None,
+        normalization: Normalization::Percentile {
+          low: 5.0,
+          high: 95.0
+        },
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn test_direction_arrows_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"direction_arrows": {"every_m": 500, "color": "yellow"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+
+    let arrows = config.direction_arrows.unwrap();
+    assert_eq!(arrows.every_m, 500.0);
+    assert_eq!(
+      arrows.color.to_bgra(),
+      Color::Yellow.to_bgra()
+    );
+    assert_eq!(arrows.size_px, 14);
+  }
+
+  #[test]
+  fn test_glitch_style_patch() {
+    let patch: RouteImageConfigPatch =
+      serde_json::from_str(r#"{"glitch_style": {"max_speed_mps": 20}}"#)
+        .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    assert!(config.glitch_style.is_none());
+    patch.apply(&mut config);
+
+    let style = config.glitch_style.unwrap();
+    assert_eq!(style.max_speed_mps, 20.0);
+    assert!(style.dashed);
+    assert_eq!(style.color, [128.0, 128.0, 128.0, 0.0]);
+  }
+
+  #[test]
+  fn test_cadence_chart_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"cadence_chart": {"size": [400, 100], "target_line": 180}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    let chart = config.cadence_chart.unwrap();
+    assert_eq!(chart.size, (400, 100));
+    assert_eq!(chart.target_line, Some(180));
+    assert_eq!(chart.position, (0.05, 0.7));
+  }
+
+  #[test]
+  fn test_number_format_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"number_format": {"stride_decimals": 1, "pace_rounding": "floor"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+
+    let format = config.number_format;
+    assert_eq!(format.stride_decimals, 1);
+    assert_eq!(
+      format.pace_rounding,
+      PaceRounding::Floor
+    );
+    assert_eq!(format.distance_decimals, 2);
+  }
+
+  #[test]
+  fn test_live_preview_patch() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"live_preview": {"every_frames": 60}}"#)
+        .unwrap();
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    let preview = config.live_preview.unwrap();
+    assert_eq!(preview.every_frames, 60);
+    assert_eq!(
+      preview.path_for("out/run.mp4"),
+      PathBuf::from("out/preview_latest.png")
+    );
+
+    // A client cannot pick where the preview is written, but a path set
+    // on the Rust side survives the patch
+    assert!(
+      serde_json::from_str::<RouteVideoConfigPatch>(
+        r#"{"live_preview": {"path": "/etc/cron.d/job"}}"#
+      )
+      .is_err()
+    );
+    config.live_preview = Some(LivePreview {
+      path: Some(PathBuf::from("outputs/peek.png")),
+      ..Default::default()
+    });
+    patch.apply(&mut config);
+    let preview = config.live_preview.unwrap();
+    assert_eq!(preview.every_frames, 60);
+    assert_eq!(
+      preview.path,
+      Some(PathBuf::from("outputs/peek.png"))
+    );
+  }
+
+  #[test]
+  fn test_course_preview_patch() {
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"course_preview": {"alpha": 0.5}}"#).unwrap();
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    let preview = config.course_preview.unwrap();
+    assert_eq!(preview.alpha, 0.5);
+    assert_eq!(preview.thickness, 2);
+  }
+
+  #[test]
+  fn test_date_stamp_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"date_stamp": {"format": "%d/%m/%Y", "corner": "top_left"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+    assert!(config.show_date);
+    assert_eq!(config.date_stamp.format, "%d/%m/%Y");
+    assert_eq!(
+      config.date_stamp.corner,
+      Corner::TopLeft
+    );
+
+    // Bad format strings are rejected with the rest of the config
+    let err = serde_json::from_str::<RouteImageConfigPatch>(
+      r#"{"date_stamp": {"format": "%Q"}}"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Invalid date format"));
+  }
+
+  #[test]
+  fn test_background_fx_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"background_fx": [{"mapify": {"strength": 0.8}},
+        {"darken": {"factor": 0.7}}]}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.background_fx,
+      vec![
+        BackgroundFx::Mapify { strength: 0.8 },
+        BackgroundFx::Darken { factor: 0.7 },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_steepest_climb_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"steepest_climb": {"window_m": 400, "color": "red_orange"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+    assert!(config.highlight_steepest_climb);
+    assert_eq!(config.steepest_climb.window_m, 400.0);
+    assert!(config.steepest_climb.show_label);
+
+    let patch: RouteVideoConfigPatch =
+      serde_json::from_str(r#"{"highlight_steepest_climb": false}"#).unwrap();
+    patch.apply(&mut config);
+    assert!(!config.highlight_steepest_climb);
+  }
+
+  #[test]
+  fn test_loop_detection_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"loop_detection": {"min_loop_m": 350, "corner": "top_right"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.png".into(),
+    );
+    patch.apply(&mut config);
+    assert!(config.show_detected_loops);
+    assert_eq!(config.loop_detection.min_loop_m, 350.0);
+    assert_eq!(config.loop_detection.proximity_m, 25.0);
+    assert_eq!(
+      config.loop_detection.corner,
+      Corner::TopRight
+    );
+  }
+
+  #[test]
+  fn test_output_options_patch() {
+    let patch: RouteImageConfigPatch = serde_json::from_str(
+      r#"{"output_options": {"format": {"jpeg": {"quality": 80}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteImageConfig::default(
+      "a.fit".into(),
+      "a.jpg".into(),
+      "a.jpg".into(),
+    );
+    patch.apply(&mut config);
+    assert_eq!(
+      config.output_options.format,
+      Some(ImageFormat::Jpeg { quality: 80 })
+    );
+
+    // Out-of-range quality is rejected with the rest of the config
+    let err = serde_json::from_str::<RouteImageConfigPatch>(
+      r#"{"output_options": {"format": {"jpeg": {"quality": 101}}}}"#,
+    )
+    .unwrap_err();
+    assert!(err
+      .to_string()
+      .contains("JPEG quality 101 is out of range (0-100)"));
+  }
+
+  #[test]
+  fn test_dynamic_patch_rejects_static_fields() {
+    let patch: DynamicConfigPatch = serde_json::from_str(
+      r#"{"pace_dist": {"font_scale": 1.2}, "output_file": "b.mp4"}"#,
+    )
+    .unwrap();
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+    assert_eq!(config.pace_dist.font_scale, 1.2);
+    assert_eq!(config.file_config.output_file, "b.mp4");
+
+    // The route scale is baked into the cached base frame
+    let err = serde_json::from_str::<DynamicConfigPatch>(
+      r#"{"route_scale": {"scale": 0.5}}"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("unknown field `route_scale`"));
+  }
+
+  #[test]
+  fn test_legend_patch() {
+    let patch: RouteVideoConfigPatch = serde_json::from_str(
+      r#"{"legend": {"orientation": "vertical", "label_format": "{value}ft"}}"#,
+    )
+    .unwrap();
+
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    let legend = config.legend.unwrap();
+    assert_eq!(
+      legend.orientation,
+      LegendOrientation::Vertical
+    );
+    assert_eq!(legend.width_px, 240);
+    assert_eq!(legend.label(1234.6), "1235ft");
+  }
+}
@@ -0,0 +1,229 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+
+use super::{
+  patch::RouteVideoConfigPatch,
+  video_config::{RouteVideoConfig, SafeArea, VideoCodec},
+};
+
+/// Output presets for social platforms.
+///
+/// A preset is a `RouteVideoConfigPatch`, so it is applied first and user
+/// settings are applied on top of it.
+///
+/// # Example
+/// ```
+/// use runarium::configs::{RouteVideoConfig, SocialPreset};
+///
+/// let preset = SocialPreset::YoutubeShort;
+/// let mut config = RouteVideoConfig::default();
+/// preset.patch().apply(&mut config);
+/// assert_eq!(config.output_size, Some((1080, 1920)));
+/// assert!(preset.validate(&config).is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocialPreset {
+  /// Instagram Reel (9:16)
+  InstagramReel,
+  /// YouTube Short (9:16)
+  YoutubeShort,
+  /// Twitter/X landscape video (16:9)
+  TwitterLandscape,
+  /// Square feed post (1:1)
+  Square,
+}
+
+impl SocialPreset {
+  /// Output size (width, height) in pixels
+  pub fn size(&self) -> (i32, i32) {
+    match self {
+      SocialPreset::InstagramReel | SocialPreset::YoutubeShort => (1080, 1920),
+      SocialPreset::TwitterLandscape => (1280, 720),
+      SocialPreset::Square => (1080, 1080),
+    }
+  }
+
+  /// Longest video the platform accepts, in seconds
+  pub fn max_duration_secs(&self) -> f64 {
+    match self {
+      SocialPreset::InstagramReel => 90.0,
+      SocialPreset::YoutubeShort => 60.0,
+      SocialPreset::TwitterLandscape => 140.0,
+      SocialPreset::Square => 60.0,
+    }
+  }
+
+  /// Margins kept clear of the platform's UI (captions, buttons)
+  pub fn safe_area(&self) -> SafeArea {
+    match self {
+      SocialPreset::InstagramReel => SafeArea::new(0.14, 0.2, 0.06, 0.06),
+      SocialPreset::YoutubeShort => SafeArea::new(0.1, 0.17, 0.05, 0.12),
+      SocialPreset::TwitterLandscape | SocialPreset::Square => {
+        SafeArea::uniform(0.05)
+      }
+    }
+  }
+
+  /// Recommended codec
+  pub fn codec(&self) -> VideoCodec {
+    VideoCodec::Avc1
+  }
+
+  /// Patch that pre-fills a config for this platform
+  pub fn patch(&self) -> RouteVideoConfigPatch {
+    RouteVideoConfigPatch {
+      output_size: Some(self.size()),
+      max_duration_secs: Some(self.max_duration_secs()),
+      codec: Some(self.codec()),
+      safe_area: Some(self.safe_area()),
+      ..Default::default()
+    }
+  }
+
+  /// Lists the settings in `config` that break this platform's limits
+  pub fn validate(&self, config: &RouteVideoConfig) -> Vec<PresetViolation> {
+    let mut violations = Vec::new();
+
+    let max = self.max_duration_secs();
+    if config.duration_secs > max {
+      violations.push(PresetViolation::DurationTooLong {
+        duration: config.duration_secs,
+        max,
+      });
+    }
+    if let Some(limit) = config.max_duration_secs {
+      if limit > max {
+        violations.push(PresetViolation::DurationTooLong {
+          duration: limit,
+          max,
+        });
+      }
+    }
+
+    let expected = self.size();
+    if let Some(actual) = config.output_size {
+      if actual != expected {
+        violations.push(PresetViolation::SizeMismatch { actual, expected });
+      }
+    }
+
+    violations
+  }
+}
+
+impl FromStr for SocialPreset {
+  type Err = Error;
+
+  /// Parses the snake_case name, e.g. `youtube_short`
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "instagram_reel" => Ok(SocialPreset::InstagramReel),
+      "youtube_short" => Ok(SocialPreset::YoutubeShort),
+      "twitter_landscape" => Ok(SocialPreset::TwitterLandscape),
+      "square" => Ok(SocialPreset::Square),
+      _ => Err(anyhow!("unknown preset `{}`", name)),
+    }
+  }
+}
+
+/// A setting that does not fit a social preset
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetViolation {
+  /// Video is longer than the platform accepts
+  DurationTooLong { duration: f64, max: f64 },
+  /// Output size differs from the platform's format
+  SizeMismatch {
+    actual: (i32, i32),
+    expected: (i32, i32),
+  },
+}
+
+impl fmt::Display for PresetViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PresetViolation::DurationTooLong { duration, max } => write!(
+        f,
+        "duration {:.0}s exceeds the platform limit of {:.0}s",
+        duration, max
+      ),
+      PresetViolation::SizeMismatch { actual, expected } => write!(
+        f,
+        "output size {}x{} differs from the platform format {}x{}",
+        actual.0, actual.1, expected.0, expected.1
+      ),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_preset_patch_fills_config() {
+    let mut config = RouteVideoConfig::default();
+    SocialPreset::TwitterLandscape.patch().apply(&mut config);
+
+    assert_eq!(config.output_size, Some((1280, 720)));
+    assert_eq!(config.max_duration_secs, Some(140.0));
+    assert_eq!(config.codec, VideoCodec::Avc1);
+    assert_eq!(
+      config.safe_area,
+      SafeArea::uniform(0.05)
+    );
+    // Settings outside the preset are untouched
+    assert_eq!(config.duration_secs, 15.0);
+  }
+
+  #[test]
+  fn test_user_settings_override_preset() {
+    let mut config = RouteVideoConfig::default();
+    SocialPreset::YoutubeShort.patch().apply(&mut config);
+
+    let user = RouteVideoConfigPatch::from_set_args(["codec=mp4v"]).unwrap();
+    user.apply(&mut config);
+    assert_eq!(config.codec, VideoCodec::Mp4v);
+    assert_eq!(config.output_size, Some((1080, 1920)));
+  }
+
+  #[test]
+  fn test_preset_from_str() {
+    assert_eq!(
+      "instagram_reel".parse::<SocialPreset>().unwrap(),
+      SocialPreset::InstagramReel
+    );
+    assert!("tiktok".parse::<SocialPreset>().is_err());
+  }
+
+  #[test]
+  fn test_validate_reports_violations() {
+    let preset = SocialPreset::YoutubeShort;
+    let mut config = RouteVideoConfig::default();
+    preset.patch().apply(&mut config);
+    assert!(preset.validate(&config).is_empty());
+
+    config.duration_secs = 75.0;
+    config.output_size = Some((1920, 1080));
+    let violations = preset.validate(&config);
+    assert_eq!(
+      violations,
+      vec![
+        PresetViolation::DurationTooLong {
+          duration: 75.0,
+          max: 60.0
+        },
+        PresetViolation::SizeMismatch {
+          actual: (1920, 1080),
+          expected: (1080, 1920)
+        },
+      ]
+    );
+    assert_eq!(
+      violations[0].to_string(),
+      "duration 75s exceeds the platform limit of 60s"
+    );
+  }
+}
@@ -1,8 +1,563 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
 // Re-export all config types for public API
-pub use super::config::{Color, FileConfig, Font, RouteColor, RouteScale};
+pub use super::config::{
+  ArrowConfig, AttributionConfig, BackgroundFx, BackgroundLayer,
+  BackgroundScaling, BackgroundTone, CadenceChartConfig, ClimbHighlightConfig,
+  Color, Corner, DateStampConfig, DateTimezone, DistanceRepair, ExtraField,
+  ExtraFieldKind, FileConfig, Font, GlitchStyle, GpsSmoothingConfig,
+  GpsSmoothingMethod, GradientDirection, Interp, LayerBlend, LayerSource,
+  LegendConfig, LegendOrientation, Limits, MarkerStyle, Normalization,
+  NumberFormat, PaceRounding, PercentPos, PixelPos, Position, PositionMarker,
+  RouteColor, RouteRenderStyle, RouteScale, RouteSegmentColoring, SeededStyle,
+  ShadowConfig, SmoothingConfig, SmoothingMethod, SpikeFilter, SportProfile,
+  StyleSeed, TimeBasis, Units, POWER_COLUMN_WIDTH,
+};
+pub use crate::utils::output_sink::OutputSink;
+
+/// How the drawn route advances from one frame to the next
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressionMode {
+  /// One GPS record per frame (uneven when records are unevenly spaced)
+  PerRecord,
+  /// A fixed distance per frame for a constant-speed "drawing pen"
+  PerDistance {
+    /// Meters the pen advances each frame
+    meters_per_frame: f64,
+  },
+}
+
+impl Default for ProgressionMode {
+  /// Creates default mode (one record per frame)
+  fn default() -> Self {
+    ProgressionMode::PerRecord
+  }
+}
+
+/// How fast the video moves through the frames `progression` plans
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacingCurve {
+  /// Every planned frame is shown
+  Constant,
+  /// Every planned frame of the first and last `edge_fraction` of the
+  /// route is shown; in between the pen eases up to `max_skip` planned
+  /// frames per video frame at the middle, then back down
+  EaseInOutRamp { max_skip: usize, edge_fraction: f64 },
+}
+
+impl Default for PacingCurve {
+  /// Creates default curve (constant)
+  fn default() -> Self {
+    PacingCurve::Constant
+  }
+}
+
+/// What a second of video stands for
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoTiming {
+  /// The planned frames are fitted into `duration_secs`, whatever the
+  /// activity's pace
+  PerPoint,
+  /// One video second covers `speedup` seconds of the activity, so the
+  /// pen moves as fast as the athlete did; pauses are shortened
+  RealTime { speedup: f64 },
+}
+
+impl Default for VideoTiming {
+  /// Creates default timing (per point)
+  fn default() -> Self {
+    VideoTiming::PerPoint
+  }
+}
+
+/// Memory/quality trade-off for video rendering
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryProfile {
+  /// Full feature set
+  Standard,
+  /// Avoids extra full-resolution frames (lap pauses hold without the
+  /// pulse highlight)
+  Low,
+}
+
+impl Default for MemoryProfile {
+  /// Creates default profile (standard)
+  fn default() -> Self {
+    MemoryProfile::Standard
+  }
+}
+
+/// Video codec used by the encoder
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoCodec {
+  /// MPEG-4 Part 2 (widest OpenCV support)
+  Mp4v,
+  /// H.264 (recommended for social platforms)
+  Avc1,
+  /// VP9
+  Vp09,
+}
+
+impl VideoCodec {
+  /// Every supported codec
+  pub const ALL: [VideoCodec; 3] =
+    [VideoCodec::Mp4v, VideoCodec::Avc1, VideoCodec::Vp09];
+
+  /// FourCC code as a string, e.g. `"mp4v"`
+  pub fn name(&self) -> String {
+    self.fourcc().iter().collect()
+  }
+
+  /// FourCC code passed to the video writer
+  pub fn fourcc(&self) -> [char; 4] {
+    match self {
+      VideoCodec::Mp4v => ['m', 'p', '4', 'v'],
+      VideoCodec::Avc1 => ['a', 'v', 'c', '1'],
+      VideoCodec::Vp09 => ['v', 'p', '0', '9'],
+    }
+  }
+}
+
+impl Default for VideoCodec {
+  /// Creates default codec (mp4v)
+  fn default() -> Self {
+    VideoCodec::Mp4v
+  }
+}
+
+/// What to write when the OpenCV build cannot open the video codec
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoFallback {
+  /// Fail before rendering
+  Error,
+  /// PNG frames in a `<output name>_frames` directory next to the output
+  FrameSequence,
+  /// Animated GIF next to the output (slow and large; a last resort)
+  Gif,
+}
+
+impl Default for VideoFallback {
+  /// Creates default fallback (error)
+  fn default() -> Self {
+    VideoFallback::Error
+  }
+}
+
+/// PNG of the latest frame, rewritten while a video renders so a long
+/// render can be checked before it finishes
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LivePreview {
+  /// Where the PNG is written (None = `preview_latest.png` next to the
+  /// output)
+  pub path: Option<PathBuf>,
+  /// Frames between rewrites
+  pub every_frames: usize,
+}
+
+impl LivePreview {
+  /// Path of the preview of a video written to `output_file`
+  pub fn path_for(&self, output_file: &str) -> PathBuf {
+    self.path.clone().unwrap_or_else(|| {
+      Path::new(output_file).with_file_name("preview_latest.png")
+    })
+  }
+}
+
+impl Default for LivePreview {
+  /// Creates default preview (next to the output, every 300 frames)
+  fn default() -> Self {
+    Self {
+      path: None,
+      every_frames: 300,
+    }
+  }
+}
+
+/// The whole route drawn faintly from the first frame, which the
+/// progressive line then fills in.
+///
+/// The route line is 4 px wide; a thicker preview shows as a halo around
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CoursePreview {
+  /// Line color (BGRA)
+  pub color: [f64; 4],
+  /// Line thickness (pixels)
+  pub thickness: i32,
+  /// Opacity over the background (0.0-1.0)
+  pub alpha: f64,
+}
+
+impl Default for CoursePreview {
+  /// Creates default preview (light gray, 2 px, 35% opaque)
+  fn default() -> Self {
+    Self {
+      color: [200.0, 200.0, 200.0, 0.0],
+      thickness: 2,
+      alpha: 0.35,
+    }
+  }
+}
+
+/// Which frames `RenderOutput::time_index` lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeIndexSampling {
+  /// No time index
+  Off,
+  /// Every `n`th frame and the last one
+  EveryFrames(usize),
+  /// The frame each lap ends on
+  LapBoundaries,
+}
+
+impl Default for TimeIndexSampling {
+  /// Creates default sampling (off)
+  fn default() -> Self {
+    TimeIndexSampling::Off
+  }
+}
+
+/// Which sessions of a multisport FIT file are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSelection {
+  /// All sessions as one route, the line running on between them
+  Joined,
+  /// Only the session at this index, from 0
+  Index(usize),
+  /// All sessions, with no line between one session's end and the next
+  /// session's start
+  Separate,
+}
+
+impl Default for SessionSelection {
+  /// Creates default selection (joined)
+  fn default() -> Self {
+    SessionSelection::Joined
+  }
+}
+
+/// What the margins of a `SafeArea` are measured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafeAreaUnit {
+  /// Fractions 0.0-1.0 of the frame size
+  Fraction,
+  /// Pixels
+  Pixels,
+}
+
+impl Default for SafeAreaUnit {
+  /// Creates default unit (fractions)
+  fn default() -> Self {
+    SafeAreaUnit::Fraction
+  }
+}
+
+/// Frame margins that overlays stay inside, e.g. to avoid platform UI
+/// chrome. Overlays that would cross a margin are shifted, not clipped.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct SafeArea {
+  pub top: f64,
+  pub bottom: f64,
+  pub left: f64,
+  pub right: f64,
+  /// Unit of the margins (fractions by default)
+  #[serde(default)]
+  pub unit: SafeAreaUnit,
+}
+
+impl SafeArea {
+  /// Creates a new SafeArea with margins as fractions of the frame size
+  pub fn new(top: f64, bottom: f64, left: f64, right: f64) -> Self {
+    Self {
+      top,
+      bottom,
+      left,
+      right,
+      unit: SafeAreaUnit::Fraction,
+    }
+  }
+
+  /// Creates a new SafeArea with margins in pixels
+  pub fn pixels(top: f64, bottom: f64, left: f64, right: f64) -> Self {
+    Self {
+      unit: SafeAreaUnit::Pixels,
+      ..Self::new(top, bottom, left, right)
+    }
+  }
+
+  /// Same margin on every side
+  pub fn uniform(margin: f64) -> Self {
+    Self::new(margin, margin, margin, margin)
+  }
+}
+
+/// Overlay elements that can be layered over the route
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayKind {
+  /// Lap statistics panel
+  LapPanel,
+  /// Bottom pace/distance bar
+  BottomBar,
+  /// Watermark (reserved, not rendered yet)
+  Watermark,
+  /// Elevation panel (reserved, not rendered yet)
+  Elevation,
+  /// Text annotation (reserved, not rendered yet)
+  Annotation,
+  /// Caller-provided overlay identified by a hook id (reserved)
+  Custom(String),
+}
+
+impl OverlayKind {
+  /// Whether the overlay is drawn once into the base frame (beneath the
+  /// route) rather than on every frame
+  pub fn is_static(&self) -> bool {
+    matches!(
+      self,
+      OverlayKind::LapPanel | OverlayKind::Watermark | OverlayKind::Annotation
+    )
+  }
+}
+
+/// An overlay with its draw order and visibility
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OverlayElement {
+  /// What to draw
+  pub kind: OverlayKind,
+  /// Draw order; lower values are drawn first (underneath)
+  pub z_index: i32,
+  /// Whether the overlay is drawn
+  pub enabled: bool,
+}
+
+impl OverlayElement {
+  /// Creates a new enabled OverlayElement
+  pub fn new(kind: OverlayKind, z_index: i32) -> Self {
+    Self {
+      kind,
+      z_index,
+      enabled: true,
+    }
+  }
+}
+
+/// Per-record stat shown by a `StatChip`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+  /// Current pace (min/km)
+  Pace,
+  /// Distance covered (km)
+  Distance,
+  /// Current heart rate (bpm)
+  HeartRate,
+  /// Current cadence (spm, or rpm when cycling)
+  Cadence,
+  /// Time since the first record; moving time (frozen during pauses) with
+  /// `RouteVideoConfig::time_basis` set to `Moving`
+  ElapsedTime,
+  /// Elevation gained so far (m)
+  ElevationGain,
+  /// Compass bearing of travel, e.g. "NE 47°", smoothed over the last few
+  /// GPS segments
+  Bearing,
+  /// Grade of the route at the pen (%), smoothed over the altitudes
+  /// around it
+  CurrentGrade,
+  /// Latitude and longitude of the record at the pen, as recorded (no
+  /// smoothing or interpolation), e.g. "13.7563°N, 100.5018°E"
+  Coordinates,
+}
+
+/// Look of a `StatChip`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChipStyle {
+  pub font_scale: f64,
+  pub thickness: i32,
+  pub font: Font,
+  pub text_color: Color,
+  /// Pill background color
+  pub background: Color,
+  /// Background opacity (0.0-1.0)
+  pub opacity: f64,
+  /// Space between the text and the pill edge (pixels)
+  pub padding: i32,
+}
+
+impl Default for ChipStyle {
+  /// Creates default style (white text on a half-transparent black pill)
+  fn default() -> Self {
+    Self {
+      font_scale: 0.6,
+      thickness: 1,
+      font: Font::Simplex,
+      text_color: Color::White,
+      background: Color::Black,
+      opacity: 0.5,
+      padding: 10,
+    }
+  }
+}
+
+/// Small floating widget showing one live stat
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StatChip {
+  /// Stat to show
+  pub metric: Metric,
+  /// Center of the chip, as a fraction of the frame or in pixels; chips
+  /// are kept inside the safe area
+  pub position: Position,
+  #[serde(default)]
+  pub style: ChipStyle,
+  /// Hide the chip while its stat has no data instead of showing "--"
+  #[serde(default)]
+  pub hide_when_missing: bool,
+  /// Draw an arrow pointing along the bearing after the text (`Bearing`
+  /// chips only)
+  #[serde(default)]
+  pub bearing_arrow: bool,
+}
+
+impl StatChip {
+  /// Creates a new StatChip with the default style
+  pub fn new(metric: Metric, position: impl Into<Position>) -> Self {
+    Self {
+      metric,
+      position: position.into(),
+      style: ChipStyle::default(),
+      hide_when_missing: false,
+      bearing_arrow: false,
+    }
+  }
+}
+
+/// Dims the route behind the pen: the most recent `window_m` meters blend
+/// from the route color to `faded_color`, older parts are fully faded
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct FadeConfig {
+  /// Length of the bright window behind the pen (meters)
+  pub window_m: f64,
+  /// Color of the route beyond the window
+  pub faded_color: Color,
+}
+
+impl FadeConfig {
+  /// Creates a new FadeConfig
+  pub fn new(window_m: f64, faded_color: Color) -> Self {
+    Self {
+      window_m,
+      faded_color,
+    }
+  }
+}
+
+/// Hold the animation briefly each time a lap completes
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct LapPause {
+  /// How long to hold the frame at each lap boundary (seconds)
+  pub hold_seconds: f64,
+  /// Whether to pulse the finished lap's row in the lap panel while holding
+  pub pulse_highlight: bool,
+}
+
+impl LapPause {
+  /// Creates a new LapPause
+  pub fn new(hold_seconds: f64, pulse_highlight: bool) -> Self {
+    Self {
+      hold_seconds,
+      pulse_highlight,
+    }
+  }
+}
+
+impl Default for LapPause {
+  /// Creates default pause (0.5 s hold with pulse)
+  fn default() -> Self {
+    Self {
+      hold_seconds: 0.5,
+      pulse_highlight: true,
+    }
+  }
+}
+
+/// Closing card held after the route is drawn: a ring around the total
+/// distance fills with the accent color over the hold
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EndCardConfig {
+  /// How long the card is held (seconds); the ring fills over this time
+  pub end_hold_seconds: f64,
+  /// Color of the filled ring
+  pub accent_color: Color,
+  /// Ring radius as a fraction of the smaller safe area side
+  pub radius: f64,
+  /// Ring thickness (pixels)
+  pub thickness: i32,
+}
+
+impl Default for EndCardConfig {
+  /// Creates default card (2 s orange ring)
+  fn default() -> Self {
+    Self {
+      end_hold_seconds: 2.0,
+      accent_color: Color::Orange,
+      radius: 0.2,
+      thickness: 14,
+    }
+  }
+}
+
+/// How the ticker moves from one metric to the next
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TickerTransition {
+  /// Switch at once
+  Cut,
+  /// Slide the next metric in from the right while the previous one
+  /// leaves to the left
+  Slide,
+}
+
+/// What the bottom bar shows
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BottomBarMode {
+  /// Pace on the left, distance on the right
+  Static,
+  /// One metric at a time, cycling through `metrics`; metrics the activity
+  /// has no data for are skipped
+  Ticker {
+    metrics: Vec<Metric>,
+    /// How long each metric is shown
+    seconds_per_metric: f64,
+    transition: TickerTransition,
+  },
+}
+
+impl Default for BottomBarMode {
+  /// Creates default mode (static pace and distance)
+  fn default() -> Self {
+    BottomBarMode::Static
+  }
+}
 
 /// Configuration for pace and distance display
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PaceDistConfig {
   /// Font scale for pace/distance text
   pub font_scale: f64,
@@ -11,11 +566,32 @@ pub struct PaceDistConfig {
   /// Font family for text
   pub font: Font,
   /// Position for the pace/distance bar (None = auto)
-  pub position: Option<(i32, i32)>,
+  pub position: Option<Position>,
   /// Whether to show pace
   pub show_pace: bool,
   /// Whether to show distance
   pub show_distance: bool,
+  /// Show the heart rate of the record at the pen next to the pace
+  /// ("HR: 152 bpm"); nothing is shown at records without one
+  #[serde(default)]
+  pub show_heart_rate: bool,
+  /// Show the power of the record at the pen after the heart rate
+  /// ("Pwr: 214 W"); nothing is shown at records without one
+  #[serde(default)]
+  pub show_power: bool,
+  /// Show the cadence of the record at the pen in the middle of the bar
+  /// (steps per minute, or rpm when cycling); nothing is shown at records
+  /// without one
+  #[serde(default)]
+  pub show_cadence: bool,
+  /// Label of the cadence (None = "Cadence")
+  #[serde(default)]
+  pub cadence_label: Option<String>,
+  /// Count the distance up smoothly between records (needs an
+  /// interpolating progression mode, otherwise a no-op)
+  pub smooth_counter: bool,
+  /// Static pace and distance, or a rotating ticker
+  pub mode: BottomBarMode,
 }
 
 impl PaceDistConfig {
@@ -24,7 +600,7 @@ impl PaceDistConfig {
     font_scale: f64,
     thickness: i32,
     font: Font,
-    position: Option<(i32, i32)>,
+    position: Option<Position>,
     show_pace: bool,
     show_distance: bool,
   ) -> Self {
@@ -35,6 +611,12 @@ impl PaceDistConfig {
       position,
       show_pace,
       show_distance,
+      show_heart_rate: false,
+      show_power: false,
+      show_cadence: false,
+      cadence_label: None,
+      smooth_counter: false,
+      mode: BottomBarMode::Static,
     }
   }
 
@@ -47,6 +629,12 @@ impl PaceDistConfig {
       position: None,
       show_pace: true,
       show_distance: true,
+      show_heart_rate: false,
+      show_power: false,
+      show_cadence: false,
+      cadence_label: None,
+      smooth_counter: false,
+      mode: BottomBarMode::Static,
     }
   }
 
@@ -59,6 +647,12 @@ impl PaceDistConfig {
       position: None,
       show_pace: true,
       show_distance: false,
+      show_heart_rate: false,
+      show_power: false,
+      show_cadence: false,
+      cadence_label: None,
+      smooth_counter: false,
+      mode: BottomBarMode::Static,
     }
   }
 }
@@ -73,15 +667,148 @@ impl Default for PaceDistConfig {
       position: None,
       show_pace: true,
       show_distance: true,
+      show_heart_rate: false,
+      show_power: false,
+      show_cadence: false,
+      cadence_label: None,
+      smooth_counter: false,
+      mode: BottomBarMode::Static,
+    }
+  }
+}
+
+/// Record metric shown in the bottom bar, such as running power or ground
+/// contact time from a foot pod's developer fields
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CustomMetricConfig {
+  /// Field name, matched like `ExtraField::match_name` (e.g. "Ground
+  /// Time")
+  pub field_name: String,
+  /// Label before the value, also the key in `RouteData::extra`
+  pub label: String,
+  /// Unit shown after the value (e.g. "ms")
+  #[serde(default)]
+  pub unit: String,
+}
+
+impl CustomMetricConfig {
+  /// Creates a metric read from `field_name`
+  pub fn new(field_name: &str, label: &str, unit: &str) -> Self {
+    Self {
+      field_name: field_name.to_string(),
+      label: label.to_string(),
+      unit: unit.to_string(),
+    }
+  }
+
+  /// Numeric extra field capturing the metric
+  pub fn extra_field(&self) -> ExtraField {
+    ExtraField::numeric(
+      &self.field_name,
+      &self.label,
+      &self.unit,
+    )
+  }
+
+  /// Formats a value with the label and unit, e.g. "GCT: 244 ms"
+  pub fn format(&self, value: f64) -> String {
+    format!(
+      "{}: {:.0} {}",
+      self.label, value, self.unit
+    )
+    .trim_end()
+    .to_string()
+  }
+}
+
+/// Where the laps of the lap panel come from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LapSource {
+  /// Laps recorded by the device (none for apps that record no laps)
+  Device,
+  /// Splits of `every_km` kilometers built from the records, whatever
+  /// the device recorded
+  AutoSplits { every_km: f64 },
+  /// Device laps, or 1 km splits when the device recorded none
+  Auto,
+}
+
+impl Default for LapSource {
+  /// Creates default source (device laps, or 1 km splits without them)
+  fn default() -> Self {
+    LapSource::Auto
+  }
+}
+
+/// Value printed past the end of each pace bar, against the fastest lap
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarLabel {
+  /// Seconds slower than the fastest lap, e.g. "+14 s"
+  DeltaSeconds,
+  /// Percent slower than the fastest lap, e.g. "+5%"
+  Percent,
+}
+
+impl Default for BarLabel {
+  /// Creates default label (seconds behind the fastest lap)
+  fn default() -> Self {
+    BarLabel::DeltaSeconds
+  }
+}
+
+/// Look of the lap panel's pace bars
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BarStyle {
+  /// Opacity of the bars over the background (0.0-1.0)
+  pub alpha: f64,
+  /// Print how each lap compares to the fastest just past its bar
+  pub show_value_label: bool,
+  pub label_format: BarLabel,
+}
+
+impl Default for BarStyle {
+  /// Creates default style (opaque bars without labels)
+  fn default() -> Self {
+    Self {
+      alpha: 1.0,
+      show_value_label: false,
+      label_format: BarLabel::default(),
+    }
+  }
+}
+
+/// Pace scale under the lap panel's pace bars: a tick and a faint
+/// gridline at each round pace between the fastest and slowest lap
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BarAxis {
+  /// Seconds between ticks; widened when there would be too many labels
+  pub tick_interval_s: f64,
+  /// Color of the ticks, gridlines and labels (BGRA)
+  pub color: [f64; 4],
+  /// Print the pace of each tick, e.g. "4:30"
+  pub show_labels: bool,
+}
+
+impl Default for BarAxis {
+  /// Creates default axis (ticks every 30 s, white, labeled)
+  fn default() -> Self {
+    Self {
+      tick_interval_s: 30.0,
+      color: [255.0, 255.0, 255.0, 0.0],
+      show_labels: true,
     }
   }
 }
 
 /// Complete configuration for route video generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LapDataConfig {
-  /// Position of the lap panel as percentage (x_percent, y_percent) where 0.0-1.0
-  pub position: (f64, f64),
+  /// Position of the lap panel, as a fraction of the frame or in pixels
+  pub position: Position,
   /// Font scale (fixed at 0.5)
   pub font_scale: f64,
   /// Text thickness (fixed at 1)
@@ -92,17 +819,34 @@ pub struct LapDataConfig {
   pub text_color: Color,
   /// Whether to show heart rate
   pub show_heart_rate: bool,
+  /// Show a PWR column of lap average power (W) next to the heart rate
+  #[serde(default)]
+  pub show_power: bool,
   /// Whether to show stride length
   pub show_stride_length: bool,
   /// Whether to show pace bars
   pub show_pace_bars: bool,
+  /// Which lap paces the bar scale spans; slower laps are clamped
+  pub pace_bar_normalization: Normalization,
+  /// Opacity and value labels of the pace bars
+  #[serde(default)]
+  pub bar_style: BarStyle,
+  /// Pace scale under the pace bars (None = bars without a scale)
+  #[serde(default)]
+  pub bar_axis: Option<BarAxis>,
+  /// Show the activity's total elevation gain above the column labels,
+  /// e.g. "Elev +412 m"
+  #[serde(default)]
+  pub show_elevation_gain: bool,
+  /// Where the laps come from: the device, or splits of the records
+  pub source: LapSource,
 }
 
 impl LapDataConfig {
   /// Creates a new LapDataConfig with custom settings
   #[allow(clippy::too_many_arguments)]
   pub fn new(
-    position: (f64, f64),
+    position: impl Into<Position>,
     font_scale: f64,
     thickness: i32,
     font: Font,
@@ -112,42 +856,70 @@ impl LapDataConfig {
     show_pace_bars: bool,
   ) -> Self {
     Self {
-      position,
+      position: position.into(),
       font_scale,
       thickness,
       font,
       text_color,
       show_heart_rate,
+      show_power: false,
       show_stride_length,
       show_pace_bars,
+      pace_bar_normalization: Normalization::MinMax,
+      bar_style: BarStyle::default(),
+      bar_axis: None,
+      show_elevation_gain: false,
+      source: LapSource::Auto,
     }
   }
 
   /// Creates minimal configuration (pace only, no extras)
   pub fn minimal() -> Self {
     Self {
-      position: (0.5, 0.09), // 50% x, 9% y
+      position: (0.5, 0.09).into(), // 50% x, 9% y
       font_scale: 0.5,
       thickness: 1,
       font: Font::Simplex,
       text_color: Color::White,
       show_heart_rate: false,
+      show_power: false,
       show_stride_length: false,
       show_pace_bars: true,
+      pace_bar_normalization: Normalization::MinMax,
+      bar_style: BarStyle::default(),
+      bar_axis: None,
+      show_elevation_gain: false,
+      source: LapSource::Auto,
     }
   }
 
   /// Creates detailed configuration (all stats, larger bars)
   pub fn detailed() -> Self {
     Self {
-      position: (0.5, 0.07), // 50% x, 7% y
+      position: (0.5, 0.07).into(), // 50% x, 7% y
       font_scale: 0.5,
       thickness: 1,
       font: Font::Simplex,
       text_color: Color::White,
       show_heart_rate: true,
+      show_power: false,
       show_stride_length: true,
       show_pace_bars: true,
+      pace_bar_normalization: Normalization::MinMax,
+      bar_style: BarStyle::default(),
+      bar_axis: None,
+      show_elevation_gain: false,
+      source: LapSource::Auto,
+    }
+  }
+
+  /// How far the columns after the heart rate move right to make room
+  /// for the power column (0 when it is hidden)
+  pub fn power_shift(&self) -> i32 {
+    if self.show_power {
+      POWER_COLUMN_WIDTH
+    } else {
+      0
     }
   }
 }
@@ -156,20 +928,26 @@ impl Default for LapDataConfig {
   /// Creates default configuration
   fn default() -> Self {
     Self {
-      position: (0.5, 0.09), // 50% x, 9% y
+      position: (0.5, 0.09).into(), // 50% x, 9% y
       font_scale: 0.5,
       thickness: 1,
       font: Font::Simplex,
       text_color: Color::White,
       show_heart_rate: true,
+      show_power: false,
       show_stride_length: true,
       show_pace_bars: true,
+      pace_bar_normalization: Normalization::MinMax,
+      bar_style: BarStyle::default(),
+      bar_axis: None,
+      show_elevation_gain: false,
+      source: LapSource::Auto,
     }
   }
 }
 
 /// Complete configuration for route video generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RouteVideoConfig {
   /// Route scaling and positioning
   pub route_scale: RouteScale,
@@ -187,6 +965,129 @@ pub struct RouteVideoConfig {
   pub show_route: bool,
   /// Whether to show the lap data panel
   pub show_lap_data: bool,
+  /// How the route advances each frame
+  pub progression: ProgressionMode,
+  /// Speed-up through the middle of the route (Constant = none)
+  pub pacing: PacingCurve,
+  /// Duration-fitted or real-time playback (RealTime ignores
+  /// `progression`, `pacing` and `duration_secs`)
+  pub timing: VideoTiming,
+  /// Pause at lap boundaries (None = no pause)
+  pub lap_pause: Option<LapPause>,
+  /// Card held after the last frame (None = the video ends with the route)
+  pub end_card: Option<EndCardConfig>,
+  /// Overlay draw order (empty = derived from the `show_*` flags)
+  pub overlays: Vec<OverlayElement>,
+  /// Memory/quality trade-off
+  pub memory_profile: MemoryProfile,
+  /// Output video size (None = background image size, max 1080)
+  pub output_size: Option<(i32, i32)>,
+  /// Target video duration in seconds (excluding lap pauses)
+  pub duration_secs: f64,
+  /// Platform duration limit in seconds (None = unlimited)
+  pub max_duration_secs: Option<f64>,
+  /// Video codec
+  pub codec: VideoCodec,
+  /// What to write instead when the codec cannot be opened
+  pub fallback: VideoFallback,
+  /// Frames listed in the output's time index, for syncing audio
+  pub time_index: TimeIndexSampling,
+  /// Margins that overlays stay inside
+  pub safe_area: SafeArea,
+  /// Outline the safe area on every frame, for tuning margins
+  pub debug_draw_safe_area: bool,
+  /// Sport-specific stats and labels (Auto = from the FIT file)
+  pub sport: SportProfile,
+  /// Extra record fields to read from the FIT file
+  pub extra_record_fields: Vec<ExtraField>,
+  /// Label of the extra field shown in the bottom bar and lap panel
+  pub display_extra_field: Option<String>,
+  /// Record metrics (e.g. developer fields) shown in the bottom bar after
+  /// the pace, each captured like an extra record field
+  pub custom_metrics: Vec<CustomMetricConfig>,
+  /// Pin encoder settings so identical inputs give byte-identical videos
+  pub deterministic: bool,
+  /// Write the config next to the output as `<output>.config.json`
+  pub embed_config: bool,
+  /// Hard limits on records, output size, frames and duration
+  pub limits: Limits,
+  /// What is done to record distances that go backwards
+  pub distance_repair: DistanceRepair,
+  /// Replacement of GPS positions that jump implausibly far (None = kept
+  /// as recorded)
+  pub spike_filter: Option<SpikeFilter>,
+  /// Most records rendered; denser activities are simplified down to
+  /// this many, keeping the shape of the route (None = every record)
+  pub max_points: Option<usize>,
+  /// Also write the route as GeoJSON here, after it is repaired,
+  /// filtered and simplified as rendered (None = no GeoJSON)
+  pub export_geojson: Option<String>,
+  /// Also write the records as CSV here, and the laps next to it as
+  /// `<name>_laps.csv`, as rendered (None = no CSV)
+  pub export_csv: Option<String>,
+  /// Sessions of a multisport FIT file that are rendered
+  pub sessions: SessionSelection,
+  /// Whether the route line runs on across timer pauses and gaps in the
+  /// records instead of breaking there
+  pub connect_pauses: bool,
+  /// Floating stat widgets drawn over everything else
+  pub chips: Vec<StatChip>,
+  /// How route segments are colored
+  pub segment_coloring: RouteSegmentColoring,
+  /// Shadow under the route line (None = no shadow)
+  pub route_shadow: Option<ShadowConfig>,
+  /// Smoothing of the drawn route line (None = straight segments)
+  pub line_smoothing: Option<SmoothingConfig>,
+  /// Smoothing of the GPS positions before they are projected (None =
+  /// as recorded)
+  pub gps_smoothing: Option<GpsSmoothingConfig>,
+  /// Marker at the current position
+  pub position_marker: PositionMarker,
+  /// Route drawn as a line, dots or both
+  pub render_style: RouteRenderStyle,
+  /// Fade the route line behind the pen (None = no fade)
+  pub route_fade: Option<FadeConfig>,
+  /// Whole route drawn faintly under the progressive line (None = no
+  /// preview)
+  pub course_preview: Option<CoursePreview>,
+  /// Color legend for gradient coloring (None = no legend)
+  pub legend: Option<LegendConfig>,
+  /// Whether to stamp the activity start date
+  pub show_date: bool,
+  /// Date stamp format, time zone and placement
+  pub date_stamp: DateStampConfig,
+  /// Whether to replace the bottom bar of the last route frame with the
+  /// session totals ("10.02 km · 52:31 · 531 kcal · 156 bpm")
+  pub show_summary: bool,
+  /// Background map credit
+  pub attribution: AttributionConfig,
+  /// Background layers composited in order, the first at the bottom
+  /// (empty = `file_config.background_image` alone)
+  pub backgrounds: Vec<BackgroundLayer>,
+  /// Filters applied in order to the background (empty = as loaded)
+  pub background_fx: Vec<BackgroundFx>,
+  /// Interpolation and upscaling of background images
+  pub background_scaling: BackgroundScaling,
+  /// Colors derived from a seed, replacing `colors` (None = `colors`)
+  pub seeded_style: Option<SeededStyle>,
+  /// Time shown by the elapsed time chip
+  pub time_basis: TimeBasis,
+  /// Cadence chart with a cursor at the current distance (None = no
+  /// chart)
+  pub cadence_chart: Option<CadenceChartConfig>,
+  /// Whether to highlight the steepest climb on the route
+  pub highlight_steepest_climb: bool,
+  /// How the steepest climb is found and drawn
+  pub steepest_climb: ClimbHighlightConfig,
+  /// Precision of distances, strides and paces
+  pub number_format: NumberFormat,
+  /// PNG of the latest frame, rewritten during the render and left
+  /// holding the last frame (None = no preview)
+  pub live_preview: Option<LivePreview>,
+  /// Run after the video is written, e.g. to upload it (None = keep the
+  /// file only). Not serialized, so it never reaches a sidecar.
+  #[serde(skip)]
+  pub on_complete: Option<OutputSink>,
 }
 
 impl RouteVideoConfig {
@@ -211,6 +1112,60 @@ impl RouteVideoConfig {
       show_bottom_bar,
       show_route,
       show_lap_data,
+      progression: ProgressionMode::default(),
+      pacing: PacingCurve::default(),
+      timing: VideoTiming::default(),
+      lap_pause: None,
+      end_card: None,
+      overlays: Vec::new(),
+      memory_profile: MemoryProfile::default(),
+      output_size: None,
+      duration_secs: 15.0,
+      max_duration_secs: None,
+      codec: VideoCodec::default(),
+      fallback: VideoFallback::Error,
+      time_index: TimeIndexSampling::Off,
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      sport: SportProfile::default(),
+      extra_record_fields: Vec::new(),
+      display_extra_field: None,
+      custom_metrics: Vec::new(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      max_points: None,
+      export_geojson: None,
+      export_csv: None,
+      sessions: SessionSelection::default(),
+      connect_pauses: false,
+      chips: Vec::new(),
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      position_marker: PositionMarker::default(),
+      render_style: RouteRenderStyle::default(),
+      route_fade: None,
+      course_preview: None,
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      time_basis: TimeBasis::default(),
+      cadence_chart: None,
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      number_format: NumberFormat::default(),
+      live_preview: None,
+      on_complete: None,
     }
   }
 
@@ -225,6 +1180,60 @@ impl RouteVideoConfig {
       show_route: true,
       show_lap_data: true,
       file_config: FileConfig::default(),
+      progression: ProgressionMode::default(),
+      pacing: PacingCurve::default(),
+      timing: VideoTiming::default(),
+      lap_pause: None,
+      end_card: None,
+      overlays: Vec::new(),
+      memory_profile: MemoryProfile::default(),
+      output_size: None,
+      duration_secs: 15.0,
+      max_duration_secs: None,
+      codec: VideoCodec::default(),
+      fallback: VideoFallback::Error,
+      time_index: TimeIndexSampling::Off,
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      sport: SportProfile::default(),
+      extra_record_fields: Vec::new(),
+      display_extra_field: None,
+      custom_metrics: Vec::new(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      max_points: None,
+      export_geojson: None,
+      export_csv: None,
+      sessions: SessionSelection::default(),
+      connect_pauses: false,
+      chips: Vec::new(),
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      position_marker: PositionMarker::default(),
+      render_style: RouteRenderStyle::default(),
+      route_fade: None,
+      course_preview: None,
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      time_basis: TimeBasis::default(),
+      cadence_chart: None,
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      number_format: NumberFormat::default(),
+      live_preview: None,
+      on_complete: None,
     }
   }
 
@@ -239,6 +1248,60 @@ impl RouteVideoConfig {
       show_route: true,
       show_lap_data: true,
       file_config: FileConfig::default(),
+      progression: ProgressionMode::default(),
+      pacing: PacingCurve::default(),
+      timing: VideoTiming::default(),
+      lap_pause: None,
+      end_card: None,
+      overlays: Vec::new(),
+      memory_profile: MemoryProfile::default(),
+      output_size: None,
+      duration_secs: 15.0,
+      max_duration_secs: None,
+      codec: VideoCodec::default(),
+      fallback: VideoFallback::Error,
+      time_index: TimeIndexSampling::Off,
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      sport: SportProfile::default(),
+      extra_record_fields: Vec::new(),
+      display_extra_field: None,
+      custom_metrics: Vec::new(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      max_points: None,
+      export_geojson: None,
+      export_csv: None,
+      sessions: SessionSelection::default(),
+      connect_pauses: false,
+      chips: Vec::new(),
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      position_marker: PositionMarker::default(),
+      render_style: RouteRenderStyle::default(),
+      route_fade: None,
+      course_preview: None,
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      time_basis: TimeBasis::default(),
+      cadence_chart: None,
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      number_format: NumberFormat::default(),
+      live_preview: None,
+      on_complete: None,
     }
   }
 
@@ -253,10 +1316,132 @@ impl RouteVideoConfig {
       show_route: true,
       show_lap_data: true,
       file_config: FileConfig::default(),
+      progression: ProgressionMode::default(),
+      pacing: PacingCurve::default(),
+      timing: VideoTiming::default(),
+      lap_pause: None,
+      end_card: None,
+      overlays: Vec::new(),
+      memory_profile: MemoryProfile::default(),
+      output_size: None,
+      duration_secs: 15.0,
+      max_duration_secs: None,
+      codec: VideoCodec::default(),
+      fallback: VideoFallback::Error,
+      time_index: TimeIndexSampling::Off,
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      sport: SportProfile::default(),
+      extra_record_fields: Vec::new(),
+      display_extra_field: None,
+      custom_metrics: Vec::new(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      max_points: None,
+      export_geojson: None,
+      export_csv: None,
+      sessions: SessionSelection::default(),
+      connect_pauses: false,
+      chips: Vec::new(),
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      position_marker: PositionMarker::default(),
+      render_style: RouteRenderStyle::default(),
+      route_fade: None,
+      course_preview: None,
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      time_basis: TimeBasis::default(),
+      cadence_chart: None,
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      number_format: NumberFormat::default(),
+      live_preview: None,
+      on_complete: None,
     }
   }
 }
 
+impl RouteVideoConfig {
+  /// Target duration capped by `max_duration_secs`
+  pub fn effective_duration(&self) -> f64 {
+    match self.max_duration_secs {
+      Some(max) => self.duration_secs.min(max),
+      None => self.duration_secs,
+    }
+  }
+
+  /// Fields to capture from the records: `extra_record_fields`, then the
+  /// fields of `custom_metrics` whose label is not taken yet
+  pub fn captured_fields(&self) -> Vec<ExtraField> {
+    let mut fields = self.extra_record_fields.clone();
+    for metric in &self.custom_metrics {
+      if !fields.iter().any(|field| field.label == metric.label) {
+        fields.push(metric.extra_field());
+      }
+    }
+    fields
+  }
+
+  /// The extra field named by `display_extra_field`, if it is captured
+  pub fn displayed_extra_field(&self) -> Option<&ExtraField> {
+    let label = self.display_extra_field.as_ref()?;
+    self
+      .extra_record_fields
+      .iter()
+      .find(|field| &field.label == label)
+  }
+
+  /// Background layers to composite: `backgrounds`, or a single layer of
+  /// `file_config.background_image` when there are none
+  pub fn background_layers(&self) -> Vec<BackgroundLayer> {
+    if self.backgrounds.is_empty() {
+      vec![BackgroundLayer::image(&self.file_config.background_image)]
+    } else {
+      self.backgrounds.clone()
+    }
+  }
+
+  /// Returns the enabled overlays sorted by z-index.
+  ///
+  /// An empty `overlays` list falls back to the `show_lap_data` and
+  /// `show_bottom_bar` flags; those flags also hide their element when the
+  /// list is given explicitly.
+  pub fn resolved_overlays(&self) -> Vec<OverlayElement> {
+    let mut overlays = if self.overlays.is_empty() {
+      vec![
+        OverlayElement::new(OverlayKind::LapPanel, 0),
+        OverlayElement::new(OverlayKind::BottomBar, 10),
+      ]
+    } else {
+      self.overlays.clone()
+    };
+
+    overlays.retain(|overlay| {
+      overlay.enabled
+        && match overlay.kind {
+          OverlayKind::LapPanel => self.show_lap_data,
+          OverlayKind::BottomBar => self.show_bottom_bar,
+          _ => true,
+        }
+    });
+    overlays.sort_by_key(|overlay| overlay.z_index);
+    overlays
+  }
+}
+
 impl Default for RouteVideoConfig {
   /// Creates default configuration
   fn default() -> Self {
@@ -269,8 +1454,117 @@ impl Default for RouteVideoConfig {
       show_route: true,
       show_lap_data: true,
       file_config: FileConfig::default(),
+      progression: ProgressionMode::default(),
+      pacing: PacingCurve::default(),
+      timing: VideoTiming::default(),
+      lap_pause: None,
+      end_card: None,
+      overlays: Vec::new(),
+      memory_profile: MemoryProfile::default(),
+      output_size: None,
+      duration_secs: 15.0,
+      max_duration_secs: None,
+      codec: VideoCodec::default(),
+      fallback: VideoFallback::Error,
+      time_index: TimeIndexSampling::Off,
+      safe_area: SafeArea::default(),
+      debug_draw_safe_area: false,
+      sport: SportProfile::default(),
+      extra_record_fields: Vec::new(),
+      display_extra_field: None,
+      custom_metrics: Vec::new(),
+      deterministic: false,
+      embed_config: false,
+      limits: Limits::default(),
+      distance_repair: DistanceRepair::default(),
+      spike_filter: None,
+      max_points: None,
+      export_geojson: None,
+      export_csv: None,
+      sessions: SessionSelection::default(),
+      connect_pauses: false,
+      chips: Vec::new(),
+      segment_coloring: RouteSegmentColoring::default(),
+      route_shadow: None,
+      line_smoothing: None,
+      gps_smoothing: None,
+      position_marker: PositionMarker::default(),
+      render_style: RouteRenderStyle::default(),
+      route_fade: None,
+      course_preview: None,
+      legend: None,
+      show_date: false,
+      date_stamp: DateStampConfig::default(),
+      show_summary: false,
+      attribution: AttributionConfig::default(),
+      backgrounds: Vec::new(),
+      background_fx: Vec::new(),
+      background_scaling: BackgroundScaling::default(),
+      seeded_style: None,
+      time_basis: TimeBasis::default(),
+      cadence_chart: None,
+      highlight_steepest_climb: false,
+      steepest_climb: ClimbHighlightConfig::default(),
+      number_format: NumberFormat::default(),
+      live_preview: None,
+      on_complete: None,
+    }
+  }
+}
+
+/// One activity of a `sequence_video` reel
+#[derive(Debug, Clone)]
+pub struct SequenceEntry {
+  /// Path to FIT file
+  pub fit_file: PathBuf,
+  /// Title card text (None = the FIT file name)
+  pub title: Option<String>,
+  /// Background image (None = the template's)
+  pub background_image: Option<PathBuf>,
+}
+
+impl SequenceEntry {
+  /// Creates an entry titled after its file, on the template background
+  pub fn new(fit_file: impl Into<PathBuf>) -> Self {
+    Self {
+      fit_file: fit_file.into(),
+      title: None,
+      background_image: None,
     }
   }
+
+  /// Text of the title card
+  pub fn title_text(&self) -> String {
+    self.title.clone().unwrap_or_else(|| {
+      self.fit_file.file_stem().map_or_else(
+        || self.fit_file.display().to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+      )
+    })
+  }
+
+  /// Template configuration pointed at this entry's files
+  pub fn config(&self, template: &RouteVideoConfig) -> RouteVideoConfig {
+    let mut config = template.clone();
+    config.file_config.fit_file = path_string(&self.fit_file);
+    if let Some(background) = &self.background_image {
+      config.file_config.background_image = path_string(background);
+    }
+    config
+  }
+}
+
+impl From<(PathBuf, Option<String>)> for SequenceEntry {
+  fn from((fit_file, title): (PathBuf, Option<String>)) -> Self {
+    Self {
+      title,
+      ..Self::new(fit_file)
+    }
+  }
+}
+
+fn path_string(path: &Path) -> String {
+  path.to_string_lossy().into_owned()
 }
 
 #[cfg(test)]
@@ -301,6 +1595,228 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_units_conversion() {
+    assert_eq!(Units::default(), Units::Metric);
+    assert_eq!(Units::Metric.distance(5000.0), 5.0);
+    assert!((Units::Imperial.distance(1609.344) - 1.0).abs() < 1e-9);
+    assert!((Units::Imperial.elevation(100.0) - 328.084).abs() < 1e-6);
+    assert_eq!(Units::Imperial.distance_label(), "mi");
+    assert_eq!(Units::Metric.pace_label(), "/km");
+  }
+
+  #[test]
+  fn test_limits() {
+    use crate::error::{LimitKind, RunariumError};
+
+    let limits = Limits {
+      max_records: 100,
+      max_output_pixels: 1000 * 1000,
+      max_frames: 50,
+      max_duration_seconds: 30.0,
+    };
+    assert!(limits.check_records(100).is_ok());
+    assert_eq!(
+      limits.check_records(101),
+      Err(RunariumError::LimitExceeded {
+        which: LimitKind::Records,
+        actual: 101,
+        limit: 100
+      })
+    );
+    assert!(limits.check_output_size(1000, 1000).is_ok());
+    assert!(limits.check_output_size(1000, 1001).is_err());
+    assert!(limits.check_frames(51).is_err());
+
+    let err = limits.check_duration(30.5).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "duration seconds 31 exceeds the limit of 30"
+    );
+  }
+
+  #[test]
+  fn test_background_scaling() {
+    // A 640x480 map under a 1920 max is kept at its size by default
+    let shrink_only = BackgroundScaling::default();
+    assert_eq!(shrink_only.fit(640.0, 480.0, 1920), 1.0);
+    assert_eq!(
+      shrink_only.fit(3840.0, 2160.0, 1920),
+      0.5
+    );
+    assert_eq!(
+      shrink_only.interpolation(false),
+      Interp::Lanczos4
+    );
+
+    let upscale = BackgroundScaling {
+      allow_upscale: true,
+      ..Default::default()
+    };
+    let scale = upscale.fit(640.0, 480.0, 1920);
+    assert_eq!(
+      (
+        (640.0 * scale) as i32,
+        (480.0 * scale) as i32
+      ),
+      (1920, 1440)
+    );
+    assert_eq!(
+      upscale.interpolation(true),
+      Interp::Cubic
+    );
+
+    let nearest = BackgroundScaling {
+      interpolation: Some(Interp::Nearest),
+      allow_upscale: true,
+    };
+    assert_eq!(
+      nearest.interpolation(true),
+      Interp::Nearest
+    );
+  }
+
+  #[test]
+  fn test_sport_profile_resolve() {
+    let auto = SportProfile::default();
+    assert_eq!(auto, SportProfile::Auto);
+    assert_eq!(
+      auto.resolve(Some("cycling")),
+      SportProfile::Cycling
+    );
+    assert_eq!(
+      auto.resolve(Some("hiking")),
+      SportProfile::Walking
+    );
+    assert_eq!(
+      auto.resolve(Some("swimming")),
+      SportProfile::Running
+    );
+    assert_eq!(
+      auto.resolve(None),
+      SportProfile::Running
+    );
+
+    // Explicit profiles ignore the file
+    let running = SportProfile::Running;
+    assert_eq!(
+      running.resolve(Some("cycling")),
+      SportProfile::Running
+    );
+    assert_eq!(
+      SportProfile::Cycling.lap_labels(false)[3].0,
+      "CAD"
+    );
+    // The power column pushes the detail column right
+    assert_eq!(
+      SportProfile::Running.lap_labels(true)[3..],
+      [("PWR", 335), ("LENGTH", 370)]
+    );
+  }
+
+  #[test]
+  fn test_extra_field_matching() {
+    let power = ExtraField::numeric("power", "Pwr", "W");
+    assert!(power.matches("Power"));
+    assert!(power.matches("0_power"));
+    assert!(power.matches("dev_0_Power"));
+    assert!(power.matches("developer_1:POWER"));
+    assert!(!power.matches("form_power"));
+    assert_eq!(power.format(287.4), "Pwr 287 W");
+    assert_eq!(
+      ExtraField::numeric("lss", "LSS", "").format(9.6),
+      "LSS 10"
+    );
+  }
+
+  #[test]
+  fn test_displayed_extra_field() {
+    let mut config = RouteVideoConfig {
+      display_extra_field: Some("Pwr".to_string()),
+      ..Default::default()
+    };
+    assert!(config.displayed_extra_field().is_none());
+
+    config.extra_record_fields = vec![ExtraField::numeric("power", "Pwr", "W")];
+    assert_eq!(
+      config.displayed_extra_field().map(|f| f.unit.as_str()),
+      Some("W")
+    );
+  }
+
+  #[test]
+  fn test_custom_metrics_are_captured() {
+    let gct = CustomMetricConfig::new("Ground Time", "GCT", "ms");
+    assert_eq!(gct.format(243.6), "GCT: 244 ms");
+    assert!(gct.extra_field().matches("1_ground time"));
+
+    let config = RouteVideoConfig {
+      extra_record_fields: vec![ExtraField::numeric("power", "Pwr", "W")],
+      custom_metrics: vec![
+        gct,
+        // Already captured under its label
+        CustomMetricConfig::new("Power", "Pwr", "W"),
+      ],
+      ..Default::default()
+    };
+    let labels: Vec<String> = config
+      .captured_fields()
+      .into_iter()
+      .map(|field| field.label)
+      .collect();
+    assert_eq!(labels, vec!["Pwr", "GCT"]);
+  }
+
+  #[test]
+  fn test_date_stamp_format() {
+    // 12 May 2024, 00:32 UTC
+    let start = 1_715_473_920;
+    let mut stamp = DateStampConfig::default();
+    assert_eq!(
+      stamp.format(start).unwrap(),
+      "12 May 2024, 00:32"
+    );
+
+    // +7 h moves the clock; -3.5 h also moves the date back a day
+    stamp.timezone = DateTimezone::FixedOffset(7.0);
+    assert_eq!(
+      stamp.format(start).unwrap(),
+      "12 May 2024, 07:32"
+    );
+    stamp.timezone = DateTimezone::FixedOffset(-3.5);
+    assert_eq!(
+      stamp.format(start).unwrap(),
+      "11 May 2024, 21:02"
+    );
+
+    // Offsets of a day or more do not exist
+    stamp.timezone = DateTimezone::FixedOffset(30.0);
+    assert!(stamp.format(start).is_none());
+
+    stamp.format = "%Y-%Q".to_string();
+    assert!(stamp.check_format().is_err());
+  }
+
+  #[test]
+  fn test_attribution_text() {
+    let mut attribution = AttributionConfig::default();
+    assert_eq!(attribution.drawn_text(), None);
+
+    // Hershey fonts are ASCII only
+    attribution.text_override =
+      Some("© OpenStreetMap contributors, Dürr".to_string());
+    assert_eq!(
+      attribution.drawn_text().as_deref(),
+      Some("(c) OpenStreetMap contributors, D?rr")
+    );
+
+    attribution.suppress = true;
+    assert_eq!(attribution.drawn_text(), None);
+    attribution.suppress = false;
+    attribution.text_override = Some("  ".to_string());
+    assert_eq!(attribution.drawn_text(), None);
+  }
+
   #[test]
   fn test_route_scale_presets() {
     let default = RouteScale::default();
@@ -331,16 +1847,64 @@ mod tests {
   fn test_lap_data_position_percentages() {
     let config = LapDataConfig::default();
 
-    // Positions should be between 0.0 and 1.0
-    assert!(config.position.0 >= 0.0 && config.position.0 <= 1.0);
-    assert!(config.position.1 >= 0.0 && config.position.1 <= 1.0);
+    // Percent positions scale with the frame
+    assert_eq!(
+      config.position,
+      Position::Percent(PercentPos::new(0.5, 0.09).unwrap())
+    );
+    assert_eq!(
+      config.position.resolve((1920, 1080)),
+      (960, 97)
+    );
+    assert_eq!(
+      config.position.resolve((1080, 1920)),
+      (540, 172)
+    );
 
-    // Calculate pixel positions for 1920x1080
-    let x = config.position.0 * 1920.0;
-    let y = config.position.1 * 1080.0;
+    // Pixel positions do not
+    let pixels = Position::from((640, 80));
+    assert_eq!(pixels.resolve((1920, 1080)), (640, 80));
+    assert_eq!(pixels.resolve((1080, 1920)), (640, 80));
+  }
 
-    assert!(x >= 0.0 && x <= 1920.0);
-    assert!(y >= 0.0 && y <= 1080.0);
+  #[test]
+  fn test_position_serde() {
+    let parse = |json| serde_json::from_str::<Position>(json);
+    assert_eq!(
+      parse(r#"{"percent": [0.5, 0.1]}"#).unwrap(),
+      Position::from((0.5, 0.1))
+    );
+    assert_eq!(
+      parse(r#"{"pixels": [640, 80]}"#).unwrap(),
+      Position::Pixels(PixelPos(640, 80))
+    );
+    // A bare pair is a percent position
+    assert_eq!(
+      parse("[0.5, 0.1]").unwrap(),
+      Position::from((0.5, 0.1))
+    );
+
+    let err = parse(r#"{"percent": [1.5, 0.1]}"#).unwrap_err();
+    assert!(
+      err.to_string().contains("outside 0.0-1.0"),
+      "{}",
+      err
+    );
+    assert!(parse("[640, 80]").is_err());
+    assert!(PercentPos::new(0.5, -0.1).is_err());
+
+    let json = serde_json::to_string(&Position::from((640, 80))).unwrap();
+    assert_eq!(json, r#"{"pixels":[640,80]}"#);
+    assert_eq!(
+      parse(&json).unwrap(),
+      Position::from((640, 80))
+    );
+
+    // Tuple constructors still work; percents are clamped
+    assert_eq!(
+      StatChip::new(Metric::Pace, (1.2, 0.5)).position,
+      Position::from((1.0, 0.5))
+    );
   }
 
   #[test]
@@ -411,6 +1975,101 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_sequence_entry() {
+    let entry = SequenceEntry::from((
+      PathBuf::from("runs/2024-05-01.fit"),
+      None,
+    ));
+    assert_eq!(entry.title_text(), "2024-05-01");
+
+    let mut template = RouteVideoConfig::default();
+    template.file_config.background_image = "shared.jpg".to_string();
+    let config = entry.config(&template);
+    assert_eq!(
+      config.file_config.fit_file,
+      "runs/2024-05-01.fit"
+    );
+    assert_eq!(
+      config.file_config.background_image,
+      "shared.jpg"
+    );
+
+    let entry = SequenceEntry {
+      title: Some("Long run".to_string()),
+      background_image: Some(PathBuf::from("hills.jpg")),
+      ..entry
+    };
+    assert_eq!(entry.title_text(), "Long run");
+    assert_eq!(
+      entry.config(&template).file_config.background_image,
+      "hills.jpg"
+    );
+  }
+
+  #[test]
+  fn test_effective_duration() {
+    let mut config = RouteVideoConfig::default();
+    assert_eq!(config.effective_duration(), 15.0);
+
+    config.duration_secs = 90.0;
+    config.max_duration_secs = Some(60.0);
+    assert_eq!(config.effective_duration(), 60.0);
+  }
+
+  #[test]
+  fn test_resolved_overlays_from_flags() {
+    let mut config = RouteVideoConfig::default();
+    let kinds: Vec<OverlayKind> = config
+      .resolved_overlays()
+      .into_iter()
+      .map(|o| o.kind)
+      .collect();
+    assert_eq!(
+      kinds,
+      vec![OverlayKind::LapPanel, OverlayKind::BottomBar]
+    );
+
+    config.show_lap_data = false;
+    let overlays = config.resolved_overlays();
+    assert_eq!(overlays.len(), 1);
+    assert_eq!(overlays[0].kind, OverlayKind::BottomBar);
+  }
+
+  #[test]
+  fn test_resolved_overlays_z_order() {
+    let mut config = RouteVideoConfig {
+      overlays: vec![
+        OverlayElement::new(OverlayKind::BottomBar, 5),
+        OverlayElement::new(OverlayKind::Watermark, 20),
+        OverlayElement::new(OverlayKind::LapPanel, 1),
+        OverlayElement {
+          kind: OverlayKind::Annotation,
+          z_index: 0,
+          enabled: false,
+        },
+      ],
+      ..Default::default()
+    };
+
+    let kinds: Vec<OverlayKind> = config
+      .resolved_overlays()
+      .into_iter()
+      .map(|o| o.kind)
+      .collect();
+    assert_eq!(
+      kinds,
+      vec![
+        OverlayKind::LapPanel,
+        OverlayKind::BottomBar,
+        OverlayKind::Watermark
+      ]
+    );
+
+    config.show_bottom_bar = false;
+    assert_eq!(config.resolved_overlays().len(), 2);
+  }
+
   #[test]
   fn test_visibility_flags() {
     let mut config = RouteVideoConfig::default();
@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// A `Limits` value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+  /// Route records drawn
+  Records,
+  /// Output width x height
+  OutputPixels,
+  /// Background image width x height, before it is scaled
+  BackgroundPixels,
+  /// Video frames, lap pause holds included
+  Frames,
+  /// Video length in seconds
+  DurationSeconds,
+}
+
+impl fmt::Display for LimitKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      LimitKind::Records => "records",
+      LimitKind::OutputPixels => "output pixels",
+      LimitKind::BackgroundPixels => "background pixels",
+      LimitKind::Frames => "frames",
+      LimitKind::DurationSeconds => "duration seconds",
+    })
+  }
+}
+
+/// Errors callers may want to handle specifically.
+///
+/// Generators return them inside `anyhow::Error`; match with
+/// `err.downcast_ref::<RunariumError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunariumError {
+  /// The input or requested output is larger than `Limits` allows
+  LimitExceeded {
+    which: LimitKind,
+    actual: u64,
+    limit: u64,
+  },
+  /// The output was written, but its `OutputSink` failed; the output is
+  /// kept
+  OutputSinkFailed {
+    output_file: String,
+    /// What failed, with the command's stderr
+    reason: String,
+  },
+}
+
+impl fmt::Display for RunariumError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RunariumError::LimitExceeded {
+        which,
+        actual,
+        limit,
+      } => write!(
+        f,
+        "{} {} exceeds the limit of {}",
+        which, actual, limit
+      ),
+      RunariumError::OutputSinkFailed {
+        output_file,
+        reason,
+      } => write!(
+        f,
+        "{} was written, but its output sink failed: {}",
+        output_file, reason
+      ),
+    }
+  }
+}
+
+impl std::error::Error for RunariumError {}
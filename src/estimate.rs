@@ -0,0 +1,358 @@
+#[cfg(feature = "render")]
+use std::{sync::OnceLock, time::Instant};
+
+#[cfg(feature = "render")]
+use anyhow::ensure;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+  analysis::{
+    downsample_route, repair_route_distances, resolve_laps, select_session,
+  },
+  configs::{LayerSource, RouteVideoConfig, VideoCodec},
+  types::{fit_data::Activity, warning::Warnings},
+  utils::{frame_schedule::FrameSchedule, read_file::activity_reader_for},
+};
+#[cfg(feature = "render")]
+use crate::{
+  configs::BackgroundLayer,
+  generators::frame_composer::FrameComposer,
+  utils::{
+    frame_sink::FrameSink, testing::synthetic_activity, workspace::Workspace,
+  },
+};
+
+/// Longest side of a background without an `output_size`, as rendered
+const MAX_DIM: i32 = 1080;
+
+/// Render seconds per megapixel of frame when calibration fails
+pub const DEFAULT_SECONDS_PER_MEGAPIXEL_FRAME: f64 = 0.004;
+
+/// Frame size and record count of the calibration render
+#[cfg(feature = "render")]
+const CALIBRATION_SIZE: (i32, i32) = (320, 180);
+#[cfg(feature = "render")]
+const CALIBRATION_POINTS: usize = 150;
+
+/// Factors from the point estimate to the low and high ends of a range
+const SPREAD: (f64, f64) = (0.5, 2.0);
+
+#[cfg(feature = "render")]
+static CALIBRATION: OnceLock<f64> = OnceLock::new();
+
+/// What an estimate needs from an activity: its records, laps and
+/// background size, without decoding the background
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityInfo {
+  pub record_count: usize,
+  /// Cumulative distance (meters) of each record
+  pub distances: Vec<f64>,
+  /// Timestamp (unix seconds) of each record, for real-time timing
+  pub timestamps: Vec<Option<i64>>,
+  /// Distance of each lap the config's lap source gives
+  pub lap_distances: Vec<f64>,
+  /// Width and height of the first background image (None = no image
+  /// layer, or one whose header cannot be read)
+  pub background_size: Option<(i32, i32)>,
+}
+
+impl ActivityInfo {
+  /// Info of `activity` as `config` renders it over a background of
+  /// `background_size`. Fails when `config` selects a session the
+  /// activity does not have.
+  pub fn new(
+    activity: &Activity,
+    config: &RouteVideoConfig,
+    background_size: Option<(i32, i32)>,
+  ) -> Result<Self> {
+    // Selected and repaired as the composer does
+    let mut activity = activity.clone();
+    select_session(&mut activity, config.sessions)?;
+    let mut route = activity.route;
+    repair_route_distances(config.distance_repair, &mut route);
+    downsample_route(config.max_points, &mut route);
+    let lap = resolve_laps(
+      config.lap_data.source,
+      &route,
+      activity.lap,
+    );
+    Ok(Self {
+      record_count: route.gps_points.len(),
+      distances: route.distances,
+      timestamps: route.timestamps,
+      lap_distances: lap.total_distance,
+      background_size,
+    })
+  }
+
+  /// Reads the FIT file of `config` and the header of its first
+  /// background image
+  pub fn read(config: &RouteVideoConfig) -> Result<Self> {
+    let activity = activity_reader_for(
+      &config.file_config,
+      &config.captured_fields(),
+    )?;
+    let background_size =
+      config
+        .background_layers()
+        .iter()
+        .find_map(|layer| match &layer.source {
+          LayerSource::Image { path } => image::image_dimensions(path)
+            .ok()
+            .map(|(w, h)| (w as i32, h as i32)),
+          _ => None,
+        });
+    Self::new(&activity, config, background_size)
+  }
+}
+
+/// Range a predicted value is expected to fall in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Bounds<T> {
+  pub low: T,
+  pub high: T,
+}
+
+impl Bounds<f64> {
+  /// `SPREAD` around `value`
+  fn around(value: f64) -> Self {
+    Self {
+      low: value * SPREAD.0,
+      high: value * SPREAD.1,
+    }
+  }
+}
+
+/// Pre-flight estimate of a video render.
+///
+/// The frame count and duration are exact; the render time and file size
+/// are approximate and given as ranges.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Estimate {
+  pub frames: usize,
+  pub video_duration_s: f64,
+  /// Width and height of the video
+  pub size: (i32, i32),
+  pub predicted_render_seconds: Bounds<f64>,
+  pub predicted_size_bytes: Bounds<u64>,
+}
+
+/// Estimates the video `config` would render from `activity`, timed with
+/// this host's calibration
+pub fn estimate(
+  config: &RouteVideoConfig,
+  activity: &ActivityInfo,
+) -> Estimate {
+  estimate_with(config, activity, host_calibration())
+}
+
+/// Estimates the video `config` would render from `activity` on a host
+/// that renders a megapixel of frame in `seconds_per_megapixel_frame`
+pub fn estimate_with(
+  config: &RouteVideoConfig,
+  activity: &ActivityInfo,
+  seconds_per_megapixel_frame: f64,
+) -> Estimate {
+  let schedule = FrameSchedule::new(
+    config,
+    activity.record_count,
+    &activity.distances,
+    &activity.timestamps,
+    &activity.lap_distances,
+    &mut Warnings::default(),
+  );
+  let frames = schedule.frames(&activity.distances).len();
+  let (width, height) = output_size(config, activity.background_size);
+  let pixel_frames = frames as f64 * width as f64 * height as f64;
+
+  let bytes = pixel_frames * bytes_per_pixel_frame(config.codec);
+  let size = Bounds::around(bytes);
+  Estimate {
+    frames,
+    video_duration_s: frames as f64 / schedule.fps,
+    size: (width, height),
+    predicted_render_seconds: Bounds::around(
+      pixel_frames / 1e6 * seconds_per_megapixel_frame,
+    ),
+    predicted_size_bytes: Bounds {
+      low: size.low.round() as u64,
+      high: size.high.round() as u64,
+    },
+  }
+}
+
+/// Render seconds per megapixel of frame on this host, measured by
+/// `calibrate` on the first call and cached for the process
+#[cfg(feature = "render")]
+pub fn host_calibration() -> f64 {
+  *CALIBRATION
+    .get_or_init(|| calibrate().unwrap_or(DEFAULT_SECONDS_PER_MEGAPIXEL_FRAME))
+}
+
+/// `DEFAULT_SECONDS_PER_MEGAPIXEL_FRAME`: without the `render` feature
+/// there is no renderer to time
+#[cfg(not(feature = "render"))]
+pub fn host_calibration() -> f64 {
+  DEFAULT_SECONDS_PER_MEGAPIXEL_FRAME
+}
+
+/// Times a tiny render of a synthetic activity over a solid background
+/// and returns its seconds per megapixel of frame, encoding included
+#[cfg(feature = "render")]
+pub fn calibrate() -> Result<f64> {
+  let workspace = Workspace::new("runarium_calibrate")?;
+  let mut config = RouteVideoConfig {
+    backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+      color: [40.0, 40.0, 40.0, 0.0],
+    })],
+    output_size: Some(CALIBRATION_SIZE),
+    ..Default::default()
+  };
+  config.file_config.output_file = workspace
+    .path_for("calibration.mp4")
+    .to_string_lossy()
+    .into_owned();
+
+  let start = Instant::now();
+  let mut composer = FrameComposer::new(
+    synthetic_activity(CALIBRATION_POINTS),
+    config,
+  )?;
+  let (width, height) = (composer.width(), composer.height());
+  let mut sink = FrameSink::open(
+    width,
+    height,
+    composer.fps(),
+    composer.config(),
+  )?;
+  let mut frames = 0;
+  while let Some(frame) = composer.next_mat() {
+    sink.write(frame?)?;
+    frames += 1;
+  }
+  sink.finish()?;
+  let seconds = start.elapsed().as_secs_f64();
+
+  ensure!(
+    frames > 0,
+    "The calibration render has no frames"
+  );
+  let megapixel_frames = frames as f64 * width as f64 * height as f64 / 1e6;
+  Ok(seconds / megapixel_frames)
+}
+
+/// Size of the video: the config's `output_size`, or the background
+/// fitted within `MAX_DIM` by the config's background scaling, as the
+/// composer loads it
+fn output_size(
+  config: &RouteVideoConfig,
+  background_size: Option<(i32, i32)>,
+) -> (i32, i32) {
+  if let Some(size) = config.output_size {
+    return size;
+  }
+  match background_size {
+    Some((w, h)) => {
+      let (w, h) = (w as f64, h as f64);
+      let scale = config.background_scaling.fit(w, h, MAX_DIM);
+      ((w * scale) as i32, (h * scale) as i32)
+    }
+    None => (MAX_DIM, MAX_DIM),
+  }
+}
+
+/// Rough encoded bytes per pixel of frame of a route video over a photo
+/// background, at the codec's default quality
+fn bytes_per_pixel_frame(codec: VideoCodec) -> f64 {
+  match codec {
+    VideoCodec::Mp4v => 0.02,
+    VideoCodec::Avc1 => 0.01,
+    VideoCodec::Vp09 => 0.008,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{configs::EndCardConfig, utils::testing::synthetic_activity};
+
+  fn activity(points: usize) -> ActivityInfo {
+    ActivityInfo::new(
+      &synthetic_activity(points),
+      &RouteVideoConfig::default(),
+      Some((4000, 3000)),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_output_size() {
+    let config = RouteVideoConfig::default();
+    assert_eq!(
+      output_size(&config, Some((4000, 3000))),
+      (1080, 810)
+    );
+    assert_eq!(
+      output_size(&config, Some((800, 600))),
+      (800, 600)
+    );
+    assert_eq!(output_size(&config, None), (1080, 1080));
+
+    let mut upscaled = config.clone();
+    upscaled.background_scaling.allow_upscale = true;
+    assert_eq!(
+      output_size(&upscaled, Some((800, 600))),
+      (1080, 810)
+    );
+
+    let config = RouteVideoConfig {
+      output_size: Some((1920, 1080)),
+      ..config
+    };
+    assert_eq!(
+      output_size(&config, Some((4000, 3000))),
+      (1920, 1080)
+    );
+  }
+
+  #[test]
+  fn test_estimate_counts_the_scheduled_frames() {
+    let config = RouteVideoConfig {
+      duration_secs: 10.0,
+      end_card: Some(EndCardConfig {
+        end_hold_seconds: 1.0,
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+    // 1000 frames at 100 fps, then 100 frames of end card
+    let estimate = estimate_with(&config, &activity(1000), 0.01);
+    assert_eq!(estimate.frames, 1100);
+    assert_eq!(estimate.video_duration_s, 11.0);
+    assert_eq!(estimate.size, (1080, 810));
+
+    let megapixel_frames = 1100.0 * 1080.0 * 810.0 / 1e6;
+    let render = estimate.predicted_render_seconds;
+    assert!(render.low < megapixel_frames * 0.01);
+    assert!(render.high > megapixel_frames * 0.01);
+  }
+
+  #[test]
+  fn test_size_depends_on_the_codec() {
+    let info = activity(500);
+    let size = |codec| {
+      let config = RouteVideoConfig {
+        codec,
+        ..Default::default()
+      };
+      estimate_with(&config, &info, 0.01).predicted_size_bytes
+    };
+    let (mp4v, avc1) = (
+      size(VideoCodec::Mp4v),
+      size(VideoCodec::Avc1),
+    );
+    assert!(mp4v.low < mp4v.high);
+    assert!(avc1.high < mp4v.high);
+  }
+}
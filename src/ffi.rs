@@ -0,0 +1,285 @@
+//! C-compatible API for embedding runarium without the HTTP server.
+//!
+//! Configs are JSON strings with the same keys as the server's `config`
+//! field (`RouteVideoConfigPatch` / `RouteImageConfigPatch`), applied on top
+//! of the defaults; input and output paths go in `file_config`.
+//!
+//! Error messages and JSON results are allocated by the library and must be
+//! released with `runarium_string_free`. Panics never cross the boundary;
+//! they are reported as `RUNARIUM_ERR_PANIC`.
+
+use std::{
+  ffi::{c_char, c_int, CStr, CString},
+  panic::{self, AssertUnwindSafe},
+  ptr,
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{
+  configs::{
+    FileConfig, RouteImageConfig, RouteImageConfigPatch, RouteVideoConfig,
+    RouteVideoConfigPatch,
+  },
+  generators::{
+    route_image::image_route_with_config,
+    route_video::progressive_route_with_config,
+  },
+  utils::read_file::activity_reader,
+  RunariumError,
+};
+
+/// Success
+pub const RUNARIUM_OK: c_int = 0;
+/// A pointer argument was null or not valid UTF-8
+pub const RUNARIUM_ERR_INVALID_ARGUMENT: c_int = 1;
+/// The config JSON does not match the config schema
+pub const RUNARIUM_ERR_CONFIG: c_int = 2;
+/// Reading the FIT file or rendering the output failed
+pub const RUNARIUM_ERR_GENERATION: c_int = 3;
+/// The library panicked; the message describes the panic
+pub const RUNARIUM_ERR_PANIC: c_int = 4;
+/// The input or requested output is larger than the configured `Limits`
+pub const RUNARIUM_ERR_LIMIT_EXCEEDED: c_int = 5;
+
+struct FfiError {
+  code: c_int,
+  message: String,
+}
+
+impl FfiError {
+  fn new(code: c_int, message: impl ToString) -> Self {
+    Self {
+      code,
+      message: message.to_string(),
+    }
+  }
+}
+
+/// Runs `f`, catching panics, and stores any error message in `out_err`
+unsafe fn guard<T>(
+  out_err: *mut *mut c_char,
+  f: impl FnOnce() -> Result<T, FfiError>,
+) -> Result<T, c_int> {
+  let result =
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+      let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+      Err(FfiError::new(
+        RUNARIUM_ERR_PANIC,
+        format!("panic: {}", message),
+      ))
+    });
+
+  result.map_err(|err| {
+    if !out_err.is_null() {
+      *out_err = into_c_string(err.message);
+    }
+    err.code
+  })
+}
+
+/// Borrows a C string argument
+unsafe fn read_str<'a>(
+  value: *const c_char,
+  name: &str,
+) -> Result<&'a str, FfiError> {
+  if value.is_null() {
+    return Err(FfiError::new(
+      RUNARIUM_ERR_INVALID_ARGUMENT,
+      format!("{} is null", name),
+    ));
+  }
+  CStr::from_ptr(value).to_str().map_err(|_| {
+    FfiError::new(
+      RUNARIUM_ERR_INVALID_ARGUMENT,
+      format!("{} is not valid UTF-8", name),
+    )
+  })
+}
+
+fn parse_patch<T: DeserializeOwned>(json: &str) -> Result<T, FfiError> {
+  serde_json::from_str(json).map_err(|e| {
+    FfiError::new(
+      RUNARIUM_ERR_CONFIG,
+      format!("Invalid config: {}", e),
+    )
+  })
+}
+
+fn generation_error(err: anyhow::Error) -> FfiError {
+  let code = match err.downcast_ref::<RunariumError>() {
+    Some(RunariumError::LimitExceeded { .. }) => RUNARIUM_ERR_LIMIT_EXCEEDED,
+    // A config from JSON has no output sink
+    Some(RunariumError::OutputSinkFailed { .. }) | None => {
+      RUNARIUM_ERR_GENERATION
+    }
+  };
+  FfiError::new(code, format!("{:#}", err))
+}
+
+/// Hands a string to the caller; interior NUL bytes are dropped
+fn into_c_string(value: String) -> *mut c_char {
+  let value = value.replace('\0', "");
+  CString::new(value).unwrap_or_default().into_raw()
+}
+
+/// Renders a route video as configured by `config_json`.
+///
+/// Returns `RUNARIUM_OK`, or an error code with a message written to
+/// `out_err` (if not null).
+///
+/// # Safety
+/// `config_json` must be a valid NUL-terminated string and `out_err` null or
+/// a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn runarium_generate_video(
+  config_json: *const c_char,
+  out_err: *mut *mut c_char,
+) -> c_int {
+  let result = guard(out_err, || {
+    let patch: RouteVideoConfigPatch =
+      parse_patch(read_str(config_json, "config_json")?)?;
+    let mut config = RouteVideoConfig::default();
+    patch.apply(&mut config);
+
+    progressive_route_with_config(config).map_err(generation_error)
+  });
+  result.map_or_else(|code| code, |_| RUNARIUM_OK)
+}
+
+/// Renders a static route image as configured by `config_json`.
+///
+/// Returns `RUNARIUM_OK`, or an error code with a message written to
+/// `out_err` (if not null).
+///
+/// # Safety
+/// `config_json` must be a valid NUL-terminated string and `out_err` null or
+/// a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn runarium_generate_image(
+  config_json: *const c_char,
+  out_err: *mut *mut c_char,
+) -> c_int {
+  let result = guard(out_err, || {
+    let patch: RouteImageConfigPatch =
+      parse_patch(read_str(config_json, "config_json")?)?;
+    let FileConfig {
+      fit_file,
+      background_image,
+      output_file,
+      ..
+    } = FileConfig::default();
+    let mut config =
+      RouteImageConfig::default(fit_file, background_image, output_file);
+    patch.apply(&mut config);
+
+    image_route_with_config(config).map_err(generation_error)
+  });
+  result.map_or_else(|code| code, |_| RUNARIUM_OK)
+}
+
+/// Summarizes a FIT file as a JSON object (sport, session totals, moving
+/// and elapsed time, record/lap counts and parse warnings).
+///
+/// Returns the JSON, to be released with `runarium_string_free`, or null
+/// with a message written to `out_err` (if not null).
+///
+/// # Safety
+/// `fit_path` must be a valid NUL-terminated string and `out_err` null or a
+/// valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn runarium_inspect(
+  fit_path: *const c_char,
+  out_err: *mut *mut c_char,
+) -> *mut c_char {
+  let result = guard(out_err, || {
+    let fit_path = read_str(fit_path, "fit_path")?;
+    let activity = activity_reader(fit_path, &[]).map_err(generation_error)?;
+    let session = activity.session;
+    let times = session.times;
+
+    Ok(
+      json!({
+        "sport": session.sport,
+        "total_distance": session.total_distance,
+        "total_timer_time": session.total_timer_time,
+        "total_ascent": session.total_ascent,
+        "start_time": session.start_time,
+        "moving_time": times.map(|times| times.moving_time),
+        "elapsed_time": times.map(|times| times.elapsed_time),
+        "average_moving_pace": times.and_then(|t| t.average_moving_pace),
+        "record_count": activity.route.gps_points.len(),
+        "lap_count": activity.lap.total_distance.len(),
+        "warnings": activity.warnings,
+      })
+      .to_string(),
+    )
+  });
+  result.map_or(ptr::null_mut(), into_c_string)
+}
+
+/// Releases a string returned by the library. Null is ignored.
+///
+/// # Safety
+/// `value` must be null or a string returned by the library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn runarium_string_free(value: *mut c_char) {
+  if !value.is_null() {
+    drop(CString::from_raw(value));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  unsafe fn take_error(err: *mut c_char) -> String {
+    let message = CStr::from_ptr(err).to_string_lossy().into_owned();
+    runarium_string_free(err);
+    message
+  }
+
+  #[test]
+  fn test_invalid_config_reports_error() {
+    let config = CString::new(r#"{"scale": 0.3}"#).unwrap();
+    let mut err = ptr::null_mut();
+
+    unsafe {
+      let code = runarium_generate_video(config.as_ptr(), &mut err);
+      assert_eq!(code, RUNARIUM_ERR_CONFIG);
+      assert!(take_error(err).contains("unknown field `scale`"));
+    }
+  }
+
+  #[test]
+  fn test_null_argument() {
+    let mut err = ptr::null_mut();
+
+    unsafe {
+      let json = runarium_inspect(ptr::null(), &mut err);
+      assert!(json.is_null());
+      assert_eq!(take_error(err), "fit_path is null");
+
+      // The error pointer is optional
+      let code = runarium_generate_image(ptr::null(), ptr::null_mut());
+      assert_eq!(code, RUNARIUM_ERR_INVALID_ARGUMENT);
+    }
+  }
+
+  #[test]
+  fn test_panic_is_caught() {
+    let mut err = ptr::null_mut();
+
+    unsafe {
+      let result: Result<(), c_int> = guard(&mut err, || panic!("boom"));
+      assert_eq!(result, Err(RUNARIUM_ERR_PANIC));
+      assert_eq!(take_error(err), "panic: boom");
+    }
+  }
+}
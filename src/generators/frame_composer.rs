@@ -0,0 +1,2711 @@
+use std::{borrow::Cow, fmt::Write, mem, ops::Range, path::Path};
+
+use anyhow::Result;
+use opencv::{core, prelude::*};
+
+use crate::{
+  analysis::{
+    downsample_route, grade_series, lap_boundaries, reject_route_spikes,
+    repair_route_distances, resolve_laps, select_session, steepest_climb,
+  },
+  configs::{
+    BottomBarMode, CustomMetricConfig, DynamicConfigPatch, ExtraField,
+    FadeConfig, LapDataConfig, MemoryProfile, Metric, NumberFormat,
+    OverlayElement, OverlayKind, PaceDistConfig, RouteVideoConfig,
+    SessionSelection, SportProfile, StatChip, TickerTransition, TimeBasis,
+    TimeIndexSampling, Units,
+  },
+  types::{
+    drawer_data::{PositionRect, Rect, SizeRect},
+    fit_data::{Activity, LapData, RouteData},
+    output::TimeIndexEntry,
+    warning::{Warning, Warnings},
+  },
+  utils::{
+    chart::{cadence_series, ChartAxes},
+    converter::{
+      activity_gain, apply_background_fx, convert_pace_to_sec, format_bearing,
+      format_capped_pace, format_coordinates, format_decimal, format_distance,
+      format_duration, format_elevation_gain, lap_detail_texts,
+      lap_heart_rate_texts, lap_power_texts, lap_stat_texts,
+      load_background_layers, pace_axis_ticks, pace_bar_fractions,
+      pace_bar_labels, smooth_gps_points, speed_to_pace_secs, string_space,
+      summary_parts,
+    },
+    element_drawer::{
+      union, Drawer, LapPanelLayout, PACE_BAR_WIDTH, ROUTE_LINE_THICKNESS,
+    },
+    end_card::EndCard,
+    export::{export_csv, write_geojson},
+    frame_schedule::FrameSchedule,
+    marker::Marker,
+    palette::{route_contrast, style_palette, StylePalette},
+    progression::{
+      bearing_series, cumulative_gain, displayed_distance, dot_indices,
+      elapsed_seconds, lap_averages, moving_seconds, pen_distance,
+      pen_pace_secs, pen_speed, PenPosition, BEARING_WINDOW, PAUSE_SPEED_MPS,
+    },
+    projection::{ProjectionInfo, Projector},
+    segment_color::{blend, gradient_scale, segment_colors},
+    smoothing::SmoothedLine,
+  },
+};
+
+/// One composed BGR video frame
+#[derive(Debug)]
+pub struct Frame {
+  /// 8-bit BGR pixels
+  pub data: Mat,
+  /// Position in the output video, lap pause holds included
+  pub index: usize,
+  pub width: i32,
+  pub height: i32,
+}
+
+impl Frame {
+  /// Raw BGR bytes, row by row
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    Ok(self.data.data_bytes()?.to_vec())
+  }
+}
+
+/// Composes the frames of a route video without encoding them.
+///
+/// `progressive_route_with_config` feeds these frames to OpenCV's
+/// `VideoWriter`; use the composer directly to send them to another
+/// encoder. `next_mat` borrows the composer's frame buffer, while
+/// `next_frame` (and the `Iterator` impl) returns an owned copy.
+///
+/// # Example
+/// ```no_run
+/// use runarium::configs::RouteVideoConfig;
+/// use runarium::generators::frame_composer::FrameComposer;
+/// use runarium::utils::read_file::activity_reader;
+///
+/// let config = RouteVideoConfig::default();
+/// let activity = activity_reader(
+///   &config.file_config.fit_file,
+///   &config.captured_fields(),
+/// )
+/// .unwrap();
+///
+/// let composer = FrameComposer::new(activity, config).unwrap();
+/// for frame in composer {
+///   let frame = frame.unwrap();
+///   println!("frame {}: {} bytes", frame.index, frame.to_bytes().unwrap().len());
+/// }
+/// ```
+pub struct FrameComposer {
+  config: RouteVideoConfig,
+  drawer: Drawer,
+  projector: Projector,
+  schedule: FrameSchedule,
+
+  // Route data, indexed by record
+  /// Pace in seconds per km
+  pace_seconds: Vec<f64>,
+  distances: Vec<f64>,
+  speeds: Vec<f64>,
+  /// Power in watts
+  powers: Vec<Option<u16>>,
+  extra_values: Option<Vec<Option<f64>>>,
+  /// Route line in pixels, smoothed if configured
+  line: SmoothedLine,
+  /// Seconds since the first record, for the time index
+  record_times: Vec<Option<f64>>,
+  /// Records that get a dot, in route order
+  dots: Vec<usize>,
+  /// Route fade, if enabled and the distance series covers the route
+  fade: Option<FadeConfig>,
+  /// Segments left undrawn: timer pauses and the gaps between separately
+  /// drawn sessions, in increasing order
+  line_breaks: Vec<usize>,
+
+  // Overlays
+  frame_overlays: Vec<OverlayElement>,
+  bottom_bar: BottomBar,
+  chips: StatChips,
+  /// Session totals shown in place of the bottom bar on the last route
+  /// frame (empty = no summary)
+  summary: Vec<String>,
+  lap_start: (i32, i32),
+  lap_stats: Vec<String>,
+  /// Axes of the cadence chart baked into the base frame, for its cursor
+  cadence_axes: Option<ChartAxes>,
+  route_color: core::Scalar,
+  /// Per-segment route colors (None = `route_color` everywhere)
+  segment_colors: Option<Vec<core::Scalar>>,
+  /// Steepest climb drawn over the route colors
+  climb: Option<ClimbHighlight>,
+  position_color: core::Scalar,
+  marker: Marker,
+  end_card: Option<EndCard>,
+  highlight_color: core::Scalar,
+  can_highlight: bool,
+  pulse_period: usize,
+
+  // Frame buffers reused across iterations
+  path_frame: Mat,
+  route_lines: Vec<(core::Point, core::Point, core::Scalar)>,
+  previous_lines: Vec<(core::Point, core::Point, core::Scalar)>,
+  current_frame: Mat,
+  highlight_frame: Mat,
+  /// Last route frame, under the end card
+  end_frame: Mat,
+
+  // Progress
+  next_plan: usize,
+  emitted: usize,
+  /// Line position the route is drawn up to
+  pen: Option<f64>,
+  next_dot: usize,
+  fade_start: usize,
+  previous_distance: Option<f64>,
+  holds_left: usize,
+  highlighted: bool,
+  /// Whether `next_mat` last returned the highlighted hold frame
+  showing_highlight: bool,
+  end_card_shown: usize,
+
+  /// Route and laps as rendered, kept for `write_exports` when the config
+  /// asks for a GeoJSON or CSV export
+  exports: Option<(RouteData, LapData)>,
+  /// Colors derived from the config's `seeded_style`, if any
+  palette: Option<StylePalette>,
+  /// Parse warnings of the activity plus what the composer worked around
+  warnings: Warnings,
+}
+
+impl FrameComposer {
+  /// Loads the background, plans the frames and draws the static overlays
+  pub fn new(
+    mut activity: Activity,
+    mut config: RouteVideoConfig,
+  ) -> Result<Self> {
+    select_session(&mut activity, config.sessions)?;
+    let repaired = repair_route_distances(
+      config.distance_repair,
+      &mut activity.route,
+    );
+    activity.warnings.extend(repaired);
+    let despiked =
+      reject_route_spikes(config.spike_filter, &mut activity.route);
+    activity.warnings.extend(despiked);
+    downsample_route(config.max_points, &mut activity.route);
+    // Splits of the records stand in for laps the device did not record
+    let device_laps = mem::take(&mut activity.lap);
+    activity.lap = resolve_laps(
+      config.lap_data.source,
+      &activity.route,
+      device_laps,
+    );
+    if !activity.lap.avg_speed.is_empty() {
+      activity
+        .warnings
+        .0
+        .retain(|warning| *warning != Warning::EmptyLapData);
+    }
+    let exported =
+      config.export_geojson.is_some() || config.export_csv.is_some();
+    let exports = exported.then(|| {
+      (
+        activity.route.clone(),
+        activity.lap.clone(),
+      )
+    });
+    // A seeded palette replaces the configured colors; the style is taken
+    // out, so an embedded config keeps the colors it chose
+    let palette = config.seeded_style.take().map(|style| {
+      let palette = style_palette(&style, activity.session.start_time);
+      let mut layers = config.background_layers();
+      palette.apply(&mut config.colors, &mut layers);
+      config.backgrounds = layers;
+      palette
+    });
+    let skipped = overlay_warnings(&config, &activity);
+    let boundaries = lap_boundaries(&activity.route, &activity.lap);
+    let grades = grade_series(&activity.route);
+    let climb = config
+      .highlight_steepest_climb
+      .then(|| {
+        steepest_climb(
+          &activity.route,
+          config.steepest_climb.window_m,
+        )
+      })
+      .flatten();
+    let Activity {
+      route,
+      lap,
+      session,
+      mut warnings,
+      sessions,
+    } = activity;
+    warnings.extend(skipped);
+    let RouteData {
+      paces: _,
+      gps_points: points,
+      distances,
+      heart_rates,
+      speeds,
+      cadences,
+      powers,
+      altitudes,
+      timestamps,
+      segment_breaks,
+      mut extra,
+    } = route;
+
+    // The line breaks at timer pauses, and each session after the first
+    // starts a new line
+    let mut line_breaks = if config.connect_pauses {
+      Vec::new()
+    } else {
+      segment_breaks
+    };
+    if config.sessions == SessionSelection::Separate {
+      line_breaks.extend(
+        sessions
+          .iter()
+          .filter(|session| !session.records.is_empty())
+          .filter_map(|session| session.records.start.checked_sub(1)),
+      );
+      line_breaks.sort_unstable();
+      line_breaks.dedup();
+    }
+
+    // Sport-specific stats and labels
+    let sport = config.sport.resolve(session.sport.as_deref());
+    if config.show_date {
+      config.date_stamp.check_format()?;
+    }
+
+    // Extra record field shown in the overlays, and the custom metrics of
+    // the bottom bar
+    let custom_metrics: Vec<_> = config
+      .custom_metrics
+      .iter()
+      .map(|metric| {
+        let values = extra.get(&metric.label).cloned().unwrap_or_default();
+        (metric.clone(), values)
+      })
+      .collect();
+    let extra_field = config.displayed_extra_field().cloned();
+    let extra_values = extra_field
+      .as_ref()
+      .and_then(|field| extra.remove(&field.label));
+
+    // Plan the frames, rejecting oversized requests before allocating
+    let limits = config.limits;
+    limits.check_records(points.len())?;
+    let schedule = FrameSchedule::new(
+      &config,
+      points.len(),
+      &distances,
+      &timestamps,
+      &lap.total_distance,
+      &mut warnings,
+    );
+    let fps = schedule.fps;
+
+    // Lap pauses add up to one hold per lap
+    let frame_count = schedule.max_frames();
+    limits.check_frames(frame_count)?;
+    limits.check_duration(frame_count as f64 / fps)?;
+
+    // Composite the background layers at the output size
+    if let Some((width, height)) = config.output_size {
+      limits.check_output_size(width, height)?;
+    }
+    let layers = config.background_layers();
+    let span = tracing::info_span!(
+      "background_load",
+      layers = layers.len(),
+      width = tracing::field::Empty,
+      height = tracing::field::Empty
+    );
+    let (background, width, height) = span.in_scope(|| {
+      load_background_layers(
+        &layers,
+        config.output_size,
+        1080,
+        &limits,
+        &config.background_scaling,
+      )
+    })?;
+    span.record("width", width).record("height", height);
+    limits.check_output_size(width, height)?;
+
+    // Coordinate normalization to image space, from smoothed positions if
+    // configured
+    let projected = match &config.gps_smoothing {
+      Some(smoothing) => Cow::Owned(smooth_gps_points(
+        &points,
+        &line_breaks,
+        smoothing.method,
+      )),
+      None => Cow::Borrowed(&points),
+    };
+    let projector = Projector::new(&projected, config.route_scale, width);
+    let pixel_points: Vec<core::Point> = projected
+      .iter()
+      .map(|&(la, lo)| projector.project(la, lo))
+      .collect();
+    let line = SmoothedLine::new(
+      &pixel_points,
+      config.line_smoothing.as_ref(),
+    );
+
+    // Initialize frame
+    let mut path_frame =
+      apply_background_fx(background, &config.background_fx)?;
+    warnings.extend(
+      route_contrast(
+        &config.colors,
+        &path_frame,
+        &pixel_points,
+      )?
+      .warnings(),
+    );
+    let drawer = Drawer::new(width, height).with_safe_area(&config.safe_area);
+    let marker = Marker::load(
+      &config.position_marker,
+      drawer.color(config.colors.current_position),
+      &drawer,
+    )?;
+
+    let extra_laps = extra_field
+      .as_ref()
+      .zip(extra_values.as_ref())
+      .map(|(field, values)| (field, lap_averages(values, &boundaries)));
+
+    let elevation_line = config
+      .lap_data
+      .show_elevation_gain
+      .then(|| activity_gain(&altitudes, session.total_ascent))
+      .flatten()
+      .map(|gain| format_elevation_gain(gain, Units::Metric));
+
+    // The whole lap panel is shifted inside the safe area
+    let lap_stats = lap_stat_texts(&lap, sport, &config.number_format);
+    let lap_start = drawer.lap_panel_start(
+      config.lap_data.position,
+      &LapPanelLayout {
+        config: &config.lap_data,
+        sport,
+        stats: &lap_stats,
+        details: &lap_detail_texts(&lap, sport, &config.number_format),
+        extra_label: extra_laps.as_ref().map(|(field, _)| field.label.as_str()),
+        summary: elevation_line.as_deref(),
+      },
+    )?;
+
+    // Static overlays are baked into the base frame, beneath the route
+    let overlays = config.resolved_overlays();
+    let (static_overlays, frame_overlays): (Vec<_>, Vec<_>) = overlays
+      .into_iter()
+      .partition(|overlay| overlay.kind.is_static());
+    for overlay in &static_overlays {
+      if overlay.kind == OverlayKind::LapPanel && !lap.avg_speed.is_empty() {
+        let _entered =
+          tracing::info_span!("lap_panel", laps = lap.avg_speed.len())
+            .entered();
+        draw_lap_panel(
+          &drawer,
+          &mut path_frame,
+          &config,
+          &lap,
+          sport,
+          extra_laps
+            .as_ref()
+            .map(|(field, averages)| (*field, averages.as_slice())),
+          elevation_line.as_deref(),
+          lap_start,
+        )?;
+      }
+    }
+
+    // The legend and date stamp are static too
+    if let Some(legend) = &config.legend {
+      if let Some(scale) = gradient_scale(config.segment_coloring, &altitudes) {
+        drawer.legend(&mut path_frame, legend, &scale)?;
+      }
+    }
+    if let (true, Some(start_time)) = (config.show_date, session.start_time) {
+      drawer.date_stamp(
+        &mut path_frame,
+        &config.date_stamp,
+        start_time,
+      )?;
+    }
+
+    // The cadence chart is static as well; only its cursor moves
+    let cadence_axes = match &config.cadence_chart {
+      Some(chart) => drawer.cadence_chart(
+        &mut path_frame,
+        chart,
+        &cadence_series(&distances, &cadences),
+      )?,
+      None => None,
+    };
+
+    // The whole course, faint, for the progressive line to fill in
+    if let (true, Some(preview)) = (
+      config.show_route,
+      &config.course_preview,
+    ) {
+      for points in line.stretches(&line_breaks) {
+        drawer.blended_polyline(
+          &mut path_frame,
+          points,
+          drawer.color(preview.color),
+          preview.thickness,
+          preview.alpha,
+        )?;
+      }
+    }
+
+    if config.debug_draw_safe_area {
+      drawer.safe_area_guides(&mut path_frame)?;
+    }
+
+    // Lap pause planning
+    let can_highlight = config.memory_profile == MemoryProfile::Standard
+      && static_overlays
+        .iter()
+        .any(|overlay| overlay.kind == OverlayKind::LapPanel);
+
+    let segment_colors = segment_colors(config.segment_coloring, &altitudes)
+      .map(|colors| {
+        colors
+          .into_iter()
+          .map(|color| drawer.color(color))
+          .collect()
+      });
+    let dots =
+      config
+        .render_style
+        .dots()
+        .map_or_else(Vec::new, |(_, spacing_m)| {
+          dot_indices(
+            pixel_points.len(),
+            &distances,
+            spacing_m,
+          )
+        });
+    let fade = drawable_fade(
+      &config,
+      pixel_points.len(),
+      distances.len(),
+    );
+    let chips = StatChips {
+      chips: config.chips.clone(),
+      sport,
+      heart_rates,
+      cadences,
+      elapsed: match config.time_basis {
+        TimeBasis::Elapsed => elapsed_seconds(&timestamps),
+        TimeBasis::Moving => {
+          moving_seconds(&timestamps, &distances, PAUSE_SPEED_MPS)
+        }
+      },
+      elevation_gain: cumulative_gain(&altitudes),
+      bearings: bearing_series(&points, BEARING_WINDOW),
+      grades,
+      coordinates: points.clone(),
+      number_format: config.number_format,
+      text: String::new(),
+    };
+    let bottom_bar = BottomBar::new(
+      &drawer,
+      config.pace_dist.clone(),
+      sport,
+      extra_field,
+      config.number_format,
+      fps,
+      &chips,
+    )?
+    .with_custom_metrics(custom_metrics);
+    let pace_seconds = speeds.iter().map(|&s| speed_to_pace_secs(s)).collect();
+    let total_distance = format_distance(
+      distances.last().copied().unwrap_or_default(),
+      Units::Metric,
+      &config.number_format,
+    );
+    let climb = climb.map(|climb| ClimbHighlight {
+      segments: climb.start_idx..climb.end_idx,
+      color: drawer.color(config.steepest_climb.color.to_bgra()),
+      label: config.steepest_climb.show_label.then(|| {
+        let middle = (climb.start_idx + climb.end_idx) / 2;
+        (
+          line.points[line.record_points[middle]],
+          climb.grade,
+        )
+      }),
+    });
+    let end_card = config
+      .end_card
+      .map(|card| {
+        EndCard::new(
+          &card,
+          &drawer,
+          fps,
+          format!("{} km", total_distance),
+        )
+      })
+      .transpose()?;
+    let summary = if config.show_summary {
+      summary_parts(&session, &config.number_format)
+    } else {
+      Vec::new()
+    };
+
+    Ok(Self {
+      route_color: drawer.color(config.colors.route_line),
+      segment_colors,
+      climb,
+      position_color: drawer.color(config.colors.current_position),
+      marker,
+      end_card,
+      highlight_color: drawer.color(config.colors.current_position),
+      pen: pixel_points.first().map(|_| 0.0),
+      config,
+      drawer,
+      projector,
+      schedule,
+      pace_seconds,
+      distances,
+      speeds,
+      powers,
+      extra_values,
+      line,
+      record_times: elapsed_seconds(&timestamps),
+      dots,
+      fade,
+      line_breaks,
+      frame_overlays,
+      bottom_bar,
+      chips,
+      summary,
+      lap_start,
+      lap_stats,
+      cadence_axes,
+      can_highlight,
+      pulse_period: ((fps / 4.0) as usize).max(1),
+      path_frame,
+      route_lines: Vec::new(),
+      previous_lines: Vec::new(),
+      current_frame: Mat::default(),
+      highlight_frame: Mat::default(),
+      end_frame: Mat::default(),
+      next_plan: 0,
+      emitted: 0,
+      next_dot: 0,
+      fade_start: 0,
+      previous_distance: None,
+      holds_left: 0,
+      highlighted: false,
+      showing_highlight: false,
+      end_card_shown: 0,
+      exports,
+      palette,
+      warnings,
+    })
+  }
+
+  /// Configuration the frames are composed with
+  pub fn config(&self) -> &RouteVideoConfig {
+    &self.config
+  }
+
+  /// Frame width in pixels
+  pub fn width(&self) -> i32 {
+    self.drawer.width
+  }
+
+  /// Frame height in pixels
+  pub fn height(&self) -> i32 {
+    self.drawer.height
+  }
+
+  /// Frame rate that fits the planned frames into the target duration
+  pub fn fps(&self) -> f64 {
+    self.schedule.fps
+  }
+
+  /// Number of route points
+  pub fn point_count(&self) -> usize {
+    self.line.record_points.len()
+  }
+
+  /// Planned frames composed so far and in total (lap pause holds excluded)
+  pub fn progress(&self) -> (usize, usize) {
+    (self.next_plan, self.schedule.plan.len())
+  }
+
+  /// How GPS coordinates map to frame pixels
+  pub fn projection_info(&self) -> ProjectionInfo {
+    self.projector.projection_info()
+  }
+
+  /// Colors the config's `seeded_style` derived (None without one)
+  pub fn palette(&self) -> Option<StylePalette> {
+    self.palette
+  }
+
+  /// Video time, activity time and distance of the frames `sampling`
+  /// picks, following the frames this composer emits
+  pub fn time_index(&self, sampling: TimeIndexSampling) -> Vec<TimeIndexEntry> {
+    self.schedule.time_index(
+      &self.distances,
+      &self.record_times,
+      sampling,
+    )
+  }
+
+  /// Problems found while parsing the activity and setting up the frames
+  pub fn warnings(&self) -> &Warnings {
+    &self.warnings
+  }
+
+  /// Writes the GeoJSON and CSV exports the config asks for, of the route
+  /// and laps as rendered. Constructing a composer writes nothing, so a
+  /// render calls this once, after the config was accepted.
+  pub fn write_exports(&self) -> Result<()> {
+    let Some((route, lap)) = &self.exports else {
+      return Ok(());
+    };
+    if let Some(path) = &self.config.export_geojson {
+      write_geojson(Path::new(path), route)?;
+    }
+    if let Some(path) = &self.config.export_csv {
+      export_csv(route, lap, path)?;
+    }
+    Ok(())
+  }
+
+  /// Copy of a composer that has not composed any frame yet, so its base
+  /// frame holds only the background and static overlays, with `patch`
+  /// applied to the per-frame settings
+  pub(crate) fn fork(&self, patch: &DynamicConfigPatch) -> Result<Self> {
+    let mut config = self.config.clone();
+    patch.apply(&mut config);
+    let frame_overlays = config
+      .resolved_overlays()
+      .into_iter()
+      .filter(|overlay| !overlay.kind.is_static())
+      .collect();
+    let chips = StatChips {
+      chips: config.chips.clone(),
+      ..self.chips.clone()
+    };
+    let bottom_bar = BottomBar::new(
+      &self.drawer,
+      config.pace_dist.clone(),
+      self.bottom_bar.sport,
+      self.bottom_bar.extra_field.clone(),
+      config.number_format,
+      self.schedule.fps,
+      &chips,
+    )?
+    .with_custom_metrics(self.bottom_bar.custom_metrics.clone());
+
+    Ok(Self {
+      config,
+      drawer: self.drawer.clone(),
+      projector: self.projector,
+      schedule: self.schedule.clone(),
+      pace_seconds: self.pace_seconds.clone(),
+      distances: self.distances.clone(),
+      speeds: self.speeds.clone(),
+      powers: self.powers.clone(),
+      extra_values: self.extra_values.clone(),
+      line: self.line.clone(),
+      record_times: self.record_times.clone(),
+      dots: self.dots.clone(),
+      fade: self.fade,
+      line_breaks: self.line_breaks.clone(),
+      frame_overlays,
+      bottom_bar,
+      chips,
+      summary: self.summary.clone(),
+      lap_start: self.lap_start,
+      lap_stats: self.lap_stats.clone(),
+      cadence_axes: self.cadence_axes,
+      route_color: self.route_color,
+      segment_colors: self.segment_colors.clone(),
+      climb: self.climb.clone(),
+      position_color: self.position_color,
+      marker: self.marker.clone(),
+      end_card: self.end_card.clone(),
+      highlight_color: self.highlight_color,
+      can_highlight: self.can_highlight,
+      pulse_period: self.pulse_period,
+      path_frame: self.path_frame.try_clone()?,
+      route_lines: Vec::new(),
+      previous_lines: Vec::new(),
+      current_frame: Mat::default(),
+      highlight_frame: Mat::default(),
+      end_frame: Mat::default(),
+      next_plan: 0,
+      emitted: 0,
+      pen: self.line.points.first().map(|_| 0.0),
+      next_dot: 0,
+      fade_start: 0,
+      previous_distance: None,
+      holds_left: 0,
+      highlighted: false,
+      showing_highlight: false,
+      end_card_shown: 0,
+      exports: None,
+      palette: self.palette,
+      warnings: self.warnings.clone(),
+    })
+  }
+
+  /// Composes planned frame `index` (lap pause holds excluded) directly,
+  /// drawing the route up to it in one pass. Meant for a fresh composer.
+  pub(crate) fn compose_planned(
+    &mut self,
+    index: usize,
+  ) -> Result<Option<&Mat>> {
+    let Some(&pen) = self.schedule.plan.get(index) else {
+      return Ok(None);
+    };
+    self.next_plan = index + 1;
+    self.compose(pen)?;
+    Ok(Some(&self.current_frame))
+  }
+
+  /// Composes the next frame into the internal buffer and borrows it
+  pub fn next_mat(&mut self) -> Option<Result<&Mat>> {
+    // Hold the frame when a lap completes
+    if self.holds_left > 0 {
+      let hold = self.schedule.hold_frames - self.holds_left;
+      self.holds_left -= 1;
+      self.emitted += 1;
+      self.showing_highlight =
+        self.highlighted && (hold / self.pulse_period).is_multiple_of(2);
+      return Some(Ok(self.last_mat()));
+    }
+    self.showing_highlight = false;
+
+    let Some(&pen) = self.schedule.plan.get(self.next_plan) else {
+      return self.next_end_card();
+    };
+    self.next_plan += 1;
+    self.emitted += 1;
+    Some(self.compose(pen).map(|()| &self.current_frame))
+  }
+
+  /// Composes the next end card frame over the last route frame, or None
+  /// once the card has been held
+  fn next_end_card(&mut self) -> Option<Result<&Mat>> {
+    let card = self.end_card.as_ref()?;
+    if self.end_card_shown >= card.frames() || self.current_frame.empty() {
+      return None;
+    }
+    let index = self.end_card_shown;
+    self.end_card_shown += 1;
+    self.emitted += 1;
+    Some(self.compose_end_card(index).map(|()| &self.current_frame))
+  }
+
+  /// Draws end card frame `index` over the last route frame
+  fn compose_end_card(&mut self, index: usize) -> Result<()> {
+    if index == 0 {
+      self.end_frame = self.current_frame.try_clone()?;
+    }
+    self.end_frame.copy_to(&mut self.current_frame)?;
+    if let Some(card) = &self.end_card {
+      card.draw(
+        &self.drawer,
+        &mut self.current_frame,
+        index,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Frame `next_mat` returned last, still in the composer's buffers
+  /// (empty before the first frame)
+  pub fn last_mat(&self) -> &Mat {
+    if self.showing_highlight {
+      &self.highlight_frame
+    } else {
+      &self.current_frame
+    }
+  }
+
+  /// Composes the next frame and returns an owned copy
+  pub fn next_frame(&mut self) -> Option<Result<Frame>> {
+    let index = self.emitted;
+    let (width, height) = (self.width(), self.height());
+    let frame = self.next_mat()?;
+    Some(frame.and_then(|mat| {
+      Ok(Frame {
+        data: mat.try_clone()?,
+        index,
+        width,
+        height,
+      })
+    }))
+  }
+
+  /// Draws the route up to `frame` and its per-frame overlays
+  fn compose(&mut self, frame: PenPosition) -> Result<()> {
+    let config = &self.config;
+    let drawer = &self.drawer;
+    let position = self.line.position(frame.index, frame.fraction);
+    let point = self.line.point_at(position);
+    let distance = pen_distance(&self.distances, frame);
+
+    // Segment `i` runs from record `i` to `i + 1`; the last record takes
+    // the last segment's color
+    let color = |segment: usize| match &self.climb {
+      Some(climb) if climb.segments.contains(&segment) => climb.color,
+      _ => self
+        .segment_colors
+        .as_ref()
+        .and_then(|colors| colors.get(segment).or(colors.last()).copied())
+        .unwrap_or(self.route_color),
+    };
+
+    if config.show_route {
+      // With a fade the accumulated line is drawn faded; the recent window
+      // is redrawn brightly on every frame
+      let line_color = |segment: usize| match &self.fade {
+        Some(fade) => drawer.color(fade.faded_color.to_bgra()),
+        None => color(segment),
+      };
+
+      // Lines through every point passed since the last frame
+      self.route_lines.clear();
+      let draws_line = config.render_style.draws_line();
+      if let (true, Some(from)) = (draws_line, self.pen) {
+        let pieces = self.line.pieces(from, position);
+        for piece in
+          pieces.filter(|piece| !self.line_breaks.contains(&piece.record))
+        {
+          self.route_lines.push((
+            piece.from,
+            piece.to,
+            line_color(piece.record),
+          ));
+        }
+      }
+
+      // Shadow the new lines, then redraw the previous frame's lines that
+      // the new shadow may overlap
+      if let Some(shadow) = &config.route_shadow {
+        drawer.shadow(
+          &mut self.path_frame,
+          self.route_lines.iter().map(|&(a, b, _)| (a, b)),
+          ROUTE_LINE_THICKNESS,
+          shadow,
+        )?;
+        for &(a, b, color) in &self.previous_lines {
+          drawer.line(&mut self.path_frame, a, b, color)?;
+        }
+      }
+      for &(a, b, color) in &self.route_lines {
+        drawer.line(&mut self.path_frame, a, b, color)?;
+      }
+      if !self.route_lines.is_empty() {
+        std::mem::swap(
+          &mut self.route_lines,
+          &mut self.previous_lines,
+        );
+      }
+
+      // Dots at the records passed, over the line
+      if let Some((radius, _)) = config.render_style.dots() {
+        while let Some(&index) = self.dots.get(self.next_dot) {
+          if index > frame.index {
+            break;
+          }
+          drawer.dot(
+            &mut self.path_frame,
+            self.line.points[self.line.record_points[index]],
+            radius,
+            color(index),
+          )?;
+          self.next_dot += 1;
+        }
+      }
+    }
+    self.pen = Some(position);
+
+    self.path_frame.copy_to(&mut self.current_frame)?;
+
+    // Recent window, blending from bright at the pen to faded at its start
+    if let (Some(fade), Some(distance)) = (&self.fade, distance) {
+      let window_start = distance - fade.window_m;
+      while self.fade_start < frame.index
+        && self.distances[self.fade_start + 1] < window_start
+      {
+        self.fade_start += 1;
+      }
+
+      let line = &self.line;
+      let start = line.position(self.fade_start, 0.0);
+      let pieces = line.pieces(start, position);
+      for piece in
+        pieces.filter(|piece| !self.line_breaks.contains(&piece.record))
+      {
+        let (index, fraction) = line.record_at(piece.end);
+        let end = pen_distance(
+          &self.distances,
+          PenPosition { index, fraction },
+        )
+        .unwrap_or(distance);
+        let age = ((distance - end) / fade.window_m).clamp(0.0, 1.0);
+        let bright = color(piece.record);
+        let faded = blend(
+          [bright[0], bright[1], bright[2], bright[3]],
+          fade.faded_color.to_bgra(),
+          age,
+        );
+        drawer.line(
+          &mut self.current_frame,
+          piece.from,
+          piece.to,
+          drawer.color(faded),
+        )?;
+      }
+    }
+
+    // The climb is labeled once the pen has climbed it
+    if let Some(ClimbHighlight {
+      segments,
+      label: Some((at, grade)),
+      ..
+    }) = &self.climb
+    {
+      if config.show_route && frame.index >= segments.end {
+        drawer.climb_label(&mut self.current_frame, *at, *grade)?;
+      }
+    }
+
+    // The planned frame being composed drives the marker pulse
+    if config.show_route {
+      self.marker.draw(
+        drawer,
+        &mut self.current_frame,
+        point,
+        self.next_plan.saturating_sub(1),
+      )?;
+    }
+
+    // Cadence chart cursor at the pen
+    if let (Some(axes), Some(distance)) = (&self.cadence_axes, distance) {
+      drawer.chart_cursor(
+        &mut self.current_frame,
+        axes,
+        distance,
+        self.position_color,
+      )?;
+    }
+
+    // Draw per-frame overlays in z-order
+    let pace = pen_pace_secs(&self.pace_seconds, frame);
+    let speed = pen_speed(&self.speeds, frame);
+    let heart_rate = self.chips.heart_rates.get(frame.index).copied().flatten();
+    let power = self.powers.get(frame.index).copied().flatten();
+    let cadence = self.chips.cadences.get(frame.index).copied().flatten();
+    let extra_value = self
+      .extra_values
+      .as_ref()
+      .and_then(|values| values.get(frame.index).copied().flatten());
+    let shown_distance = displayed_distance(
+      &self.distances,
+      frame,
+      config.pace_dist.smooth_counter,
+    );
+    // The session totals take the bottom bar's place on the last route
+    // frame, and stay on it under the end card
+    let show_summary =
+      !self.summary.is_empty() && self.next_plan == self.schedule.plan.len();
+    for overlay in &self.frame_overlays {
+      if let (OverlayKind::BottomBar, Some(pace), Some(shown_distance)) =
+        (&overlay.kind, pace, shown_distance)
+      {
+        if show_summary {
+          continue;
+        }
+        if self.bottom_bar.ticker.is_empty() {
+          self.bottom_bar.draw(
+            drawer,
+            &mut self.current_frame,
+            pace,
+            speed.unwrap_or_default(),
+            heart_rate,
+            power,
+            cadence,
+            extra_value,
+            frame.index,
+            shown_distance,
+          )?;
+        } else {
+          self.bottom_bar.draw_ticker(
+            drawer,
+            &mut self.current_frame,
+            &mut self.chips,
+            self.next_plan.saturating_sub(1),
+            frame,
+            pace,
+            shown_distance,
+          )?;
+        }
+      }
+    }
+
+    if show_summary {
+      drawer.summary_bar(
+        &mut self.current_frame,
+        &self.summary,
+        config.pace_dist.font_scale,
+        config.pace_dist.thickness,
+        config.pace_dist.font,
+      )?;
+    }
+
+    // Stat chips float above every overlay
+    self.chips.draw(
+      drawer,
+      &mut self.current_frame,
+      frame,
+      pace,
+      shown_distance,
+    )?;
+
+    // The map credit goes over everything else, lap highlights and the
+    // end card included, as they start from this frame
+    drawer.attribution(
+      &mut self.current_frame,
+      &config.attribution,
+    )?;
+
+    // Plan the hold when a lap completes, as the schedule does
+    self.holds_left = 0;
+    self.highlighted = false;
+    let completed = self
+      .schedule
+      .completed_lap(self.previous_distance, distance);
+    if let (Some(pause), Some(lap_index)) = (config.lap_pause, completed) {
+      self.highlighted = pause.pulse_highlight
+        && self.can_highlight
+        && lap_index < self.lap_stats.len();
+      if self.highlighted {
+        self.current_frame.copy_to(&mut self.highlight_frame)?;
+        draw_lap_highlight(
+          drawer,
+          &mut self.highlight_frame,
+          &config.lap_data,
+          &self.lap_stats,
+          lap_index,
+          self.lap_start,
+          self.highlight_color,
+        )?;
+      }
+      self.holds_left = self.schedule.hold_frames;
+    }
+    self.previous_distance = distance;
+
+    Ok(())
+  }
+}
+
+impl Iterator for FrameComposer {
+  type Item = Result<Frame>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_frame()
+  }
+}
+
+/// Steepest climb of the route, drawn in its own color
+#[derive(Clone)]
+struct ClimbHighlight {
+  /// Route segments of the climb
+  segments: Range<usize>,
+  color: core::Scalar,
+  /// Where on the route the label goes and the grade on it (None = no
+  /// label)
+  label: Option<(core::Point, f32)>,
+}
+
+/// Redraws one lap row of the lap panel in the highlight color
+fn draw_lap_highlight(
+  drawer: &Drawer,
+  frame: &mut Mat,
+  lap_config: &LapDataConfig,
+  stats: &[String],
+  lap: usize,
+  start: (i32, i32),
+  color: core::Scalar,
+) -> Result<()> {
+  let stat = &stats[lap];
+  let size = drawer.text_size(
+    stat,
+    lap_config.font_scale,
+    lap_config.thickness,
+    lap_config.font,
+  )?;
+  let x = start.0 - size.width / 2;
+  let y = start.1 + lap as i32 * (size.height + 5);
+
+  let stat_space = string_space(stats.len(), lap + 1, stat);
+  drawer.text(
+    frame,
+    &stat_space,
+    x,
+    y,
+    lap_config.font_scale,
+    lap_config.thickness,
+    lap_config.font,
+    color,
+  )
+}
+
+/// Route fade, if enabled and the distance series covers the route
+fn drawable_fade(
+  config: &RouteVideoConfig,
+  points: usize,
+  distances: usize,
+) -> Option<FadeConfig> {
+  config.route_fade.filter(|fade| {
+    fade.window_m > 0.0
+      && config.show_route
+      && config.render_style.draws_line()
+      && distances >= points
+  })
+}
+
+/// Overlays `config` asks for that `activity` has no data for; they are
+/// left out of the frames
+fn overlay_warnings(
+  config: &RouteVideoConfig,
+  activity: &Activity,
+) -> Warnings {
+  let Activity {
+    route,
+    lap,
+    session,
+    ..
+  } = activity;
+  let mut warnings = Warnings::default();
+
+  let lap_panel = config
+    .resolved_overlays()
+    .iter()
+    .any(|overlay| overlay.kind == OverlayKind::LapPanel);
+  warnings.push_if(
+    lap_panel && lap.avg_speed.is_empty(),
+    Warning::LapPanelSkipped,
+  );
+  let gradient = gradient_scale(
+    config.segment_coloring,
+    &route.altitudes,
+  );
+  warnings.push_if(
+    config.legend.is_some() && gradient.is_none(),
+    Warning::LegendSkipped,
+  );
+  warnings.push_if(
+    config.show_date && session.start_time.is_none(),
+    Warning::DateStampSkipped,
+  );
+  let requested = config.route_fade.is_some_and(|fade| fade.window_m > 0.0);
+  let fade = drawable_fade(
+    config,
+    route.gps_points.len(),
+    route.distances.len(),
+  );
+  warnings.push_if(
+    requested && fade.is_none(),
+    Warning::RouteFadeSkipped,
+  );
+  let cadence = cadence_series(&route.distances, &route.cadences);
+  warnings.push_if(
+    config.cadence_chart.is_some()
+      && cadence.iter().all(|(_, value)| value.is_none()),
+    Warning::CadenceChartSkipped,
+  );
+  warnings.push_if(
+    config.highlight_steepest_climb
+      && steepest_climb(route, config.steepest_climb.window_m).is_none(),
+    Warning::SteepestClimbSkipped,
+  );
+  warnings
+}
+
+/// Draws the lap statistics panel (header, paces, heart rate, power,
+/// stride and pace bars) with its header at `start`; cycling shows speed
+/// and cadence.
+/// `extra` adds a column of lap averages for an extra record field, and
+/// `summary` a line above the header.
+#[allow(clippy::too_many_arguments)]
+fn draw_lap_panel(
+  drawer: &Drawer,
+  frame: &mut Mat,
+  config: &RouteVideoConfig,
+  lap: &LapData,
+  sport: SportProfile,
+  extra: Option<(&ExtraField, &[Option<f64>])>,
+  summary: Option<&str>,
+  start: (i32, i32),
+) -> Result<()> {
+  let pace_seconds: Vec<f32> = lap
+    .enhanced_avg_speed
+    .iter()
+    .map(|p| convert_pace_to_sec(p))
+    .collect();
+
+  let (start_x, start_y) = start;
+  let bar_fractions = pace_bar_fractions(
+    &pace_seconds,
+    config.lap_data.pace_bar_normalization,
+  );
+  let bar_labels = pace_bar_labels(
+    &pace_seconds,
+    config.lap_data.bar_style.label_format,
+  );
+
+  // Columns after the heart rate make room for the power column
+  let shift = config.lap_data.power_shift();
+  let mut labels: Vec<(&str, i32)> =
+    sport.lap_labels(config.lap_data.show_power);
+  labels.extend(extra.map(|(field, _)| (field.label.as_str(), 400 + shift)));
+  drawer
+    .header(
+      frame,
+      start_x,
+      start_y,
+      config.lap_data.font_scale,
+      2,
+      config.lap_data.font,
+      &labels,
+      summary,
+    )
+    .expect("Failed to draw header!");
+
+  let text_color = drawer.color(config.lap_data.text_color.to_bgra());
+  let bar_color = drawer.color(config.colors.lap_bars);
+  let lap_stats = lap_stat_texts(lap, sport, &config.number_format);
+  let lap_details = lap_detail_texts(lap, sport, &config.number_format);
+  let heart_rates = lap_heart_rate_texts(lap);
+  let powers = lap_power_texts(lap);
+  let size_of_speeds = lap_stats.len();
+  let mut bar_column = None;
+
+  for (i, stat) in lap_stats.iter().enumerate() {
+    let size = drawer.text_size(
+      stat,
+      config.lap_data.font_scale,
+      config.lap_data.thickness,
+      config.lap_data.font,
+    )?;
+    let x = start_x - size.width / 2;
+    let y = start_y + i as i32 * (size.height + 5);
+
+    // Draw pace (or speed)
+    let stat_space = string_space(size_of_speeds, i + 1, stat);
+    drawer
+      .text(
+        frame,
+        &stat_space,
+        x,
+        y,
+        config.lap_data.font_scale,
+        config.lap_data.thickness,
+        config.lap_data.font,
+        text_color,
+      )
+      .expect("Failed to draw pace");
+
+    // Draw heart rate if enabled
+    if config.lap_data.show_heart_rate {
+      let hr = &heart_rates[i];
+      drawer
+        .text(
+          frame,
+          hr,
+          x + 300,
+          y,
+          config.lap_data.font_scale,
+          config.lap_data.thickness,
+          config.lap_data.font,
+          text_color,
+        )
+        .expect("Failed to draw heart rate");
+    }
+
+    // Draw power if enabled
+    if config.lap_data.show_power {
+      drawer.text(
+        frame,
+        &powers[i],
+        x + 350,
+        y,
+        config.lap_data.font_scale,
+        config.lap_data.thickness,
+        config.lap_data.font,
+        text_color,
+      )?;
+    }
+
+    // Draw stride length (or cadence) if enabled
+    if config.lap_data.show_stride_length {
+      drawer
+        .text(
+          frame,
+          &lap_details[i],
+          x + 350 + shift,
+          y,
+          config.lap_data.font_scale,
+          config.lap_data.thickness,
+          config.lap_data.font,
+          text_color,
+        )
+        .expect("Failed to draw stride length");
+    }
+
+    // Draw the extra field's lap average if configured
+    if let Some((_, averages)) = extra {
+      let average = match averages.get(i).copied().flatten() {
+        Some(average) => format_decimal(average, 0),
+        None => "-".to_string(),
+      };
+      drawer.text(
+        frame,
+        &average,
+        x + 420 + shift,
+        y,
+        config.lap_data.font_scale,
+        config.lap_data.thickness,
+        config.lap_data.font,
+        text_color,
+      )?;
+    }
+
+    // Draw pace bars if enabled
+    if config.lap_data.show_pace_bars {
+      let bar_width = (bar_fractions[i] * PACE_BAR_WIDTH as f32) as i32;
+      let bar_height = size.height;
+      let bar_x = x + size.width + 60;
+      let bar_y = y - size.height;
+      bar_column = Some(union(
+        bar_column,
+        core::Rect::new(bar_x, bar_y, PACE_BAR_WIDTH, bar_height),
+      ));
+      let rect = Rect {
+        pos: PositionRect { x: bar_x, y: bar_y },
+        size: SizeRect {
+          width: bar_width,
+          height: bar_height,
+        },
+      };
+      drawer
+        .pace_bar(
+          frame,
+          rect,
+          bar_color,
+          text_color,
+          &config.lap_data,
+          bar_labels[i].as_deref(),
+        )
+        .expect("Failed to draw bar");
+    }
+  }
+
+  if let (Some(axis), Some(column)) = (config.lap_data.bar_axis, bar_column) {
+    let ticks = pace_axis_ticks(
+      &pace_seconds,
+      config.lap_data.pace_bar_normalization,
+      axis.tick_interval_s,
+    );
+    drawer.pace_axis(
+      frame,
+      column,
+      &ticks,
+      &axis,
+      &config.lap_data,
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Frames the ticker takes to slide from one metric to the next
+const TICKER_SLIDE_FRAMES: usize = 10;
+
+/// Bottom pace/distance bar with text buffers reused across frames
+#[derive(Clone)]
+struct BottomBar {
+  config: PaceDistConfig,
+  sport: SportProfile,
+  extra_field: Option<ExtraField>,
+  /// Custom metrics with their values by record
+  custom_metrics: Vec<(CustomMetricConfig, Vec<Option<f64>>)>,
+  number_format: NumberFormat,
+  bar_height: i32,
+  /// Ticker metrics the activity has data for; empty for a static bar
+  ticker: Vec<Metric>,
+  /// Frames each ticker metric is shown for
+  ticker_frames: usize,
+  slide: bool,
+  pace_text: String,
+  /// Cadence in the middle of a static bar
+  center_text: String,
+  dist_text: String,
+}
+
+impl BottomBar {
+  /// Measures the bar once; its height does not depend on the text
+  fn new(
+    drawer: &Drawer,
+    config: PaceDistConfig,
+    sport: SportProfile,
+    extra_field: Option<ExtraField>,
+    number_format: NumberFormat,
+    fps: f64,
+    chips: &StatChips,
+  ) -> Result<Self> {
+    let bar_height = drawer.bar_height(
+      "Dist: 0.00 km",
+      config.font_scale,
+      config.thickness,
+      config.font,
+    )?;
+    let (ticker, ticker_frames, slide) = match &config.mode {
+      BottomBarMode::Static => (Vec::new(), 1, false),
+      BottomBarMode::Ticker {
+        metrics,
+        seconds_per_metric,
+        transition,
+      } => (
+        metrics
+          .iter()
+          .copied()
+          .filter(|&metric| chips.has_data(metric))
+          .collect(),
+        ((seconds_per_metric * fps).round() as usize).max(1),
+        *transition == TickerTransition::Slide,
+      ),
+    };
+    Ok(Self {
+      config,
+      sport,
+      extra_field,
+      custom_metrics: Vec::new(),
+      number_format,
+      bar_height,
+      ticker,
+      ticker_frames,
+      slide,
+      pace_text: String::new(),
+      center_text: String::new(),
+      dist_text: String::new(),
+    })
+  }
+
+  /// Shows `metrics`, each with its values by record, after the pace
+  fn with_custom_metrics(
+    mut self,
+    metrics: Vec<(CustomMetricConfig, Vec<Option<f64>>)>,
+  ) -> Self {
+    self.custom_metrics = metrics;
+    self
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn draw(
+    &mut self,
+    drawer: &Drawer,
+    frame: &mut Mat,
+    pace_seconds: f64,
+    speed: f64,
+    heart_rate: Option<u8>,
+    power: Option<u16>,
+    cadence: Option<u16>,
+    extra: Option<f64>,
+    record: usize,
+    distance: f64,
+  ) -> Result<()> {
+    let config = &self.config;
+    let show_extra = self.extra_field.is_some();
+    if !config.show_pace
+      && !config.show_distance
+      && !config.show_heart_rate
+      && !config.show_power
+      && !config.show_cadence
+      && !show_extra
+      && self.custom_metrics.is_empty()
+    {
+      return Ok(());
+    }
+
+    self.write_text(
+      pace_seconds,
+      speed,
+      heart_rate,
+      power,
+      cadence,
+      extra,
+      record,
+      distance,
+    )?;
+    drawer.text_bar_with_height(
+      frame,
+      &self.pace_text,
+      &self.center_text,
+      &self.dist_text,
+      self.bar_height,
+      self.config.font_scale,
+      self.config.thickness,
+      self.config.font,
+    )
+  }
+
+  /// Writes the left (pace, heart rate, power, extra field and the custom
+  /// metrics at `record`), center (cadence) and right (distance) texts of
+  /// the static bar
+  #[allow(clippy::too_many_arguments)]
+  fn write_text(
+    &mut self,
+    pace_seconds: f64,
+    speed: f64,
+    heart_rate: Option<u8>,
+    power: Option<u16>,
+    cadence: Option<u16>,
+    extra: Option<f64>,
+    record: usize,
+    distance: f64,
+  ) -> Result<()> {
+    let config = &self.config;
+    self.pace_text.clear();
+    if config.show_pace {
+      if self.sport.shows_speed() {
+        write!(
+          self.pace_text,
+          "Speed: {} km/h",
+          format_decimal(speed * 3.6, 1)
+        )?;
+      } else {
+        let pace = format_capped_pace(
+          pace_seconds,
+          self.sport.pace_cap_secs(),
+          &self.number_format,
+        );
+        write!(self.pace_text, "Pace: {} min/km", pace)?;
+      }
+    }
+    // A record without a heart rate shows none, not the last one
+    if let (true, Some(hr)) = (config.show_heart_rate, heart_rate) {
+      if !self.pace_text.is_empty() {
+        self.pace_text.push_str("  ");
+      }
+      write!(self.pace_text, "HR: {} bpm", hr)?;
+    }
+    if let (true, Some(power)) = (config.show_power, power) {
+      if !self.pace_text.is_empty() {
+        self.pace_text.push_str("  ");
+      }
+      write!(self.pace_text, "Pwr: {} W", power)?;
+    }
+    if let (Some(field), Some(value)) = (&self.extra_field, extra) {
+      if !self.pace_text.is_empty() {
+        self.pace_text.push_str("  ");
+      }
+      self.pace_text.push_str(&field.format(value));
+    }
+    // Like the heart rate, a record without the metric shows none
+    for (metric, values) in &self.custom_metrics {
+      if let Some(value) = values.get(record).copied().flatten() {
+        if !self.pace_text.is_empty() {
+          self.pace_text.push_str("  ");
+        }
+        self.pace_text.push_str(&metric.format(value));
+      }
+    }
+
+    self.center_text.clear();
+    if let (true, Some(cadence)) = (config.show_cadence, cadence) {
+      let label = config.cadence_label.as_deref().unwrap_or("Cadence");
+      write!(self.center_text, "{}: ", label)?;
+      write_cadence(
+        &mut self.center_text,
+        cadence,
+        self.sport,
+      )?;
+    }
+
+    self.dist_text.clear();
+    if config.show_distance {
+      write!(
+        self.dist_text,
+        "Dist: {} km",
+        format_distance(
+          distance,
+          Units::Metric,
+          &self.number_format
+        )
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Draws the ticker metric of planned frame `frame_index`, sliding in
+  /// over the previous one right after a switch
+  #[allow(clippy::too_many_arguments)]
+  fn draw_ticker(
+    &mut self,
+    drawer: &Drawer,
+    frame: &mut Mat,
+    chips: &mut StatChips,
+    frame_index: usize,
+    pen: PenPosition,
+    pace_seconds: f64,
+    distance: f64,
+  ) -> Result<()> {
+    let (active, previous) = ticker_slot(
+      frame_index,
+      self.ticker_frames,
+      self.ticker.len(),
+      self.slide,
+    );
+    let (pace, distance) = (Some(pace_seconds), Some(distance));
+    chips.write_labeled(
+      &mut self.pace_text,
+      self.ticker[active],
+      pen,
+      pace,
+      distance,
+    )?;
+
+    // The outgoing metric leaves to the left as the new one comes in
+    let width = drawer.width as f64;
+    let (outgoing_offset, offset) = match previous {
+      Some((previous, progress)) => {
+        chips.write_labeled(
+          &mut self.dist_text,
+          self.ticker[previous],
+          pen,
+          pace,
+          distance,
+        )?;
+        (
+          -(progress * width) as i32,
+          ((1.0 - progress) * width) as i32,
+        )
+      }
+      None => {
+        self.dist_text.clear();
+        (0, 0)
+      }
+    };
+
+    let config = &self.config;
+    drawer.ticker_bar(
+      frame,
+      &[
+        (&self.dist_text, outgoing_offset),
+        (&self.pace_text, offset),
+      ],
+      self.bar_height,
+      config.font_scale,
+      config.thickness,
+      config.font,
+    )
+  }
+}
+
+/// Ticker metric shown at planned frame `index` when each of `count`
+/// metrics is shown for `slot_frames` frames, and while sliding, the
+/// previous metric with how far the slide has got (0.0-1.0)
+fn ticker_slot(
+  index: usize,
+  slot_frames: usize,
+  count: usize,
+  slide: bool,
+) -> (usize, Option<(usize, f64)>) {
+  let slot = index / slot_frames;
+  let active = slot % count;
+  let slide_frames = TICKER_SLIDE_FRAMES.min(slot_frames);
+  let into_slot = index % slot_frames;
+  if !slide || slot == 0 || count < 2 || into_slot + 1 >= slide_frames {
+    return (active, None);
+  }
+  let progress = (into_slot + 1) as f64 / slide_frames as f64;
+  (
+    active,
+    Some(((slot - 1) % count, progress)),
+  )
+}
+
+/// Writes a record's cadence: steps per minute, or rpm when cycling
+fn write_cadence(
+  out: &mut String,
+  cadence: u16,
+  sport: SportProfile,
+) -> std::fmt::Result {
+  let unit = if sport.shows_speed() { "rpm" } else { "spm" };
+  write!(out, "{} {}", cadence, unit)
+}
+
+/// Floating stat chips with the per-record series they show
+#[derive(Clone, Default)]
+struct StatChips {
+  chips: Vec<StatChip>,
+  sport: SportProfile,
+  heart_rates: Vec<Option<u8>>,
+  cadences: Vec<Option<u16>>,
+  /// Elapsed or moving seconds, per `RouteVideoConfig::time_basis`
+  elapsed: Vec<Option<f64>>,
+  elevation_gain: Vec<Option<f64>>,
+  /// Smoothed compass bearing of travel
+  bearings: Vec<Option<f64>>,
+  /// Smoothed grade (%)
+  grades: Vec<Option<f32>>,
+  /// (lat, lon) of each record as parsed, untouched by line smoothing,
+  /// for verification footage
+  coordinates: Vec<(f64, f64)>,
+  number_format: NumberFormat,
+  text: String,
+}
+
+impl StatChips {
+  fn draw(
+    &mut self,
+    drawer: &Drawer,
+    frame: &mut Mat,
+    pen: PenPosition,
+    pace: Option<f64>,
+    distance: Option<f64>,
+  ) -> Result<()> {
+    for i in 0..self.chips.len() {
+      let chip = &self.chips[i];
+      let (metric, position, hide) = (
+        chip.metric,
+        chip.position,
+        chip.hide_when_missing,
+      );
+      let arrow = match metric {
+        Metric::Bearing if chip.bearing_arrow => {
+          self.bearings.get(pen.index).copied().flatten()
+        }
+        _ => None,
+      };
+      if !self.write_value(metric, pen, pace, distance)? {
+        if hide {
+          continue;
+        }
+        // Hershey fonts are ASCII-only, so no em dash
+        self.text.clear();
+        self.text.push_str("--");
+      }
+
+      let center = position.resolve((drawer.width, drawer.height));
+      drawer.chip(
+        frame,
+        &self.text,
+        center,
+        &self.chips[i].style,
+        arrow,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Whether the activity has data for the metric anywhere
+  fn has_data(&self, metric: Metric) -> bool {
+    match metric {
+      Metric::Pace | Metric::Distance => true,
+      Metric::HeartRate => self.heart_rates.iter().any(Option::is_some),
+      Metric::Cadence => self.cadences.iter().any(Option::is_some),
+      Metric::ElapsedTime => self.elapsed.iter().any(Option::is_some),
+      Metric::ElevationGain => self.elevation_gain.iter().any(Option::is_some),
+      Metric::Bearing => self.bearings.iter().any(Option::is_some),
+      Metric::CurrentGrade => self.grades.iter().any(Option::is_some),
+      Metric::Coordinates => !self.coordinates.is_empty(),
+    }
+  }
+
+  /// Writes "Label: value" for the metric at the pen into `out`, with
+  /// "--" when there is no data there
+  fn write_labeled(
+    &mut self,
+    out: &mut String,
+    metric: Metric,
+    pen: PenPosition,
+    pace: Option<f64>,
+    distance: Option<f64>,
+  ) -> Result<()> {
+    let found = self.write_value(metric, pen, pace, distance)?;
+    let label = match metric {
+      Metric::Pace => "Pace",
+      Metric::Distance => "Dist",
+      Metric::HeartRate => "HR",
+      Metric::Cadence => "Cadence",
+      Metric::ElapsedTime => "Time",
+      Metric::ElevationGain => "Gain",
+      Metric::Bearing => "Heading",
+      Metric::CurrentGrade => "Grade",
+      Metric::Coordinates => "GPS",
+    };
+    out.clear();
+    write!(
+      out,
+      "{}: {}",
+      label,
+      if found { self.text.as_str() } else { "--" }
+    )?;
+    Ok(())
+  }
+
+  /// Writes the metric's value at the pen into `text`; false when the
+  /// activity has no data for it there
+  fn write_value(
+    &mut self,
+    metric: Metric,
+    pen: PenPosition,
+    pace: Option<f64>,
+    distance: Option<f64>,
+  ) -> Result<bool> {
+    let at = |values: &[Option<f64>]| values.get(pen.index).copied().flatten();
+    let text = &mut self.text;
+    text.clear();
+    match metric {
+      Metric::Pace => {
+        let Some(pace) = pace else {
+          return Ok(false);
+        };
+        let pace = format_capped_pace(
+          pace,
+          self.sport.pace_cap_secs(),
+          &self.number_format,
+        );
+        write!(text, "{} /km", pace)?;
+      }
+      Metric::Distance => {
+        let Some(distance) = distance else {
+          return Ok(false);
+        };
+        let distance = format_distance(
+          distance,
+          Units::Metric,
+          &self.number_format,
+        );
+        write!(text, "{} km", distance)?;
+      }
+      Metric::HeartRate => {
+        let Some(hr) = self.heart_rates.get(pen.index).copied().flatten()
+        else {
+          return Ok(false);
+        };
+        write!(text, "{} bpm", hr)?;
+      }
+      Metric::Cadence => {
+        let Some(cadence) = self.cadences.get(pen.index).copied().flatten()
+        else {
+          return Ok(false);
+        };
+        write_cadence(text, cadence, self.sport)?;
+      }
+      Metric::ElapsedTime => {
+        let Some(elapsed) = at(&self.elapsed) else {
+          return Ok(false);
+        };
+        text.push_str(&format_duration(elapsed));
+      }
+      Metric::ElevationGain => {
+        let Some(gain) = at(&self.elevation_gain) else {
+          return Ok(false);
+        };
+        write!(text, "+{} m", format_decimal(gain, 0))?;
+      }
+      Metric::Bearing => {
+        let Some(bearing) = at(&self.bearings) else {
+          return Ok(false);
+        };
+        text.push_str(&format_bearing(bearing));
+      }
+      Metric::CurrentGrade => {
+        let Some(grade) = self.grades.get(pen.index).copied().flatten() else {
+          return Ok(false);
+        };
+        write!(
+          text,
+          "{}%",
+          format_decimal(grade as f64, 0)
+        )?;
+      }
+      Metric::Coordinates => {
+        // The record the pen is on, never a point between records
+        let Some(&(lat, lon)) = self.coordinates.get(pen.index) else {
+          return Ok(false);
+        };
+        text.push_str(&format_coordinates(
+          lat,
+          lon,
+          self.number_format.coordinate_decimals,
+        ));
+      }
+    }
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+  use crate::{
+    configs::{
+      BackgroundLayer, Color, CoursePreview, LayerSource, LegendConfig, Limits,
+      RouteRenderStyle, SeededStyle,
+    },
+    error::{LimitKind, RunariumError},
+    types::fit_data::{SessionRange, SessionSummary},
+  };
+
+  fn activity(records: usize) -> Activity {
+    Activity {
+      route: RouteData {
+        paces: vec!["5:00".to_string(); records],
+        gps_points: (0..records)
+          .map(|i| (13.7 + i as f64 * 1e-4, 100.5))
+          .collect(),
+        distances: (0..records).map(|i| i as f64 * 10.0).collect(),
+        heart_rates: vec![None; records],
+        speeds: vec![3.3; records],
+        cadences: vec![None; records],
+        powers: vec![None; records],
+        altitudes: vec![None; records],
+        timestamps: vec![None; records],
+        segment_breaks: Vec::new(),
+        extra: HashMap::new(),
+      },
+      lap: LapData {
+        avg_heart_rate: vec![],
+        avg_power: vec![],
+        enhanced_avg_speed: vec![],
+        avg_step_length: vec![],
+        total_distance: vec![],
+        avg_speed: vec![],
+        avg_cadence: vec![],
+        total_elapsed_time: vec![],
+      },
+      session: SessionSummary::default(),
+      warnings: Warnings::default(),
+      sessions: Vec::new(),
+    }
+  }
+
+  fn limit_error(result: Result<FrameComposer>) -> RunariumError {
+    let err = result.err().expect("limit not enforced");
+    err.downcast::<RunariumError>().unwrap()
+  }
+
+  #[test]
+  fn test_stat_chip_values() {
+    let mut chips = StatChips {
+      chips: Vec::new(),
+      sport: SportProfile::Running,
+      heart_rates: vec![Some(142), None],
+      cadences: vec![Some(176), Some(180)],
+      elapsed: elapsed_seconds(&[Some(1000), Some(4725)]),
+      elevation_gain: cumulative_gain(&[None, Some(20.0)]),
+      bearings: vec![None, Some(47.0)],
+      grades: vec![Some(-0.3), Some(7.6)],
+      coordinates: vec![(13.75632, 100.50177), (-33.8688, 151.2093)],
+      number_format: NumberFormat::default(),
+      text: String::new(),
+    };
+    let pen = |index| PenPosition {
+      index,
+      fraction: 0.0,
+    };
+    let mut value = |metric, index| {
+      chips
+        .write_value(
+          metric,
+          pen(index),
+          Some(306.8),
+          Some(1234.0),
+        )
+        .unwrap()
+        .then(|| chips.text.clone())
+    };
+
+    assert_eq!(
+      value(Metric::Pace, 0).as_deref(),
+      Some("5:07 /km")
+    );
+    assert_eq!(
+      value(Metric::Distance, 0).as_deref(),
+      Some("1.23 km")
+    );
+    assert_eq!(
+      value(Metric::HeartRate, 0).as_deref(),
+      Some("142 bpm")
+    );
+    assert_eq!(
+      value(Metric::Cadence, 1).as_deref(),
+      Some("180 spm")
+    );
+    assert_eq!(
+      value(Metric::ElapsedTime, 1).as_deref(),
+      Some("1:02:05")
+    );
+    assert_eq!(
+      value(Metric::ElevationGain, 1).as_deref(),
+      Some("+0 m")
+    );
+    assert_eq!(
+      value(Metric::Bearing, 1).as_deref(),
+      Some("NE 47°")
+    );
+    assert_eq!(
+      value(Metric::CurrentGrade, 1).as_deref(),
+      Some("8%")
+    );
+    assert_eq!(
+      value(Metric::CurrentGrade, 0).as_deref(),
+      Some("0%")
+    );
+    assert_eq!(
+      value(Metric::Coordinates, 1).as_deref(),
+      Some("33.8688°S, 151.2093°E")
+    );
+
+    // Missing data
+    assert_eq!(value(Metric::HeartRate, 1), None);
+    assert_eq!(value(Metric::ElevationGain, 0), None);
+    assert_eq!(value(Metric::Bearing, 0), None);
+    assert_eq!(value(Metric::HeartRate, 5), None);
+    assert_eq!(value(Metric::Coordinates, 5), None);
+
+    // Ticker text is labeled
+    let mut text = String::new();
+    for (metric, expected) in [
+      (Metric::HeartRate, "HR: 142 bpm"),
+      (Metric::Distance, "Dist: 1.23 km"),
+    ] {
+      chips
+        .write_labeled(
+          &mut text,
+          metric,
+          pen(0),
+          None,
+          Some(1234.0),
+        )
+        .unwrap();
+      assert_eq!(text, expected);
+    }
+    chips
+      .write_labeled(
+        &mut text,
+        Metric::Pace,
+        pen(0),
+        None,
+        None,
+      )
+      .unwrap();
+    assert_eq!(text, "Pace: --");
+  }
+
+  #[test]
+  fn test_ticker_slot() {
+    // Three metrics, 30 frames each
+    assert_eq!(ticker_slot(0, 30, 3, true), (0, None));
+    assert_eq!(ticker_slot(29, 30, 3, true), (0, None));
+    assert_eq!(
+      ticker_slot(30, 30, 3, true),
+      (1, Some((0, 0.1)))
+    );
+    assert_eq!(
+      ticker_slot(34, 30, 3, true),
+      (1, Some((0, 0.5)))
+    );
+    assert_eq!(ticker_slot(39, 30, 3, true), (1, None));
+    assert_eq!(
+      ticker_slot(90, 30, 3, true),
+      (0, Some((2, 0.1)))
+    );
+
+    // Cuts, a single metric and one-frame slots never slide
+    assert_eq!(ticker_slot(31, 30, 3, false), (1, None));
+    assert_eq!(ticker_slot(31, 30, 1, true), (0, None));
+    assert_eq!(ticker_slot(5, 1, 3, true), (2, None));
+  }
+
+  #[test]
+  fn test_ticker_skips_metrics_without_data() {
+    let chips = StatChips {
+      chips: Vec::new(),
+      sport: SportProfile::Running,
+      heart_rates: vec![None, None],
+      cadences: vec![None, Some(180)],
+      elapsed: vec![None, None],
+      elevation_gain: vec![None, None],
+      bearings: vec![None, None],
+      grades: vec![None, None],
+      coordinates: Vec::new(),
+      number_format: NumberFormat::default(),
+      text: String::new(),
+    };
+    let config = PaceDistConfig {
+      mode: BottomBarMode::Ticker {
+        metrics: vec![
+          Metric::Pace,
+          Metric::HeartRate,
+          Metric::Cadence,
+          Metric::ElevationGain,
+        ],
+        seconds_per_metric: 2.5,
+        transition: TickerTransition::Cut,
+      },
+      ..Default::default()
+    };
+    let bar = BottomBar::new(
+      &Drawer::new(100, 100),
+      config,
+      SportProfile::Running,
+      None,
+      NumberFormat::default(),
+      30.0,
+      &chips,
+    )
+    .unwrap();
+    assert_eq!(
+      bar.ticker,
+      vec![Metric::Pace, Metric::Cadence]
+    );
+    assert_eq!(bar.ticker_frames, 75);
+    assert!(!bar.slide);
+  }
+
+  #[test]
+  fn test_bottom_bar_heart_rate() {
+    let config = PaceDistConfig {
+      show_heart_rate: true,
+      ..Default::default()
+    };
+    let mut bar = BottomBar::new(
+      &Drawer::new(100, 100),
+      config,
+      SportProfile::Running,
+      None,
+      NumberFormat::default(),
+      30.0,
+      &StatChips::default(),
+    )
+    .unwrap();
+
+    bar
+      .write_text(
+        300.0,
+        3.3,
+        Some(152),
+        None,
+        None,
+        None,
+        0,
+        1234.0,
+      )
+      .unwrap();
+    assert_eq!(
+      bar.pace_text,
+      "Pace: 5:00 min/km  HR: 152 bpm"
+    );
+    assert_eq!(bar.dist_text, "Dist: 1.23 km");
+    bar
+      .write_text(
+        300.0, 3.3, None, None, None, None, 0, 1234.0,
+      )
+      .unwrap();
+    assert_eq!(bar.pace_text, "Pace: 5:00 min/km");
+
+    bar.config.show_pace = false;
+    bar
+      .write_text(
+        300.0,
+        3.3,
+        Some(98),
+        None,
+        None,
+        None,
+        0,
+        0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.pace_text, "HR: 98 bpm");
+  }
+
+  #[test]
+  fn test_bottom_bar_power() {
+    let config = PaceDistConfig {
+      show_heart_rate: true,
+      show_power: true,
+      ..Default::default()
+    };
+    let mut bar = BottomBar::new(
+      &Drawer::new(100, 100),
+      config,
+      SportProfile::Cycling,
+      None,
+      NumberFormat::default(),
+      30.0,
+      &StatChips::default(),
+    )
+    .unwrap();
+
+    bar
+      .write_text(
+        120.0,
+        8.0,
+        Some(141),
+        Some(214),
+        None,
+        None,
+        0,
+        0.0,
+      )
+      .unwrap();
+    assert_eq!(
+      bar.pace_text,
+      "Speed: 28.8 km/h  HR: 141 bpm  Pwr: 214 W"
+    );
+    // A record without power shows none
+    bar
+      .write_text(
+        120.0, 8.0, None, None, None, None, 0, 0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.pace_text, "Speed: 28.8 km/h");
+  }
+
+  #[test]
+  fn test_bottom_bar_custom_metrics() {
+    let gct = CustomMetricConfig::new("Ground Time", "GCT", "ms");
+    let mut bar = BottomBar::new(
+      &Drawer::new(100, 100),
+      PaceDistConfig::default(),
+      SportProfile::Running,
+      None,
+      NumberFormat::default(),
+      30.0,
+      &StatChips::default(),
+    )
+    .unwrap()
+    .with_custom_metrics(vec![(gct, vec![Some(244.0), None])]);
+
+    bar
+      .write_text(
+        300.0, 3.3, None, None, None, None, 0, 0.0,
+      )
+      .unwrap();
+    assert_eq!(
+      bar.pace_text,
+      "Pace: 5:00 min/km  GCT: 244 ms"
+    );
+    // Records without the metric show none
+    bar
+      .write_text(
+        300.0, 3.3, None, None, None, None, 1, 0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.pace_text, "Pace: 5:00 min/km");
+  }
+
+  #[test]
+  fn test_bottom_bar_cadence() {
+    let config = PaceDistConfig {
+      show_cadence: true,
+      ..Default::default()
+    };
+    let mut bar = BottomBar::new(
+      &Drawer::new(100, 100),
+      config,
+      SportProfile::Running,
+      None,
+      NumberFormat::default(),
+      30.0,
+      &StatChips::default(),
+    )
+    .unwrap();
+
+    bar
+      .write_text(
+        300.0,
+        3.3,
+        None,
+        None,
+        Some(176),
+        None,
+        0,
+        0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.center_text, "Cadence: 176 spm");
+    assert_eq!(bar.pace_text, "Pace: 5:00 min/km");
+    bar
+      .write_text(
+        300.0, 3.3, None, None, None, None, 0, 0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.center_text, "");
+
+    bar.sport = SportProfile::Cycling;
+    bar.config.cadence_label = Some("Cad".to_string());
+    bar
+      .write_text(
+        300.0,
+        8.0,
+        None,
+        None,
+        Some(92),
+        None,
+        0,
+        0.0,
+      )
+      .unwrap();
+    assert_eq!(bar.center_text, "Cad: 92 rpm");
+  }
+
+  #[test]
+  fn test_bottom_bar_heart_rate_follows_the_records() {
+    let mut activity = activity(6);
+    activity.route.heart_rates =
+      vec![Some(150), None, Some(152), Some(155), None, None];
+    let mut config = RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((64, 64)),
+      show_lap_data: false,
+      ..Default::default()
+    };
+    config.pace_dist.show_heart_rate = true;
+    let expected = activity.route.heart_rates.clone();
+    let mut composer = FrameComposer::new(activity, config).unwrap();
+
+    let mut shown = Vec::new();
+    while let Some(frame) = composer.next_mat() {
+      frame.unwrap();
+      let text = &composer.bottom_bar.pace_text;
+      shown.push(
+        text
+          .split_once("HR: ")
+          .map(|(_, hr)| hr.trim_end_matches(" bpm").parse().unwrap()),
+      );
+    }
+    assert_eq!(shown, expected);
+  }
+
+  #[test]
+  fn test_record_limit() {
+    let config = RouteVideoConfig {
+      limits: Limits {
+        max_records: 10,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let err = limit_error(FrameComposer::new(activity(11), config));
+    assert_eq!(
+      err,
+      RunariumError::LimitExceeded {
+        which: LimitKind::Records,
+        actual: 11,
+        limit: 10
+      }
+    );
+  }
+
+  #[test]
+  fn test_output_size_limit() {
+    let config = RouteVideoConfig {
+      output_size: Some((16000, 16000)),
+      ..Default::default()
+    };
+
+    let err = limit_error(FrameComposer::new(activity(10), config));
+    assert!(matches!(
+      err,
+      RunariumError::LimitExceeded {
+        which: LimitKind::OutputPixels,
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn test_frame_and_duration_limits() {
+    let config = RouteVideoConfig {
+      limits: Limits {
+        max_frames: 5,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+    let err = limit_error(FrameComposer::new(activity(10), config));
+    assert!(matches!(
+      err,
+      RunariumError::LimitExceeded {
+        which: LimitKind::Frames,
+        ..
+      }
+    ));
+
+    // One frame per second for 1000 records
+    let config = RouteVideoConfig {
+      duration_secs: 900.0,
+      ..Default::default()
+    };
+    let err = limit_error(FrameComposer::new(
+      activity(1000),
+      config,
+    ));
+    assert!(matches!(
+      err,
+      RunariumError::LimitExceeded {
+        which: LimitKind::DurationSeconds,
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn test_overlay_warnings() {
+    // No laps, no altitudes, no start time, a dots-only route
+    let config = RouteVideoConfig {
+      legend: Some(LegendConfig::default()),
+      show_date: true,
+      route_fade: Some(FadeConfig::new(500.0, Color::Black)),
+      render_style: RouteRenderStyle::Dots {
+        radius: 4,
+        spacing_m: 50.0,
+      },
+      highlight_steepest_climb: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      overlay_warnings(&config, &activity(10)).0,
+      vec![
+        Warning::LapPanelSkipped,
+        Warning::LegendSkipped,
+        Warning::DateStampSkipped,
+        Warning::RouteFadeSkipped,
+        Warning::SteepestClimbSkipped,
+      ]
+    );
+
+    // Nothing requested, nothing skipped
+    let config = RouteVideoConfig {
+      show_lap_data: false,
+      ..Default::default()
+    };
+    assert!(overlay_warnings(&config, &activity(10)).is_empty());
+  }
+
+  #[test]
+  fn test_seeded_style_replaces_the_colors() {
+    let background = BackgroundLayer::new(LayerSource::Solid {
+      color: [40.0, 40.0, 40.0, 0.0],
+    });
+    let config = RouteVideoConfig {
+      backgrounds: vec![background.clone()],
+      output_size: Some((64, 64)),
+      seeded_style: Some(SeededStyle::fixed(7)),
+      ..Default::default()
+    };
+    let composer = FrameComposer::new(activity(10), config).unwrap();
+
+    let palette = composer.palette().expect("palette not reported");
+    assert_eq!(palette.seed, 7);
+    let config = composer.config();
+    assert_eq!(
+      config.colors.route_line,
+      palette.route_line
+    );
+    assert_eq!(config.colors.lap_bars, palette.lap_bars);
+    // The wash is laid over the background, and the style is taken out
+    // so the config re-renders with the same colors
+    assert_eq!(config.backgrounds.len(), 2);
+    assert_eq!(config.backgrounds[0], background);
+    assert_eq!(config.seeded_style, None);
+  }
+
+  #[test]
+  fn test_summary_comes_from_the_session() {
+    let config = RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((64, 64)),
+      show_summary: true,
+      ..Default::default()
+    };
+    let mut activity = activity(10);
+    activity.session = SessionSummary {
+      total_distance: Some(10020.0),
+      total_timer_time: Some(3151.0),
+      total_calories: Some(531),
+      avg_heart_rate: Some(156),
+      ..Default::default()
+    };
+    let composer =
+      FrameComposer::new(activity.clone(), config.clone()).unwrap();
+    assert_eq!(
+      composer.summary,
+      vec!["10.02 km", "52:31", "531 kcal", "156 bpm"]
+    );
+
+    // Without a Session message there is no footer
+    activity.session = SessionSummary::default();
+    let composer = FrameComposer::new(activity, config).unwrap();
+    assert!(composer.summary.is_empty());
+  }
+
+  #[test]
+  fn test_course_preview_is_filled_in() {
+    let config = RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((64, 64)),
+      show_bottom_bar: false,
+      show_lap_data: false,
+      course_preview: Some(CoursePreview {
+        color: [240.0, 240.0, 240.0, 0.0],
+        thickness: 3,
+        alpha: 0.5,
+      }),
+      ..Default::default()
+    };
+    let mut composer = FrameComposer::new(activity(10), config).unwrap();
+    let pixel = |frame: &Frame, point: core::Point| {
+      *frame.data.at_2d::<core::Vec3b>(point.y, point.x).unwrap()
+    };
+    // Midway along the route, away from the position marker
+    let points = composer.line.points.clone();
+    let middle = points[points.len() / 2];
+    let corner = core::Point::new(0, 0);
+
+    // Half way from the background to the preview color
+    let first = composer.next_frame().unwrap().unwrap();
+    assert_eq!(
+      pixel(&first, middle),
+      core::Vec3b::all(140)
+    );
+    assert_eq!(
+      pixel(&first, corner),
+      core::Vec3b::all(40)
+    );
+
+    // Covered by the route line on the last frame
+    let route = composer.route_color;
+    let last = composer.last().unwrap().unwrap();
+    assert_eq!(
+      pixel(&last, middle),
+      core::Vec3b::from_array(
+        [route[0] as u8, route[1] as u8, route[2] as u8,]
+      )
+    );
+    assert_eq!(
+      pixel(&last, corner),
+      core::Vec3b::all(40)
+    );
+  }
+
+  #[test]
+  fn test_separate_sessions_leave_a_gap() {
+    let mut duathlon = activity(10);
+    duathlon.sessions = vec![
+      SessionRange {
+        records: 0..5,
+        distances: 0..5,
+        laps: 0..0,
+        sport: None,
+      },
+      SessionRange {
+        records: 5..10,
+        distances: 5..10,
+        laps: 0..0,
+        sport: None,
+      },
+    ];
+    let config = |sessions| RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((256, 256)),
+      show_bottom_bar: false,
+      show_lap_data: false,
+      sessions,
+      ..Default::default()
+    };
+    let last_pixel = |sessions| {
+      let composer =
+        FrameComposer::new(duathlon.clone(), config(sessions)).unwrap();
+      let points = &composer.line.points;
+      let gap = core::Point::new(
+        (points[4].x + points[5].x) / 2,
+        (points[4].y + points[5].y) / 2,
+      );
+      let last = composer.last().unwrap().unwrap();
+      *last.data.at_2d::<core::Vec3b>(gap.y, gap.x).unwrap()
+    };
+
+    assert_ne!(
+      last_pixel(SessionSelection::Joined),
+      core::Vec3b::all(40)
+    );
+    assert_eq!(
+      last_pixel(SessionSelection::Separate),
+      core::Vec3b::all(40)
+    );
+
+    // A single session is rendered on its own
+    let composer = FrameComposer::new(
+      duathlon.clone(),
+      config(SessionSelection::Index(1)),
+    )
+    .unwrap();
+    assert_eq!(composer.point_count(), 5);
+  }
+
+  #[test]
+  fn test_timer_pauses_leave_a_gap() {
+    let mut paused = activity(10);
+    paused.route.segment_breaks = vec![4];
+    let last_pixel = |connect_pauses| {
+      let config = RouteVideoConfig {
+        backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+          color: [40.0, 40.0, 40.0, 0.0],
+        })],
+        output_size: Some((256, 256)),
+        show_bottom_bar: false,
+        show_lap_data: false,
+        connect_pauses,
+        ..Default::default()
+      };
+      let composer = FrameComposer::new(paused.clone(), config).unwrap();
+      let points = &composer.line.points;
+      let gap = core::Point::new(
+        (points[4].x + points[5].x) / 2,
+        (points[4].y + points[5].y) / 2,
+      );
+      let last = composer.last().unwrap().unwrap();
+      *last.data.at_2d::<core::Vec3b>(gap.y, gap.x).unwrap()
+    };
+
+    assert_eq!(last_pixel(false), core::Vec3b::all(40));
+    assert_ne!(last_pixel(true), core::Vec3b::all(40));
+  }
+}
@@ -0,0 +1,533 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt,
+  sync::{Arc, Condvar, Mutex, MutexGuard},
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{
+  configs::RouteVideoConfig,
+  generators::route_video::progressive_route_from_bytes_with_progress,
+  types::output::RenderedBytes, utils::fingerprint::input_fingerprint,
+};
+
+/// Identifier of a submitted job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct JobId(pub u64);
+
+impl fmt::Display for JobId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+/// Inputs of a video job
+#[derive(Debug, Clone)]
+pub struct VideoJob {
+  pub fit: Vec<u8>,
+  pub background: Vec<u8>,
+  pub config: RouteVideoConfig,
+}
+
+/// Where a job is
+#[derive(Debug, Clone)]
+pub enum JobStatus {
+  /// Waiting for a free worker
+  Queued,
+  /// Rendering; `done` of `total` planned frames are encoded
+  Running {
+    done: usize,
+    total: usize,
+  },
+  Done(Arc<RenderedBytes>),
+  Failed(Arc<anyhow::Error>),
+}
+
+/// How submissions were served since the manager started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+  /// Served from a finished result in the `ResultStore`
+  pub hits: u64,
+  /// Joined an identical job that was queued or running
+  pub coalesced: u64,
+  /// Rendered from scratch
+  pub misses: u64,
+}
+
+/// Progress callback of a render, given `(done, total)` frames
+pub type OnProgress<'a> = dyn FnMut(usize, usize) + 'a;
+
+/// Renders a job, reporting its progress to the callback
+pub type VideoRenderer =
+  dyn Fn(&VideoJob, &mut OnProgress<'_>) -> Result<RenderedBytes> + Send + Sync;
+
+/// Finished renders by key (an input fingerprint, or a download id), each
+/// kept for the store's TTL
+#[derive(Debug)]
+pub struct ResultStore {
+  ttl: Duration,
+  results: HashMap<String, (Instant, Arc<RenderedBytes>)>,
+}
+
+impl ResultStore {
+  pub fn new(ttl: Duration) -> Self {
+    Self {
+      ttl,
+      results: HashMap::new(),
+    }
+  }
+
+  /// Result stored under `fingerprint`, unless it is missing or expired
+  pub fn get(&mut self, fingerprint: &str) -> Option<Arc<RenderedBytes>> {
+    self.purge_expired();
+    self
+      .results
+      .get(fingerprint)
+      .map(|(_, result)| Arc::clone(result))
+  }
+
+  pub fn insert(&mut self, fingerprint: String, result: Arc<RenderedBytes>) {
+    self.results.insert(fingerprint, (Instant::now(), result));
+  }
+
+  /// Drops every result older than the TTL
+  pub fn purge_expired(&mut self) {
+    let ttl = self.ttl;
+    self.results.retain(|_, (stored, _)| stored.elapsed() < ttl);
+  }
+
+  pub fn len(&self) -> usize {
+    self.results.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.results.is_empty()
+  }
+}
+
+struct Job {
+  fingerprint: String,
+  status: JobStatus,
+  /// When the job was done or failed, to expire it with the results
+  finished: Option<Instant>,
+}
+
+struct State {
+  next_id: u64,
+  jobs: HashMap<JobId, Job>,
+  /// Queued or running job of each fingerprint
+  in_flight: HashMap<String, JobId>,
+  queue: VecDeque<(JobId, VideoJob)>,
+  results: ResultStore,
+  stats: CacheStats,
+  shutdown: bool,
+}
+
+impl State {
+  fn add_job(&mut self, fingerprint: String, status: JobStatus) -> JobId {
+    let id = JobId(self.next_id);
+    self.next_id += 1;
+    let finished = matches!(status, JobStatus::Done(_)).then(Instant::now);
+    self.jobs.insert(
+      id,
+      Job {
+        fingerprint,
+        status,
+        finished,
+      },
+    );
+    id
+  }
+
+  /// Forgets finished jobs once their result would have expired
+  fn purge_expired(&mut self) {
+    let ttl = self.results.ttl;
+    self.jobs.retain(|_, job| {
+      job.finished.is_none_or(|finished| finished.elapsed() < ttl)
+    });
+    self.results.purge_expired();
+  }
+}
+
+struct Shared {
+  state: Mutex<State>,
+  /// Signalled when a job is queued, finishes, or the manager shuts down
+  changed: Condvar,
+}
+
+impl Shared {
+  fn lock(&self) -> MutexGuard<'_, State> {
+    // A panicking renderer cannot leave the state half updated
+    self.state.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+/// Runs video renders on a fixed pool of worker threads, rendering each
+/// distinct input once.
+///
+/// Jobs are keyed by `input_fingerprint` of their config and files. A
+/// submission identical to a queued or running job gets that job's id,
+/// and one identical to a job finished within the TTL is served from the
+/// `ResultStore` without rendering again. Failed jobs are not cached.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use runarium::configs::RouteVideoConfig;
+/// use runarium::generators::jobs::JobManager;
+///
+/// let manager = JobManager::new(2, Duration::from_secs(600));
+/// let fit = std::fs::read("source/example.fit").unwrap();
+/// let background = std::fs::read("source/example.jpg").unwrap();
+///
+/// let id = manager.submit_video(fit, background, RouteVideoConfig::default());
+/// let rendered = manager.wait(id).unwrap();
+/// println!("{} bytes, {:?}", rendered.bytes.len(), manager.stats());
+/// ```
+pub struct JobManager {
+  shared: Arc<Shared>,
+}
+
+impl JobManager {
+  /// Manager rendering with `progressive_route_from_bytes` on `workers`
+  /// threads, keeping finished results for `ttl`
+  pub fn new(workers: usize, ttl: Duration) -> Self {
+    Self::with_renderer(workers, ttl, |job, on_progress| {
+      progressive_route_from_bytes_with_progress(
+        &job.fit,
+        &job.background,
+        job.config.clone(),
+        on_progress,
+      )
+    })
+  }
+
+  /// Manager rendering every job with `renderer`
+  pub fn with_renderer(
+    workers: usize,
+    ttl: Duration,
+    renderer: impl Fn(&VideoJob, &mut OnProgress<'_>) -> Result<RenderedBytes>
+      + Send
+      + Sync
+      + 'static,
+  ) -> Self {
+    let shared = Arc::new(Shared {
+      state: Mutex::new(State {
+        next_id: 0,
+        jobs: HashMap::new(),
+        in_flight: HashMap::new(),
+        queue: VecDeque::new(),
+        results: ResultStore::new(ttl),
+        stats: CacheStats::default(),
+        shutdown: false,
+      }),
+      changed: Condvar::new(),
+    });
+    let renderer: Arc<VideoRenderer> = Arc::new(renderer);
+    for _ in 0..workers.max(1) {
+      let shared = Arc::clone(&shared);
+      let renderer = Arc::clone(&renderer);
+      thread::spawn(move || work(&shared, renderer.as_ref()));
+    }
+    Self { shared }
+  }
+
+  /// Queues a render of `fit` over `background` with `config`, or returns
+  /// the job already serving the same inputs
+  pub fn submit_video(
+    &self,
+    fit: Vec<u8>,
+    background: Vec<u8>,
+    config: RouteVideoConfig,
+  ) -> JobId {
+    let fingerprint = input_fingerprint(&config, &fit, &background);
+    let mut state = self.shared.lock();
+    state.purge_expired();
+
+    if let Some(&id) = state.in_flight.get(&fingerprint) {
+      state.stats.coalesced += 1;
+      return id;
+    }
+    if let Some(result) = state.results.get(&fingerprint) {
+      state.stats.hits += 1;
+      return state.add_job(fingerprint, JobStatus::Done(result));
+    }
+
+    state.stats.misses += 1;
+    let id = state.add_job(fingerprint.clone(), JobStatus::Queued);
+    state.in_flight.insert(fingerprint, id);
+    state.queue.push_back((
+      id,
+      VideoJob {
+        fit,
+        background,
+        config,
+      },
+    ));
+    self.shared.changed.notify_all();
+    id
+  }
+
+  /// Status of job `id`; None once it is unknown or expired
+  pub fn status(&self, id: JobId) -> Option<JobStatus> {
+    let state = self.shared.lock();
+    state.jobs.get(&id).map(|job| job.status.clone())
+  }
+
+  /// Blocks until job `id` is done and returns its result
+  pub fn wait(
+    &self,
+    id: JobId,
+  ) -> Result<Arc<RenderedBytes>, Arc<anyhow::Error>> {
+    let mut state = self.shared.lock();
+    loop {
+      match state.jobs.get(&id).map(|job| &job.status) {
+        Some(JobStatus::Done(result)) => return Ok(Arc::clone(result)),
+        Some(JobStatus::Failed(err)) => return Err(Arc::clone(err)),
+        Some(_) => {}
+        None => return Err(Arc::new(anyhow!("Unknown job {}", id))),
+      }
+      state = self
+        .shared
+        .changed
+        .wait(state)
+        .unwrap_or_else(|e| e.into_inner());
+    }
+  }
+
+  /// Hit, coalesced and miss counts of the submissions so far
+  pub fn stats(&self) -> CacheStats {
+    self.shared.lock().stats
+  }
+}
+
+impl Drop for JobManager {
+  /// Workers finish the job they are rendering and stop; queued jobs are
+  /// dropped
+  fn drop(&mut self) {
+    self.shared.lock().shutdown = true;
+    self.shared.changed.notify_all();
+  }
+}
+
+/// Worker loop: renders queued jobs until the manager is dropped
+fn work(shared: &Shared, renderer: &VideoRenderer) {
+  loop {
+    let (id, job) = {
+      let mut state = shared.lock();
+      loop {
+        if state.shutdown {
+          return;
+        }
+        if let Some(next) = state.queue.pop_front() {
+          break next;
+        }
+        state = shared
+          .changed
+          .wait(state)
+          .unwrap_or_else(|e| e.into_inner());
+      }
+    };
+    set_status(
+      shared,
+      id,
+      JobStatus::Running { done: 0, total: 0 },
+    );
+
+    let result = renderer(&job, &mut |done, total| {
+      set_status(
+        shared,
+        id,
+        JobStatus::Running { done, total },
+      );
+    });
+
+    let mut state = shared.lock();
+    let Some(fingerprint) =
+      state.jobs.get(&id).map(|job| job.fingerprint.clone())
+    else {
+      continue;
+    };
+    state.in_flight.remove(&fingerprint);
+    let status = match result {
+      Ok(rendered) => {
+        let rendered = Arc::new(rendered);
+        state.results.insert(fingerprint, Arc::clone(&rendered));
+        JobStatus::Done(rendered)
+      }
+      Err(err) => JobStatus::Failed(Arc::new(err)),
+    };
+    if let Some(job) = state.jobs.get_mut(&id) {
+      job.status = status;
+      job.finished = Some(Instant::now());
+    }
+    shared.changed.notify_all();
+  }
+}
+
+fn set_status(shared: &Shared, id: JobId, status: JobStatus) {
+  if let Some(job) = shared.lock().jobs.get_mut(&id) {
+    job.status = status;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc, Barrier,
+  };
+
+  use super::*;
+  use crate::{
+    config::RouteScale,
+    types::{output::RenderOutput, warning::Warnings},
+    utils::projection::Projector,
+  };
+
+  fn rendered(bytes: Vec<u8>) -> RenderedBytes {
+    let points = [(13.7, 100.5), (13.8, 100.6)];
+    RenderedBytes {
+      bytes,
+      output: RenderOutput {
+        output_file: "route.mp4".to_string(),
+        projection: Projector::new(&points, RouteScale::default(), 100)
+          .projection_info(),
+        warnings: Warnings::default(),
+        time_index: Vec::new(),
+        palette: None,
+      },
+    }
+  }
+
+  /// Manager whose renders count themselves and finish only when the
+  /// returned sender says so
+  fn gated_manager(
+    ttl: Duration,
+  ) -> (
+    JobManager,
+    Arc<AtomicUsize>,
+    mpsc::Sender<()>,
+  ) {
+    let renders = Arc::new(AtomicUsize::new(0));
+    let (release, gate) = mpsc::channel::<()>();
+    let gate = Mutex::new(gate);
+    let counter = Arc::clone(&renders);
+    let manager = JobManager::with_renderer(2, ttl, move |job, on_progress| {
+      on_progress(0, 2);
+      counter.fetch_add(1, Ordering::SeqCst);
+      gate.lock().unwrap().recv()?;
+      on_progress(2, 2);
+      Ok(rendered(job.fit.clone()))
+    });
+    (manager, renders, release)
+  }
+
+  #[test]
+  fn test_identical_jobs_render_once() {
+    let (manager, renders, release) = gated_manager(Duration::from_secs(60));
+    let manager = Arc::new(manager);
+
+    let start = Arc::new(Barrier::new(2));
+    let submitters: Vec<_> = (0..2)
+      .map(|_| {
+        let (manager, start) = (Arc::clone(&manager), Arc::clone(&start));
+        thread::spawn(move || {
+          start.wait();
+          manager.submit_video(
+            b"fit".to_vec(),
+            b"bg".to_vec(),
+            RouteVideoConfig::default(),
+          )
+        })
+      })
+      .collect();
+    let ids: Vec<JobId> =
+      submitters.into_iter().map(|s| s.join().unwrap()).collect();
+    assert_eq!(ids[0], ids[1]);
+
+    release.send(()).unwrap();
+    let (first, second) = (
+      manager.wait(ids[0]),
+      manager.wait(ids[1]),
+    );
+    assert!(Arc::ptr_eq(
+      &first.unwrap(),
+      &second.unwrap()
+    ));
+    assert_eq!(renders.load(Ordering::SeqCst), 1);
+
+    // Served from the result store while it is fresh
+    let again = manager.submit_video(
+      b"fit".to_vec(),
+      b"bg".to_vec(),
+      RouteVideoConfig::default(),
+    );
+    assert_ne!(again, ids[0]);
+    assert_eq!(
+      manager.wait(again).unwrap().bytes,
+      b"fit"
+    );
+    assert_eq!(renders.load(Ordering::SeqCst), 1);
+    assert_eq!(
+      manager.stats(),
+      CacheStats {
+        hits: 1,
+        coalesced: 1,
+        misses: 1,
+      }
+    );
+  }
+
+  #[test]
+  fn test_different_inputs_and_expired_results_render_again() {
+    let (manager, renders, release) = gated_manager(Duration::ZERO);
+    let submit = |fit: &[u8]| {
+      manager.submit_video(
+        fit.to_vec(),
+        b"bg".to_vec(),
+        RouteVideoConfig::default(),
+      )
+    };
+
+    let (a, b) = (submit(b"fit a"), submit(b"fit b"));
+    assert_ne!(a, b);
+    release.send(()).unwrap();
+    release.send(()).unwrap();
+    manager.wait(a).unwrap();
+    manager.wait(b).unwrap();
+
+    // A zero TTL expires the result at once
+    let c = submit(b"fit a");
+    release.send(()).unwrap();
+    manager.wait(c).unwrap();
+    assert_eq!(renders.load(Ordering::SeqCst), 3);
+    assert_eq!(manager.stats().misses, 3);
+    assert_eq!(manager.stats().hits, 0);
+  }
+
+  #[test]
+  fn test_failed_jobs_are_not_cached() {
+    let manager =
+      JobManager::with_renderer(1, Duration::from_secs(60), |_, _| {
+        Err(anyhow!("broken FIT file"))
+      });
+    let submit = || {
+      manager.submit_video(
+        b"fit".to_vec(),
+        b"bg".to_vec(),
+        RouteVideoConfig::default(),
+      )
+    };
+
+    let err = manager.wait(submit()).unwrap_err();
+    assert_eq!(err.to_string(), "broken FIT file");
+    assert!(manager.wait(submit()).is_err());
+    assert_eq!(manager.stats().misses, 2);
+  }
+}
@@ -1,2 +1,5 @@
+pub mod frame_composer;
+pub mod jobs;
+pub mod render_cache;
 pub mod route_image;
 pub mod route_video;
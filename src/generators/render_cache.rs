@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use opencv::prelude::*;
+
+use crate::{
+  configs::{DynamicConfigPatch, RouteVideoConfig},
+  generators::{
+    frame_composer::{Frame, FrameComposer},
+    route_video::encode_video,
+  },
+  types::{fit_data::Activity, output::RenderOutput},
+  utils::performance::processed,
+};
+
+/// Reusable render state of one activity: the parsed records, the
+/// projected route, the resized background and the static overlays baked
+/// into the base frame.
+///
+/// Renders only accept a `DynamicConfigPatch`, which cannot touch what the
+/// base frame was built from, so tuning the bottom bar or the stat chips
+/// skips parsing, decoding and the lap panel on every iteration.
+///
+/// # Example
+/// ```no_run
+/// use runarium::configs::{DynamicConfigPatch, RouteVideoConfig};
+/// use runarium::generators::render_cache::RenderCache;
+/// use runarium::utils::read_file::activity_reader;
+///
+/// let config = RouteVideoConfig::default();
+/// let activity = activity_reader(
+///   &config.file_config.fit_file,
+///   &config.captured_fields(),
+/// )
+/// .unwrap();
+/// let cache = RenderCache::prepare(activity, config).unwrap();
+///
+/// let patch: DynamicConfigPatch =
+///   serde_json::from_str(r#"{"pace_dist": {"font_scale": 1.2}}"#).unwrap();
+/// let preview = cache.render_frame(&patch, 120).unwrap();
+/// println!("{}x{}", preview.width, preview.height);
+/// cache.render_video(&patch).unwrap();
+/// ```
+pub struct RenderCache {
+  /// Composer that never composes; every render starts from a fork of it
+  base: FrameComposer,
+}
+
+impl RenderCache {
+  /// Loads the background, projects the route and draws the static
+  /// overlays once
+  pub fn prepare(
+    activity: Activity,
+    base_config: RouteVideoConfig,
+  ) -> Result<Self> {
+    Ok(Self {
+      base: FrameComposer::new(activity, base_config)?,
+    })
+  }
+
+  /// Configuration the cache was prepared with
+  pub fn config(&self) -> &RouteVideoConfig {
+    self.base.config()
+  }
+
+  /// Number of planned frames (lap pause holds excluded)
+  pub fn frame_count(&self) -> usize {
+    self.base.progress().1
+  }
+
+  /// Renders the whole video with `overrides` applied
+  pub fn render_video(
+    &self,
+    overrides: &DynamicConfigPatch,
+  ) -> Result<RenderOutput> {
+    let composer = self.base.fork(overrides)?;
+    encode_video(composer, |done, total| {
+      processed(done - 1, total)
+    })
+  }
+
+  /// Renders planned frame `index` (lap pause holds excluded) with
+  /// `overrides` applied, without composing the frames before it
+  pub fn render_frame(
+    &self,
+    overrides: &DynamicConfigPatch,
+    index: usize,
+  ) -> Result<Frame> {
+    let mut composer = self.base.fork(overrides)?;
+    let (width, height) = (composer.width(), composer.height());
+    let mat = composer.compose_planned(index)?.ok_or_else(|| {
+      anyhow!(
+        "Frame {} is out of range ({} planned)",
+        index,
+        self.frame_count()
+      )
+    })?;
+
+    Ok(Frame {
+      data: mat.try_clone()?,
+      index,
+      width,
+      height,
+    })
+  }
+}
@@ -1,32 +1,68 @@
-use anyhow::Result;
-use opencv::{core, imgproc, prelude::*};
+use std::{borrow::Cow, fs, path::Path};
+
+use anyhow::{bail, Result};
+use opencv::{core, imgcodecs, imgproc, prelude::*};
 
 use crate::{
-  config::RouteScale,
-  configs::RouteImageConfig,
+  analysis::{
+    detect_loops, lap_boundaries, reject_route_spikes, repair_route_distances,
+    resolve_laps, speed_glitches, steepest_climb, Loop,
+  },
+  config::{FileConfig, Font, RouteColor, RouteScale, TimeBasis, Units},
+  configs::{
+    ColorAssignment, InputFormat, LapDataConfig, LapGridConfig,
+    MultiRouteImageConfig, RouteImageConfig, ShareCardBackground,
+    ShareCardConfig,
+  },
+  provenance::embed_config,
   types::{
     drawer_data::{PositionRect, Rect, SizeRect},
-    fit_data::{LapData, RouteData},
+    fit_data::{Activity, LapData, RouteData},
+    output::{RenderOutput, RenderedBytes},
+    warning::{Warning, Warnings},
   },
   utils::{
+    chart::cadence_series,
     converter::{
-      convert_pace_to_sec, get_bounds, load_and_resize_image, pace_percentage,
-      string_space,
+      activity_gain, apply_background_fx, convert_pace_to_sec, format_date,
+      format_decimal, format_distance, format_duration, format_elevation_gain,
+      format_pace, lap_detail_texts, lap_heart_rate_texts, lap_power_texts,
+      lap_stat_texts, load_and_resize_image, load_background_layers,
+      pace_axis_ticks, pace_bar_fractions, pace_bar_labels, smooth_gps_points,
+      string_space, summary_parts,
+    },
+    creator::{image_creator, image_creator_with_format},
+    direction::arrow_placements,
+    element_drawer::{union, Drawer, LapPanelLayout, PACE_BAR_WIDTH},
+    marker::Marker,
+    palette::{hashed_color, route_contrast, style_palette},
+    parallel::map_ordered,
+    pixel_map::{pixel_map, write_pixel_map},
+    progression::dot_indices,
+    projection::Projector,
+    read_file::{
+      activity_reader, activity_reader_for, fit_reader_many, parse_warnings,
+      route_reader,
     },
-    creator::image_creator,
-    element_drawer::Drawer,
-    read_file::fit_reader,
+    segment_color::{gradient_scale, segment_colors},
+    smoothing::SmoothedLine,
+    workspace::{output_name, Workspace},
   },
 };
 
+/// Length of the dashes, and the gaps between them, of dashed glitch
+/// segments
+const GLITCH_DASH_PX: i32 = 8;
+
 /// Generates a static route image from FIT file data.
 ///
-/// Creates an image showing the complete route path overlaid on a background image.
+/// Renders `source/example.fit` over `source/example.jpg` into
+/// `outputs/route.png` with the default look and the lap panel, through
+/// the same pipeline as `image_route_with_config`.
 ///
 /// # Arguments
-/// * `route_scale` - Scale factor for route visualization (0.0-1.0 recommended)
-/// * `offset_x_percent` - Horizontal offset as percentage of image width
-/// * `offset_y_percent` - Vertical offset as percentage of image height
+/// * `route_scale` - Scale factor and offsets of the route, as fractions
+///   of the image width
 ///
 /// # Returns
 /// * `Ok(())` - Image successfully created and saved
@@ -35,203 +71,28 @@ use crate::{
 /// # Output
 /// Creates `outputs/route.png` with:
 /// - Complete route path (red line)
+/// - Lap statistics panel (pace, heart rate, stride length)
 /// - Route overlaid on background image
 pub fn route_image(route_scale: RouteScale) -> Result<()> {
-  let RouteScale {
-    scale,
-    offset_x_percent,
-    offset_y_percent,
-  } = route_scale;
-
-  #[rustfmt::skip]
-  let (route, lap) = fit_reader("source/example.fit")?;
-  let RouteData {
-    paces: _,
-    gps_points: points,
-    distances: _,
-  } = route;
-  let LapData {
-    avg_heart_rate,
-    enhanced_avg_speed,
-    avg_step_length,
-  } = lap;
-
-  // -------- Normalize coordinates --------
-  #[rustfmt::skip]
-  let (
-    (lat_min, lat_max),
-    (lon_min, lon_max)
-  ) = get_bounds(&points);
-
-  // -------- Use background image ----------
-  let (bg_image, width, height) =
-    load_and_resize_image("source/example.jpg", 1080)?;
-  let output_file = "outputs/route.png";
-
-  // --- Coordinate normalization to image space ---
-  let to_px = |lat: f64, lon: f64| -> core::Point {
-    let nx = if lon_max != lon_min {
-      (lon - lon_min) / (lon_max - lon_min)
-    } else {
-      0.5
-    };
-    let ny = if lat_max != lat_min {
-      (lat - lat_min) / (lat_max - lat_min)
-    } else {
-      0.5
-    };
-
-    let x = ((offset_x_percent + nx * scale) * width as f64) as i32;
-    #[rustfmt::skip]
-    let y = ((offset_y_percent + (1.0 - ny) * scale) * width as f64) as i32;
-    core::Point::new(x, y)
-  };
-
-  #[rustfmt::skip]
-  let pixel_points: Vec<core::Point> = points
-    .iter()
-    .map(|&(la, lo)| to_px(la, lo))
-    .collect();
-
-  // -------- Initialize image --------
-  let mut resized = Mat::default();
-  imgproc::resize(
-    &bg_image,
-    &mut resized,
-    core::Size::new(width, height),
-    0.0,
-    0.0,
-    imgproc::INTER_LANCZOS4,
-  )?;
-
-  let mut route_image = resized.clone();
-  let drawer = Drawer::new(width, height);
-
-  let font = crate::configs::video_config::Font::Simplex;
-  let font_scale = 0.5;
-  let thickness = 1;
-
-  let pace_seconds: Vec<f32> = enhanced_avg_speed
-    .iter()
-    .map(|p| convert_pace_to_sec(p))
-    .collect();
-
-  let start_x = width / 2;
-  let start_y = 100;
-  let min_val = *pace_seconds
-    .iter()
-    .min_by(|a, b| a.total_cmp(b))
-    .expect("Failed to find min pace");
-  let min_denominator = (min_val / 30.0).floor() * 30.0;
-  let bar_max_width = 200;
-
-  // Create lap data
-  drawer
-    .header(
-      &mut route_image,
-      start_x,
-      start_y,
-      font_scale,
-      2,
-      font,
-    )
-    .expect("Failed to draw header!");
-
-  let green_color = drawer.color([0.0, 255.0, 0.0, 0.0]);
-  let white_color = drawer.color([255.0, 255.0, 255.0, 0.0]);
-  let size_of_speeds = enhanced_avg_speed.len();
-  for (i, pace) in enhanced_avg_speed.iter().enumerate() {
-    let size = drawer.text_size(pace, font_scale, thickness, font)?;
-    let x = start_x - size.width / 2;
-    let y = start_y + i as i32 * (size.height + 5);
-
-    let pace_space = string_space(size_of_speeds, i + 1, pace);
-    drawer
-      .text(
-        &mut route_image,
-        &pace_space,
-        x,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw pace");
-
-    let hr = &format!("{}", avg_heart_rate[i]);
-    drawer
-      .text(
-        &mut route_image,
-        hr,
-        x + 300,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw heart rate");
-
-    let lenght_meters = avg_step_length[i] / 10.0;
-    let stride_length = &format!("{}", lenght_meters);
-    drawer
-      .text(
-        &mut route_image,
-        stride_length,
-        x + 350,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw stride length");
-
-    let percent = pace_percentage(min_denominator, pace_seconds[i]);
-    let bar_width = (percent * bar_max_width as f32) as i32;
-    let bar_height = size.height;
-    let bar_x = x + size.width + 60;
-    let bar_y = y - size.height;
-    let rect = Rect {
-      pos: PositionRect { x: bar_x, y: bar_y },
-      size: SizeRect {
-        width: bar_width,
-        height: bar_height,
-      },
-    };
-    drawer
-      .rectangle(&mut route_image, rect, green_color)
-      .expect("Failed to draw bar");
-  }
-
-  // Draw route path
-  let red_color = drawer.color([0.0, 0.0, 255.0, 0.0]);
-  let pts = core::Vector::<core::Point>::from_iter(pixel_points.clone());
-  let mut all_pts = core::Vector::<core::Vector<core::Point>>::new();
-  all_pts.push(pts);
-
-  imgproc::polylines(
-    &mut route_image,
-    &all_pts,
-    false,
-    red_color,
-    2,
-    imgproc::LINE_AA,
-    0,
-  )?;
-
-  image_creator(output_file, &route_image)?;
-
-  println!(
-    "✅ Image created: {} with {} points",
-    output_file,
-    pixel_points.len()
-  );
-
+  image_route_with_config(legacy_config(route_scale))?;
   Ok(())
 }
 
+/// Config `route_image` renders with
+fn legacy_config(route_scale: RouteScale) -> RouteImageConfig {
+  RouteImageConfig::with_lap_data(
+    route_scale,
+    RouteColor::default(),
+    FileConfig::new(
+      "source/example.fit".to_string(),
+      "source/example.jpg".to_string(),
+      "outputs/route.png".to_string(),
+    ),
+    2, // line_thickness
+    LapDataConfig::default(),
+  )
+}
+
 /// Generates a static route image from FIT file data using custom configuration.
 ///
 /// Creates an image showing the complete route path overlaid on a background image,
@@ -245,7 +106,7 @@ pub fn route_image(route_scale: RouteScale) -> Result<()> {
 ///   - `line_thickness` - Thickness of the route line
 ///
 /// # Returns
-/// * `Ok(())` - Image successfully created and saved
+/// * `Ok(RenderOutput)` - Output path and the projection used for the route
 /// * `Err` - If FIT file reading, image loading, or drawing operations fail
 ///
 /// # Output
@@ -253,85 +114,203 @@ pub fn route_image(route_scale: RouteScale) -> Result<()> {
 /// - Complete route path with custom color and thickness
 /// - Route overlaid on background image
 /// - Customizable route positioning and scale
-pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
-  // Read FIT file
-  let (route, lap) = fit_reader(&config.file_config.fit_file)?;
+pub fn image_route_with_config(
+  mut config: RouteImageConfig,
+) -> Result<RenderOutput> {
+  // Read the activity; splits of the records may stand in for missing laps
+  let Activity {
+    mut route,
+    lap,
+    session,
+    ..
+  } = activity_reader_for(&config.file_config, &[])?;
+  let repaired = repair_route_distances(config.distance_repair, &mut route);
+  let despiked = reject_route_spikes(config.spike_filter, &mut route);
+  let lap = match &config.lap_data {
+    Some(lap_config) => resolve_laps(lap_config.source, &route, lap),
+    None => lap,
+  };
   let RouteData {
     paces: _,
     gps_points: points,
-    distances: _,
-  } = route;
+    distances,
+    heart_rates: _,
+    speeds: _,
+    cadences,
+    powers: _,
+    altitudes,
+    timestamps: _,
+    segment_breaks,
+    extra: _,
+  } = &route;
   let LapData {
-    avg_heart_rate,
+    avg_heart_rate: _,
+    avg_power: _,
     enhanced_avg_speed,
-    avg_step_length,
-  } = lap;
+    avg_step_length: _,
+    total_distance: _,
+    avg_speed,
+    avg_cadence: _,
+    total_elapsed_time: _,
+  } = &lap;
+  config.limits.check_records(points.len())?;
+  // Reject a mismatched output format before drawing anything
+  config
+    .output_options
+    .resolve(&config.file_config.output_file)?;
+  if let Some(size) = config.transparent_canvas {
+    check_transparent_canvas(size, &config.file_config.output_file)?;
+  }
+  let mut warnings = parse_warnings(&route, &lap);
+  warnings.extend(repaired);
+  warnings.extend(despiked);
+  if config.show_date {
+    config.date_stamp.check_format()?;
+  }
+  let seeded_by_start = config
+    .seeded_style
+    .is_some_and(|style| style.needs_start_time());
+  let start_time = if config.show_date || seeded_by_start {
+    session.start_time
+  } else {
+    None
+  };
+  warnings.push_if(
+    config.show_date && start_time.is_none(),
+    Warning::DateStampSkipped,
+  );
 
-  // Normalize coordinates
-  let ((lat_min, lat_max), (lon_min, lon_max)) = get_bounds(&points);
+  // A seeded palette replaces the configured colors; the style is taken
+  // out, so an embedded config keeps the colors it chose
+  let palette = config.seeded_style.take().map(|style| {
+    let palette = style_palette(&style, start_time);
+    let mut layers = config.background_layers();
+    palette.apply(&mut config.colors, &mut layers);
+    config.backgrounds = layers;
+    palette
+  });
 
-  // Load background image
-  let (bg_image, width, height) = load_and_resize_image(
-    &config.file_config.background_image,
-    1080,
-  )?;
-
-  // Coordinate normalization to image space
-  let to_px = |lat: f64, lon: f64| -> core::Point {
-    let nx = if lon_max != lon_min {
-      (lon - lon_min) / (lon_max - lon_min)
-    } else {
-      0.5
-    };
-    let ny = if lat_max != lat_min {
-      (lat - lat_min) / (lat_max - lat_min)
-    } else {
-      0.5
-    };
+  // Sport-specific lap columns
+  let sport = config.sport.resolve(session.sport.as_deref());
+  let lap_stats = lap_stat_texts(&lap, sport, &config.number_format);
+  let lap_details = lap_detail_texts(&lap, sport, &config.number_format);
+  let heart_rate_texts = lap_heart_rate_texts(&lap);
+  let power_texts = lap_power_texts(&lap);
+  let elevation_line = config
+    .lap_data
+    .as_ref()
+    .filter(|lap_config| lap_config.show_elevation_gain)
+    .and_then(|_| activity_gain(altitudes, session.total_ascent))
+    .map(|gain| format_elevation_gain(gain, Units::Metric));
 
-    let x = ((config.route_scale.offset_x_percent
-      + nx * config.route_scale.scale)
-      * width as f64) as i32;
-    let y = ((config.route_scale.offset_y_percent
-      + (1.0 - ny) * config.route_scale.scale)
-      * width as f64) as i32;
-    core::Point::new(x, y)
+  // Composite the background layers, unless drawing on a transparent
+  // canvas
+  let (background, width, height) = match config.transparent_canvas {
+    Some((width, height)) => (None, width, height),
+    None => {
+      let (bg_image, width, height) = load_background_layers(
+        &config.background_layers(),
+        None,
+        1080,
+        &config.limits,
+        &config.background_scaling,
+      )?;
+      (Some(bg_image), width, height)
+    }
   };
+  config.limits.check_output_size(width, height)?;
 
-  let pixel_points: Vec<core::Point> =
-    points.iter().map(|&(la, lo)| to_px(la, lo)).collect();
+  // Coordinate normalization to image space, from smoothed positions if
+  // configured
+  let projected = match &config.gps_smoothing {
+    Some(smoothing) => {
+      let breaks: &[usize] = if config.connect_pauses {
+        &[]
+      } else {
+        segment_breaks
+      };
+      Cow::Owned(smooth_gps_points(
+        points,
+        breaks,
+        smoothing.method,
+      ))
+    }
+    None => Cow::Borrowed(points),
+  };
+  let projector = Projector::new(&projected, config.route_scale, width);
 
-  // Initialize image
-  let mut resized = Mat::default();
-  imgproc::resize(
-    &bg_image,
-    &mut resized,
-    core::Size::new(width, height),
-    0.0,
-    0.0,
-    imgproc::INTER_LANCZOS4,
-  )?;
+  let pixel_points: Vec<core::Point> = projected
+    .iter()
+    .map(|&(la, lo)| projector.project(la, lo))
+    .collect();
+  let line = SmoothedLine::new(
+    &pixel_points,
+    config.line_smoothing.as_ref(),
+  );
 
-  let mut route_image = resized.clone();
-  let drawer = Drawer::new(width, height);
+  // Initialize image; a transparent canvas is BGRA with alpha 0, and the
+  // drawer writes alpha 255 on everything drawn over it
+  let mut drawer = Drawer::new(width, height).with_safe_area(&config.safe_area);
+  let mut route_image = match background {
+    Some(bg_image) => {
+      let image = apply_background_fx(bg_image, &config.background_fx)?;
+      warnings.extend(
+        route_contrast(&config.colors, &image, &pixel_points)?.warnings(),
+      );
+      image
+    }
+    None => {
+      drawer = drawer.with_opaque_alpha();
+      transparent_canvas(width, height)?
+    }
+  };
+  let finish_marker = match &config.finish_marker {
+    Some(marker) => Some(Marker::load(
+      marker,
+      drawer.color(config.colors.current_position),
+      &drawer,
+    )?),
+    None => None,
+  };
 
-  // Draw lap data if enabled
-  if config.show_lap_data {
-    if let Some(lap_config) = &config.lap_data {
+  // Draw lap data if enabled and the activity has laps with a pace; the
+  // splits of a planned route without times (e.g. KML) have none
+  let lap_config = config.lap_data.as_ref().filter(|_| config.show_lap_data);
+  let has_paces = avg_speed.iter().any(|&speed| speed > 0.0);
+  warnings.push_if(
+    lap_config.is_some() && !has_paces,
+    Warning::LapPanelSkipped,
+  );
+  if has_paces {
+    if let Some(lap_config) = lap_config {
       let pace_seconds: Vec<f32> = enhanced_avg_speed
         .iter()
         .map(|p| convert_pace_to_sec(p))
         .collect();
 
-      let start_x = (lap_config.position.0 * width as f64) as i32;
-      let start_y = (lap_config.position.1 * height as f64) as i32;
-      let min_val = *pace_seconds
-        .iter()
-        .min_by(|a, b| a.total_cmp(b))
-        .expect("Failed to find min pace");
-      let min_denominator = (min_val / 30.0).floor() * 30.0;
+      // The whole panel is shifted inside the safe area
+      let (start_x, start_y) = drawer.lap_panel_start(
+        lap_config.position,
+        &LapPanelLayout {
+          config: lap_config,
+          sport,
+          stats: &lap_stats,
+          details: &lap_details,
+          extra_label: None,
+          summary: elevation_line.as_deref(),
+        },
+      )?;
+      let bar_fractions = pace_bar_fractions(
+        &pace_seconds,
+        lap_config.pace_bar_normalization,
+      );
+      let bar_labels = pace_bar_labels(
+        &pace_seconds,
+        lap_config.bar_style.label_format,
+      );
 
-      // Draw header
+      // Draw header; columns after the heart rate make room for power
+      let shift = lap_config.power_shift();
       drawer
         .header(
           &mut route_image,
@@ -340,16 +319,19 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
           lap_config.font_scale,
           2,
           lap_config.font,
+          &sport.lap_labels(lap_config.show_power),
+          elevation_line.as_deref(),
         )
         .expect("Failed to draw header!");
 
       let text_color = drawer.color(lap_config.text_color.to_bgra());
       let bar_color = drawer.color(config.colors.lap_bars);
-      let size_of_speeds = enhanced_avg_speed.len();
+      let size_of_speeds = lap_stats.len();
+      let mut bar_column = None;
 
-      for (i, pace) in enhanced_avg_speed.iter().enumerate() {
+      for (i, stat) in lap_stats.iter().enumerate() {
         let size = drawer.text_size(
-          pace,
+          stat,
           lap_config.font_scale,
           lap_config.thickness,
           lap_config.font,
@@ -357,12 +339,12 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
         let x = start_x - size.width / 2;
         let y = start_y + i as i32 * (size.height + 5);
 
-        // Draw pace
-        let pace_space = string_space(size_of_speeds, i + 1, pace);
+        // Draw pace (or speed)
+        let stat_space = string_space(size_of_speeds, i + 1, stat);
         drawer
           .text(
             &mut route_image,
-            &pace_space,
+            &stat_space,
             x,
             y,
             lap_config.font_scale,
@@ -374,7 +356,7 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
 
         // Draw heart rate if enabled
         if lap_config.show_heart_rate {
-          let hr = &format!("{}", avg_heart_rate[i]);
+          let hr = &heart_rate_texts[i];
           drawer
             .text(
               &mut route_image,
@@ -389,15 +371,27 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
             .expect("Failed to draw heart rate");
         }
 
-        // Draw stride length if enabled
+        // Draw power if enabled
+        if lap_config.show_power {
+          drawer.text(
+            &mut route_image,
+            &power_texts[i],
+            x + 350,
+            y,
+            lap_config.font_scale,
+            lap_config.thickness,
+            lap_config.font,
+            text_color,
+          )?;
+        }
+
+        // Draw stride length (or cadence) if enabled
         if lap_config.show_stride_length {
-          let length_meters = avg_step_length[i] / 10.0;
-          let stride_length = &format!("{}", length_meters);
           drawer
             .text(
               &mut route_image,
-              stride_length,
-              x + 350,
+              &lap_details[i],
+              x + 350 + shift,
               y,
               lap_config.font_scale,
               lap_config.thickness,
@@ -409,11 +403,14 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
 
         // Draw pace bars if enabled
         if lap_config.show_pace_bars {
-          let percent = pace_percentage(min_denominator, pace_seconds[i]);
-          let bar_width = (percent * 200.0) as i32;
+          let bar_width = (bar_fractions[i] * PACE_BAR_WIDTH as f32) as i32;
           let bar_height = size.height;
           let bar_x = x + size.width + 60;
           let bar_y = y - size.height;
+          bar_column = Some(union(
+            bar_column,
+            core::Rect::new(bar_x, bar_y, PACE_BAR_WIDTH, bar_height),
+          ));
           let rect = Rect {
             pos: PositionRect { x: bar_x, y: bar_y },
             size: SizeRect {
@@ -422,40 +419,1287 @@ pub fn image_route_with_config(config: RouteImageConfig) -> Result<()> {
             },
           };
           drawer
-            .rectangle(&mut route_image, rect, bar_color)
+            .pace_bar(
+              &mut route_image,
+              rect,
+              bar_color,
+              text_color,
+              lap_config,
+              bar_labels[i].as_deref(),
+            )
             .expect("Failed to draw bar");
         }
       }
+
+      if let (Some(axis), Some(column)) = (lap_config.bar_axis, bar_column) {
+        let ticks = pace_axis_ticks(
+          &pace_seconds,
+          lap_config.pace_bar_normalization,
+          axis.tick_interval_s,
+        );
+        drawer.pace_axis(
+          &mut route_image,
+          column,
+          &ticks,
+          &axis,
+          lap_config,
+        )?;
+      }
     }
   }
 
+  // The line breaks at timer pauses unless they are connected
+  let breaks: &[usize] = if config.connect_pauses {
+    &[]
+  } else {
+    segment_breaks
+  };
+  let stretches = line.stretches(breaks);
+
+  // Draw the shadow beneath the route line
+  let draws_line = config.render_style.draws_line();
+  if let (true, Some(shadow)) = (draws_line, &config.route_shadow) {
+    drawer.shadow(
+      &mut route_image,
+      stretches
+        .iter()
+        .flat_map(|points| points.windows(2))
+        .map(|pair| (pair[0], pair[1])),
+      config.line_thickness,
+      shadow,
+    )?;
+  }
+
+  // Segments too fast to be real, flagged whether or not a line is drawn
+  let glitches = config.glitch_style.map(|style| {
+    let glitches = speed_glitches(&route, style.max_speed_mps);
+    let count = glitches.iter().filter(|&&glitch| glitch).count();
+    warnings.push_if(
+      count > 0,
+      Warning::GlitchSegments {
+        count,
+        max_speed_mps: style.max_speed_mps,
+      },
+    );
+    (style, glitches)
+  });
+
   // Draw route path with configured color
   let route_color = drawer.color(config.colors.route_line);
-  let pts = core::Vector::<core::Point>::from_iter(pixel_points.clone());
+  let colors = segment_colors(config.segment_coloring, altitudes);
+  if draws_line {
+    if colors.is_some() || glitches.is_some() {
+      // Runs of glitch pieces, drawn after the rest so dashes run on
+      let mut glitch_runs: Vec<Vec<core::Point>> = Vec::new();
+      let mut in_run = false;
+      let end = line.points.len().saturating_sub(1) as f64;
+      for piece in line.pieces(0.0, end) {
+        if breaks.contains(&piece.record) {
+          in_run = false;
+          continue;
+        }
+        let glitch = glitches.as_ref().is_some_and(|(_, glitches)| {
+          glitches.get(piece.record) == Some(&true)
+        });
+        if glitch {
+          match glitch_runs.last_mut() {
+            Some(run) if in_run => run.push(piece.to),
+            _ => glitch_runs.push(vec![piece.from, piece.to]),
+          }
+          in_run = true;
+          continue;
+        }
+        in_run = false;
+
+        let color = match &colors {
+          Some(colors) => {
+            let Some(color) = colors.get(piece.record).or(colors.last()) else {
+              break;
+            };
+            drawer.color(*color)
+          }
+          None => route_color,
+        };
+        imgproc::line(
+          &mut route_image,
+          piece.from,
+          piece.to,
+          color,
+          config.line_thickness,
+          imgproc::LINE_AA,
+          0,
+        )?;
+      }
+
+      if let Some((style, _)) = &glitches {
+        let color = drawer.color(style.color);
+        for run in &glitch_runs {
+          if style.dashed {
+            drawer.dashed_polyline(
+              &mut route_image,
+              run,
+              color,
+              config.line_thickness,
+              GLITCH_DASH_PX,
+            )?;
+          } else {
+            let mut runs = core::Vector::<core::Vector<core::Point>>::new();
+            runs.push(core::Vector::from_iter(
+              run.iter().copied(),
+            ));
+            imgproc::polylines(
+              &mut route_image,
+              &runs,
+              false,
+              color,
+              config.line_thickness,
+              imgproc::LINE_AA,
+              0,
+            )?;
+          }
+        }
+      }
+    } else {
+      let mut all_pts = core::Vector::<core::Vector<core::Point>>::new();
+      for points in &stretches {
+        all_pts.push(core::Vector::from_iter(
+          points.iter().copied(),
+        ));
+      }
+
+      imgproc::polylines(
+        &mut route_image,
+        &all_pts,
+        false,
+        route_color,
+        config.line_thickness,
+        imgproc::LINE_AA,
+        0,
+      )?;
+    }
+  }
+
+  // Draw dots over the line; the last point takes the last segment's color
+  if let Some((radius, spacing_m)) = config.render_style.dots() {
+    for index in dot_indices(pixel_points.len(), distances, spacing_m) {
+      let color = colors
+        .as_ref()
+        .and_then(|colors| colors.get(index).or(colors.last()))
+        .map_or(route_color, |color| {
+          drawer.color(*color)
+        });
+      drawer.dot(
+        &mut route_image,
+        line.points[line.record_points[index]],
+        radius,
+        color,
+      )?;
+    }
+  }
+
+  // Draw the steepest climb over the route in its own color
+  if config.highlight_steepest_climb {
+    let highlight = &config.steepest_climb;
+    let climb = steepest_climb(&route, highlight.window_m);
+    warnings.push_if(
+      climb.is_none(),
+      Warning::SteepestClimbSkipped,
+    );
+    if let Some(climb) = climb {
+      let color = drawer.color(highlight.color.to_bgra());
+      let from = line.position(climb.start_idx, 0.0);
+      let to = line.position(climb.end_idx, 0.0);
+      let pieces = line.pieces(from, to);
+      for piece in pieces.filter(|piece| !breaks.contains(&piece.record)) {
+        imgproc::line(
+          &mut route_image,
+          piece.from,
+          piece.to,
+          color,
+          config.line_thickness,
+          imgproc::LINE_AA,
+          0,
+        )?;
+      }
+      if highlight.show_label {
+        let middle = (climb.start_idx + climb.end_idx) / 2;
+        drawer.climb_label(
+          &mut route_image,
+          line.points[line.record_points[middle]],
+          climb.grade,
+        )?;
+      }
+    }
+  }
+
+  // Draw direction arrows over the route
+  if let Some(arrows) = &config.direction_arrows {
+    let points: Vec<(f64, f64)> = pixel_points
+      .iter()
+      .map(|point| (point.x as f64, point.y as f64))
+      .collect();
+    let placements = arrow_placements(
+      &points,
+      distances,
+      arrows.every_m,
+      arrows.size_px as f64,
+    );
+    warnings.push_if(
+      distances.len() < points.len(),
+      Warning::DirectionArrowsSkipped,
+    );
+    let color = drawer.color(arrows.color.to_bgra());
+    for placement in &placements {
+      drawer.arrow(
+        &mut route_image,
+        placement,
+        arrows.size_px,
+        color,
+      )?;
+    }
+  }
+
+  // Mark the finish, as the video's last frame would
+  if let (Some(marker), Some(&finish)) = (&finish_marker, line.points.last()) {
+    marker.draw(&drawer, &mut route_image, finish, 0)?;
+  }
+
+  // Draw the cadence chart over the route
+  if let Some(chart) = &config.cadence_chart {
+    let series = cadence_series(distances, cadences);
+    let axes = drawer.cadence_chart(&mut route_image, chart, &series)?;
+    warnings.push_if(
+      axes.is_none(),
+      Warning::CadenceChartSkipped,
+    );
+  }
+
+  // Draw the legend over the route
+  if let Some(legend) = &config.legend {
+    match gradient_scale(config.segment_coloring, altitudes) {
+      Some(scale) => drawer.legend(&mut route_image, legend, &scale)?,
+      None => warnings.push(Warning::LegendSkipped),
+    }
+  }
+
+  // List the loops the GPS track runs, independent of the watch's laps
+  if config.show_detected_loops {
+    let detection = &config.loop_detection;
+    let loops = detect_loops(
+      &route,
+      detection.min_loop_m,
+      detection.proximity_m,
+    );
+    warnings.push_if(
+      loops.is_empty(),
+      Warning::DetectedLoopsSkipped,
+    );
+    drawer.corner_lines(
+      &mut route_image,
+      &loop_texts(&loops),
+      detection.corner,
+      detection.font_scale,
+      1,
+      Font::Simplex,
+      drawer.color(detection.color.to_bgra()),
+    )?;
+  }
+
+  // The session totals go beneath the date stamp
+  if config.show_summary {
+    let stamp = &config.date_stamp;
+    drawer.summary_bar(
+      &mut route_image,
+      &summary_parts(&session, &config.number_format),
+      stamp.font_scale,
+      stamp.thickness,
+      stamp.font,
+    )?;
+  }
+
+  if let (true, Some(start_time)) = (config.show_date, start_time) {
+    drawer.date_stamp(
+      &mut route_image,
+      &config.date_stamp,
+      start_time,
+    )?;
+  }
+
+  // The map credit goes over everything else
+  drawer.attribution(&mut route_image, &config.attribution)?;
+
+  if config.debug_draw_safe_area {
+    drawer.safe_area_guides(&mut route_image)?;
+  }
+
+  // Save image
+  image_creator_with_format(
+    &config.file_config.output_file,
+    &route_image,
+    &config.output_options,
+    config.deterministic,
+  )?;
+  if config.embed_config {
+    embed_config(&config.file_config.output_file, &config)?;
+  }
+
+  println!(
+    "✅ Image created: {} with {} points",
+    config.file_config.output_file,
+    pixel_points.len()
+  );
+  for warning in &warnings {
+    println!("⚠️  {}", warning);
+  }
+
+  // Export hover data from the drawn points
+  if let Some(path) = &config.pixel_map_export {
+    let entries = pixel_map(
+      &pixel_points,
+      &route,
+      config.pixel_map_stride,
+      width,
+      height,
+    );
+    write_pixel_map(path, &entries)?;
+    println!(
+      "✅ Pixel map created: {} with {} points",
+      path.display(),
+      entries.len()
+    );
+  }
+
+  let output = RenderOutput {
+    output_file: config.file_config.output_file.clone(),
+    projection: projector.projection_info(),
+    warnings,
+    time_index: Vec::new(),
+    palette,
+  };
+  if let Some(sink) = &config.on_complete {
+    sink.run(&output)?;
+  }
+  Ok(output)
+}
+
+/// Renders a route image from in-memory FIT and background files and
+/// returns the encoded image.
+///
+/// The files live in a `Workspace` that is removed before returning, even
+/// on panic; `config.file_config` only supplies the output extension.
+pub fn image_route_from_bytes(
+  fit: &[u8],
+  background: &[u8],
+  mut config: RouteImageConfig,
+) -> Result<RenderedBytes> {
+  let workspace = Workspace::new("runarium_img")?;
+  let output_name = output_name(&config.file_config.output_file, "png");
+  let input_format = config.file_config.input_format;
+  config.file_config = workspace.file_config(fit, background, &output_name)?;
+  config.file_config.input_format = input_format;
+
+  let output = image_route_with_config(config)?;
+  let bytes = fs::read(&output.output_file)?;
+  Ok(RenderedBytes { bytes, output })
+}
+
+/// Route of one activity drawn on its own transparent layer
+struct RouteLayer {
+  image: Mat,
+  mask: Mat,
+}
+
+/// Generates a static image with several activities drawn on one map.
+///
+/// All routes share one set of bounds so they line up geographically. Each
+/// activity is drawn onto its own layer (in parallel with the `rayon`
+/// feature) and the layers are composited in `routes` order, so the
+/// result does not depend on thread scheduling.
+///
+/// # Arguments
+/// * `config` - Multi-route image configuration
+///
+/// # Returns
+/// * `Ok(RenderOutput)` - Output path and the projection used for the route
+/// * `Err` - If any FIT file, the background image, or drawing fails
+///
+/// # Example
+/// ```no_run
+/// use runarium::configs::image_config::MultiRouteImageConfig;
+/// use runarium::generators::route_image::multi_route_image;
+///
+/// let config = MultiRouteImageConfig::new(
+///   vec!["source/a.fit".to_string(), "source/b.fit".to_string()],
+///   "source/example.jpg".to_string(),
+///   "outputs/multi.png".to_string(),
+/// );
+/// multi_route_image(config).unwrap();
+/// ```
+pub fn multi_route_image(
+  config: MultiRouteImageConfig,
+) -> Result<RenderOutput> {
+  // Read FIT files
+  let fit_files: Vec<String> = config
+    .routes
+    .iter()
+    .map(|entry| entry.fit_file.clone())
+    .collect();
+  let activities = fit_reader_many(&fit_files)?;
+  let colors: Vec<[f64; 4]> = activities
+    .iter()
+    .enumerate()
+    .map(|(i, (route, _))| entry_color(&config, i, route))
+    .collect();
+  let all_points: Vec<(f64, f64)> = activities
+    .iter()
+    .flat_map(|(route, _)| route.gps_points.iter().copied())
+    .collect();
+
+  // Load background image
+  let (bg_image, width, height) =
+    load_and_resize_image(&config.background_image, 1080)?;
+
+  // Coordinate normalization to image space, over every activity
+  let projector = Projector::new(&all_points, config.route_scale, width);
+
+  // Draw each activity onto its own layer
+  let indexed: Vec<(usize, &RouteData)> = activities
+    .iter()
+    .map(|(route, _)| route)
+    .enumerate()
+    .collect();
+  let layers = map_ordered(&indexed, |&(i, route)| {
+    let pixel_points: Vec<core::Point> = route
+      .gps_points
+      .iter()
+      .map(|&(la, lo)| projector.project(la, lo))
+      .collect();
+    route_layer(
+      &pixel_points,
+      width,
+      height,
+      colors[i],
+      config.line_thickness,
+    )
+  })
+  .into_iter()
+  .collect::<Result<Vec<_>>>()?;
+
+  // Initialize image
+  let mut route_image = Mat::default();
+  imgproc::resize(
+    &bg_image,
+    &mut route_image,
+    core::Size::new(width, height),
+    0.0,
+    0.0,
+    imgproc::INTER_LANCZOS4,
+  )?;
+
+  // Composite layers in order
+  let opacity = config.opacity.clamp(0.0, 1.0);
+  let mut blended = Mat::default();
+  for layer in &layers {
+    core::add_weighted_def(
+      &route_image,
+      1.0 - opacity,
+      &layer.image,
+      opacity,
+      0.0,
+      &mut blended,
+    )?;
+    blended.copy_to_masked(&mut route_image, &layer.mask)?;
+  }
+
+  // Legend of the colors actually drawn
+  if let Some(position) = config.legend_position {
+    let drawer = Drawer::new(width, height);
+    let entries: Vec<(String, core::Scalar)> = config
+      .routes
+      .iter()
+      .zip(&colors)
+      .map(|(entry, &color)| {
+        (
+          file_stem(&entry.fit_file),
+          drawer.color(color),
+        )
+      })
+      .collect();
+    drawer.swatch_legend(&mut route_image, position, &entries)?;
+  }
+
+  // Save image
+  image_creator(&config.output_file, &route_image)?;
+
+  println!(
+    "✅ Image created: {} with {} activities",
+    config.output_file,
+    layers.len()
+  );
+
+  let mut warnings = Warnings::default();
+  for (route, lap) in &activities {
+    warnings.extend(parse_warnings(route, lap));
+  }
+  for warning in &warnings {
+    println!("⚠️  {}", warning);
+  }
+
+  Ok(RenderOutput {
+    output_file: config.output_file.clone(),
+    projection: projector.projection_info(),
+    warnings,
+    time_index: Vec::new(),
+    palette: None,
+  })
+}
+
+/// Color of the activity at `index`: its own assignment, or the next
+/// palette color
+fn entry_color(
+  config: &MultiRouteImageConfig,
+  index: usize,
+  route: &RouteData,
+) -> [f64; 4] {
+  match config.routes[index].color {
+    Some(ColorAssignment::Fixed(color)) => color.to_bgra(),
+    Some(ColorAssignment::Auto) => {
+      // The file name (not its directory) and start time identify the
+      // activity wherever it is stored
+      let start_time = route.timestamps.iter().flatten().next();
+      let key = format!(
+        "{}|{}",
+        file_name(&config.routes[index].fit_file),
+        start_time.map_or(String::new(), |t| t.to_string())
+      );
+      hashed_color(&key)
+    }
+    None => config.route_color(index),
+  }
+}
+
+fn file_name(path: &str) -> String {
+  Path::new(path)
+    .file_name()
+    .map_or(path.to_string(), |name| {
+      name.to_string_lossy().into_owned()
+    })
+}
+
+fn file_stem(path: &str) -> String {
+  Path::new(path)
+    .file_stem()
+    .map_or(path.to_string(), |stem| {
+      stem.to_string_lossy().into_owned()
+    })
+}
+
+/// Draws one route onto a black layer with a matching mask
+fn route_layer(
+  pixel_points: &[core::Point],
+  width: i32,
+  height: i32,
+  color: [f64; 4],
+  thickness: i32,
+) -> Result<RouteLayer> {
+  let mut image = Mat::zeros(height, width, core::CV_8UC3)?.to_mat()?;
+  let mut mask = Mat::zeros(height, width, core::CV_8UC1)?.to_mat()?;
+
+  let pts = core::Vector::<core::Point>::from_iter(pixel_points.to_vec());
   let mut all_pts = core::Vector::<core::Vector<core::Point>>::new();
   all_pts.push(pts);
 
+  let [b, g, r, a] = color;
+  for (target, color) in [
+    (
+      &mut image,
+      core::Scalar::new(b, g, r, a),
+    ),
+    (&mut mask, core::Scalar::all(255.0)),
+  ] {
+    imgproc::polylines(
+      target,
+      &all_pts,
+      false,
+      color,
+      thickness,
+      imgproc::LINE_AA,
+      0,
+    )?;
+  }
+
+  Ok(RouteLayer { image, mask })
+}
+
+/// Generates a 1080x1080 activity share card.
+///
+/// Fixed layout: distance title and date at the top, the route centered in
+/// the accent color, and three stat chips (time, pace, elevation) at the
+/// bottom. Totals come from the FIT Session message, falling back to the
+/// record stream when it is missing. The time is moving or elapsed time
+/// per `time_basis`, and the pace follows it.
+///
+/// # Arguments
+/// * `config` - Share card configuration
+///
+/// # Returns
+/// * `Ok(())` - Card successfully created and saved
+/// * `Err` - If FIT file reading, image loading, or drawing fails
+///
+/// # Example
+/// ```no_run
+/// use runarium::configs::image_config::ShareCardConfig;
+/// use runarium::generators::route_image::share_card;
+///
+/// let config = ShareCardConfig::new(
+///   "source/example.fit".to_string(),
+///   "outputs/share.png".to_string(),
+/// );
+/// share_card(config).unwrap();
+/// ```
+pub fn share_card(config: ShareCardConfig) -> Result<()> {
+  const SIZE: i32 = 1080;
+  const MARGIN: i32 = 80;
+
+  // Read FIT file
+  let Activity { route, session, .. } = activity_reader(&config.fit_file, &[])?;
+  let units = config.units;
+
+  let distance = session
+    .total_distance
+    .or_else(|| route.distances.last().copied())
+    .unwrap_or(0.0);
+  let time = session
+    .times
+    .map(|times| match config.time_basis {
+      TimeBasis::Moving => times.moving_time,
+      TimeBasis::Elapsed => times.elapsed_time,
+    })
+    .or(session.total_timer_time);
+
+  // Background
+  let mut card = match &config.background {
+    ShareCardBackground::Solid(color) => Mat::new_rows_cols_with_default(
+      SIZE,
+      SIZE,
+      core::CV_8UC3,
+      core::Scalar::new(color[0], color[1], color[2], color[3]),
+    )?,
+    ShareCardBackground::Blurred(path) => blurred_background(path, SIZE)?,
+  };
+
+  // Route, centered using the regular projection
+  let projector = Projector::new(
+    &route.gps_points,
+    RouteScale::new(0.5, 0.25, 0.22),
+    SIZE,
+  );
+
+  let pixel_points: Vec<core::Point> = route
+    .gps_points
+    .iter()
+    .map(|&(la, lo)| projector.project(la, lo))
+    .collect();
+
+  let drawer = Drawer::new(SIZE, SIZE);
+  let accent = drawer.color(config.accent_color);
+  let white = drawer.color([255.0, 255.0, 255.0, 0.0]);
+  let muted = drawer.color([180.0, 180.0, 180.0, 0.0]);
+  let chip_color = drawer.color([20.0, 20.0, 20.0, 0.0]);
+
+  let pts = core::Vector::<core::Point>::from_iter(pixel_points.clone());
+  let mut all_pts = core::Vector::<core::Vector<core::Point>>::new();
+  all_pts.push(pts);
   imgproc::polylines(
-    &mut route_image,
+    &mut card,
     &all_pts,
     false,
-    route_color,
-    config.line_thickness,
+    accent,
+    6,
     imgproc::LINE_AA,
     0,
   )?;
+  if let (Some(start), Some(end)) = (
+    pixel_points.first(),
+    pixel_points.last(),
+  ) {
+    drawer.point(&mut card, *start, white)?;
+    drawer.point(&mut card, *end, accent)?;
+  }
+
+  // Title and date
+  let title = config.title_override.clone().unwrap_or_else(|| {
+    format!(
+      "{} {}",
+      format_distance(distance, units, &config.number_format),
+      units.distance_label()
+    )
+  });
+  drawer.text(
+    &mut card,
+    &title,
+    MARGIN,
+    150,
+    2.4,
+    5,
+    Font::Duplex,
+    white,
+  )?;
+  if let Some(start_time) = session.start_time {
+    drawer.text(
+      &mut card,
+      &format_date(start_time),
+      MARGIN,
+      205,
+      0.9,
+      2,
+      Font::Simplex,
+      muted,
+    )?;
+  }
+
+  // Stat chips
+  let pace = match time {
+    Some(time) if distance > 0.0 => format!(
+      "{}{}",
+      format_pace(
+        time / units.distance(distance),
+        config.number_format.pace_rounding
+      ),
+      units.pace_label()
+    ),
+    _ => "--".to_string(),
+  };
+  let elevation = session.total_ascent.map_or("--".to_string(), |ascent| {
+    format!(
+      "{} {}",
+      format_decimal(units.elevation(ascent as f64), 0),
+      units.elevation_label()
+    )
+  });
+  let chips = [
+    (
+      config.time_basis.label(),
+      time.map_or("--".to_string(), format_duration),
+    ),
+    ("PACE", pace),
+    ("ELEVATION", elevation),
+  ];
+
+  let gap = 30;
+  let chip_width = (SIZE - 2 * MARGIN - 2 * gap) / 3;
+  let chip_height = 130;
+  let chip_y = SIZE - MARGIN - chip_height;
+  for (i, (label, value)) in chips.iter().enumerate() {
+    let chip_x = MARGIN + i as i32 * (chip_width + gap);
+    let rect = Rect {
+      pos: PositionRect {
+        x: chip_x,
+        y: chip_y,
+      },
+      size: SizeRect {
+        width: chip_width,
+        height: chip_height,
+      },
+    };
+    drawer.rectangle(&mut card, rect, chip_color)?;
+    drawer.text(
+      &mut card,
+      label,
+      chip_x + 24,
+      chip_y + 45,
+      0.7,
+      2,
+      Font::Simplex,
+      accent,
+    )?;
+    drawer.text(
+      &mut card,
+      value,
+      chip_x + 24,
+      chip_y + 100,
+      1.2,
+      2,
+      Font::Duplex,
+      white,
+    )?;
+  }
 
   // Save image
-  image_creator(
-    &config.file_config.output_file,
-    &route_image,
+  image_creator(&config.output_file, &card)?;
+
+  println!(
+    "✅ Share card created: {}",
+    config.output_file
+  );
+
+  Ok(())
+}
+
+/// Loads an image as a blurred, darkened square of `size` pixels
+fn blurred_background(path: &str, size: i32) -> Result<Mat> {
+  let image = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
+  let image_size = image.size()?;
+  let side = image_size.width.min(image_size.height);
+  let crop = core::Rect::new(
+    (image_size.width - side) / 2,
+    (image_size.height - side) / 2,
+    side,
+    side,
+  );
+  let square = Mat::roi(&image, crop)?;
+
+  let mut resized = Mat::default();
+  imgproc::resize(
+    &square,
+    &mut resized,
+    core::Size::new(size, size),
+    0.0,
+    0.0,
+    imgproc::INTER_AREA,
   )?;
 
+  let mut blurred = Mat::default();
+  imgproc::gaussian_blur_def(
+    &resized,
+    &mut blurred,
+    core::Size::new(0, 0),
+    25.0,
+  )?;
+
+  let mut darkened = Mat::default();
+  blurred.convert_to(&mut darkened, -1, 0.6, 0.0)?;
+  Ok(darkened)
+}
+
+/// Generates a grid of route thumbnails, one tile per lap.
+///
+/// Records are split into laps with `lap_boundaries` and each lap is
+/// projected on its own, so every tile fills its route area. The lap
+/// number and average pace are written under each tile. A single-lap
+/// activity gives a single tile; one without lap data gets a tile per
+/// kilometer, without a pace.
+///
+/// # Arguments
+/// * `config` - Lap grid configuration
+///
+/// # Returns
+/// * `Ok(())` - Grid successfully created and saved
+/// * `Err` - If FIT file reading or drawing fails
+///
+/// # Example
+/// ```no_run
+/// use runarium::configs::image_config::LapGridConfig;
+/// use runarium::generators::route_image::lap_grid_image;
+///
+/// let mut config = LapGridConfig::new(
+///   "source/example.fit".to_string(),
+///   "outputs/laps.png".to_string(),
+/// );
+/// config.columns = 4;
+/// lap_grid_image(config).unwrap();
+/// ```
+pub fn lap_grid_image(config: LapGridConfig) -> Result<()> {
+  let (tile_width, tile_height) = config.tile_size;
+  if tile_width <= 0 || tile_height <= 0 {
+    bail!(
+      "Lap grid tile size {}x{} must be positive",
+      tile_width,
+      tile_height
+    );
+  }
+
+  // Read the activity and split the records by lap
+  let (route, lap) = route_reader(&config.fit_file, InputFormat::Auto)?;
+  let points = &route.gps_points;
+  let boundaries = lap_boundaries(&route, &lap);
+  let tiles = boundaries.len();
+
+  // Background
+  let (width, height) = config.grid_size(tiles);
+  let drawer = Drawer::new(width, height);
+  let [b, g, r, a] = config.background_color;
+  let mut grid = Mat::new_rows_cols_with_default(
+    height,
+    width,
+    core::CV_8UC3,
+    core::Scalar::new(b, g, r, a),
+  )?;
+
+  let border = config.border_thickness.max(0);
+  let border_color = drawer.color(config.border_color);
+  let tile_color = drawer.color(config.tile_color);
+  let route_color = drawer.color(config.route_color);
+  let text_color = drawer.color(config.text_color);
+
+  for (i, boundary) in boundaries.iter().enumerate() {
+    let (x, y) = config.tile_origin(i, tiles);
+
+    // Tile and its border
+    if border > 0 {
+      let rect = Rect {
+        pos: PositionRect {
+          x: x - border,
+          y: y - border,
+        },
+        size: SizeRect {
+          width: tile_width + 2 * border,
+          height: tile_height + 2 * border,
+        },
+      };
+      drawer.rectangle(&mut grid, rect, border_color)?;
+    }
+    let rect = Rect {
+      pos: PositionRect { x, y },
+      size: SizeRect {
+        width: tile_width,
+        height: tile_height,
+      },
+    };
+    drawer.rectangle(&mut grid, rect, tile_color)?;
+
+    // Lap route, fitted to the tile
+    let lap_points = &points[boundary.joined_records()];
+    let projector = tile_projector(lap_points, &config);
+    let pixel_points: Vec<core::Point> = lap_points
+      .iter()
+      .map(|&(la, lo)| projector.project(la, lo) + core::Point::new(x, y))
+      .collect();
+    let pts = core::Vector::<core::Point>::from_iter(pixel_points);
+    let mut all_pts = core::Vector::<core::Vector<core::Point>>::new();
+    all_pts.push(pts);
+    imgproc::polylines(
+      &mut grid,
+      &all_pts,
+      false,
+      route_color,
+      config.line_thickness,
+      imgproc::LINE_AA,
+      0,
+    )?;
+
+    // Lap number and pace, centered under the tile
+    let label = format!(
+      "Lap {}  {}",
+      i + 1,
+      lap_pace(&lap, i, &config)
+    );
+    let size = drawer.text_size(
+      &label,
+      config.font_scale,
+      1,
+      config.font,
+    )?;
+    drawer.text(
+      &mut grid,
+      &label,
+      x + (tile_width - size.width) / 2,
+      y + tile_height + border + (config.label_height + size.height) / 2,
+      config.font_scale,
+      1,
+      config.font,
+      text_color,
+    )?;
+  }
+
+  // Save image
+  image_creator(&config.output_file, &grid)?;
+
   println!(
-    "✅ Image created: {} with {} points",
-    config.file_config.output_file,
-    pixel_points.len()
+    "✅ Lap grid created: {} with {} tiles",
+    config.output_file, tiles
   );
+  for warning in &parse_warnings(&route, &lap) {
+    println!("⚠️  {}", warning);
+  }
+
+  Ok(())
+}
+
+/// Projector that fits `points` into a tile's route area, centered and
+/// inset by the configured padding
+fn tile_projector(points: &[(f64, f64)], config: &LapGridConfig) -> Projector {
+  let (width, height) = config.tile_size;
+  let base = width.min(height);
+  let scale = 1.0 - 2.0 * config.padding.clamp(0.0, 0.5);
+  let side = base as f64 * scale;
+  Projector::new(
+    points,
+    RouteScale::new(
+      scale,
+      (width as f64 - side) / 2.0 / base as f64,
+      (height as f64 - side) / 2.0 / base as f64,
+    ),
+    base,
+  )
+}
+
+/// Average pace of lap `index` in the configured units, or `--` without
+/// lap data
+fn lap_pace(lap: &LapData, index: usize, config: &LapGridConfig) -> String {
+  let units = config.units;
+  match lap.avg_speed.get(index) {
+    Some(&speed) if speed > 0.0 => format!(
+      "{}{}",
+      format_pace(
+        1.0 / units.distance(speed),
+        config.number_format.pace_rounding
+      ),
+      units.pace_label()
+    ),
+    _ => "--".to_string(),
+  }
+}
+
+/// "Loop 2  1:35 (+0:03)  400 m" for each loop: its time, the change
+/// from the loop before and its distance
+fn loop_texts(loops: &[Loop]) -> Vec<String> {
+  loops
+    .iter()
+    .enumerate()
+    .map(|(i, found)| {
+      let time = found
+        .duration_s
+        .map_or_else(|| "--".to_string(), format_duration);
+      let previous = i.checked_sub(1).and_then(|p| loops[p].duration_s);
+      let change = match (previous, found.duration_s) {
+        (Some(previous), Some(current)) => {
+          let sign = if current < previous { '-' } else { '+' };
+          format!(
+            " ({}{})",
+            sign,
+            format_duration((current - previous).abs())
+          )
+        }
+        _ => String::new(),
+      };
+      format!(
+        "Loop {}  {}{}  {:.0} m",
+        i + 1,
+        time,
+        change,
+        found.distance_m
+      )
+    })
+    .collect()
+}
 
+/// Fails unless a transparent canvas has a positive size and is written
+/// as a PNG, so the alpha is kept
+fn check_transparent_canvas(
+  (width, height): (i32, i32),
+  output_file: &str,
+) -> Result<()> {
+  if width <= 0 || height <= 0 {
+    bail!(
+      "Transparent canvas size {}x{} must be positive",
+      width,
+      height
+    );
+  }
+  let is_png = Path::new(output_file)
+    .extension()
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+  if !is_png {
+    bail!(
+      "Output file {} must be a .png to keep the transparent canvas",
+      output_file
+    );
+  }
   Ok(())
 }
+
+/// BGRA canvas of `width` x `height` with every pixel fully transparent
+fn transparent_canvas(width: i32, height: i32) -> Result<Mat> {
+  Ok(Mat::new_rows_cols_with_default(
+    height,
+    width,
+    core::CV_8UC4,
+    core::Scalar::all(0.0),
+  )?)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{env, path::Path};
+
+  use super::*;
+  use crate::{
+    configs::RouteColor,
+    utils::{fingerprint::content_hash, read_file::fit_reader},
+  };
+
+  #[test]
+  fn test_deterministic_image_is_byte_identical() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("source");
+    let (fit, background) = (
+      source.join("example.fit"),
+      source.join("example.jpg"),
+    );
+    if !fit.exists() || !background.exists() {
+      return;
+    }
+
+    let render = |name: &str| {
+      let output = env::temp_dir().join(name);
+      let mut config = RouteImageConfig::default(
+        fit.to_string_lossy().into_owned(),
+        background.to_string_lossy().into_owned(),
+        output.to_string_lossy().into_owned(),
+      );
+      config.deterministic = true;
+      image_route_with_config(config).unwrap();
+      content_hash(&fs::read(output).unwrap())
+    };
+
+    assert_eq!(
+      render("runarium_deterministic_a.png"),
+      render("runarium_deterministic_b.png")
+    );
+  }
+
+  #[test]
+  fn test_transparent_canvas_alpha() {
+    let mut canvas = transparent_canvas(200, 100).unwrap();
+    let drawer = Drawer::new(200, 100).with_opaque_alpha();
+    let route = core::Vector::<core::Vector<core::Point>>::from_iter([
+      core::Vector::from_iter([
+        core::Point::new(20, 50),
+        core::Point::new(180, 50),
+      ]),
+    ]);
+    // The default route color has alpha 0, as on BGR backgrounds
+    let color = drawer.color(RouteColor::default().route_line);
+    imgproc::polylines(
+      &mut canvas,
+      &route,
+      false,
+      color,
+      4,
+      imgproc::LINE_AA,
+      0,
+    )
+    .unwrap();
+
+    let alpha =
+      |x, y| canvas.at_2d::<core::Vec4b>(y, x).map(|px| px[3]).unwrap();
+    for (x, y) in [(0, 0), (199, 0), (0, 99), (199, 99)] {
+      assert_eq!(alpha(x, y), 0);
+    }
+    assert_eq!(alpha(100, 50), 255);
+  }
+
+  #[test]
+  fn test_loop_texts() {
+    let found = |duration_s| Loop {
+      start_idx: 0,
+      end_idx: 80,
+      duration_s,
+      distance_m: 400.2,
+    };
+    assert_eq!(
+      loop_texts(&[
+        found(Some(92.0)),
+        found(Some(95.0)),
+        found(Some(89.0)),
+        found(None),
+      ]),
+      vec![
+        "Loop 1  1:32  400 m",
+        "Loop 2  1:35 (+0:03)  400 m",
+        "Loop 3  1:29 (-0:06)  400 m",
+        "Loop 4  --  400 m",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_transparent_canvas_needs_png() {
+    assert!(check_transparent_canvas((800, 600), "route.png").is_ok());
+    assert!(check_transparent_canvas((800, 600), "route.PNG").is_ok());
+    assert!(check_transparent_canvas((800, 600), "route.jpg").is_err());
+    assert!(check_transparent_canvas((0, 600), "route.png").is_err());
+  }
+
+  #[test]
+  fn test_transparent_image_keeps_alpha() {
+    let fit = Path::new(env!("CARGO_MANIFEST_DIR")).join("source/example.fit");
+    if !fit.exists() {
+      return;
+    }
+
+    let output = env::temp_dir().join("runarium_transparent.png");
+    let mut config = RouteImageConfig::default(
+      fit.to_string_lossy().into_owned(),
+      "missing.jpg".into(),
+      output.to_string_lossy().into_owned(),
+    );
+    config.transparent_canvas = Some((800, 600));
+    config.line_thickness = 6;
+    image_route_with_config(config.clone()).unwrap();
+
+    let image = imgcodecs::imread(
+      &config.file_config.output_file,
+      imgcodecs::IMREAD_UNCHANGED,
+    )
+    .unwrap();
+    assert_eq!(image.channels(), 4);
+    let alpha = |point: core::Point| {
+      image
+        .at_2d::<core::Vec4b>(point.y, point.x)
+        .map(|px| px[3])
+        .unwrap()
+    };
+    assert_eq!(alpha(core::Point::new(0, 0)), 0);
+    assert_eq!(alpha(core::Point::new(799, 599)), 0);
+
+    // The background is never read, and the route is opaque
+    let (route, _) = fit_reader(&config.file_config.fit_file).unwrap();
+    let projector = Projector::new(
+      &route.gps_points,
+      config.route_scale,
+      800,
+    );
+    let (lat, lon) = route.gps_points[route.gps_points.len() / 2];
+    assert_eq!(alpha(projector.project(lat, lon)), 255);
+  }
+
+  #[test]
+  fn test_embedded_config_replays_the_image() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("source");
+    let (fit, background) = (
+      source.join("example.fit"),
+      source.join("example.jpg"),
+    );
+    if !fit.exists() || !background.exists() {
+      return;
+    }
+
+    let output = env::temp_dir().join("runarium_provenance.png");
+    let output = output.to_string_lossy().into_owned();
+    let mut config = RouteImageConfig::default(
+      fit.to_string_lossy().into_owned(),
+      background.to_string_lossy().into_owned(),
+      output.clone(),
+    );
+    config.deterministic = true;
+    config.embed_config = true;
+    config.line_thickness = 7;
+    image_route_with_config(config).unwrap();
+    let first = content_hash(&fs::read(&output).unwrap());
+    assert!(
+      Path::new(&crate::provenance::sidecar_path(
+        &output
+      ))
+      .exists()
+    );
+
+    let replayed = RouteImageConfig::from_image_metadata(&output).unwrap();
+    assert_eq!(replayed.line_thickness, 7);
+    image_route_with_config(replayed).unwrap();
+    assert_eq!(
+      content_hash(&fs::read(&output).unwrap()),
+      first
+    );
+  }
+}
@@ -1,28 +1,36 @@
-use anyhow::Result;
+use std::fs;
+
+use anyhow::{bail, Result};
 use opencv::{core, imgproc, prelude::*};
+use tracing::{debug, field, info_span};
 
 use crate::{
-  configs::RouteVideoConfig,
+  configs::{
+    FileConfig, Font, PaceDistConfig, RouteScale, RouteVideoConfig,
+    SequenceEntry, VideoFallback,
+  },
+  generators::frame_composer::FrameComposer,
+  provenance::embed_config,
   types::{
-    drawer_data::{PositionRect, Rect, SizeRect},
-    fit_data::{LapData, RouteData},
+    output::{RenderOutput, RenderedBytes, SequenceOutput},
+    warning::{Warning, Warnings},
   },
   utils::{
-    converter::{
-      convert_pace_to_sec, get_bounds, load_and_resize_image, pace_percentage,
-      string_space,
-    },
-    creator::video_creator,
+    converter::format_date,
+    creator::image_creator_atomic,
     element_drawer::Drawer,
+    frame_sink::FrameSink,
     performance::processed,
-    read_file::fit_reader,
+    read_file::activity_reader_for,
+    workspace::{output_name, Workspace},
   },
 };
 
 /// Generates an animated video of a running route with lap statistics overlay.
 ///
-/// Creates a video showing progressive route drawing on a background image,
-/// with real-time pace/distance info and a lap statistics panel.
+/// Renders `source/example.fit` over `source/example.jpg` into
+/// `outputs/simple.mp4` with the default look, through the same pipeline
+/// as `progressive_route_with_config`.
 ///
 /// # Arguments
 /// * `route_scale` - Scale factor for route visualization (0.0-1.0 recommended)
@@ -34,7 +42,7 @@ use crate::{
 /// * `Err` - If FIT file reading, video encoding, or drawing operations fail
 ///
 /// # Output
-/// Creates `outputs/example.mp4` with:
+/// Creates `outputs/simple.mp4` with:
 /// - Animated route drawing (red line)
 /// - Current position marker (green dot)
 /// - Lap statistics panel (pace, heart rate, stride length)
@@ -44,215 +52,35 @@ pub fn progressive_route(
   offset_x_percent: f64,
   offset_y_percent: f64,
 ) -> Result<()> {
-  // Read and extract data
-  #[rustfmt::skip]
-  let (route, lap) = fit_reader("source/example.fit")?;
-  let RouteData {
-    paces,
-    gps_points: points,
-    distances,
-  } = route;
-  let LapData {
-    avg_heart_rate,
-    enhanced_avg_speed,
-    avg_step_length,
-  } = lap;
-
-  // Normalize coordinates
-  #[rustfmt::skip]
-  let (
-    (lat_min, lat_max),
-    (lon_min, lon_max)
-  ) = get_bounds(&points);
-
-  // Get background image
-  let (bg_image, width, height) =
-    load_and_resize_image("source/example.jpg", 1080)?;
-
-  // Coordinate normalization to image space
-  let to_px = |lat: f64, lon: f64| -> core::Point {
-    let nx = if lon_max != lon_min {
-      (lon - lon_min) / (lon_max - lon_min)
-    } else {
-      0.5
-    };
-    let ny = if lat_max != lat_min {
-      (lat - lat_min) / (lat_max - lat_min)
-    } else {
-      0.5
-    };
-
-    let x = ((offset_x_percent + nx * route_scale) * width as f64) as i32;
-    #[rustfmt::skip]
-    let y = ((offset_y_percent + (1.0 - ny) * route_scale) * width as f64) as i32;
-    core::Point::new(x, y)
-  };
-
-  // Initialized video generator
-  #[rustfmt::skip]
-  let pixel_points: Vec<core::Point> = points
-    .iter()
-    .map(|&(la, lo)| to_px(la, lo))
-    .collect();
-  let fps = (pixel_points.len() / 15) as f64;
-  let output_file = "outputs/simple.mp4";
-  let mut video = video_creator(width, height, fps, output_file)?;
-
-  // Initialize frame
-  // let mut path_frame = Mat::zeros(height, width, core::CV_8UC3)?.to_mat()?;
-  let mut resized = Mat::default();
-  imgproc::resize(
-    &bg_image,
-    &mut resized,
-    core::Size::new(width, height),
-    0.0,
-    0.0,
-    imgproc::INTER_LANCZOS4,
-  )?;
-
-  let mut path_frame = resized.clone();
-  let drawer = Drawer::new(width, height);
-
-  let font = crate::configs::video_config::Font::Simplex;
-  let font_scale = 0.5;
-  let thickness = 1;
-
-  let pace_seconds: Vec<f32> = enhanced_avg_speed
-    .iter()
-    .map(|p| convert_pace_to_sec(p))
-    .collect();
-
-  let start_x = width / 2;
-  let start_y = 100;
-  let min_val = *pace_seconds
-    .iter()
-    .min_by(|a, b| a.total_cmp(b))
-    .expect("Failed to find min pace");
-  let min_denominator = (min_val / 30.0).floor() * 30.0;
-  let bar_max_width = 200;
-
-  // Create lap data
-  drawer
-    .header(
-      &mut path_frame,
-      start_x,
-      start_y,
-      font_scale,
-      2,
-      font,
-    )
-    .expect("Failed to draw header!");
-
-  let green_color = drawer.color([0.0, 255.0, 0.0, 0.0]);
-  let white_color = drawer.color([255.0, 255.0, 255.0, 0.0]);
-  let size_of_speeds = enhanced_avg_speed.len();
-  for (i, pace) in enhanced_avg_speed.iter().enumerate() {
-    let size = drawer.text_size(pace, font_scale, thickness, font)?;
-    let x = start_x - size.width / 2;
-    let y = start_y + i as i32 * (size.height + 5);
-
-    let pace_space = string_space(size_of_speeds, i + 1, pace);
-    drawer
-      .text(
-        &mut path_frame,
-        &pace_space,
-        x,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw pace");
-
-    let hr = &format!("{}", avg_heart_rate[i]);
-    drawer
-      .text(
-        &mut path_frame,
-        hr,
-        x + 300,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw heart rate");
-
-    let lenght_meters = avg_step_length[i] / 10.0;
-    let stride_length = &format!("{}", lenght_meters);
-    drawer
-      .text(
-        &mut path_frame,
-        stride_length,
-        x + 350,
-        y,
-        font_scale,
-        thickness,
-        font,
-        white_color,
-      )
-      .expect("Failed to draw stride length");
-
-    let percent = pace_percentage(min_denominator, pace_seconds[i]);
-    let bar_width = (percent * bar_max_width as f32) as i32;
-    let bar_height = size.height;
-    let bar_x = x + size.width + 60;
-    let bar_y = y - size.height;
-    let rect = Rect {
-      pos: PositionRect { x: bar_x, y: bar_y },
-      size: SizeRect {
-        width: bar_width,
-        height: bar_height,
-      },
-    };
-    drawer
-      .rectangle(&mut path_frame, rect, green_color)
-      .expect("Failed to draw bar");
-  }
-
-  // Create progressive route
-  let red_color = drawer.color([0.0, 0.0, 255.0, 0.0]);
-  for (i, point) in pixel_points.iter().enumerate() {
-    if i > 0 {
-      drawer.line(
-        &mut path_frame,
-        pixel_points[i - 1],
-        *point,
-        red_color,
-      )?;
-    }
-
-    let mut current_frame = path_frame.clone();
-    drawer.point(&mut current_frame, *point, green_color)?;
-
-    if i < paces.len() && i < distances.len() {
-      let pace_text = format!("Pace: {} min/km", paces[i]);
-      let dist_text = format!("Dist: {:.2} km", distances[i] / 1000.0);
-      let font_scale = 0.8;
-      let thickness = 2;
-
-      drawer.text_bar(
-        &mut current_frame,
-        &pace_text,
-        &dist_text,
-        font_scale,
-        thickness,
-        font,
-      )?;
-    }
+  progressive_route_with_config(legacy_config(
+    route_scale,
+    offset_x_percent,
+    offset_y_percent,
+  ))?;
+  Ok(())
+}
 
-    video.write(&current_frame)?;
-    processed(i, pixel_points.clone());
+/// Config `progressive_route` renders with
+fn legacy_config(
+  route_scale: f64,
+  offset_x_percent: f64,
+  offset_y_percent: f64,
+) -> RouteVideoConfig {
+  RouteVideoConfig {
+    route_scale: RouteScale::new(
+      route_scale,
+      offset_x_percent,
+      offset_y_percent,
+    ),
+    // Large pace and distance text, as before the config API
+    pace_dist: PaceDistConfig::new(0.8, 2, Font::Simplex, None, true, true),
+    file_config: FileConfig::new(
+      "source/example.fit".to_string(),
+      "source/example.jpg".to_string(),
+      "outputs/simple.mp4".to_string(),
+    ),
+    ..Default::default()
   }
-
-  video.release()?;
-  println!(
-    "✅ Video created: {} with {} points",
-    output_file,
-    pixel_points.len()
-  );
-  Ok(())
 }
 
 /// Generates an animated video with custom configuration.
@@ -264,7 +92,7 @@ pub fn progressive_route(
 /// * `config` - RouteVideoConfig containing all customization options
 ///
 /// # Returns
-/// * `Ok(())` - Video successfully created and saved
+/// * `Ok(RenderOutput)` - Video saved; includes the projection info
 /// * `Err` - If FIT file reading, video encoding, or drawing operations fail
 ///
 /// # Example
@@ -275,254 +103,770 @@ pub fn progressive_route(
 /// let config = RouteVideoConfig::default();
 /// progressive_route_with_config(config);
 /// ```
-pub fn progressive_route_with_config(config: RouteVideoConfig) -> Result<()> {
+pub fn progressive_route_with_config(
+  config: RouteVideoConfig,
+) -> Result<RenderOutput> {
+  progressive_route_with_progress(config, |done, total| {
+    processed(done - 1, total)
+  })
+}
+
+/// Same as `progressive_route_with_config`, reporting progress to
+/// `on_progress(done, total)` after each planned frame is encoded
+pub fn progressive_route_with_progress(
+  config: RouteVideoConfig,
+  on_progress: impl FnMut(usize, usize),
+) -> Result<RenderOutput> {
+  let span = info_span!(
+    "render_video",
+    codec = ?config.codec,
+    point_count = field::Empty
+  );
+  let _entered = span.enter();
+
   // Read and extract data
-  #[rustfmt::skip]
-  let (route, lap) = fit_reader(&config.file_config.fit_file)?;
-  let RouteData {
-    paces,
-    gps_points: points,
-    distances,
-  } = route;
-  let LapData {
-    avg_heart_rate,
-    enhanced_avg_speed,
-    avg_step_length,
-  } = lap;
-
-  // Normalize coordinates
-  #[rustfmt::skip]
-  let (
-    (lat_min, lat_max),
-    (lon_min, lon_max)
-  ) = get_bounds(&points);
-
-  // Get background image
-  let (bg_image, width, height) = load_and_resize_image(
-    &config.file_config.background_image,
-    1080,
+  let activity = activity_reader_for(
+    &config.file_config,
+    &config.captured_fields(),
   )?;
+  span.record(
+    "point_count",
+    activity.route.gps_points.len(),
+  );
+
+  let composer = FrameComposer::new(activity, config)?;
+  composer.write_exports()?;
+  encode_video(composer, on_progress)
+}
+
+/// Renders a video from in-memory FIT and background files and returns the
+/// encoded video.
+///
+/// The files live in a `Workspace` that is removed before returning, even
+/// on panic; `config.file_config` only supplies the output extension.
+/// A `VideoFallback::FrameSequence` fallback is rejected up front, as a
+/// directory of frames has no single file to return.
+pub fn progressive_route_from_bytes(
+  fit: &[u8],
+  background: &[u8],
+  config: RouteVideoConfig,
+) -> Result<RenderedBytes> {
+  progressive_route_from_bytes_with_progress(
+    fit,
+    background,
+    config,
+    |done, total| processed(done - 1, total),
+  )
+}
+
+/// Same as `progressive_route_from_bytes`, reporting progress to
+/// `on_progress(done, total)` after each planned frame is encoded
+pub fn progressive_route_from_bytes_with_progress(
+  fit: &[u8],
+  background: &[u8],
+  mut config: RouteVideoConfig,
+  on_progress: impl FnMut(usize, usize),
+) -> Result<RenderedBytes> {
+  if config.fallback == VideoFallback::FrameSequence {
+    bail!("A frame sequence fallback cannot be returned as bytes");
+  }
+  let workspace = Workspace::new("runarium")?;
+  let output_name = output_name(&config.file_config.output_file, "mp4");
+  let input_format = config.file_config.input_format;
+  config.file_config = workspace.file_config(fit, background, &output_name)?;
+  config.file_config.input_format = input_format;
+
+  let output = progressive_route_with_progress(config, on_progress)?;
+  let bytes = fs::read(&output.output_file)?;
+  Ok(RenderedBytes { bytes, output })
+}
 
-  // Coordinate normalization to image space
-  let to_px = |lat: f64, lon: f64| -> core::Point {
-    let nx = if lon_max != lon_min {
-      (lon - lon_min) / (lon_max - lon_min)
-    } else {
-      0.5
+/// Frame rate of `sequence_video` reels
+pub const SEQUENCE_FPS: f64 = 30.0;
+
+/// Length of the title card before each activity of a reel
+pub const TITLE_CARD_SECS: f64 = 1.0;
+
+/// Generates one video playing several activities back to back, each
+/// introduced by a title card with its title and date.
+///
+/// Every entry is rendered with `template`, pointed at the entry's FIT
+/// file and background, into one shared video at `SEQUENCE_FPS`.
+/// `template.effective_duration()` is the length of the whole reel: what
+/// the title cards leave is shared evenly by the activities, and each
+/// activity's frames are resampled to fill its share. Frames are scaled
+/// to `template.output_size`, or to the size of the first activity, so
+/// entries may use backgrounds of different sizes.
+///
+/// Entries whose FIT file or background cannot be read are left out with
+/// a `SequenceEntrySkipped` warning, and the reel is shorter by their
+/// share. Encoding errors stop the reel.
+///
+/// # Arguments
+/// * `entries` - Activities in playing order, as `SequenceEntry` values or
+///   `(fit_file, title)` pairs
+/// * `template` - Configuration every activity is rendered with
+///
+/// # Returns
+/// * `Ok(SequenceOutput)` - Reel saved; includes the skipped entries
+/// * `Err` - If no entry can be rendered, the reel is too short for its
+///   title cards, or encoding fails
+///
+/// # Example
+/// ```no_run
+/// use std::path::PathBuf;
+///
+/// use runarium::configs::RouteVideoConfig;
+/// use runarium::generators::route_video::sequence_video;
+///
+/// let mut template = RouteVideoConfig::default();
+/// template.duration_secs = 60.0;
+/// template.file_config.output_file = "outputs/may.mp4".to_string();
+///
+/// let entries = vec![
+///   (PathBuf::from("source/may-04.fit"), Some("Easy 5K".to_string())),
+///   (PathBuf::from("source/may-11.fit"), None),
+/// ];
+/// let output = sequence_video(entries, template).unwrap();
+/// println!("{} activities", output.activities);
+/// ```
+pub fn sequence_video(
+  entries: impl IntoIterator<Item = impl Into<SequenceEntry>>,
+  template: RouteVideoConfig,
+) -> Result<SequenceOutput> {
+  let entries: Vec<SequenceEntry> =
+    entries.into_iter().map(Into::into).collect();
+  let mut warnings = Warnings::default();
+
+  // Read every FIT file first so the time budget counts only usable ones
+  let mut activities = Vec::new();
+  for entry in &entries {
+    let config = entry.config(&template);
+    match activity_reader_for(
+      &config.file_config,
+      &config.captured_fields(),
+    ) {
+      Ok(activity) => activities.push((entry, config, activity)),
+      Err(e) => warnings.push(entry_skipped(entry, &e)),
+    }
+  }
+  if activities.is_empty() {
+    bail!("No activity of the sequence could be read");
+  }
+
+  let activity_secs = activity_secs(
+    template.effective_duration(),
+    activities.len(),
+  )?;
+  let card_frames = (TITLE_CARD_SECS * SEQUENCE_FPS).round() as usize;
+  let total = activities.len();
+
+  let mut reel: Option<(FrameSink, core::Size)> = None;
+  let (mut frames, mut rendered) = (0, 0);
+  for (i, (entry, mut config, activity)) in activities.into_iter().enumerate() {
+    config.duration_secs = activity_secs;
+    config.max_duration_secs = None;
+    let start_time = activity.session.start_time;
+    let mut composer = match FrameComposer::new(activity, config) {
+      Ok(composer) => composer,
+      Err(e) => {
+        warnings.push(entry_skipped(entry, &e));
+        continue;
+      }
     };
-    let ny = if lat_max != lat_min {
-      (lat - lat_min) / (lat_max - lat_min)
-    } else {
-      0.5
+
+    // The first rendered activity opens the video
+    let (video, size) = match &mut reel {
+      Some(reel) => reel,
+      None => {
+        let (width, height) = template
+          .output_size
+          .unwrap_or((composer.width(), composer.height()));
+        let mut video =
+          FrameSink::open(width, height, SEQUENCE_FPS, &template)?;
+        if let Some(warning) = video.warning.take() {
+          warnings.push(warning);
+        }
+        reel.insert((video, core::Size::new(width, height)))
+      }
     };
 
-    let x = ((config.route_scale.offset_x_percent
-      + nx * config.route_scale.scale)
-      * width as f64) as i32;
-    #[rustfmt::skip]
-    let y = ((config.route_scale.offset_y_percent + (1.0 - ny) * config.route_scale.scale) * width as f64) as i32;
-    core::Point::new(x, y)
+    // Title card
+    let card = title_card(*size, &entry.title_text(), start_time)?;
+    for _ in 0..card_frames {
+      video.write(&card)?;
+    }
+
+    // Activity frames, resampled to the reel frame rate
+    let source_fps = composer.fps();
+    let (mut source, mut written) = (0, 0);
+    let mut resized = Mat::default();
+    while let Some(frame) = composer.next_mat() {
+      let mut frame = frame?;
+      if frame.size()? != *size {
+        imgproc::resize(
+          frame,
+          &mut resized,
+          *size,
+          0.0,
+          0.0,
+          imgproc::INTER_AREA,
+        )?;
+        frame = &resized;
+      }
+      source += 1;
+      while written < resampled_frames(source, source_fps, SEQUENCE_FPS) {
+        video.write(frame)?;
+        written += 1;
+      }
+    }
+
+    frames += card_frames + written;
+    rendered += 1;
+    warnings.extend(composer.warnings().clone());
+    println!(
+      "✅ Activity {}/{}: {}",
+      i + 1,
+      total,
+      entry.fit_file.display()
+    );
+  }
+
+  let Some((video, _)) = reel else {
+    bail!("No activity of the sequence could be rendered");
   };
+  let output_file = video.output_file.clone();
+  video.finish()?;
 
-  // Initialized video generator
-  #[rustfmt::skip]
-  let pixel_points: Vec<core::Point> = points
-    .iter()
-    .map(|&(la, lo)| to_px(la, lo))
-    .collect();
-  let fps = (pixel_points.len() / 15) as f64;
-  let mut video = video_creator(
-    width,
-    height,
-    fps,
-    &config.file_config.output_file,
+  println!(
+    "✅ Video created: {} with {} activities",
+    output_file, rendered
+  );
+  for warning in &warnings {
+    println!("⚠️  {}", warning);
+  }
+  Ok(SequenceOutput {
+    output_file,
+    activities: rendered,
+    duration_secs: frames as f64 / SEQUENCE_FPS,
+    warnings,
+  })
+}
+
+fn entry_skipped(entry: &SequenceEntry, error: &anyhow::Error) -> Warning {
+  Warning::SequenceEntrySkipped {
+    fit_file: entry.fit_file.display().to_string(),
+    reason: error.to_string(),
+  }
+}
+
+/// Seconds each of `activities` activities gets of a `total`-second reel,
+/// after its title card
+fn activity_secs(total: f64, activities: usize) -> Result<f64> {
+  let secs = total / activities as f64 - TITLE_CARD_SECS;
+  if secs <= 0.0 {
+    bail!(
+      "A {} s reel is too short for {} activities with {} s title cards",
+      total,
+      activities,
+      TITLE_CARD_SECS
+    );
+  }
+  Ok(secs)
+}
+
+/// Frames at `target_fps` covering the first `source_frames` frames of a
+/// video at `source_fps`
+fn resampled_frames(
+  source_frames: usize,
+  source_fps: f64,
+  target_fps: f64,
+) -> usize {
+  (source_frames as f64 * target_fps / source_fps).round() as usize
+}
+
+/// Dark frame of `size` with `title` centered and the activity date
+/// under it
+fn title_card(
+  size: core::Size,
+  title: &str,
+  start_time: Option<i64>,
+) -> Result<Mat> {
+  let mut card = Mat::new_rows_cols_with_default(
+    size.height,
+    size.width,
+    core::CV_8UC3,
+    core::Scalar::new(30.0, 30.0, 30.0, 0.0),
   )?;
+  let drawer = Drawer::new(size.width, size.height);
+  let white = drawer.color([255.0, 255.0, 255.0, 0.0]);
+  let muted = drawer.color([180.0, 180.0, 180.0, 0.0]);
+
+  // Scale with the frame, shrinking titles too long to fit
+  let base_scale = size.width as f64 / 600.0;
+  let thickness = (base_scale * 2.0).round().max(1.0) as i32;
+  let mut title_scale = base_scale * 1.4;
+  let width = drawer.text_size(
+    title,
+    title_scale,
+    thickness,
+    Font::Duplex,
+  )?;
+  let max_width = size.width as f64 * 0.9;
+  if width.width as f64 > max_width {
+    title_scale *= max_width / width.width as f64;
+  }
 
-  // Initialize frame
-  let mut resized = Mat::default();
-  imgproc::resize(
-    &bg_image,
-    &mut resized,
-    core::Size::new(width, height),
-    0.0,
-    0.0,
-    imgproc::INTER_LANCZOS4,
+  let center_y = size.height / 2;
+  let title_size = drawer.text_size(
+    title,
+    title_scale,
+    thickness,
+    Font::Duplex,
+  )?;
+  drawer.text(
+    &mut card,
+    title,
+    (size.width - title_size.width) / 2,
+    center_y,
+    title_scale,
+    thickness,
+    Font::Duplex,
+    white,
   )?;
 
-  let mut path_frame = resized.clone();
-  let drawer = Drawer::new(width, height);
-
-  let pace_seconds: Vec<f32> = enhanced_avg_speed
-    .iter()
-    .map(|p| convert_pace_to_sec(p))
-    .collect();
-
-  let start_x = (config.lap_data.position.0 * width as f64) as i32;
-  let start_y = (config.lap_data.position.1 * height as f64) as i32;
-  let min_val = *pace_seconds
-    .iter()
-    .min_by(|a, b| a.total_cmp(b))
-    .expect("Failed to find min pace");
-  let min_denominator = (min_val / 30.0).floor() * 30.0;
-
-  // Create lap data
-  if config.show_lap_data {
-    drawer
-      .header(
-        &mut path_frame,
-        start_x,
-        start_y,
-        config.lap_data.font_scale,
-        2,
-        config.lap_data.font,
-      )
-      .expect("Failed to draw header!");
-
-    let text_color = drawer.color(config.lap_data.text_color.to_bgra());
-    let bar_color = drawer.color(config.colors.lap_bars);
-    let size_of_speeds = enhanced_avg_speed.len();
-
-    for (i, pace) in enhanced_avg_speed.iter().enumerate() {
-      let size = drawer.text_size(
-        pace,
-        config.lap_data.font_scale,
-        config.lap_data.thickness,
-        config.lap_data.font,
-      )?;
-      let x = start_x - size.width / 2;
-      let y = start_y + i as i32 * (size.height + 5);
-
-      // Draw pace
-      let pace_space = string_space(size_of_speeds, i + 1, pace);
-      drawer
-        .text(
-          &mut path_frame,
-          &pace_space,
-          x,
-          y,
-          config.lap_data.font_scale,
-          config.lap_data.thickness,
-          config.lap_data.font,
-          text_color,
-        )
-        .expect("Failed to draw pace");
-
-      // Draw heart rate if enabled
-      if config.lap_data.show_heart_rate {
-        let hr = &format!("{}", avg_heart_rate[i]);
-        drawer
-          .text(
-            &mut path_frame,
-            hr,
-            x + 300,
-            y,
-            config.lap_data.font_scale,
-            config.lap_data.thickness,
-            config.lap_data.font,
-            text_color,
-          )
-          .expect("Failed to draw heart rate");
-      }
+  if let Some(start_time) = start_time {
+    let date = format_date(start_time);
+    let date_size = drawer.text_size(
+      &date,
+      base_scale * 0.7,
+      1,
+      Font::Simplex,
+    )?;
+    drawer.text(
+      &mut card,
+      &date,
+      (size.width - date_size.width) / 2,
+      center_y + title_size.height + date_size.height * 2,
+      base_scale * 0.7,
+      1,
+      Font::Simplex,
+      muted,
+    )?;
+  }
+  Ok(card)
+}
 
-      // Draw stride length if enabled
-      if config.lap_data.show_stride_length {
-        let length_meters = avg_step_length[i] / 10.0;
-        let stride_length = &format!("{}", length_meters);
-        drawer
-          .text(
-            &mut path_frame,
-            stride_length,
-            x + 350,
-            y,
-            config.lap_data.font_scale,
-            config.lap_data.thickness,
-            config.lap_data.font,
-            text_color,
-          )
-          .expect("Failed to draw stride length");
-      }
+/// Frames between the progress events of the `frame_loop` span
+pub const FRAME_EVENT_INTERVAL: usize = 100;
+
+/// Feeds every frame of `composer` to the encoder configured in its
+/// `RouteVideoConfig`
+pub(crate) fn encode_video(
+  mut composer: FrameComposer,
+  mut on_progress: impl FnMut(usize, usize),
+) -> Result<RenderOutput> {
+  let codec = composer.config().codec;
+  let frame_loop = info_span!(
+    "frame_loop",
+    width = composer.width(),
+    height = composer.height(),
+    fps = composer.fps(),
+    ?codec,
+    frames = field::Empty
+  );
+  let entered = frame_loop.enter();
+  let mut sink = FrameSink::open(
+    composer.width(),
+    composer.height(),
+    composer.fps(),
+    composer.config(),
+  )?;
 
-      // Draw pace bars if enabled
-      if config.lap_data.show_pace_bars {
-        let percent = pace_percentage(min_denominator, pace_seconds[i]);
-        let bar_width = (percent * 200.0) as i32;
-        let bar_height = size.height;
-        let bar_x = x + size.width + 60;
-        let bar_y = y - size.height;
-        let rect = Rect {
-          pos: PositionRect { x: bar_x, y: bar_y },
-          size: SizeRect {
-            width: bar_width,
-            height: bar_height,
-          },
-        };
-        drawer
-          .rectangle(&mut path_frame, rect, bar_color)
-          .expect("Failed to draw bar");
+  let preview = composer.config().live_preview.as_ref().map(|preview| {
+    let output_file = &composer.config().file_config.output_file;
+    (
+      preview.path_for(output_file),
+      preview.every_frames.max(1),
+    )
+  });
+
+  let mut reported = 0;
+  let mut frames: usize = 0;
+  while let Some(frame) = composer.next_mat() {
+    let frame = frame?;
+    sink.write(frame)?;
+    frames += 1;
+    if frames.is_multiple_of(FRAME_EVENT_INTERVAL) {
+      debug!(frames, "frames rendered");
+    }
+    // Written from the frame buffer the encoder just read
+    if let Some((path, every_frames)) = &preview {
+      if frames.is_multiple_of(*every_frames) {
+        image_creator_atomic(path, frame)?;
       }
     }
+
+    // Lap pause holds repeat a planned frame
+    let (done, total) = composer.progress();
+    if done > reported {
+      on_progress(done, total);
+      reported = done;
+    }
   }
+  frame_loop.record("frames", frames);
+  drop(entered);
 
-  // Create progressive route
-  let route_color = drawer.color(config.colors.route_line);
-  let position_color = drawer.color(config.colors.current_position);
-
-  for (i, point) in pixel_points.iter().enumerate() {
-    if config.show_route && i > 0 {
-      drawer.line(
-        &mut path_frame,
-        pixel_points[i - 1],
-        *point,
-        route_color,
-      )?;
+  // Leave the preview on the last frame
+  if let Some((path, every_frames)) = &preview {
+    if frames > 0 && !frames.is_multiple_of(*every_frames) {
+      image_creator_atomic(path, composer.last_mat())?;
     }
+  }
+
+  let output_file = sink.output_file.clone();
+  let mut warnings = Warnings::default();
+  if let Some(warning) = sink.warning.take() {
+    warnings.push(warning);
+  }
+  warnings.extend(composer.warnings().clone());
+  info_span!("encode", ?codec, output_file = %output_file)
+    .in_scope(|| sink.finish())?;
+  if composer.config().embed_config {
+    embed_config(&output_file, composer.config())?;
+  }
+  let output = RenderOutput {
+    output_file,
+    projection: composer.projection_info(),
+    warnings,
+    time_index: composer.time_index(composer.config().time_index),
+    palette: composer.palette(),
+  };
 
-    let mut current_frame = path_frame.clone();
-    if config.show_route {
-      drawer.point(
-        &mut current_frame,
-        *point,
-        position_color,
-      )?;
+  println!(
+    "✅ Video created: {} with {} points",
+    output.output_file,
+    composer.point_count()
+  );
+  for warning in &output.warnings {
+    println!("⚠️  {}", warning);
+  }
+  if let Some(sink) = &composer.config().on_complete {
+    sink.run(&output)?;
+  }
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    env,
+    path::Path,
+    sync::{Arc, Mutex},
+  };
+
+  use opencv::imgcodecs;
+  use tracing::{
+    field::{Field, Visit},
+    span, Subscriber,
+  };
+  use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer,
+  };
+
+  use super::*;
+  use crate::{
+    configs::{BackgroundLayer, LayerSource, LivePreview, ProgressionMode},
+    utils::{fingerprint::content_hash, testing::synthetic_activity},
+  };
+
+  /// Span, name and value of each integer field recorded
+  type Fields = Mutex<Vec<(&'static str, &'static str, i64)>>;
+
+  /// Names of the opened spans and the integer fields recorded on them
+  #[derive(Clone, Default)]
+  struct SpanRecorder {
+    opened: Arc<Mutex<Vec<&'static str>>>,
+    fields: Arc<Fields>,
+  }
+
+  struct FieldVisitor<'a> {
+    span: &'static str,
+    fields: &'a Fields,
+  }
+
+  impl Visit for FieldVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+      self
+        .fields
+        .lock()
+        .unwrap()
+        .push((self.span, field.name(), value));
     }
 
-    // Draw pace and distance overlay if enabled
-    if config.show_bottom_bar
-      && i < paces.len()
-      && i < distances.len()
-      && (config.pace_dist.show_pace || config.pace_dist.show_distance)
-    {
-      let pace_text = if config.pace_dist.show_pace {
-        format!("Pace: {} min/km", paces[i])
-      } else {
-        String::new()
-      };
+    fn record_u64(&mut self, field: &Field, value: u64) {
+      self.record_i64(field, value as i64);
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+  }
 
-      let dist_text = if config.pace_dist.show_distance {
-        format!("Dist: {:.2} km", distances[i] / 1000.0)
-      } else {
-        String::new()
+  impl<S> Layer<S> for SpanRecorder
+  where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+  {
+    fn on_new_span(
+      &self,
+      attrs: &span::Attributes<'_>,
+      _: &span::Id,
+      _: Context<'_, S>,
+    ) {
+      let span = attrs.metadata().name();
+      self.opened.lock().unwrap().push(span);
+      attrs.record(&mut FieldVisitor {
+        span,
+        fields: &self.fields,
+      });
+    }
+
+    fn on_record(
+      &self,
+      id: &span::Id,
+      values: &span::Record<'_>,
+      ctx: Context<'_, S>,
+    ) {
+      let span = ctx.span(id).unwrap().name();
+      values.record(&mut FieldVisitor {
+        span,
+        fields: &self.fields,
+      });
+    }
+  }
+
+  #[test]
+  fn test_activity_secs_share_reel() {
+    // 60 s for 3 activities: 1 s card + 19 s each
+    assert_eq!(activity_secs(60.0, 3).unwrap(), 19.0);
+    assert!(activity_secs(3.0, 3).is_err());
+  }
+
+  #[test]
+  fn test_resampled_frames() {
+    // 10 fps source at 30 fps: three reel frames per source frame
+    assert_eq!(resampled_frames(1, 10.0, 30.0), 3);
+    assert_eq!(resampled_frames(10, 10.0, 30.0), 30);
+    // 60 fps source at 30 fps: every other source frame
+    assert_eq!(resampled_frames(1, 60.0, 30.0), 1);
+    assert_eq!(resampled_frames(2, 60.0, 30.0), 1);
+    assert_eq!(resampled_frames(120, 60.0, 30.0), 60);
+  }
+
+  #[test]
+  fn test_legacy_video_renders_like_the_config_path() {
+    // Small and short, over a plain background instead of the example
+    let frames = |config: RouteVideoConfig| -> Vec<Vec<u8>> {
+      let config = RouteVideoConfig {
+        backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+          color: [40.0, 40.0, 40.0, 0.0],
+        })],
+        output_size: Some((320, 320)),
+        duration_secs: 1.0,
+        ..config
       };
+      FrameComposer::new(synthetic_activity(60), config)
+        .unwrap()
+        .map(|frame| frame.unwrap().to_bytes().unwrap())
+        .collect()
+    };
+
+    let mut config = RouteVideoConfig {
+      route_scale: RouteScale::new(0.2, 0.1, 0.1),
+      ..Default::default()
+    };
+    config.pace_dist.font_scale = 0.8;
+    config.pace_dist.thickness = 2;
+
+    let legacy = frames(legacy_config(0.2, 0.1, 0.1));
+    assert_eq!(legacy.len(), 60);
+    assert!(legacy == frames(config));
+  }
 
-      drawer.text_bar(
-        &mut current_frame,
-        &pace_text,
-        &dist_text,
-        config.pace_dist.font_scale,
-        config.pace_dist.thickness,
-        config.pace_dist.font,
-      )?;
+  #[test]
+  fn test_generation_stages_emit_spans() {
+    let recorder = SpanRecorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    let output = env::temp_dir().join("runarium_spans.mp4");
+    let mut config = RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((160, 160)),
+      duration_secs: 1.0,
+      ..Default::default()
+    };
+    config.file_config.output_file = output.to_string_lossy().into_owned();
+
+    tracing::subscriber::with_default(subscriber, || {
+      let composer =
+        FrameComposer::new(synthetic_activity(60), config).unwrap();
+      encode_video(composer, |_, _| {}).unwrap();
+    });
+    let _ = fs::remove_file(output);
+
+    let opened = recorder.opened.lock().unwrap().clone();
+    assert_eq!(
+      opened,
+      ["background_load", "lap_panel", "frame_loop", "encode"]
+    );
+    let fields = recorder.fields.lock().unwrap().clone();
+    assert!(fields.contains(&("background_load", "width", 160)));
+    assert!(fields.contains(&("frame_loop", "frames", 60)));
+  }
+
+  #[test]
+  fn test_live_preview_ends_on_the_last_frame() {
+    let output = env::temp_dir().join("runarium_preview.mp4");
+    let preview = env::temp_dir().join("runarium_preview.png");
+    let mut config = RouteVideoConfig {
+      backgrounds: vec![BackgroundLayer::new(LayerSource::Solid {
+        color: [40.0, 40.0, 40.0, 0.0],
+      })],
+      output_size: Some((160, 160)),
+      duration_secs: 1.0,
+      live_preview: Some(LivePreview {
+        path: Some(preview.clone()),
+        every_frames: 5,
+      }),
+      ..Default::default()
+    };
+    config.file_config.output_file = output.to_string_lossy().into_owned();
+
+    // 62 frames: the last preview is written after the loop
+    let composer =
+      FrameComposer::new(synthetic_activity(62), config.clone()).unwrap();
+    encode_video(composer, |_, _| {}).unwrap();
+    let _ = fs::remove_file(output);
+
+    let last = FrameComposer::new(synthetic_activity(62), config)
+      .unwrap()
+      .last()
+      .unwrap()
+      .unwrap();
+    let written = imgcodecs::imread(
+      &preview.to_string_lossy(),
+      imgcodecs::IMREAD_UNCHANGED,
+    )
+    .unwrap();
+    assert_eq!(
+      written.data_bytes().unwrap(),
+      last.to_bytes().unwrap()
+    );
+    assert!(!env::temp_dir().join(".tmp-runarium_preview.png").exists());
+    fs::remove_file(preview).unwrap();
+  }
+
+  #[test]
+  fn test_deterministic_video_is_byte_identical() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("source");
+    let (fit, background) = (
+      source.join("example.fit"),
+      source.join("example.jpg"),
+    );
+    if !fit.exists() || !background.exists() {
+      return;
     }
 
-    video.write(&current_frame)?;
-    processed(i, pixel_points.clone());
+    // Short, small video to keep the test fast
+    let render = |name: &str| {
+      let output = env::temp_dir().join(name);
+      let config = RouteVideoConfig {
+        file_config: FileConfig::new(
+          fit.to_string_lossy().into_owned(),
+          background.to_string_lossy().into_owned(),
+          output.to_string_lossy().into_owned(),
+        ),
+        output_size: Some((320, 320)),
+        duration_secs: 1.0,
+        progression: ProgressionMode::PerDistance {
+          meters_per_frame: 250.0,
+        },
+        deterministic: true,
+        ..Default::default()
+      };
+      progressive_route_with_config(config).unwrap();
+      content_hash(&fs::read(output).unwrap())
+    };
+
+    assert_eq!(
+      render("runarium_deterministic_a.mp4"),
+      render("runarium_deterministic_b.mp4")
+    );
   }
 
-  video.release()?;
-  println!(
-    "✅ Video created: {} with {} points",
-    config.file_config.output_file,
-    pixel_points.len()
-  );
-  Ok(())
+  #[test]
+  fn test_unopenable_codec_falls_back() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("source");
+    let (fit, background) = (
+      source.join("example.fit"),
+      source.join("example.jpg"),
+    );
+    if !fit.exists() || !background.exists() {
+      return;
+    }
+
+    // No codec opens for an unknown container, as on a build without
+    // FFmpeg
+    let render = |name: &str, fallback| {
+      let config = RouteVideoConfig {
+        file_config: FileConfig::new(
+          fit.to_string_lossy().into_owned(),
+          background.to_string_lossy().into_owned(),
+          env::temp_dir().join(name).to_string_lossy().into_owned(),
+        ),
+        output_size: Some((160, 160)),
+        duration_secs: 1.0,
+        progression: ProgressionMode::PerDistance {
+          meters_per_frame: 500.0,
+        },
+        fallback,
+        ..Default::default()
+      };
+      progressive_route_with_config(config)
+    };
+
+    assert!(render(
+      "runarium_fallback.bogus",
+      VideoFallback::Error
+    )
+    .is_err());
+
+    let output = render(
+      "runarium_fallback.bogus",
+      VideoFallback::FrameSequence,
+    )
+    .unwrap();
+    let frames = env::temp_dir().join("runarium_fallback_frames");
+    assert_eq!(
+      output.output_file,
+      frames.to_string_lossy()
+    );
+    assert!(frames.join("frame_000001.png").exists());
+    assert!(matches!(
+      output.warnings.iter().next(),
+      Some(Warning::VideoFallback { .. })
+    ));
+    fs::remove_dir_all(frames).unwrap();
+
+    let output = render(
+      "runarium_fallback.bogus",
+      VideoFallback::Gif,
+    )
+    .unwrap();
+    let gif = fs::read(&output.output_file).unwrap();
+    assert!(output.output_file.ends_with("runarium_fallback.gif"));
+    assert!(gif.starts_with(b"GIF89a"));
+    fs::remove_file(output.output_file).unwrap();
+  }
 }
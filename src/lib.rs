@@ -1,10 +1,21 @@
+pub mod analysis;
 pub mod configs;
+pub mod error;
+pub mod estimate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "render")]
 pub mod generators;
+pub mod provenance;
+#[cfg(feature = "axum")]
+pub mod server_support;
 pub mod types;
 pub mod utils;
 
 // Re-export commonly used items at crate root
-pub use configs::{config, image_config, video_config};
+pub use configs::{config, image_config, patch, social, video_config};
+pub use error::RunariumError;
+#[cfg(feature = "render")]
 pub use generators::{
   route_image::image_route_with_config,
   route_video::progressive_route_with_config,
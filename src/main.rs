@@ -4,19 +4,28 @@ use anyhow::Result;
 use runarium::{
   configs::{
     image_config::RouteImageConfig,
+    patch::RouteVideoConfigPatch,
+    social::SocialPreset,
     video_config::{
-      Color, FileConfig, Font, LapDataConfig, PaceDistConfig, RouteColor,
-      RouteScale, RouteVideoConfig,
+      Color, FileConfig, Font, LapDataConfig, OutputSink, PaceDistConfig,
+      RouteColor, RouteScale, RouteVideoConfig,
     },
   },
+  estimate::{estimate, ActivityInfo, Estimate},
   generators::{
     route_image::{image_route_with_config, route_image},
     route_video::{progressive_route, progressive_route_with_config},
   },
-  utils::performance::measure,
+  utils::{performance::measure, runtime::runtime_report},
 };
 
 fn main() -> Result<()> {
+  // `doctor` reports what this OpenCV build can encode
+  if std::env::args().nth(1).as_deref() == Some("doctor") {
+    println!("{}", runtime_report()?);
+    return Ok(());
+  }
+
   // Configure route scale and position
   let route_scale = RouteScale::new(
     0.2, // scale: 20% of map size
@@ -62,7 +71,7 @@ fn main() -> Result<()> {
   );
 
   // Combine all video configurations
-  let video_config = RouteVideoConfig::new(
+  let mut video_config = RouteVideoConfig::new(
     route_scale,
     colors,
     pace_dist,
@@ -73,6 +82,53 @@ fn main() -> Result<()> {
     true, // show_lap_data
   );
 
+  // Apply `--preset name`, then `--set key=value` overrides on top of it,
+  // e.g. `--preset youtube_short --set lap_data.position.1=0.2`
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let preset = match flag_values(args.iter().cloned(), "--preset")?.pop() {
+    Some(name) => Some(name.parse::<SocialPreset>()?),
+    None => None,
+  };
+  if let Some(preset) = preset {
+    preset.patch().apply(&mut video_config);
+  }
+
+  let overrides = flag_values(args.iter().cloned(), "--set")?;
+  RouteVideoConfigPatch::from_set_args(overrides)?.apply(&mut video_config);
+
+  // `--post-cmd "aws s3 cp {output} s3://runs/{id}.mp4"` runs once the
+  // video is written
+  let post_cmd = flag_values(args.iter().cloned(), "--post-cmd")?.pop();
+  if let Some(command) = post_cmd {
+    video_config.on_complete = Some(OutputSink::parse_command(&command)?);
+  }
+
+  // `--export-geojson outputs/run.geojson` also writes the route as
+  // rendered
+  let geojson = flag_values(args.iter().cloned(), "--export-geojson")?.pop();
+  if let Some(path) = geojson {
+    video_config.export_geojson = Some(path);
+  }
+  // `--export-csv outputs/run.csv` writes the records, and the laps next to
+  // them as `run_laps.csv`
+  let csv = flag_values(args.iter().cloned(), "--export-csv")?.pop();
+  if let Some(path) = csv {
+    video_config.export_csv = Some(path);
+  }
+
+  if let Some(preset) = preset {
+    for violation in preset.validate(&video_config) {
+      eprintln!("⚠️ {:?}: {}", preset, violation);
+    }
+  }
+
+  // `--dry-run` prints what the render would take, without rendering
+  if args.iter().any(|arg| arg == "--dry-run") {
+    let info = ActivityInfo::read(&video_config)?;
+    print_estimate(&estimate(&video_config, &info));
+    return Ok(());
+  }
+
   // Configure image file paths
   let image_file_config = FileConfig::new(
     "source/example.fit".to_string(),
@@ -102,3 +158,49 @@ fn main() -> Result<()> {
 
   Ok(())
 }
+
+/// Prints an estimate, one line per value
+fn print_estimate(estimate: &Estimate) {
+  let render = estimate.predicted_render_seconds;
+  let bytes = estimate.predicted_size_bytes;
+  let mb = |bytes: u64| bytes as f64 / 1_000_000.0;
+  println!(
+    "Frames:      {} ({}x{})",
+    estimate.frames, estimate.size.0, estimate.size.1
+  );
+  println!(
+    "Duration:    {:.1} s",
+    estimate.video_duration_s
+  );
+  println!(
+    "Render time: {:.0}-{:.0} s",
+    render.low, render.high
+  );
+  println!(
+    "File size:   {:.1}-{:.1} MB",
+    mb(bytes.low),
+    mb(bytes.high)
+  );
+}
+
+/// Collects the values of `<flag> value` / `<flag>=value` arguments
+fn flag_values(
+  mut args: impl Iterator<Item = String>,
+  flag: &str,
+) -> Result<Vec<String>> {
+  let mut values = Vec::new();
+  let prefix = format!("{}=", flag);
+
+  while let Some(arg) = args.next() {
+    if arg == flag {
+      let value = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} requires a value", flag))?;
+      values.push(value);
+    } else if let Some(value) = arg.strip_prefix(&prefix) {
+      values.push(value.to_string());
+    }
+  }
+
+  Ok(values)
+}
@@ -0,0 +1,256 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// PNG text keyword the config is stored under
+pub const CONFIG_KEYWORD: &str = "runarium:config";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The config an output was rendered with and the crate version that
+/// rendered it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigRecord<T> {
+  pub version: String,
+  pub config: T,
+}
+
+impl<T: Serialize> ConfigRecord<T> {
+  /// Record of `config` for this crate version
+  pub fn new(config: T) -> Self {
+    Self {
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      config,
+    }
+  }
+
+  /// Pretty JSON with every non-ASCII character escaped, so it fits a
+  /// Latin-1 PNG tEXt chunk unchanged
+  pub fn to_json(&self) -> Result<String> {
+    Ok(ascii_escape(
+      &serde_json::to_string_pretty(self)?,
+    ))
+  }
+}
+
+/// `<output>.config.json` next to an output file
+pub fn sidecar_path(output_file: &str) -> String {
+  format!("{}.config.json", output_file)
+}
+
+/// Writes the sidecar of `output_file` with `config`, and embeds the same
+/// JSON in the output itself when it is a PNG
+pub fn embed_config<T: Serialize>(output_file: &str, config: &T) -> Result<()> {
+  let json = ConfigRecord::new(config).to_json()?;
+  fs::write(sidecar_path(output_file), &json)?;
+
+  let is_png = Path::new(output_file)
+    .extension()
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+  if is_png {
+    let png = fs::read(output_file)?;
+    fs::write(
+      output_file,
+      with_text_chunk(&png, CONFIG_KEYWORD, &json)?,
+    )?;
+  }
+  Ok(())
+}
+
+/// Config record embedded in a PNG, or in the sidecar of any other file
+pub fn read_config<T: DeserializeOwned>(path: &str) -> Result<ConfigRecord<T>> {
+  let bytes = fs::read(path)?;
+  let json = if bytes.starts_with(PNG_SIGNATURE) {
+    text_chunk(&bytes, CONFIG_KEYWORD)
+      .ok_or_else(|| anyhow!("{} has no embedded config", path))?
+  } else {
+    fs::read_to_string(sidecar_path(path))
+      .map_err(|e| anyhow!("{} has no config sidecar: {}", path, e))?
+  };
+  Ok(serde_json::from_str(&json)?)
+}
+
+/// `png` with a tEXt chunk of `keyword` and `text` before its IEND chunk;
+/// an earlier chunk of the same keyword is replaced
+pub fn with_text_chunk(
+  png: &[u8],
+  keyword: &str,
+  text: &str,
+) -> Result<Vec<u8>> {
+  if !text.is_ascii() || !keyword.is_ascii() || keyword.contains('\0') {
+    bail!("PNG text must be ASCII without NUL in the keyword");
+  }
+  let chunks = chunks(png)?;
+  let mut out = png[..PNG_SIGNATURE.len()].to_vec();
+  for chunk in &chunks {
+    if chunk.kind == *b"IEND" {
+      let mut data = keyword.as_bytes().to_vec();
+      data.push(0);
+      data.extend(text.as_bytes());
+      write_chunk(&mut out, b"tEXt", &data);
+    }
+    if chunk.kind != *b"tEXt" || text_of(chunk.data, keyword).is_none() {
+      out.extend(chunk.raw);
+    }
+  }
+  Ok(out)
+}
+
+/// Text of the first tEXt chunk of `keyword` in `png`
+pub fn text_chunk(png: &[u8], keyword: &str) -> Option<String> {
+  chunks(png)
+    .ok()?
+    .iter()
+    .filter(|chunk| chunk.kind == *b"tEXt")
+    .find_map(|chunk| text_of(chunk.data, keyword))
+}
+
+/// One chunk of a PNG file
+struct Chunk<'a> {
+  kind: [u8; 4],
+  data: &'a [u8],
+  /// Length, type, data and CRC as stored
+  raw: &'a [u8],
+}
+
+/// Chunks of `png` up to and including IEND
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>> {
+  if !png.starts_with(PNG_SIGNATURE) {
+    bail!("Not a PNG file");
+  }
+  let mut chunks = Vec::new();
+  let mut at = PNG_SIGNATURE.len();
+  while at + 12 <= png.len() {
+    let length =
+      u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]])
+        as usize;
+    let end = at + 12 + length;
+    if end > png.len() {
+      break;
+    }
+    let kind = [png[at + 4], png[at + 5], png[at + 6], png[at + 7]];
+    chunks.push(Chunk {
+      kind,
+      data: &png[at + 8..at + 8 + length],
+      raw: &png[at..end],
+    });
+    if kind == *b"IEND" {
+      return Ok(chunks);
+    }
+    at = end;
+  }
+  bail!("PNG file is truncated")
+}
+
+/// Text of a tEXt chunk's data when its keyword is `keyword`
+fn text_of(data: &[u8], keyword: &str) -> Option<String> {
+  let split = data.iter().position(|&b| b == 0)?;
+  if &data[..split] != keyword.as_bytes() {
+    return None;
+  }
+  // tEXt is Latin-1, whose code points are the first 256 of Unicode
+  Some(data[split + 1..].iter().map(|&b| b as char).collect())
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  out.extend((data.len() as u32).to_be_bytes());
+  out.extend(kind);
+  out.extend(data);
+  let crc = crc32(kind.iter().chain(data));
+  out.extend(crc.to_be_bytes());
+}
+
+/// CRC-32 as PNG chunks use it (ISO 3309, reflected 0xEDB88320)
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+  let mut crc = !0u32;
+  for &byte in bytes {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+/// `json` with every non-ASCII character written as a `\u` escape
+fn ascii_escape(json: &str) -> String {
+  let mut out = String::with_capacity(json.len());
+  for c in json.chars() {
+    if c.is_ascii() {
+      out.push(c);
+    } else {
+      for unit in c.encode_utf16(&mut [0; 2]) {
+        out.push_str(&format!("\\u{:04x}", unit));
+      }
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Smallest valid PNG: one black pixel
+  fn tiny_png() -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(
+      &mut png,
+      b"IHDR",
+      &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+    );
+    write_chunk(
+      &mut png,
+      b"IDAT",
+      &[0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01],
+    );
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+  }
+
+  #[test]
+  fn test_crc32() {
+    // Stored CRC of every IEND chunk
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+  }
+
+  #[test]
+  fn test_text_chunk_round_trip() {
+    let png = tiny_png();
+    assert_eq!(text_chunk(&png, CONFIG_KEYWORD), None);
+
+    let tagged = with_text_chunk(&png, CONFIG_KEYWORD, "{\"a\": 1}").unwrap();
+    assert_eq!(
+      text_chunk(&tagged, CONFIG_KEYWORD).as_deref(),
+      Some("{\"a\": 1}")
+    );
+    assert!(tagged.ends_with(&png[png.len() - 12..]));
+
+    // A second embed replaces the first
+    let retagged = with_text_chunk(&tagged, CONFIG_KEYWORD, "{}").unwrap();
+    assert_eq!(
+      retagged.len(),
+      png.len() + 12 + CONFIG_KEYWORD.len() + 3
+    );
+    assert_eq!(
+      text_chunk(&retagged, CONFIG_KEYWORD).as_deref(),
+      Some("{}")
+    );
+
+    assert!(with_text_chunk(b"GIF89a", CONFIG_KEYWORD, "{}").is_err());
+    assert!(with_text_chunk(&png, CONFIG_KEYWORD, "©").is_err());
+  }
+
+  #[test]
+  fn test_record_json_is_ascii() {
+    let record = ConfigRecord::new("© OpenStreetMap 🏃".to_string());
+    let json = record.to_json().unwrap();
+    assert!(json.is_ascii());
+    let back: ConfigRecord<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.config, "© OpenStreetMap 🏃");
+    assert_eq!(back.version, env!("CARGO_PKG_VERSION"));
+  }
+}
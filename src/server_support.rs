@@ -0,0 +1,387 @@
+use std::fmt;
+
+use axum::{body::Bytes, extract::Multipart, http::StatusCode};
+
+use crate::configs::{RouteVideoConfig, RouteVideoConfigPatch};
+
+/// Bytes 8-11 of every FIT file header
+const FIT_SIGNATURE: &[u8] = b".FIT";
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Most bytes accepted in each field of a generate request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLimits {
+  pub fit_file: usize,
+  pub background: usize,
+  pub config: usize,
+}
+
+impl Default for FieldLimits {
+  /// Creates default limits (16 MB FIT file, 64 MB background, 64 KB
+  /// config)
+  fn default() -> Self {
+    Self {
+      fit_file: 16 * 1024 * 1024,
+      background: 64 * 1024 * 1024,
+      config: 64 * 1024,
+    }
+  }
+}
+
+/// Fields of a multipart generate request: `fit_file`, `background` and
+/// an optional JSON `config` patch. Other fields are skipped unread.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationRequest {
+  pub fit_bytes: Bytes,
+  /// JPEG or PNG background (None = not uploaded)
+  pub background_bytes: Option<Bytes>,
+  pub config_patch: Option<RouteVideoConfigPatch>,
+}
+
+impl GenerationRequest {
+  /// The background, which the render endpoints require
+  pub fn background(&self) -> Result<Bytes, RequestError> {
+    self
+      .background_bytes
+      .clone()
+      .ok_or(RequestError::MissingField("background"))
+  }
+
+  /// `base` with the request's config patch applied
+  pub fn config(&self, mut base: RouteVideoConfig) -> RouteVideoConfig {
+    if let Some(patch) = &self.config_patch {
+      patch.apply(&mut base);
+    }
+    base
+  }
+}
+
+/// Why a generate request was rejected; every kind is the client's fault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestError {
+  /// The body is not valid multipart/form-data
+  Malformed(String),
+  /// A required field was not uploaded
+  MissingField(&'static str),
+  /// A field is larger than its limit; reading stopped at the limit
+  FieldTooLarge { field: &'static str, limit: usize },
+  /// A file does not start with the signature of its format
+  InvalidFile {
+    field: &'static str,
+    expected: &'static str,
+  },
+  /// The config is not a valid JSON config patch
+  InvalidConfig(String),
+}
+
+impl RequestError {
+  /// HTTP status to answer with
+  pub fn status(&self) -> StatusCode {
+    match self {
+      RequestError::FieldTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+      RequestError::InvalidFile { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      RequestError::Malformed(_)
+      | RequestError::MissingField(_)
+      | RequestError::InvalidConfig(_) => StatusCode::BAD_REQUEST,
+    }
+  }
+}
+
+impl fmt::Display for RequestError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RequestError::Malformed(reason) => {
+        write!(
+          f,
+          "Malformed multipart body: {}",
+          reason
+        )
+      }
+      RequestError::MissingField(field) => {
+        write!(f, "Missing {} in request", field)
+      }
+      RequestError::FieldTooLarge { field, limit } => {
+        write!(
+          f,
+          "{} is larger than the limit of {} bytes",
+          field, limit
+        )
+      }
+      RequestError::InvalidFile { field, expected } => {
+        write!(f, "{} is not {}", field, expected)
+      }
+      RequestError::InvalidConfig(reason) => {
+        write!(f, "Invalid config: {}", reason)
+      }
+    }
+  }
+}
+
+impl std::error::Error for RequestError {}
+
+/// Reads a generate request within the default `FieldLimits`
+pub async fn extract_generation_request(
+  multipart: Multipart,
+) -> Result<GenerationRequest, RequestError> {
+  extract_generation_request_with_limits(multipart, &FieldLimits::default())
+    .await
+}
+
+/// Reads a generate request, rejecting a field as soon as it passes its
+/// limit in `limits` and files whose first bytes are not a FIT file, or a
+/// JPEG or PNG background. The FIT file is required.
+pub async fn extract_generation_request_with_limits(
+  mut multipart: Multipart,
+  limits: &FieldLimits,
+) -> Result<GenerationRequest, RequestError> {
+  let mut fit_bytes = None;
+  let mut request = GenerationRequest::default();
+  while let Some(mut field) = next_field(&mut multipart).await? {
+    let (name, limit) = match field.name() {
+      Some("fit_file") => ("fit_file", limits.fit_file),
+      Some("background") => ("background", limits.background),
+      Some("config") => ("config", limits.config),
+      _ => continue,
+    };
+
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(malformed)? {
+      if data.len() + chunk.len() > limit {
+        return Err(RequestError::FieldTooLarge { field: name, limit });
+      }
+      data.extend_from_slice(&chunk);
+    }
+
+    match name {
+      "fit_file" => {
+        check_signature(&data, name, "a FIT file", is_fit)?;
+        fit_bytes = Some(Bytes::from(data));
+      }
+      "background" => {
+        check_signature(
+          &data,
+          name,
+          "a JPEG or PNG image",
+          is_image,
+        )?;
+        request.background_bytes = Some(Bytes::from(data));
+      }
+      _ => request.config_patch = Some(parse_patch(&data)?),
+    }
+  }
+
+  request.fit_bytes =
+    fit_bytes.ok_or(RequestError::MissingField("fit_file"))?;
+  Ok(request)
+}
+
+async fn next_field(
+  multipart: &mut Multipart,
+) -> Result<Option<axum::extract::multipart::Field<'_>>, RequestError> {
+  multipart.next_field().await.map_err(malformed)
+}
+
+fn malformed(err: axum::extract::multipart::MultipartError) -> RequestError {
+  RequestError::Malformed(err.body_text())
+}
+
+fn check_signature(
+  data: &[u8],
+  field: &'static str,
+  expected: &'static str,
+  matches: fn(&[u8]) -> bool,
+) -> Result<(), RequestError> {
+  if matches(data) {
+    Ok(())
+  } else {
+    Err(RequestError::InvalidFile { field, expected })
+  }
+}
+
+/// Whether `data` starts with a FIT file header
+fn is_fit(data: &[u8]) -> bool {
+  data.get(8..12) == Some(FIT_SIGNATURE)
+}
+
+fn is_image(data: &[u8]) -> bool {
+  data.starts_with(JPEG_SIGNATURE) || data.starts_with(PNG_SIGNATURE)
+}
+
+fn parse_patch(data: &[u8]) -> Result<RouteVideoConfigPatch, RequestError> {
+  let json = std::str::from_utf8(data)
+    .map_err(|e| RequestError::InvalidConfig(e.to_string()))?;
+  serde_json::from_str(json)
+    .map_err(|e| RequestError::InvalidConfig(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{
+    body::Body,
+    extract::FromRequest,
+    http::{header, Request},
+  };
+
+  use super::*;
+
+  const FIT: &[u8] = b"\x0e\x10\x00\x00\x00\x00\x00\x00.FIT\x00\x00";
+  const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+
+  /// Multipart extractor over `body`, whose parts are separated by `XYZ`
+  async fn multipart(body: Vec<u8>) -> Multipart {
+    let request = Request::builder()
+      .header(
+        header::CONTENT_TYPE,
+        "multipart/form-data; boundary=XYZ",
+      )
+      .body(Body::from(body))
+      .unwrap();
+    Multipart::from_request(request, &()).await.unwrap()
+  }
+
+  /// Multipart body of `fields`
+  fn form(fields: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, data) in fields {
+      body.extend(
+        format!(
+          "--XYZ\r\nContent-Disposition: form-data; name=\"{}\"; \
+         filename=\"{}\"\r\n\r\n",
+          name, name
+        )
+        .bytes(),
+      );
+      body.extend_from_slice(data);
+      body.extend(b"\r\n");
+    }
+    body.extend(b"--XYZ--\r\n");
+    body
+  }
+
+  async fn extract(body: Vec<u8>) -> Result<GenerationRequest, RequestError> {
+    extract_generation_request(multipart(body).await).await
+  }
+
+  #[tokio::test]
+  async fn test_extract_generation_request() {
+    let request = extract(form(&[
+      ("fit_file", FIT),
+      ("background", PNG),
+      ("notes", b"skipped"),
+      ("config", br#"{"duration_secs": 12.0}"#),
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(request.fit_bytes.as_ref(), FIT);
+    assert_eq!(
+      request.background().unwrap().as_ref(),
+      PNG
+    );
+    let config = request.config(RouteVideoConfig::default());
+    assert_eq!(config.duration_secs, 12.0);
+
+    // The background is only required by the render endpoints
+    let request = extract(form(&[("fit_file", FIT)])).await.unwrap();
+    assert!(request.config_patch.is_none());
+    assert_eq!(
+      request.background(),
+      Err(RequestError::MissingField("background"))
+    );
+    assert_eq!(
+      extract(form(&[("background", PNG)])).await.unwrap_err(),
+      RequestError::MissingField("fit_file")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_rejects_files_by_signature() {
+    let err = extract(form(&[(
+      "fit_file",
+      b"<html>not a fit</html>",
+    )]))
+    .await
+    .unwrap_err();
+    assert_eq!(
+      err,
+      RequestError::InvalidFile {
+        field: "fit_file",
+        expected: "a FIT file",
+      }
+    );
+    assert_eq!(
+      err.status(),
+      StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+
+    let err = extract(form(&[
+      ("fit_file", FIT),
+      ("background", b"GIF89a"),
+    ]))
+    .await
+    .unwrap_err();
+    assert!(matches!(
+      err,
+      RequestError::InvalidFile {
+        field: "background",
+        ..
+      }
+    ));
+
+    let err = extract(form(&[
+      ("fit_file", FIT),
+      ("config", br#"{"duration": 12}"#),
+    ]))
+    .await
+    .unwrap_err();
+    assert!(err.to_string().starts_with("Invalid config: unknown field"));
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn test_field_limits() {
+    let limits = FieldLimits {
+      fit_file: FIT.len(),
+      background: 8,
+      ..Default::default()
+    };
+    let body = form(&[("fit_file", FIT), ("background", PNG)]);
+    let err =
+      extract_generation_request_with_limits(multipart(body).await, &limits)
+        .await
+        .unwrap_err();
+    assert_eq!(
+      err,
+      RequestError::FieldTooLarge {
+        field: "background",
+        limit: 8,
+      }
+    );
+    assert_eq!(
+      err.status(),
+      StatusCode::PAYLOAD_TOO_LARGE
+    );
+  }
+
+  #[tokio::test]
+  async fn test_malformed_bodies() {
+    // Cut off before the closing boundary
+    let mut truncated = form(&[("fit_file", FIT)]);
+    truncated.truncate(truncated.len() - 12);
+    // A part without headers
+    let headless = b"--XYZ\r\n\r\ndata\r\n--XYZ--\r\n".to_vec();
+
+    for body in [truncated, headless, b"garbage".to_vec()] {
+      let err = extract(body).await.unwrap_err();
+      assert!(
+        matches!(
+          err,
+          RequestError::Malformed(_) | RequestError::MissingField(_)
+        ),
+        "{:?}",
+        err
+      );
+      assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+  }
+}
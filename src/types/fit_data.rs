@@ -1,13 +1,94 @@
-#[derive(Debug)]
+use std::{collections::HashMap, ops::Range};
+
+use crate::types::warning::Warnings;
+
+#[derive(Debug, Clone)]
 pub struct RouteData {
   pub paces: Vec<String>,
   pub gps_points: Vec<(f64, f64)>,
   pub distances: Vec<f64>,
+  pub heart_rates: Vec<Option<u8>>,
+  pub speeds: Vec<f64>,
+  /// Steps per minute, or rpm when cycling
+  pub cadences: Vec<Option<u16>>,
+  /// Power in watts
+  pub powers: Vec<Option<u16>>,
+  /// Altitude in meters
+  pub altitudes: Vec<Option<f64>>,
+  /// Unix timestamps in seconds
+  pub timestamps: Vec<Option<i64>>,
+  /// Records after which the line breaks, at a timer pause or a gap in
+  /// the records, in increasing order
+  pub segment_breaks: Vec<usize>,
+  /// Values of the configured extra fields, keyed by label
+  pub extra: HashMap<String, Vec<Option<f64>>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct LapData {
-  pub avg_heart_rate: Vec<u8>,
+  /// Average heart rate (None = not recorded)
+  pub avg_heart_rate: Vec<Option<u8>>,
+  /// Average power in watts (None = not recorded)
+  pub avg_power: Vec<Option<u16>>,
   pub enhanced_avg_speed: Vec<String>,
-  pub avg_step_length: Vec<f64>,
+  /// Average stride in decimeters (None = not recorded, e.g. a bike lap)
+  pub avg_step_length: Vec<Option<f64>>,
+  pub total_distance: Vec<f64>,
+  pub avg_speed: Vec<f64>,
+  pub avg_cadence: Vec<u8>,
+  /// Lap duration in seconds, pauses included
+  pub total_elapsed_time: Vec<f64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SessionSummary {
+  pub total_distance: Option<f64>,
+  pub total_timer_time: Option<f64>,
+  pub total_ascent: Option<u16>,
+  /// Energy burned (kcal)
+  pub total_calories: Option<u16>,
+  /// Average heart rate over the whole activity
+  pub avg_heart_rate: Option<u8>,
+  pub start_time: Option<i64>,
+  pub sport: Option<String>,
+  /// Moving and elapsed time from the records (filled by
+  /// `activity_reader`)
+  pub times: Option<ActivityTimes>,
+}
+
+/// Moving and elapsed time of an activity
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ActivityTimes {
+  /// Seconds from the first to the last record
+  pub elapsed_time: f64,
+  /// Elapsed time minus the detected pauses
+  pub moving_time: f64,
+  /// Moving seconds per kilometer (None without distance)
+  pub average_moving_pace: Option<f64>,
+}
+
+/// Records and laps of one session of a multisport activity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRange {
+  /// Indices of the session's GPS records in `RouteData`
+  pub records: Range<usize>,
+  /// Indices of the session's record distances in `RouteData::distances`
+  pub distances: Range<usize>,
+  /// Indices of the session's laps in `LapData`
+  pub laps: Range<usize>,
+  /// Sport the session's Session message names
+  pub sport: Option<String>,
+}
+
+/// Everything read from one FIT file
+#[derive(Debug, Clone)]
+pub struct Activity {
+  pub route: RouteData,
+  pub lap: LapData,
+  pub session: SessionSummary,
+  /// Problems found in the records and laps while parsing
+  pub warnings: Warnings,
+  /// Sessions of a multisport FIT file, in order (empty for a single
+  /// session); `session` summarizes the first
+  pub sessions: Vec<SessionRange>,
 }
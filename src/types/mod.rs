@@ -1,2 +1,4 @@
 pub mod drawer_data;
 pub mod fit_data;
+pub mod output;
+pub mod warning;
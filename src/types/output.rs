@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+use crate::{
+  types::warning::Warnings,
+  utils::{palette::StylePalette, projection::ProjectionInfo},
+};
+
+/// Result of a successful render
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderOutput {
+  /// Path of the written file
+  pub output_file: String,
+  /// How GPS coordinates map onto the output pixels
+  pub projection: ProjectionInfo,
+  /// What the render worked around (missing data, skipped overlays)
+  pub warnings: Warnings,
+  /// Video and activity time of the frames picked by the config's
+  /// `time_index` (empty for images)
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub time_index: Vec<TimeIndexEntry>,
+  /// Colors the config's `seeded_style` derived, to pin a good one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub palette: Option<StylePalette>,
+}
+
+/// Where the activity is at one video frame
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeIndexEntry {
+  /// Output frame, lap pause holds and end card included
+  pub frame: usize,
+  /// Seconds from the start of the video
+  pub video_time_s: f64,
+  /// Seconds since the first record at the pen (None without timestamps)
+  pub activity_time_s: Option<f64>,
+  /// Distance at the pen in meters (None without distances)
+  pub distance_m: Option<f64>,
+  /// Lap (0-based) that ends on this frame
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub lap: Option<usize>,
+}
+
+/// Result of a successful `sequence_video` render
+#[derive(Debug, Clone, Serialize)]
+pub struct SequenceOutput {
+  /// Path of the written video
+  pub output_file: String,
+  /// Activities in the reel (skipped entries excluded)
+  pub activities: usize,
+  /// Length of the reel in seconds
+  pub duration_secs: f64,
+  /// Skipped entries, then what each activity's render worked around
+  pub warnings: Warnings,
+}
+
+/// Result of a render from in-memory inputs
+#[derive(Debug, Clone)]
+pub struct RenderedBytes {
+  /// Contents of the written video or image
+  pub bytes: Vec<u8>,
+  /// Render details; `output_file` named a file in the removed workspace
+  pub output: RenderOutput,
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::utils::palette::MIN_CONTRAST;
+
+/// Something a render quietly worked around: missing data or a setting
+/// that could not be honored. Unlike errors, warnings never stop a render.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Warning {
+  /// The FIT file has no laps with an average speed
+  EmptyLapData,
+  /// A record series does not line up with the GPS points
+  SeriesLengthMismatch {
+    series: &'static str,
+    expected: usize,
+    actual: usize,
+  },
+  /// The lap panel was requested but there are no laps to show
+  LapPanelSkipped,
+  /// The legend was requested but the route has no gradient to explain
+  LegendSkipped,
+  /// The date stamp was requested but the activity has no start time
+  DateStampSkipped,
+  /// The route fade was requested but cannot be drawn
+  RouteFadeSkipped,
+  /// Direction arrows were requested but the route has no distance data
+  DirectionArrowsSkipped,
+  /// The cadence chart was requested but the records have no cadence
+  /// (or no distance)
+  CadenceChartSkipped,
+  /// Detected loops were requested but the route never closes a loop
+  DetectedLoopsSkipped,
+  /// The steepest climb was requested but no stretch of the route long
+  /// enough climbs with altitude data throughout
+  SteepestClimbSkipped,
+  /// Too few planned frames for the duration; the video plays at 1 fps
+  /// and runs longer than requested
+  FrameRateRaised { planned_fps: f64 },
+  /// Real-time timing was requested but the records have too few
+  /// timestamps; the video is fitted to its duration instead
+  RealTimeSkipped,
+  /// An activity of a sequence could not be read and was left out
+  SequenceEntrySkipped { fit_file: String, reason: String },
+  /// The video codec could not be opened, so the frames were written to
+  /// `output_file` by the configured fallback instead
+  VideoFallback { codec: String, output_file: String },
+  /// A configured color barely stands out from the background under the
+  /// route: its contrast ratio is below `MIN_CONTRAST`
+  LowContrast {
+    element: &'static str,
+    contrast: f64,
+  },
+  /// Route segments implying a speed above the glitch style's limit were
+  /// drawn as GPS glitches
+  GlitchSegments { count: usize, max_speed_mps: f64 },
+  /// The cumulative distance dropped `resets` times; the `points` records
+  /// after the drops were shifted to keep it increasing
+  DistanceRepaired { resets: usize, points: usize },
+  /// `count` GPS positions implying a speed above `max_speed_mps` were
+  /// replaced by positions between their neighbors
+  SpikesReplaced { count: usize, max_speed_mps: f64 },
+}
+
+impl fmt::Display for Warning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Warning::EmptyLapData => f.write_str("activity has no lap data"),
+      Warning::SeriesLengthMismatch {
+        series,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "{} has {} values for {} GPS points",
+        series, actual, expected
+      ),
+      Warning::LapPanelSkipped => {
+        f.write_str("lap panel skipped: activity has no laps")
+      }
+      Warning::LegendSkipped => {
+        f.write_str("legend skipped: route has no gradient coloring data")
+      }
+      Warning::DateStampSkipped => {
+        f.write_str("date stamp skipped: activity has no start time")
+      }
+      Warning::RouteFadeSkipped => f.write_str(
+        "route fade skipped: needs a drawn route line and distances",
+      ),
+      Warning::DirectionArrowsSkipped => {
+        f.write_str("direction arrows skipped: route has no distance data")
+      }
+      Warning::CadenceChartSkipped => {
+        f.write_str("cadence chart skipped: records have no cadence data")
+      }
+      Warning::DetectedLoopsSkipped => {
+        f.write_str("loop list skipped: route never returns to a loop start")
+      }
+      Warning::SteepestClimbSkipped => f.write_str(
+        "steepest climb skipped: no climb long enough with altitude data",
+      ),
+      Warning::FrameRateRaised { planned_fps } => write!(
+        f,
+        "frame rate raised from {:.2} to 1 fps; video runs longer",
+        planned_fps
+      ),
+      Warning::RealTimeSkipped => {
+        f.write_str("real-time timing skipped: records have no timestamps")
+      }
+      Warning::SequenceEntrySkipped { fit_file, reason } => {
+        write!(f, "{} skipped: {}", fit_file, reason)
+      }
+      Warning::VideoFallback { codec, output_file } => write!(
+        f,
+        "NO VIDEO: codec {} is unavailable in this OpenCV build; frames \
+         were written to {} instead",
+        codec, output_file
+      ),
+      Warning::LowContrast { element, contrast } => write!(
+        f,
+        "low contrast: {} is {:.1}:1 against the background, below {}:1",
+        element, contrast, MIN_CONTRAST
+      ),
+      Warning::GlitchSegments {
+        count,
+        max_speed_mps,
+      } => write!(
+        f,
+        "{} route segments faster than {} m/s drawn as GPS glitches",
+        count, max_speed_mps
+      ),
+      Warning::DistanceRepaired { resets, points } => write!(
+        f,
+        "distance went backwards {} times; {} records shifted to keep it \
+         increasing",
+        resets, points
+      ),
+      Warning::SpikesReplaced {
+        count,
+        max_speed_mps,
+      } => write!(
+        f,
+        "{} GPS positions faster than {} m/s replaced by their neighbors",
+        count, max_speed_mps
+      ),
+    }
+  }
+}
+
+/// Warnings collected while parsing and rendering, in the order they
+/// were raised
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Warnings(pub Vec<Warning>);
+
+impl Warnings {
+  pub fn push(&mut self, warning: Warning) {
+    self.0.push(warning);
+  }
+
+  /// Appends `warning` when `condition` holds
+  pub fn push_if(&mut self, condition: bool, warning: Warning) {
+    if condition {
+      self.push(warning);
+    }
+  }
+
+  pub fn extend(&mut self, other: Warnings) {
+    self.0.extend(other.0);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+    self.0.iter()
+  }
+
+  pub fn contains(&self, warning: &Warning) -> bool {
+    self.0.contains(warning)
+  }
+}
+
+/// One warning per line
+impl fmt::Display for Warnings {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, warning) in self.0.iter().enumerate() {
+      if i > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{}", warning)?;
+    }
+    Ok(())
+  }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+  type Item = &'a Warning;
+  type IntoIter = std::slice::Iter<'a, Warning>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.iter()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_warnings_display_and_json() {
+    let mut warnings = Warnings::default();
+    warnings.push(Warning::EmptyLapData);
+    warnings.push_if(false, Warning::LegendSkipped);
+    warnings.push(Warning::SeriesLengthMismatch {
+      series: "distances",
+      expected: 10,
+      actual: 12,
+    });
+
+    assert_eq!(
+      warnings.to_string(),
+      "activity has no lap data\n\
+       distances has 12 values for 10 GPS points"
+    );
+    assert_eq!(
+      serde_json::to_value(&warnings).unwrap(),
+      serde_json::json!([
+        {"kind": "empty_lap_data"},
+        {
+          "kind": "series_length_mismatch",
+          "series": "distances",
+          "expected": 10,
+          "actual": 12
+        }
+      ])
+    );
+  }
+}
@@ -0,0 +1,609 @@
+use std::mem;
+
+use anyhow::{bail, Result};
+use opencv::{core, imgcodecs, imgproc, prelude::*};
+
+use crate::configs::{
+  BackgroundFx, BackgroundLayer, BackgroundScaling, GradientDirection, Interp,
+  LayerBlend, LayerSource, Limits,
+};
+
+/// CLAHE clip limit of the mapify filter
+const MAPIFY_CLIP_LIMIT: f64 = 2.0;
+/// CLAHE tiles per side of the mapify filter
+const MAPIFY_TILES: i32 = 8;
+/// Share of the color the mapify filter takes out
+const MAPIFY_DESATURATION: f64 = 0.25;
+/// Share of the lightness range the mapify filter lifts black to
+const MAPIFY_SHADOW_LIFT: f64 = 0.12;
+
+pub fn load_and_resize_image(
+  path: &str,
+  max_dim: i32,
+) -> Result<(Mat, i32, i32)> {
+  load_and_resize_image_with_limits(
+    path,
+    max_dim,
+    &Limits::default(),
+    &BackgroundScaling::default(),
+  )
+}
+
+/// Loads an image scaled to fit within `max_dim`, rejecting backgrounds
+/// over `limits` before their pixels are decoded. Smaller images are only
+/// enlarged when `scaling` allows upscaling.
+pub fn load_and_resize_image_with_limits(
+  path: &str,
+  max_dim: i32,
+  limits: &Limits,
+  scaling: &BackgroundScaling,
+) -> Result<(Mat, i32, i32)> {
+  // Compute scale factor to fit within max_dim
+  let fit = |w: f64, h: f64| scaling.fit(w, h, max_dim);
+
+  // Load the background image
+  let (img, orig_w, orig_h) = read_background(path, limits, fit)?;
+  let scale = fit(orig_w, orig_h);
+
+  let width = (orig_w * scale) as i32;
+  let height = (orig_h * scale) as i32;
+  let resized = resize_background(&img, width, height, scaling)?;
+
+  Ok((resized, width, height))
+}
+
+/// Loads an image scaled to cover `width` x `height`, center-cropped to
+/// exactly that size
+pub fn load_and_fill_image(
+  path: &str,
+  width: i32,
+  height: i32,
+  limits: &Limits,
+  scaling: &BackgroundScaling,
+) -> Result<Mat> {
+  let cover = |w: f64, h: f64| (width as f64 / w).max(height as f64 / h);
+  let (img, orig_w, orig_h) = read_background(path, limits, cover)?;
+  let scale = cover(orig_w, orig_h);
+  let scaled_w = ((orig_w * scale).ceil() as i32).max(width);
+  let scaled_h = ((orig_h * scale).ceil() as i32).max(height);
+  let scaled = resize_background(&img, scaled_w, scaled_h, scaling)?;
+
+  let crop = core::Rect::new(
+    (scaled_w - width) / 2,
+    (scaled_h - height) / 2,
+    width,
+    height,
+  );
+  Ok(Mat::roi(&scaled, crop)?.try_clone()?)
+}
+
+/// Composites background `layers` in order, the first at the bottom, into
+/// one BGR image of `size`. Without a size the canvas takes the size of
+/// the first image layer fitted within `max_dim`, or is a `max_dim`
+/// square when there is no image layer.
+///
+/// Returns the image with its width and height; an error names the index
+/// of the layer that failed to load.
+pub fn load_background_layers(
+  layers: &[BackgroundLayer],
+  size: Option<(i32, i32)>,
+  max_dim: i32,
+  limits: &Limits,
+  scaling: &BackgroundScaling,
+) -> Result<(Mat, i32, i32)> {
+  let layer_error = |index: usize| {
+    move |err: anyhow::Error| {
+      err.context(format!(
+        "Background layer {} failed to load",
+        index
+      ))
+    }
+  };
+
+  // The first image layer sizes the canvas, loaded once
+  let mut first_image = None;
+  let (width, height) = match size {
+    Some(size) => size,
+    None => {
+      let image_layer = layers.iter().enumerate().find_map(|(index, layer)| {
+        match &layer.source {
+          LayerSource::Image { path } => Some((index, path)),
+          _ => None,
+        }
+      });
+      match image_layer {
+        Some((index, path)) => {
+          let (image, width, height) =
+            load_and_resize_image_with_limits(path, max_dim, limits, scaling)
+              .map_err(layer_error(index))?;
+          first_image = Some((index, image));
+          (width, height)
+        }
+        None => (max_dim, max_dim),
+      }
+    }
+  };
+
+  let mut canvas = Mat::new_rows_cols_with_default(
+    height,
+    width,
+    core::CV_8UC3,
+    core::Scalar::all(0.0),
+  )?;
+  for (index, layer) in layers.iter().enumerate() {
+    let pixels = match first_image.as_mut() {
+      Some((first, image)) if *first == index => mem::take(image),
+      _ => layer_pixels(
+        &layer.source,
+        width,
+        height,
+        limits,
+        scaling,
+      )
+      .map_err(layer_error(index))?,
+    };
+    canvas = blend_layer(
+      canvas,
+      pixels,
+      layer.opacity,
+      layer.blend,
+    )?;
+  }
+  Ok((canvas, width, height))
+}
+
+/// BGR pixels of a layer `source` at `width` x `height`
+fn layer_pixels(
+  source: &LayerSource,
+  width: i32,
+  height: i32,
+  limits: &Limits,
+  scaling: &BackgroundScaling,
+) -> Result<Mat> {
+  match source {
+    LayerSource::Image { path } => {
+      load_and_fill_image(path, width, height, limits, scaling)
+    }
+    LayerSource::Solid { color } => Ok(Mat::new_rows_cols_with_default(
+      height,
+      width,
+      core::CV_8UC3,
+      core::Scalar::new(color[0], color[1], color[2], 0.0),
+    )?),
+    LayerSource::Gradient {
+      from,
+      to,
+      direction,
+    } => {
+      let mut image = Mat::new_rows_cols_with_default(
+        height,
+        width,
+        core::CV_8UC3,
+        core::Scalar::all(0.0),
+      )?;
+      let steps = match direction {
+        GradientDirection::Vertical => height,
+        GradientDirection::Horizontal => width,
+      };
+      let span = (steps - 1).max(1) as f64;
+      let width = width.max(1) as usize;
+      for (i, pixel) in image.data_bytes_mut()?.chunks_exact_mut(3).enumerate()
+      {
+        let step = match direction {
+          GradientDirection::Vertical => i / width,
+          GradientDirection::Horizontal => i % width,
+        };
+        let t = step as f64 / span;
+        for (c, value) in pixel.iter_mut().enumerate() {
+          *value =
+            (from[c] + (to[c] - from[c]) * t).round().clamp(0.0, 255.0) as u8;
+        }
+      }
+      Ok(image)
+    }
+  }
+}
+
+/// `img` resized to `width` x `height` with the interpolation of
+/// `scaling`; every background image goes through it
+fn resize_background(
+  img: &Mat,
+  width: i32,
+  height: i32,
+  scaling: &BackgroundScaling,
+) -> Result<Mat> {
+  let size = img.size()?;
+  let enlarging = width > size.width || height > size.height;
+  let mut resized = Mat::default();
+  imgproc::resize(
+    img,
+    &mut resized,
+    core::Size::new(width, height),
+    0.0,
+    0.0,
+    interpolation_flag(scaling.interpolation(enlarging)),
+  )?;
+  Ok(resized)
+}
+
+/// OpenCV flag of `interp`
+fn interpolation_flag(interp: Interp) -> i32 {
+  match interp {
+    Interp::Nearest => imgproc::INTER_NEAREST,
+    Interp::Linear => imgproc::INTER_LINEAR,
+    Interp::Cubic => imgproc::INTER_CUBIC,
+    Interp::Lanczos4 => imgproc::INTER_LANCZOS4,
+  }
+}
+
+/// `layer` combined with the `below` image of the same size at `opacity`
+fn blend_layer(
+  below: Mat,
+  layer: Mat,
+  opacity: f64,
+  blend: LayerBlend,
+) -> Result<Mat> {
+  let opacity = opacity.clamp(0.0, 1.0);
+  match blend {
+    LayerBlend::Normal if opacity >= 1.0 => Ok(layer),
+    LayerBlend::Normal if opacity <= 0.0 => Ok(below),
+    LayerBlend::Normal => {
+      let mut blended = Mat::default();
+      core::add_weighted(
+        &layer,
+        opacity,
+        &below,
+        1.0 - opacity,
+        0.0,
+        &mut blended,
+        -1,
+      )?;
+      Ok(blended)
+    }
+  }
+}
+
+/// Reads a background that will be drawn at `scale(width, height)` of its
+/// full size, returning it with its full width and height.
+///
+/// The size comes from the file header, so oversized images are rejected
+/// before decoding, and images at least 4 times larger than needed are
+/// decoded at a reduced scale. Smaller images, and formats whose header
+/// cannot be read, are decoded whole as before.
+fn read_background(
+  path: &str,
+  limits: &Limits,
+  scale: impl Fn(f64, f64) -> f64,
+) -> Result<(Mat, f64, f64)> {
+  let Ok((header_w, header_h)) = image::image_dimensions(path) else {
+    let img = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
+    if img.empty() {
+      bail!("Cannot read background image {}", path);
+    }
+    let size = img.size()?;
+    return Ok((
+      img,
+      size.width as f64,
+      size.height as f64,
+    ));
+  };
+  limits.check_background_size(header_w, header_h)?;
+  let (w, h) = (header_w as f64, header_h as f64);
+
+  // EXIF orientation may swap the sides, so either way must fit
+  let flag = reduced_read_flag(scale(w, h).max(scale(h, w)));
+  let img = imgcodecs::imread(path, flag)?;
+  let size = img.size()?;
+  let rotated = (size.width > size.height) != (header_w > header_h);
+  Ok(if rotated { (img, h, w) } else { (img, w, h) })
+}
+
+/// `imread` flag for an image drawn at `scale` of its full size: the
+/// largest reduction that still leaves twice the needed resolution for the
+/// final LANCZOS resize
+pub fn reduced_read_flag(scale: f64) -> i32 {
+  if scale <= 1.0 / 16.0 {
+    imgcodecs::IMREAD_REDUCED_COLOR_8
+  } else if scale <= 1.0 / 8.0 {
+    imgcodecs::IMREAD_REDUCED_COLOR_4
+  } else if scale <= 1.0 / 4.0 {
+    imgcodecs::IMREAD_REDUCED_COLOR_2
+  } else {
+    imgcodecs::IMREAD_COLOR
+  }
+}
+
+/// Applies `effects` in order to a loaded background
+pub fn apply_background_fx(
+  mut image: Mat,
+  effects: &[BackgroundFx],
+) -> Result<Mat> {
+  for effect in effects {
+    image = match *effect {
+      BackgroundFx::Blur { sigma } if sigma > 0.0 => {
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur_def(
+          &image,
+          &mut blurred,
+          core::Size::new(0, 0),
+          sigma,
+        )?;
+        blurred
+      }
+      BackgroundFx::Blur { .. } => image,
+      BackgroundFx::Darken { factor } => {
+        let mut darkened = Mat::default();
+        image.convert_to(&mut darkened, -1, factor.max(0.0), 0.0)?;
+        darkened
+      }
+      BackgroundFx::Mapify { strength } => mapify(&image, strength)?,
+    };
+  }
+  Ok(image)
+}
+
+/// Muted "map style" version of a BGR satellite photo: CLAHE on the LAB
+/// lightness, less color and lifted shadows, blended with the original by
+/// `strength` (0.0-1.0)
+pub fn mapify(image: &Mat, strength: f64) -> Result<Mat> {
+  let strength = strength.clamp(0.0, 1.0);
+  if strength == 0.0 {
+    return Ok(image.try_clone()?);
+  }
+
+  let mut lab = Mat::default();
+  imgproc::cvt_color_def(image, &mut lab, imgproc::COLOR_BGR2LAB)?;
+  let mut channels = core::Vector::<Mat>::new();
+  core::split(&lab, &mut channels)?;
+
+  // Local contrast on the lightness, then black lifted towards gray
+  let mut clahe = imgproc::create_clahe(
+    MAPIFY_CLIP_LIMIT,
+    core::Size::new(MAPIFY_TILES, MAPIFY_TILES),
+  )?;
+  let mut contrasted = Mat::default();
+  clahe.apply(&channels.get(0)?, &mut contrasted)?;
+  let mut lightness = Mat::default();
+  contrasted.convert_to(
+    &mut lightness,
+    -1,
+    1.0 - MAPIFY_SHADOW_LIFT,
+    255.0 * MAPIFY_SHADOW_LIFT,
+  )?;
+
+  // a and b pulled towards their neutral 128
+  let mut muted = core::Vector::<Mat>::from_iter([lightness]);
+  for i in 1..3 {
+    let mut color = Mat::default();
+    channels.get(i)?.convert_to(
+      &mut color,
+      -1,
+      1.0 - MAPIFY_DESATURATION,
+      128.0 * MAPIFY_DESATURATION,
+    )?;
+    muted.push(color);
+  }
+  core::merge(&muted, &mut lab)?;
+  let mut processed = Mat::default();
+  imgproc::cvt_color_def(
+    &lab,
+    &mut processed,
+    imgproc::COLOR_LAB2BGR,
+  )?;
+
+  let mut blended = Mat::default();
+  core::add_weighted_def(
+    &processed,
+    strength,
+    image,
+    1.0 - strength,
+    0.0,
+    &mut blended,
+  )?;
+  Ok(blended)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reduced_read_flag() {
+    // A 4000 px photo drawn at 1080 px is decoded whole
+    assert_eq!(
+      reduced_read_flag(1080.0 / 4000.0),
+      imgcodecs::IMREAD_COLOR
+    );
+    assert_eq!(
+      reduced_read_flag(0.25),
+      imgcodecs::IMREAD_REDUCED_COLOR_2
+    );
+    // A 12000 px drone photo keeps 3000 px for the final resize
+    assert_eq!(
+      reduced_read_flag(1080.0 / 12000.0),
+      imgcodecs::IMREAD_REDUCED_COLOR_4
+    );
+    assert_eq!(
+      reduced_read_flag(0.01),
+      imgcodecs::IMREAD_REDUCED_COLOR_8
+    );
+  }
+
+  #[test]
+  fn test_oversized_background_rejected_from_header() {
+    use crate::{
+      error::{LimitKind, RunariumError},
+      utils::workspace::Workspace,
+    };
+
+    // A 65535x65535 GIF header over a single 1x1 image
+    let mut gif = b"GIF89a\xff\xff\xff\xff\x00\x00\x00".to_vec();
+    gif.extend(b",\x00\x00\x00\x00\x01\x00\x01\x00\x00");
+    gif.extend(b"\x02\x02\x44\x01\x00;");
+    let workspace = Workspace::new("runarium_test").unwrap();
+    let path = workspace.write_file("huge.gif", &gif).unwrap();
+
+    let err = load_and_resize_image(&path.to_string_lossy(), 1080)
+      .expect_err("oversized background accepted");
+    assert_eq!(
+      err.downcast_ref::<RunariumError>(),
+      Some(&RunariumError::LimitExceeded {
+        which: LimitKind::BackgroundPixels,
+        actual: 65535 * 65535,
+        limit: 4096 * 4096 * 64,
+      })
+    );
+  }
+
+  /// Gray level of the darkest pixel of a BGR image
+  fn darkest(image: &Mat) -> u32 {
+    image
+      .data_bytes()
+      .unwrap()
+      .chunks(3)
+      .map(|px| px.iter().map(|&c| c as u32).sum::<u32>() / 3)
+      .min()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_mapify_background_fixture() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("source/example.jpg");
+    if !path.exists() {
+      return;
+    }
+    let (original, _, _) =
+      load_and_resize_image(&path.to_string_lossy(), 540).unwrap();
+    let bytes = |image: &Mat| image.data_bytes().unwrap().to_vec();
+
+    let full = mapify(&original, 1.0).unwrap();
+    assert_eq!(
+      full.size().unwrap(),
+      original.size().unwrap()
+    );
+    assert_eq!(full.typ(), core::CV_8UC3);
+    assert_ne!(bytes(&full), bytes(&original));
+    // Shadows are lifted, not crushed to black
+    assert!(darkest(&full) >= darkest(&original));
+
+    // Strength blends between the two; 0 leaves the photo as it is
+    let half = mapify(&original, 0.5).unwrap();
+    assert_ne!(bytes(&half), bytes(&full));
+    assert_ne!(bytes(&half), bytes(&original));
+    assert_eq!(
+      bytes(&mapify(&original, 0.0).unwrap()),
+      bytes(&original)
+    );
+
+    // Composes with the other filters in order
+    let composed = apply_background_fx(
+      original.try_clone().unwrap(),
+      &[
+        BackgroundFx::Blur { sigma: 2.0 },
+        BackgroundFx::Mapify { strength: 0.8 },
+        BackgroundFx::Darken { factor: 0.7 },
+      ],
+    )
+    .unwrap();
+    assert_eq!(
+      composed.size().unwrap(),
+      original.size().unwrap()
+    );
+    assert_eq!(composed.typ(), core::CV_8UC3);
+  }
+
+  fn pixel(image: &Mat, row: i32, col: i32) -> [u8; 3] {
+    let bgr = image.at_2d::<core::Vec3b>(row, col).unwrap();
+    [bgr[0], bgr[1], bgr[2]]
+  }
+
+  #[test]
+  fn test_background_layers_blend_in_order() {
+    let solid = |color| BackgroundLayer::new(LayerSource::Solid { color });
+    let layers = [
+      solid([200.0, 100.0, 0.0, 0.0]),
+      solid([0.0, 0.0, 250.0, 0.0]).with_opacity(0.4),
+      solid([255.0, 255.0, 255.0, 0.0]).with_opacity(0.2),
+    ];
+    let (image, width, height) = load_background_layers(
+      &layers,
+      Some((4, 3)),
+      1080,
+      &Limits::default(),
+      &BackgroundScaling::default(),
+    )
+    .unwrap();
+    assert_eq!((width, height), (4, 3));
+    // 60% of the first under 40% red, then 80% of that under 20% white
+    assert_eq!(pixel(&image, 2, 3), [147, 99, 131]);
+
+    // A transparent layer changes nothing; without a size or an image
+    // layer the canvas is a square
+    let (image, width, height) = load_background_layers(
+      &[layers[0].clone(), layers[1].clone().with_opacity(0.0)],
+      None,
+      8,
+      &Limits::default(),
+      &BackgroundScaling::default(),
+    )
+    .unwrap();
+    assert_eq!((width, height), (8, 8));
+    assert_eq!(pixel(&image, 0, 0), [200, 100, 0]);
+  }
+
+  #[test]
+  fn test_gradient_layer() {
+    let gradient = |direction| {
+      BackgroundLayer::new(LayerSource::Gradient {
+        from: [0.0, 0.0, 0.0, 0.0],
+        to: [200.0, 100.0, 50.0, 0.0],
+        direction,
+      })
+    };
+    let (image, _, _) = load_background_layers(
+      &[gradient(GradientDirection::Vertical)],
+      Some((2, 3)),
+      1080,
+      &Limits::default(),
+      &BackgroundScaling::default(),
+    )
+    .unwrap();
+    assert_eq!(pixel(&image, 0, 1), [0, 0, 0]);
+    assert_eq!(pixel(&image, 1, 0), [100, 50, 25]);
+    assert_eq!(pixel(&image, 2, 1), [200, 100, 50]);
+
+    let (image, _, _) = load_background_layers(
+      &[gradient(GradientDirection::Horizontal)],
+      Some((3, 2)),
+      1080,
+      &Limits::default(),
+      &BackgroundScaling::default(),
+    )
+    .unwrap();
+    assert_eq!(pixel(&image, 1, 1), [100, 50, 25]);
+  }
+
+  #[test]
+  fn test_failed_background_layer_names_its_index() {
+    let layers = [
+      BackgroundLayer::new(LayerSource::Solid {
+        color: [0.0, 0.0, 0.0, 0.0],
+      }),
+      BackgroundLayer::image("missing/background.png"),
+    ];
+    let err = load_background_layers(
+      &layers,
+      Some((4, 4)),
+      1080,
+      &Limits::default(),
+      &BackgroundScaling::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Background layer 1 failed to load"
+    );
+    assert!(format!("{:#}", err).contains("missing/background.png"));
+  }
+}
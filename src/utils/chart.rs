@@ -0,0 +1,162 @@
+use opencv::core;
+
+/// Look of a line chart drawn by `Drawer::line_chart`
+#[derive(Debug, Clone, Copy)]
+pub struct ChartStyle {
+  pub line_color: core::Scalar,
+  pub thickness: i32,
+  /// Fill behind the chart (None = transparent)
+  pub background: Option<core::Scalar>,
+  /// Dashed horizontal reference line at this value (None = no line)
+  pub reference: Option<f64>,
+  pub reference_color: core::Scalar,
+}
+
+/// Maps chart values onto the pixels of the chart box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChartAxes {
+  /// Chart box in pixels
+  pub rect: core::Rect,
+  /// Values at the left and right edges
+  pub x_range: (f64, f64),
+  /// Values at the bottom and top edges
+  pub y_range: (f64, f64),
+}
+
+impl ChartAxes {
+  /// Axes spanning every value of `series`, stretched to include
+  /// `reference`. None when the series has no values.
+  pub fn fit(
+    rect: core::Rect,
+    series: &[(f64, Option<f64>)],
+    reference: Option<f64>,
+  ) -> Option<Self> {
+    let values = || series.iter().filter_map(|&(x, y)| Some((x, y?)));
+    let (first, _) = values().next()?;
+    let (mut x_range, mut y_range) = ((first, first), (f64::MAX, f64::MIN));
+    for (x, y) in values() {
+      x_range = (x_range.0.min(x), x_range.1.max(x));
+      y_range = (y_range.0.min(y), y_range.1.max(y));
+    }
+    if let Some(reference) = reference {
+      y_range = (
+        y_range.0.min(reference),
+        y_range.1.max(reference),
+      );
+    }
+    // A flat series sits in the middle
+    if y_range.0 == y_range.1 {
+      y_range = (y_range.0 - 1.0, y_range.1 + 1.0);
+    }
+
+    Some(Self {
+      rect,
+      x_range,
+      y_range,
+    })
+  }
+
+  /// Pixel of a value; values outside the ranges are kept inside the box
+  pub fn point(&self, x: f64, y: f64) -> core::Point {
+    let share = |value: f64, (low, high): (f64, f64)| {
+      if high > low {
+        ((value - low) / (high - low)).clamp(0.0, 1.0)
+      } else {
+        0.0
+      }
+    };
+    let core::Rect {
+      x: left,
+      y: top,
+      width,
+      height,
+    } = self.rect;
+    core::Point::new(
+      left + (share(x, self.x_range) * (width - 1) as f64).round() as i32,
+      top + height
+        - 1
+        - (share(y, self.y_range) * (height - 1) as f64).round() as i32,
+    )
+  }
+}
+
+/// Cadence per record (steps per minute, or rpm when cycling) against
+/// distance
+pub fn cadence_series(
+  distances: &[f64],
+  cadences: &[Option<u16>],
+) -> Vec<(f64, Option<f64>)> {
+  distances
+    .iter()
+    .zip(cadences)
+    .map(|(&distance, cadence)| {
+      (
+        distance,
+        cadence.map(|cadence| cadence as f64),
+      )
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rect() -> core::Rect {
+    core::Rect::new(10, 20, 101, 51)
+  }
+
+  #[test]
+  fn test_axes_fit_values_and_reference() {
+    let series = [(0.0, Some(160.0)), (500.0, None), (1000.0, Some(170.0))];
+    let axes = ChartAxes::fit(rect(), &series, None).unwrap();
+    assert_eq!(axes.x_range, (0.0, 1000.0));
+    assert_eq!(axes.y_range, (160.0, 170.0));
+
+    // Corners of the box: left-bottom is the minimum
+    assert_eq!(
+      axes.point(0.0, 160.0),
+      core::Point::new(10, 70)
+    );
+    assert_eq!(
+      axes.point(1000.0, 170.0),
+      core::Point::new(110, 20)
+    );
+    assert_eq!(
+      axes.point(500.0, 165.0),
+      core::Point::new(60, 45)
+    );
+
+    // The reference line stretches the value range
+    let axes = ChartAxes::fit(rect(), &series, Some(180.0)).unwrap();
+    assert_eq!(axes.y_range, (160.0, 180.0));
+  }
+
+  #[test]
+  fn test_axes_edge_cases() {
+    assert!(ChartAxes::fit(rect(), &[(0.0, None)], Some(180.0)).is_none());
+    assert!(ChartAxes::fit(rect(), &[], None).is_none());
+
+    // Flat series are centered, out-of-range values clamped
+    let axes = ChartAxes::fit(rect(), &[(0.0, Some(90.0))], None).unwrap();
+    assert_eq!(axes.y_range, (89.0, 91.0));
+    assert_eq!(
+      axes.point(0.0, 90.0),
+      core::Point::new(10, 45)
+    );
+    assert_eq!(
+      axes.point(-5.0, 500.0),
+      core::Point::new(10, 20)
+    );
+  }
+
+  #[test]
+  fn test_cadence_series() {
+    let distances = [0.0, 10.0, 20.0];
+    let cadences = [Some(170), None, Some(180)];
+    assert_eq!(
+      cadence_series(&distances, &cadences),
+      vec![(0.0, Some(170.0)), (10.0, None), (20.0, Some(180.0))]
+    );
+  }
+}
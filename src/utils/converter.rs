@@ -1,62 +1,480 @@
-use anyhow::Result;
-use opencv::{core, imgcodecs, imgproc, prelude::*};
+use crate::{
+  configs::{
+    BarLabel, GpsSmoothingMethod, Normalization, NumberFormat, PaceRounding,
+    SportProfile, Units,
+  },
+  types::fit_data::{LapData, SessionSummary},
+  utils::stats::normalized_range,
+};
+
+// Background loading moved to `background`, which needs OpenCV
+#[cfg(feature = "render")]
+pub use crate::utils::background::{
+  apply_background_fx, load_and_fill_image, load_and_resize_image,
+  load_and_resize_image_with_limits, load_background_layers, mapify,
+  reduced_read_flag,
+};
 
 pub fn speed_to_pace(speed: f32) -> String {
-  if speed <= 0.0 {
+  sec_to_pace(speed_to_pace_secs(speed as f64) as f32)
+}
+
+pub fn sec_to_pace(pace_seconds: f32) -> String {
+  format_pace(
+    pace_seconds as f64,
+    PaceRounding::RoundHalfUp,
+  )
+}
+
+/// Seconds per km at `speed` m/s; 0 when not moving (shown as "0:00")
+pub fn speed_to_pace_secs(speed: f64) -> f64 {
+  if speed.is_finite() && speed > 0.0 {
+    1000.0 / speed
+  } else {
+    0.0
+  }
+}
+
+/// Formats seconds per km (or mile) as "m:ss". The seconds are rounded
+/// before splitting off the minutes, so 359.5 s carries into "6:00".
+pub fn format_pace(pace_seconds: f64, rounding: PaceRounding) -> String {
+  if !pace_seconds.is_finite() || pace_seconds <= 0.0 {
     return String::from("0:00");
   }
-  let pace_seconds = 1000.0 / speed;
-  let minutes = (pace_seconds / 60.0).floor() as u32;
-  let seconds = (pace_seconds % 60.0).round() as u32;
-  format!("{}:{:02}", minutes, seconds)
+  let total = match rounding {
+    PaceRounding::RoundHalfUp => pace_seconds.round(),
+    PaceRounding::Floor => pace_seconds.floor(),
+  } as u64;
+  format!("{}:{:02}", total / 60, total % 60)
+}
+
+/// Formats `value` with `decimals` places; "--" when it is not a number
+pub fn format_decimal(value: f64, decimals: usize) -> String {
+  if !value.is_finite() {
+    return "--".to_string();
+  }
+  let text = format!("{:.*}", decimals, value);
+  // No "-0.00" for tiny negative values
+  match text.strip_prefix('-') {
+    Some(rest) if rest.bytes().all(|b| b == b'0' || b == b'.') => {
+      rest.to_string()
+    }
+    _ => text,
+  }
+}
+
+/// Distance in km or miles with the configured decimals; negative and
+/// missing distances show as zero
+pub fn format_distance(
+  meters: f64,
+  units: Units,
+  format: &NumberFormat,
+) -> String {
+  let meters = if meters.is_finite() {
+    meters.max(0.0)
+  } else {
+    0.0
+  };
+  format_decimal(
+    units.distance(meters),
+    format.distance_decimals,
+  )
+}
+
+/// Lap stride length (FIT `avg_step_length`) in meters with the
+/// configured decimals; negative and missing lengths show as zero
+pub fn format_stride(step_length: f64, format: &NumberFormat) -> String {
+  let meters = if step_length.is_finite() {
+    step_length.max(0.0) / 10.0
+  } else {
+    0.0
+  };
+  format_decimal(meters, format.stride_decimals)
+}
+
+/// Altitude changes (meters) `elevation_change` treats as jitter
+pub const ELEVATION_HYSTERESIS_M: f64 = 1.0;
+
+/// Total elevation gain and loss (meters) over the known altitudes.
+///
+/// A climb or descent only counts once it moves `threshold_m` away from
+/// the altitude last counted, so jitter smaller than that adds nothing
+/// while slow, steady climbs still add up.
+pub fn elevation_change(
+  altitudes: &[Option<f64>],
+  threshold_m: f64,
+) -> (f64, f64) {
+  let (mut gain, mut loss) = (0.0, 0.0);
+  let mut counted: Option<f64> = None;
+  for &altitude in altitudes.iter().flatten() {
+    let Some(base) = counted else {
+      counted = Some(altitude);
+      continue;
+    };
+    let change = altitude - base;
+    if change.abs() >= threshold_m {
+      if change > 0.0 {
+        gain += change;
+      } else {
+        loss -= change;
+      }
+      counted = Some(altitude);
+    }
+  }
+  (gain, loss)
+}
+
+/// Total elevation gain (meters) of an activity: from the record
+/// altitudes when there are any, else the device's total ascent
+pub fn activity_gain(
+  altitudes: &[Option<f64>],
+  total_ascent: Option<u16>,
+) -> Option<f64> {
+  if altitudes.iter().any(Option::is_some) {
+    Some(elevation_change(altitudes, ELEVATION_HYSTERESIS_M).0)
+  } else {
+    total_ascent.map(f64::from)
+  }
+}
+
+/// Lap panel line of a total elevation gain, e.g. "Elev +412 m"
+pub fn format_elevation_gain(gain_m: f64, units: Units) -> String {
+  format!(
+    "Elev +{} {}",
+    format_decimal(units.elevation(gain_m.max(0.0)), 0),
+    units.elevation_label()
+  )
+}
+
+/// Parts of the one-line activity summary, e.g. "10.02 km", "52:31",
+/// "531 kcal" and "156 bpm"; totals the session did not record are left
+/// out
+pub fn summary_parts(
+  session: &SessionSummary,
+  format: &NumberFormat,
+) -> Vec<String> {
+  let mut parts = Vec::new();
+  if let Some(meters) = session.total_distance {
+    parts.push(format!(
+      "{} km",
+      format_distance(meters, Units::Metric, format)
+    ));
+  }
+  if let Some(seconds) = session.total_timer_time {
+    parts.push(format_duration(seconds));
+  }
+  if let Some(calories) = session.total_calories {
+    parts.push(format!("{} kcal", calories));
+  }
+  if let Some(hr) = session.avg_heart_rate {
+    parts.push(format!("{} bpm", hr));
+  }
+  parts
 }
 
 pub fn semicircles_to_degrees(semicircles: i32) -> f64 {
   (semicircles as f64) * (180.0 / (i32::MAX as f64 + 1.0))
 }
 
+/// FIT's invalid sint32 value, recorded for positions while the device has
+/// no GPS fix
+pub const INVALID_SEMICIRCLES: i32 = i32::MAX;
+
+/// Degrees of a FIT position, or None for the invalid value or a position
+/// beyond `limit` degrees (90 for latitudes, 180 for longitudes)
+pub fn position_degrees(semicircles: i32, limit: f64) -> Option<f64> {
+  if semicircles == INVALID_SEMICIRCLES {
+    return None;
+  }
+  let degrees = semicircles_to_degrees(semicircles);
+  (degrees.abs() <= limit).then_some(degrees)
+}
+
+/// Latitude and longitude bounds of `points` (lat, lon).
+///
+/// A route spanning more than 180° of longitude is taken to cross the
+/// antimeridian when that makes it narrower: its western longitudes are
+/// shifted by 360°, so `lon_max` exceeds 180. `wrap_longitude` maps
+/// longitudes into the same range.
 pub fn get_bounds(points: &[(f64, f64)]) -> ((f64, f64), (f64, f64)) {
   let (lat_min, lat_max) = points.iter().fold(
     (f64::INFINITY, f64::NEG_INFINITY),
     |(mn, mx), (lat, _)| (mn.min(*lat), mx.max(*lat)),
   );
 
-  let (lon_min, lon_max) = points.iter().fold(
-    (f64::INFINITY, f64::NEG_INFINITY),
-    |(mn, mx), (_, lon)| (mn.min(*lon), mx.max(*lon)),
-  );
+  let lon_bounds = |shift: f64| {
+    points.iter().fold(
+      (f64::INFINITY, f64::NEG_INFINITY),
+      |(mn, mx), &(_, lon)| {
+        let lon = if lon < 0.0 { lon + shift } else { lon };
+        (mn.min(lon), mx.max(lon))
+      },
+    )
+  };
+  let (lon_min, lon_max) = lon_bounds(0.0);
+  let span = lon_max - lon_min;
+  let wrapped = lon_bounds(360.0);
+  let (lon_min, lon_max) = if span > 180.0 && wrapped.1 - wrapped.0 < span {
+    wrapped
+  } else {
+    (lon_min, lon_max)
+  };
 
   ((lat_min, lat_max), (lon_min, lon_max))
 }
 
-pub fn load_and_resize_image(
-  path: &str,
-  max_dim: i32,
-) -> Result<(Mat, i32, i32)> {
-  // Load the background image
-  let img = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
-  let size = img.size()?;
-  let (orig_w, orig_h) = (size.width as f64, size.height as f64);
+/// Longitude in the range of bounds from `get_bounds`: western longitudes
+/// are shifted by 360° when the bounds cross the antimeridian
+pub fn wrap_longitude(lon: f64, lon_max: f64) -> f64 {
+  if lon_max > 180.0 && lon < 0.0 {
+    lon + 360.0
+  } else {
+    lon
+  }
+}
+
+/// Most records a GPS moving average spans
+pub const MAX_GPS_WINDOW: usize = 51;
+
+/// GPS positions (lat, lon) smoothed with `method`, one per record, so
+/// record series stay aligned. Each stretch between `breaks` (records
+/// after which the line breaks, in increasing order) is smoothed on its
+/// own, so positions are not pulled across a pause.
+pub fn smooth_gps_points(
+  points: &[(f64, f64)],
+  breaks: &[usize],
+  method: GpsSmoothingMethod,
+) -> Vec<(f64, f64)> {
+  let mut smoothed = Vec::with_capacity(points.len());
+  let mut start = 0;
+  let ends = breaks
+    .iter()
+    .map(|&record| record + 1)
+    .filter(|&end| end < points.len())
+    .chain([points.len()]);
+  for end in ends {
+    if end <= start {
+      continue;
+    }
+    let stretch = &points[start..end];
+    match method {
+      GpsSmoothingMethod::MovingAverage { window } => smoothed.extend(
+        moving_average(stretch, window.min(MAX_GPS_WINDOW)),
+      ),
+      GpsSmoothingMethod::Kalman {
+        process_noise_m,
+        measurement_noise_m,
+      } => smoothed.extend(kalman(
+        stretch,
+        process_noise_m,
+        measurement_noise_m,
+      )),
+    }
+    start = end;
+  }
+  smoothed
+}
+
+/// Centered moving average over `window` points, narrowed near the ends
+/// so the first and last point stay where they are
+fn moving_average(points: &[(f64, f64)], window: usize) -> Vec<(f64, f64)> {
+  let half = window / 2;
+  (0..points.len())
+    .map(|i| {
+      let reach = half.min(i).min(points.len() - 1 - i);
+      let lon = points[i].1;
+      let neighbors = &points[i - reach..=i + reach];
+      let count = neighbors.len() as f64;
+      let lat_sum: f64 = neighbors.iter().map(|p| p.0).sum();
+      let lon_shift: f64 = neighbors.iter().map(|p| lon_delta(lon, p.1)).sum();
+      (
+        lat_sum / count,
+        normalize_lon(lon + lon_shift / count),
+      )
+    })
+    .collect()
+}
+
+/// Kalman filter of a position held steady between records; the noises
+/// are standard deviations in meters, and only their ratio matters
+fn kalman(
+  points: &[(f64, f64)],
+  process_noise_m: f64,
+  measurement_noise_m: f64,
+) -> Vec<(f64, f64)> {
+  let q = process_noise_m.powi(2);
+  let r = measurement_noise_m.powi(2);
+  let Some(&(mut lat, mut lon)) = points.first() else {
+    return Vec::new();
+  };
+  let mut variance = r;
+  points
+    .iter()
+    .map(|&(measured_lat, measured_lon)| {
+      variance += q;
+      let gain = if variance + r > 0.0 {
+        variance / (variance + r)
+      } else {
+        1.0
+      };
+      lat += gain * (measured_lat - lat);
+      lon = normalize_lon(lon + gain * lon_delta(lon, measured_lon));
+      variance *= 1.0 - gain;
+      (lat, lon)
+    })
+    .collect()
+}
+
+/// Shortest signed longitude step from `from` to `to`, across the
+/// antimeridian if that is shorter
+fn lon_delta(from: f64, to: f64) -> f64 {
+  (to - from + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Longitude brought back into -180..=180
+fn normalize_lon(lon: f64) -> f64 {
+  if lon > 180.0 {
+    lon - 360.0
+  } else if lon < -180.0 {
+    lon + 360.0
+  } else {
+    lon
+  }
+}
 
-  // Compute scale factor to fit within max_dim
-  let max_side = orig_w.max(orig_h);
-  let scale = (max_dim as f64 / max_side).min(1.0);
+/// Indices of at most `max_points` of `points` (lat, lon) keeping the
+/// shape of the track: Douglas-Peucker simplification that keeps splitting
+/// the stretch with the farthest point off its chord until `max_points`
+/// are picked or the rest lie on the chords. The first and last point and
+/// the points on both sides of each of `breaks` (records after which the
+/// line breaks) are always kept, even past `max_points`. Indices are in
+/// increasing order; a track within `max_points` keeps every index.
+pub fn simplify_indices(
+  points: &[(f64, f64)],
+  breaks: &[usize],
+  max_points: usize,
+) -> Vec<usize> {
+  let count = points.len();
+  if count <= max_points.max(2) {
+    return (0..count).collect();
+  }
+  let mut kept = vec![false; count];
+  kept[0] = true;
+  kept[count - 1] = true;
+  for &record in breaks.iter().filter(|&&record| record + 1 < count) {
+    kept[record] = true;
+    kept[record + 1] = true;
+  }
+  let anchors: Vec<usize> = (0..count).filter(|&i| kept[i]).collect();
+  let mut picked = anchors.len();
 
-  let width = (orig_w * scale) as i32;
-  let height = (orig_h * scale) as i32;
+  // Stretches between kept points, by their point farthest off the chord
+  let mut stretches: Vec<_> = anchors
+    .windows(2)
+    .filter_map(|pair| farthest(points, pair[0], pair[1]))
+    .collect();
+  while picked < max_points {
+    let Some(at) = (0..stretches.len())
+      .max_by(|&a, &b| stretches[a].3.total_cmp(&stretches[b].3))
+    else {
+      break;
+    };
+    let (start, index, end, _) = stretches.swap_remove(at);
+    kept[index] = true;
+    picked += 1;
+    stretches.extend(farthest(points, start, index));
+    stretches.extend(farthest(points, index, end));
+  }
+  (0..count).filter(|&i| kept[i]).collect()
+}
 
-  // Resize using high-quality interpolation
-  let mut resized = Mat::default();
-  imgproc::resize(
-    &img,
-    &mut resized,
-    core::Size::new(width, height),
-    0.0,
-    0.0,
-    imgproc::INTER_LANCZOS4,
-  )?;
+/// Point strictly between `start` and `end` farthest from their chord,
+/// as `(start, index, end, distance)` with the distance in local degrees;
+/// None when every point lies on the chord
+fn farthest(
+  points: &[(f64, f64)],
+  start: usize,
+  end: usize,
+) -> Option<(usize, usize, usize, f64)> {
+  // Longitudes shrink toward the poles
+  let scale = points[start].0.to_radians().cos();
+  let local = |(lat, lon): (f64, f64)| {
+    (
+      lon_delta(points[start].1, lon) * scale,
+      lat - points[start].0,
+    )
+  };
+  let (ex, ey) = local(points[end]);
+  let length = ex.hypot(ey);
+  (start + 1..end)
+    .map(|i| {
+      let (x, y) = local(points[i]);
+      let distance = if length > 0.0 {
+        (x * ey - y * ex).abs() / length
+      } else {
+        x.hypot(y)
+      };
+      (start, i, end, distance)
+    })
+    .max_by(|a, b| a.3.total_cmp(&b.3))
+    .filter(|stretch| stretch.3 > 0.0)
+}
 
-  Ok((resized, width, height))
+/// Initial compass bearing (0-360°, clockwise from north) of the great
+/// circle from `from` to `to` (lat, lon); None when the points are equal
+pub fn bearing(from: (f64, f64), to: (f64, f64)) -> Option<f64> {
+  if from == to {
+    return None;
+  }
+  let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+  let d_lon = (to.1 - from.1).to_radians();
+  let y = d_lon.sin() * lat2.cos();
+  let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+  let degrees = y.atan2(x).to_degrees().rem_euclid(360.0);
+  // rem_euclid rounds a tiny negative angle up to exactly 360
+  Some(if degrees >= 360.0 { 0.0 } else { degrees })
+}
+
+/// Nearest of the 8 compass points to `bearing` degrees ("N", "NE", ...)
+pub fn compass_point(bearing: f64) -> &'static str {
+  const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+  POINTS[(bearing.rem_euclid(360.0) / 45.0).round() as usize % 8]
+}
+
+/// Formats a bearing as "NE 47°"
+pub fn format_bearing(bearing: f64) -> String {
+  let degrees = bearing.rem_euclid(360.0).round() as u32 % 360;
+  format!(
+    "{} {}°",
+    compass_point(bearing),
+    degrees
+  )
+}
+
+/// Formats a position as "13.7563°N, 100.5018°E" with `decimals` places.
+/// The equator and the zero meridian, and positions that round to them,
+/// are written N and E.
+pub fn format_coordinates(lat: f64, lon: f64, decimals: usize) -> String {
+  format!(
+    "{}, {}",
+    hemisphere(lat, decimals, 'N', 'S'),
+    hemisphere(lon, decimals, 'E', 'W')
+  )
+}
+
+/// `value` without its sign, followed by a degree sign and the letter of
+/// its hemisphere
+fn hemisphere(
+  value: f64,
+  decimals: usize,
+  positive: char,
+  negative: char,
+) -> String {
+  let text = format_decimal(value, decimals);
+  match text.strip_prefix('-') {
+    Some(magnitude) => format!("{}°{}", magnitude, negative),
+    None => format!("{}°{}", text, positive),
+  }
 }
 
 pub fn string_space(size: usize, index: usize, pace: &str) -> String {
@@ -67,13 +485,25 @@ pub fn string_space(size: usize, index: usize, pace: &str) -> String {
   format!("{}{}{}", index, padding, pace)
 }
 
+/// Pace text of a record without a speed, e.g. a GPX point without time
+pub const NO_PACE: &str = "-:--";
+
+/// Lap panel text of a value the lap did not record
+pub const NO_VALUE: &str = "-";
+
+/// Seconds of a pace ("m:ss"); 0 for `NO_PACE` or other text
 pub fn convert_pace_to_sec(pace: &str) -> f32 {
-  let (min, sec) = pace.split_once(':').unwrap();
+  parse_pace(pace).unwrap_or(0.0)
+}
+
+/// Seconds of a pace ("m:ss"), or None when it is not one
+pub fn parse_pace(pace: &str) -> Option<f32> {
+  let (min, sec) = pace.split_once(':')?;
 
-  let minutes: f32 = min.parse().unwrap();
-  let seconds: f32 = sec.parse().unwrap();
+  let minutes: f32 = min.parse().ok()?;
+  let seconds: f32 = sec.parse().ok()?;
 
-  minutes * 60.0 + seconds
+  Some(minutes * 60.0 + seconds)
 }
 
 fn count_digits_iterative(mut num: usize) -> usize {
@@ -92,6 +522,275 @@ pub fn pace_percentage(numer: f32, denum: f32) -> f32 {
   numer / denum
 }
 
+/// Length of each lap's pace bar as a fraction of the full bar: the
+/// fastest pace of the normalized range, rounded down to 30 s, over the
+/// lap's pace clamped to the range
+pub fn pace_bar_fractions(
+  pace_seconds: &[f32],
+  normalization: Normalization,
+) -> Vec<f32> {
+  let paces: Vec<f64> = pace_seconds.iter().map(|&p| p as f64).collect();
+  let Some((fastest, slowest)) = normalized_range(&paces, normalization) else {
+    return vec![0.0; pace_seconds.len()];
+  };
+  let min_denominator = full_bar_pace(fastest);
+  pace_seconds
+    .iter()
+    .map(|&pace| {
+      pace_percentage(
+        min_denominator,
+        pace.clamp(fastest as f32, slowest as f32),
+      )
+    })
+    .collect()
+}
+
+/// Label past each lap's pace bar: how much slower the lap is than the
+/// fastest one, e.g. "+14 s" or "+5%". The fastest lap reads "+0 s" (or
+/// "+0%"); laps without a pace get no label.
+pub fn pace_bar_labels(
+  pace_seconds: &[f32],
+  format: BarLabel,
+) -> Vec<Option<String>> {
+  let is_pace = |pace: f32| pace.is_finite() && pace > 0.0;
+  let fastest = pace_seconds
+    .iter()
+    .copied()
+    .filter(|&pace| is_pace(pace))
+    .min_by(f32::total_cmp);
+  pace_seconds
+    .iter()
+    .map(|&pace| {
+      let fastest = fastest.filter(|_| is_pace(pace))? as f64;
+      let delta = pace as f64 - fastest;
+      Some(match format {
+        BarLabel::DeltaSeconds => format!("+{} s", delta.round() as u64),
+        BarLabel::Percent => {
+          format!(
+            "+{}%",
+            (delta / fastest * 100.0).round() as u64
+          )
+        }
+      })
+    })
+    .collect()
+}
+
+/// Most ticks on the pace axis under the pace bars
+pub const MAX_AXIS_TICKS: usize = 5;
+
+/// Ticks of the pace axis under the pace bars: round paces over the
+/// normalized range, each with its position as a fraction of the full
+/// bar on the scale `pace_bar_fractions` draws the bars with, so a bar
+/// ends on the tick of its pace. Ticks past the full bar are dropped.
+pub fn pace_axis_ticks(
+  pace_seconds: &[f32],
+  normalization: Normalization,
+  interval_s: f64,
+) -> Vec<(f64, f32)> {
+  let paces: Vec<f64> = pace_seconds.iter().map(|&p| p as f64).collect();
+  let Some((fastest, slowest)) = normalized_range(&paces, normalization) else {
+    return Vec::new();
+  };
+  let min_denominator = full_bar_pace(fastest);
+  round_ticks(
+    fastest,
+    slowest,
+    interval_s,
+    MAX_AXIS_TICKS,
+  )
+  .into_iter()
+  .filter(|&pace| pace > 0.0)
+  .map(|pace| {
+    (
+      pace,
+      pace_percentage(min_denominator, pace as f32),
+    )
+  })
+  .filter(|&(_, fraction)| fraction <= 1.0)
+  .collect()
+}
+
+/// Multiples of `interval` from the last one at or below `low` to the
+/// first one at or above `high`, so a range within one interval still
+/// gets the ticks around it. The step is widened to a multiple of
+/// `interval` until there are at most `max_ticks` (but never fewer than
+/// the two around the range).
+pub fn round_ticks(
+  low: f64,
+  high: f64,
+  interval: f64,
+  max_ticks: usize,
+) -> Vec<f64> {
+  let valid =
+    low.is_finite() && high.is_finite() && low <= high && interval > 0.0;
+  if !valid {
+    return Vec::new();
+  }
+  let mut step = interval;
+  loop {
+    let (first, last) = (
+      (low / step).floor(),
+      (high / step).ceil(),
+    );
+    let count = (last - first) as usize + 1;
+    if count <= max_ticks.max(2) {
+      return (0..count).map(|i| (first + i as f64) * step).collect();
+    }
+    step += interval;
+  }
+}
+
+/// Pace of a full-length pace bar: the fastest pace rounded down to 30 s
+fn full_bar_pace(fastest: f64) -> f32 {
+  ((fastest / 30.0).floor() * 30.0) as f32
+}
+
+/// Formats seconds as "h:mm:ss", or "m:ss" under an hour
+pub fn format_duration(seconds: f64) -> String {
+  if !seconds.is_finite() || seconds <= 0.0 {
+    return "0:00".to_string();
+  }
+
+  let total = seconds.round() as u64;
+  let (hours, minutes, secs) = (
+    total / 3600,
+    (total % 3600) / 60,
+    total % 60,
+  );
+  if hours > 0 {
+    format!("{}:{:02}:{:02}", hours, minutes, secs)
+  } else {
+    format!("{}:{:02}", minutes, secs)
+  }
+}
+
+/// Formats a Unix timestamp (UTC) as "16 Oct 2026"
+pub fn format_date(unix_seconds: i64) -> String {
+  const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+    "Nov", "Dec",
+  ];
+
+  // Civil-from-days conversion (proleptic Gregorian calendar)
+  let days = unix_seconds.div_euclid(86_400) + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+    - day_of_era / 146_096)
+    / 365;
+  let day_of_year =
+    day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{} {} {}",
+    day,
+    MONTHS[(month - 1) as usize],
+    year
+  )
+}
+
+/// Limits a pace ("m:ss") to `cap_secs` seconds per km
+pub fn cap_pace(pace: &str, cap_secs: f32) -> String {
+  if convert_pace_to_sec(pace) > cap_secs {
+    sec_to_pace(cap_secs)
+  } else {
+    pace.to_string()
+  }
+}
+
+/// Formats seconds per km as "m:ss", limited to `cap_secs`
+pub fn format_capped_pace(
+  pace_seconds: f64,
+  cap_secs: f32,
+  format: &NumberFormat,
+) -> String {
+  format_pace(
+    pace_seconds.min(cap_secs as f64),
+    format.pace_rounding,
+  )
+}
+
+/// Main lap panel column: pace (capped for the sport), or speed in km/h
+/// when the sport shows speed
+pub fn lap_stat_texts(
+  lap: &LapData,
+  sport: SportProfile,
+  format: &NumberFormat,
+) -> Vec<String> {
+  if sport.shows_speed() {
+    lap
+      .avg_speed
+      .iter()
+      .map(|speed| format_decimal(speed * 3.6, 1))
+      .collect()
+  } else {
+    lap
+      .avg_speed
+      .iter()
+      .map(|&speed| {
+        format_capped_pace(
+          speed_to_pace_secs(speed),
+          sport.pace_cap_secs(),
+          format,
+        )
+      })
+      .collect()
+  }
+}
+
+/// Lap panel heart rate column; laps without one show `NO_VALUE`
+pub fn lap_heart_rate_texts(lap: &LapData) -> Vec<String> {
+  lap
+    .avg_heart_rate
+    .iter()
+    .map(|hr| {
+      hr.map_or(NO_VALUE.to_string(), |hr| {
+        hr.to_string()
+      })
+    })
+    .collect()
+}
+
+/// Lap panel power column (W); laps without one show `NO_VALUE`
+pub fn lap_power_texts(lap: &LapData) -> Vec<String> {
+  lap
+    .avg_power
+    .iter()
+    .map(|power| {
+      power.map_or(NO_VALUE.to_string(), |power| {
+        power.to_string()
+      })
+    })
+    .collect()
+}
+
+/// Detail lap panel column: stride length (m), or cadence (rpm) when the
+/// sport shows speed. Laps without a stride show `NO_VALUE`.
+pub fn lap_detail_texts(
+  lap: &LapData,
+  sport: SportProfile,
+  format: &NumberFormat,
+) -> Vec<String> {
+  if sport.shows_speed() {
+    lap.avg_cadence.iter().map(|c| format!("{}", c)).collect()
+  } else {
+    lap
+      .avg_step_length
+      .iter()
+      .map(|length| {
+        length.map_or(NO_VALUE.to_string(), |length| {
+          format_stride(length, format)
+        })
+      })
+      .collect()
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -104,6 +803,9 @@ mod tests {
 
     // 2.77 m/s ≈ 6:00 min/km (10 km/h)
     assert_eq!(speed_to_pace(2.77), "6:01");
+    assert_eq!(speed_to_pace(2.7777), "6:00");
+    // 359.7 s rounds up to a whole minute instead of "5:60"
+    assert_eq!(speed_to_pace(2.78), "6:00");
 
     // 5.0 m/s = 3:20 min/km (18 km/h)
     assert_eq!(speed_to_pace(5.0), "3:20");
@@ -115,6 +817,194 @@ mod tests {
     assert_eq!(speed_to_pace(-1.0), "0:00");
   }
 
+  #[test]
+  fn test_sec_to_pace() {
+    assert_eq!(sec_to_pace(300.0), "5:00");
+    assert_eq!(sec_to_pace(330.0), "5:30");
+    assert_eq!(sec_to_pace(0.0), "0:00");
+    assert_eq!(sec_to_pace(f32::NAN), "0:00");
+  }
+
+  #[test]
+  fn test_format_pace_carries_seconds() {
+    let half_up = PaceRounding::RoundHalfUp;
+    assert_eq!(format_pace(359.5, half_up), "6:00");
+    assert_eq!(format_pace(359.4, half_up), "5:59");
+    assert_eq!(format_pace(59.5, half_up), "1:00");
+    assert_eq!(format_pace(3599.5, half_up), "60:00");
+
+    assert_eq!(
+      format_pace(359.5, PaceRounding::Floor),
+      "5:59"
+    );
+    assert_eq!(
+      format_pace(359.99, PaceRounding::Floor),
+      "5:59"
+    );
+    assert_eq!(
+      format_pace(360.0, PaceRounding::Floor),
+      "6:00"
+    );
+  }
+
+  #[test]
+  fn test_format_pace_invalid() {
+    for rounding in [PaceRounding::RoundHalfUp, PaceRounding::Floor] {
+      assert_eq!(format_pace(0.0, rounding), "0:00");
+      assert_eq!(format_pace(-30.0, rounding), "0:00");
+      assert_eq!(format_pace(f64::NAN, rounding), "0:00");
+      assert_eq!(
+        format_pace(f64::INFINITY, rounding),
+        "0:00"
+      );
+    }
+    assert_eq!(speed_to_pace_secs(0.0), 0.0);
+    assert_eq!(speed_to_pace_secs(f64::NAN), 0.0);
+    assert_eq!(speed_to_pace_secs(4.0), 250.0);
+  }
+
+  #[test]
+  fn test_format_distance_and_stride() {
+    let format = NumberFormat::default();
+    assert_eq!(
+      format_distance(12_345.0, Units::Metric, &format),
+      "12.35"
+    );
+    assert_eq!(
+      format_distance(1609.344, Units::Imperial, &format),
+      "1.00"
+    );
+    assert_eq!(
+      format_distance(0.0, Units::Metric, &format),
+      "0.00"
+    );
+    assert_eq!(
+      format_distance(-5.0, Units::Metric, &format),
+      "0.00"
+    );
+    assert_eq!(
+      format_distance(f64::NAN, Units::Metric, &format),
+      "0.00"
+    );
+
+    let format = NumberFormat {
+      distance_decimals: 0,
+      stride_decimals: 1,
+      ..NumberFormat::default()
+    };
+    assert_eq!(
+      format_distance(4_600.0, Units::Metric, &format),
+      "5"
+    );
+    // 11.3 / 10 is 1.1300000000000001 in floating point
+    assert_eq!(format_stride(11.3, &format), "1.1");
+    assert_eq!(format_stride(-2.0, &format), "0.0");
+    assert_eq!(format_stride(f64::NAN, &format), "0.0");
+  }
+
+  #[test]
+  fn test_elevation_change() {
+    // Half-meter jitter around 100 m adds nothing
+    let jitter: Vec<Option<f64>> = (0..20)
+      .map(|i| Some(100.0 + if i % 2 == 0 { 0.5 } else { -0.4 }))
+      .collect();
+    assert_eq!(
+      elevation_change(&jitter, ELEVATION_HYSTERESIS_M),
+      (0.0, 0.0)
+    );
+
+    // A slow climb still adds up; missing altitudes are skipped
+    let altitudes = [
+      None,
+      Some(10.0),
+      Some(10.6),
+      Some(11.2),
+      None,
+      Some(15.0),
+      Some(14.5),
+      Some(12.0),
+    ];
+    let (gain, loss) = elevation_change(&altitudes, ELEVATION_HYSTERESIS_M);
+    assert!((gain - 5.0).abs() < 1e-9);
+    assert!((loss - 3.0).abs() < 1e-9);
+    assert_eq!(elevation_change(&[], 1.0), (0.0, 0.0));
+
+    // The device total only stands in for missing altitudes
+    assert!((activity_gain(&altitudes, Some(90)).unwrap() - 5.0).abs() < 1e-9);
+    assert_eq!(
+      activity_gain(&[None], Some(90)),
+      Some(90.0)
+    );
+    assert_eq!(activity_gain(&[], None), None);
+
+    assert_eq!(
+      format_elevation_gain(411.6, Units::Metric),
+      "Elev +412 m"
+    );
+    assert_eq!(
+      format_elevation_gain(100.0, Units::Imperial),
+      "Elev +328 ft"
+    );
+  }
+
+  #[test]
+  fn test_summary_parts() {
+    let format = NumberFormat::default();
+    let session = SessionSummary {
+      total_distance: Some(10020.0),
+      total_timer_time: Some(3151.0),
+      total_calories: Some(531),
+      avg_heart_rate: Some(156),
+      ..Default::default()
+    };
+    assert_eq!(
+      summary_parts(&session, &format),
+      vec!["10.02 km", "52:31", "531 kcal", "156 bpm"]
+    );
+
+    // Totals the file did not record are left out
+    let session = SessionSummary {
+      total_distance: Some(5000.0),
+      avg_heart_rate: Some(140),
+      ..Default::default()
+    };
+    assert_eq!(
+      summary_parts(&session, &format),
+      vec!["5.00 km", "140 bpm"]
+    );
+    assert!(summary_parts(&SessionSummary::default(), &format).is_empty());
+  }
+
+  #[test]
+  fn test_format_decimal() {
+    assert_eq!(format_decimal(1.26, 1), "1.3");
+    assert_eq!(format_decimal(-0.001, 2), "0.00");
+    assert_eq!(format_decimal(-1.5, 1), "-1.5");
+    assert_eq!(format_decimal(f64::NAN, 2), "--");
+    assert_eq!(
+      format_decimal(f64::NEG_INFINITY, 0),
+      "--"
+    );
+  }
+
+  #[test]
+  fn test_format_duration() {
+    assert_eq!(format_duration(0.0), "0:00");
+    assert_eq!(format_duration(65.4), "1:05");
+    assert_eq!(format_duration(3723.0), "1:02:03");
+    assert_eq!(format_duration(f64::NAN), "0:00");
+  }
+
+  #[test]
+  fn test_format_date() {
+    assert_eq!(format_date(0), "1 Jan 1970");
+    assert_eq!(format_date(951_782_400), "29 Feb 2000");
+    assert_eq!(
+      format_date(1_792_108_800),
+      "16 Oct 2026"
+    );
+  }
+
   #[test]
   fn test_semicircles_to_degrees() {
     // 0 semicircles = 0 degrees
@@ -133,6 +1023,24 @@ mod tests {
     assert!(neg_degrees < -179.9 && neg_degrees > -180.0);
   }
 
+  #[test]
+  fn test_position_degrees() {
+    assert_eq!(position_degrees(0, 90.0), Some(0.0));
+    let bangkok = position_degrees(1_199_011_703, 180.0).unwrap();
+    assert!((bangkok - 100.5).abs() < 1e-6);
+    // The invalid value, even where it would fit the range
+    assert_eq!(
+      position_degrees(INVALID_SEMICIRCLES, 180.0),
+      None
+    );
+    // Beyond ±90° is no latitude
+    assert_eq!(
+      position_degrees(i32::MAX / 2 + 1_000_000, 90.0),
+      None
+    );
+    assert!(position_degrees(-(i32::MAX / 2), 90.0).is_some());
+  }
+
   #[test]
   fn test_get_bounds() {
     let points = vec![(10.0, 20.0), (5.0, 25.0), (15.0, 15.0), (8.0, 30.0)];
@@ -156,6 +1064,131 @@ mod tests {
     assert_eq!(lon_max, 20.0);
   }
 
+  #[test]
+  fn test_get_bounds_antimeridian() {
+    // 179.9°E to 179.9°W is 0.2° wide, not 359.8°
+    let points = vec![(-17.0, 179.9), (-17.1, 179.95), (-17.2, -179.9)];
+    let (_, (lon_min, lon_max)) = get_bounds(&points);
+    assert_eq!(lon_min, 179.9);
+    assert!((lon_max - 180.1).abs() < 1e-9);
+    assert!((wrap_longitude(-179.9, lon_max) - 180.1).abs() < 1e-9);
+    assert_eq!(wrap_longitude(179.95, lon_max), 179.95);
+
+    // Routes around the prime meridian are not wrapped
+    let points = vec![(51.5, -0.1), (51.5, 0.1)];
+    assert_eq!(get_bounds(&points).1, (-0.1, 0.1));
+    assert_eq!(wrap_longitude(-0.1, 0.1), -0.1);
+  }
+
+  /// Eastward track along the equator, alternately `jitter` degrees north
+  /// and south of it
+  fn noisy_track(count: usize, jitter: f64) -> Vec<(f64, f64)> {
+    (0..count)
+      .map(|i| {
+        let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+        (side * jitter, 100.0 + i as f64 * 0.0001)
+      })
+      .collect()
+  }
+
+  /// Mean distance (degrees) of the track from the equator
+  fn mean_offset(points: &[(f64, f64)]) -> f64 {
+    points.iter().map(|p| p.0.abs()).sum::<f64>() / points.len() as f64
+  }
+
+  #[test]
+  fn test_smooth_gps_moving_average() {
+    let track = noisy_track(50, 0.0001);
+    let method = GpsSmoothingMethod::MovingAverage { window: 5 };
+    let smoothed = smooth_gps_points(&track, &[], method);
+
+    assert_eq!(smoothed.len(), track.len());
+    assert!(mean_offset(&smoothed) < mean_offset(&track) / 3.0);
+    // The ends stay put; along the track the points keep their place
+    assert_eq!(smoothed[0], track[0]);
+    assert_eq!(smoothed[49], track[49]);
+    assert!((smoothed[20].1 - track[20].1).abs() < 1e-12);
+
+    // A window of one point leaves the track as it is
+    let method = GpsSmoothingMethod::MovingAverage { window: 1 };
+    assert_eq!(
+      smooth_gps_points(&track, &[], method),
+      track
+    );
+  }
+
+  #[test]
+  fn test_smooth_gps_kalman() {
+    let track = noisy_track(50, 0.0001);
+    let method = GpsSmoothingMethod::Kalman {
+      process_noise_m: 1.0,
+      measurement_noise_m: 5.0,
+    };
+    let smoothed = smooth_gps_points(&track, &[], method);
+    assert_eq!(smoothed.len(), track.len());
+    assert!(mean_offset(&smoothed[10..]) < mean_offset(&track) / 3.0);
+    assert!(smooth_gps_points(&[], &[], method).is_empty());
+  }
+
+  #[test]
+  fn test_smooth_gps_stretches_and_antimeridian() {
+    // Two stretches far apart: neither is pulled toward the other
+    let mut track = vec![(0.0, 10.0); 5];
+    track.extend(vec![(1.0, 11.0); 5]);
+    let method = GpsSmoothingMethod::MovingAverage { window: 5 };
+    assert_eq!(
+      smooth_gps_points(&track, &[4], method),
+      track
+    );
+    assert_ne!(
+      smooth_gps_points(&track, &[], method),
+      track
+    );
+
+    // Averaging across the antimeridian stays near it
+    let track = vec![(0.0, 179.99), (0.0, -179.99), (0.0, 179.99)];
+    let smoothed = smooth_gps_points(&track, &[], method);
+    assert!(smoothed[1].1.abs() > 179.98);
+  }
+
+  #[test]
+  fn test_simplify_indices() {
+    // A straight run east, then north: the corner is the first point kept
+    let mut track: Vec<(f64, f64)> =
+      (0..50).map(|i| (0.0, i as f64 * 0.0001)).collect();
+    track.extend((1..50).map(|i| (i as f64 * 0.0001, 0.0049)));
+    let indices = simplify_indices(&track, &[], 3);
+    assert_eq!(indices, vec![0, 49, 98]);
+
+    // Points on straight lines add nothing once the corner is in
+    assert_eq!(
+      simplify_indices(&track, &[], 10),
+      vec![0, 49, 98]
+    );
+
+    // A wiggly track fills the budget, ends included
+    let wiggly: Vec<(f64, f64)> = (0..1000)
+      .map(|i| {
+        (
+          (i as f64 * 0.1).sin() * 0.001,
+          i as f64 * 0.0001,
+        )
+      })
+      .collect();
+    let indices = simplify_indices(&wiggly, &[], 100);
+    assert_eq!(indices.len(), 100);
+    assert_eq!((indices[0], indices[99]), (0, 999));
+    assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // Both sides of a break are kept; short tracks are untouched
+    let indices = simplify_indices(&track, &[20], 3);
+    assert!(indices.contains(&20) && indices.contains(&21));
+    assert_eq!(
+      simplify_indices(&track[..5], &[], 10).len(),
+      5
+    );
+  }
+
   #[test]
   fn test_string_space() {
     // Testing with size=100 (3 digits), various indices
@@ -188,6 +1221,8 @@ mod tests {
     assert_eq!(convert_pace_to_sec("6:30"), 390.0);
     assert_eq!(convert_pace_to_sec("3:20"), 200.0);
     assert_eq!(convert_pace_to_sec("0:45"), 45.0);
+    assert_eq!(convert_pace_to_sec(NO_PACE), 0.0);
+    assert_eq!(parse_pace(NO_PACE), None);
   }
 
   #[test]
@@ -216,4 +1251,252 @@ mod tests {
     // Division by zero - will return infinity
     assert!(pace_percentage(100.0, 0.0).is_infinite());
   }
+
+  #[test]
+  fn test_pace_bar_fractions() {
+    let paces = [300.0, 330.0, 900.0];
+    // Min-max keeps the plain ratio to the fastest lap
+    assert_eq!(
+      pace_bar_fractions(&paces, Normalization::MinMax),
+      vec![1.0, 300.0 / 330.0, 300.0 / 900.0]
+    );
+    // The climb is clamped to the 50th percentile pace
+    assert_eq!(
+      pace_bar_fractions(
+        &paces,
+        Normalization::Percentile {
+          low: 0.0,
+          high: 50.0,
+        },
+      ),
+      vec![1.0, 300.0 / 330.0, 300.0 / 330.0]
+    );
+    assert!(pace_bar_fractions(&[], Normalization::ZScoreClamped).is_empty());
+  }
+
+  #[test]
+  fn test_round_ticks() {
+    assert_eq!(
+      round_ticks(275.0, 335.0, 30.0, 5),
+      vec![270.0, 300.0, 330.0, 360.0]
+    );
+    // Within one interval: the ticks around it, or just the one it is on
+    assert_eq!(
+      round_ticks(305.0, 320.0, 30.0, 5),
+      vec![300.0, 330.0]
+    );
+    assert_eq!(
+      round_ticks(300.0, 300.0, 30.0, 5),
+      vec![300.0]
+    );
+    // 3:00 to 10:00 would take 15 ticks; 2:00 steps take 5
+    assert_eq!(
+      round_ticks(180.0, 600.0, 30.0, 5),
+      vec![120.0, 240.0, 360.0, 480.0, 600.0]
+    );
+    assert!(round_ticks(300.0, 200.0, 30.0, 5).is_empty());
+    assert!(round_ticks(200.0, 300.0, 0.0, 5).is_empty());
+  }
+
+  #[test]
+  fn test_pace_axis_ticks() {
+    let paces = [300.0, 330.0, 345.0];
+    let ticks = pace_axis_ticks(&paces, Normalization::MinMax, 30.0);
+    let fractions = pace_bar_fractions(&paces, Normalization::MinMax);
+    assert_eq!(
+      ticks,
+      vec![(300.0, 1.0), (330.0, fractions[1]), (360.0, 300.0 / 360.0)]
+    );
+
+    // The percentile scale ends at 330, like the bars
+    let ticks = pace_axis_ticks(
+      &[300.0, 330.0, 900.0],
+      Normalization::Percentile {
+        low: 0.0,
+        high: 50.0,
+      },
+      30.0,
+    );
+    assert_eq!(ticks.last().unwrap().0, 330.0);
+    // A tick below the full bar's pace would be past its end
+    let ticks = pace_axis_ticks(&paces, Normalization::MinMax, 45.0);
+    assert_eq!(ticks[0].0, 315.0);
+    assert!(pace_axis_ticks(&[], Normalization::MinMax, 30.0).is_empty());
+  }
+
+  #[test]
+  fn test_pace_bar_labels() {
+    let paces = [330.0, 316.0, 345.4, 0.0];
+    let label = |text: &str| Some(text.to_string());
+    assert_eq!(
+      pace_bar_labels(&paces, BarLabel::DeltaSeconds),
+      vec![label("+14 s"), label("+0 s"), label("+29 s"), None]
+    );
+    assert_eq!(
+      pace_bar_labels(&paces, BarLabel::Percent),
+      vec![label("+4%"), label("+0%"), label("+9%"), None]
+    );
+
+    // Tied fastest laps both read zero
+    assert_eq!(
+      pace_bar_labels(&[300.0, 300.0], BarLabel::DeltaSeconds),
+      vec![label("+0 s"), label("+0 s")]
+    );
+    assert!(pace_bar_labels(&[], BarLabel::Percent).is_empty());
+  }
+
+  fn lap() -> LapData {
+    LapData {
+      avg_heart_rate: vec![Some(150), None],
+      avg_power: vec![Some(214), None],
+      enhanced_avg_speed: vec!["5:00".to_string(), "40:00".to_string()],
+      avg_step_length: vec![Some(12.5), None],
+      total_distance: vec![1000.0, 1000.0],
+      avg_speed: vec![3.33, 0.42],
+      avg_cadence: vec![85, 0],
+      total_elapsed_time: vec![300.0, 2400.0],
+    }
+  }
+
+  #[test]
+  fn test_cap_pace() {
+    assert_eq!(cap_pace("5:00", 900.0), "5:00");
+    assert_eq!(cap_pace("40:00", 900.0), "15:00");
+    assert_eq!(cap_pace("40:00", 1800.0), "30:00");
+  }
+
+  #[test]
+  fn test_lap_texts_follow_sport() {
+    let lap = lap();
+    let format = NumberFormat::default();
+    assert_eq!(
+      lap_stat_texts(&lap, SportProfile::Running, &format),
+      vec!["5:00", "15:00"]
+    );
+    assert_eq!(
+      lap_stat_texts(&lap, SportProfile::Walking, &format),
+      vec!["5:00", "30:00"]
+    );
+    assert_eq!(
+      lap_stat_texts(&lap, SportProfile::Cycling, &format),
+      vec!["12.0", "1.5"]
+    );
+    assert_eq!(
+      lap_detail_texts(&lap, SportProfile::Running, &format),
+      vec!["1.25", "-"]
+    );
+    assert_eq!(
+      lap_detail_texts(&lap, SportProfile::Cycling, &format),
+      vec!["85", "0"]
+    );
+    // The second lap has no heart rate
+    assert_eq!(
+      lap_heart_rate_texts(&lap),
+      vec!["150", "-"]
+    );
+    assert_eq!(lap_power_texts(&lap), vec!["214", "-"]);
+  }
+
+  #[test]
+  fn test_lap_pace_follows_rounding() {
+    let mut lap = lap();
+    // 1000 / 2.7816 m/s = 359.5 s per km
+    lap.avg_speed = vec![2.7816];
+    let format = |pace_rounding| NumberFormat {
+      pace_rounding,
+      ..NumberFormat::default()
+    };
+    assert_eq!(
+      lap_stat_texts(
+        &lap,
+        SportProfile::Running,
+        &format(PaceRounding::RoundHalfUp)
+      ),
+      vec!["6:00"]
+    );
+    assert_eq!(
+      lap_stat_texts(
+        &lap,
+        SportProfile::Running,
+        &format(PaceRounding::Floor)
+      ),
+      vec!["5:59"]
+    );
+  }
+
+  #[test]
+  fn test_bearing_cardinal_directions() {
+    let origin = (0.0, 0.0);
+    let at = |to| bearing(origin, to).unwrap();
+    assert!(at((1.0, 0.0)).abs() < 1e-9);
+    assert!((at((0.0, 1.0)) - 90.0).abs() < 1e-9);
+    assert!((at((-1.0, 0.0)) - 180.0).abs() < 1e-9);
+    assert!((at((0.0, -1.0)) - 270.0).abs() < 1e-9);
+    assert!((at((1.0, 1.0)) - 45.0).abs() < 0.1);
+    assert_eq!(bearing(origin, origin), None);
+  }
+
+  #[test]
+  fn test_bearing_wraps_at_north() {
+    // Just west of north is near 360, never negative
+    let west = bearing((13.7, 100.5), (13.8, 100.499)).unwrap();
+    assert!(west > 359.0 && west < 360.0);
+    let east = bearing((13.7, 100.5), (13.8, 100.501)).unwrap();
+    assert!(east > 0.0 && east < 1.0);
+
+    assert_eq!(format_bearing(west), "N 359°");
+    assert_eq!(format_bearing(359.7), "N 0°");
+    assert_eq!(format_bearing(47.0), "NE 47°");
+    assert_eq!(format_bearing(200.0), "S 200°");
+    assert_eq!(compass_point(337.4), "NW");
+    assert_eq!(compass_point(337.6), "N");
+    assert_eq!(compass_point(-10.0), "N");
+  }
+
+  #[test]
+  fn test_format_coordinates_quadrants() {
+    // Bangkok, Rio de Janeiro, Sydney and New York
+    assert_eq!(
+      format_coordinates(13.75632, 100.50177, 4),
+      "13.7563°N, 100.5018°E"
+    );
+    assert_eq!(
+      format_coordinates(-22.9068, -43.1729, 4),
+      "22.9068°S, 43.1729°W"
+    );
+    assert_eq!(
+      format_coordinates(-33.8688, 151.2093, 2),
+      "33.87°S, 151.21°E"
+    );
+    assert_eq!(
+      format_coordinates(40.7128, -74.006, 3),
+      "40.713°N, 74.006°W"
+    );
+  }
+
+  #[test]
+  fn test_format_coordinates_at_zero() {
+    // Null Island, on the equator and the zero meridian
+    assert_eq!(
+      format_coordinates(0.0, 0.0, 4),
+      "0.0000°N, 0.0000°E"
+    );
+    assert_eq!(
+      format_coordinates(-0.0, -0.0, 1),
+      "0.0°N, 0.0°E"
+    );
+    // Just south and west, rounding to zero
+    assert_eq!(
+      format_coordinates(-0.00001, -0.00004, 4),
+      "0.0000°N, 0.0000°E"
+    );
+    assert_eq!(
+      format_coordinates(-0.00006, 51.4779, 4),
+      "0.0001°S, 51.4779°E"
+    );
+    assert_eq!(
+      format_coordinates(51.4779, -0.0015, 0),
+      "51°N, 0°E"
+    );
+  }
 }
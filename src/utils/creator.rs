@@ -1,30 +1,245 @@
+use std::{fs, path::Path};
+
 use anyhow::Result;
 use opencv::{
   core::{self, Mat},
   imgcodecs, videoio,
 };
 
+use crate::configs::{ImageFormat, ImageOutputOptions, VideoCodec};
+
+/// zlib level used for PNGs in deterministic mode
+const DETERMINISTIC_PNG_COMPRESSION: i32 = 3;
+
 pub fn image_creator(output_file: &str, image: &Mat) -> Result<()> {
-  imgcodecs::imwrite(output_file, image, &core::Vector::new())?;
+  image_creator_with_options(output_file, image, false)
+}
 
+/// Writes an image to a temporary file next to `output_file`, then
+/// renames it into place, so a reader never sees a half-written image
+pub fn image_creator_atomic(output_file: &Path, image: &Mat) -> Result<()> {
+  let name = output_file
+    .file_name()
+    .unwrap_or_default()
+    .to_string_lossy();
+  // Keeps the extension, which picks the encoder
+  let temp = output_file.with_file_name(format!(".tmp-{}", name));
+  image_creator(&temp.to_string_lossy(), image)?;
+  fs::rename(&temp, output_file)?;
   Ok(())
 }
 
+/// Writes an image; `deterministic` pins the PNG encoder parameters instead
+/// of relying on the OpenCV build's defaults
+pub fn image_creator_with_options(
+  output_file: &str,
+  image: &Mat,
+  deterministic: bool,
+) -> Result<()> {
+  image_creator_with_format(
+    output_file,
+    image,
+    &ImageOutputOptions::default(),
+    deterministic,
+  )
+}
+
+/// Writes an image encoded as `options` asks, failing if the format does
+/// not match the output extension
+pub fn image_creator_with_format(
+  output_file: &str,
+  image: &Mat,
+  options: &ImageOutputOptions,
+  deterministic: bool,
+) -> Result<()> {
+  let format = options.resolve(output_file)?;
+  let params = imwrite_params(format, deterministic);
+  imgcodecs::imwrite(output_file, image, &params)?;
+
+  Ok(())
+}
+
+/// `imwrite` flags for `format`; without one, deterministic mode still pins
+/// the PNG compression
+fn imwrite_params(
+  format: Option<ImageFormat>,
+  deterministic: bool,
+) -> core::Vector<i32> {
+  let mut params = core::Vector::new();
+  match format {
+    Some(ImageFormat::Png { compression }) => {
+      params.push(imgcodecs::IMWRITE_PNG_COMPRESSION);
+      params.push(compression);
+    }
+    Some(ImageFormat::Jpeg { quality }) => {
+      params.push(imgcodecs::IMWRITE_JPEG_QUALITY);
+      params.push(quality);
+    }
+    Some(ImageFormat::Webp { quality }) => {
+      params.push(imgcodecs::IMWRITE_WEBP_QUALITY);
+      params.push(quality);
+    }
+    None if deterministic => {
+      params.push(imgcodecs::IMWRITE_PNG_COMPRESSION);
+      params.push(DETERMINISTIC_PNG_COMPRESSION);
+    }
+    None => {}
+  }
+  if deterministic {
+    params.push(imgcodecs::IMWRITE_PNG_STRATEGY);
+    params.push(imgcodecs::IMWRITE_PNG_STRATEGY_DEFAULT);
+  }
+  params
+}
+
 pub fn video_creator(
   width: i32,
   height: i32,
   fps: f64,
   output_file: &str,
 ) -> Result<videoio::VideoWriter> {
-  let fourcc = videoio::VideoWriter::fourcc('m', 'p', '4', 'v')?;
-  let video_size = core::Size::new(width, height);
-  let video = videoio::VideoWriter::new(
+  video_creator_with_codec(
+    width,
+    height,
+    fps,
     output_file,
-    fourcc,
+    VideoCodec::default(),
+  )
+}
+
+pub fn video_creator_with_codec(
+  width: i32,
+  height: i32,
+  fps: f64,
+  output_file: &str,
+  codec: VideoCodec,
+) -> Result<videoio::VideoWriter> {
+  video_creator_with_options(
+    width,
+    height,
     fps,
-    video_size,
-    true,
-  )?;
+    output_file,
+    codec,
+    false,
+  )
+}
+
+/// Opens a video writer; `deterministic` pins the FFmpeg backend and
+/// disables hardware encoding, whose output varies between devices
+pub fn video_creator_with_options(
+  width: i32,
+  height: i32,
+  fps: f64,
+  output_file: &str,
+  codec: VideoCodec,
+  deterministic: bool,
+) -> Result<videoio::VideoWriter> {
+  let [a, b, c, d] = codec.fourcc();
+  let fourcc = videoio::VideoWriter::fourcc(a, b, c, d)?;
+  let video_size = core::Size::new(width, height);
+  let video = if deterministic {
+    let params = core::Vector::from_slice(&[
+      videoio::VIDEOWRITER_PROP_HW_ACCELERATION,
+      videoio::VIDEO_ACCELERATION_NONE,
+    ]);
+    videoio::VideoWriter::new_with_backend_params(
+      output_file,
+      videoio::CAP_FFMPEG,
+      fourcc,
+      fps,
+      video_size,
+      &params,
+    )?
+  } else {
+    videoio::VideoWriter::new(
+      output_file,
+      fourcc,
+      fps,
+      video_size,
+      true,
+    )?
+  };
 
   Ok(video)
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{env, fs};
+
+  use opencv::{imgproc, prelude::*};
+
+  use super::*;
+
+  #[test]
+  fn test_jpeg_is_smaller_than_png() {
+    // OpenCV builds without the JPEG codec cannot run this
+    if !imgcodecs::have_image_writer(".jpg").unwrap() {
+      return;
+    }
+
+    // Smooth gradient with a route-like line, like a rendered frame
+    let (width, height) = (320, 240);
+    let mut frame = Mat::new_rows_cols_with_default(
+      height,
+      width,
+      core::CV_8UC3,
+      core::Scalar::all(0.0),
+    )
+    .unwrap();
+    for y in 0..height {
+      let row = core::Scalar::new(y as f64, 120.0, 255.0 - y as f64, 0.0);
+      imgproc::line(
+        &mut frame,
+        core::Point::new(0, y),
+        core::Point::new(width - 1, y),
+        row,
+        1,
+        imgproc::LINE_8,
+        0,
+      )
+      .unwrap();
+    }
+    imgproc::line(
+      &mut frame,
+      core::Point::new(20, 200),
+      core::Point::new(300, 30),
+      core::Scalar::new(0.0, 0.0, 255.0, 0.0),
+      4,
+      imgproc::LINE_AA,
+      0,
+    )
+    .unwrap();
+
+    let write = |name: &str, format| {
+      let path = env::temp_dir().join(name);
+      let path = path.to_str().unwrap();
+      let options = ImageOutputOptions {
+        format: Some(format),
+      };
+      image_creator_with_format(path, &frame, &options, false).unwrap();
+
+      let decoded = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR).unwrap();
+      assert_eq!(
+        (decoded.cols(), decoded.rows()),
+        (width, height)
+      );
+      fs::metadata(path).unwrap().len()
+    };
+
+    let png = write(
+      "runarium_format.png",
+      ImageFormat::Png { compression: 9 },
+    );
+    let jpeg = write(
+      "runarium_format.jpg",
+      ImageFormat::Jpeg { quality: 50 },
+    );
+    assert!(
+      jpeg < png,
+      "JPEG {} bytes, PNG {} bytes",
+      jpeg,
+      png
+    );
+  }
+}
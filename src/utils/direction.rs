@@ -0,0 +1,171 @@
+use crate::utils::progression::lerp;
+
+/// Share of the route at each end left without arrows, where the route
+/// starts and finishes
+pub const ARROW_END_MARGIN: f64 = 0.05;
+
+/// Where to draw one direction arrow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrowPlacement {
+  /// Arrow center in pixels
+  pub x: f64,
+  pub y: f64,
+  /// Direction of travel in radians: 0 points right and angles grow
+  /// clockwise, since image y points down
+  pub angle: f64,
+}
+
+/// Direction from `from` to `to` in image coordinates, in radians
+pub fn segment_angle(from: (f64, f64), to: (f64, f64)) -> f64 {
+  (to.1 - from.1).atan2(to.0 - from.0)
+}
+
+/// Arrows every `every_m` meters along the route, skipping the first and
+/// last `ARROW_END_MARGIN` of its length.
+///
+/// Each arrow sits on the segment its distance falls in and follows that
+/// segment's direction. Segments shorter than `min_segment_px` (GPS jitter
+/// or pauses) give no reliable direction, so their arrows are dropped.
+/// Nothing is placed when the distance series does not cover the points.
+pub fn arrow_placements(
+  points: &[(f64, f64)],
+  distances: &[f64],
+  every_m: f64,
+  min_segment_px: f64,
+) -> Vec<ArrowPlacement> {
+  let count = points.len();
+  if count < 2 || distances.len() < count || every_m <= 0.0 {
+    return Vec::new();
+  }
+
+  let (first, last) = (distances[0], distances[count - 1]);
+  let margin = (last - first) * ARROW_END_MARGIN;
+  let (start, end) = (first + margin, last - margin);
+
+  let mut placements = Vec::new();
+  let mut segment = 0;
+  let mut target = first + every_m;
+  while target <= end {
+    if target >= start {
+      // Segment whose distance range holds the target
+      while segment + 2 < count && distances[segment + 1] <= target {
+        segment += 1;
+      }
+      let (a, b) = (points[segment], points[segment + 1]);
+      let (da, db) = (
+        distances[segment],
+        distances[segment + 1],
+      );
+      let length = (b.0 - a.0).hypot(b.1 - a.1);
+      if length >= min_segment_px && db > da {
+        let t = ((target - da) / (db - da)).clamp(0.0, 1.0);
+        placements.push(ArrowPlacement {
+          x: lerp(a.0, b.0, t),
+          y: lerp(a.1, b.1, t),
+          angle: segment_angle(a, b),
+        });
+      }
+    }
+    target += every_m;
+  }
+  placements
+}
+
+/// Corners of a filled arrowhead `size` pixels long centered on
+/// `placement`: the tip, then the two back corners
+pub fn arrowhead(placement: &ArrowPlacement, size: f64) -> [(f64, f64); 3] {
+  let (dx, dy) = (
+    placement.angle.cos(),
+    placement.angle.sin(),
+  );
+  let (half, width) = (size / 2.0, size * 0.4);
+  let (x, y) = (placement.x, placement.y);
+  [
+    (x + dx * half, y + dy * half),
+    (
+      x - dx * half - dy * width,
+      y - dy * half + dx * width,
+    ),
+    (
+      x - dx * half + dy * width,
+      y - dy * half - dx * width,
+    ),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::{FRAC_PI_2, PI};
+
+  use super::*;
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+  }
+
+  #[test]
+  fn test_segment_angle() {
+    assert!(close(
+      segment_angle((0.0, 0.0), (5.0, 0.0)),
+      0.0
+    ));
+    // Down the image is clockwise from right
+    assert!(close(
+      segment_angle((0.0, 0.0), (0.0, 5.0)),
+      FRAC_PI_2
+    ));
+    assert!(close(
+      segment_angle((5.0, 0.0), (0.0, 0.0)),
+      PI
+    ));
+  }
+
+  #[test]
+  fn test_arrow_placements_follow_route() {
+    // 1 km east then 1 km south, 10 px per 100 m
+    let points = [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)];
+    let distances = [0.0, 1000.0, 2000.0];
+    let arrows = arrow_placements(&points, &distances, 500.0, 8.0);
+
+    assert_eq!(arrows.len(), 3);
+    assert_eq!((arrows[0].x, arrows[0].y), (50.0, 0.0));
+    assert!(close(arrows[0].angle, 0.0));
+    // The corner takes the direction of the segment it starts
+    assert_eq!((arrows[1].x, arrows[1].y), (100.0, 0.0));
+    assert!(close(arrows[1].angle, FRAC_PI_2));
+    assert_eq!(
+      (arrows[2].x, arrows[2].y),
+      (100.0, 50.0)
+    );
+  }
+
+  #[test]
+  fn test_arrow_placements_skip_ends_and_short_segments() {
+    // 100 m steps; the ends (first and last 5%) get no arrow
+    let points: Vec<(f64, f64)> =
+      (0..=20).map(|i| (i as f64 * 10.0, 0.0)).collect();
+    let distances: Vec<f64> = (0..=20).map(|i| i as f64 * 100.0).collect();
+    let arrows = arrow_placements(&points, &distances, 50.0, 4.0);
+    assert_eq!(arrows.first().unwrap().x, 10.0);
+    assert_eq!(arrows.last().unwrap().x, 190.0);
+
+    // Segments shorter than the arrow are skipped
+    assert!(arrow_placements(&points, &distances, 500.0, 12.0).is_empty());
+
+    // No distances, no arrows
+    assert!(arrow_placements(&points, &[], 500.0, 4.0).is_empty());
+  }
+
+  #[test]
+  fn test_arrowhead_points_along_angle() {
+    let placement = ArrowPlacement {
+      x: 10.0,
+      y: 10.0,
+      angle: 0.0,
+    };
+    let [tip, left, right] = arrowhead(&placement, 10.0);
+    assert_eq!(tip, (15.0, 10.0));
+    assert_eq!(left, (5.0, 14.0));
+    assert_eq!(right, (5.0, 6.0));
+  }
+}
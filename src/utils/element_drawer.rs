@@ -1,20 +1,77 @@
+use std::borrow::Cow;
+
 use anyhow::Result;
 use opencv::{core, imgproc, prelude::*};
 
 use crate::{
-  configs::Font,
+  configs::{
+    AttributionConfig, BarAxis, BarLabel, CadenceChartConfig, ChipStyle,
+    Corner, DateStampConfig, Font, LapDataConfig, LegendConfig,
+    LegendOrientation, Position, SafeArea, SafeAreaUnit, ShadowConfig,
+    SportProfile,
+  },
   types::drawer_data::{PositionRect, Rect, SizeRect},
+  utils::{
+    chart::{ChartAxes, ChartStyle},
+    converter::{format_decimal, sec_to_pace, string_space},
+    direction::{arrowhead, ArrowPlacement},
+    progression::lerp,
+    segment_color::GradientScale,
+  },
 };
 
+/// Width of the route line drawn by `Drawer::line`
+pub const ROUTE_LINE_THICKNESS: i32 = 4;
+/// Opacity of the background map credit
+const ATTRIBUTION_OPACITY: f64 = 0.7;
+/// Distance of corner texts from the safe area edges
+const CORNER_MARGIN: i32 = 20;
+/// Space between stacked corner text lines
+const LINE_GAP: i32 = 6;
+/// Height of a climb label's center above the climb
+const CLIMB_LABEL_RISE: i32 = 30;
+/// Drawn as a ring by `Drawer::text`, which the fonts cannot render
+const DEGREE_SIGN: char = '°';
+/// Space between the end of a pace bar and its value label
+const BAR_LABEL_GAP: i32 = 6;
+/// Length of a pace bar of the full-bar pace
+pub const PACE_BAR_WIDTH: i32 = 200;
+/// Length of the pace axis tick marks below the bars
+const AXIS_TICK_LENGTH: i32 = 4;
+/// Pace axis label size, relative to the lap panel's font scale
+const AXIS_LABEL_SCALE: f64 = 0.7;
+/// Opacity of the pace axis gridlines through the bars
+const GRIDLINE_ALPHA: f64 = 0.35;
+
 enum Align {
   Left,
+  Center,
   Right,
 }
 
+/// What a lap panel shows, to measure it before drawing
+pub struct LapPanelLayout<'a> {
+  pub config: &'a LapDataConfig,
+  pub sport: SportProfile,
+  /// Main column texts, one per lap
+  pub stats: &'a [String],
+  /// Detail column texts, one per lap
+  pub details: &'a [String],
+  /// Header of the extra field column, when it is shown
+  pub extra_label: Option<&'a str>,
+  /// Line above the header labels (e.g. the elevation gain), when shown
+  pub summary: Option<&'a str>,
+}
+
+#[derive(Clone)]
 pub struct Drawer {
   pub width: i32,
   pub height: i32,
   pub line: i32,
+  /// Region overlays are kept inside (whole frame by default)
+  pub safe_rect: core::Rect,
+  /// Draw every color with alpha 255, for transparent 4-channel canvases
+  pub opaque_alpha: bool,
 }
 
 impl Drawer {
@@ -23,7 +80,203 @@ impl Drawer {
       width,
       height,
       line: imgproc::LINE_AA,
+      safe_rect: core::Rect::new(0, 0, width, height),
+      opaque_alpha: false,
+    }
+  }
+
+  /// Draws with alpha 255 whatever the configured colors' alpha, so drawn
+  /// pixels show on a transparent canvas
+  pub fn with_opaque_alpha(mut self) -> Self {
+    self.opaque_alpha = true;
+    self
+  }
+
+  /// Restricts overlays to the frame minus the safe-area margins
+  pub fn with_safe_area(mut self, area: &SafeArea) -> Self {
+    let (w, h) = match area.unit {
+      SafeAreaUnit::Fraction => (self.width as f64, self.height as f64),
+      SafeAreaUnit::Pixels => (1.0, 1.0),
+    };
+    let left = ((area.left * w) as i32).max(0);
+    let top = ((area.top * h) as i32).max(0);
+    let right = ((area.right * w) as i32).max(0);
+    let bottom = ((area.bottom * h) as i32).max(0);
+    self.safe_rect = core::Rect::new(
+      left,
+      top,
+      (self.width - left - right).max(0),
+      (self.height - top - bottom).max(0),
+    );
+    self
+  }
+
+  /// Moves a point inside the safe area
+  pub fn clamp_to_safe(&self, x: i32, y: i32) -> (i32, i32) {
+    let area = self.safe_rect;
+    (
+      x.clamp(area.x, area.x + area.width),
+      y.clamp(area.y, area.y + area.height),
+    )
+  }
+
+  /// Shifts `rect` inside the safe area without resizing it; a box larger
+  /// than the area keeps its top-left corner inside
+  pub fn fit_rect(&self, rect: core::Rect) -> core::Rect {
+    let area = self.safe_rect;
+    core::Rect::new(
+      rect.x.min(area.x + area.width - rect.width).max(area.x),
+      rect.y.min(area.y + area.height - rect.height).max(area.y),
+      rect.width,
+      rect.height,
+    )
+  }
+
+  /// Outlines the safe area, for tuning margins
+  pub fn safe_area_guides(&self, frame: &mut Mat) -> Result<()> {
+    let magenta = self.color([255.0, 0.0, 255.0, 0.0]);
+    imgproc::rectangle(
+      frame,
+      self.safe_rect,
+      magenta,
+      2,
+      imgproc::LINE_8,
+      0,
+    )?;
+    Ok(())
+  }
+
+  /// Header position of a lap panel placed at `position`, shifted so the
+  /// whole panel stays in the safe area
+  pub fn lap_panel_start(
+    &self,
+    position: Position,
+    panel: &LapPanelLayout,
+  ) -> Result<(i32, i32)> {
+    let start = position.resolve((self.width, self.height));
+    let rect = self.lap_panel_rect(start, panel)?;
+    let fitted = self.fit_rect(rect);
+    Ok((
+      start.0 + fitted.x - rect.x,
+      start.1 + fitted.y - rect.y,
+    ))
+  }
+
+  /// Bounding box of a lap panel drawn with its header at `start`.
+  /// Heart rate, power and extra field columns are measured as three
+  /// digits and pace bars at their full length.
+  pub fn lap_panel_rect(
+    &self,
+    start: (i32, i32),
+    panel: &LapPanelLayout,
+  ) -> Result<core::Rect> {
+    let config = panel.config;
+    let (scale, thickness, font) = (
+      config.font_scale,
+      config.thickness,
+      config.font,
+    );
+    let (start_x, start_y) = start;
+
+    // Header labels sit 20px above the first row
+    let shift = config.power_shift();
+    let mut labels: Vec<(&str, i32)> =
+      panel.sport.lap_labels(config.show_power);
+    labels.extend(panel.extra_label.map(|label| (label, 400 + shift)));
+    let mut bounds = None;
+    for &(label, offset) in &labels {
+      let text = self.text_box(
+        label,
+        (start_x + offset, start_y - 20),
+        scale,
+        2,
+        font,
+      )?;
+      bounds = Some(union(bounds, text));
+    }
+    if let Some(summary) = panel.summary {
+      let (x, y) =
+        self.header_summary_start(summary, start, &labels, scale, font)?;
+      let text = self.text_box(summary, (x, y - 20), scale, 2, font)?;
+      bounds = Some(union(bounds, text));
+    }
+
+    for (i, stat) in panel.stats.iter().enumerate() {
+      let size = self.text_size(stat, scale, thickness, font)?;
+      let x = start_x - size.width / 2;
+      let y = start_y + i as i32 * (size.height + 5);
+      let mut columns = vec![(
+        string_space(panel.stats.len(), i + 1, stat),
+        0,
+      )];
+      if config.show_heart_rate {
+        columns.push(("000".to_string(), 300));
+      }
+      if config.show_power {
+        columns.push(("000".to_string(), 350));
+      }
+      if let (true, Some(detail)) = (
+        config.show_stride_length,
+        panel.details.get(i),
+      ) {
+        columns.push((detail.clone(), 350 + shift));
+      }
+      if panel.extra_label.is_some() {
+        columns.push(("000".to_string(), 420 + shift));
+      }
+      for (text, offset) in columns {
+        let text = self.text_box(
+          &text,
+          (x + offset, y),
+          scale,
+          thickness,
+          font,
+        )?;
+        bounds = Some(union(bounds, text));
+      }
+      if config.show_pace_bars {
+        let bar = core::Rect::new(
+          x + size.width + 60,
+          y - size.height,
+          200,
+          size.height,
+        );
+        bounds = Some(union(bounds, bar));
+        if config.bar_style.show_value_label {
+          let label = widest_bar_label(config.bar_style.label_format);
+          let label = self.text_box(
+            label,
+            (bar.x + bar.width + BAR_LABEL_GAP, y),
+            scale,
+            thickness,
+            font,
+          )?;
+          bounds = Some(union(bounds, label));
+        }
+      }
     }
+
+    Ok(bounds.unwrap_or(core::Rect::new(start_x, start_y, 0, 0)))
+  }
+
+  /// Box covered by `text` drawn with its baseline at `origin`, descenders
+  /// included
+  fn text_box(
+    &self,
+    text: &str,
+    origin: (i32, i32),
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<core::Rect> {
+    let (size, baseline) =
+      self.text_extent(text, font_scale, thickness, font)?;
+    Ok(core::Rect::new(
+      origin.0,
+      origin.1 - size.height,
+      size.width,
+      size.height + baseline,
+    ))
   }
 
   pub fn line(
@@ -33,10 +286,217 @@ impl Drawer {
     p2: core::Point,
     color: core::Scalar,
   ) -> Result<()> {
-    imgproc::line(frame, p1, p2, color, 4, self.line, 0)?;
+    imgproc::line(
+      frame,
+      p1,
+      p2,
+      color,
+      ROUTE_LINE_THICKNESS,
+      self.line,
+      0,
+    )?;
+    Ok(())
+  }
+
+  /// Draws the polyline `points` at `alpha` (0.0-1.0) over what is
+  /// beneath it; where the line crosses itself it is blended once
+  pub fn blended_polyline(
+    &self,
+    frame: &mut Mat,
+    points: &[core::Point],
+    color: core::Scalar,
+    thickness: i32,
+    alpha: f64,
+  ) -> Result<()> {
+    let alpha = alpha.clamp(0.0, 1.0);
+    if alpha <= 0.0 || points.len() < 2 {
+      return Ok(());
+    }
+    let mut polyline = core::Vector::<core::Vector<core::Point>>::new();
+    polyline.push(core::Vector::from_slice(points));
+    let mut layer = frame.try_clone()?;
+    imgproc::polylines(
+      &mut layer, &polyline, false, color, thickness, self.line, 0,
+    )?;
+    let mut blended = Mat::default();
+    core::add_weighted_def(
+      &layer,
+      alpha,
+      &*frame,
+      1.0 - alpha,
+      0.0,
+      &mut blended,
+    )?;
+    *frame = blended;
+    Ok(())
+  }
+
+  /// Draws the polyline `points` as dashes of `dash` pixels with gaps of
+  /// the same length; the pattern runs on across the corners
+  pub fn dashed_polyline(
+    &self,
+    frame: &mut Mat,
+    points: &[core::Point],
+    color: core::Scalar,
+    thickness: i32,
+    dash: i32,
+  ) -> Result<()> {
+    let dash = dash.max(1) as f64;
+    let mut along = 0.0;
+    for pair in points.windows(2) {
+      let (from, to) = (pair[0], pair[1]);
+      let length = ((to.x - from.x) as f64).hypot((to.y - from.y) as f64);
+      let at = |d: f64| {
+        let t = d / length;
+        core::Point::new(
+          lerp(from.x as f64, to.x as f64, t).round() as i32,
+          lerp(from.y as f64, to.y as f64, t).round() as i32,
+        )
+      };
+      let mut start = 0.0;
+      while start < length {
+        // Position within the current dash and gap
+        let phase = along % (2.0 * dash);
+        let drawing = phase < dash;
+        let step = (dash - phase % dash).min(length - start);
+        if drawing {
+          imgproc::line(
+            frame,
+            at(start),
+            at(start + step),
+            color,
+            thickness,
+            self.line,
+            0,
+          )?;
+        }
+        start += step;
+        along += step;
+      }
+    }
     Ok(())
   }
 
+  /// Blends a blurred, offset copy of the line `segments` into `frame`.
+  ///
+  /// Only the region around the segments is touched, so shadowing a few new
+  /// segments per video frame stays cheap. Draw the line itself afterwards.
+  pub fn shadow(
+    &self,
+    frame: &mut Mat,
+    segments: impl IntoIterator<Item = (core::Point, core::Point)> + Clone,
+    thickness: i32,
+    shadow: &ShadowConfig,
+  ) -> Result<()> {
+    let offset = core::Point::new(shadow.offset.0, shadow.offset.1);
+    let mut points = segments
+      .clone()
+      .into_iter()
+      .flat_map(|(a, b)| [a + offset, b + offset]);
+    let Some(first) = points.next() else {
+      return Ok(());
+    };
+    let (min, max) = points.fold((first, first), |(min, max), p| {
+      (
+        core::Point::new(p.x.min(min.x), p.y.min(min.y)),
+        core::Point::new(p.x.max(max.x), p.y.max(max.y)),
+      )
+    });
+
+    // Dirty region: the offset segments plus the line width and blur reach
+    let pad = thickness + (shadow.blur_sigma.max(0.0) * 3.0).ceil() as i32 + 1;
+    let left = (min.x - pad).max(0);
+    let top = (min.y - pad).max(0);
+    let right = (max.x + pad + 1).min(self.width);
+    let bottom = (max.y + pad + 1).min(self.height);
+    if right <= left || bottom <= top {
+      return Ok(());
+    }
+    let region = core::Rect::new(left, top, right - left, bottom - top);
+    let origin = core::Point::new(left, top) - offset;
+
+    // Shadow mask, blurred and scaled by the opacity
+    let mut mask = Mat::zeros(
+      region.height,
+      region.width,
+      core::CV_8UC1,
+    )?
+    .to_mat()?;
+    for (a, b) in segments {
+      imgproc::line(
+        &mut mask,
+        a - origin,
+        b - origin,
+        core::Scalar::all(255.0),
+        thickness,
+        self.line,
+        0,
+      )?;
+    }
+    if shadow.blur_sigma > 0.0 {
+      let mut blurred = Mat::default();
+      imgproc::gaussian_blur_def(
+        &mask,
+        &mut blurred,
+        core::Size::new(0, 0),
+        shadow.blur_sigma,
+      )?;
+      mask = blurred;
+    }
+    let mut alpha = Mat::default();
+    mask.convert_to(
+      &mut alpha,
+      -1,
+      shadow.opacity.clamp(0.0, 1.0),
+      0.0,
+    )?;
+    let layer = Mat::new_size_with_default(
+      region.size(),
+      frame.typ(),
+      self.color(shadow.color.to_bgra()),
+    )?;
+    blend_region(frame, region, &layer, &alpha)
+  }
+
+  /// Alpha-composites `image` (BGR or BGRA) centered on `center`; the part
+  /// outside the frame is dropped
+  pub fn overlay_image(
+    &self,
+    frame: &mut Mat,
+    image: &Mat,
+    center: core::Point,
+  ) -> Result<()> {
+    let size = image.size()?;
+    let origin = center - core::Point::new(size.width / 2, size.height / 2);
+    let left = origin.x.max(0);
+    let top = origin.y.max(0);
+    let right = (origin.x + size.width).min(self.width);
+    let bottom = (origin.y + size.height).min(self.height);
+    if right <= left || bottom <= top {
+      return Ok(());
+    }
+    let region = core::Rect::new(left, top, right - left, bottom - top);
+    let visible = Mat::roi(
+      image,
+      core::Rect::new(
+        left - origin.x,
+        top - origin.y,
+        region.width,
+        region.height,
+      ),
+    )?;
+
+    let layer = opaque_layer(&visible, frame.channels())?;
+    if image.channels() != 4 {
+      let mut roi = Mat::roi_mut(frame, region)?;
+      layer.copy_to(&mut *roi)?;
+      return Ok(());
+    }
+    let mut alpha = Mat::default();
+    core::extract_channel(&*visible, &mut alpha, 3)?;
+    blend_region(frame, region, &layer, &alpha)
+  }
+
   pub fn point(
     &self,
     frame: &mut Mat,
@@ -47,43 +507,133 @@ impl Drawer {
     Ok(())
   }
 
+  /// Draws an outlined circle
+  pub fn ring(
+    &self,
+    frame: &mut Mat,
+    point: core::Point,
+    radius: i32,
+    thickness: i32,
+    color: core::Scalar,
+  ) -> Result<()> {
+    imgproc::circle(
+      frame, point, radius, color, thickness, self.line, 0,
+    )?;
+    Ok(())
+  }
+
+  /// Draws `sweep_deg` degrees of a ring, clockwise from `start_deg`
+  /// (0 = right, -90 = top)
+  #[allow(clippy::too_many_arguments)]
+  pub fn arc_ring(
+    &self,
+    frame: &mut Mat,
+    center: core::Point,
+    radius: i32,
+    thickness: i32,
+    start_deg: f64,
+    sweep_deg: f64,
+    color: core::Scalar,
+  ) -> Result<()> {
+    if sweep_deg <= 0.0 {
+      return Ok(());
+    }
+    imgproc::ellipse(
+      frame,
+      center,
+      core::Size::new(radius, radius),
+      0.0,
+      start_deg,
+      start_deg + sweep_deg.min(360.0),
+      color,
+      thickness,
+      self.line,
+      0,
+    )?;
+    Ok(())
+  }
+
+  /// Draws a filled route dot
+  pub fn dot(
+    &self,
+    frame: &mut Mat,
+    point: core::Point,
+    radius: i32,
+    color: core::Scalar,
+  ) -> Result<()> {
+    imgproc::circle(
+      frame, point, radius, color, -1, self.line, 0,
+    )?;
+    Ok(())
+  }
+
+  /// Draws the bottom bar with `pace` on the left, `center` in the middle
+  /// and `dist` on the right; empty texts are skipped
+  #[allow(clippy::too_many_arguments)]
   pub fn text_bar(
     &self,
     frame: &mut Mat,
     pace: &str,
+    center: &str,
     dist: &str,
     font_scale: f64,
     thickness: i32,
     font: Font,
   ) -> Result<()> {
-    // ----- draw background bar -----
-    let text_size = self.text_size(dist, font_scale, thickness, font)?;
-    let bar_height = text_size.height + 30;
-    let rect = Rect {
-      pos: PositionRect {
-        x: 0,
-        y: self.height - bar_height,
-      },
-      size: SizeRect {
-        width: self.width,
-        height: bar_height,
-      },
-    };
-    let black_color = self.color([0.0; 4]);
-    self.rectangle(frame, rect, black_color)?;
+    let bar_height = self.bar_height(dist, font_scale, thickness, font)?;
+    self.text_bar_with_height(
+      frame, pace, center, dist, bar_height, font_scale, thickness, font,
+    )
+  }
+
+  /// Height of the bottom bar for the given text style; measure it once and
+  /// pass it to `text_bar_with_height` to skip re-measuring every frame
+  pub fn bar_height(
+    &self,
+    text: &str,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<i32> {
+    let (size, baseline) =
+      self.text_extent(text, font_scale, thickness, font)?;
+    Ok(size.height + 10 + bar_margin(baseline))
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  pub fn text_bar_with_height(
+    &self,
+    frame: &mut Mat,
+    pace: &str,
+    center: &str,
+    dist: &str,
+    bar_height: i32,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<()> {
+    let area = self.safe_rect;
+    let bar_bottom = self.bar_background(frame, bar_height)?;
 
     // ----- draw pace and distance -----
     let white_color = self.color([255.0, 255.0, 255.0, 0.0]);
-    let margin = 20;
-    let y_text = self.height - margin;
-    let items = vec![(pace, Align::Left), (dist, Align::Right)];
+    let items = [
+      (pace, Align::Left),
+      (center, Align::Center),
+      (dist, Align::Right),
+    ];
     for (text, align) in items {
+      if text.is_empty() {
+        continue;
+      }
+      let (size, baseline) =
+        self.text_extent(text, font_scale, thickness, font)?;
+      // Descenders stay above the bar bottom
+      let y_text = bar_bottom - bar_margin(baseline);
       let x = match align {
-        Align::Left => margin,
-        Align::Right => {
-          let size = self.text_size(text, font_scale, thickness, font)?;
-          self.width - size.width - margin
-        }
+        Align::Left => area.x + BAR_MARGIN,
+        Align::Center => area.x + (area.width - size.width) / 2,
+        Align::Right => area.x + area.width - size.width - BAR_MARGIN,
       };
 
       self.text(
@@ -101,89 +651,613 @@ impl Drawer {
     Ok(())
   }
 
-  pub fn header(
+  /// Draws the bottom bar with each text at the left text position shifted
+  /// right by its offset (pixels); text beyond the frame edge is cut off
+  pub fn ticker_bar(
     &self,
     frame: &mut Mat,
-    x: i32,
-    y: i32,
+    texts: &[(&str, i32)],
+    bar_height: i32,
     font_scale: f64,
     thickness: i32,
     font: Font,
   ) -> Result<()> {
-    let bluish_color = self.color([255.0, 255.0, 0.0, 0.0]);
-    const LABELS: [(&str, i32); 4] = [
-      ("KM   PACE", -20),
-      ("BAR", 150),
-      ("HR", 285),
-      ("LENGTH", 320),
-    ];
-
-    let y_start = y - 20;
-
-    for (label, offset) in LABELS {
+    let bar_bottom = self.bar_background(frame, bar_height)?;
+    let white_color = self.color([255.0, 255.0, 255.0, 0.0]);
+    for &(text, offset) in texts {
+      let (_, baseline) =
+        self.text_extent(text, font_scale, thickness, font)?;
       self.text(
         frame,
-        label,
-        x + offset,
-        y_start,
+        text,
+        self.safe_rect.x + BAR_MARGIN + offset,
+        bar_bottom - bar_margin(baseline),
         font_scale,
         thickness,
         font,
-        bluish_color,
+        white_color,
       )?;
     }
-
     Ok(())
   }
 
-  #[allow(clippy::too_many_arguments)]
-  pub fn text(
+  /// Draws the bottom bar with the activity summary `parts` centered on
+  /// one line, a dot between each two (the fonts have no middle dot);
+  /// draws nothing without parts
+  pub fn summary_bar(
     &self,
     frame: &mut Mat,
-    text: &str,
-    x: i32,
-    y: i32,
+    parts: &[String],
     font_scale: f64,
     thickness: i32,
     font: Font,
-    color: core::Scalar,
   ) -> Result<()> {
-    imgproc::put_text(
-      frame,
+    let mut sizes = Vec::with_capacity(parts.len());
+    let mut baseline = 0;
+    for part in parts {
+      let (size, part_baseline) =
+        self.text_extent(part, font_scale, thickness, font)?;
+      baseline = baseline.max(part_baseline);
+      sizes.push(size);
+    }
+    let Some(text_height) = sizes.iter().map(|size| size.height).max() else {
+      return Ok(());
+    };
+    // Each dot sits in a gap as wide as the text is tall
+    let gap = text_height;
+    let width = sizes.iter().map(|size| size.width).sum::<i32>()
+      + gap * (parts.len() as i32 - 1);
+
+    let bar_height = text_height + 10 + bar_margin(baseline);
+    let bar_bottom = self.bar_background(frame, bar_height)?;
+    let y = bar_bottom - bar_margin(baseline);
+    let white_color = self.color([255.0, 255.0, 255.0, 0.0]);
+    let mut x = self.safe_rect.x + (self.safe_rect.width - width) / 2;
+    for (i, (part, size)) in parts.iter().zip(&sizes).enumerate() {
+      if i > 0 {
+        self.dot(
+          frame,
+          core::Point::new(x - gap / 2, y - text_height / 2),
+          (text_height / 8).max(2),
+          white_color,
+        )?;
+      }
+      self.text(
+        frame,
+        part,
+        x,
+        y,
+        font_scale,
+        thickness,
+        font,
+        white_color,
+      )?;
+      x += size.width + gap;
+    }
+    Ok(())
+  }
+
+  /// Fills the bottom bar across the frame, ending at the safe area
+  /// bottom, and returns that bottom edge
+  fn bar_background(&self, frame: &mut Mat, bar_height: i32) -> Result<i32> {
+    let bar_bottom = self.safe_rect.y + self.safe_rect.height;
+    let rect = Rect {
+      pos: PositionRect {
+        x: 0,
+        y: bar_bottom - bar_height,
+      },
+      size: SizeRect {
+        width: self.width,
+        height: bar_height,
+      },
+    };
+    self.rectangle(frame, rect, self.color([0.0; 4]))?;
+    Ok(bar_bottom)
+  }
+
+  /// Draws `text` on a translucent pill sized to it, centered at `center`
+  /// and pushed inside the safe area. With `arrow`, a compass arrow
+  /// pointing at that bearing (degrees) follows the text.
+  pub fn chip(
+    &self,
+    frame: &mut Mat,
+    text: &str,
+    center: (i32, i32),
+    style: &ChipStyle,
+    arrow: Option<f64>,
+  ) -> Result<()> {
+    let (size, baseline) = self.text_extent(
       text,
-      core::Point::new(x, y),
-      font.to_opencv(),
-      font_scale,
-      color,
-      thickness,
+      style.font_scale,
+      style.thickness,
+      style.font,
+    )?;
+    let height = size.height + baseline + 2 * style.padding;
+    let radius = height / 2;
+    // The arrow gets a square as tall as the text, after a padding
+    let arrow_slot = size.height + baseline;
+    let text_width = match arrow {
+      Some(_) => size.width + style.padding + arrow_slot,
+      None => size.width,
+    };
+    let width = text_width + 2 * radius;
+
+    // Keep the pill inside the safe area, then inside the frame
+    let core::Rect { x, y, .. } = self.fit_rect(core::Rect::new(
+      center.0 - width / 2,
+      center.1 - height / 2,
+      width,
+      height,
+    ));
+    let pill = core::Rect::new(
+      x,
+      y,
+      width.min(self.width - x),
+      height.min(self.height - y),
+    );
+    if pill.width <= 0 || pill.height <= 0 {
+      return Ok(());
+    }
+
+    // ----- draw background pill -----
+    let opacity = style.opacity.clamp(0.0, 1.0);
+    let background = self.color(style.background.to_bgra());
+    let mut roi = Mat::roi_mut(frame, pill)?;
+    let mut shape = roi.try_clone()?;
+    imgproc::rectangle(
+      &mut shape,
+      core::Rect::new(radius, 0, width - 2 * radius, height),
+      background,
+      -1,
       self.line,
-      false,
+      0,
+    )?;
+    for cap_x in [radius, width - radius] {
+      imgproc::circle(
+        &mut shape,
+        core::Point::new(cap_x, radius),
+        radius,
+        background,
+        -1,
+        self.line,
+        0,
+      )?;
+    }
+    let mut blended = Mat::default();
+    core::add_weighted_def(
+      &shape,
+      opacity,
+      &*roi,
+      1.0 - opacity,
+      0.0,
+      &mut blended,
     )?;
+    blended.copy_to(&mut *roi)?;
+    drop(roi);
+
+    // ----- draw text -----
+    let text_color = self.color(style.text_color.to_bgra());
+    self.text(
+      frame,
+      text,
+      x + radius,
+      y + style.padding + size.height,
+      style.font_scale,
+      style.thickness,
+      style.font,
+      text_color,
+    )?;
+
+    if let Some(bearing) = arrow {
+      let center = core::Point::new(
+        x + radius + size.width + style.padding + arrow_slot / 2,
+        y + height / 2,
+      );
+      self.bearing_arrow(
+        frame,
+        center,
+        arrow_slot,
+        bearing,
+        style.thickness,
+        text_color,
+      )?;
+    }
     Ok(())
   }
 
-  pub fn rectangle(
+  /// Labels a climb of average `grade` (%) "Max grade 11%" on a chip of
+  /// the default style, just above `at` on the climb
+  pub fn climb_label(
     &self,
     frame: &mut Mat,
-    rect: Rect,
+    at: core::Point,
+    grade: f32,
+  ) -> Result<()> {
+    let text = format!(
+      "Max grade {}%",
+      format_decimal(grade as f64, 0)
+    );
+    self.chip(
+      frame,
+      &text,
+      (at.x, at.y - CLIMB_LABEL_RISE),
+      &ChipStyle::default(),
+      None,
+    )
+  }
+
+  /// Draws an arrow `length` pixels long through `center`, pointing at
+  /// `bearing` degrees clockwise from north (up): a shaft and an open head
+  pub fn bearing_arrow(
+    &self,
+    frame: &mut Mat,
+    center: core::Point,
+    length: i32,
+    bearing: f64,
+    thickness: i32,
     color: core::Scalar,
   ) -> Result<()> {
-    let Rect { pos, size } = rect;
-    let PositionRect { x, y } = pos;
-    let SizeRect { width, height } = size;
-    let rect = core::Rect::new(x, y, width, height);
+    let point = |angle: f64, distance: f64, from: (f64, f64)| {
+      let angle = angle.to_radians();
+      (
+        from.0 + distance * angle.sin(),
+        from.1 - distance * angle.cos(),
+      )
+    };
+    let to_point =
+      |(x, y): (f64, f64)| core::Point::new(x.round() as i32, y.round() as i32);
 
-    imgproc::rectangle(frame, rect, color, -1, self.line, 0)?;
+    let half = length as f64 / 2.0;
+    let middle = (center.x as f64, center.y as f64);
+    let tip = point(bearing, half, middle);
+    let tail = point(bearing + 180.0, half, middle);
+    imgproc::line(
+      frame,
+      to_point(tail),
+      to_point(tip),
+      color,
+      thickness,
+      self.line,
+      0,
+    )?;
+
+    let barb = half * 0.6;
+    let head = core::Vector::<core::Point>::from_iter([
+      to_point(point(bearing + 150.0, barb, tip)),
+      to_point(tip),
+      to_point(point(bearing - 150.0, barb, tip)),
+    ]);
+    imgproc::polylines(
+      frame, &head, false, color, thickness, self.line, 0,
+    )?;
     Ok(())
   }
 
-  pub fn text_size(
+  /// Draws a filled direction arrowhead
+  pub fn arrow(
     &self,
-    text: &str,
+    frame: &mut Mat,
+    placement: &ArrowPlacement,
+    size: i32,
+    color: core::Scalar,
+  ) -> Result<()> {
+    let corners = arrowhead(placement, size as f64)
+      .map(|(x, y)| core::Point::new(x.round() as i32, y.round() as i32));
+    let polygon = core::Vector::<core::Point>::from_iter(corners);
+    let polygons =
+      core::Vector::<core::Vector<core::Point>>::from_iter([polygon]);
+    imgproc::fill_poly(
+      frame,
+      &polygons,
+      color,
+      self.line,
+      0,
+      core::Point::default(),
+    )?;
+    Ok(())
+  }
+
+  /// Draws `series` (x, value) as a line chart filling `rect`; missing
+  /// values break the line. Returns the axes for cursors, or None (and
+  /// draws nothing) when the series has no values.
+  pub fn line_chart(
+    &self,
+    frame: &mut Mat,
+    rect: core::Rect,
+    series: &[(f64, Option<f64>)],
+    style: &ChartStyle,
+  ) -> Result<Option<ChartAxes>> {
+    const DASH: i32 = 6;
+
+    let Some(axes) = ChartAxes::fit(rect, series, style.reference) else {
+      return Ok(None);
+    };
+    if let Some(background) = style.background {
+      imgproc::rectangle(
+        frame,
+        rect,
+        background,
+        -1,
+        imgproc::LINE_8,
+        0,
+      )?;
+    }
+
+    // ----- draw reference line -----
+    if let Some(reference) = style.reference {
+      let y = axes.point(axes.x_range.0, reference).y;
+      self.dashed_polyline(
+        frame,
+        &[
+          core::Point::new(rect.x, y),
+          core::Point::new(rect.x + rect.width - 1, y),
+        ],
+        style.reference_color,
+        1,
+        DASH,
+      )?;
+    }
+
+    // ----- draw series, one polyline per run of values -----
+    let mut runs = core::Vector::<core::Vector<core::Point>>::new();
+    let mut run = core::Vector::<core::Point>::new();
+    for &(x, value) in series {
+      match value {
+        Some(value) => run.push(axes.point(x, value)),
+        None if !run.is_empty() => {
+          runs.push(std::mem::take(&mut run));
+        }
+        None => {}
+      }
+    }
+    if !run.is_empty() {
+      runs.push(run);
+    }
+    imgproc::polylines(
+      frame,
+      &runs,
+      false,
+      style.line_color,
+      style.thickness,
+      self.line,
+      0,
+    )?;
+
+    Ok(Some(axes))
+  }
+
+  /// Draws the cadence chart on a dark box, shifted inside the safe area
+  pub fn cadence_chart(
+    &self,
+    frame: &mut Mat,
+    chart: &CadenceChartConfig,
+    series: &[(f64, Option<f64>)],
+  ) -> Result<Option<ChartAxes>> {
+    let (width, height) = chart.size;
+    let rect = self.fit_rect(core::Rect::new(
+      (chart.position.0 * self.width as f64) as i32,
+      (chart.position.1 * self.height as f64) as i32,
+      width.max(2),
+      height.max(2),
+    ));
+    let style = ChartStyle {
+      line_color: self.color(chart.color.to_bgra()),
+      thickness: 2,
+      background: Some(self.color([20.0, 20.0, 20.0, 0.0])),
+      reference: chart.target_line.map(f64::from),
+      reference_color: self.color([200.0, 200.0, 200.0, 0.0]),
+    };
+    self.line_chart(frame, rect, series, &style)
+  }
+
+  /// Draws a vertical cursor across a chart at `x`
+  pub fn chart_cursor(
+    &self,
+    frame: &mut Mat,
+    axes: &ChartAxes,
+    x: f64,
+    color: core::Scalar,
+  ) -> Result<()> {
+    let top = axes.point(x, axes.y_range.1);
+    let bottom = axes.point(x, axes.y_range.0);
+    imgproc::line(
+      frame, top, bottom, color, 2, self.line, 0,
+    )?;
+    Ok(())
+  }
+
+  /// Draws a gradient color bar of 1px strips with the range ends as
+  /// labels, shifted with its labels inside the safe area
+  pub fn legend(
+    &self,
+    frame: &mut Mat,
+    legend: &LegendConfig,
+    scale: &GradientScale,
+  ) -> Result<()> {
+    const THICKNESS: i32 = 12;
+    const GAP: i32 = 6;
+    const FONT_SCALE: f64 = 0.5;
+    let (font, font_thickness) = (Font::Simplex, 1);
+    let text_color = self.color([255.0, 255.0, 255.0, 0.0]);
+
+    let length = legend.width_px.max(2);
+    let (low, high) = (
+      legend.label(scale.min),
+      legend.label(scale.max),
+    );
+    let (low_size, low_baseline) =
+      self.text_extent(&low, FONT_SCALE, font_thickness, font)?;
+    let (high_size, _) =
+      self.text_extent(&high, FONT_SCALE, font_thickness, font)?;
+
+    // Bar and labels as one box, shifted inside the safe area
+    let label_width = low_size.width.max(high_size.width);
+    let (width, height) = match legend.orientation {
+      LegendOrientation::Horizontal => (
+        length.max(label_width),
+        THICKNESS + GAP + low_size.height + low_baseline,
+      ),
+      LegendOrientation::Vertical => (
+        THICKNESS + GAP + label_width,
+        length + low_baseline,
+      ),
+    };
+    let core::Rect { x, y, .. } = self.fit_rect(core::Rect::new(
+      (legend.position.0 * self.width as f64) as i32,
+      (legend.position.1 * self.height as f64) as i32,
+      width,
+      height,
+    ));
+
+    // ----- draw color bar -----
+    for i in 0..length {
+      let t = i as f64 / (length - 1) as f64;
+      let strip = match legend.orientation {
+        LegendOrientation::Horizontal => {
+          core::Rect::new(x + i, y, 1, THICKNESS)
+        }
+        LegendOrientation::Vertical => {
+          core::Rect::new(x, y + length - 1 - i, THICKNESS, 1)
+        }
+      };
+      imgproc::rectangle(
+        frame,
+        strip,
+        self.color(scale.color_at(t)),
+        -1,
+        imgproc::LINE_8,
+        0,
+      )?;
+    }
+
+    // ----- draw labels -----
+    let (low_origin, high_origin) = match legend.orientation {
+      LegendOrientation::Horizontal => {
+        let baseline = y + THICKNESS + GAP + low_size.height;
+        (
+          (x, baseline),
+          (x + length - high_size.width, baseline),
+        )
+      }
+      LegendOrientation::Vertical => {
+        let left = x + THICKNESS + GAP;
+        (
+          (left, y + length),
+          (left, y + high_size.height),
+        )
+      }
+    };
+    for (text, (text_x, text_y)) in [(low, low_origin), (high, high_origin)] {
+      self.text(
+        frame,
+        &text,
+        text_x,
+        text_y,
+        FONT_SCALE,
+        font_thickness,
+        font,
+        text_color,
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Draws a color swatch and label per entry, one row each, with the
+  /// top-left corner at `position` (fractions of the frame) shifted
+  /// inside the safe area
+  pub fn swatch_legend(
+    &self,
+    frame: &mut Mat,
+    position: (f64, f64),
+    entries: &[(String, core::Scalar)],
+  ) -> Result<()> {
+    const SWATCH: i32 = 14;
+    const GAP: i32 = 6;
+    const FONT_SCALE: f64 = 0.5;
+    let (font, font_thickness) = (Font::Simplex, 1);
+    let text_color = self.color([255.0, 255.0, 255.0, 0.0]);
+
+    let mut label_width = 0;
+    let mut row_height = SWATCH;
+    for (label, _) in entries {
+      let (size, baseline) =
+        self.text_extent(label, FONT_SCALE, font_thickness, font)?;
+      label_width = label_width.max(size.width);
+      row_height = row_height.max(size.height + baseline);
+    }
+    let core::Rect { x, y, .. } = self.fit_rect(core::Rect::new(
+      (position.0 * self.width as f64) as i32,
+      (position.1 * self.height as f64) as i32,
+      SWATCH + GAP + label_width,
+      entries.len() as i32 * (row_height + GAP) - GAP,
+    ));
+
+    for (i, (label, color)) in entries.iter().enumerate() {
+      let top = y + i as i32 * (row_height + GAP);
+      imgproc::rectangle(
+        frame,
+        core::Rect::new(
+          x,
+          top + (row_height - SWATCH) / 2,
+          SWATCH,
+          SWATCH,
+        ),
+        *color,
+        -1,
+        imgproc::LINE_8,
+        0,
+      )?;
+      let (size, _) =
+        self.text_extent(label, FONT_SCALE, font_thickness, font)?;
+      self.text(
+        frame,
+        label,
+        x + SWATCH + GAP,
+        top + size.height,
+        FONT_SCALE,
+        font_thickness,
+        font,
+        text_color,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Stamps `unix_seconds` as configured; an unformattable time draws
+  /// nothing
+  pub fn date_stamp(
+    &self,
+    frame: &mut Mat,
+    stamp: &DateStampConfig,
+    unix_seconds: i64,
+  ) -> Result<()> {
+    let Some(text) = stamp.format(unix_seconds) else {
+      return Ok(());
+    };
+    self.corner_text(
+      frame,
+      &text,
+      stamp.corner,
+      stamp.font_scale,
+      stamp.thickness,
+      stamp.font,
+      self.color(stamp.color.to_bgra()),
+    )
+  }
+
+  /// Draws `text` in a corner of the safe area, `CORNER_MARGIN` pixels in
+  #[allow(clippy::too_many_arguments)]
+  pub fn corner_text(
+    &self,
+    frame: &mut Mat,
+    text: &str,
+    corner: Corner,
     font_scale: f64,
     thickness: i32,
     font: Font,
-  ) -> Result<core::Size> {
+    color: core::Scalar,
+  ) -> Result<()> {
     let mut baseline = 0;
     let size = imgproc::get_text_size(
       text,
@@ -192,10 +1266,802 @@ impl Drawer {
       thickness,
       &mut baseline,
     )?;
+    let (x, y) = self.corner_origin(size, baseline, corner);
+    self.text(
+      frame, text, x, y, font_scale, thickness, font, color,
+    )
+  }
+
+  /// Draws `lines` stacked in a corner of the safe area, each aligned to
+  /// the corner's side
+  #[allow(clippy::too_many_arguments)]
+  pub fn corner_lines(
+    &self,
+    frame: &mut Mat,
+    lines: &[String],
+    corner: Corner,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+    color: core::Scalar,
+  ) -> Result<()> {
+    let mut sizes = Vec::with_capacity(lines.len());
+    let mut baseline = 0;
+    for line in lines {
+      let (size, line_baseline) =
+        self.text_extent(line, font_scale, thickness, font)?;
+      baseline = baseline.max(line_baseline);
+      sizes.push(size);
+    }
+    let Some(text_height) = sizes.iter().map(|size| size.height).max() else {
+      return Ok(());
+    };
+    let width = sizes.iter().map(|size| size.width).max().unwrap_or(0);
+    let step = text_height + baseline + LINE_GAP;
+    let height = step * lines.len() as i32 - baseline - LINE_GAP;
+
+    let (x, y) = self.corner_origin(
+      core::Size::new(width, height),
+      baseline,
+      corner,
+    );
+    let top = y - height;
+    let right_aligned = matches!(
+      corner,
+      Corner::TopRight | Corner::BottomRight
+    );
+    for (i, (line, size)) in lines.iter().zip(&sizes).enumerate() {
+      let line_x = if right_aligned {
+        x + width - size.width
+      } else {
+        x
+      };
+      self.text(
+        frame,
+        line,
+        line_x,
+        top + text_height + i as i32 * step,
+        font_scale,
+        thickness,
+        font,
+        color,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Draws the background map credit in its corner of the safe area,
+  /// blended over the frame; draws nothing without credit text
+  pub fn attribution(
+    &self,
+    frame: &mut Mat,
+    config: &AttributionConfig,
+  ) -> Result<()> {
+    let Some(text) = config.drawn_text() else {
+      return Ok(());
+    };
+    let font = Font::Simplex;
+    let mut baseline = 0;
+    let size = imgproc::get_text_size(
+      &text,
+      font.to_opencv(),
+      config.font_scale,
+      1,
+      &mut baseline,
+    )?;
+    let (x, y) = self.corner_origin(size, baseline, config.position);
+
+    // Blend only the text's box, clipped to the frame
+    let (left, top) = (x.max(0), (y - size.height).max(0));
+    let right = (x + size.width).min(self.width);
+    let bottom = (y + baseline).min(self.height);
+    if right <= left || bottom <= top {
+      return Ok(());
+    }
+    let rect = core::Rect::new(left, top, right - left, bottom - top);
+    let mut roi = Mat::roi_mut(frame, rect)?;
+    let mut shape = roi.try_clone()?;
+    self.text(
+      &mut shape,
+      &text,
+      x - left,
+      y - top,
+      config.font_scale,
+      1,
+      font,
+      self.color([255.0, 255.0, 255.0, 0.0]),
+    )?;
+    let mut blended = Mat::default();
+    core::add_weighted_def(
+      &shape,
+      ATTRIBUTION_OPACITY,
+      &*roi,
+      1.0 - ATTRIBUTION_OPACITY,
+      0.0,
+      &mut blended,
+    )?;
+    blended.copy_to(&mut *roi)?;
+    Ok(())
+  }
+
+  /// Baseline origin of a text of `size` in a corner of the safe area
+  fn corner_origin(
+    &self,
+    size: core::Size,
+    baseline: i32,
+    corner: Corner,
+  ) -> (i32, i32) {
+    let area = self.safe_rect;
+    let (left, top) = (
+      area.x + CORNER_MARGIN,
+      area.y + CORNER_MARGIN + size.height,
+    );
+    let right = area.x + area.width - CORNER_MARGIN - size.width;
+    let bottom = area.y + area.height - CORNER_MARGIN - baseline;
+    match corner {
+      Corner::TopLeft => (left, top),
+      Corner::TopRight => (right, top),
+      Corner::BottomLeft => (left, bottom),
+      Corner::BottomRight => (right, bottom),
+    }
+  }
+
+  /// Draws the lap panel column `labels` at their x offsets above row
+  /// position `y`, and the `summary` line (e.g. "Elev +412 m") above them
+  #[allow(clippy::too_many_arguments)]
+  pub fn header(
+    &self,
+    frame: &mut Mat,
+    x: i32,
+    y: i32,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+    labels: &[(&str, i32)],
+    summary: Option<&str>,
+  ) -> Result<()> {
+    for &(label, offset) in labels {
+      self.header_label(
+        frame,
+        label,
+        x + offset,
+        y,
+        font_scale,
+        thickness,
+        font,
+      )?;
+    }
+    if let Some(summary) = summary {
+      let (x, y) = self.header_summary_start(
+        summary,
+        (x, y),
+        labels,
+        font_scale,
+        font,
+      )?;
+      self.header_label(
+        frame, summary, x, y, font_scale, thickness, font,
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Row position of the header summary line of a panel at `start`: one
+  /// line above the `labels`, aligned with the first one
+  fn header_summary_start(
+    &self,
+    summary: &str,
+    start: (i32, i32),
+    labels: &[(&str, i32)],
+    font_scale: f64,
+    font: Font,
+  ) -> Result<(i32, i32)> {
+    let size = self.text_size(summary, font_scale, 2, font)?;
+    let offset = labels.first().map_or(0, |&(_, offset)| offset);
+    Ok((
+      start.0 + offset,
+      start.1 - size.height - LINE_GAP,
+    ))
+  }
+
+  /// Draws one lap panel header label above row position `y`
+  #[allow(clippy::too_many_arguments)]
+  pub fn header_label(
+    &self,
+    frame: &mut Mat,
+    label: &str,
+    x: i32,
+    y: i32,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<()> {
+    let bluish_color = self.color([255.0, 255.0, 0.0, 0.0]);
+    self.text(
+      frame,
+      label,
+      x,
+      y - 20,
+      font_scale,
+      thickness,
+      font,
+      bluish_color,
+    )
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  pub fn text(
+    &self,
+    frame: &mut Mat,
+    text: &str,
+    x: i32,
+    y: i32,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+    color: core::Scalar,
+  ) -> Result<()> {
+    // Hershey fonts are ASCII-only, so a degree sign is drawn as a ring
+    // in the space of an "o"
+    let Some((before, after)) = text.split_once(DEGREE_SIGN) else {
+      imgproc::put_text(
+        frame,
+        text,
+        core::Point::new(x, y),
+        font.to_opencv(),
+        font_scale,
+        color,
+        thickness,
+        self.line,
+        false,
+      )?;
+      return Ok(());
+    };
+
+    self.text(
+      frame, before, x, y, font_scale, thickness, font, color,
+    )?;
+    let x = x + self.text_size(before, font_scale, thickness, font)?.width;
+    let ring = self.text_size("o", font_scale, thickness, font)?;
+    let digit = self.text_size("0", font_scale, thickness, font)?;
+    let radius = (ring.width / 4).max(1);
+    imgproc::circle(
+      frame,
+      core::Point::new(
+        x + ring.width / 2,
+        y - digit.height + radius,
+      ),
+      radius,
+      color,
+      thickness,
+      self.line,
+      0,
+    )?;
+    self.text(
+      frame,
+      after,
+      x + ring.width,
+      y,
+      font_scale,
+      thickness,
+      font,
+      color,
+    )
+  }
+
+  pub fn rectangle(
+    &self,
+    frame: &mut Mat,
+    rect: Rect,
+    color: core::Scalar,
+  ) -> Result<()> {
+    let Rect { pos, size } = rect;
+    let PositionRect { x, y } = pos;
+    let SizeRect { width, height } = size;
+    let rect = core::Rect::new(x, y, width, height);
+
+    imgproc::rectangle(frame, rect, color, -1, self.line, 0)?;
+    Ok(())
+  }
+
+  /// Fills `rect` with `color` at `alpha` (0.0-1.0) over what is beneath
+  /// it; the part outside the frame is skipped
+  pub fn blended_rectangle(
+    &self,
+    frame: &mut Mat,
+    rect: Rect,
+    color: core::Scalar,
+    alpha: f64,
+  ) -> Result<()> {
+    let alpha = alpha.clamp(0.0, 1.0);
+    if alpha >= 1.0 {
+      return self.rectangle(frame, rect, color);
+    }
+    let Rect { pos, size } = rect;
+    let (left, top) = (pos.x.max(0), pos.y.max(0));
+    let right = (pos.x + size.width).min(self.width);
+    let bottom = (pos.y + size.height).min(self.height);
+    if alpha <= 0.0 || right <= left || bottom <= top {
+      return Ok(());
+    }
+
+    let area = core::Rect::new(left, top, right - left, bottom - top);
+    let mut roi = Mat::roi_mut(frame, area)?;
+    let mut fill = roi.try_clone()?;
+    fill.set_to_def(&color)?;
+    let mut blended = Mat::default();
+    core::add_weighted_def(
+      &fill,
+      alpha,
+      &*roi,
+      1.0 - alpha,
+      0.0,
+      &mut blended,
+    )?;
+    blended.copy_to(&mut *roi)?;
+    Ok(())
+  }
+
+  /// Draws a lap's pace bar at the opacity of `config.bar_style`, and its
+  /// `label` just past the bar's end when value labels are on. The label
+  /// is shifted left as needed to stay inside the frame.
+  pub fn pace_bar(
+    &self,
+    frame: &mut Mat,
+    rect: Rect,
+    color: core::Scalar,
+    text_color: core::Scalar,
+    config: &LapDataConfig,
+    label: Option<&str>,
+  ) -> Result<()> {
+    let (end, baseline) = (
+      rect.pos.x + rect.size.width + BAR_LABEL_GAP,
+      rect.pos.y + rect.size.height,
+    );
+    self.blended_rectangle(
+      frame,
+      rect,
+      color,
+      config.bar_style.alpha,
+    )?;
+
+    let style = config.bar_style;
+    let (true, Some(label)) = (style.show_value_label, label) else {
+      return Ok(());
+    };
+    let (scale, thickness, font) = (
+      config.font_scale,
+      config.thickness,
+      config.font,
+    );
+    let width = self.text_size(label, scale, thickness, font)?.width;
+    self.text(
+      frame,
+      label,
+      end.min(self.width - width).max(0),
+      baseline,
+      scale,
+      thickness,
+      font,
+      text_color,
+    )
+  }
+
+  /// Draws the pace axis of the pace bars in `column`, the box covering
+  /// every bar at full length: a faint gridline through the bars at each
+  /// of the `ticks` (pace and fraction of the full bar), a mark below
+  /// them and, when the axis shows labels, the pace under the mark
+  pub fn pace_axis(
+    &self,
+    frame: &mut Mat,
+    column: core::Rect,
+    ticks: &[(f64, f32)],
+    axis: &BarAxis,
+    config: &LapDataConfig,
+  ) -> Result<()> {
+    let color = self.color(axis.color);
+    let bottom = column.y + column.height;
+    let scale = config.font_scale * AXIS_LABEL_SCALE;
+    for &(pace, fraction) in ticks {
+      let x = column.x + (fraction * PACE_BAR_WIDTH as f32) as i32;
+      let gridline = Rect {
+        pos: PositionRect { x, y: column.y },
+        size: SizeRect {
+          width: 1,
+          height: column.height,
+        },
+      };
+      self.blended_rectangle(frame, gridline, color, GRIDLINE_ALPHA)?;
+      let mark = Rect {
+        pos: PositionRect { x, y: bottom },
+        size: SizeRect {
+          width: 1,
+          height: AXIS_TICK_LENGTH,
+        },
+      };
+      self.rectangle(frame, mark, color)?;
+
+      if axis.show_labels {
+        let label = sec_to_pace(pace as f32);
+        let size = self.text_size(&label, scale, 1, config.font)?;
+        self.text(
+          frame,
+          &label,
+          x - size.width / 2,
+          bottom + AXIS_TICK_LENGTH + size.height + 2,
+          scale,
+          1,
+          config.font,
+          color,
+        )?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn text_size(
+    &self,
+    text: &str,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<core::Size> {
+    let mut baseline = 0;
+    let size = imgproc::get_text_size(
+      &measured_text(text),
+      font.to_opencv(),
+      font_scale,
+      thickness,
+      &mut baseline,
+    )?;
     Ok(size)
   }
 
+  /// Size of `text` above its baseline, and how far descenders reach
+  /// below the baseline
+  pub fn text_extent(
+    &self,
+    text: &str,
+    font_scale: f64,
+    thickness: i32,
+    font: Font,
+  ) -> Result<(core::Size, i32)> {
+    let mut baseline = 0;
+    let size = imgproc::get_text_size(
+      &measured_text(text),
+      font.to_opencv(),
+      font_scale,
+      thickness,
+      &mut baseline,
+    )?;
+    Ok((size, baseline))
+  }
+
   pub fn color(&self, bgra: [f64; 4]) -> core::Scalar {
-    core::Scalar::new(bgra[0], bgra[1], bgra[2], bgra[3])
+    let alpha = if self.opaque_alpha { 255.0 } else { bgra[3] };
+    core::Scalar::new(bgra[0], bgra[1], bgra[2], alpha)
+  }
+}
+
+/// Placeholder of the widest pace bar label in `format`, to measure the
+/// lap panel
+fn widest_bar_label(format: BarLabel) -> &'static str {
+  match format {
+    BarLabel::DeltaSeconds => "+000 s",
+    BarLabel::Percent => "+100%",
+  }
+}
+
+/// `text` as `Drawer::text` lays it out, a degree sign taking an "o"
+fn measured_text(text: &str) -> Cow<'_, str> {
+  if text.contains(DEGREE_SIGN) {
+    Cow::Owned(text.replace(DEGREE_SIGN, "o"))
+  } else {
+    Cow::Borrowed(text)
+  }
+}
+
+/// Space between the bottom bar text and the bar edges
+const BAR_MARGIN: i32 = 20;
+
+/// Space between the bottom bar text baseline and the bar bottom: the
+/// margin, or more when descenders reach further
+fn bar_margin(baseline: i32) -> i32 {
+  BAR_MARGIN.max(baseline)
+}
+
+/// Smallest box covering `a` (if any) and `b`
+pub fn union(a: Option<core::Rect>, b: core::Rect) -> core::Rect {
+  let Some(a) = a else {
+    return b;
+  };
+  let (left, top) = (a.x.min(b.x), a.y.min(b.y));
+  let right = (a.x + a.width).max(b.x + b.width);
+  let bottom = (a.y + a.height).max(b.y + b.height);
+  core::Rect::new(left, top, right - left, bottom - top)
+}
+
+/// `frame * (1 - alpha) + layer * alpha` inside `region`; `layer` has the
+/// frame's channels and `alpha` one 8-bit channel, both the size of the
+/// region
+fn blend_region(
+  frame: &mut Mat,
+  region: core::Rect,
+  layer: &Mat,
+  alpha: &Mat,
+) -> Result<()> {
+  // One copy of the mask per frame channel, alpha included
+  let mut weights = Mat::default();
+  core::merge(
+    &core::Vector::<Mat>::from_iter(
+      (0..frame.channels()).map(|_| alpha.clone()),
+    ),
+    &mut weights,
+  )?;
+  let mut inverse = Mat::default();
+  core::bitwise_not_def(&weights, &mut inverse)?;
+
+  let mut roi = Mat::roi_mut(frame, region)?;
+  let (mut kept, mut tint) = (Mat::default(), Mat::default());
+  core::multiply(
+    &*roi,
+    &inverse,
+    &mut kept,
+    1.0 / 255.0,
+    -1,
+  )?;
+  core::multiply(
+    layer,
+    &weights,
+    &mut tint,
+    1.0 / 255.0,
+    -1,
+  )?;
+  let mut blended = Mat::default();
+  core::add_weighted_def(
+    &kept,
+    1.0,
+    &tint,
+    1.0,
+    0.0,
+    &mut blended,
+  )?;
+  blended.copy_to(&mut *roi)?;
+  Ok(())
+}
+
+/// `image` without its alpha, with an opaque alpha added back when
+/// `channels` is 4
+fn opaque_layer(image: &Mat, channels: i32) -> Result<Mat> {
+  let mut bgr = Mat::default();
+  if image.channels() == 4 {
+    imgproc::cvt_color_def(image, &mut bgr, imgproc::COLOR_BGRA2BGR)?;
+  } else {
+    image.copy_to(&mut bgr)?;
+  }
+  if channels != 4 {
+    return Ok(bgr);
+  }
+  let mut bgra = Mat::default();
+  imgproc::cvt_color_def(&bgr, &mut bgra, imgproc::COLOR_BGR2BGRA)?;
+  Ok(bgra)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::configs::POWER_COLUMN_WIDTH;
+
+  fn inside(rect: core::Rect, area: core::Rect) -> bool {
+    rect.x >= area.x
+      && rect.y >= area.y
+      && rect.x + rect.width <= area.x + area.width
+      && rect.y + rect.height <= area.y + area.height
+  }
+
+  #[test]
+  fn test_safe_area_units() {
+    let drawer =
+      Drawer::new(1000, 2000).with_safe_area(&SafeArea::uniform(0.1));
+    assert_eq!(
+      drawer.safe_rect,
+      core::Rect::new(100, 200, 800, 1600)
+    );
+
+    let pixels = SafeArea::pixels(100.0, 50.0, 20.0, 30.0);
+    let drawer = Drawer::new(1000, 2000).with_safe_area(&pixels);
+    assert_eq!(
+      drawer.safe_rect,
+      core::Rect::new(20, 100, 950, 1850)
+    );
+  }
+
+  #[test]
+  fn test_header_summary_sits_above_the_labels() {
+    let config = LapDataConfig::default();
+    let stats = vec!["5:00".to_string(); 3];
+    let mut panel = LapPanelLayout {
+      config: &config,
+      sport: SportProfile::Running,
+      stats: &stats,
+      details: &[],
+      extra_label: None,
+      summary: None,
+    };
+    let drawer = Drawer::new(1080, 1920);
+    let start = (500, 300);
+    let without = drawer.lap_panel_rect(start, &panel).unwrap();
+    panel.summary = Some("Elev +412 m");
+    let with = drawer.lap_panel_rect(start, &panel).unwrap();
+
+    let (x, y) = drawer
+      .header_summary_start(
+        "Elev +412 m",
+        start,
+        &SportProfile::Running.lap_labels(false),
+        0.5,
+        Font::Simplex,
+      )
+      .unwrap();
+    assert_eq!(x, 480);
+    assert!(y < start.1);
+    assert!(with.y < without.y);
+    assert_eq!(
+      with.y + with.height,
+      without.y + without.height
+    );
+  }
+
+  #[test]
+  fn test_power_column_widens_lap_panel() {
+    let mut config = LapDataConfig::default();
+    let stats = vec!["5:00".to_string(); 3];
+    let details = vec!["1.25 m".to_string(); 3];
+    let drawer = Drawer::new(1080, 1920);
+    let rect = |config: &LapDataConfig| {
+      let panel = LapPanelLayout {
+        config,
+        sport: SportProfile::Running,
+        stats: &stats,
+        details: &details,
+        extra_label: None,
+        summary: None,
+      };
+      drawer.lap_panel_rect((300, 300), &panel).unwrap()
+    };
+    let without = rect(&config);
+    config.show_power = true;
+    let with = rect(&config);
+    assert_eq!(with.x, without.x);
+    assert_eq!(
+      with.width,
+      without.width + POWER_COLUMN_WIDTH
+    );
+  }
+
+  #[test]
+  fn test_lap_panel_shifted_inside_safe_area() {
+    let config = LapDataConfig::default();
+    let stats: Vec<String> = (0..12).map(|i| format!("5:{:02}", i)).collect();
+    let details = vec!["1.25".to_string(); 12];
+    let panel = LapPanelLayout {
+      config: &config,
+      sport: SportProfile::Running,
+      stats: &stats,
+      details: &details,
+      extra_label: Some("POWER"),
+      summary: Some("Elev +412 m"),
+    };
+
+    for area in [
+      SafeArea::default(),
+      SafeArea::uniform(0.05),
+      SafeArea::pixels(40.0, 120.0, 30.0, 60.0),
+    ] {
+      let drawer = Drawer::new(1080, 1920).with_safe_area(&area);
+      let start = drawer.lap_panel_start((0.99, 0.99).into(), &panel).unwrap();
+      let rect = drawer.lap_panel_rect(start, &panel).unwrap();
+      assert!(
+        inside(rect, drawer.safe_rect),
+        "{:?} outside {:?}",
+        rect,
+        drawer.safe_rect
+      );
+      // Shifted, not shrunk: the far corner sits on the safe edges
+      assert_eq!(
+        rect.x + rect.width,
+        drawer.safe_rect.x + drawer.safe_rect.width
+      );
+      assert_eq!(
+        rect.y + rect.height,
+        drawer.safe_rect.y + drawer.safe_rect.height
+      );
+    }
+  }
+
+  #[test]
+  fn test_fit_rect() {
+    let drawer = Drawer::new(100, 100).with_safe_area(&SafeArea::uniform(0.1));
+    // Already inside: unchanged
+    let rect = core::Rect::new(20, 30, 10, 10);
+    assert_eq!(drawer.fit_rect(rect), rect);
+    // Past the bottom right: shifted back
+    assert_eq!(
+      drawer.fit_rect(core::Rect::new(95, 95, 20, 10)),
+      core::Rect::new(70, 80, 20, 10)
+    );
+    // Larger than the area: top-left corner kept inside
+    assert_eq!(
+      drawer.fit_rect(core::Rect::new(-5, 50, 200, 10)),
+      core::Rect::new(10, 50, 200, 10)
+    );
+  }
+
+  #[test]
+  fn test_bearing_arrow_points_along_the_bearing() {
+    let lit = |bearing: f64, x: i32, y: i32| {
+      let mut frame = Mat::new_rows_cols_with_default(
+        41,
+        41,
+        core::CV_8UC3,
+        core::Scalar::all(0.0),
+      )
+      .unwrap();
+      Drawer::new(41, 41)
+        .bearing_arrow(
+          &mut frame,
+          core::Point::new(20, 20),
+          30,
+          bearing,
+          1,
+          core::Scalar::all(255.0),
+        )
+        .unwrap();
+      frame.at_2d::<core::Vec3b>(y, x).unwrap()[0] > 0
+    };
+
+    // East: the tip is right of the center, nothing above it
+    assert!(lit(90.0, 35, 20));
+    assert!(!lit(90.0, 20, 8));
+    // North is up the frame
+    assert!(lit(0.0, 20, 5));
+    assert!(!lit(0.0, 32, 20));
+  }
+
+  #[test]
+  fn test_dashed_polyline_runs_on_across_corners() {
+    let mut frame = Mat::new_rows_cols_with_default(
+      30,
+      30,
+      core::CV_8UC3,
+      core::Scalar::all(0.0),
+    )
+    .unwrap();
+    let mut drawer = Drawer::new(30, 30);
+    drawer.line = imgproc::LINE_8;
+    // Right 3 pixels, then 25 down, in dashes of 5
+    drawer
+      .dashed_polyline(
+        &mut frame,
+        &[
+          core::Point::new(0, 2),
+          core::Point::new(3, 2),
+          core::Point::new(3, 27),
+        ],
+        core::Scalar::all(255.0),
+        1,
+        5,
+      )
+      .unwrap();
+    let lit = |x: i32, y: i32| frame.at_2d::<core::Vec3b>(y, x).unwrap()[0] > 0;
+
+    assert!(lit(1, 2));
+    // The first dash ends 2 pixels past the corner
+    assert!(lit(3, 3));
+    assert!(!lit(3, 6));
+    assert!(lit(3, 10));
+    assert!(!lit(3, 16));
+    assert!(lit(3, 20));
   }
 }
@@ -0,0 +1,149 @@
+use anyhow::Result;
+use opencv::{core, prelude::*};
+
+use crate::{
+  configs::{EndCardConfig, Font},
+  utils::{element_drawer::Drawer, progression::hold_frame_count},
+};
+
+/// Color of the unfilled ring track
+const TRACK_COLOR: [f64; 4] = [60.0, 60.0, 60.0, 0.0];
+/// Largest font scale of the centered text
+const MAX_FONT_SCALE: f64 = 2.0;
+
+/// Completion ring held after the route is drawn, filling from the top
+/// around a centered stat over the card's frames
+#[derive(Debug, Clone)]
+pub struct EndCard {
+  frames: usize,
+  center: core::Point,
+  radius: i32,
+  thickness: i32,
+  accent: core::Scalar,
+  track: core::Scalar,
+  text: String,
+  font_scale: f64,
+}
+
+impl EndCard {
+  /// Lays out the card in the middle of the safe area, with `text` scaled
+  /// to fit inside the ring
+  pub fn new(
+    config: &EndCardConfig,
+    drawer: &Drawer,
+    fps: f64,
+    text: String,
+  ) -> Result<Self> {
+    let area = drawer.safe_rect;
+    let side = area.width.min(area.height) as f64;
+    let radius = ((side * config.radius) as i32).max(1);
+    let thickness = config.thickness.clamp(1, radius);
+
+    // The text keeps a margin inside the ring
+    let inner = (2 * (radius - thickness / 2 - thickness)).max(1);
+    let width = drawer.text_size(&text, 1.0, 2, Font::Duplex)?.width;
+    let font_scale = if width > 0 {
+      (inner as f64 / width as f64).min(MAX_FONT_SCALE)
+    } else {
+      1.0
+    };
+
+    Ok(Self {
+      frames: hold_frame_count(config.end_hold_seconds, fps),
+      center: core::Point::new(
+        area.x + area.width / 2,
+        area.y + area.height / 2,
+      ),
+      radius,
+      thickness,
+      accent: drawer.color(config.accent_color.to_bgra()),
+      track: drawer.color(TRACK_COLOR),
+      text,
+      font_scale,
+    })
+  }
+
+  /// Number of frames the card is held for
+  pub fn frames(&self) -> usize {
+    self.frames
+  }
+
+  /// Draws card frame `index` (0-based) over `frame`
+  pub fn draw(
+    &self,
+    drawer: &Drawer,
+    frame: &mut Mat,
+    index: usize,
+  ) -> Result<()> {
+    drawer.ring(
+      frame,
+      self.center,
+      self.radius,
+      self.thickness,
+      self.track,
+    )?;
+    drawer.arc_ring(
+      frame,
+      self.center,
+      self.radius,
+      self.thickness,
+      -90.0,
+      ring_sweep(index, self.frames),
+      self.accent,
+    )?;
+
+    let size = drawer.text_size(
+      &self.text,
+      self.font_scale,
+      2,
+      Font::Duplex,
+    )?;
+    drawer.text(
+      frame,
+      &self.text,
+      self.center.x - size.width / 2,
+      self.center.y + size.height / 2,
+      self.font_scale,
+      2,
+      Font::Duplex,
+      drawer.color([255.0, 255.0, 255.0, 0.0]),
+    )
+  }
+}
+
+/// Degrees of the ring filled on card frame `index` of `frames`: the ring
+/// grows every frame and is full on the last one
+pub fn ring_sweep(index: usize, frames: usize) -> f64 {
+  360.0 * (index + 1).min(frames) as f64 / frames.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_ring_sweep_fills_over_the_hold() {
+    assert_eq!(ring_sweep(0, 4), 90.0);
+    assert_eq!(ring_sweep(1, 4), 180.0);
+    assert_eq!(ring_sweep(3, 4), 360.0);
+    // Extra frames stay full
+    assert_eq!(ring_sweep(9, 4), 360.0);
+    assert_eq!(ring_sweep(0, 1), 360.0);
+  }
+
+  #[test]
+  fn test_end_card_layout() {
+    let drawer = Drawer::new(400, 800);
+    let card = EndCard::new(
+      &EndCardConfig::default(),
+      &drawer,
+      30.0,
+      "12.34 km".to_string(),
+    )
+    .unwrap();
+    assert_eq!(card.frames(), 60);
+    assert_eq!(card.center, core::Point::new(200, 400));
+    assert_eq!(card.radius, 80);
+    assert!(card.font_scale <= MAX_FONT_SCALE);
+  }
+}
@@ -0,0 +1,274 @@
+use std::{
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::types::fit_data::{LapData, RouteData};
+
+/// Features `route_to_geojson_with` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoJsonFeatures {
+  /// One LineString of the whole route
+  Line,
+  /// The LineString, then a Point for every record with its distance and
+  /// pace
+  LineAndPoints,
+}
+
+/// GeoJSON FeatureCollection of `route`: a LineString of its GPS points,
+/// with the total distance and point count as properties, for web maps
+pub fn route_to_geojson(route: &RouteData) -> String {
+  route_to_geojson_with(route, GeoJsonFeatures::Line)
+}
+
+/// Like `route_to_geojson`, with the `features` asked for. Positions are
+/// `[lon, lat]`, as GeoJSON orders them.
+pub fn route_to_geojson_with(
+  route: &RouteData,
+  features: GeoJsonFeatures,
+) -> String {
+  let position = |&(lat, lon): &(f64, f64)| json!([lon, lat]);
+  let mut collection = vec![json!({
+    "type": "Feature",
+    "geometry": {
+      "type": "LineString",
+      "coordinates": route.gps_points.iter().map(position).collect::<Value>(),
+    },
+    "properties": {
+      "total_distance_m": route.distances.last(),
+      "point_count": route.gps_points.len(),
+    },
+  })];
+  if features == GeoJsonFeatures::LineAndPoints {
+    collection.extend(
+      route.gps_points.iter().enumerate().map(|(i, point)| {
+        json!({
+          "type": "Feature",
+          "geometry": {"type": "Point", "coordinates": position(point)},
+          "properties": {
+            "index": i,
+            "distance_m": route.distances.get(i),
+            "pace": route.paces.get(i),
+          },
+        })
+      }),
+    );
+  }
+  json!({"type": "FeatureCollection", "features": collection}).to_string()
+}
+
+/// Writes `route_to_geojson` of `route` to `path`
+pub fn write_geojson(path: &Path, route: &RouteData) -> Result<()> {
+  fs::write(path, route_to_geojson(route))?;
+  Ok(())
+}
+
+/// CSV of `route` with a header row and one row per record: `index`,
+/// `lat`, `lon`, `distance_m`, `pace`, `hr` and `cadence`. Values that
+/// were not recorded are left empty.
+pub fn route_to_csv(route: &RouteData) -> String {
+  let mut csv = csv_row(
+    ["index", "lat", "lon", "distance_m", "pace", "hr", "cadence"]
+      .map(String::from),
+  );
+  for (i, &(lat, lon)) in route.gps_points.iter().enumerate() {
+    csv.push_str(&csv_row([
+      i.to_string(),
+      lat.to_string(),
+      lon.to_string(),
+      cell(route.distances.get(i)),
+      cell(route.paces.get(i)),
+      cell(route.heart_rates.get(i).copied().flatten()),
+      cell(route.cadences.get(i).copied().flatten()),
+    ]));
+  }
+  csv
+}
+
+/// CSV of `lap` with a header row and one row per lap: `lap`,
+/// `distance_m`, `pace`, `avg_hr`, `avg_cadence`, `avg_stride_dm` and
+/// `elapsed_s`
+pub fn laps_to_csv(lap: &LapData) -> String {
+  let mut csv = csv_row(
+    [
+      "lap",
+      "distance_m",
+      "pace",
+      "avg_hr",
+      "avg_cadence",
+      "avg_stride_dm",
+      "elapsed_s",
+    ]
+    .map(String::from),
+  );
+  for i in 0..lap.avg_speed.len() {
+    csv.push_str(&csv_row([
+      (i + 1).to_string(),
+      cell(lap.total_distance.get(i)),
+      cell(lap.enhanced_avg_speed.get(i)),
+      cell(lap.avg_heart_rate.get(i).copied().flatten()),
+      cell(lap.avg_cadence.get(i)),
+      cell(lap.avg_step_length.get(i).copied().flatten()),
+      cell(lap.total_elapsed_time.get(i)),
+    ]));
+  }
+  csv
+}
+
+/// Writes `route_to_csv` of `route` to `path` and, when there are laps,
+/// `laps_to_csv` of `lap` next to it (see `laps_csv_path`)
+pub fn export_csv(route: &RouteData, lap: &LapData, path: &str) -> Result<()> {
+  let path = Path::new(path);
+  fs::write(path, route_to_csv(route))?;
+  if !lap.avg_speed.is_empty() {
+    fs::write(laps_csv_path(path), laps_to_csv(lap))?;
+  }
+  Ok(())
+}
+
+/// Where `export_csv` writes the laps for records written to `path`:
+/// `run.csv` gives `run_laps.csv`
+pub fn laps_csv_path(path: &Path) -> PathBuf {
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!("{stem}_laps.csv"))
+}
+
+fn cell<T: Display>(value: Option<T>) -> String {
+  value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// One CSV line of `fields`, quoting those holding a comma, quote or line
+/// break and doubling their quotes
+fn csv_row<const N: usize>(fields: [String; N]) -> String {
+  let quoted: Vec<String> = fields
+    .into_iter()
+    .map(|field| {
+      if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+      } else {
+        field
+      }
+    })
+    .collect();
+  quoted.join(",") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::testing::synthetic_activity;
+  use crate::utils::workspace::Workspace;
+
+  /// Fields of each line of `csv`, undoing the quoting of `csv_row`
+  fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    csv
+      .lines()
+      .map(|line| {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+          match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+              chars.next();
+              fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+          }
+        }
+        fields
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_route_to_geojson() {
+    let route = synthetic_activity(100).route;
+    let geojson: Value =
+      serde_json::from_str(&route_to_geojson(&route)).unwrap();
+
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 1);
+    let line = &features[0];
+    assert_eq!(line["geometry"]["type"], "LineString");
+    let coordinates = line["geometry"]["coordinates"].as_array().unwrap();
+    assert_eq!(coordinates.len(), 100);
+    // Longitude first
+    let (lat, lon) = route.gps_points[0];
+    assert_eq!(coordinates[0], json!([lon, lat]));
+    assert_eq!(line["properties"]["point_count"], 100);
+    assert_eq!(
+      line["properties"]["total_distance_m"],
+      495.0
+    );
+  }
+
+  #[test]
+  fn test_route_to_geojson_with_points() {
+    let route = synthetic_activity(10).route;
+    let text = route_to_geojson_with(&route, GeoJsonFeatures::LineAndPoints);
+    let geojson: Value = serde_json::from_str(&text).unwrap();
+
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 11);
+    let point = &features[4];
+    assert_eq!(point["geometry"]["type"], "Point");
+    assert_eq!(point["properties"]["index"], 3);
+    assert_eq!(point["properties"]["distance_m"], 15.0);
+    assert_eq!(
+      point["properties"]["pace"],
+      route.paces[3].as_str()
+    );
+  }
+
+  #[test]
+  fn test_export_csv() {
+    // 2.25 km: three laps, the last one partial
+    let activity = synthetic_activity(450);
+    let workspace = Workspace::new("runarium_test").unwrap();
+    let path = workspace.path_for("run.csv");
+    export_csv(
+      &activity.route,
+      &activity.lap,
+      path.to_str().unwrap(),
+    )
+    .unwrap();
+
+    let records = parse_csv(&fs::read_to_string(&path).unwrap());
+    assert_eq!(records.len(), 451);
+    assert_eq!(
+      records[0],
+      ["index", "lat", "lon", "distance_m", "pace", "hr", "cadence"]
+    );
+    assert!(records.iter().all(|row| row.len() == 7));
+    assert_eq!(records[4][0], "3");
+    assert_eq!(records[4][3], "15");
+    assert_eq!(records[4][4], activity.route.paces[3]);
+
+    let laps = parse_csv(
+      &fs::read_to_string(workspace.path_for("run_laps.csv")).unwrap(),
+    );
+    assert_eq!(laps.len(), 4);
+    assert!(laps.iter().all(|row| row.len() == 7));
+  }
+
+  #[test]
+  fn test_csv_quoting() {
+    let mut route = synthetic_activity(2).route;
+    route.paces[0] = "5'30\", easy".to_string();
+    route.heart_rates[1] = None;
+    let rows = parse_csv(&route_to_csv(&route));
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1][4], "5'30\", easy");
+    assert_eq!(rows[2][5], "");
+    assert!(route_to_csv(&route).contains("\"5'30\"\", easy\""));
+  }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use crate::configs::RouteVideoConfig;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a hash of `bytes`.
+///
+/// Unlike `std`'s `DefaultHasher`, the result is stable across Rust
+/// releases and platforms, so it can key a persistent cache.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(FNV_OFFSET, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+  })
+}
+
+/// FNV-1a hash of `bytes` as 16 hex digits
+pub fn content_hash(bytes: &[u8]) -> String {
+  format!("{:016x}", fnv1a(bytes))
+}
+
+/// Cache key for a video rendered from `config` and a FIT file whose
+/// contents hash to `fit_bytes_hash` (e.g. `content_hash` of the file).
+///
+/// The FIT and output paths are ignored, so the same activity uploaded
+/// twice maps to the same key; the crate version is included because
+/// rendering may change between releases. Only meaningful for configs with
+/// `deterministic` set.
+///
+/// # Example
+/// ```
+/// use runarium::configs::RouteVideoConfig;
+/// use runarium::utils::fingerprint::{config_fingerprint, content_hash};
+///
+/// let fit_hash = content_hash(b"fit file bytes");
+/// let mut config = RouteVideoConfig::default();
+/// let key = config_fingerprint(&config, &fit_hash);
+///
+/// config.file_config.output_file = "outputs/other.mp4".to_string();
+/// assert_eq!(config_fingerprint(&config, &fit_hash), key);
+/// ```
+pub fn config_fingerprint(
+  config: &RouteVideoConfig,
+  fit_bytes_hash: &str,
+) -> String {
+  let mut config = config.clone();
+  config.file_config.fit_file.clear();
+  config.file_config.output_file.clear();
+
+  let key = format!(
+    "runarium {}\n{:?}\n{}",
+    env!("CARGO_PKG_VERSION"),
+    config,
+    fit_bytes_hash
+  );
+  content_hash(key.as_bytes())
+}
+
+/// Cache key for a video rendered from `config` and in-memory FIT and
+/// background files, as `progressive_route_from_bytes` takes them.
+///
+/// The background path is ignored like the other paths, but the output
+/// extension is kept, as it picks the container of the returned bytes.
+pub fn input_fingerprint(
+  config: &RouteVideoConfig,
+  fit: &[u8],
+  background: &[u8],
+) -> String {
+  let mut config = config.clone();
+  config.file_config.background_image.clear();
+  let extension = Path::new(&config.file_config.output_file)
+    .extension()
+    .map(|ext| ext.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+
+  let key = format!(
+    "{}\n{}\n{}",
+    config_fingerprint(&config, &content_hash(fit)),
+    content_hash(background),
+    extension
+  );
+  content_hash(key.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_content_hash() {
+    // Reference FNV-1a 64 values
+    assert_eq!(content_hash(b""), "cbf29ce484222325");
+    assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+  }
+
+  #[test]
+  fn test_fingerprint_tracks_render_settings() {
+    let config = RouteVideoConfig::default();
+    let key = config_fingerprint(&config, "fit-a");
+
+    assert_eq!(
+      config_fingerprint(&config, "fit-a"),
+      key
+    );
+    assert_ne!(
+      config_fingerprint(&config, "fit-b"),
+      key
+    );
+
+    let mut scaled = config.clone();
+    scaled.route_scale.scale = 0.3;
+    assert_ne!(
+      config_fingerprint(&scaled, "fit-a"),
+      key
+    );
+
+    // Paths do not change the rendered content
+    let mut moved = config;
+    moved.file_config.fit_file = "uploads/123.fit".to_string();
+    moved.file_config.output_file = "outputs/123.mp4".to_string();
+    assert_eq!(config_fingerprint(&moved, "fit-a"), key);
+  }
+
+  #[test]
+  fn test_input_fingerprint_tracks_both_files() {
+    let config = RouteVideoConfig::default();
+    let key = input_fingerprint(&config, b"fit", b"background");
+
+    let mut moved = config.clone();
+    moved.file_config.background_image = "uploads/bg.png".to_string();
+    assert_eq!(
+      input_fingerprint(&moved, b"fit", b"background"),
+      key
+    );
+    assert_ne!(
+      input_fingerprint(&config, b"fit", b"other background"),
+      key
+    );
+
+    // A different container is a different file
+    let mut webm = config.clone();
+    webm.file_config.output_file = "outputs/route.webm".to_string();
+    assert_ne!(
+      input_fingerprint(&webm, b"fit", b"background"),
+      key
+    );
+  }
+}
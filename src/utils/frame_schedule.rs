@@ -0,0 +1,493 @@
+use crate::{
+  analysis::{downsample_route, repair_route_distances, resolve_laps},
+  configs::{RouteVideoConfig, TimeIndexSampling, VideoTiming},
+  types::{
+    fit_data::Activity,
+    output::TimeIndexEntry,
+    warning::{Warning, Warnings},
+  },
+  utils::progression::{
+    crossed_lap, elapsed_seconds, hold_frame_count, lap_end_distances, lerp,
+    paced_indices, pen_distance, plan_frames, plan_real_time, PenPosition,
+    MAX_PAUSE_S,
+  },
+};
+
+/// Frame rate of `VideoTiming::RealTime` videos
+pub const REAL_TIME_FPS: f64 = 30.0;
+
+/// Frame timeline of a route video: one planned frame per pen position,
+/// a hold after each completed lap and the end card.
+///
+/// `FrameComposer` renders from it, and `compute_time_index` reads it
+/// without rendering, so both agree on every frame.
+#[derive(Debug, Clone)]
+pub struct FrameSchedule {
+  /// Pen position of each planned frame
+  pub plan: Vec<PenPosition>,
+  pub fps: f64,
+  /// Cumulative distance (meters) at which each lap ends
+  pub lap_ends: Vec<f64>,
+  /// Frames held after each completed lap (0 = no lap pause)
+  pub hold_frames: usize,
+  /// Frames of the end card (0 = no end card)
+  pub end_frames: usize,
+}
+
+/// One output frame of a `FrameSchedule`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduledFrame {
+  /// Planned frame `plan`, on which lap `completed_lap` may end
+  Route {
+    plan: usize,
+    pen: PenPosition,
+    completed_lap: Option<usize>,
+  },
+  /// Frame `hold` of the pause after lap `lap`
+  LapHold {
+    lap: usize,
+    hold: usize,
+    pen: PenPosition,
+  },
+  /// End card frame `index`
+  EndCard { index: usize, pen: PenPosition },
+}
+
+impl ScheduledFrame {
+  /// Pen position the frame shows
+  pub fn pen(&self) -> PenPosition {
+    match *self {
+      ScheduledFrame::Route { pen, .. }
+      | ScheduledFrame::LapHold { pen, .. }
+      | ScheduledFrame::EndCard { pen, .. } => pen,
+    }
+  }
+}
+
+impl FrameSchedule {
+  /// Plans the frames of a route of `point_count` records, fitting them
+  /// into the config's duration, or spacing them by `timestamps` for
+  /// real-time timing
+  pub fn new(
+    config: &RouteVideoConfig,
+    point_count: usize,
+    distances: &[f64],
+    timestamps: &[Option<i64>],
+    lap_distances: &[f64],
+    warnings: &mut Warnings,
+  ) -> Self {
+    let real_time = match config.timing {
+      VideoTiming::PerPoint => None,
+      VideoTiming::RealTime { speedup } => {
+        let len = point_count.min(timestamps.len());
+        let plan = plan_real_time(
+          &timestamps[..len],
+          speedup / REAL_TIME_FPS,
+          MAX_PAUSE_S,
+        );
+        warnings.push_if(plan.is_none(), Warning::RealTimeSkipped);
+        plan
+      }
+    };
+    let (plan, fps) = match real_time {
+      Some(plan) => (plan, REAL_TIME_FPS),
+      None => {
+        let planned = plan_frames(
+          config.progression,
+          point_count,
+          distances,
+        );
+        // The fps is fitted to the frames the pacing curve leaves
+        let plan: Vec<PenPosition> =
+          paced_indices(config.pacing, planned.len())
+            .into_iter()
+            .map(|i| planned[i])
+            .collect();
+        let fps = frame_rate(
+          plan.len(),
+          config.effective_duration(),
+          warnings,
+        );
+        (plan, fps)
+      }
+    };
+    Self {
+      lap_ends: lap_end_distances(lap_distances),
+      hold_frames: config.lap_pause.map_or(0, |pause| {
+        hold_frame_count(pause.hold_seconds, fps)
+      }),
+      end_frames: config.end_card.map_or(0, |card| {
+        hold_frame_count(card.end_hold_seconds, fps)
+      }),
+      plan,
+      fps,
+    }
+  }
+
+  /// Most frames the video can have, with a hold after every lap
+  pub fn max_frames(&self) -> usize {
+    self.plan.len() + self.lap_ends.len() * self.hold_frames + self.end_frames
+  }
+
+  /// Lap that ends as the pen moves from `previous` to `current` meters
+  pub fn completed_lap(
+    &self,
+    previous: Option<f64>,
+    current: Option<f64>,
+  ) -> Option<usize> {
+    crossed_lap(&self.lap_ends, previous?, current?)
+  }
+
+  /// Every output frame in order; `distances` places the lap ends
+  pub fn frames(&self, distances: &[f64]) -> Vec<ScheduledFrame> {
+    let mut frames = Vec::with_capacity(self.plan.len());
+    let mut previous = None;
+    for (plan, &pen) in self.plan.iter().enumerate() {
+      let distance = pen_distance(distances, pen);
+      let completed_lap = self.completed_lap(previous, distance);
+      previous = distance;
+
+      frames.push(ScheduledFrame::Route {
+        plan,
+        pen,
+        completed_lap,
+      });
+      if let Some(lap) = completed_lap {
+        frames.extend(
+          (0..self.hold_frames).map(|hold| ScheduledFrame::LapHold {
+            lap,
+            hold,
+            pen,
+          }),
+        );
+      }
+    }
+
+    // The card is drawn over the last route frame
+    if let Some(&pen) = self.plan.last() {
+      frames.extend(
+        (0..self.end_frames)
+          .map(|index| ScheduledFrame::EndCard { index, pen }),
+      );
+    }
+    frames
+  }
+
+  /// Video time, activity time and distance of the frames `sampling`
+  /// picks. `record_times` holds the seconds since the first record.
+  pub fn time_index(
+    &self,
+    distances: &[f64],
+    record_times: &[Option<f64>],
+    sampling: TimeIndexSampling,
+  ) -> Vec<TimeIndexEntry> {
+    let frames = self.frames(distances);
+    let last = frames.len().saturating_sub(1);
+    frames
+      .iter()
+      .enumerate()
+      .filter_map(|(frame, scheduled)| {
+        let lap = match *scheduled {
+          ScheduledFrame::Route { completed_lap, .. } => completed_lap,
+          _ => None,
+        };
+        let picked = match sampling {
+          TimeIndexSampling::Off => false,
+          TimeIndexSampling::EveryFrames(n) => {
+            frame.is_multiple_of(n.max(1)) || frame == last
+          }
+          TimeIndexSampling::LapBoundaries => lap.is_some(),
+        };
+        picked.then(|| {
+          let pen = scheduled.pen();
+          TimeIndexEntry {
+            frame,
+            video_time_s: frame as f64 / self.fps,
+            activity_time_s: pen_time(record_times, pen),
+            distance_m: pen_distance(distances, pen),
+            lap,
+          }
+        })
+      })
+      .collect()
+  }
+}
+
+/// Time index of the video `config` would render from `activity`, without
+/// loading the background or drawing anything
+pub fn compute_time_index(
+  activity: &Activity,
+  config: &RouteVideoConfig,
+  sampling: TimeIndexSampling,
+) -> Vec<TimeIndexEntry> {
+  // The distances and laps the composer would hold after
+  let mut route = activity.route.clone();
+  repair_route_distances(config.distance_repair, &mut route);
+  downsample_route(config.max_points, &mut route);
+  let lap = resolve_laps(
+    config.lap_data.source,
+    &route,
+    activity.lap.clone(),
+  );
+  let schedule = FrameSchedule::new(
+    config,
+    route.gps_points.len(),
+    &route.distances,
+    &route.timestamps,
+    &lap.total_distance,
+    &mut Warnings::default(),
+  );
+  schedule.time_index(
+    &route.distances,
+    &elapsed_seconds(&route.timestamps),
+    sampling,
+  )
+}
+
+/// Frame rate that fits `frames` into `duration` seconds, at least 1 fps
+fn frame_rate(frames: usize, duration: f64, warnings: &mut Warnings) -> f64 {
+  let planned_fps = frames as f64 / duration;
+  warnings.push_if(
+    planned_fps < 1.0,
+    Warning::FrameRateRaised { planned_fps },
+  );
+  planned_fps.floor().max(1.0)
+}
+
+/// Seconds since the first record at the pen, interpolated between
+/// timestamped records
+fn pen_time(record_times: &[Option<f64>], pen: PenPosition) -> Option<f64> {
+  let current = (*record_times.get(pen.index)?)?;
+  match record_times.get(pen.index + 1) {
+    Some(&Some(next)) if pen.fraction > 0.0 => {
+      Some(lerp(current, next, pen.fraction))
+    }
+    _ => Some(current),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    configs::{EndCardConfig, LapPause, PacingCurve, ProgressionMode},
+    utils::testing::synthetic_activity,
+  };
+
+  fn config() -> RouteVideoConfig {
+    RouteVideoConfig {
+      duration_secs: 10.0,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_frame_rate_warning() {
+    let mut warnings = Warnings::default();
+    assert_eq!(
+      frame_rate(300, 10.0, &mut warnings),
+      30.0
+    );
+    assert!(warnings.is_empty());
+
+    // 5 frames cannot fill 10 seconds
+    assert_eq!(frame_rate(5, 10.0, &mut warnings), 1.0);
+    assert_eq!(
+      warnings.0,
+      vec![Warning::FrameRateRaised { planned_fps: 0.5 }]
+    );
+  }
+
+  #[test]
+  fn test_frames_hold_after_laps_and_end_with_the_card() {
+    // 1000 records 5 m apart: 5 km in 1 km laps, at 100 fps
+    let activity = synthetic_activity(1000);
+    let mut config = config();
+    config.lap_pause = Some(LapPause {
+      hold_seconds: 0.05,
+      pulse_highlight: false,
+    });
+    config.end_card = Some(EndCardConfig {
+      end_hold_seconds: 0.1,
+      ..Default::default()
+    });
+    let schedule = FrameSchedule::new(
+      &config,
+      1000,
+      &activity.route.distances,
+      &activity.route.timestamps,
+      &activity.lap.total_distance,
+      &mut Warnings::default(),
+    );
+    assert_eq!(schedule.fps, 100.0);
+    assert_eq!(schedule.hold_frames, 5);
+
+    let frames = schedule.frames(&activity.route.distances);
+    // The last lap ends past the last record, so 4 laps are held
+    assert_eq!(frames.len(), 1000 + 4 * 5 + 10);
+    assert!(frames.len() <= schedule.max_frames());
+    assert_eq!(
+      frames[201],
+      ScheduledFrame::LapHold {
+        lap: 0,
+        hold: 0,
+        pen: PenPosition {
+          index: 200,
+          fraction: 0.0,
+        },
+      }
+    );
+    assert!(matches!(
+      frames.last(),
+      Some(ScheduledFrame::EndCard { index: 9, .. })
+    ));
+  }
+
+  #[test]
+  fn test_pacing_curve_sets_the_frame_rate() {
+    let activity = synthetic_activity(1000);
+    let config = RouteVideoConfig {
+      pacing: PacingCurve::EaseInOutRamp {
+        max_skip: 4,
+        edge_fraction: 0.1,
+      },
+      ..config()
+    };
+    let schedule = FrameSchedule::new(
+      &config,
+      1000,
+      &activity.route.distances,
+      &activity.route.timestamps,
+      &[],
+      &mut Warnings::default(),
+    );
+    let shown = paced_indices(config.pacing, 1000).len();
+    assert!(shown < 700);
+    assert_eq!(schedule.plan.len(), shown);
+    assert_eq!(
+      schedule.fps,
+      (shown as f64 / 10.0).floor()
+    );
+    assert_eq!(schedule.plan.last().unwrap().index, 999);
+  }
+
+  #[test]
+  fn test_real_time_timing() {
+    let activity = synthetic_activity(1000);
+    let route = &activity.route;
+    let config = RouteVideoConfig {
+      timing: VideoTiming::RealTime { speedup: 60.0 },
+      ..config()
+    };
+    let mut warnings = Warnings::default();
+    let schedule = FrameSchedule::new(
+      &config,
+      1000,
+      &route.distances,
+      &route.timestamps,
+      &[],
+      &mut warnings,
+    );
+    assert!(warnings.is_empty());
+    assert_eq!(schedule.fps, REAL_TIME_FPS);
+
+    // A video second covers a minute of the run, whatever duration_secs
+    let activity_s =
+      (route.timestamps[999].unwrap() - route.timestamps[0].unwrap()) as f64;
+    assert_eq!(
+      schedule.plan.len(),
+      (activity_s / 2.0).ceil() as usize + 1
+    );
+    assert_eq!(schedule.plan.last().unwrap().index, 999);
+
+    // Without timestamps the duration is fitted as usual
+    let mut warnings = Warnings::default();
+    let schedule = FrameSchedule::new(
+      &config,
+      1000,
+      &route.distances,
+      &[None; 1000],
+      &[],
+      &mut warnings,
+    );
+    assert_eq!(
+      warnings.0,
+      vec![Warning::RealTimeSkipped]
+    );
+    assert_eq!(schedule.plan.len(), 1000);
+    assert_eq!(schedule.fps, 100.0);
+  }
+
+  #[test]
+  fn test_time_index_sampling() {
+    let activity = synthetic_activity(1000);
+    let mut config = config();
+    config.lap_pause = Some(LapPause {
+      hold_seconds: 1.0,
+      pulse_highlight: false,
+    });
+
+    // Lap 1 ends on record 200 (1000 m), then 100 held frames follow
+    let laps = compute_time_index(
+      &activity,
+      &config,
+      TimeIndexSampling::LapBoundaries,
+    );
+    assert_eq!(laps.len(), 4);
+    assert_eq!(laps[0].frame, 200);
+    assert_eq!(laps[0].distance_m, Some(1000.0));
+    assert_eq!(laps[0].lap, Some(0));
+    assert_eq!(laps[1].frame, 400 + 100);
+    assert_eq!(laps[1].video_time_s, 5.0);
+
+    // The holds add video time, not activity time
+    let every = compute_time_index(
+      &activity,
+      &config,
+      TimeIndexSampling::EveryFrames(50),
+    );
+    let at = |frame| every.iter().find(|e| e.frame == frame).unwrap();
+    assert_eq!(
+      at(250).activity_time_s,
+      at(300).activity_time_s
+    );
+    assert_eq!(at(250).distance_m, Some(1000.0));
+    assert_eq!(
+      every.last().unwrap().frame,
+      1000 + 4 * 100 - 1
+    );
+
+    assert!(compute_time_index(
+      &activity,
+      &config,
+      TimeIndexSampling::Off
+    )
+    .is_empty());
+  }
+
+  #[test]
+  fn test_time_index_interpolates_between_records() {
+    let activity = synthetic_activity(100);
+    let config = RouteVideoConfig {
+      progression: ProgressionMode::PerDistance {
+        meters_per_frame: 2.5,
+      },
+      ..config()
+    };
+    let index = compute_time_index(
+      &activity,
+      &config,
+      TimeIndexSampling::EveryFrames(1),
+    );
+    // Halfway between the first two records
+    let (first, second) = (
+      activity.route.timestamps[0].unwrap(),
+      activity.route.timestamps[1].unwrap(),
+    );
+    assert_eq!(index[1].distance_m, Some(2.5));
+    assert_eq!(
+      index[1].activity_time_s,
+      Some((second - first) as f64 / 2.0)
+    );
+  }
+}
@@ -0,0 +1,192 @@
+use std::{
+  fs::{self, File},
+  io::BufWriter,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use anyhow::{bail, Result};
+use image::{
+  codecs::gif::{GifEncoder, Repeat},
+  Delay, Frame, RgbaImage,
+};
+use opencv::{core::Mat, imgproc, prelude::*, videoio};
+
+use crate::{
+  configs::{RouteVideoConfig, VideoFallback},
+  types::warning::Warning,
+  utils::creator::{image_creator, video_creator_with_options},
+};
+
+/// GIF encoder speed (1-30); higher is faster with coarser colors
+const GIF_SPEED: i32 = 10;
+
+/// Where composed frames go: the requested video or, when its codec
+/// cannot be opened, the fallback writer of `RouteVideoConfig::fallback`
+pub struct FrameSink {
+  writer: Writer,
+  /// File, or directory of frames, the sink writes to
+  pub output_file: String,
+  /// Set when the frames go to a fallback instead of the video
+  pub warning: Option<Warning>,
+}
+
+enum Writer {
+  Video(videoio::VideoWriter),
+  Sequence {
+    dir: PathBuf,
+    written: usize,
+  },
+  Gif {
+    encoder: GifEncoder<BufWriter<File>>,
+    delay: Delay,
+    rgba: Mat,
+  },
+}
+
+impl FrameSink {
+  /// Opens a video writer with the config's codec and output file. When
+  /// the OpenCV build cannot open the codec, opens the fallback writer
+  /// instead, or fails for `VideoFallback::Error`, so no frame is composed
+  /// in vain.
+  pub fn open(
+    width: i32,
+    height: i32,
+    fps: f64,
+    config: &RouteVideoConfig,
+  ) -> Result<Self> {
+    let output_file = &config.file_config.output_file;
+    let video = video_creator_with_options(
+      width,
+      height,
+      fps,
+      output_file,
+      config.codec,
+      config.deterministic,
+    )?;
+    if video.is_opened()? {
+      return Ok(Self {
+        writer: Writer::Video(video),
+        output_file: output_file.clone(),
+        warning: None,
+      });
+    }
+
+    let codec = config.codec.name();
+    let (writer, path) = match config.fallback {
+      VideoFallback::Error => bail!(
+        "Cannot open video codec {} for {}; this OpenCV build may lack \
+         FFmpeg. Run `runarium doctor`, or set `fallback` to write a frame \
+         sequence or GIF instead",
+        codec,
+        output_file
+      ),
+      VideoFallback::FrameSequence => {
+        let dir = frame_sequence_dir(output_file);
+        fs::create_dir_all(&dir)?;
+        let writer = Writer::Sequence {
+          dir: dir.clone(),
+          written: 0,
+        };
+        (writer, dir)
+      }
+      VideoFallback::Gif => {
+        let path = gif_path(output_file);
+        let file = BufWriter::new(File::create(&path)?);
+        let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let writer = Writer::Gif {
+          encoder,
+          delay: Delay::from_saturating_duration(Duration::from_secs_f64(
+            1.0 / fps.max(1.0),
+          )),
+          rgba: Mat::default(),
+        };
+        (writer, path)
+      }
+    };
+
+    let output_file = path.to_string_lossy().into_owned();
+    Ok(Self {
+      writer,
+      warning: Some(Warning::VideoFallback {
+        codec,
+        output_file: output_file.clone(),
+      }),
+      output_file,
+    })
+  }
+
+  /// Writes one BGR frame
+  pub fn write(&mut self, frame: &Mat) -> Result<()> {
+    match &mut self.writer {
+      Writer::Video(video) => video.write(frame)?,
+      Writer::Sequence { dir, written } => {
+        *written += 1;
+        let path = dir.join(format!("frame_{:06}.png", written));
+        image_creator(&path.to_string_lossy(), frame)?;
+      }
+      Writer::Gif {
+        encoder,
+        delay,
+        rgba,
+      } => {
+        imgproc::cvt_color_def(frame, rgba, imgproc::COLOR_BGR2RGBA)?;
+        let Some(image) = RgbaImage::from_raw(
+          rgba.cols() as u32,
+          rgba.rows() as u32,
+          rgba.data_bytes()?.to_vec(),
+        ) else {
+          bail!("Frame could not be converted for the GIF");
+        };
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, *delay))?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Finishes the video or GIF; frame sequences are complete already
+  pub fn finish(self) -> Result<()> {
+    match self.writer {
+      Writer::Video(mut video) => video.release()?,
+      // Dropping the encoder writes the GIF trailer
+      Writer::Gif { encoder, .. } => drop(encoder),
+      Writer::Sequence { .. } => {}
+    }
+    Ok(())
+  }
+}
+
+/// Directory the frame sequence fallback writes to: `<name>_frames` next
+/// to the output
+pub fn frame_sequence_dir(output_file: &str) -> PathBuf {
+  let path = Path::new(output_file);
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!("{}_frames", stem))
+}
+
+/// File the GIF fallback writes to: the output with a `.gif` extension
+pub fn gif_path(output_file: &str) -> PathBuf {
+  Path::new(output_file).with_extension("gif")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fallback_paths() {
+    assert_eq!(
+      frame_sequence_dir("outputs/run.mp4"),
+      PathBuf::from("outputs/run_frames")
+    );
+    assert_eq!(
+      frame_sequence_dir("run"),
+      PathBuf::from("run_frames")
+    );
+    assert_eq!(
+      gif_path("outputs/run.mp4"),
+      PathBuf::from("outputs/run.gif")
+    );
+  }
+}
@@ -0,0 +1,243 @@
+use std::ops::Range;
+
+use axum::{
+  body::Bytes,
+  http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+
+/// What a `Range` request header asks of a body of known length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ByteRange {
+  /// The whole body: the header is malformed, names another unit or asks
+  /// for several ranges, and is ignored
+  Full,
+  /// One satisfiable range of byte offsets (end exclusive)
+  Partial(Range<usize>),
+  /// A well-formed byte range that starts past the end, or an empty
+  /// suffix
+  Unsatisfiable,
+}
+
+/// Parses a `Range` header for a body of `len` bytes, per RFC 7233.
+///
+/// Handles `bytes=first-last`, open-ended `bytes=first-` and suffix
+/// `bytes=-count` ranges; a last byte past the end is clamped to it.
+/// Malformed headers and multi-range requests are ignored and answered
+/// with the full body; only well-formed ranges can be unsatisfiable.
+pub fn parse_range(header: &str, len: usize) -> ByteRange {
+  let Some((unit, spec)) = header.split_once('=') else {
+    return ByteRange::Full;
+  };
+  if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+    return ByteRange::Full;
+  }
+  let Some((first, last)) = spec.split_once('-') else {
+    return ByteRange::Full;
+  };
+  let len = len as u64;
+
+  let range = match (offset(first), offset(last)) {
+    // The last `count` bytes
+    (None, Some(count)) if first.trim().is_empty() && count > 0 => {
+      len.saturating_sub(count)..len
+    }
+    (Some(first), None) if last.trim().is_empty() => first..len,
+    (Some(first), Some(last)) if last >= first => {
+      first..last.saturating_add(1).min(len)
+    }
+    (None, Some(0)) if first.trim().is_empty() => {
+      return ByteRange::Unsatisfiable
+    }
+    _ => return ByteRange::Full,
+  };
+  if range.start >= len {
+    return ByteRange::Unsatisfiable;
+  }
+  ByteRange::Partial(range.start as usize..range.end as usize)
+}
+
+/// Response to a GET of `data` with the request's `Range` header: 200
+/// with the whole body, 206 with one range, or 416 when the range cannot
+/// be served. Every response advertises `Accept-Ranges: bytes`.
+pub fn serve_bytes(
+  range_header: Option<&str>,
+  data: &[u8],
+) -> (StatusCode, HeaderMap, Bytes) {
+  let mut headers = HeaderMap::new();
+  headers.insert(
+    header::ACCEPT_RANGES,
+    HeaderValue::from_static("bytes"),
+  );
+  let range = range_header.map_or(ByteRange::Full, |h| {
+    parse_range(h, data.len())
+  });
+
+  match range {
+    ByteRange::Full => (
+      StatusCode::OK,
+      headers,
+      Bytes::copy_from_slice(data),
+    ),
+    ByteRange::Partial(range) => {
+      headers.insert(
+        header::CONTENT_RANGE,
+        content_range(format!(
+          "bytes {}-{}/{}",
+          range.start,
+          range.end - 1,
+          data.len()
+        )),
+      );
+      (
+        StatusCode::PARTIAL_CONTENT,
+        headers,
+        Bytes::copy_from_slice(&data[range]),
+      )
+    }
+    ByteRange::Unsatisfiable => {
+      headers.insert(
+        header::CONTENT_RANGE,
+        content_range(format!("bytes */{}", data.len())),
+      );
+      (
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        headers,
+        Bytes::new(),
+      )
+    }
+  }
+}
+
+/// Byte offset of a range bound; offsets too large for u64 saturate
+fn offset(value: &str) -> Option<u64> {
+  let value = value.trim();
+  if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  Some(value.parse().unwrap_or(u64::MAX))
+}
+
+fn content_range(value: String) -> HeaderValue {
+  // Digits, spaces and punctuation only
+  HeaderValue::from_str(&value).expect("Content-Range is ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_range() {
+    let partial = |r: Range<usize>| ByteRange::Partial(r);
+    assert_eq!(
+      parse_range("bytes=0-99", 1000),
+      partial(0..100)
+    );
+    assert_eq!(
+      parse_range("bytes=500-", 1000),
+      partial(500..1000)
+    );
+    assert_eq!(
+      parse_range("bytes=-200", 1000),
+      partial(800..1000)
+    );
+    assert_eq!(
+      parse_range("Bytes = 10-10", 1000),
+      partial(10..11)
+    );
+
+    // Past the end: the last byte is clamped, a suffix takes everything
+    assert_eq!(
+      parse_range("bytes=900-5000", 1000),
+      partial(900..1000)
+    );
+    assert_eq!(
+      parse_range("bytes=-5000", 1000),
+      partial(0..1000)
+    );
+    assert_eq!(
+      parse_range("bytes=0-99999999999999999999999", 10),
+      partial(0..10)
+    );
+
+    // Other units and multi-range requests get the whole body
+    assert_eq!(
+      parse_range("items=0-5", 1000),
+      ByteRange::Full
+    );
+    assert_eq!(
+      parse_range("bytes=0-1,5-6", 1000),
+      ByteRange::Full
+    );
+  }
+
+  #[test]
+  fn test_parse_malformed_ranges() {
+    // Ignored, so the whole body is sent
+    for header in [
+      "bytes=50-10",
+      "bytes=-",
+      "bytes=a-b",
+      "bytes=5",
+      "bytes=+1-2",
+      "0-10",
+      "",
+    ] {
+      assert_eq!(
+        parse_range(header, 1000),
+        ByteRange::Full,
+        "{}",
+        header
+      );
+    }
+  }
+
+  #[test]
+  fn test_parse_unsatisfiable_ranges() {
+    for header in ["bytes=1000-", "bytes=1000-1200", "bytes=-0"] {
+      assert_eq!(
+        parse_range(header, 1000),
+        ByteRange::Unsatisfiable,
+        "{}",
+        header
+      );
+    }
+    assert_eq!(
+      parse_range("bytes=-10", 0),
+      ByteRange::Unsatisfiable
+    );
+  }
+
+  #[test]
+  fn test_serve_bytes() {
+    let data: Vec<u8> = (0..10).collect();
+
+    let (status, headers, body) = serve_bytes(None, &data);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+    assert_eq!(body.as_ref(), &data[..]);
+
+    let (status, headers, body) = serve_bytes(Some("bytes=2-4"), &data);
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+      headers[header::CONTENT_RANGE],
+      "bytes 2-4/10"
+    );
+    assert_eq!(body.as_ref(), &[2, 3, 4]);
+
+    let (status, headers, body) = serve_bytes(Some("bytes=10-"), &data);
+    assert_eq!(
+      status,
+      StatusCode::RANGE_NOT_SATISFIABLE
+    );
+    assert_eq!(
+      headers[header::CONTENT_RANGE],
+      "bytes */10"
+    );
+    assert!(body.is_empty());
+
+    let (status, _, body) = serve_bytes(Some("bytes=4"), &data);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_ref(), &data[..]);
+  }
+}
@@ -0,0 +1,212 @@
+use std::f64::consts::TAU;
+
+use anyhow::{bail, Result};
+use opencv::{core, imgcodecs, imgproc, prelude::*};
+
+use crate::{
+  configs::{MarkerStyle, PositionMarker},
+  utils::element_drawer::Drawer,
+};
+
+/// Radius of the ring marker, as the dot drawn by `Drawer::point`
+pub const RING_RADIUS: i32 = 8;
+
+/// Position marker ready to draw: checked, colored and with its sprite
+/// loaded, so a bad marker fails before rendering starts
+#[derive(Clone)]
+pub struct Marker {
+  style: MarkerStyle,
+  color: core::Scalar,
+  sprite: Option<Mat>,
+}
+
+impl Marker {
+  /// Checks `marker` and loads its sprite; `default_color` is used when the
+  /// marker has no color of its own
+  pub fn load(
+    marker: &PositionMarker,
+    default_color: core::Scalar,
+    drawer: &Drawer,
+  ) -> Result<Self> {
+    let color = marker.color.map_or(default_color, |color| {
+      drawer.color(color.to_bgra())
+    });
+    let sprite = match &marker.style {
+      MarkerStyle::Dot => None,
+      MarkerStyle::Ring { thickness } => {
+        if *thickness < 1 {
+          bail!("Position marker ring thickness must be at least 1");
+        }
+        None
+      }
+      MarkerStyle::Sprite { path, scale } => Some(load_sprite(path, *scale)?),
+      MarkerStyle::PulsingDot {
+        period_frames,
+        min_r,
+        max_r,
+      } => {
+        if *period_frames == 0 || *min_r < 1 || max_r < min_r {
+          bail!(
+            "Position marker pulse needs period_frames > 0 and \
+             1 <= min_r <= max_r"
+          );
+        }
+        None
+      }
+    };
+
+    Ok(Self {
+      style: marker.style.clone(),
+      color,
+      sprite,
+    })
+  }
+
+  /// Draws the marker at `point`; `frame_index` drives the pulse
+  pub fn draw(
+    &self,
+    drawer: &Drawer,
+    frame: &mut Mat,
+    point: core::Point,
+    frame_index: usize,
+  ) -> Result<()> {
+    match (&self.style, &self.sprite) {
+      (MarkerStyle::Ring { thickness }, _) => drawer.ring(
+        frame,
+        point,
+        RING_RADIUS,
+        *thickness,
+        self.color,
+      ),
+      (MarkerStyle::Sprite { .. }, Some(sprite)) => {
+        drawer.overlay_image(frame, sprite, point)
+      }
+      (
+        &MarkerStyle::PulsingDot {
+          period_frames,
+          min_r,
+          max_r,
+        },
+        _,
+      ) => drawer.dot(
+        frame,
+        point,
+        pulse_radius(frame_index, period_frames, min_r, max_r),
+        self.color,
+      ),
+      _ => drawer.point(frame, point, self.color),
+    }
+  }
+}
+
+/// Pulse radius at `frame_index`: `max_r` at the start of every period,
+/// easing down to `min_r` halfway through
+pub fn pulse_radius(
+  frame_index: usize,
+  period_frames: u32,
+  min_r: i32,
+  max_r: i32,
+) -> i32 {
+  let period = period_frames.max(1) as usize;
+  let phase = (frame_index % period) as f64 / period as f64;
+  let swing = (1.0 + (TAU * phase).cos()) / 2.0;
+  min_r + ((max_r - min_r) as f64 * swing).round() as i32
+}
+
+/// Reads a sprite, keeping its alpha channel, and scales it
+fn load_sprite(path: &str, scale: f64) -> Result<Mat> {
+  if !(scale.is_finite() && scale > 0.0) {
+    bail!("Position marker sprite scale must be positive");
+  }
+  let image = imgcodecs::imread(path, imgcodecs::IMREAD_UNCHANGED)?;
+  if image.empty() {
+    bail!(
+      "Position marker sprite '{}' could not be read",
+      path
+    );
+  }
+  let image = if image.channels() == 1 {
+    let mut color = Mat::default();
+    imgproc::cvt_color_def(
+      &image,
+      &mut color,
+      imgproc::COLOR_GRAY2BGR,
+    )?;
+    color
+  } else {
+    image
+  };
+  if scale == 1.0 {
+    return Ok(image);
+  }
+
+  let size = image.size()?;
+  let mut scaled = Mat::default();
+  imgproc::resize(
+    &image,
+    &mut scaled,
+    core::Size::new(
+      ((size.width as f64 * scale).round() as i32).max(1),
+      ((size.height as f64 * scale).round() as i32).max(1),
+    ),
+    0.0,
+    0.0,
+    imgproc::INTER_AREA,
+  )?;
+  Ok(scaled)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load(style: MarkerStyle) -> Result<Marker> {
+    Marker::load(
+      &PositionMarker { style, color: None },
+      core::Scalar::all(255.0),
+      &Drawer::new(100, 100),
+    )
+  }
+
+  #[test]
+  fn test_pulse_radius_cycle() {
+    assert_eq!(pulse_radius(0, 10, 4, 12), 12);
+    assert_eq!(pulse_radius(5, 10, 4, 12), 4);
+    assert_eq!(pulse_radius(10, 10, 4, 12), 12);
+    // A quarter period is halfway between
+    assert_eq!(pulse_radius(2, 8, 4, 12), 8);
+    assert_eq!(pulse_radius(7, 1, 4, 12), 12);
+  }
+
+  #[test]
+  fn test_invalid_markers_fail_to_load() {
+    assert!(load(MarkerStyle::Dot).is_ok());
+    assert!(load(MarkerStyle::Ring { thickness: 3 }).is_ok());
+    assert!(load(MarkerStyle::Ring { thickness: 0 }).is_err());
+    assert!(load(MarkerStyle::PulsingDot {
+      period_frames: 0,
+      min_r: 4,
+      max_r: 12,
+    })
+    .is_err());
+    assert!(load(MarkerStyle::PulsingDot {
+      period_frames: 10,
+      min_r: 12,
+      max_r: 4,
+    })
+    .is_err());
+
+    let error = load(MarkerStyle::Sprite {
+      path: "missing/runner.png".into(),
+      scale: 0.5,
+    })
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("missing/runner.png"));
+    assert!(load(MarkerStyle::Sprite {
+      path: "runner.png".into(),
+      scale: 0.0,
+    })
+    .is_err());
+  }
+}
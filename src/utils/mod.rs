@@ -1,5 +1,38 @@
+#[cfg(feature = "render")]
+pub mod background;
+#[cfg(feature = "render")]
+pub mod chart;
 pub mod converter;
+#[cfg(feature = "render")]
 pub mod creator;
+pub mod direction;
+#[cfg(feature = "render")]
 pub mod element_drawer;
+#[cfg(feature = "render")]
+pub mod end_card;
+pub mod export;
+pub mod fingerprint;
+pub mod frame_schedule;
+#[cfg(feature = "render")]
+pub mod frame_sink;
+#[cfg(feature = "axum")]
+pub mod http_range;
+#[cfg(feature = "render")]
+pub mod marker;
+pub mod output_sink;
+pub mod palette;
+pub mod parallel;
 pub mod performance;
+#[cfg(feature = "render")]
+pub mod pixel_map;
+pub mod progression;
+pub mod projection;
 pub mod read_file;
+#[cfg(feature = "render")]
+pub mod runtime;
+pub mod segment_color;
+#[cfg(feature = "render")]
+pub mod smoothing;
+pub mod stats;
+pub mod testing;
+pub mod workspace;
@@ -0,0 +1,213 @@
+use std::{fmt, path::Path, process::Command, sync::Arc};
+
+use anyhow::{bail, Result};
+
+use crate::{error::RunariumError, types::output::RenderOutput};
+
+/// Callback of an `OutputSink::Callback`, given the finished render
+pub type OutputCallback = dyn Fn(&RenderOutput) -> Result<()> + Send + Sync;
+
+/// Where a finished output goes after it is written, e.g. an uploader.
+///
+/// The sink runs once the output and its sidecar are on disk. A failure
+/// leaves the output in place and is returned as
+/// `RunariumError::OutputSinkFailed`.
+#[derive(Clone)]
+pub enum OutputSink {
+  /// Runs `program` with `args_template`, where `{output}` is replaced by
+  /// the output path and `{id}` by its file name without the extension
+  Command {
+    program: String,
+    args_template: Vec<String>,
+  },
+  /// Calls a function of the library user
+  Callback(Arc<OutputCallback>),
+}
+
+impl OutputSink {
+  /// Creates a command sink
+  pub fn command(
+    program: impl Into<String>,
+    args_template: impl IntoIterator<Item = impl Into<String>>,
+  ) -> Self {
+    OutputSink::Command {
+      program: program.into(),
+      args_template: args_template.into_iter().map(Into::into).collect(),
+    }
+  }
+
+  /// Creates a callback sink
+  pub fn callback(
+    f: impl Fn(&RenderOutput) -> Result<()> + Send + Sync + 'static,
+  ) -> Self {
+    OutputSink::Callback(Arc::new(f))
+  }
+
+  /// Parses a command line such as `aws s3 cp {output} s3://runs/{id}.mp4`.
+  ///
+  /// Words are split on whitespace; quoting is not supported.
+  pub fn parse_command(command_line: &str) -> Result<Self> {
+    let mut words = command_line.split_whitespace();
+    let Some(program) = words.next() else {
+      bail!("The post command is empty");
+    };
+    Ok(Self::command(program, words))
+  }
+
+  /// Hands `output` to the sink
+  pub fn run(&self, output: &RenderOutput) -> Result<(), RunariumError> {
+    let failed = |reason: String| RunariumError::OutputSinkFailed {
+      output_file: output.output_file.clone(),
+      reason,
+    };
+    match self {
+      OutputSink::Command {
+        program,
+        args_template,
+      } => {
+        let args: Vec<String> = args_template
+          .iter()
+          .map(|arg| expand(arg, &output.output_file))
+          .collect();
+        let result =
+          Command::new(program).args(&args).output().map_err(|e| {
+            failed(format!(
+              "{} could not start: {}",
+              program, e
+            ))
+          })?;
+        if !result.status.success() {
+          return Err(failed(format!(
+            "{} exited with {}: {}",
+            program,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+          )));
+        }
+        Ok(())
+      }
+      OutputSink::Callback(f) => {
+        f(output).map_err(|e| failed(format!("{:#}", e)))
+      }
+    }
+  }
+}
+
+impl fmt::Debug for OutputSink {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OutputSink::Command {
+        program,
+        args_template,
+      } => f
+        .debug_struct("Command")
+        .field("program", program)
+        .field("args_template", args_template)
+        .finish(),
+      OutputSink::Callback(_) => f.write_str("Callback(..)"),
+    }
+  }
+}
+
+/// `arg` with the placeholders of `output_file` filled in
+fn expand(arg: &str, output_file: &str) -> String {
+  let id = Path::new(output_file)
+    .file_stem()
+    .map(|stem| stem.to_string_lossy())
+    .unwrap_or_default();
+  arg.replace("{output}", output_file).replace("{id}", &id)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+  use crate::{
+    config::RouteScale, types::warning::Warnings, utils::projection::Projector,
+  };
+
+  fn output(output_file: &str) -> RenderOutput {
+    let points = [(13.7, 100.5), (13.8, 100.6)];
+    RenderOutput {
+      output_file: output_file.to_string(),
+      projection: Projector::new(&points, RouteScale::default(), 100)
+        .projection_info(),
+      warnings: Warnings::default(),
+      time_index: Vec::new(),
+      palette: None,
+    }
+  }
+
+  #[test]
+  fn test_expand_placeholders() {
+    assert_eq!(
+      expand(
+        "s3://runs/{id}.mp4",
+        "outputs/morning.mp4"
+      ),
+      "s3://runs/morning.mp4"
+    );
+    assert_eq!(
+      expand("{output}", "outputs/morning.mp4"),
+      "outputs/morning.mp4"
+    );
+
+    let sink =
+      OutputSink::parse_command("  aws s3 cp {output} s3://runs/ ").unwrap();
+    assert_eq!(
+      format!("{:?}", sink),
+      format!(
+        "{:?}",
+        OutputSink::command(
+          "aws",
+          ["s3", "cp", "{output}", "s3://runs/"]
+        )
+      )
+    );
+    assert!(OutputSink::parse_command(" ").is_err());
+  }
+
+  #[test]
+  fn test_callback_sink() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let sink = OutputSink::callback(move |output| {
+      log.lock().unwrap().push(output.output_file.clone());
+      Ok(())
+    });
+    sink.run(&output("a.mp4")).unwrap();
+    assert_eq!(*seen.lock().unwrap(), ["a.mp4"]);
+
+    let failing = OutputSink::callback(|_| bail!("bucket is gone"));
+    assert_eq!(
+      failing.run(&output("a.mp4")),
+      Err(RunariumError::OutputSinkFailed {
+        output_file: "a.mp4".to_string(),
+        reason: "bucket is gone".to_string(),
+      })
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_command_sink_reports_stderr() {
+    let ok = OutputSink::command("sh", ["-c", "test {id} = route"]);
+    ok.run(&output("outputs/route.mp4")).unwrap();
+
+    let failing = OutputSink::command("sh", ["-c", "echo denied >&2; exit 3"]);
+    let Err(RunariumError::OutputSinkFailed { reason, .. }) =
+      failing.run(&output("route.mp4"))
+    else {
+      panic!("the sink should fail");
+    };
+    assert!(
+      reason.ends_with(": denied"),
+      "{}",
+      reason
+    );
+
+    let missing = OutputSink::command("runarium-no-such-uploader", [""; 0]);
+    assert!(missing.run(&output("route.mp4")).is_err());
+  }
+}
@@ -0,0 +1,540 @@
+#[cfg(feature = "render")]
+use anyhow::Result;
+#[cfg(feature = "render")]
+use opencv::{core, prelude::*};
+use serde::Serialize;
+
+use crate::{
+  configs::{
+    BackgroundLayer, BackgroundTone, LayerSource, RouteColor, SeededStyle,
+    StyleSeed,
+  },
+  types::warning::{Warning, Warnings},
+  utils::fingerprint::fnv1a,
+};
+
+/// Saturation of generated colors
+pub const SATURATION: f64 = 0.8;
+/// Brightness of generated colors
+pub const VALUE: f64 = 0.95;
+/// Least contrast ratio of a seeded color against its background tone,
+/// and of a configured color against the background it is drawn over;
+/// WCAG's minimum for graphics
+pub const MIN_CONTRAST: f64 = 3.0;
+/// Opacity of a seeded palette's wash over the background
+const WASH_OPACITY: f64 = 0.3;
+/// Attempts at moving a color away from the background before giving up
+const CONTRAST_STEPS: usize = 32;
+
+/// Colors a `SeededStyle` derived, as listed in the render's output.
+///
+/// Copying the colors into `RouteColor`, or the seed into a
+/// `StyleSeed::Fixed`, pins the look for later renders.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StylePalette {
+  pub seed: u64,
+  pub background: BackgroundTone,
+  /// BGRA colors
+  pub route_line: [f64; 4],
+  pub current_position: [f64; 4],
+  pub lap_bars: [f64; 4],
+  /// Laid over the background at 30% opacity
+  pub background_wash: [f64; 4],
+}
+
+impl StylePalette {
+  /// Sets the palette's colors in `colors` and lays its wash over the
+  /// top of the background `layers`
+  pub fn apply(
+    &self,
+    colors: &mut RouteColor,
+    layers: &mut Vec<BackgroundLayer>,
+  ) {
+    colors.route_line = self.route_line;
+    colors.current_position = self.current_position;
+    colors.lap_bars = self.lap_bars;
+    layers.push(
+      BackgroundLayer::new(LayerSource::Solid {
+        color: self.background_wash,
+      })
+      .with_opacity(WASH_OPACITY),
+    );
+  }
+}
+
+/// Palette of `style` for an activity that started at `start_time`
+/// (seconds since the epoch). With `StyleSeed::FromActivity`, activities
+/// without a start time all get the palette of start time 0.
+pub fn style_palette(
+  style: &SeededStyle,
+  start_time: Option<i64>,
+) -> StylePalette {
+  let seed = match style.seed {
+    StyleSeed::Fixed(seed) => seed,
+    StyleSeed::FromActivity => {
+      fnv1a(&start_time.unwrap_or_default().to_le_bytes())
+    }
+  };
+  seeded_palette(seed, style.background)
+}
+
+/// Palette derived from `seed`: the route line takes a random hue, the
+/// marker one across the color wheel and the lap bars a neighbouring one,
+/// each at least `MIN_CONTRAST` against `background`. The same seed always
+/// gives the same palette.
+pub fn seeded_palette(seed: u64, background: BackgroundTone) -> StylePalette {
+  let mut rng = SplitMix64(seed);
+  let hue = rng.range(0.0, 360.0);
+  let mut color = |hue: f64| {
+    let (saturation, value) = match background {
+      BackgroundTone::Dark => (
+        rng.range(0.55, 0.9),
+        rng.range(0.85, 1.0),
+      ),
+      BackgroundTone::Light => (
+        rng.range(0.6, 0.95),
+        rng.range(0.35, 0.6),
+      ),
+    };
+    readable(hue, saturation, value, background)
+  };
+  let route_line = color(hue);
+  let current_position = color(hue + 180.0);
+  let lap_bars = color(hue + 40.0);
+
+  let background_wash = match background {
+    BackgroundTone::Dark => hsv_to_bgra(hue, 0.35, 0.15),
+    BackgroundTone::Light => hsv_to_bgra(hue, 0.15, 0.95),
+  };
+  StylePalette {
+    seed,
+    background,
+    route_line,
+    current_position,
+    lap_bars,
+    background_wash,
+  }
+}
+
+/// WCAG contrast ratio of two BGRA colors, from 1.0 (the same) to 21.0
+/// (black on white)
+pub fn contrast_ratio(a: [f64; 4], b: [f64; 4]) -> f64 {
+  let (a, b) = (
+    relative_luminance(a),
+    relative_luminance(b),
+  );
+  (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Contrast ratio of each configured color against a background sample
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ContrastReport {
+  /// Mean BGRA color of the sample
+  pub background: [f64; 4],
+  pub route_line: f64,
+  pub current_position: f64,
+  pub text: f64,
+  pub lap_bars: f64,
+}
+
+impl ContrastReport {
+  /// A `LowContrast` warning for each color below `MIN_CONTRAST`
+  pub fn warnings(&self) -> Warnings {
+    let mut warnings = Warnings::default();
+    for (element, contrast) in [
+      ("route_line", self.route_line),
+      (
+        "current_position",
+        self.current_position,
+      ),
+      ("text", self.text),
+      ("lap_bars", self.lap_bars),
+    ] {
+      warnings.push_if(
+        contrast < MIN_CONTRAST,
+        Warning::LowContrast { element, contrast },
+      );
+    }
+    warnings
+  }
+}
+
+/// Contrast of `colors` against the mean color of `background_sample`
+#[cfg(feature = "render")]
+pub fn contrast_report(
+  colors: &RouteColor,
+  background_sample: &Mat,
+) -> Result<ContrastReport> {
+  let mean = core::mean_def(background_sample)?;
+  let background = [mean[0], mean[1], mean[2], 0.0];
+  Ok(ContrastReport {
+    background,
+    route_line: contrast_ratio(colors.route_line, background),
+    current_position: contrast_ratio(colors.current_position, background),
+    text: contrast_ratio(colors.text, background),
+    lap_bars: contrast_ratio(colors.lap_bars, background),
+  })
+}
+
+/// Contrast of `colors` against the part of `frame` the route covers:
+/// the bounding box of its pixel `points`, or the whole frame without
+/// points inside it
+#[cfg(feature = "render")]
+pub fn route_contrast(
+  colors: &RouteColor,
+  frame: &Mat,
+  points: &[core::Point],
+) -> Result<ContrastReport> {
+  let covered = covered_range(points.iter().map(|p| p.x), frame.cols()).zip(
+    covered_range(points.iter().map(|p| p.y), frame.rows()),
+  );
+  match covered {
+    Some(((x, width), (y, height))) => {
+      let rect = core::Rect::new(x, y, width, height);
+      contrast_report(
+        colors,
+        &Mat::roi(frame, rect)?.try_clone()?,
+      )
+    }
+    None => contrast_report(colors, frame),
+  }
+}
+
+/// Start and length of the span of `values`, clamped to `0..len` (None =
+/// no values, or all outside)
+#[cfg(feature = "render")]
+fn covered_range(
+  values: impl Iterator<Item = i32> + Clone,
+  len: i32,
+) -> Option<(i32, i32)> {
+  let low = values.clone().min()?.max(0);
+  let high = values.max()?.min(len - 1);
+  (high >= low).then_some((low, high - low + 1))
+}
+
+/// Color a background of `tone` is assumed to be
+fn tone_color(tone: BackgroundTone) -> [f64; 4] {
+  match tone {
+    BackgroundTone::Dark => [24.0, 24.0, 24.0, 0.0],
+    BackgroundTone::Light => [232.0, 232.0, 232.0, 0.0],
+  }
+}
+
+/// Color of `hue` at `saturation` and `value`, desaturated and brightened
+/// over a dark background, or darkened over a light one, until it stands
+/// out from it
+fn readable(
+  hue: f64,
+  mut saturation: f64,
+  mut value: f64,
+  background: BackgroundTone,
+) -> [f64; 4] {
+  let against = tone_color(background);
+  let mut color = hsv_to_bgra(hue, saturation, value);
+  for _ in 0..CONTRAST_STEPS {
+    if contrast_ratio(color, against) >= MIN_CONTRAST {
+      break;
+    }
+    match background {
+      BackgroundTone::Dark => {
+        saturation *= 0.8;
+        value = (value + 0.05).min(1.0);
+      }
+      BackgroundTone::Light => value *= 0.85,
+    }
+    color = hsv_to_bgra(hue, saturation, value);
+  }
+  color
+}
+
+/// Relative luminance of a BGRA color (0.0 black to 1.0 white)
+fn relative_luminance(bgra: [f64; 4]) -> f64 {
+  let linear = |c: f64| {
+    let c = c / 255.0;
+    if c <= 0.03928 {
+      c / 12.92
+    } else {
+      ((c + 0.055) / 1.055).powf(2.4)
+    }
+  };
+  0.2126 * linear(bgra[2]) + 0.7152 * linear(bgra[1]) + 0.0722 * linear(bgra[0])
+}
+
+/// SplitMix64 generator: small, and stable across platforms and releases
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Uniform value in `low..high`
+  fn range(&mut self, low: f64, high: f64) -> f64 {
+    let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+    low + (high - low) * unit
+  }
+}
+
+/// BGRA color of a hue (degrees) at the given saturation and value
+/// (0.0-1.0)
+pub fn hsv_to_bgra(hue: f64, saturation: f64, value: f64) -> [f64; 4] {
+  let hue = hue.rem_euclid(360.0) / 60.0;
+  let chroma = value * saturation;
+  let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+  let (r, g, b) = match hue as u32 {
+    0 => (chroma, x, 0.0),
+    1 => (x, chroma, 0.0),
+    2 => (0.0, chroma, x),
+    3 => (0.0, x, chroma),
+    4 => (x, 0.0, chroma),
+    _ => (chroma, 0.0, x),
+  };
+  let m = value - chroma;
+  let channel = |c: f64| ((c + m) * 255.0).round();
+  [channel(b), channel(g), channel(r), 0.0]
+}
+
+/// `n` colors with hues spread evenly around the color wheel, starting at
+/// red, so neighbouring colors in the palette are as far apart as possible
+pub fn distinct_colors(n: usize) -> Vec<[f64; 4]> {
+  (0..n)
+    .map(|i| {
+      hsv_to_bgra(
+        i as f64 * 360.0 / n as f64,
+        SATURATION,
+        VALUE,
+      )
+    })
+    .collect()
+}
+
+/// Stable color for `key`: its hash picks the hue, so the same key gets
+/// the same color on every render
+pub fn hashed_color(key: &str) -> [f64; 4] {
+  let hue = (fnv1a(key.as_bytes()) % 3600) as f64 / 10.0;
+  hsv_to_bgra(hue, SATURATION, VALUE)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hsv_to_bgra() {
+    assert_eq!(
+      hsv_to_bgra(0.0, 1.0, 1.0),
+      [0.0, 0.0, 255.0, 0.0]
+    );
+    assert_eq!(
+      hsv_to_bgra(120.0, 1.0, 1.0),
+      [0.0, 255.0, 0.0, 0.0]
+    );
+    assert_eq!(
+      hsv_to_bgra(600.0, 1.0, 1.0),
+      [255.0, 0.0, 0.0, 0.0]
+    );
+    assert_eq!(
+      hsv_to_bgra(60.0, 0.0, 0.5),
+      [128.0, 128.0, 128.0, 0.0]
+    );
+  }
+
+  #[test]
+  fn test_distinct_colors() {
+    assert!(distinct_colors(0).is_empty());
+
+    // Three colors are red, green and blue at the palette saturation
+    assert_eq!(
+      distinct_colors(3),
+      vec![
+        [48.0, 48.0, 242.0, 0.0],
+        [48.0, 242.0, 48.0, 0.0],
+        [242.0, 48.0, 48.0, 0.0],
+      ]
+    );
+
+    // Every color of a large palette is different
+    let colors = distinct_colors(30);
+    for (i, a) in colors.iter().enumerate() {
+      assert!(colors[i + 1..].iter().all(|b| b != a));
+    }
+  }
+
+  #[test]
+  fn test_hashed_color_is_stable() {
+    let key = "morning_run.fit|1715499120";
+    assert_eq!(hashed_color(key), hashed_color(key));
+    assert_ne!(
+      hashed_color(key),
+      hashed_color("evening_run.fit|1715535120")
+    );
+  }
+
+  #[test]
+  fn test_contrast_ratio() {
+    let (black, white) = ([0.0; 4], [255.0, 255.0, 255.0, 0.0]);
+    assert!((contrast_ratio(black, white) - 21.0).abs() < 1e-9);
+    assert_eq!(
+      contrast_ratio(white, black),
+      contrast_ratio(black, white)
+    );
+    assert_eq!(contrast_ratio(white, white), 1.0);
+
+    // Luminance weights of pure red, green and blue
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-4;
+    assert!(close(
+      relative_luminance([0.0, 0.0, 255.0, 0.0]),
+      0.2126
+    ));
+    assert!(close(
+      relative_luminance([0.0, 255.0, 0.0, 0.0]),
+      0.7152
+    ));
+    assert!(close(
+      relative_luminance([255.0, 0.0, 0.0, 0.0]),
+      0.0722
+    ));
+    // #777777 on white is WCAG's classic 4.48:1
+    let gray = [119.0, 119.0, 119.0, 0.0];
+    assert!(close(
+      contrast_ratio(gray, white),
+      4.4781
+    ));
+    // Pure red on black
+    assert!(close(
+      contrast_ratio([0.0, 0.0, 255.0, 0.0], black),
+      5.252
+    ));
+  }
+
+  #[test]
+  fn test_colorblind_safe_presets_are_readable() {
+    for (colors, tone) in [
+      (
+        RouteColor::colorblind_safe(),
+        BackgroundTone::Dark,
+      ),
+      (
+        RouteColor::colorblind_safe_light(),
+        BackgroundTone::Light,
+      ),
+    ] {
+      for color in [
+        colors.route_line,
+        colors.current_position,
+        colors.text,
+        colors.lap_bars,
+      ] {
+        let contrast = contrast_ratio(color, tone_color(tone));
+        assert!(
+          contrast >= MIN_CONTRAST,
+          "{:?} at {}",
+          color,
+          contrast
+        );
+      }
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "render")]
+  fn test_contrast_report_warnings() {
+    let black = Mat::new_rows_cols_with_default(
+      10,
+      10,
+      core::CV_8UC3,
+      core::Scalar::all(0.0),
+    )
+    .unwrap();
+    let report = contrast_report(&RouteColor::default(), &black).unwrap();
+    assert_eq!(report.background, [0.0; 4]);
+    assert_eq!(report.text, 21.0);
+    assert!(report.warnings().is_empty());
+
+    // Black text vanishes on a black background
+    let colors = RouteColor {
+      text: [0.0; 4],
+      ..Default::default()
+    };
+    let points = [core::Point::new(2, 2), core::Point::new(40, 5)];
+    let warnings = route_contrast(&colors, &black, &points).unwrap().warnings();
+    assert_eq!(
+      warnings.0,
+      vec![Warning::LowContrast {
+        element: "text",
+        contrast: 1.0,
+      }]
+    );
+
+    assert_eq!(
+      covered_range([2, 40].into_iter(), 10),
+      Some((2, 8))
+    );
+    assert_eq!(
+      covered_range([-5, -1].into_iter(), 10),
+      None
+    );
+    assert_eq!(covered_range([].into_iter(), 10), None);
+  }
+
+  #[test]
+  fn test_seeded_palette_is_deterministic() {
+    for tone in [BackgroundTone::Dark, BackgroundTone::Light] {
+      assert_eq!(
+        seeded_palette(42, tone),
+        seeded_palette(42, tone)
+      );
+      assert_ne!(
+        seeded_palette(42, tone).route_line,
+        seeded_palette(43, tone).route_line
+      );
+    }
+
+    // The activity's start time is the seed
+    let style = SeededStyle {
+      seed: StyleSeed::FromActivity,
+      background: BackgroundTone::Light,
+    };
+    let start = Some(1_715_499_120);
+    assert_eq!(
+      style_palette(&style, start),
+      style_palette(&style, start)
+    );
+    let pinned = SeededStyle {
+      seed: StyleSeed::Fixed(style_palette(&style, start).seed),
+      ..style
+    };
+    assert_eq!(
+      style_palette(&pinned, None),
+      style_palette(&style, start)
+    );
+  }
+
+  #[test]
+  fn test_seeded_palette_contrast() {
+    for tone in [BackgroundTone::Dark, BackgroundTone::Light] {
+      for seed in 0..500 {
+        let palette = seeded_palette(seed, tone);
+        for color in [
+          palette.route_line,
+          palette.current_position,
+          palette.lap_bars,
+        ] {
+          let contrast = contrast_ratio(color, tone_color(tone));
+          assert!(
+            contrast >= MIN_CONTRAST,
+            "seed {} {:?}: {:?} at {}",
+            seed,
+            tone,
+            color,
+            contrast
+          );
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,38 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Maps `f` over `items`, keeping the input order in the result.
+///
+/// Runs on the rayon thread pool with the `rayon` feature and serially
+/// otherwise, so callers get the same output either way.
+pub fn map_ordered<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+  T: Sync,
+  R: Send,
+  F: Fn(&T) -> R + Sync + Send,
+{
+  #[cfg(feature = "rayon")]
+  {
+    items.par_iter().map(f).collect()
+  }
+  #[cfg(not(feature = "rayon"))]
+  {
+    items.iter().map(f).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_map_ordered_keeps_order() {
+    let items: Vec<u64> = (0..1000).collect();
+    let squares = map_ordered(&items, |n| n * n);
+    assert_eq!(squares.len(), 1000);
+    assert!(squares
+      .iter()
+      .enumerate()
+      .all(|(i, sq)| *sq == (i * i) as u64));
+  }
+}
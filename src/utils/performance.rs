@@ -1,7 +1,5 @@
 use std::time::Instant;
 
-use opencv::core;
-
 pub fn measure<F, R>(label: &str, f: F) -> R
 where
   F: FnOnce() -> R,
@@ -16,12 +14,8 @@ where
   result
 }
 
-pub fn processed(i: usize, points: Vec<core::Point>) {
+pub fn processed(i: usize, total: usize) {
   if (i + 1).is_multiple_of(100) {
-    println!(
-      "Processed {}/{} points",
-      i + 1,
-      points.len()
-    );
+    println!("Processed {}/{} points", i + 1, total);
   }
 }
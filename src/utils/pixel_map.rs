@@ -0,0 +1,114 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use opencv::core;
+use serde::Serialize;
+
+use crate::types::fit_data::RouteData;
+
+/// A sampled route point and its stats, for hover lookups on a static image
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PixelMapEntry {
+  pub x: i32,
+  pub y: i32,
+  pub distance_m: Option<f64>,
+  pub pace: Option<String>,
+  pub heart_rate: Option<u8>,
+}
+
+/// Samples every `stride`th drawn point with the stats of its record.
+///
+/// `pixel_points` must be the points passed to the drawer, indexed like
+/// `route`. Points outside the `width` x `height` image are clipped from
+/// the drawing, so they are left out here too.
+pub fn pixel_map(
+  pixel_points: &[core::Point],
+  route: &RouteData,
+  stride: usize,
+  width: i32,
+  height: i32,
+) -> Vec<PixelMapEntry> {
+  pixel_points
+    .iter()
+    .enumerate()
+    .step_by(stride.max(1))
+    .filter(|(_, p)| p.x >= 0 && p.x < width && p.y >= 0 && p.y < height)
+    .map(|(i, p)| PixelMapEntry {
+      x: p.x,
+      y: p.y,
+      distance_m: route.distances.get(i).copied(),
+      pace: route.paces.get(i).cloned(),
+      heart_rate: route.heart_rates.get(i).copied().flatten(),
+    })
+    .collect()
+}
+
+/// Writes the pixel map as a JSON array
+pub fn write_pixel_map(path: &Path, entries: &[PixelMapEntry]) -> Result<()> {
+  fs::write(path, serde_json::to_string(entries)?)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+
+  fn route() -> RouteData {
+    RouteData {
+      paces: vec!["5:00".into(), "5:10".into(), "5:20".into(), "5:30".into()],
+      gps_points: vec![(0.0, 0.0); 4],
+      distances: vec![0.0, 10.0, 20.0, 30.0],
+      heart_rates: vec![Some(140), None, Some(150), Some(155)],
+      speeds: vec![3.3, 3.2, 3.1, 3.0],
+      cadences: vec![None; 4],
+      powers: vec![None; 4],
+      altitudes: vec![None; 4],
+      timestamps: vec![None; 4],
+      segment_breaks: Vec::new(),
+      extra: HashMap::new(),
+    }
+  }
+
+  #[test]
+  fn test_pixel_map_schema() {
+    let points = [
+      core::Point::new(10, 10),
+      core::Point::new(20, 20),
+      core::Point::new(30, 30),
+      core::Point::new(40, 40),
+    ];
+    let entries = pixel_map(&points, &route(), 2, 100, 100);
+    assert_eq!(entries.len(), 2);
+
+    let json = serde_json::to_value(&entries).unwrap();
+    assert_eq!(
+      json[1],
+      serde_json::json!({
+        "x": 30,
+        "y": 30,
+        "distance_m": 20.0,
+        "pace": "5:20",
+        "heart_rate": 150
+      })
+    );
+  }
+
+  #[test]
+  fn test_pixel_map_within_image() {
+    let points = [
+      core::Point::new(-5, 10),
+      core::Point::new(20, 20),
+      core::Point::new(99, 99),
+      core::Point::new(100, 40),
+    ];
+    let entries = pixel_map(&points, &route(), 1, 100, 100);
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+      .iter()
+      .all(|e| e.x >= 0 && e.x < 100 && e.y >= 0 && e.y < 100));
+    // Skipped points keep the stats of their own record
+    assert_eq!(entries[1].pace.as_deref(), Some("5:20"));
+  }
+}
@@ -0,0 +1,866 @@
+use crate::{
+  analysis::LapBoundary,
+  configs::{PacingCurve, ProgressionMode},
+  types::fit_data::ActivityTimes,
+  utils::converter::{bearing, parse_pace, sec_to_pace},
+};
+
+/// Intervals covered slower than this (m/s) count as paused
+pub const PAUSE_SPEED_MPS: f64 = 0.5;
+
+/// Longest gap between records (seconds) real-time playback shows in
+/// full; longer pauses are shortened to it
+pub const MAX_PAUSE_S: f64 = 10.0;
+
+/// GPS segments averaged into the bearing readout
+pub const BEARING_WINDOW: usize = 5;
+
+/// Position of the drawing pen for a single video frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PenPosition {
+  /// Index of the record the pen has most recently passed
+  pub index: usize,
+  /// Fraction (0.0-1.0) of the way from `index` to `index + 1`
+  pub fraction: f64,
+}
+
+/// Plans one pen position per video frame.
+///
+/// `PerRecord` yields one frame per point. `PerDistance` advances the pen by
+/// a fixed distance each frame, interpolating inside segments; it falls back
+/// to `PerRecord` when the distance series is missing or flat.
+pub fn plan_frames(
+  mode: ProgressionMode,
+  point_count: usize,
+  distances: &[f64],
+) -> Vec<PenPosition> {
+  let per_record = || {
+    (0..point_count)
+      .map(|index| PenPosition {
+        index,
+        fraction: 0.0,
+      })
+      .collect()
+  };
+
+  let meters_per_frame = match mode {
+    ProgressionMode::PerRecord => return per_record(),
+    ProgressionMode::PerDistance { meters_per_frame } => meters_per_frame,
+  };
+
+  let len = point_count.min(distances.len());
+  plan_along(&distances[..len], meters_per_frame).unwrap_or_else(per_record)
+}
+
+/// Plans pen positions `step` apart along `series`, a non-decreasing value
+/// per record (distance or time), interpolating inside segments. None when
+/// the series is too short or flat, or `step` is not positive.
+fn plan_along(series: &[f64], step: f64) -> Option<Vec<PenPosition>> {
+  let len = series.len();
+  if len < 2 || step <= 0.0 {
+    return None;
+  }
+
+  let start = series[0];
+  let total = series[len - 1] - start;
+  if total <= 0.0 {
+    return None;
+  }
+
+  let steps = (total / step).ceil() as usize;
+  let mut frames = Vec::with_capacity(steps + 1);
+
+  for i in 0..=steps {
+    let target = (start + i as f64 * step).min(start + total);
+
+    // Last record whose value is not past the pen
+    let index = series
+      .partition_point(|&v| v <= target)
+      .saturating_sub(1)
+      .min(len - 1);
+
+    let fraction = if index + 1 < len {
+      let span = series[index + 1] - series[index];
+      if span > 0.0 {
+        ((target - series[index]) / span).clamp(0.0, 1.0)
+      } else {
+        0.0
+      }
+    } else {
+      0.0
+    };
+
+    frames.push(PenPosition { index, fraction });
+  }
+
+  // Always finish exactly on the last record
+  if let Some(last) = frames.last_mut() {
+    *last = PenPosition {
+      index: len - 1,
+      fraction: 0.0,
+    };
+  }
+
+  Some(frames)
+}
+
+/// Plans one pen position per `seconds_per_frame` of activity time, so
+/// records are repeated through slow stretches and skipped through fast
+/// ones. Gaps between records longer than `max_pause_s` count as
+/// `max_pause_s`; a record without a timestamp shares the time of the one
+/// before. None when fewer than two records are timestamped or no time
+/// passes.
+pub fn plan_real_time(
+  timestamps: &[Option<i64>],
+  seconds_per_frame: f64,
+  max_pause_s: f64,
+) -> Option<Vec<PenPosition>> {
+  let mut previous = None;
+  let mut clock = 0.0;
+  let times: Vec<f64> = timestamps
+    .iter()
+    .map(|&t| {
+      if let (Some(t), Some(last)) = (t, previous) {
+        clock += ((t - last) as f64).clamp(0.0, max_pause_s);
+      }
+      previous = t.or(previous);
+      clock
+    })
+    .collect();
+  plan_along(&times, seconds_per_frame)
+}
+
+/// Indices of the `count` planned frames that `curve` shows, in order.
+///
+/// `EaseInOutRamp` shows every frame of the first and last `edge_fraction`
+/// (at most 0.5); in between the step eases in with a smoothstep up to
+/// `max_skip` at the middle and back down. The first and last frames are
+/// always shown, and no step jumps into the closing edge.
+pub fn paced_indices(curve: PacingCurve, count: usize) -> Vec<usize> {
+  let (max_skip, edge_fraction) = match curve {
+    PacingCurve::Constant => return (0..count).collect(),
+    PacingCurve::EaseInOutRamp {
+      max_skip,
+      edge_fraction,
+    } => (
+      max_skip.max(1),
+      edge_fraction.clamp(0.0, 0.5),
+    ),
+  };
+  if count < 2 {
+    return (0..count).collect();
+  }
+
+  let last = count - 1;
+  let edge = edge_fraction * last as f64;
+  // First frame of the closing edge, and the ramp from an edge to the
+  // middle
+  let closing = (last as f64 - edge).ceil() as usize;
+  let ramp = last as f64 / 2.0 - edge;
+
+  let mut indices = Vec::new();
+  let mut index = 0;
+  while index < last {
+    indices.push(index);
+    let from_edge =
+      (index as f64 - edge).min(last as f64 - edge - index as f64);
+    let step = if from_edge > 0.0 && ramp > 0.0 {
+      let t = (from_edge / ramp).min(1.0);
+      let ease = t * t * (3.0 - 2.0 * t);
+      1 + ((max_skip - 1) as f64 * ease).round() as usize
+    } else {
+      1
+    };
+    index = if index < closing {
+      (index + step).min(closing)
+    } else {
+      index + step
+    };
+  }
+  indices.push(last);
+  indices
+}
+
+/// Cumulative distance (meters) at which each lap ends
+pub fn lap_end_distances(lap_distances: &[f64]) -> Vec<f64> {
+  lap_distances
+    .iter()
+    .scan(0.0, |total, d| {
+      *total += d;
+      Some(*total)
+    })
+    .collect()
+}
+
+/// Latest lap that ends within `(previous, current]` meters, if any
+pub fn crossed_lap(
+  lap_ends: &[f64],
+  previous: f64,
+  current: f64,
+) -> Option<usize> {
+  lap_ends
+    .iter()
+    .rposition(|&end| end > previous && end <= current)
+}
+
+/// Mean of each lap's values (None for a lap without any)
+pub fn lap_averages(
+  values: &[Option<f64>],
+  boundaries: &[LapBoundary],
+) -> Vec<Option<f64>> {
+  boundaries
+    .iter()
+    .map(|boundary| {
+      let end = boundary.end_record.min(values.len());
+      let start = boundary.start_record.min(end);
+      let lap: Vec<f64> =
+        values[start..end].iter().flatten().copied().collect();
+      (!lap.is_empty()).then(|| lap.iter().sum::<f64>() / lap.len() as f64)
+    })
+    .collect()
+}
+
+/// Seconds since the first timestamped record, per record
+pub fn elapsed_seconds(timestamps: &[Option<i64>]) -> Vec<Option<f64>> {
+  let start = timestamps.iter().flatten().next().copied();
+  timestamps
+    .iter()
+    .map(|t| Some((t.as_ref()? - start?) as f64))
+    .collect()
+}
+
+/// Moving seconds since the first timestamped record, per record. Like
+/// `elapsed_seconds`, but intervals covered slower than `pause_speed` (m/s)
+/// add nothing, so the clock freezes through stops and auto-pause gaps.
+///
+/// Every interval counts as moving when the distance series is missing.
+pub fn moving_seconds(
+  timestamps: &[Option<i64>],
+  distances: &[f64],
+  pause_speed: f64,
+) -> Vec<Option<f64>> {
+  let has_distances = distances.len() >= timestamps.len();
+  let mut previous: Option<(usize, i64)> = None;
+  let mut moving = 0.0;
+  timestamps
+    .iter()
+    .enumerate()
+    .map(|(i, t)| {
+      let t = (*t)?;
+      if let Some((last, last_t)) = previous {
+        let seconds = (t - last_t) as f64;
+        let moved = !has_distances
+          || distances[i] - distances[last] >= pause_speed * seconds;
+        if seconds > 0.0 && moved {
+          moving += seconds;
+        }
+      }
+      previous = Some((i, t));
+      Some(moving)
+    })
+    .collect()
+}
+
+/// Elapsed and moving time of an activity and its average moving pace over
+/// `distance_m`, with pauses detected at `PAUSE_SPEED_MPS`.
+///
+/// Without record timestamps, both times are the sum of `lap_times`; None
+/// when there are no laps either.
+pub fn activity_times(
+  timestamps: &[Option<i64>],
+  distances: &[f64],
+  lap_times: &[f64],
+  distance_m: f64,
+) -> Option<ActivityTimes> {
+  let elapsed = elapsed_seconds(timestamps).into_iter().flatten().last();
+  let moving = moving_seconds(timestamps, distances, PAUSE_SPEED_MPS)
+    .into_iter()
+    .flatten()
+    .last();
+  let (elapsed_time, moving_time) = match (elapsed, moving) {
+    (Some(elapsed), Some(moving)) => (elapsed, moving),
+    _ if !lap_times.is_empty() => {
+      let total = lap_times.iter().sum();
+      (total, total)
+    }
+    _ => return None,
+  };
+
+  Some(ActivityTimes {
+    elapsed_time,
+    moving_time,
+    average_moving_pace: (distance_m > 0.0)
+      .then(|| moving_time / (distance_m / 1000.0)),
+  })
+}
+
+/// Elevation gained (meters) up to each record: the sum of climbs between
+/// consecutive known altitudes, None before the first one
+pub fn cumulative_gain(altitudes: &[Option<f64>]) -> Vec<Option<f64>> {
+  let mut previous: Option<f64> = None;
+  let mut gain = 0.0;
+  altitudes
+    .iter()
+    .map(|altitude| {
+      if let Some(altitude) = *altitude {
+        if let Some(previous) = previous {
+          gain += (altitude - previous).max(0.0);
+        }
+        previous = Some(altitude);
+      }
+      previous.map(|_| gain)
+    })
+    .collect()
+}
+
+/// Direction of travel (compass degrees) at each record: the circular mean
+/// of the bearings of the last `window` segments, so GPS jitter does not
+/// swing it. Stationary segments repeat the bearing before them; None
+/// before the route first moves.
+pub fn bearing_series(
+  points: &[(f64, f64)],
+  window: usize,
+) -> Vec<Option<f64>> {
+  let mut last = None;
+  let segments: Vec<Option<f64>> = points
+    .iter()
+    .enumerate()
+    .map(|(i, &point)| {
+      if let Some(bearing) = i
+        .checked_sub(1)
+        .and_then(|previous| bearing(points[previous], point))
+      {
+        last = Some(bearing);
+      }
+      last
+    })
+    .collect();
+
+  (0..segments.len())
+    .map(|i| {
+      let current = segments[i]?;
+      let recent = &segments[(i + 1).saturating_sub(window.max(1))..=i];
+      let (sin, cos) = recent.iter().flatten().fold(
+        (0.0, 0.0),
+        |(sin, cos), bearing: &f64| {
+          let radians = bearing.to_radians();
+          (sin + radians.sin(), cos + radians.cos())
+        },
+      );
+      // Opposite bearings cancel out; keep the latest one then
+      if sin.hypot(cos) < 1e-9 {
+        return Some(current);
+      }
+      Some(sin.atan2(cos).to_degrees().rem_euclid(360.0))
+    })
+    .collect()
+}
+
+/// Indices of the points that get a dot: the first point, then each point
+/// at least `spacing_m` meters along the route from the previous dot.
+///
+/// Every point gets a dot when the distance series is missing.
+pub fn dot_indices(
+  point_count: usize,
+  distances: &[f64],
+  spacing_m: f64,
+) -> Vec<usize> {
+  if distances.len() < point_count {
+    return (0..point_count).collect();
+  }
+
+  let mut indices = Vec::new();
+  let mut last: Option<f64> = None;
+  for (index, &distance) in distances[..point_count].iter().enumerate() {
+    if last.is_none_or(|last| distance - last >= spacing_m) {
+      indices.push(index);
+      last = Some(distance);
+    }
+  }
+  indices
+}
+
+/// Number of frames needed to hold for `seconds` at `fps`
+pub fn hold_frame_count(seconds: f64, fps: f64) -> usize {
+  if seconds <= 0.0 || fps <= 0.0 {
+    return 0;
+  }
+  (seconds * fps).round() as usize
+}
+
+/// Linear interpolation between `a` and `b`
+pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
+  a + (b - a) * t
+}
+
+/// Distance (meters) at the pen position
+pub fn pen_distance(distances: &[f64], pen: PenPosition) -> Option<f64> {
+  let current = *distances.get(pen.index)?;
+  match distances.get(pen.index + 1) {
+    Some(&next) if pen.fraction > 0.0 => {
+      Some(lerp(current, next, pen.fraction))
+    }
+    _ => Some(current),
+  }
+}
+
+/// Speed (m/s) at the pen position, interpolated like `pen_distance`
+pub fn pen_speed(speeds: &[f64], pen: PenPosition) -> Option<f64> {
+  pen_distance(speeds, pen)
+}
+
+/// Pace (seconds per km) at the pen position, interpolated like
+/// `pen_distance`
+pub fn pen_pace_secs(pace_seconds: &[f64], pen: PenPosition) -> Option<f64> {
+  pen_distance(pace_seconds, pen)
+}
+
+/// Distance (meters) shown in the overlay at the pen position.
+///
+/// With `smooth` the value counts up between records; otherwise it holds
+/// the distance of the last record passed.
+pub fn displayed_distance(
+  distances: &[f64],
+  pen: PenPosition,
+  smooth: bool,
+) -> Option<f64> {
+  if smooth {
+    pen_distance(distances, pen)
+  } else {
+    distances.get(pen.index).copied()
+  }
+}
+
+/// Pace text ("m:ss") at the pen position
+pub fn pen_pace(paces: &[String], pen: PenPosition) -> Option<String> {
+  let current = paces.get(pen.index)?;
+  let next = paces.get(pen.index + 1).and_then(|next| parse_pace(next));
+  match (parse_pace(current), next) {
+    // Paces without a value (`NO_PACE`) are shown as they are
+    (Some(a), Some(b)) if pen.fraction > 0.0 => {
+      let pace = lerp(a as f64, b as f64, pen.fraction);
+      Some(sec_to_pace(pace as f32))
+    }
+    _ => Some(current.clone()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_per_record_one_frame_per_point() {
+    let frames = plan_frames(ProgressionMode::PerRecord, 4, &[]);
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[3].index, 3);
+    assert!(frames.iter().all(|f| f.fraction == 0.0));
+  }
+
+  #[test]
+  fn test_paced_indices() {
+    assert_eq!(
+      paced_indices(PacingCurve::Constant, 4),
+      vec![0, 1, 2, 3]
+    );
+    assert!(paced_indices(PacingCurve::default(), 0).is_empty());
+
+    let ramp = PacingCurve::EaseInOutRamp {
+      max_skip: 4,
+      edge_fraction: 0.1,
+    };
+    let indices = paced_indices(ramp, 1001);
+    assert!(indices.windows(2).all(|w| w[0] < w[1]));
+    assert!(indices.windows(2).all(|w| w[1] - w[0] <= 4));
+    // Every frame of both edges is shown
+    assert!((0..=100).all(|i| indices.contains(&i)));
+    assert!((900..=1000).all(|i| indices.contains(&i)));
+    // The middle reaches the full skip
+    assert!(indices.windows(2).any(|w| w[1] - w[0] == 4));
+
+    // 200 edge frames, and the 800 middle frames in about half as many,
+    // since the eased steps spend more frames near the edges
+    assert!(
+      (600..=640).contains(&indices.len()),
+      "{}",
+      indices.len()
+    );
+    assert_eq!(paced_indices(ramp, 1001), indices);
+
+    // No skip, or no middle, shows everything
+    for curve in [
+      PacingCurve::EaseInOutRamp {
+        max_skip: 1,
+        edge_fraction: 0.1,
+      },
+      PacingCurve::EaseInOutRamp {
+        max_skip: 4,
+        edge_fraction: 0.7,
+      },
+    ] {
+      assert_eq!(paced_indices(curve, 50).len(), 50);
+    }
+    assert_eq!(paced_indices(ramp, 1), vec![0]);
+  }
+
+  #[test]
+  fn test_per_distance_frame_count() {
+    // Uneven spacing: 0, 10, 100 meters
+    let distances = [0.0, 10.0, 100.0];
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 25.0,
+    };
+    let frames = plan_frames(mode, 3, &distances);
+
+    // 100 m / 25 m = 4 steps + the starting frame
+    assert_eq!(frames.len(), 5);
+    assert_eq!(
+      frames[0],
+      PenPosition {
+        index: 0,
+        fraction: 0.0
+      }
+    );
+    assert_eq!(
+      frames[4],
+      PenPosition {
+        index: 2,
+        fraction: 0.0
+      }
+    );
+  }
+
+  #[test]
+  fn test_per_distance_splits_segments() {
+    let distances = [0.0, 10.0, 100.0];
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 25.0,
+    };
+    let frames = plan_frames(mode, 3, &distances);
+
+    // 25 m lies inside the 10..100 segment
+    assert_eq!(frames[1].index, 1);
+    assert!((frames[1].fraction - 15.0 / 90.0).abs() < 1e-9);
+
+    let d = pen_distance(&distances, frames[1]).unwrap();
+    assert!((d - 25.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_per_distance_constant_pen_speed() {
+    let distances = [0.0, 3.0, 50.0, 51.0, 120.0];
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 10.0,
+    };
+    let frames = plan_frames(mode, 5, &distances);
+
+    let pen: Vec<f64> = frames
+      .iter()
+      .map(|f| pen_distance(&distances, *f).unwrap())
+      .collect();
+    for pair in pen.windows(2) {
+      assert!((pair[1] - pair[0] - 10.0).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn test_per_distance_falls_back_without_distances() {
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 10.0,
+    };
+    assert_eq!(plan_frames(mode, 3, &[]).len(), 3);
+    assert_eq!(
+      plan_frames(mode, 3, &[5.0, 5.0, 5.0]).len(),
+      3
+    );
+  }
+
+  #[test]
+  fn test_real_time_follows_timestamps() {
+    // 4 s, then a 300 s pause shortened to 10 s, then 2 s untimed-through
+    let timestamps = [Some(0), Some(4), Some(304), None, Some(306)];
+    let frames = plan_real_time(&timestamps, 2.0, 10.0).unwrap();
+
+    // 16 s of compressed time at 2 s per frame
+    assert_eq!(frames.len(), 9);
+    assert_eq!(
+      frames[1],
+      PenPosition {
+        index: 0,
+        fraction: 0.5
+      }
+    );
+    assert_eq!(
+      frames[2],
+      PenPosition {
+        index: 1,
+        fraction: 0.0
+      }
+    );
+    // The pause is held over five frames
+    assert_eq!(
+      frames[4],
+      PenPosition {
+        index: 1,
+        fraction: 0.4
+      }
+    );
+    assert_eq!(
+      frames[8],
+      PenPosition {
+        index: 4,
+        fraction: 0.0
+      }
+    );
+
+    assert!(plan_real_time(&[Some(5), None, Some(5)], 1.0, 10.0).is_none());
+    assert!(plan_real_time(&[None, None], 1.0, 10.0).is_none());
+  }
+
+  #[test]
+  fn test_smooth_counter_monotonic() {
+    let distances = [0.0, 3.0, 50.0, 51.0, 120.0];
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 7.0,
+    };
+    let frames = plan_frames(mode, 5, &distances);
+
+    let shown: Vec<f64> = frames
+      .iter()
+      .map(|f| displayed_distance(&distances, *f, true).unwrap())
+      .collect();
+    assert!(shown.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert_eq!(*shown.last().unwrap(), 120.0);
+
+    // Interpolated values appear between records
+    assert!(shown.iter().any(|d| !distances.contains(d)));
+  }
+
+  #[test]
+  fn test_smooth_counter_steps_when_disabled() {
+    let distances = [0.0, 3.0, 50.0, 51.0, 120.0];
+    let mode = ProgressionMode::PerDistance {
+      meters_per_frame: 7.0,
+    };
+    let frames = plan_frames(mode, 5, &distances);
+
+    for frame in frames {
+      let shown = displayed_distance(&distances, frame, false).unwrap();
+      assert!(distances.contains(&shown));
+    }
+  }
+
+  #[test]
+  fn test_smooth_counter_no_op_per_record() {
+    let distances = [0.0, 3.0, 50.0];
+    let frames = plan_frames(
+      ProgressionMode::PerRecord,
+      3,
+      &distances,
+    );
+
+    for frame in frames {
+      assert_eq!(
+        displayed_distance(&distances, frame, true),
+        displayed_distance(&distances, frame, false)
+      );
+    }
+  }
+
+  #[test]
+  fn test_lap_end_distances() {
+    let ends = lap_end_distances(&[1000.0, 1000.0, 420.5]);
+    assert_eq!(ends, vec![1000.0, 2000.0, 2420.5]);
+    assert!(lap_end_distances(&[]).is_empty());
+  }
+
+  #[test]
+  fn test_crossed_lap() {
+    let ends = [1000.0, 2000.0, 2420.5];
+    assert_eq!(
+      crossed_lap(&ends, 990.0, 1000.0),
+      Some(0)
+    );
+    assert_eq!(crossed_lap(&ends, 1000.0, 1010.0), None);
+    assert_eq!(crossed_lap(&ends, 1500.0, 1999.0), None);
+    // Several boundaries in one frame report the latest lap
+    assert_eq!(
+      crossed_lap(&ends, 900.0, 2500.0),
+      Some(2)
+    );
+  }
+
+  #[test]
+  fn test_lap_averages() {
+    let boundary = |lap_index, start_record, end_record| LapBoundary {
+      lap_index,
+      start_record,
+      end_record,
+      start_distance_m: 0.0,
+      end_distance_m: 0.0,
+    };
+    let boundaries = [boundary(0, 0, 3), boundary(1, 3, 4), boundary(2, 4, 5)];
+    let values = [
+      Some(200.0),
+      Some(300.0),
+      None,
+      Some(250.0),
+      None,
+      Some(100.0),
+    ];
+    assert_eq!(
+      lap_averages(&values, &boundaries),
+      vec![Some(250.0), Some(250.0), None]
+    );
+
+    // Values shorter than the records
+    assert_eq!(
+      lap_averages(&values[..2], &boundaries),
+      vec![Some(250.0), None, None]
+    );
+  }
+
+  #[test]
+  fn test_hold_frame_count() {
+    assert_eq!(hold_frame_count(0.5, 30.0), 15);
+    assert_eq!(hold_frame_count(0.5, 0.0), 0);
+    assert_eq!(hold_frame_count(-1.0, 30.0), 0);
+  }
+
+  #[test]
+  fn test_elapsed_seconds() {
+    let timestamps = [None, Some(1000), Some(1005), None, Some(1012)];
+    assert_eq!(
+      elapsed_seconds(&timestamps),
+      vec![None, Some(0.0), Some(5.0), None, Some(12.0)]
+    );
+    assert_eq!(
+      elapsed_seconds(&[None, None]),
+      vec![None, None]
+    );
+  }
+
+  #[test]
+  fn test_moving_seconds_freeze_in_pauses() {
+    // 10 s running, 60 s at a stoplight, 10 s running
+    let timestamps = [Some(0), Some(10), Some(70), Some(80)];
+    let distances = [0.0, 30.0, 32.0, 62.0];
+    assert_eq!(
+      moving_seconds(&timestamps, &distances, PAUSE_SPEED_MPS),
+      vec![Some(0.0), Some(10.0), Some(10.0), Some(20.0)]
+    );
+
+    // Without distances nothing is detected as paused
+    assert_eq!(
+      moving_seconds(&timestamps, &[], PAUSE_SPEED_MPS),
+      elapsed_seconds(&timestamps)
+    );
+  }
+
+  #[test]
+  fn test_activity_times() {
+    let timestamps = [Some(0), Some(10), Some(70), Some(80)];
+    let distances = [0.0, 30.0, 32.0, 62.0];
+    let times = activity_times(&timestamps, &distances, &[], 62.0).unwrap();
+    assert_eq!(times.elapsed_time, 80.0);
+    assert_eq!(times.moving_time, 20.0);
+    // 20 s over 62 m is about 5:23 per km
+    assert_eq!(
+      times.average_moving_pace.unwrap().round(),
+      323.0
+    );
+
+    // No timestamps: lap times, no pause detection
+    let times =
+      activity_times(&[None, None], &[], &[300.0, 320.0], 0.0).unwrap();
+    assert_eq!(
+      (times.elapsed_time, times.moving_time),
+      (620.0, 620.0)
+    );
+    assert!(times.average_moving_pace.is_none());
+    assert!(activity_times(&[None], &[], &[], 0.0).is_none());
+  }
+
+  #[test]
+  fn test_bearing_series_smooths_and_holds() {
+    // North, a stop, then a zig-zag of 20° either side of north
+    let points = [
+      (13.7, 100.5),
+      (13.7001, 100.5),
+      (13.7001, 100.5),
+      (13.7002, 100.50004),
+      (13.7003, 100.5),
+      (13.7004, 100.50004),
+    ];
+    let bearings = bearing_series(&points, 3);
+    assert_eq!(bearings[0], None);
+    assert!(bearings[1].unwrap().abs() < 1e-9);
+    assert_eq!(bearings[2], bearings[1]);
+
+    // The raw segments swing about 20° each way; the mean stays near north
+    let off_north = |b: f64| b.min(360.0 - b);
+    assert!(off_north(bearing(points[3], points[4]).unwrap()) > 15.0);
+    for bearing in &bearings[3..] {
+      assert!(off_north(bearing.unwrap()) < 10.0);
+    }
+  }
+
+  #[test]
+  fn test_bearing_series_wraps_at_north() {
+    // 10° either side of north averages to north, not south
+    let points = [(13.7, 100.5), (13.7001, 100.499982), (13.7002, 100.5)];
+    let last = bearing_series(&points, 2)[2].unwrap();
+    assert!(last.min(360.0 - last) < 1.0);
+  }
+
+  #[test]
+  fn test_cumulative_gain() {
+    let altitudes =
+      [None, Some(10.0), Some(12.5), Some(11.0), None, Some(14.0)];
+    assert_eq!(
+      cumulative_gain(&altitudes),
+      vec![None, Some(0.0), Some(2.5), Some(2.5), Some(2.5), Some(5.5)]
+    );
+  }
+
+  #[test]
+  fn test_dot_indices_respect_spacing() {
+    // Straight line with a record every 3 m
+    let distances: Vec<f64> = (0..101).map(|i| i as f64 * 3.0).collect();
+    let dots = dot_indices(101, &distances, 10.0);
+
+    // 0, 12, 24, ... 300 m: 26 dots, each at least 10 m apart
+    assert_eq!(dots.len(), 26);
+    assert_eq!(dots[0], 0);
+    for pair in dots.windows(2) {
+      assert!(distances[pair[1]] - distances[pair[0]] >= 10.0);
+      // No dot is skipped once the spacing is reached
+      assert!(distances[pair[1] - 1] - distances[pair[0]] < 10.0);
+    }
+
+    // Without distances every point gets a dot
+    assert_eq!(dot_indices(3, &[], 10.0), vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn test_pen_pace_interpolates() {
+    let paces = vec!["5:00".to_string(), "6:00".to_string()];
+    let pen = PenPosition {
+      index: 0,
+      fraction: 0.5,
+    };
+    assert_eq!(pen_pace(&paces, pen).unwrap(), "5:30");
+    assert_eq!(
+      pen_pace(
+        &paces,
+        PenPosition {
+          index: 1,
+          fraction: 0.0
+        }
+      )
+      .unwrap(),
+      "6:00"
+    );
+    assert!(pen_pace(&[], pen).is_none());
+  }
+}
@@ -0,0 +1,296 @@
+#[cfg(feature = "render")]
+use opencv::core;
+use serde::Serialize;
+
+use crate::{
+  configs::RouteScale,
+  utils::converter::{get_bounds, wrap_longitude},
+};
+
+/// Geographic bounding box in degrees. `lon_max` exceeds 180 when the
+/// route crosses the antimeridian (see `get_bounds`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GeoBounds {
+  pub lat_min: f64,
+  pub lat_max: f64,
+  pub lon_min: f64,
+  pub lon_max: f64,
+}
+
+/// Pixel rectangle the geographic bounds are mapped onto
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PixelRect {
+  pub x: f64,
+  pub y: f64,
+  pub width: f64,
+  pub height: f64,
+}
+
+/// How geographic coordinates map to output pixels, for aligning the output
+/// with an external map
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProjectionInfo {
+  /// Route bounds
+  pub geo_bounds: GeoBounds,
+  /// Where `geo_bounds` lands in the output (top-left = north-west)
+  pub pixel_rect: PixelRect,
+  /// Horizontal pixels per degree of longitude (0.0 if the route has no
+  /// east-west extent)
+  pub scale_px_per_deg_x: f64,
+  /// Vertical pixels per degree of latitude (0.0 if the route has no
+  /// north-south extent)
+  pub scale_px_per_deg_y: f64,
+  /// Clockwise rotation in degrees (always 0.0, north is up)
+  pub rotation: f64,
+}
+
+/// Maps GPS coordinates to pixels.
+///
+/// Each axis is normalized to the route bounds and placed using
+/// `RouteScale`, relative to `base` pixels (the output width).
+///
+/// # Example
+/// ```
+/// use runarium::configs::RouteScale;
+/// use runarium::utils::projection::Projector;
+///
+/// let points = [(13.70, 100.50), (13.80, 100.60)];
+/// let projector = Projector::new(&points, RouteScale::default(), 1000);
+///
+/// let (x, y) = projector.project_f64(13.75, 100.55);
+/// let (lat, lon) = projector.unproject(x, y);
+/// assert!((lat - 13.75).abs() < 1e-9 && (lon - 100.55).abs() < 1e-9);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Projector {
+  bounds: GeoBounds,
+  route_scale: RouteScale,
+  base: i32,
+}
+
+impl Projector {
+  /// Creates a projector fitted to `points` (lat, lon)
+  pub fn new(
+    points: &[(f64, f64)],
+    route_scale: RouteScale,
+    base: i32,
+  ) -> Self {
+    let ((lat_min, lat_max), (lon_min, lon_max)) = get_bounds(points);
+    Self::from_bounds(
+      GeoBounds {
+        lat_min,
+        lat_max,
+        lon_min,
+        lon_max,
+      },
+      route_scale,
+      base,
+    )
+  }
+
+  /// Creates a projector for known bounds
+  pub fn from_bounds(
+    bounds: GeoBounds,
+    route_scale: RouteScale,
+    base: i32,
+  ) -> Self {
+    Self {
+      bounds,
+      route_scale,
+      base,
+    }
+  }
+
+  /// Sub-pixel position of a coordinate
+  pub fn project_f64(&self, lat: f64, lon: f64) -> (f64, f64) {
+    let GeoBounds {
+      lat_min,
+      lat_max,
+      lon_min,
+      lon_max,
+    } = self.bounds;
+
+    let lon = wrap_longitude(lon, lon_max);
+    let nx = if lon_max != lon_min {
+      (lon - lon_min) / (lon_max - lon_min)
+    } else {
+      0.5
+    };
+    let ny = if lat_max != lat_min {
+      (lat - lat_min) / (lat_max - lat_min)
+    } else {
+      0.5
+    };
+
+    let RouteScale {
+      scale,
+      offset_x_percent,
+      offset_y_percent,
+    } = self.route_scale;
+    let base = self.base as f64;
+    (
+      (offset_x_percent + nx * scale) * base,
+      (offset_y_percent + (1.0 - ny) * scale) * base,
+    )
+  }
+
+  /// Pixel position of a coordinate
+  #[cfg(feature = "render")]
+  pub fn project(&self, lat: f64, lon: f64) -> core::Point {
+    let (x, y) = self.project_f64(lat, lon);
+    core::Point::new(x as i32, y as i32)
+  }
+
+  /// Coordinate (lat, lon) at a pixel position
+  pub fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+    let GeoBounds {
+      lat_min,
+      lat_max,
+      lon_min,
+      lon_max,
+    } = self.bounds;
+    let RouteScale {
+      scale,
+      offset_x_percent,
+      offset_y_percent,
+    } = self.route_scale;
+    let base = self.base as f64;
+
+    let nx = (x / base - offset_x_percent) / scale;
+    let ny = 1.0 - (y / base - offset_y_percent) / scale;
+    let lon = lon_min + nx * (lon_max - lon_min);
+    (
+      lat_min + ny * (lat_max - lat_min),
+      if lon > 180.0 { lon - 360.0 } else { lon },
+    )
+  }
+
+  /// Projection details for callers
+  pub fn projection_info(&self) -> ProjectionInfo {
+    let GeoBounds {
+      lat_min,
+      lat_max,
+      lon_min,
+      lon_max,
+    } = self.bounds;
+    let RouteScale {
+      scale,
+      offset_x_percent,
+      offset_y_percent,
+    } = self.route_scale;
+    let base = self.base as f64;
+    let size = scale * base;
+
+    let per_degree = |span: f64| if span > 0.0 { size / span } else { 0.0 };
+
+    ProjectionInfo {
+      geo_bounds: self.bounds,
+      pixel_rect: PixelRect {
+        x: offset_x_percent * base,
+        y: offset_y_percent * base,
+        width: size,
+        height: size,
+      },
+      scale_px_per_deg_x: per_degree(lon_max - lon_min),
+      scale_px_per_deg_y: per_degree(lat_max - lat_min),
+      rotation: 0.0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn projector() -> Projector {
+    let points = [(13.70, 100.50), (13.76, 100.58), (13.80, 100.60)];
+    Projector::new(
+      &points,
+      RouteScale::new(0.4, 0.3, 0.2),
+      1080,
+    )
+  }
+
+  #[test]
+  fn test_project_unproject_corners() {
+    let projector = projector();
+    let corners = [
+      (13.70, 100.50),
+      (13.70, 100.60),
+      (13.80, 100.50),
+      (13.80, 100.60),
+    ];
+
+    for (lat, lon) in corners {
+      let (x, y) = projector.project_f64(lat, lon);
+      let (back_lat, back_lon) = projector.unproject(x, y);
+      assert!((back_lat - lat).abs() < 1e-9);
+      assert!((back_lon - lon).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn test_info_matches_projection() {
+    let projector = projector();
+    let info = projector.projection_info();
+
+    // North-west corner is the top-left of the pixel rect
+    let (x, y) = projector.project_f64(13.80, 100.50);
+    assert!((x - info.pixel_rect.x).abs() < 1e-9);
+    assert!((y - info.pixel_rect.y).abs() < 1e-9);
+
+    // South-east corner is the bottom-right
+    let (x, y) = projector.project_f64(13.70, 100.60);
+    assert!((x - (info.pixel_rect.x + info.pixel_rect.width)).abs() < 1e-9);
+    assert!((y - (info.pixel_rect.y + info.pixel_rect.height)).abs() < 1e-9);
+
+    assert!((info.scale_px_per_deg_x - 432.0 / 0.1).abs() < 1e-6);
+    assert_eq!(info.rotation, 0.0);
+  }
+
+  #[test]
+  fn test_antimeridian_route_is_contiguous() {
+    let points = [
+      (-17.0, 179.9),
+      (-17.0, 179.95),
+      (-17.0, -179.95),
+      (-17.0, -179.9),
+    ];
+    let projector = Projector::new(
+      &points,
+      RouteScale::new(0.8, 0.1, 0.1),
+      1000,
+    );
+
+    // One line from left to right, no jump across the frame
+    let xs: Vec<f64> = points
+      .iter()
+      .map(|&(lat, lon)| projector.project_f64(lat, lon).0)
+      .collect();
+    for (x, expected) in xs.iter().zip([100.0, 300.0, 700.0, 900.0]) {
+      assert!((x - expected).abs() < 1e-6, "{:?}", xs);
+    }
+    let info = projector.projection_info();
+    assert!((info.scale_px_per_deg_x - 4000.0).abs() < 1e-6);
+
+    // Unprojected longitudes are back in -180..180
+    let (x, y) = projector.project_f64(-17.0, -179.95);
+    let (_, lon) = projector.unproject(x, y);
+    assert!((lon + 179.95).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_degenerate_route_is_centered() {
+    let projector = Projector::new(
+      &[(13.7, 100.5)],
+      RouteScale::new(0.4, 0.3, 0.2),
+      1000,
+    );
+    let point = projector.project_f64(13.7, 100.5);
+    assert_eq!(point, (500.0, 400.0));
+    assert_eq!(
+      projector.projection_info().scale_px_per_deg_x,
+      0.0
+    );
+  }
+}
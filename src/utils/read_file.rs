@@ -1,93 +1,1916 @@
-use std::fs::File;
+use std::{
+  fs::{self, File},
+  io::Read,
+  mem,
+};
 
-use anyhow::Result;
-use fitparser::{profile::MesgNum, Value};
+use anyhow::{ensure, Context, Result};
+use chrono::DateTime;
+use fitparser::{profile::MesgNum, FitDataRecord, Value};
+use roxmltree::{Document, Node};
 
 use crate::{
-  types::fit_data::{LapData, RouteData},
-  utils::converter::{semicircles_to_degrees, speed_to_pace},
+  analysis::ground_m,
+  configs::{
+    ExtraField, ExtraFieldKind, FileConfig, InputFormat, KmlLines, SportProfile,
+  },
+  types::{
+    fit_data::{Activity, LapData, RouteData, SessionRange, SessionSummary},
+    warning::{Warning, Warnings},
+  },
+  utils::{
+    converter::{position_degrees, speed_to_pace, NO_PACE},
+    parallel::map_ordered,
+    progression::activity_times,
+  },
 };
 
+/// Seconds between two records above which the route line breaks, as it
+/// does at a timer pause
+pub const PAUSE_GAP_S: i64 = 60;
+
 pub fn fit_reader(file_path: &str) -> Result<(RouteData, LapData)> {
-  let mut paces = Vec::new();
-  let mut gps_points = Vec::new();
-  let mut distances = Vec::new();
+  fit_reader_with_fields(file_path, &[])
+}
 
-  let mut avg_heart_rate = Vec::new();
-  let mut enhanced_avg_speed = Vec::new();
-  let mut avg_step_length = Vec::new();
+/// Like `fit_reader`, also capturing `extra_fields` from every record into
+/// `RouteData::extra`
+pub fn fit_reader_with_fields(
+  file_path: &str,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, LapData)> {
+  fit_reader_from_with_fields(File::open(file_path)?, extra_fields)
+}
 
-  let mut fp = File::open(file_path)?;
+/// Like `fit_reader`, reading the FIT data from `reader`, e.g. uploaded
+/// bytes, so they need not be written to a file first
+pub fn fit_reader_from<R: Read>(reader: R) -> Result<(RouteData, LapData)> {
+  fit_reader_from_with_fields(reader, &[])
+}
 
-  for data in fitparser::from_reader(&mut fp)? {
-    match data.kind() {
-      MesgNum::Lap => {
-        let mut hr = None;
-        let mut speed = None;
-        let mut length = None;
+/// Like `fit_reader_from`, also capturing `extra_fields` from every
+/// record into `RouteData::extra`
+pub fn fit_reader_from_with_fields<R: Read>(
+  reader: R,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, LapData)> {
+  let sessions = read_fit_sessions(fit_records(reader)?, extra_fields);
+  let (route, lap, _) = join_sessions(sessions, extra_fields);
+  Ok((route, lap))
+}
 
-        for field in data.fields() {
-          match (field.name(), field.value()) {
-            ("avg_heart_rate", Value::UInt8(v)) => hr = Some(*v),
-            ("enhanced_avg_speed", Value::Float64(v)) => {
-              speed = Some(speed_to_pace(*v as f32))
-            }
-            ("avg_step_length", Value::Float64(v)) => length = Some(*v),
-            _ => {}
-          }
-        }
+/// Reads the records and laps of each session of a FIT file, e.g. the
+/// run, bike and run of a duathlon. A session holds the messages before
+/// its Session message; records after the last one form a session of
+/// their own, so a file without Session messages is a single session.
+pub fn fit_reader_sessions(
+  file_path: &str,
+) -> Result<Vec<(RouteData, LapData)>> {
+  fit_reader_sessions_with_fields(file_path, &[])
+}
 
-        if let (Some(hr), Some(speed), Some(length)) = (hr, speed, length) {
-          avg_heart_rate.push(hr);
-          enhanced_avg_speed.push(speed);
-          avg_step_length.push(length);
-        }
+/// Like `fit_reader_sessions`, also capturing `extra_fields` from every
+/// record into `RouteData::extra`
+pub fn fit_reader_sessions_with_fields(
+  file_path: &str,
+  extra_fields: &[ExtraField],
+) -> Result<Vec<(RouteData, LapData)>> {
+  let records = fit_records(File::open(file_path)?)?;
+  let sessions = read_fit_sessions(records, extra_fields);
+  Ok(
+    sessions
+      .into_iter()
+      .map(|(route, lap, _)| (route, lap))
+      .collect(),
+  )
+}
+
+/// Messages of the FIT data in `reader`
+fn fit_records(mut reader: impl Read) -> Result<Vec<FitDataRecord>> {
+  Ok(fitparser::from_reader(&mut reader)?)
+}
+
+/// Records and laps of each session among the messages of a FIT file,
+/// with the sport its Session message names
+fn read_fit_sessions(
+  records: Vec<FitDataRecord>,
+  extra_fields: &[ExtraField],
+) -> Vec<(RouteData, LapData, Option<String>)> {
+  let mut sessions = Vec::new();
+  let mut route = empty_route(extra_fields);
+  let mut recorded_speeds = Vec::new();
+  let mut recorded_cadences = Vec::new();
+  let mut lap = LapData::default();
+  // Whether the timer stopped since the last GPS record
+  let mut paused = false;
+
+  for data in records {
+    match data.kind() {
+      MesgNum::Lap => {
+        let fields = data.fields().iter().map(|f| (f.name(), f.value()));
+        push_fit_lap(fields, &mut lap);
       }
 
       MesgNum::Record => {
         let mut lat = None;
         let mut lon = None;
-        let mut pace = None;
+        let mut distance = None;
+        let mut heart_rate = None;
+        let mut cadence = None;
+        let mut fractional_cadence = 0.0;
+        let mut power = None;
+        let mut altitude = None;
+        let mut enhanced_altitude = None;
+        let mut timestamp = None;
 
         for field in data.fields() {
           match (field.name(), field.value()) {
-            ("enhanced_speed", Value::Float32(v)) => {
-              pace = Some(speed_to_pace(*v))
-            }
-            ("enhanced_speed", Value::Float64(v)) => {
-              pace = Some(speed_to_pace(*v as f32))
-            }
+            // Without a GPS fix, the record is dropped like one without
+            // a position
             ("position_lat", Value::SInt32(v)) => {
-              lat = Some(semicircles_to_degrees(*v))
+              lat = position_degrees(*v, 90.0)
             }
             ("position_long", Value::SInt32(v)) => {
-              lon = Some(semicircles_to_degrees(*v))
+              lon = position_degrees(*v, 180.0)
+            }
+            ("distance", Value::Float64(v)) => distance = Some(*v),
+            ("heart_rate", Value::UInt8(v)) => heart_rate = Some(*v),
+            ("cadence", Value::UInt8(v)) => cadence = Some(*v as f64),
+            ("fractional_cadence", Value::Float64(v)) => {
+              fractional_cadence = *v
+            }
+            ("power", Value::UInt16(v)) => power = Some(*v),
+            ("altitude", Value::Float64(v)) => altitude = Some(*v),
+            ("enhanced_altitude", Value::Float64(v)) => {
+              enhanced_altitude = Some(*v)
+            }
+            ("timestamp", Value::Timestamp(v)) => {
+              timestamp = Some(v.timestamp())
             }
-            ("distance", Value::Float64(v)) => distances.push(*v),
             _ => {}
           }
         }
 
-        if let (Some(lat), Some(lon), Some(pace)) = (lat, lon, pace) {
-          gps_points.push((lat, lon));
-          paces.push(pace);
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+          // The line breaks across a timer pause or a gap in the records
+          let previous = route.timestamps.last().copied().flatten();
+          let gap = match (previous, timestamp) {
+            (Some(previous), Some(now)) => now - previous > PAUSE_GAP_S,
+            _ => false,
+          };
+          if (paused || gap) && !route.gps_points.is_empty() {
+            route.segment_breaks.push(route.gps_points.len() - 1);
+          }
+          paused = false;
+
+          // Without a recorded distance, add the ground distance
+          let distance = distance.unwrap_or_else(|| {
+            match (
+              route.gps_points.last(),
+              route.distances.last(),
+            ) {
+              (Some(&previous), Some(&total)) => {
+                total + ground_m(previous, (lat, lon))
+              }
+              _ => 0.0,
+            }
+          });
+
+          let fields = data.fields().iter().map(|f| (f.name(), f.value()));
+          recorded_speeds.push(record_speed(fields));
+          route.gps_points.push((lat, lon));
+          route.distances.push(distance);
+          route.heart_rates.push(heart_rate);
+          recorded_cadences.push(cadence.map(|rpm| rpm + fractional_cadence));
+          route.powers.push(power);
+          route.altitudes.push(enhanced_altitude.or(altitude));
+          route.timestamps.push(timestamp);
+
+          let fields = data.fields().iter().map(|f| (f.name(), f.value()));
+          let values = extra_field_values(fields, extra_fields);
+          for (field, value) in extra_fields.iter().zip(values) {
+            if let Some(series) = route.extra.get_mut(&field.label) {
+              series.push(value);
+            }
+          }
         }
       }
 
+      MesgNum::Event => {
+        let fields = data.fields().iter().map(|f| (f.name(), f.value()));
+        paused |= is_timer_stop(fields);
+      }
+
+      MesgNum::Session => {
+        let sport = data.fields().iter().find_map(|field| {
+          match (field.name(), field.value()) {
+            ("sport", Value::String(v)) => Some(v.clone()),
+            _ => None,
+          }
+        });
+        fill_speeds(
+          &mut route,
+          mem::take(&mut recorded_speeds),
+        );
+        fill_cadences(
+          &mut route,
+          mem::take(&mut recorded_cadences),
+          sport.as_deref(),
+        );
+        let next = empty_route(extra_fields);
+        sessions.push((
+          mem::replace(&mut route, next),
+          mem::take(&mut lap),
+          sport,
+        ));
+        paused = false;
+      }
+
       _ => {}
     }
   }
 
-  Ok((
-    RouteData {
-      paces,
-      gps_points,
-      distances,
+  // Records after the last Session message
+  fill_speeds(&mut route, recorded_speeds);
+  fill_cadences(&mut route, recorded_cadences, None);
+  let trailing = !route.gps_points.is_empty() || !lap.avg_speed.is_empty();
+  if trailing || sessions.is_empty() {
+    sessions.push((route, lap, None));
+  }
+  sessions
+}
+
+/// Joins sessions into one route and lap list, with the records and laps
+/// each session takes in them
+fn join_sessions(
+  sessions: Vec<(RouteData, LapData, Option<String>)>,
+  extra_fields: &[ExtraField],
+) -> (RouteData, LapData, Vec<SessionRange>) {
+  let mut route = empty_route(extra_fields);
+  let mut lap = LapData::default();
+  let mut ranges = Vec::new();
+  for (session_route, session_lap, sport) in sessions {
+    let first_record = route.gps_points.len();
+    let first_distance = route.distances.len();
+    let first_lap = lap.avg_speed.len();
+    route.paces.extend(session_route.paces);
+    route.gps_points.extend(session_route.gps_points);
+    route.distances.extend(session_route.distances);
+    route.heart_rates.extend(session_route.heart_rates);
+    route.speeds.extend(session_route.speeds);
+    route.cadences.extend(session_route.cadences);
+    route.powers.extend(session_route.powers);
+    route.altitudes.extend(session_route.altitudes);
+    route.timestamps.extend(session_route.timestamps);
+    route.segment_breaks.extend(
+      session_route
+        .segment_breaks
+        .iter()
+        .map(|record| first_record + record),
+    );
+    for (label, values) in session_route.extra {
+      route.extra.entry(label).or_default().extend(values);
+    }
+
+    lap.avg_heart_rate.extend(session_lap.avg_heart_rate);
+    lap.avg_power.extend(session_lap.avg_power);
+    lap
+      .enhanced_avg_speed
+      .extend(session_lap.enhanced_avg_speed);
+    lap.avg_step_length.extend(session_lap.avg_step_length);
+    lap.total_distance.extend(session_lap.total_distance);
+    lap.avg_speed.extend(session_lap.avg_speed);
+    lap.avg_cadence.extend(session_lap.avg_cadence);
+    lap
+      .total_elapsed_time
+      .extend(session_lap.total_elapsed_time);
+
+    ranges.push(SessionRange {
+      records: first_record..route.gps_points.len(),
+      distances: first_distance..route.distances.len(),
+      laps: first_lap..lap.avg_speed.len(),
+      sport,
+    });
+  }
+  (route, lap, ranges)
+}
+
+/// Reads the track points of a GPX file. GPX has no laps, so the
+/// `LapData` is empty.
+pub fn gpx_reader(file_path: &str) -> Result<(RouteData, LapData)> {
+  gpx_reader_with_fields(file_path, &[])
+}
+
+/// Like `gpx_reader`, also capturing `extra_fields` from the extensions of
+/// every point into `RouteData::extra`
+pub fn gpx_reader_with_fields(
+  file_path: &str,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, LapData)> {
+  let text = fs::read_to_string(file_path)?;
+  let (route, _) = parse_gpx(&text, extra_fields)?;
+  Ok((route, LapData::default()))
+}
+
+/// Route and session summary of a GPX document.
+///
+/// The points of every track and segment are joined in order. Distances
+/// are summed along the points; points without a recorded speed take the
+/// speed between them and the point before, and have no pace (`NO_PACE`)
+/// without timestamps.
+fn parse_gpx(
+  text: &str,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, SessionSummary)> {
+  let document = Document::parse(text).context("Invalid GPX file")?;
+  let root = document.root_element();
+  ensure!(
+    root.has_tag_name("gpx"),
+    "Not a GPX file: the root element is <{}>",
+    root.tag_name().name()
+  );
+
+  let mut route = empty_route(extra_fields);
+  let mut recorded_speeds = Vec::new();
+  let mut recorded_cadences = Vec::new();
+  let points = root.descendants().filter(|node| node.has_tag_name("trkpt"));
+  for point in points {
+    let coordinate = |name| point.attribute(name)?.trim().parse::<f64>().ok();
+    let (Some(lat), Some(lon)) = (coordinate("lat"), coordinate("lon")) else {
+      continue;
+    };
+
+    let distance = match (
+      route.gps_points.last(),
+      route.distances.last(),
+    ) {
+      (Some(&previous), Some(&total)) => total + ground_m(previous, (lat, lon)),
+      _ => 0.0,
+    };
+    let extensions = point.children().find(|n| n.has_tag_name("extensions"));
+    let extension =
+      |name| extensions.and_then(|node| element_value(node, name));
+
+    route.gps_points.push((lat, lon));
+    route.distances.push(distance);
+    route.altitudes.push(element_value(point, "ele"));
+    route.timestamps.push(
+      child_text(point, "time")
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.timestamp()),
+    );
+    route.heart_rates.push(extension("hr").map(|hr| hr as u8));
+    recorded_cadences.push(extension("cad"));
+    route
+      .powers
+      .push(extension("power").map(|power| power as u16));
+    recorded_speeds.push(element_value(point, "speed"));
+    push_extension_fields(&mut route, extensions, extra_fields);
+  }
+  fill_speeds(&mut route, recorded_speeds);
+
+  let sport = root
+    .descendants()
+    .find(|node| node.has_tag_name("trk"))
+    .and_then(|track| child_text(track, "type"))
+    .map(|sport| sport.to_lowercase());
+  fill_cadences(
+    &mut route,
+    recorded_cadences,
+    sport.as_deref(),
+  );
+  let session = SessionSummary {
+    total_distance: route.distances.last().copied(),
+    start_time: route.timestamps.iter().flatten().next().copied(),
+    sport,
+    ..Default::default()
+  };
+  Ok((route, session))
+}
+
+/// Reads the trackpoints and laps of a TCX file (Garmin Connect export)
+pub fn tcx_reader(file_path: &str) -> Result<(RouteData, LapData)> {
+  tcx_reader_with_fields(file_path, &[])
+}
+
+/// Like `tcx_reader`, also capturing `extra_fields` from the extensions of
+/// every trackpoint into `RouteData::extra`
+pub fn tcx_reader_with_fields(
+  file_path: &str,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, LapData)> {
+  let text = fs::read_to_string(file_path)?;
+  let (route, lap, _) = parse_tcx(&text, extra_fields)?;
+  Ok((route, lap))
+}
+
+/// Route, laps and session summary of a TCX document.
+///
+/// Trackpoints without a position are skipped; their distances are the
+/// recorded `DistanceMeters`, or summed along the points without one.
+/// Every `<Lap>` gives a lap row with the fields of a FIT Lap message; a
+/// lap without an average heart rate keeps its row with no heart rate,
+/// shown as "-", like a FIT lap without one.
+fn parse_tcx(
+  text: &str,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, LapData, SessionSummary)> {
+  let document = Document::parse(text).context("Invalid TCX file")?;
+  let root = document.root_element();
+  ensure!(
+    root.has_tag_name("TrainingCenterDatabase"),
+    "Not a TCX file: the root element is <{}>",
+    root.tag_name().name()
+  );
+
+  let mut route = empty_route(extra_fields);
+  let mut recorded_speeds = Vec::new();
+  let points = root
+    .descendants()
+    .filter(|node| node.has_tag_name("Trackpoint"));
+  for point in points {
+    let position = point.children().find(|n| n.has_tag_name("Position"));
+    let coordinate = |name| position.and_then(|node| child_value(node, name));
+    let (Some(lat), Some(lon)) = (
+      coordinate("LatitudeDegrees"),
+      coordinate("LongitudeDegrees"),
+    ) else {
+      continue;
+    };
+
+    let distance = match (
+      child_value(point, "DistanceMeters"),
+      route.gps_points.last(),
+      route.distances.last(),
+    ) {
+      (Some(distance), ..) => distance,
+      (None, Some(&previous), Some(&total)) => {
+        total + ground_m(previous, (lat, lon))
+      }
+      _ => 0.0,
+    };
+    let extensions = point.children().find(|n| n.has_tag_name("Extensions"));
+    let extension =
+      |name| extensions.and_then(|node| element_value(node, name));
+    // Bike cadence is a trackpoint field in rpm, run cadence an extension
+    // counting one foot
+    let cadence = child_value(point, "Cadence")
+      .or(extension("RunCadence").map(|cadence| cadence * 2.0));
+
+    route.gps_points.push((lat, lon));
+    route.distances.push(distance);
+    route.altitudes.push(child_value(point, "AltitudeMeters"));
+    route.timestamps.push(
+      child_text(point, "Time")
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.timestamp()),
+    );
+    route.heart_rates.push(
+      point
+        .children()
+        .find(|n| n.has_tag_name("HeartRateBpm"))
+        .and_then(|node| child_value(node, "Value"))
+        .map(|hr| hr as u8),
+    );
+    route.cadences.push(cadence.map(|cad| cad.round() as u16));
+    route
+      .powers
+      .push(extension("Watts").map(|watts| watts as u16));
+    recorded_speeds.push(extension("Speed"));
+    push_extension_fields(&mut route, extensions, extra_fields);
+  }
+  fill_speeds(&mut route, recorded_speeds);
+
+  let mut lap = LapData::default();
+  let laps = root.descendants().filter(|node| node.has_tag_name("Lap"));
+  for node in laps.clone() {
+    let distance = child_value(node, "DistanceMeters").unwrap_or(0.0);
+    let elapsed = child_value(node, "TotalTimeSeconds").unwrap_or(0.0);
+    let extensions = node.children().find(|n| n.has_tag_name("Extensions"));
+    let extension =
+      |name| extensions.and_then(|node| element_value(node, name));
+    let speed = extension("AvgSpeed")
+      .or((elapsed > 0.0).then(|| distance / elapsed))
+      .unwrap_or(0.0);
+    let heart_rate = node
+      .children()
+      .find(|n| n.has_tag_name("AverageHeartRateBpm"))
+      .and_then(|node| child_value(node, "Value"));
+    let run_cadence = extension("AvgRunCadence");
+    let cadence = run_cadence.or(child_value(node, "Cadence"));
+
+    // Run cadence counts one foot; strides are stored in decimeters.
+    // Bike laps have no stride, as in FIT.
+    let stride_dm = run_cadence
+      .filter(|&cadence| cadence > 0.0)
+      .map(|cadence| speed * 60.0 / (cadence * 2.0) * 10.0);
+
+    lap.avg_heart_rate.push(heart_rate.map(|hr| hr as u8));
+    lap
+      .avg_power
+      .push(extension("AvgWatts").map(|watts| watts as u16));
+    lap.enhanced_avg_speed.push(speed_to_pace(speed as f32));
+    lap.avg_step_length.push(stride_dm);
+    lap.total_distance.push(distance);
+    lap.avg_speed.push(speed);
+    lap
+      .avg_cadence
+      .push(cadence.map_or(0, |cad| cad.round() as u8));
+    lap.total_elapsed_time.push(elapsed);
+  }
+
+  let activity = root.descendants().find(|n| n.has_tag_name("Activity"));
+  let start_time = activity
+    .and_then(|node| child_text(node, "Id"))
+    .or_else(|| laps.clone().find_map(|node| node.attribute("StartTime")))
+    .and_then(|time| DateTime::parse_from_rfc3339(time.trim()).ok())
+    .map(|time| time.timestamp())
+    .or_else(|| route.timestamps.iter().flatten().next().copied());
+  // TCX sports are Running, Biking and Other
+  let sport = activity
+    .and_then(|node| node.attribute("Sport"))
+    .map(
+      |sport| match sport.to_lowercase().as_str() {
+        "biking" => "cycling".to_string(),
+        sport => sport.to_string(),
+      },
+    );
+  let has_laps = !lap.total_distance.is_empty();
+  let session = SessionSummary {
+    total_distance: if has_laps {
+      Some(lap.total_distance.iter().sum())
+    } else {
+      route.distances.last().copied()
     },
+    total_timer_time: has_laps.then(|| lap.total_elapsed_time.iter().sum()),
+    start_time,
+    sport,
+    ..Default::default()
+  };
+  Ok((route, lap, session))
+}
+
+/// Reads the route of a KML file, e.g. one planned in Google My Maps.
+/// KML has no times or laps: distances are summed along the points, paces
+/// are `NO_PACE` and the `LapData` is empty. `lines` picks which
+/// LineStrings make up the route.
+pub fn kml_reader(
+  file_path: &str,
+  lines: KmlLines,
+) -> Result<(RouteData, LapData)> {
+  let text = fs::read_to_string(file_path)?;
+  let (route, _) = parse_kml(&text, lines, &[])?;
+  Ok((route, LapData::default()))
+}
+
+/// Route and session summary of a KML document.
+///
+/// Each `<LineString>` holds `lon,lat[,alt]` tuples separated by
+/// whitespace. Concatenated LineStrings break the line between them, and
+/// the gap adds no distance. `extra_fields` get no values.
+fn parse_kml(
+  text: &str,
+  lines: KmlLines,
+  extra_fields: &[ExtraField],
+) -> Result<(RouteData, SessionSummary)> {
+  let document = Document::parse(text).context("Invalid KML file")?;
+  let root = document.root_element();
+  ensure!(
+    root.has_tag_name("kml"),
+    "Not a KML file: the root element is <{}>",
+    root.tag_name().name()
+  );
+
+  let line_strings: Vec<Vec<(f64, f64, Option<f64>)>> = root
+    .descendants()
+    .filter(|node| node.has_tag_name("LineString"))
+    .map(|line| {
+      child_text(line, "coordinates")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|tuple| {
+          let mut values = tuple.split(',').map(|v| v.trim().parse().ok());
+          let lon = values.next()??;
+          let lat = values.next()??;
+          Some((lat, lon, values.next().flatten()))
+        })
+        .collect::<Vec<_>>()
+    })
+    .filter(|points| !points.is_empty())
+    .collect();
+  ensure!(
+    !line_strings.is_empty(),
+    "KML file has no LineString with coordinates"
+  );
+  let line_strings = match lines {
+    KmlLines::Concatenate => line_strings,
+    KmlLines::Longest => {
+      let length = |points: &[(f64, f64, Option<f64>)]| -> f64 {
+        points
+          .windows(2)
+          .map(|pair| {
+            ground_m(
+              (pair[0].0, pair[0].1),
+              (pair[1].0, pair[1].1),
+            )
+          })
+          .sum()
+      };
+      line_strings
+        .into_iter()
+        .max_by(|a, b| length(a).total_cmp(&length(b)))
+        .into_iter()
+        .collect()
+    }
+  };
+
+  let mut route = empty_route(extra_fields);
+  for points in line_strings {
+    if !route.gps_points.is_empty() {
+      route.segment_breaks.push(route.gps_points.len() - 1);
+    }
+    let mut previous = None;
+    for (lat, lon, altitude) in points {
+      let distance = match (previous, route.distances.last()) {
+        (Some(previous), Some(&total)) => {
+          total + ground_m(previous, (lat, lon))
+        }
+        (None, Some(&total)) => total,
+        _ => 0.0,
+      };
+      previous = Some((lat, lon));
+      route.gps_points.push((lat, lon));
+      route.distances.push(distance);
+      route.altitudes.push(altitude);
+      route.timestamps.push(None);
+      route.heart_rates.push(None);
+      route.cadences.push(None);
+      route.powers.push(None);
+    }
+  }
+  let count = route.gps_points.len();
+  for series in route.extra.values_mut() {
+    series.resize(count, None);
+  }
+  fill_speeds(&mut route, vec![None; count]);
+
+  let session = SessionSummary {
+    total_distance: route.distances.last().copied(),
+    ..Default::default()
+  };
+  Ok((route, session))
+}
+
+/// Empty route with a series for each of `extra_fields`
+fn empty_route(extra_fields: &[ExtraField]) -> RouteData {
+  RouteData {
+    paces: Vec::new(),
+    gps_points: Vec::new(),
+    distances: Vec::new(),
+    heart_rates: Vec::new(),
+    speeds: Vec::new(),
+    cadences: Vec::new(),
+    powers: Vec::new(),
+    altitudes: Vec::new(),
+    timestamps: Vec::new(),
+    segment_breaks: Vec::new(),
+    extra: extra_fields
+      .iter()
+      .map(|field| (field.label.clone(), Vec::new()))
+      .collect(),
+  }
+}
+
+/// Pushes the values of `extra_fields` among the numeric leaves of an
+/// XML point's `extensions` (e.g. Strava's `<power>`)
+fn push_extension_fields(
+  route: &mut RouteData,
+  extensions: Option<Node>,
+  extra_fields: &[ExtraField],
+) {
+  let leaves: Vec<(&str, Value)> = extensions
+    .into_iter()
+    .flat_map(|node| node.descendants())
+    .filter(|node| {
+      node.is_element() && !node.children().any(|c| c.is_element())
+    })
+    .filter_map(|node| {
+      let value = node.text()?.trim().parse().ok()?;
+      Some((
+        node.tag_name().name(),
+        Value::Float64(value),
+      ))
+    })
+    .collect();
+  let fields = leaves.iter().map(|(name, value)| (*name, value));
+  let values = extra_field_values(fields, extra_fields);
+  for (field, value) in extra_fields.iter().zip(values) {
+    if let Some(series) = route.extra.get_mut(&field.label) {
+      series.push(value);
+    }
+  }
+}
+
+/// Fills the speeds and paces of a route: the `recorded` speed of each
+/// point, or the speed over the segment ending at it (the first point
+/// takes the first segment's). Points without either keep the speed
+/// before them, or have none (`NO_PACE`).
+fn fill_speeds(route: &mut RouteData, recorded: Vec<Option<f64>>) {
+  let segment_speed = |i: usize| {
+    let seconds = route.timestamps[i]? - route.timestamps[i - 1]?;
+    let meters = route.distances[i] - route.distances[i - 1];
+    (seconds > 0).then(|| meters / seconds as f64)
+  };
+  let count = route.gps_points.len();
+  let mut last_speed = None;
+  for (i, recorded) in recorded.into_iter().enumerate() {
+    let derived = (count > 1).then(|| segment_speed(i.max(1))).flatten();
+    let speed = recorded.or(derived).or(last_speed);
+    last_speed = speed;
+    route.speeds.push(speed.unwrap_or(0.0));
+    route.paces.push(speed.map_or_else(
+      || NO_PACE.to_string(),
+      |speed| speed_to_pace(speed as f32),
+    ));
+  }
+}
+
+/// Adds the `recorded` cadences (with their fractions) of records of
+/// `sport` to `route` in steps per minute. Running and walking cadence is
+/// recorded for one foot and doubled; cycling cadence is kept in rpm.
+fn fill_cadences(
+  route: &mut RouteData,
+  recorded: Vec<Option<f64>>,
+  sport: Option<&str>,
+) {
+  let factor = if SportProfile::Auto.resolve(sport).shows_speed() {
+    1.0
+  } else {
+    2.0
+  };
+  route.cadences.extend(
+    recorded
+      .into_iter()
+      .map(|cadence| cadence.map(|cadence| (cadence * factor).round() as u16)),
+  );
+}
+
+/// Trimmed text of the first child element of `node` named `name`
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+  node
+    .children()
+    .find(|child| child.has_tag_name(name))?
+    .text()
+    .map(str::trim)
+}
+
+/// Number in the first child element of `node` named `name`
+fn child_value(node: Node, name: &str) -> Option<f64> {
+  child_text(node, name)?.parse().ok()
+}
+
+/// Number in the first element named `name` inside `node`, nested ones
+/// included (e.g. within Garmin's `<TrackPointExtension>`)
+fn element_value(node: Node, name: &str) -> Option<f64> {
+  node
+    .descendants()
+    .find(|child| child.has_tag_name(name))?
+    .text()?
+    .trim()
+    .parse()
+    .ok()
+}
+
+/// Reads records, laps and the session summary of a FIT, GPX, TCX or KML
+/// file, told apart by the file extension
+pub fn activity_reader(
+  file_path: &str,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  activity_reader_with_format(
+    file_path,
+    InputFormat::Auto,
+    extra_fields,
+  )
+}
+
+/// Like `activity_reader`, reading the file as `format`
+pub fn activity_reader_with_format(
+  file_path: &str,
+  format: InputFormat,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  read_activity_file(
+    file_path,
+    format,
+    KmlLines::default(),
+    extra_fields,
+  )
+}
+
+/// Reads the activity of `file_config`: its in-memory `fit_bytes` if set,
+/// otherwise the file at `fit_file`
+pub fn activity_reader_for(
+  file_config: &FileConfig,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  match &file_config.fit_bytes {
+    Some(bytes) => read_activity(
+      &bytes[..],
+      file_config.input_format,
+      file_config.kml_lines,
+      extra_fields,
+    ),
+    None => read_activity_file(
+      &file_config.fit_file,
+      file_config.input_format,
+      file_config.kml_lines,
+      extra_fields,
+    ),
+  }
+}
+
+/// Like `activity_reader_with_format`, reading the activity from `reader`.
+/// With no file extension to go by, `InputFormat::Auto` reads FIT data.
+pub fn activity_reader_from<R: Read>(
+  reader: R,
+  format: InputFormat,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  read_activity(
+    reader,
+    format,
+    KmlLines::default(),
+    extra_fields,
+  )
+}
+
+/// Reads the activity file at `file_path` in a `parse` span
+fn read_activity_file(
+  file_path: &str,
+  format: InputFormat,
+  kml_lines: KmlLines,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  let span = tracing::info_span!(
+    "parse",
+    file = file_path,
+    point_count = tracing::field::Empty
+  );
+  let _entered = span.enter();
+  let activity = read_activity(
+    File::open(file_path)?,
+    format.resolve(file_path),
+    kml_lines,
+    extra_fields,
+  )?;
+  span.record(
+    "point_count",
+    activity.route.gps_points.len(),
+  );
+  Ok(activity)
+}
+
+/// Reads the activity in `reader` as `format`, `Auto` reading FIT data
+fn read_activity<R: Read>(
+  mut reader: R,
+  format: InputFormat,
+  kml_lines: KmlLines,
+  extra_fields: &[ExtraField],
+) -> Result<Activity> {
+  let mut sessions = Vec::new();
+  let (route, lap, mut session) = match format {
+    InputFormat::Gpx => {
+      let mut text = String::new();
+      reader.read_to_string(&mut text)?;
+      let (route, session) = parse_gpx(&text, extra_fields)?;
+      (route, LapData::default(), session)
+    }
+    InputFormat::Tcx => {
+      let mut text = String::new();
+      reader.read_to_string(&mut text)?;
+      parse_tcx(&text, extra_fields)?
+    }
+    InputFormat::Kml => {
+      let mut text = String::new();
+      reader.read_to_string(&mut text)?;
+      let (route, session) = parse_kml(&text, kml_lines, extra_fields)?;
+      (route, LapData::default(), session)
+    }
+    InputFormat::Fit | InputFormat::Auto => {
+      let records = fit_records(reader)?;
+      let summary = session_summary(&records);
+      let fit_sessions = read_fit_sessions(records, extra_fields);
+      let (route, lap, ranges) = join_sessions(fit_sessions, extra_fields);
+      if ranges.len() > 1 {
+        sessions = ranges;
+      }
+      (route, lap, summary)
+    }
+  };
+  let distance = session
+    .total_distance
+    .or_else(|| route.distances.last().copied())
+    .unwrap_or(0.0);
+  session.times = activity_times(
+    &route.timestamps,
+    &route.distances,
+    &lap.total_elapsed_time,
+    distance,
+  );
+  Ok(Activity {
+    warnings: parse_warnings(&route, &lap),
+    route,
+    lap,
+    session,
+    sessions,
+  })
+}
+
+/// Records and laps that parsed but will not render as expected: no laps,
+/// or a record series that does not line up with the GPS points (the
+/// distance of a record without position is kept, its point is not)
+pub fn parse_warnings(route: &RouteData, lap: &LapData) -> Warnings {
+  let mut warnings = Warnings::default();
+  warnings.push_if(
+    lap.avg_speed.is_empty(),
+    Warning::EmptyLapData,
+  );
+
+  let expected = route.gps_points.len();
+  let series = [
+    ("distances", route.distances.len()),
+    ("paces", route.paces.len()),
+    ("timestamps", route.timestamps.len()),
+  ];
+  for (series, actual) in series {
+    warnings.push_if(
+      actual != expected,
+      Warning::SeriesLengthMismatch {
+        series,
+        expected,
+        actual,
+      },
+    );
+  }
+  warnings
+}
+
+/// Adds a Lap message's `(name, value)` fields to `lap`. Only the speed
+/// is required: cycling laps have no step length, and a lap missing its
+/// heart rate or step length keeps its row with None there.
+fn push_fit_lap<'a>(
+  fields: impl IntoIterator<Item = (&'a str, &'a Value)>,
+  lap: &mut LapData,
+) {
+  let mut hr = None;
+  let mut power = None;
+  let mut speed = None;
+  let mut length = None;
+  let mut lap_distance = None;
+  let mut cadence = None;
+  let mut elapsed = None;
+
+  for (name, value) in fields {
+    match (name, value) {
+      ("avg_heart_rate", Value::UInt8(v)) => hr = Some(*v),
+      ("avg_power", Value::UInt16(v)) => power = Some(*v),
+      ("enhanced_avg_speed", Value::Float64(v)) => speed = Some(*v),
+      ("avg_step_length", Value::Float64(v)) => length = Some(*v),
+      ("total_distance", Value::Float64(v)) => lap_distance = Some(*v),
+      ("avg_cadence", Value::UInt8(v)) => cadence = Some(*v),
+      ("total_elapsed_time", Value::Float64(v)) => elapsed = Some(*v),
+      _ => {}
+    }
+  }
+
+  if let Some(speed) = speed {
+    lap.avg_heart_rate.push(hr);
+    lap.avg_power.push(power);
+    lap.enhanced_avg_speed.push(speed_to_pace(speed as f32));
+    lap.avg_step_length.push(length);
+    lap.total_distance.push(lap_distance.unwrap_or(0.0));
+    lap.avg_speed.push(speed);
+    lap.avg_cadence.push(cadence.unwrap_or(0));
+    lap.total_elapsed_time.push(elapsed.unwrap_or(0.0));
+  }
+}
+
+/// Speed (m/s) among a Record message's `(name, value)` fields:
+/// `enhanced_speed`, or else `speed`, which older devices record alone
+fn record_speed<'a>(
+  fields: impl IntoIterator<Item = (&'a str, &'a Value)>,
+) -> Option<f64> {
+  let mut enhanced = None;
+  let mut speed = None;
+  for (name, value) in fields {
+    match (name, value) {
+      ("enhanced_speed", Value::Float32(v)) => enhanced = Some(*v as f64),
+      ("enhanced_speed", Value::Float64(v)) => enhanced = Some(*v),
+      ("speed", Value::Float64(v)) => speed = Some(*v),
+      // Unscaled, in mm/s
+      ("speed", Value::UInt16(v)) => speed = Some(*v as f64 / 1000.0),
+      _ => {}
+    }
+  }
+  enhanced.or(speed)
+}
+
+/// Whether an Event message's `(name, value)` fields stop the timer, as a
+/// manual or auto pause does
+fn is_timer_stop<'a>(
+  fields: impl IntoIterator<Item = (&'a str, &'a Value)>,
+) -> bool {
+  let mut timer = false;
+  let mut stop = false;
+  for (name, value) in fields {
+    match (name, value) {
+      ("event", Value::String(v)) => timer = v == "timer",
+      ("event_type", Value::String(v)) => stop = v.starts_with("stop"),
+      _ => {}
+    }
+  }
+  timer && stop
+}
+
+/// Values of `extra_fields` among one record's `(name, value)` fields
+pub fn extra_field_values<'a>(
+  fields: impl IntoIterator<Item = (&'a str, &'a Value)>,
+  extra_fields: &[ExtraField],
+) -> Vec<Option<f64>> {
+  let mut values = vec![None; extra_fields.len()];
+  for (name, value) in fields {
+    for (i, field) in extra_fields.iter().enumerate() {
+      if values[i].is_none() && field.matches(name) {
+        values[i] = match field.kind {
+          ExtraFieldKind::Numeric => numeric_value(value),
+        };
+      }
+    }
+  }
+  values
+}
+
+/// Integer or float FIT value as f64
+fn numeric_value(value: &Value) -> Option<f64> {
+  match value {
+    Value::Byte(v) | Value::UInt8(v) | Value::UInt8z(v) => Some(*v as f64),
+    Value::SInt8(v) => Some(*v as f64),
+    Value::SInt16(v) => Some(*v as f64),
+    Value::UInt16(v) | Value::UInt16z(v) => Some(*v as f64),
+    Value::SInt32(v) => Some(*v as f64),
+    Value::UInt32(v) | Value::UInt32z(v) => Some(*v as f64),
+    Value::SInt64(v) => Some(*v as f64),
+    Value::UInt64(v) | Value::UInt64z(v) => Some(*v as f64),
+    Value::Float32(v) => Some(*v as f64),
+    Value::Float64(v) => Some(*v),
+    _ => None,
+  }
+}
+
+/// Reads the records and laps of an activity file in `format`
+/// (`InputFormat::Auto` goes by the extension)
+pub fn route_reader(
+  file_path: &str,
+  format: InputFormat,
+) -> Result<(RouteData, LapData)> {
+  match format.resolve(file_path) {
+    InputFormat::Gpx => gpx_reader(file_path),
+    InputFormat::Tcx => tcx_reader(file_path),
+    InputFormat::Kml => kml_reader(file_path, KmlLines::default()),
+    InputFormat::Fit | InputFormat::Auto => fit_reader(file_path),
+  }
+}
+
+/// Reads several FIT, GPX, TCX or KML files (by extension), in parallel
+/// with the `rayon` feature; results keep the order of `file_paths`
+pub fn fit_reader_many(
+  file_paths: &[String],
+) -> Result<Vec<(RouteData, LapData)>> {
+  map_ordered(file_paths, |path| {
+    route_reader(path, InputFormat::Auto)
+  })
+  .into_iter()
+  .collect()
+}
+
+/// Reads the first Session message (activity totals) of a FIT file
+pub fn session_reader(file_path: &str) -> Result<SessionSummary> {
+  let records = fit_records(File::open(file_path)?)?;
+  Ok(session_summary(&records))
+}
+
+/// Totals of the first Session message among `records`
+fn session_summary(records: &[FitDataRecord]) -> SessionSummary {
+  let mut summary = SessionSummary::default();
+  let session = records.iter().find(|data| data.kind() == MesgNum::Session);
+  if let Some(data) = session {
+    for field in data.fields() {
+      match (field.name(), field.value()) {
+        ("total_distance", Value::Float64(v)) => {
+          summary.total_distance = Some(*v)
+        }
+        ("total_timer_time", Value::Float64(v)) => {
+          summary.total_timer_time = Some(*v)
+        }
+        ("total_ascent", Value::UInt16(v)) => summary.total_ascent = Some(*v),
+        ("total_calories", Value::UInt16(v)) => {
+          summary.total_calories = Some(*v)
+        }
+        ("avg_heart_rate", Value::UInt8(v)) => {
+          summary.avg_heart_rate = Some(*v)
+        }
+        ("start_time", Value::Timestamp(v)) => {
+          summary.start_time = Some(v.timestamp())
+        }
+        ("sport", Value::String(v)) => summary.sport = Some(v.clone()),
+        _ => {}
+      }
+    }
+  }
+
+  // Without a session start, use the time the file was created
+  if summary.start_time.is_none() {
+    let file_id = records.iter().find(|data| data.kind() == MesgNum::FileId);
+    if let Some(data) = file_id {
+      for field in data.fields() {
+        if let ("time_created", Value::Timestamp(v)) =
+          (field.name(), field.value())
+        {
+          summary.start_time = Some(v.timestamp());
+        }
+      }
+    }
+  }
+
+  // Some devices only name the sport in the Sport message
+  if summary.sport.is_none() {
+    let sport = records.iter().find(|data| data.kind() == MesgNum::Sport);
+    if let Some(data) = sport {
+      for field in data.fields() {
+        if let ("sport", Value::String(v)) = (field.name(), field.value()) {
+          summary.sport = Some(v.clone());
+        }
+      }
+    }
+  }
+
+  summary
+}
+
+/// Resolves `SportProfile::Auto` from the sport recorded in a FIT file;
+/// explicit profiles are returned without reading the file
+pub fn sport_reader(
+  file_path: &str,
+  profile: SportProfile,
+) -> Result<SportProfile> {
+  if profile != SportProfile::Auto {
+    return Ok(profile);
+  }
+  let summary = session_reader(file_path)?;
+  Ok(profile.resolve(summary.sport.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, io::Cursor, path::Path};
+
+  use fitparser::FitDataField;
+
+  use super::*;
+  use crate::utils::converter::{get_bounds, INVALID_SEMICIRCLES};
+
+  #[test]
+  fn test_extra_field_values_from_developer_fields() {
+    // Record with a Stryd developer field next to regular fields
+    let record = [
+      ("heart_rate", Value::UInt8(150)),
+      ("0_Power", Value::UInt16(287)),
+      ("Form Power", Value::UInt16(70)),
+      (
+        "vertical_oscillation",
+        Value::Float64(8.4),
+      ),
+    ];
+    let extra_fields = [
+      ExtraField::numeric("power", "Pwr", "W"),
+      ExtraField::numeric("Vertical_Oscillation", "VO", "cm"),
+      ExtraField::numeric("leg_spring_stiffness", "LSS", ""),
+    ];
+
+    let fields = record.iter().map(|(name, value)| (*name, value));
+    let values = extra_field_values(fields, &extra_fields);
+    assert_eq!(
+      values,
+      vec![Some(287.0), Some(8.4), None]
+    );
+  }
+
+  #[test]
+  fn test_extra_field_ignores_non_numeric() {
+    let record = [(
+      "power",
+      Value::String("high".to_string()),
+    )];
+    let fields = record.iter().map(|(name, value)| (*name, value));
+    let values = extra_field_values(
+      fields,
+      &[ExtraField::numeric("power", "Pwr", "W")],
+    );
+    assert_eq!(values, vec![None]);
+  }
+
+  fn route(points: usize, distances: usize) -> RouteData {
+    RouteData {
+      paces: vec!["5:00".to_string(); points],
+      gps_points: vec![(13.7, 100.5); points],
+      distances: (0..distances).map(|i| i as f64 * 10.0).collect(),
+      heart_rates: vec![None; points],
+      speeds: vec![3.3; points],
+      cadences: vec![None; points],
+      powers: vec![None; points],
+      altitudes: vec![None; points],
+      timestamps: vec![None; points],
+      segment_breaks: Vec::new(),
+      extra: HashMap::new(),
+    }
+  }
+
+  fn laps(count: usize) -> LapData {
     LapData {
-      avg_heart_rate,
-      enhanced_avg_speed,
-      avg_step_length,
-    },
-  ))
+      avg_heart_rate: vec![Some(150); count],
+      avg_power: vec![None; count],
+      enhanced_avg_speed: vec!["5:00".to_string(); count],
+      avg_step_length: vec![Some(1.1); count],
+      total_distance: vec![1000.0; count],
+      avg_speed: vec![3.3; count],
+      avg_cadence: vec![88; count],
+      total_elapsed_time: vec![300.0; count],
+    }
+  }
+
+  #[test]
+  fn test_record_speed_sources() {
+    let speed = |fields: &[(&'static str, Value)]| {
+      record_speed(fields.iter().map(|(name, value)| (*name, value)))
+    };
+    // The enhanced speed wins over the plain one
+    assert_eq!(
+      speed(&[
+        ("speed", Value::Float64(2.0)),
+        ("enhanced_speed", Value::Float64(3.0)),
+      ]),
+      Some(3.0)
+    );
+    assert_eq!(
+      speed(&[("enhanced_speed", Value::Float32(2.5))]),
+      Some(2.5)
+    );
+    // Plain speed, scaled to m/s or as raw mm/s
+    assert_eq!(
+      speed(&[("speed", Value::Float64(2.0))]),
+      Some(2.0)
+    );
+    assert_eq!(
+      speed(&[("speed", Value::UInt16(2500))]),
+      Some(2.5)
+    );
+    assert_eq!(
+      speed(&[("distance", Value::Float64(10.0))]),
+      None
+    );
+  }
+
+  #[test]
+  fn test_speeds_derived_from_distances() {
+    let mut route = empty_route(&[]);
+    route.gps_points = vec![(13.7, 100.5); 4];
+    route.distances = vec![0.0, 10.0, 25.0, 40.0];
+    route.timestamps = vec![Some(0), Some(5), Some(10), Some(10)];
+    fill_speeds(
+      &mut route,
+      vec![None, None, None, Some(2.5)],
+    );
+
+    // The first record takes the first segment's speed; a recorded
+    // speed still wins
+    assert_eq!(route.speeds, vec![2.0, 2.0, 3.0, 2.5]);
+    let paces: Vec<_> = [2.0, 2.0, 3.0, 2.5]
+      .iter()
+      .map(|&speed| speed_to_pace(speed))
+      .collect();
+    assert_eq!(route.paces, paces);
+
+    // Without a time step, the speed before is kept
+    let mut route = empty_route(&[]);
+    route.gps_points = vec![(13.7, 100.5); 2];
+    route.distances = vec![0.0, 10.0];
+    route.timestamps = vec![Some(0), Some(0)];
+    fill_speeds(&mut route, vec![Some(2.0), None]);
+    assert_eq!(route.speeds, vec![2.0, 2.0]);
+  }
+
+  /// Record message at `(lat, lon)` semicircles, `distance` meters in
+  fn fit_record(lat: i32, lon: i32, distance: f64) -> FitDataRecord {
+    let mut record = FitDataRecord::new(MesgNum::Record);
+    let fields = [
+      ("position_lat", Value::SInt32(lat)),
+      ("position_long", Value::SInt32(lon)),
+      ("distance", Value::Float64(distance)),
+    ];
+    for (number, (name, value)) in fields.into_iter().enumerate() {
+      record.push(FitDataField::new(
+        name.to_string(),
+        number as u8,
+        None,
+        value,
+        String::new(),
+      ));
+    }
+    record
+  }
+
+  #[test]
+  fn test_invalid_positions_are_dropped() {
+    let semicircles =
+      |degrees: f64| (degrees * (i32::MAX as f64 + 1.0) / 180.0).round() as i32;
+    let (lat, lon) = (semicircles(13.7), semicircles(100.5));
+    let records = vec![
+      fit_record(lat, lon, 0.0),
+      fit_record(lat + 10_000, lon + 10_000, 10.0),
+      // Signal lost: the invalid value, then a latitude beyond 90°
+      fit_record(
+        INVALID_SEMICIRCLES,
+        INVALID_SEMICIRCLES,
+        20.0,
+      ),
+      fit_record(i32::MAX - 1, lon, 30.0),
+      fit_record(lat + 20_000, lon + 20_000, 40.0),
+    ];
+    let sessions = read_fit_sessions(records, &[]);
+    let (route, _, _) = join_sessions(sessions, &[]);
+
+    assert_eq!(route.gps_points.len(), 3);
+    assert_eq!(route.distances, vec![0.0, 10.0, 40.0]);
+    assert_eq!(route.paces.len(), 3);
+    let ((lat_min, lat_max), (lon_min, lon_max)) =
+      get_bounds(&route.gps_points);
+    assert!((lat_min - 13.7).abs() < 1e-6 && lat_max < 13.71);
+    assert!((lon_min - 100.5).abs() < 1e-6 && lon_max < 100.51);
+  }
+
+  #[test]
+  fn test_fit_cadence_in_steps_per_minute() {
+    let field = |name: &str, value| {
+      FitDataField::new(
+        name.to_string(),
+        0,
+        None,
+        value,
+        String::new(),
+      )
+    };
+    let cadence_record = |distance, cadence, fraction| {
+      let mut record = fit_record(0, 0, distance);
+      record.push(field("cadence", Value::UInt8(cadence)));
+      record.push(field(
+        "fractional_cadence",
+        Value::Float64(fraction),
+      ));
+      record
+    };
+    let session = |sport: &str| {
+      let mut session = FitDataRecord::new(MesgNum::Session);
+      session.push(field(
+        "sport",
+        Value::String(sport.to_string()),
+      ));
+      session
+    };
+    let records = vec![
+      cadence_record(0.0, 85, 0.5),
+      fit_record(0, 0, 10.0),
+      session("running"),
+      cadence_record(20.0, 85, 0.5),
+      session("cycling"),
+    ];
+    let sessions = read_fit_sessions(records, &[]);
+    let (route, _, _) = join_sessions(sessions, &[]);
+
+    // Running cadence counts one foot; cycling cadence is kept in rpm
+    assert_eq!(
+      route.cadences,
+      vec![Some(171), None, Some(86)]
+    );
+  }
+
+  #[test]
+  fn test_timer_stop_events() {
+    let event = |event: &str, event_type: &str| {
+      [
+        (
+          "event",
+          Value::String(event.to_string()),
+        ),
+        (
+          "event_type",
+          Value::String(event_type.to_string()),
+        ),
+      ]
+    };
+    let stops = |fields: [(&'static str, Value); 2]| {
+      is_timer_stop(fields.iter().map(|(name, value)| (*name, value)))
+    };
+    assert!(stops(event("timer", "stop_all")));
+    assert!(stops(event("timer", "stop")));
+    assert!(!stops(event("timer", "start")));
+    assert!(!stops(event("off_course", "stop")));
+  }
+
+  #[test]
+  fn test_laps_missing_a_field_keep_their_row() {
+    let stream = [
+      vec![
+        ("avg_heart_rate", Value::UInt8(150)),
+        (
+          "enhanced_avg_speed",
+          Value::Float64(3.33),
+        ),
+        ("avg_step_length", Value::Float64(11.0)),
+        ("total_distance", Value::Float64(1000.0)),
+      ],
+      // No heart rate
+      vec![
+        (
+          "enhanced_avg_speed",
+          Value::Float64(2.77),
+        ),
+        ("avg_step_length", Value::Float64(10.5)),
+        ("total_distance", Value::Float64(1000.0)),
+      ],
+      // No step length
+      vec![
+        ("avg_heart_rate", Value::UInt8(160)),
+        (
+          "enhanced_avg_speed",
+          Value::Float64(3.33),
+        ),
+        ("total_distance", Value::Float64(500.0)),
+      ],
+      // Without a speed there is nothing to show
+      vec![("avg_heart_rate", Value::UInt8(120))],
+    ];
+    let mut lap = LapData::default();
+    for fields in &stream {
+      push_fit_lap(
+        fields.iter().map(|(name, value)| (*name, value)),
+        &mut lap,
+      );
+    }
+
+    assert_eq!(
+      lap.avg_heart_rate,
+      vec![Some(150), None, Some(160)]
+    );
+    assert_eq!(
+      lap.avg_step_length,
+      vec![Some(11.0), Some(10.5), None]
+    );
+    assert_eq!(
+      lap.enhanced_avg_speed,
+      vec!["5:00", "6:01", "5:00"]
+    );
+    assert_eq!(
+      lap.total_distance,
+      vec![1000.0, 1000.0, 500.0]
+    );
+  }
+
+  #[test]
+  fn test_join_sessions() {
+    // The cycling session was paused after its first record
+    let mut cycling = route(2, 2);
+    cycling.segment_breaks = vec![0];
+    let sessions = vec![
+      (
+        route(3, 4),
+        laps(1),
+        Some("running".to_string()),
+      ),
+      (
+        cycling,
+        laps(0),
+        Some("cycling".to_string()),
+      ),
+      (route(0, 0), laps(0), None),
+    ];
+    let (route, lap, ranges) = join_sessions(sessions, &[]);
+    assert_eq!(route.gps_points.len(), 5);
+    assert_eq!(route.distances.len(), 6);
+    assert_eq!(lap.avg_speed.len(), 1);
+    assert_eq!(route.segment_breaks, vec![3]);
+    assert_eq!(
+      ranges,
+      vec![
+        SessionRange {
+          records: 0..3,
+          distances: 0..4,
+          laps: 0..1,
+          sport: Some("running".to_string()),
+        },
+        SessionRange {
+          records: 3..5,
+          distances: 4..6,
+          laps: 1..1,
+          sport: Some("cycling".to_string()),
+        },
+        SessionRange {
+          records: 5..5,
+          distances: 6..6,
+          laps: 1..1,
+          sport: None,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_warnings() {
+    assert!(parse_warnings(&route(5, 5), &laps(2)).is_empty());
+
+    // Two records had a distance but no position
+    let warnings = parse_warnings(&route(5, 7), &laps(0));
+    assert_eq!(
+      warnings.0,
+      vec![
+        Warning::EmptyLapData,
+        Warning::SeriesLengthMismatch {
+          series: "distances",
+          expected: 5,
+          actual: 7,
+        },
+      ]
+    );
+  }
+
+  const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="StravaGPX"
+  xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk>
+    <name>Morning Run</name>
+    <type>running</type>
+    <trkseg>
+      <trkpt lat="13.7000" lon="100.5">
+        <ele>2.0</ele><time>2024-05-04T06:00:00Z</time>
+      </trkpt>
+      <trkpt lat="13.7009" lon="100.5">
+        <ele>3.0</ele><time>2024-05-04T06:00:30Z</time>
+      </trkpt>
+    </trkseg>
+    <trkseg>
+      <trkpt lat="13.7018" lon="100.5">
+        <ele>4.0</ele><time>2024-05-04T06:01:00Z</time>
+        <extensions>
+          <power>250</power>
+          <gpxtpx:TrackPointExtension>
+            <gpxtpx:hr>150</gpxtpx:hr><gpxtpx:cad>85</gpxtpx:cad>
+          </gpxtpx:TrackPointExtension>
+        </extensions>
+      </trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+  #[test]
+  fn test_parse_gpx() {
+    let power = ExtraField::numeric("power", "Pwr", "W");
+    let (route, session) = parse_gpx(GPX, &[power]).unwrap();
+
+    // Both segments, about 100 m apart and 30 s apart
+    assert_eq!(route.gps_points.len(), 3);
+    assert_eq!(route.distances[0], 0.0);
+    assert!((route.distances[2] - 200.2).abs() < 0.5);
+    assert_eq!(route.paces, vec!["5:00"; 3]);
+    assert_eq!(
+      route.altitudes,
+      vec![Some(2.0), Some(3.0), Some(4.0)]
+    );
+    assert_eq!(
+      route.heart_rates,
+      vec![None, None, Some(150)]
+    );
+    // Running cadence doubled to steps per minute
+    assert_eq!(route.cadences[2], Some(170));
+    assert_eq!(
+      route.extra["Pwr"],
+      vec![None, None, Some(250.0)]
+    );
+
+    assert_eq!(session.start_time, Some(1714802400));
+    assert_eq!(
+      session.sport.as_deref(),
+      Some("running")
+    );
+    assert_eq!(
+      session.total_distance,
+      route.distances.last().copied()
+    );
+  }
+
+  #[test]
+  fn test_gpx_without_timestamps() {
+    let untimed: String = GPX
+      .lines()
+      .map(|line| match line.find("<time>") {
+        Some(start) => &line[..start],
+        None => line,
+      })
+      .collect();
+    let (route, session) = parse_gpx(&untimed, &[]).unwrap();
+
+    // Still drawable, with no pace to show
+    assert_eq!(route.gps_points.len(), 3);
+    assert_eq!(route.paces, vec![NO_PACE; 3]);
+    assert_eq!(route.speeds, vec![0.0; 3]);
+    assert_eq!(session.start_time, None);
+    assert_eq!(
+      parse_warnings(&route, &LapData::default()).0,
+      vec![Warning::EmptyLapData]
+    );
+
+    let err = parse_gpx("<TrainingCenterDatabase/>", &[]).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Not a GPX file: the root element is <TrainingCenterDatabase>"
+    );
+  }
+
+  const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase
+  xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2"
+  xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+  <Activities>
+    <Activity Sport="Running">
+      <Id>2024-05-04T06:00:00Z</Id>
+      <Lap StartTime="2024-05-04T06:00:00Z">
+        <TotalTimeSeconds>300.0</TotalTimeSeconds>
+        <DistanceMeters>1000.0</DistanceMeters>
+        <AverageHeartRateBpm><Value>148</Value></AverageHeartRateBpm>
+        <Track>
+          <Trackpoint>
+            <Time>2024-05-04T06:00:00Z</Time>
+            <Position>
+              <LatitudeDegrees>13.7</LatitudeDegrees>
+              <LongitudeDegrees>100.5</LongitudeDegrees>
+            </Position>
+            <AltitudeMeters>2.0</AltitudeMeters>
+            <DistanceMeters>0.0</DistanceMeters>
+            <HeartRateBpm><Value>140</Value></HeartRateBpm>
+            <Extensions>
+              <ns3:TPX><ns3:Speed>3.0</ns3:Speed><ns3:Watts>250</ns3:Watts>
+              <ns3:RunCadence>84</ns3:RunCadence></ns3:TPX>
+            </Extensions>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2024-05-04T06:00:05Z</Time>
+          </Trackpoint>
+        </Track>
+        <Extensions>
+          <ns3:LX>
+            <ns3:AvgSpeed>3.3333</ns3:AvgSpeed>
+            <ns3:AvgRunCadence>80</ns3:AvgRunCadence>
+          </ns3:LX>
+        </Extensions>
+      </Lap>
+      <Lap StartTime="2024-05-04T06:05:00Z">
+        <TotalTimeSeconds>200.0</TotalTimeSeconds>
+        <DistanceMeters>500.0</DistanceMeters>
+        <Track>
+          <Trackpoint>
+            <Time>2024-05-04T06:08:20Z</Time>
+            <Position>
+              <LatitudeDegrees>13.7135</LatitudeDegrees>
+              <LongitudeDegrees>100.5</LongitudeDegrees>
+            </Position>
+            <DistanceMeters>1500.0</DistanceMeters>
+          </Trackpoint>
+        </Track>
+      </Lap>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>"#;
+
+  #[test]
+  fn test_parse_tcx() {
+    let power = ExtraField::numeric("Watts", "Pwr", "W");
+    let (route, lap, session) = parse_tcx(TCX, &[power]).unwrap();
+
+    // The trackpoint without a position is skipped
+    assert_eq!(route.gps_points.len(), 2);
+    assert_eq!(route.distances, vec![0.0, 1500.0]);
+    assert_eq!(route.speeds, vec![3.0, 3.0]);
+    assert_eq!(route.heart_rates, vec![Some(140), None]);
+    assert_eq!(route.cadences, vec![Some(168), None]);
+    assert_eq!(route.altitudes, vec![Some(2.0), None]);
+    assert_eq!(
+      route.extra["Pwr"],
+      vec![Some(250.0), None]
+    );
+
+    // Laps read like FIT laps
+    assert_eq!(lap.total_distance, vec![1000.0, 500.0]);
+    assert_eq!(
+      lap.total_elapsed_time,
+      vec![300.0, 200.0]
+    );
+    assert_eq!(
+      lap.enhanced_avg_speed,
+      vec!["5:00", "6:40"]
+    );
+    assert_eq!(lap.avg_cadence, vec![80, 0]);
+    assert!((lap.avg_step_length[0].unwrap() - 12.5).abs() < 0.01);
+    assert_eq!(lap.avg_step_length[1], None);
+
+    // The lap without a heart rate keeps its row
+    assert_eq!(
+      lap.avg_heart_rate,
+      vec![Some(148), None]
+    );
+
+    assert_eq!(session.start_time, Some(1714802400));
+    assert_eq!(
+      session.sport.as_deref(),
+      Some("running")
+    );
+    assert_eq!(session.total_distance, Some(1500.0));
+    assert_eq!(session.total_timer_time, Some(500.0));
+
+    let err = parse_tcx(GPX, &[]).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Not a TCX file: the root element is <gpx>"
+    );
+  }
+
+  #[test]
+  fn test_readers_from_memory() {
+    let tcx =
+      activity_reader_from(Cursor::new(TCX), InputFormat::Tcx, &[]).unwrap();
+    assert_eq!(tcx.route.gps_points.len(), 2);
+    assert_eq!(
+      tcx.lap.total_distance,
+      vec![1000.0, 500.0]
+    );
+    assert!(tcx.session.times.is_some());
+
+    // In-memory bytes of a FileConfig are read in place of its path
+    let file_config = FileConfig {
+      fit_bytes: Some(GPX.as_bytes().into()),
+      input_format: InputFormat::Gpx,
+      ..FileConfig::new(
+        "missing.gpx".into(),
+        String::new(),
+        String::new(),
+      )
+    };
+    let gpx = activity_reader_for(&file_config, &[]).unwrap();
+    assert_eq!(gpx.route.gps_points.len(), 3);
+
+    // A FIT file read through a reader matches the file read by path
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("source/example.fit");
+    let Ok(bytes) = fs::read(&path) else {
+      return;
+    };
+    let (route, lap) = fit_reader_from(Cursor::new(bytes)).unwrap();
+    let (expected_route, expected_lap) =
+      fit_reader(&path.to_string_lossy()).unwrap();
+    assert_eq!(
+      route.gps_points,
+      expected_route.gps_points
+    );
+    assert_eq!(
+      lap.total_distance,
+      expected_lap.total_distance
+    );
+  }
+
+  const KML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+  <Document>
+    <name>Saturday long run</name>
+    <Placemark>
+      <name>Warm-up</name>
+      <LineString>
+        <coordinates>
+          100.5,13.7,0 100.5,13.701,0
+        </coordinates>
+      </LineString>
+    </Placemark>
+    <Placemark>
+      <name>Park loop</name>
+      <LineString>
+        <tessellate>1</tessellate>
+        <coordinates>100.5,13.71 100.5,13.72 100.51,13.72</coordinates>
+      </LineString>
+    </Placemark>
+    <Placemark>
+      <name>Start</name>
+      <Point><coordinates>100.5,13.7</coordinates></Point>
+    </Placemark>
+  </Document>
+</kml>"#;
+
+  #[test]
+  fn test_parse_kml() {
+    let (route, session) = parse_kml(KML, KmlLines::Concatenate, &[]).unwrap();
+    assert_eq!(
+      route.gps_points,
+      vec![
+        (13.7, 100.5),
+        (13.701, 100.5),
+        (13.71, 100.5),
+        (13.72, 100.5),
+        (13.72, 100.51),
+      ]
+    );
+    assert_eq!(
+      route.altitudes[..3],
+      [Some(0.0), Some(0.0), None]
+    );
+    // The line breaks between the LineStrings, and the gap adds nothing
+    assert_eq!(route.segment_breaks, vec![1]);
+    assert_eq!(route.distances[1], route.distances[2]);
+    assert!((route.distances[1] - 111.2).abs() < 0.1);
+    assert_eq!(
+      session.total_distance,
+      route.distances.last().copied()
+    );
+    // No times: no paces, and the series line up with the points
+    assert!(route.paces.iter().all(|pace| pace == NO_PACE));
+    assert!(
+      parse_warnings(&route, &LapData::default())
+        .iter()
+        .all(|warning| *warning == Warning::EmptyLapData)
+    );
+
+    let (route, _) = parse_kml(KML, KmlLines::Longest, &[]).unwrap();
+    assert_eq!(route.gps_points.len(), 3);
+    assert_eq!(route.gps_points[0], (13.71, 100.5));
+    assert!(route.segment_breaks.is_empty());
+
+    assert!(parse_kml(
+      "<kml><Document/></kml>",
+      KmlLines::Concatenate,
+      &[]
+    )
+    .is_err());
+    assert!(parse_kml(GPX, KmlLines::Concatenate, &[]).is_err());
+  }
+
+  #[test]
+  fn test_kml_lines_from_file_config() {
+    let file_config = FileConfig {
+      fit_bytes: Some(KML.as_bytes().into()),
+      input_format: InputFormat::Kml,
+      kml_lines: KmlLines::Longest,
+      ..FileConfig::new(
+        "planned.kml".into(),
+        String::new(),
+        String::new(),
+      )
+    };
+    let activity = activity_reader_for(&file_config, &[]).unwrap();
+    assert_eq!(activity.route.gps_points.len(), 3);
+    assert!(activity.lap.avg_speed.is_empty());
+  }
+
+  #[test]
+  fn test_input_format_from_extension() {
+    let auto = InputFormat::Auto;
+    assert_eq!(
+      auto.resolve("runs/Morning.GPX"),
+      InputFormat::Gpx
+    );
+    assert_eq!(
+      auto.resolve("exports/activity_123.tcx"),
+      InputFormat::Tcx
+    );
+    assert_eq!(
+      auto.resolve("plans/long_run.KML"),
+      InputFormat::Kml
+    );
+    assert_eq!(
+      auto.resolve("source/example.fit"),
+      InputFormat::Fit
+    );
+    assert_eq!(
+      auto.resolve("no_extension"),
+      InputFormat::Fit
+    );
+    assert_eq!(
+      InputFormat::Fit.resolve("run.gpx"),
+      InputFormat::Fit
+    );
+  }
 }
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use anyhow::Result;
+use opencv::{
+  core::{self, Mat},
+  imgcodecs,
+  prelude::*,
+  videoio,
+};
+use serde::Serialize;
+
+use crate::{configs::VideoCodec, utils::workspace::Workspace};
+
+/// Image formats the generators may be asked to write
+const IMAGE_FORMATS: [&str; 5] = ["png", "jpg", "webp", "bmp", "tiff"];
+
+/// What the OpenCV build this process runs on can encode
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeReport {
+  pub crate_version: String,
+  pub opencv_version: String,
+  /// Video writer backends compiled into OpenCV, e.g. `FFMPEG`
+  pub videoio_backends: Vec<String>,
+  /// Codecs a tiny test video could be written with
+  pub working_codecs: Vec<String>,
+  /// Image formats that survive an encode/decode round trip
+  pub image_formats: Vec<String>,
+}
+
+impl RuntimeReport {
+  /// Install hints for every missing backend, codec or image format
+  pub fn hints(&self) -> Vec<String> {
+    let mut hints = Vec::new();
+    if !self.videoio_backends.iter().any(|name| name == "FFMPEG") {
+      hints.push(
+        "FFMPEG backend missing — deterministic videos need it; install \
+         libopencv-videoio with ffmpeg"
+          .to_string(),
+      );
+    }
+    for codec in VideoCodec::ALL {
+      let name = codec.name();
+      if !self.working_codecs.contains(&name) {
+        hints.push(format!(
+          "{} unavailable — install libopencv-videoio with ffmpeg{}",
+          name,
+          match codec {
+            VideoCodec::Avc1 => " and an H.264 encoder (libx264/openh264)",
+            VideoCodec::Vp09 => " and libvpx",
+            VideoCodec::Mp4v => "",
+          }
+        ));
+      }
+    }
+    for format in IMAGE_FORMATS {
+      if !self.image_formats.iter().any(|name| name == format) {
+        hints.push(format!(
+          "{} unavailable — rebuild OpenCV with the {} codec",
+          format, format
+        ));
+      }
+    }
+    hints
+  }
+}
+
+/// Report with a line per item and the hints at the end
+impl fmt::Display for RuntimeReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let list = |items: &[String]| {
+      if items.is_empty() {
+        "none".to_string()
+      } else {
+        items.join(", ")
+      }
+    };
+    writeln!(f, "runarium {}", self.crate_version)?;
+    writeln!(f, "OpenCV {}", self.opencv_version)?;
+    writeln!(
+      f,
+      "Video backends: {}",
+      list(&self.videoio_backends)
+    )?;
+    writeln!(
+      f,
+      "Video codecs: {}",
+      list(&self.working_codecs)
+    )?;
+    write!(
+      f,
+      "Image formats: {}",
+      list(&self.image_formats)
+    )?;
+    for hint in self.hints() {
+      write!(f, "\n⚠️  {}", hint)?;
+    }
+    Ok(())
+  }
+}
+
+/// Probes the OpenCV build: writes a 16x16 one-frame video per codec into
+/// a `Workspace` (removed before returning) and round-trips the same frame
+/// through each format in memory. Takes well under a second.
+pub fn runtime_report() -> Result<RuntimeReport> {
+  let mut videoio_backends = Vec::new();
+  for api in videoio::get_writer_backends()? {
+    videoio_backends.push(videoio::get_backend_name(api)?);
+  }
+
+  let workspace = Workspace::new("runarium_probe")?;
+  let frame = Mat::new_rows_cols_with_default(
+    16,
+    16,
+    core::CV_8UC3,
+    core::Scalar::all(128.0),
+  )?;
+  let working_codecs = VideoCodec::ALL
+    .into_iter()
+    .filter(|codec| probe_codec(&workspace, *codec, &frame))
+    .map(|codec| codec.name())
+    .collect();
+  let image_formats = IMAGE_FORMATS
+    .into_iter()
+    .filter(|format| probe_image_format(format, &frame))
+    .map(str::to_string)
+    .collect();
+
+  Ok(RuntimeReport {
+    crate_version: env!("CARGO_PKG_VERSION").to_string(),
+    opencv_version: core::get_version_string()?,
+    videoio_backends,
+    working_codecs,
+    image_formats,
+  })
+}
+
+/// Whether a one-frame video opens and encodes with `codec`
+fn probe_codec(workspace: &Workspace, codec: VideoCodec, frame: &Mat) -> bool {
+  let path = workspace.path_for(&format!("{}.mp4", codec.name()));
+  let probe = || -> Result<bool> {
+    let [a, b, c, d] = codec.fourcc();
+    let mut video = videoio::VideoWriter::new(
+      &path.to_string_lossy(),
+      videoio::VideoWriter::fourcc(a, b, c, d)?,
+      1.0,
+      core::Size::new(frame.cols(), frame.rows()),
+      true,
+    )?;
+    if !video.is_opened()? {
+      return Ok(false);
+    }
+    video.write(frame)?;
+    video.release()?;
+    Ok(true)
+  };
+  probe().unwrap_or(false)
+}
+
+/// Whether `frame` encodes as `format` and decodes back
+fn probe_image_format(format: &str, frame: &Mat) -> bool {
+  let probe = || -> Result<bool> {
+    let mut buffer = core::Vector::<u8>::new();
+    let extension = format!(".{}", format);
+    if !imgcodecs::imencode(
+      &extension,
+      frame,
+      &mut buffer,
+      &core::Vector::new(),
+    )? {
+      return Ok(false);
+    }
+    let decoded = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_COLOR)?;
+    Ok(!decoded.empty())
+  };
+  probe().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{env, fs};
+
+  use super::*;
+
+  fn leftover_probes() -> usize {
+    fs::read_dir(env::temp_dir())
+      .unwrap()
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| {
+        entry
+          .file_name()
+          .to_string_lossy()
+          .starts_with("runarium_probe_")
+      })
+      .count()
+  }
+
+  #[test]
+  fn test_runtime_report_leaves_no_files() {
+    let report = runtime_report().unwrap();
+    assert_eq!(
+      report.crate_version,
+      env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(leftover_probes(), 0);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert!(json["working_codecs"].is_array());
+  }
+
+  #[test]
+  fn test_hints_name_missing_capabilities() {
+    let report = RuntimeReport {
+      crate_version: "0.1.0".to_string(),
+      opencv_version: "4.10.0".to_string(),
+      videoio_backends: vec!["FFMPEG".to_string()],
+      working_codecs: vec!["avc1".to_string(), "vp09".to_string()],
+      image_formats: IMAGE_FORMATS.map(str::to_string).to_vec(),
+    };
+    assert_eq!(
+      report.hints(),
+      vec!["mp4v unavailable — install libopencv-videoio with ffmpeg"]
+    );
+    assert!(report.to_string().ends_with(
+      "⚠️  mp4v unavailable — install libopencv-videoio with ffmpeg"
+    ));
+  }
+}
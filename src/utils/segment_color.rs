@@ -0,0 +1,229 @@
+use crate::{
+  configs::RouteSegmentColoring,
+  utils::{progression::lerp, stats::normalized_range},
+};
+
+/// Colors and value range of a gradient coloring, shared by the route and
+/// its legend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientScale {
+  /// BGRA color at `min`
+  pub low: [f64; 4],
+  /// BGRA color at `max`
+  pub high: [f64; 4],
+  pub min: f64,
+  pub max: f64,
+}
+
+impl GradientScale {
+  /// Color for `value`, clamped to the range
+  pub fn color(&self, value: f64) -> [f64; 4] {
+    self.color_at(normalize(value, self.min, self.max))
+  }
+
+  /// Color at fraction `t` (0.0-1.0) of the way from `low` to `high`
+  pub fn color_at(&self, t: f64) -> [f64; 4] {
+    blend(self.low, self.high, t)
+  }
+}
+
+/// Gradient scale of `coloring` for an activity, or None for solid coloring
+/// or when the activity has no data for it
+pub fn gradient_scale(
+  coloring: RouteSegmentColoring,
+  altitudes: &[Option<f64>],
+) -> Option<GradientScale> {
+  gradient(coloring, altitudes).map(|(scale, _)| scale)
+}
+
+/// BGRA color of each route segment (point `i` to `i + 1`), or None when
+/// every segment uses the solid route color.
+///
+/// The elevation gradient also falls back to the solid color when the
+/// activity has no altitude data.
+pub fn segment_colors(
+  coloring: RouteSegmentColoring,
+  altitudes: &[Option<f64>],
+) -> Option<Vec<[f64; 4]>> {
+  let (scale, altitudes) = gradient(coloring, altitudes)?;
+  Some(
+    altitudes
+      .windows(2)
+      .map(|pair| scale.color((pair[0] + pair[1]) / 2.0))
+      .collect(),
+  )
+}
+
+/// Gradient scale and the gap-filled values it colors
+fn gradient(
+  coloring: RouteSegmentColoring,
+  altitudes: &[Option<f64>],
+) -> Option<(GradientScale, Vec<f64>)> {
+  let (low, high, range, normalization) = match coloring {
+    RouteSegmentColoring::Solid => return None,
+    RouteSegmentColoring::ElevationGradient {
+      low,
+      high,
+      range,
+      normalization,
+    } => (
+      low.to_bgra(),
+      high.to_bgra(),
+      range,
+      normalization,
+    ),
+  };
+
+  let altitudes = fill_gaps(altitudes)?;
+  let (min, max) = match range {
+    Some(range) => range,
+    None => normalized_range(&altitudes, normalization)?,
+  };
+
+  let scale = GradientScale {
+    low,
+    high,
+    min,
+    max,
+  };
+  Some((scale, altitudes))
+}
+
+/// Fills missing values by interpolating between the known neighbors;
+/// leading and trailing gaps take the nearest known value. None when no
+/// value is known.
+pub fn fill_gaps(values: &[Option<f64>]) -> Option<Vec<f64>> {
+  let known: Vec<(usize, f64)> = values
+    .iter()
+    .enumerate()
+    .filter_map(|(i, value)| value.map(|value| (i, value)))
+    .collect();
+  let (first, last) = (known.first()?, known.last()?);
+
+  let mut filled = vec![first.1; first.0];
+  for pair in known.windows(2) {
+    let ((start, a), (end, b)) = (pair[0], pair[1]);
+    for i in start..end {
+      let t = (i - start) as f64 / (end - start) as f64;
+      filled.push(lerp(a, b, t));
+    }
+  }
+  filled.resize(values.len(), last.1);
+  Some(filled)
+}
+
+/// Position of `value` in `[min, max]`, clamped to 0.0-1.0
+fn normalize(value: f64, min: f64, max: f64) -> f64 {
+  if max > min {
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+  } else {
+    0.5
+  }
+}
+
+/// Linear blend of two BGRA colors
+pub fn blend(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+  [
+    lerp(a[0], b[0], t),
+    lerp(a[1], b[1], t),
+    lerp(a[2], b[2], t),
+    lerp(a[3], b[3], t),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::configs::{Color, Normalization};
+
+  fn gradient(range: Option<(f64, f64)>) -> RouteSegmentColoring {
+    RouteSegmentColoring::ElevationGradient {
+      low: Color::Blue,
+      high: Color::Red,
+      range,
+      normalization: Normalization::MinMax,
+    }
+  }
+
+  #[test]
+  fn test_fill_gaps() {
+    let values = [None, Some(10.0), None, None, Some(40.0), None];
+    assert_eq!(
+      fill_gaps(&values).unwrap(),
+      vec![10.0, 10.0, 20.0, 30.0, 40.0, 40.0]
+    );
+    assert!(fill_gaps(&[None, None]).is_none());
+  }
+
+  #[test]
+  fn test_elevation_gradient() {
+    let altitudes = [Some(100.0), Some(100.0), Some(200.0), Some(200.0)];
+    let colors = segment_colors(gradient(None), &altitudes).unwrap();
+
+    // Lowest segment is blue, highest red, the climb in between
+    assert_eq!(colors.len(), 3);
+    assert_eq!(colors[0], Color::Blue.to_bgra());
+    assert_eq!(colors[1], [127.5, 0.0, 127.5, 0.0]);
+    assert_eq!(colors[2], Color::Red.to_bgra());
+  }
+
+  #[test]
+  fn test_elevation_gradient_fixed_range() {
+    let altitudes = [Some(100.0), Some(100.0), None, Some(300.0)];
+    let colors =
+      segment_colors(gradient(Some((0.0, 400.0))), &altitudes).unwrap();
+    assert_eq!(colors[0], [191.25, 0.0, 63.75, 0.0]);
+    // The missing altitude is interpolated (200 m)
+    assert_eq!(colors[1], [159.375, 0.0, 95.625, 0.0]);
+  }
+
+  #[test]
+  fn test_gradient_scale_matches_route() {
+    let altitudes = [Some(120.0), None, Some(480.0)];
+    let scale = gradient_scale(gradient(None), &altitudes).unwrap();
+    assert_eq!((scale.min, scale.max), (120.0, 480.0));
+    assert_eq!(
+      scale.color_at(0.0),
+      Color::Blue.to_bgra()
+    );
+
+    // A fixed range is what the legend shows, whatever the data
+    let scale = gradient_scale(
+      gradient(Some((0.0, 2000.0))),
+      &altitudes,
+    )
+    .unwrap();
+    assert_eq!((scale.min, scale.max), (0.0, 2000.0));
+    assert!(gradient_scale(RouteSegmentColoring::Solid, &altitudes).is_none());
+  }
+
+  #[test]
+  fn test_elevation_gradient_percentile() {
+    // A GPS spike would otherwise push every real altitude into the blue
+    let altitudes =
+      [Some(0.0), Some(10.0), Some(20.0), Some(30.0), Some(1000.0)];
+    let coloring = RouteSegmentColoring::ElevationGradient {
+      low: Color::Blue,
+      high: Color::Red,
+      range: None,
+      normalization: Normalization::Percentile {
+        low: 0.0,
+        high: 75.0,
+      },
+    };
+    let scale = gradient_scale(coloring, &altitudes).unwrap();
+    assert_eq!((scale.min, scale.max), (0.0, 30.0));
+
+    let colors = segment_colors(coloring, &altitudes).unwrap();
+    assert_eq!(colors[1], [127.5, 0.0, 127.5, 0.0]);
+    // The spike segment is clamped to the top color
+    assert_eq!(colors[3], Color::Red.to_bgra());
+  }
+
+  #[test]
+  fn test_solid_and_missing_altitudes() {
+    let altitudes = [Some(100.0), Some(200.0)];
+    assert!(segment_colors(RouteSegmentColoring::Solid, &altitudes).is_none());
+    assert!(segment_colors(gradient(None), &[None, None]).is_none());
+  }
+}
@@ -0,0 +1,394 @@
+use opencv::core;
+
+use crate::{
+  configs::{SmoothingConfig, SmoothingMethod},
+  utils::progression::lerp,
+};
+
+/// Most Chaikin iterations applied; each one doubles the points
+pub const MAX_CHAIKIN_ITERATIONS: u32 = 5;
+/// Most Catmull-Rom points per segment
+pub const MAX_SAMPLES_PER_SEGMENT: u32 = 32;
+
+/// Route line as drawn: the record pixels, possibly smoothed into more
+/// points, and where each record falls on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothedLine {
+  pub points: Vec<core::Point>,
+  /// Index into `points` of each record, strictly increasing; record `i`'s
+  /// segment runs from `record_points[i]` to `record_points[i + 1]`
+  pub record_points: Vec<usize>,
+}
+
+/// Piece of the line between two positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinePiece {
+  /// Record whose segment the piece lies on
+  pub record: usize,
+  pub from: core::Point,
+  pub to: core::Point,
+  /// Line position the piece ends at
+  pub end: f64,
+}
+
+impl SmoothedLine {
+  /// The record pixels as they are
+  pub fn unsmoothed(points: &[core::Point]) -> Self {
+    Self {
+      points: points.to_vec(),
+      record_points: (0..points.len()).collect(),
+    }
+  }
+
+  /// Smooths the record pixels, or keeps them without `smoothing`
+  pub fn new(
+    points: &[core::Point],
+    smoothing: Option<&SmoothingConfig>,
+  ) -> Self {
+    let Some(smoothing) = smoothing.filter(|_| points.len() > 2) else {
+      return Self::unsmoothed(points);
+    };
+    let points: Vec<(f64, f64)> = points
+      .iter()
+      .map(|point| (point.x as f64, point.y as f64))
+      .collect();
+    let (smoothed, record_points) = match smoothing.method {
+      SmoothingMethod::Chaikin { iterations } => chaikin(
+        &points,
+        iterations.min(MAX_CHAIKIN_ITERATIONS),
+      ),
+      SmoothingMethod::CatmullRom {
+        samples_per_segment,
+      } => catmull_rom(
+        &points,
+        samples_per_segment.clamp(1, MAX_SAMPLES_PER_SEGMENT),
+      ),
+    };
+    Self {
+      points: smoothed
+        .into_iter()
+        .map(|(x, y)| core::Point::new(x.round() as i32, y.round() as i32))
+        .collect(),
+      record_points,
+    }
+  }
+
+  /// Line position (fractional index into `points`) of record `index`
+  /// plus `fraction` of the way to the next record
+  pub fn position(&self, index: usize, fraction: f64) -> f64 {
+    let start = self.record_points[index] as f64;
+    match self.record_points.get(index + 1) {
+      Some(&next) if fraction > 0.0 => lerp(start, next as f64, fraction),
+      _ => start,
+    }
+  }
+
+  /// Record and fraction toward the next record at a line position; the
+  /// inverse of `position`
+  pub fn record_at(&self, position: f64) -> (usize, f64) {
+    let record = self
+      .record_points
+      .partition_point(|&point| point as f64 <= position)
+      .saturating_sub(1);
+    match self.record_points.get(record + 1) {
+      Some(&next) => {
+        let start = self.record_points[record] as f64;
+        (
+          record,
+          (position - start) / (next as f64 - start),
+        )
+      }
+      None => (record, 0.0),
+    }
+  }
+
+  /// Pixel at a line position, interpolated between points
+  pub fn point_at(&self, position: f64) -> core::Point {
+    let index = (position.max(0.0).floor() as usize).min(self.points.len() - 1);
+    let current = self.points[index];
+    let fraction = position - index as f64;
+    match self.points.get(index + 1) {
+      Some(next) if fraction > 0.0 => core::Point::new(
+        lerp(
+          current.x as f64,
+          next.x as f64,
+          fraction,
+        )
+        .round() as i32,
+        lerp(
+          current.y as f64,
+          next.y as f64,
+          fraction,
+        )
+        .round() as i32,
+      ),
+      _ => current,
+    }
+  }
+
+  /// The line from position `from` to `to`, split at every point
+  pub fn pieces(
+    &self,
+    from: f64,
+    to: f64,
+  ) -> impl Iterator<Item = LinePiece> + '_ {
+    let first = from.floor() as usize + 1;
+    let last = to.ceil().max(0.0) as usize;
+    let mut start = from;
+    (first..=last).filter_map(move |point| {
+      let end = (point as f64).min(to);
+      if end <= start {
+        return None;
+      }
+      let piece = LinePiece {
+        record: self.record_at((start + end) / 2.0).0,
+        from: self.point_at(start),
+        to: self.point_at(end),
+        end,
+      };
+      start = end;
+      Some(piece)
+    })
+  }
+
+  /// The line in stretches that leave out the segments of the records in
+  /// `gaps`, in increasing order
+  pub fn stretches(&self, gaps: &[usize]) -> Vec<&[core::Point]> {
+    let mut stretches = Vec::new();
+    let mut start = 0;
+    for &gap in gaps {
+      let (Some(&end), Some(&next)) = (
+        self.record_points.get(gap),
+        self.record_points.get(gap + 1),
+      ) else {
+        continue;
+      };
+      stretches.push(&self.points[start..=end]);
+      start = next;
+    }
+    stretches.push(&self.points[start..]);
+    stretches
+  }
+}
+
+/// Chaikin corner cutting. Each iteration replaces every segment by its
+/// quarter and three-quarter points and keeps both ends, so a record
+/// moves to the three-quarter point of the segment before it.
+fn chaikin(
+  points: &[(f64, f64)],
+  iterations: u32,
+) -> (Vec<(f64, f64)>, Vec<usize>) {
+  let mut line = points.to_vec();
+  let mut record_points: Vec<usize> = (0..points.len()).collect();
+  for _ in 0..iterations {
+    let mut cut = Vec::with_capacity(line.len() * 2);
+    cut.push(line[0]);
+    for pair in line.windows(2) {
+      let ((ax, ay), (bx, by)) = (pair[0], pair[1]);
+      cut.push((lerp(ax, bx, 0.25), lerp(ay, by, 0.25)));
+      cut.push((lerp(ax, bx, 0.75), lerp(ay, by, 0.75)));
+    }
+    cut.push(line[line.len() - 1]);
+
+    // Point `i` becomes index 2i; the last point stays last
+    let last = line.len() - 1;
+    for point in &mut record_points {
+      *point = if *point == last {
+        cut.len() - 1
+      } else {
+        *point * 2
+      };
+    }
+    line = cut;
+  }
+  (line, record_points)
+}
+
+/// Uniform Catmull-Rom spline through every point,
+/// `samples` points per segment; the ends are repeated as tangents
+fn catmull_rom(
+  points: &[(f64, f64)],
+  samples: u32,
+) -> (Vec<(f64, f64)>, Vec<usize>) {
+  let count = points.len();
+  let at = |i: isize| points[i.clamp(0, count as isize - 1) as usize];
+  let mut line = Vec::with_capacity((count - 1) * samples as usize + 1);
+  let mut record_points = Vec::with_capacity(count);
+  for i in 0..count - 1 {
+    record_points.push(line.len());
+    let i = i as isize;
+    let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+    line.push(p1);
+    for sample in 1..samples {
+      let t = sample as f64 / samples as f64;
+      line.push((
+        catmull_rom_value(p0.0, p1.0, p2.0, p3.0, t),
+        catmull_rom_value(p0.1, p1.1, p2.1, p3.1, t),
+      ));
+    }
+  }
+  record_points.push(line.len());
+  line.push(points[count - 1]);
+  (line, record_points)
+}
+
+fn catmull_rom_value(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+  let (t2, t3) = (t * t, t * t * t);
+  0.5
+    * (2.0 * p1
+      + (p2 - p0) * t
+      + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+      + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn zigzag(count: i32) -> Vec<core::Point> {
+    (0..count)
+      .map(|i| core::Point::new(i * 40, if i % 2 == 0 { 0 } else { 40 }))
+      .collect()
+  }
+
+  fn smoothed(method: SmoothingMethod, points: &[core::Point]) -> SmoothedLine {
+    SmoothedLine::new(
+      points,
+      Some(&SmoothingConfig { method }),
+    )
+  }
+
+  #[test]
+  fn test_chaikin_point_counts_and_ends() {
+    let points = zigzag(5);
+    for iterations in 1..=3 {
+      let line = smoothed(
+        SmoothingMethod::Chaikin { iterations },
+        &points,
+      );
+      // Each iteration turns n points into 2n
+      assert_eq!(line.points.len(), 5 << iterations);
+      assert_eq!(line.points[0], points[0]);
+      assert_eq!(line.points.last(), points.last());
+      assert_eq!(line.record_points.len(), 5);
+      assert_eq!(line.record_points[0], 0);
+      assert_eq!(
+        line.record_points[4],
+        line.points.len() - 1
+      );
+      assert!(line.record_points.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    // Iterations are capped
+    let line = smoothed(
+      SmoothingMethod::Chaikin { iterations: 50 },
+      &points,
+    );
+    assert_eq!(
+      line.points.len(),
+      5 << MAX_CHAIKIN_ITERATIONS
+    );
+  }
+
+  #[test]
+  fn test_catmull_rom_passes_through_records() {
+    let points = zigzag(4);
+    let line = smoothed(
+      SmoothingMethod::CatmullRom {
+        samples_per_segment: 8,
+      },
+      &points,
+    );
+    assert_eq!(line.points.len(), 3 * 8 + 1);
+    assert_eq!(line.record_points, vec![0, 8, 16, 24]);
+    for (record, &point) in points.iter().enumerate() {
+      assert_eq!(
+        line.points[line.record_points[record]],
+        point
+      );
+    }
+
+    // One sample per segment is the raw line
+    let line = smoothed(
+      SmoothingMethod::CatmullRom {
+        samples_per_segment: 0,
+      },
+      &points,
+    );
+    assert_eq!(line, SmoothedLine::unsmoothed(&points));
+  }
+
+  #[test]
+  fn test_short_lines_are_not_smoothed() {
+    let points = zigzag(2);
+    let line = smoothed(
+      SmoothingMethod::Chaikin { iterations: 2 },
+      &points,
+    );
+    assert_eq!(line, SmoothedLine::unsmoothed(&points));
+  }
+
+  #[test]
+  fn test_positions_map_records_to_the_line() {
+    let points = zigzag(3);
+    let line = smoothed(
+      SmoothingMethod::CatmullRom {
+        samples_per_segment: 4,
+      },
+      &points,
+    );
+    assert_eq!(line.position(1, 0.0), 4.0);
+    assert_eq!(line.position(1, 0.5), 6.0);
+    assert_eq!(line.position(2, 0.5), 8.0);
+    assert_eq!(line.record_at(6.0), (1, 0.5));
+    assert_eq!(line.record_at(8.0), (2, 0.0));
+    assert_eq!(line.point_at(4.0), points[1]);
+  }
+
+  #[test]
+  fn test_stretches_leave_out_gaps() {
+    let points = zigzag(5);
+    let line = SmoothedLine::unsmoothed(&points);
+    assert_eq!(line.stretches(&[]), vec![&points[..]]);
+    assert_eq!(
+      line.stretches(&[1]),
+      vec![&points[..2], &points[2..]]
+    );
+
+    let line = smoothed(
+      SmoothingMethod::CatmullRom {
+        samples_per_segment: 4,
+      },
+      &points,
+    );
+    let stretches = line.stretches(&[2]);
+    assert_eq!(stretches[0].len(), 9);
+    assert_eq!(stretches[0].last(), Some(&points[2]));
+    assert_eq!(stretches[1].first(), Some(&points[3]));
+  }
+
+  #[test]
+  fn test_pieces_split_at_points() {
+    let points = zigzag(3);
+    let line = SmoothedLine::unsmoothed(&points);
+    let pieces: Vec<LinePiece> = line.pieces(0.5, 2.0).collect();
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(
+      pieces[0],
+      LinePiece {
+        record: 0,
+        from: core::Point::new(20, 20),
+        to: points[1],
+        end: 1.0,
+      }
+    );
+    assert_eq!(pieces[1].record, 1);
+    assert_eq!(pieces[1].to, points[2]);
+
+    // A partial piece ends at the position itself
+    let pieces: Vec<LinePiece> = line.pieces(1.0, 1.25).collect();
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].end, 1.25);
+    assert_eq!(line.pieces(1.0, 1.0).count(), 0);
+  }
+}
@@ -0,0 +1,144 @@
+use crate::{configs::Normalization, utils::progression::lerp};
+
+/// Standard deviations around the mean kept by `ZScoreClamped`
+pub const ZSCORE_LIMIT: f64 = 2.0;
+
+/// Sorted copy of the finite values
+pub fn sorted(values: &[f64]) -> Vec<f64> {
+  let mut sorted: Vec<f64> =
+    values.iter().copied().filter(|v| v.is_finite()).collect();
+  sorted.sort_by(f64::total_cmp);
+  sorted
+}
+
+/// Percentile `p` (0-100) of sorted values, interpolated between the two
+/// nearest values; None when there are no values
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+  let last = sorted.len().checked_sub(1)?;
+  let rank = p.clamp(0.0, 100.0) / 100.0 * last as f64;
+  let below = rank.floor() as usize;
+  let above = (below + 1).min(last);
+  Some(lerp(
+    sorted[below],
+    sorted[above],
+    rank - below as f64,
+  ))
+}
+
+/// Mean and (population) standard deviation; None when there are no values
+pub fn mean_std(values: &[f64]) -> Option<(f64, f64)> {
+  if values.is_empty() {
+    return None;
+  }
+  let count = values.len() as f64;
+  let mean = values.iter().sum::<f64>() / count;
+  let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+  Some((mean, variance.sqrt()))
+}
+
+/// Range a scale over `values` spans under `normalization`, as (low, high);
+/// None when no value is finite
+pub fn normalized_range(
+  values: &[f64],
+  normalization: Normalization,
+) -> Option<(f64, f64)> {
+  let sorted = sorted(values);
+  let (&min, &max) = (sorted.first()?, sorted.last()?);
+  match normalization {
+    Normalization::MinMax => Some((min, max)),
+    Normalization::Percentile { low, high } => Some((
+      percentile(&sorted, low.min(high))?,
+      percentile(&sorted, low.max(high))?,
+    )),
+    Normalization::ZScoreClamped => {
+      let (mean, std) = mean_std(&sorted)?;
+      Some((
+        (mean - ZSCORE_LIMIT * std).max(min),
+        (mean + ZSCORE_LIMIT * std).min(max),
+      ))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sorted_drops_non_finite() {
+    assert_eq!(
+      sorted(&[3.0, f64::NAN, 1.0, f64::INFINITY, 2.0]),
+      vec![1.0, 2.0, 3.0]
+    );
+  }
+
+  #[test]
+  fn test_percentile_interpolates() {
+    let values = [10.0, 20.0, 30.0, 40.0, 50.0];
+    assert_eq!(percentile(&values, 0.0), Some(10.0));
+    assert_eq!(percentile(&values, 50.0), Some(30.0));
+    assert_eq!(percentile(&values, 100.0), Some(50.0));
+    assert_eq!(percentile(&values, 10.0), Some(14.0));
+    assert_eq!(percentile(&values, 87.5), Some(45.0));
+    // Out-of-range percentiles clamp to the ends
+    assert_eq!(percentile(&values, 150.0), Some(50.0));
+    assert_eq!(percentile(&[7.0], 95.0), Some(7.0));
+    assert_eq!(percentile(&[], 50.0), None);
+  }
+
+  #[test]
+  fn test_mean_std() {
+    assert_eq!(
+      mean_std(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]),
+      Some((5.0, 2.0))
+    );
+    assert_eq!(mean_std(&[]), None);
+  }
+
+  #[test]
+  fn test_normalized_range_ignores_outliers() {
+    // Steady laps around 5:00/km and one 15:00/km climb
+    let mut paces = vec![290.0, 295.0, 300.0, 300.0, 305.0, 310.0];
+    paces.extend([300.0; 13]);
+    paces.push(900.0);
+
+    assert_eq!(
+      normalized_range(&paces, Normalization::MinMax),
+      Some((290.0, 900.0))
+    );
+
+    let (low, high) = normalized_range(
+      &paces,
+      Normalization::Percentile {
+        low: 5.0,
+        high: 95.0,
+      },
+    )
+    .unwrap();
+    assert_eq!(low, 294.75);
+    // Only 5% of the way from the 310 s lap toward the climb
+    assert!((high - 339.5).abs() < 1e-9);
+
+    // Mean 330, deviation about 131: the climb is beyond two deviations
+    let (low, high) =
+      normalized_range(&paces, Normalization::ZScoreClamped).unwrap();
+    assert_eq!(low, 290.0);
+    assert!(high > 590.0 && high < 900.0);
+
+    // Swapped percentiles still give a low-to-high range
+    assert_eq!(
+      normalized_range(
+        &[1.0, 2.0, 3.0],
+        Normalization::Percentile {
+          low: 100.0,
+          high: 0.0,
+        },
+      ),
+      Some((1.0, 3.0))
+    );
+    assert_eq!(
+      normalized_range(&[f64::NAN], Normalization::MinMax),
+      None
+    );
+  }
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashMap, f64::consts::TAU};
+
+use crate::{
+  types::{
+    fit_data::{Activity, LapData, RouteData, SessionSummary},
+    warning::Warnings,
+  },
+  utils::converter::speed_to_pace,
+};
+
+/// Meters between synthetic records
+const STEP_M: f64 = 5.0;
+/// Start time of the synthetic activity (2024-01-01 06:00 UTC)
+const START_TIME: i64 = 1_704_088_800;
+
+/// Activity of `points` records on a closed loop with a varying pace,
+/// heart rate and elevation, split into 1 km laps, for benchmarks and
+/// tests that should not need a FIT file
+pub fn synthetic_activity(points: usize) -> Activity {
+  let loop_m = (points as f64 * STEP_M).max(STEP_M);
+  let radius_deg = loop_m / TAU / 111_320.0;
+
+  let mut route = RouteData {
+    paces: Vec::with_capacity(points),
+    gps_points: Vec::with_capacity(points),
+    distances: Vec::with_capacity(points),
+    heart_rates: Vec::with_capacity(points),
+    speeds: Vec::with_capacity(points),
+    cadences: Vec::with_capacity(points),
+    powers: Vec::with_capacity(points),
+    altitudes: Vec::with_capacity(points),
+    timestamps: Vec::with_capacity(points),
+    segment_breaks: Vec::new(),
+    extra: HashMap::new(),
+  };
+  let mut elapsed = 0.0;
+  for i in 0..points {
+    let angle = TAU * i as f64 / points as f64;
+    // Between about 4:45 and 5:30 /km
+    let speed = 3.3 + 0.25 * (angle * 3.0).sin();
+    if i > 0 {
+      elapsed += STEP_M / speed;
+    }
+
+    route.paces.push(speed_to_pace(speed as f32));
+    route.gps_points.push((
+      13.7 + radius_deg * angle.sin(),
+      100.5 + radius_deg * angle.cos(),
+    ));
+    route.distances.push(i as f64 * STEP_M);
+    route.heart_rates.push(Some(
+      (150.0 + 10.0 * (angle * 2.0).sin()) as u8,
+    ));
+    route.speeds.push(speed);
+    route.cadences.push(Some(176));
+    route.powers.push(None);
+    route.altitudes.push(Some(20.0 + 15.0 * angle.sin()));
+    route.timestamps.push(Some(START_TIME + elapsed as i64));
+  }
+
+  let lap = laps(&route);
+  let total_distance = route.distances.last().copied();
+  Activity {
+    route,
+    lap,
+    session: SessionSummary {
+      total_distance,
+      total_timer_time: Some(elapsed),
+      total_ascent: Some(30),
+      total_calories: Some(250),
+      avg_heart_rate: Some(150),
+      start_time: Some(START_TIME),
+      sport: Some("running".to_string()),
+      times: None,
+    },
+    warnings: Warnings::default(),
+    sessions: Vec::new(),
+  }
+}
+
+/// 1 km laps over the records, the last one possibly shorter
+fn laps(route: &RouteData) -> LapData {
+  let mut lap = LapData {
+    avg_heart_rate: vec![],
+    avg_power: vec![],
+    enhanced_avg_speed: vec![],
+    avg_step_length: vec![],
+    total_distance: vec![],
+    avg_speed: vec![],
+    avg_cadence: vec![],
+    total_elapsed_time: vec![],
+  };
+  let per_lap = (1000.0 / STEP_M) as usize;
+  for records in route.speeds.chunks(per_lap) {
+    let count = records.len() as f64;
+    let speed = records.iter().sum::<f64>() / count;
+    let distance = count * STEP_M;
+
+    lap.avg_heart_rate.push(Some(150));
+    lap.avg_power.push(None);
+    lap.enhanced_avg_speed.push(speed_to_pace(speed as f32));
+    lap.avg_step_length.push(Some(1.1));
+    lap.total_distance.push(distance);
+    lap.avg_speed.push(speed);
+    lap.avg_cadence.push(88);
+    lap.total_elapsed_time.push(distance / speed);
+  }
+  lap
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_synthetic_activity() {
+    let activity = synthetic_activity(5000);
+    let route = &activity.route;
+    assert_eq!(route.gps_points.len(), 5000);
+    assert_eq!(route.timestamps.len(), 5000);
+    assert_eq!(route.distances[4999], 24_995.0);
+    // 25 km of records in 1 km laps
+    assert_eq!(activity.lap.avg_speed.len(), 25);
+    assert!(route.timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // The loop closes near its start
+    let (first, last) = (
+      route.gps_points[0],
+      route.gps_points[4999],
+    );
+    assert!((first.0 - last.0).abs() < 1e-3);
+    assert!((first.1 - last.1).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,147 @@
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::configs::FileConfig;
+
+/// Private temporary directory for one render, removed with everything in
+/// it when dropped, including while unwinding from a panic.
+///
+/// # Example
+/// ```no_run
+/// use runarium::utils::workspace::Workspace;
+///
+/// let workspace = Workspace::new("runarium").unwrap();
+/// let fit_path = workspace.write_file("data.fit", b"...").unwrap();
+/// let output_path = workspace.path_for("output.mp4");
+/// // ... render from fit_path to output_path, read it back ...
+/// drop(workspace); // both files are gone
+/// ```
+#[derive(Debug)]
+pub struct Workspace {
+  path: PathBuf,
+}
+
+impl Workspace {
+  /// Creates a new, uniquely named directory `<prefix>_<uuid>` under the
+  /// system temp directory
+  pub fn new(prefix: &str) -> Result<Self> {
+    let path = env::temp_dir().join(format!("{}_{}", prefix, Uuid::new_v4()));
+    fs::create_dir(&path).with_context(|| {
+      format!(
+        "Failed to create temp directory {}",
+        path.display()
+      )
+    })?;
+    Ok(Self { path })
+  }
+
+  /// The workspace directory
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Path of `name` inside the workspace; the file need not exist
+  pub fn path_for(&self, name: &str) -> PathBuf {
+    self.path.join(name)
+  }
+
+  /// Writes `bytes` to `name` inside the workspace and returns its path
+  pub fn write_file(&self, name: &str, bytes: &[u8]) -> Result<PathBuf> {
+    let path = self.path_for(name);
+    fs::write(&path, bytes)
+      .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+  }
+
+  /// Writes the background of a render and points a `FileConfig` at it,
+  /// with the FIT file kept in memory and the output at `output_name`
+  pub fn file_config(
+    &self,
+    fit: &[u8],
+    background: &[u8],
+    output_name: &str,
+  ) -> Result<FileConfig> {
+    let background_path = self.write_file("background.jpg", background)?;
+    Ok(FileConfig {
+      fit_bytes: Some(fit.into()),
+      ..FileConfig::new(
+        String::new(),
+        background_path.to_string_lossy().into_owned(),
+        self.path_for(output_name).to_string_lossy().into_owned(),
+      )
+    })
+  }
+}
+
+impl Drop for Workspace {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
+
+/// `output.<ext>`, keeping the extension of `output_file` (or `default`)
+/// so the encoder matches what the caller asked for
+pub fn output_name(output_file: &str, default: &str) -> String {
+  let extension = Path::new(output_file)
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or(default);
+  format!("output.{}", extension)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::panic;
+
+  use super::*;
+
+  #[test]
+  fn test_workspace_removed_on_drop() {
+    let workspace = Workspace::new("runarium_test").unwrap();
+    let path = workspace.path().to_path_buf();
+    let file = workspace.write_file("data.fit", b"fit").unwrap();
+    assert_eq!(file, workspace.path_for("data.fit"));
+    assert_eq!(fs::read(&file).unwrap(), b"fit");
+
+    drop(workspace);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn test_workspace_removed_on_panic() {
+    let mut path = PathBuf::new();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+      let workspace = Workspace::new("runarium_test").unwrap();
+      path = workspace.path().to_path_buf();
+      workspace.write_file("output.mp4", b"partial").unwrap();
+      panic!("render failed");
+    }));
+
+    assert!(result.is_err());
+    assert!(!path.as_os_str().is_empty());
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn test_output_name_keeps_extension() {
+    assert_eq!(
+      output_name("outputs/route.jpg", "png"),
+      "output.jpg"
+    );
+    assert_eq!(output_name("", "mp4"), "output.mp4");
+  }
+
+  #[test]
+  fn test_workspaces_are_unique() {
+    let (a, b) = (
+      Workspace::new("runarium_test").unwrap(),
+      Workspace::new("runarium_test").unwrap(),
+    );
+    assert_ne!(a.path(), b.path());
+  }
+}